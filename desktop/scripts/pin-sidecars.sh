@@ -0,0 +1,54 @@
+#!/usr/bin/env bash
+# =============================================================================
+# GeoTruth Sidecar Checksum Script
+# Downloads every build listed in src-tauri/sidecars.json and pins its SHA-256
+# =============================================================================
+
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+MANIFEST="${SCRIPT_DIR}/../src-tauri/sidecars.json"
+CACHE_DIR="${HOME}/.cache/geotruth-binaries/pin"
+
+GREEN='\033[0;32m'
+RED='\033[0;31m'
+NC='\033[0m' # No Color
+
+log_info() { echo -e "${GREEN}[INFO]${NC} $1"; }
+log_error() { echo -e "${RED}[ERROR]${NC} $1"; }
+
+sha256() {
+    if command -v sha256sum &> /dev/null; then
+        sha256sum "$1" | cut -d' ' -f1
+    else
+        shasum -a 256 "$1" | cut -d' ' -f1
+    fi
+}
+
+main() {
+    if ! command -v jq &> /dev/null; then
+        log_error "jq is required"
+        exit 1
+    fi
+    mkdir -p "$CACHE_DIR"
+
+    local count index url file sum
+    count="$(jq '.builds | length' "$MANIFEST")"
+    for ((index = 0; index < count; index++)); do
+        url="$(jq -r ".builds[$index].url" "$MANIFEST")"
+        # The macOS builds share an archive between architectures
+        file="${CACHE_DIR}/$(echo -n "$url" | tr -c 'A-Za-z0-9._-' '_')"
+        if [ ! -f "$file" ]; then
+            log_info "Downloading: $url"
+            curl -Lf --progress-bar -o "$file" "$url"
+        fi
+        sum="$(sha256 "$file")"
+        jq --indent 2 ".builds[$index].sha256 = \"$sum\"" "$MANIFEST" > "${MANIFEST}.tmp"
+        mv "${MANIFEST}.tmp" "$MANIFEST"
+        log_info "$(basename "$url"): $sum"
+    done
+
+    log_info "Pinned $count builds in $(basename "$MANIFEST")"
+}
+
+main "$@"
@@ -0,0 +1,181 @@
+//! Truth Bundle Schema
+//!
+//! Truth Bundles are kept in the database, written to files and handed to
+//! the frontend, and outlive the version of the app that made them. Each
+//! records the [`TRUTH_BUNDLE_SCHEMA_VERSION`] it was written with; one from
+//! an earlier version is upgraded a version at a time as it's read, so old
+//! bundles keep loading as the types change.
+//!
+//! A change that old bundles don't read as, such as a renamed field or a
+//! value that now means something else, takes a new version:
+//!
+//! 1. add a variant to [`SchemaVersion`] and make it [`SchemaVersion::CURRENT`]
+//! 2. map its number in [`SchemaVersion::from_number`]
+//! 3. give it an arm in [`SchemaVersion::upgrade_into`], which has no
+//!    wildcard, so the build fails until it has one
+//! 4. freeze a bundle of the new version in `testdata/` and add it to the
+//!    fixtures below, next to those of every earlier version
+//!
+//! A field added with a default needs none of this.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use crate::types::TruthBundle;
+
+/// Version of the Truth Bundle schema bundles are written with
+pub const TRUTH_BUNDLE_SCHEMA_VERSION: u32 = SchemaVersion::CURRENT as u32;
+
+/// The versions of the Truth Bundle schema there have been, oldest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// From before bundles recorded a version. Events didn't always say what
+    /// kind they were, and the first bundles put a location of 0, 0 where
+    /// there was no GPS fix
+    Unversioned = 0,
+    /// Bundles record their version; events without a fix have no location
+    V1 = 1,
+}
+
+impl SchemaVersion {
+    pub const CURRENT: Self = Self::V1;
+
+    pub fn from_number(number: u32) -> Option<Self> {
+        match number {
+            0 => Some(Self::Unversioned),
+            1 => Some(Self::V1),
+            _ => None,
+        }
+    }
+
+    /// Rewrite `bundle`, in the shape of the version before this one, into
+    /// this version's shape
+    fn upgrade_into(self, bundle: &mut Map<String, Value>) {
+        match self {
+            Self::Unversioned => {}
+            Self::V1 => {
+                let events = bundle.get_mut("events").and_then(Value::as_array_mut).into_iter().flatten();
+                for event in events.filter_map(Value::as_object_mut) {
+                    event.entry("kind").or_insert_with(|| "speech".into());
+                    let placeholder = event.get("location").is_some_and(|location| {
+                        location["lat"].as_f64() == Some(0.0) && location["lon"].as_f64() == Some(0.0)
+                    });
+                    if placeholder {
+                        event.insert("location".to_string(), Value::Null);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A serialized Truth Bundle of any version, brought up to the current one
+pub fn upgrade(mut value: Value) -> Result<Value, String> {
+    let Value::Object(bundle) = &mut value else {
+        return Err("a Truth Bundle must be a JSON object".to_string());
+    };
+    let version = match bundle.get("schema_version") {
+        None | Some(Value::Null) => 0,
+        Some(version) => version
+            .as_u64()
+            .and_then(|version| u32::try_from(version).ok())
+            .ok_or_else(|| format!("invalid Truth Bundle schema_version {}", version))?,
+    };
+
+    if version > TRUTH_BUNDLE_SCHEMA_VERSION {
+        warn!(
+            "Truth Bundle is from a newer schema ({} > {}); fields it added are dropped",
+            version, TRUTH_BUNDLE_SCHEMA_VERSION
+        );
+    }
+    for number in version.saturating_add(1)..=TRUTH_BUNDLE_SCHEMA_VERSION {
+        SchemaVersion::from_number(number)
+            .expect("every version up to the current one is known")
+            .upgrade_into(bundle);
+    }
+    bundle.insert("schema_version".to_string(), TRUTH_BUNDLE_SCHEMA_VERSION.into());
+    Ok(value)
+}
+
+// `TruthBundle` derives its serde code as `remote = "Self"`, which leaves
+// these two impls to be written by hand, reading through `upgrade`
+
+impl Serialize for TruthBundle {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        TruthBundle::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TruthBundle {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = upgrade(Value::deserialize(deserializer)?).map_err(D::Error::custom)?;
+        TruthBundle::deserialize(value).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EventKind;
+
+    /// A bundle of each version, as that version wrote it; never edit these,
+    /// only add to them
+    const FIXTURES: &[(u32, &str, &str)] = &[
+        (0, "truth_bundle_v0_initial.json", include_str!("testdata/truth_bundle_v0_initial.json")),
+        (0, "truth_bundle_v0.json", include_str!("testdata/truth_bundle_v0.json")),
+        (1, "truth_bundle_v1.json", include_str!("testdata/truth_bundle_v1.json")),
+    ];
+
+    #[test]
+    fn test_every_version_has_a_fixture_that_still_reads() {
+        for number in 0..=TRUTH_BUNDLE_SCHEMA_VERSION {
+            assert!(FIXTURES.iter().any(|(version, ..)| *version == number), "no fixture of version {}", number);
+        }
+        for (_, name, json) in FIXTURES {
+            let bundle: TruthBundle = serde_json::from_str(json).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            assert_eq!(bundle.schema_version, TRUTH_BUNDLE_SCHEMA_VERSION, "{}", name);
+            assert!(!bundle.events.is_empty(), "{}", name);
+
+            // Written again, it reads back the same
+            let written = serde_json::to_value(&bundle).unwrap();
+            assert_eq!(written["schema_version"], TRUTH_BUNDLE_SCHEMA_VERSION);
+            let again: TruthBundle = serde_json::from_value(written.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&again).unwrap(), written, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_unversioned_placeholders_are_upgraded() {
+        let bundle: TruthBundle = serde_json::from_str(FIXTURES[0].2).unwrap();
+        assert!(bundle.events.iter().all(|e| e.kind == EventKind::Speech));
+        assert!(bundle.events[0].location.is_none());
+        let located = bundle.events[1].location.as_ref().unwrap();
+        assert_eq!((located.lat, located.lon), (43.7384, 7.4246));
+        assert_eq!(bundle.confidence, 0.0);
+
+        let bundle: TruthBundle = serde_json::from_str(FIXTURES[1].2).unwrap();
+        assert_eq!(bundle.events[1].kind, EventKind::NearestPass);
+        assert_eq!(bundle.events[1].heading_deg, Some(84.0));
+        assert_eq!(bundle.route_segments.len(), 1);
+    }
+
+    #[test]
+    fn test_versions_are_numbered_in_order() {
+        for number in 0..=TRUTH_BUNDLE_SCHEMA_VERSION {
+            assert_eq!(SchemaVersion::from_number(number).map(|v| v as u32), Some(number));
+        }
+        assert_eq!(SchemaVersion::from_number(TRUTH_BUNDLE_SCHEMA_VERSION + 1), None);
+
+        let newer = serde_json::json!({
+            "schema_version": TRUTH_BUNDLE_SCHEMA_VERSION + 1,
+            "verification_mode": "offline",
+            "generated_at": "2026-06-12T09:00:00Z",
+            "added_later": true,
+        });
+        let bundle: TruthBundle = serde_json::from_value(newer).unwrap();
+        assert_eq!(bundle.schema_version, TRUTH_BUNDLE_SCHEMA_VERSION);
+        assert!(serde_json::from_value::<TruthBundle>(serde_json::json!({"schema_version": "two"})).is_err());
+    }
+}
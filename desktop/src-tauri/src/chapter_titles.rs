@@ -0,0 +1,225 @@
+//! Chapter Titles
+//!
+//! Chapters end up as YouTube chapters, where a blank title, "Chapter 3" or
+//! the same title twice looks broken. Titles like that are picked out here
+//! so the model can be asked for better ones, and the first chapter is
+//! moved to 00:00, without which YouTube shows no chapters at all.
+
+use crate::pacing;
+use crate::types::{Chapter, ScriptSegment, TitleStyle};
+
+/// Longest title worth asking for; longer ones are cut off in most players
+pub const MAX_TITLE_CHARS: usize = 45;
+
+/// Words of narration to describe a chapter by when it has no description
+const SUMMARY_WORDS: usize = 40;
+
+/// Words a title can be made of without saying anything, in the languages
+/// narrations are usually written in
+const GENERIC_WORDS: &[&str] = &[
+    "chapter", "part", "section", "segment", "scene", "title", "untitled", "new", "tbd", "capítulo", "capitulo",
+    "parte", "sección", "chapitre", "partie", "kapitel", "teil", "abschnitt", "one", "two", "three", "four",
+    "five", "six", "seven", "eight", "nine", "ten", "first", "second", "third", "last",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TitleProblem {
+    Empty,
+    /// Only says it's a chapter, e.g. "Chapter 3" or "Part II"
+    Generic,
+    /// The same as an earlier chapter's
+    Duplicate,
+}
+
+impl TitleProblem {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TitleProblem::Empty => "empty",
+            TitleProblem::Generic => "generic",
+            TitleProblem::Duplicate => "duplicate",
+        }
+    }
+}
+
+/// How the style asks for titles to be written, for the prompts
+pub fn style_instruction(style: TitleStyle) -> &'static str {
+    match style {
+        TitleStyle::Descriptive => "descriptive, saying where we are or what happens, e.g. \"Climbing to the Col de Turini\"",
+        TitleStyle::Punchy => "punchy, two to four words that make you want to watch, e.g. \"Into the Clouds\"",
+        TitleStyle::Minimal => "minimal, just the place or landmark, e.g. \"Col de Turini\"",
+    }
+}
+
+/// A title's words, lowercase and without punctuation
+fn words(title: &str) -> Vec<String> {
+    title
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn is_generic_word(word: &str) -> bool {
+    GENERIC_WORDS.contains(&word)
+        || word.chars().all(|c| c.is_ascii_digit())
+        // Roman numerals up to XXXIX
+        || word.chars().all(|c| matches!(c, 'i' | 'v' | 'x'))
+}
+
+/// What's wrong with `title`, given the titles before it
+fn problem(title: &str, earlier: &[Vec<String>]) -> Option<TitleProblem> {
+    let words = words(title);
+    if words.is_empty() {
+        Some(TitleProblem::Empty)
+    } else if words.iter().all(|word| is_generic_word(word)) {
+        Some(TitleProblem::Generic)
+    } else if earlier.contains(&words) {
+        Some(TitleProblem::Duplicate)
+    } else {
+        None
+    }
+}
+
+/// The chapters whose titles need writing again, by index
+///
+/// Of two chapters with the same title the later one is the duplicate.
+pub fn problems(chapters: &[Chapter]) -> Vec<(usize, TitleProblem)> {
+    let mut earlier = Vec::with_capacity(chapters.len());
+    let mut found = Vec::new();
+    for (index, chapter) in chapters.iter().enumerate() {
+        if let Some(problem) = problem(&chapter.title, &earlier) {
+            found.push((index, problem));
+        }
+        earlier.push(words(&chapter.title));
+    }
+    found
+}
+
+/// Whether `title` would do for the chapter at `index`, next to the others
+pub fn acceptable(title: &str, index: usize, chapters: &[Chapter]) -> bool {
+    let others: Vec<Vec<String>> = chapters
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(_, chapter)| words(&chapter.title))
+        .collect();
+    problem(title, &others).is_none()
+}
+
+/// `title` on one line, without wrapping quotes, cut at a word to at most
+/// [`MAX_TITLE_CHARS`] characters
+pub fn fit(title: &str) -> String {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+    let title = title.trim_matches(|c: char| matches!(c, '"' | '\'' | '“' | '”' | '«' | '»') || c.is_whitespace());
+    let title = title.trim_end_matches('.');
+    if title.chars().count() <= MAX_TITLE_CHARS {
+        return title.to_string();
+    }
+
+    let mut fitted = String::new();
+    for word in title.split(' ') {
+        let length = fitted.chars().count() + usize::from(!fitted.is_empty()) + word.chars().count();
+        if length > MAX_TITLE_CHARS {
+            break;
+        }
+        if !fitted.is_empty() {
+            fitted.push(' ');
+        }
+        fitted.push_str(word);
+    }
+    if fitted.is_empty() {
+        // One very long word
+        fitted = title.chars().take(MAX_TITLE_CHARS).collect();
+    }
+    fitted.trim_end_matches([',', ';', ':', '-', '–']).trim_end().to_string()
+}
+
+/// What the chapter at `index` covers, to title it by: its description, or
+/// the start of the narration between it and the next chapter
+pub fn summary(chapters: &[Chapter], segments: &[ScriptSegment], index: usize) -> String {
+    if let Some(description) = chapters[index].description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+        return description.to_string();
+    }
+
+    let start = pacing::time_code_seconds(&chapters[index].time_code).unwrap_or_default();
+    let end = chapters
+        .get(index + 1)
+        .and_then(|next| pacing::time_code_seconds(&next.time_code))
+        .unwrap_or(f64::INFINITY);
+    let narration: Vec<&str> = segments
+        .iter()
+        .filter(|segment| {
+            pacing::time_code_seconds(&segment.time_code).is_some_and(|seconds| seconds >= start && seconds < end)
+        })
+        .flat_map(|segment| segment.narration.split_whitespace())
+        .take(SUMMARY_WORDS)
+        .collect();
+    narration.join(" ")
+}
+
+/// Move the first chapter to 00:00, as YouTube needs; returns a warning if
+/// it started later
+///
+/// The time codes must already be canonical.
+pub fn start_at_zero(chapters: &mut [Chapter]) -> Option<String> {
+    let first = chapters.first_mut()?;
+    let seconds = pacing::time_code_seconds(&first.time_code).filter(|s| *s > 0.0)?;
+    first.time_code = pacing::format_time_code(0.0);
+    Some(format!("Moved chapter 0 from {} to the start of the video", pacing::format_time_code(seconds)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::chapter;
+
+    #[test]
+    fn test_finds_empty_generic_and_duplicate_titles() {
+        let chapters = vec![
+            chapter("00:00", "Leaving Nice"),
+            chapter("01:00", "Chapter 2"),
+            chapter("02:00", "  "),
+            chapter("03:00", "leaving nice!"),
+            chapter("04:00", "Part II"),
+            chapter("05:00", "Chapter 6: The Coast Road"),
+            chapter("06:00", "Chapter 2"),
+            chapter("07:00", "Capítulo 8"),
+        ];
+        assert_eq!(
+            problems(&chapters),
+            [
+                (1, TitleProblem::Generic),
+                (2, TitleProblem::Empty),
+                (3, TitleProblem::Duplicate),
+                (4, TitleProblem::Generic),
+                (6, TitleProblem::Generic),
+                (7, TitleProblem::Generic),
+            ]
+        );
+
+        assert!(acceptable("The Harbour", 2, &chapters));
+        assert!(!acceptable("Leaving Nice.", 2, &chapters));
+        // A chapter's own title doesn't count against it
+        assert!(acceptable("Chapter 6: The Coast Road", 5, &chapters));
+    }
+
+    #[test]
+    fn test_fit_cuts_long_titles_at_a_word() {
+        assert_eq!(fit("  \"Into the\n Clouds.\" "), "Into the Clouds");
+        let fitted = fit("A Long Slow Climb Through the Hairpins Above the Valley, Then Down Again");
+        assert_eq!(fitted, "A Long Slow Climb Through the Hairpins Above");
+        assert!(fitted.chars().count() <= MAX_TITLE_CHARS);
+        assert_eq!(fit(&"x".repeat(60)).chars().count(), MAX_TITLE_CHARS);
+    }
+
+    #[test]
+    fn test_first_chapter_starts_at_zero() {
+        let mut chapters = vec![chapter("00:07", "Leaving Nice"), chapter("01:00", "The Coast")];
+        assert!(start_at_zero(&mut chapters).unwrap().contains("00:07"));
+        assert_eq!(chapters[0].time_code, "00:00");
+        assert_eq!(chapters[1].time_code, "01:00");
+        assert!(start_at_zero(&mut chapters).is_none());
+        assert!(start_at_zero(&mut []).is_none());
+    }
+}
@@ -0,0 +1,163 @@
+//! Narration Citations
+//!
+//! Each script segment names the Truth Bundle events and POIs it draws on.
+//! The ids are checked against the bundle: a segment citing nothing has no
+//! backing, and one citing ids the bundle doesn't have was most likely made
+//! up. Such segments are kept but flagged, so the user decides what goes.
+
+use std::collections::HashMap;
+
+use crate::types::{CitationStatus, Evidence, EvidenceKind, ScriptSegment, TruthBundle};
+
+/// Evidence for every id in the bundle
+fn bundle_evidence(bundle: &TruthBundle) -> HashMap<String, Evidence> {
+    let mut evidence = HashMap::new();
+    for event in &bundle.events {
+        for poi in &event.pois {
+            evidence.entry(poi.id.clone()).or_insert_with(|| Evidence {
+                kind: EvidenceKind::Poi,
+                label: poi.name.clone(),
+                timestamp: event.timestamp,
+                lat: Some(poi.lat),
+                lon: Some(poi.lon),
+            });
+        }
+
+        let label = match event.pois.first() {
+            Some(poi) => format!("Near {}", poi.name),
+            None => "No landmarks".to_string(),
+        };
+        evidence.insert(event.id.clone(), Evidence {
+            kind: EvidenceKind::Event,
+            label,
+            timestamp: event.timestamp,
+            lat: event.location.as_ref().map(|l| l.lat),
+            lon: event.location.as_ref().map(|l| l.lon),
+        });
+    }
+    evidence
+}
+
+/// Check each segment's citations against `bundle`
+///
+/// Moves ids the bundle doesn't have to `unknown_refs` and sets each
+/// segment's status. Returns the evidence for the ids that were cited.
+pub fn check(bundle: &TruthBundle, segments: &mut [ScriptSegment]) -> HashMap<String, Evidence> {
+    let known = bundle_evidence(bundle);
+    let mut cited = HashMap::new();
+
+    for segment in segments.iter_mut() {
+        let mut refs: Vec<String> = segment.source_refs.drain(..).map(|r| r.trim().to_string()).collect();
+        refs.retain(|r| !r.is_empty());
+        refs.dedup();
+        let (found, unknown): (Vec<String>, Vec<String>) = refs.into_iter().partition(|r| known.contains_key(r));
+
+        segment.citation = if !unknown.is_empty() {
+            CitationStatus::Fabricated
+        } else if found.is_empty() {
+            CitationStatus::Uncited
+        } else {
+            CitationStatus::Verified
+        };
+        for id in &found {
+            cited.insert(id.clone(), known[id].clone());
+        }
+        segment.source_refs = found;
+        segment.unknown_refs = unknown;
+    }
+    cited
+}
+
+/// Indexes of the segments with `status`, comma-separated, for response meta
+pub fn indexes_with(segments: &[ScriptSegment], status: CitationStatus) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .filter(|(_, s)| s.citation == status)
+        .map(|(i, _)| i.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle_schema::TRUTH_BUNDLE_SCHEMA_VERSION;
+    use crate::types::{EventKind, LocationResult, TruthEvent, POI};
+    use chrono::Utc;
+
+    fn bundle() -> TruthBundle {
+        let poi = POI {
+            id: "osm-node-1".to_string(),
+            name: "Prince's Palace".to_string(),
+            name_local: None,
+            category: "landmark".to_string(),
+            subcategory: None,
+            lat: 43.7314,
+            lon: 7.4198,
+            distance_m: 120.0,
+            bearing_deg: 45.0,
+            in_fov: true,
+            confidence: 0.9,
+            facts: None,
+            pinned: false,
+        };
+        TruthBundle {
+            schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
+            project_id: None,
+            video_id: None,
+            events: vec![TruthEvent {
+                id: "event-0".to_string(),
+                kind: EventKind::Speech,
+                timestamp: Utc::now(),
+                local_time: None,
+                time_uncertain: false,
+                duration_seconds: None,
+                location: Some(LocationResult { lat: 43.7314, lon: 7.4212 }),
+                heading_deg: None,
+                pois: vec![poi],
+                detected_objects: vec![],
+                image_path: None,
+                sun: None,
+                weather: None,
+                context: None,
+            }],
+            verification_mode: "offline".to_string(),
+            confidence: 0.8,
+            generated_at: Utc::now(),
+            track_stats: None,
+            meta: HashMap::new(),
+            route_segments: vec![],
+        }
+    }
+
+    fn segment(refs: &[&str]) -> ScriptSegment {
+        ScriptSegment {
+            time_code: "00:00".to_string(),
+            narration: "The palace sits above the harbour.".to_string(),
+            source_refs: refs.iter().map(|r| r.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fabricated_reference_is_caught() {
+        let mut segments = vec![
+            segment(&["event-0", "osm-node-1"]),
+            segment(&[]),
+            segment(&["osm-node-1", "osm-node-999"]),
+        ];
+        let evidence = check(&bundle(), &mut segments);
+
+        assert_eq!(segments[0].citation, CitationStatus::Verified);
+        assert_eq!(segments[1].citation, CitationStatus::Uncited);
+        assert_eq!(segments[2].citation, CitationStatus::Fabricated);
+        assert_eq!(segments[2].source_refs, ["osm-node-1"]);
+        assert_eq!(segments[2].unknown_refs, ["osm-node-999"]);
+
+        assert_eq!(evidence.len(), 2);
+        assert_eq!(evidence["osm-node-1"].label, "Prince's Palace");
+        assert_eq!(evidence["event-0"].kind, EvidenceKind::Event);
+        assert_eq!(indexes_with(&segments, CitationStatus::Fabricated), "2");
+    }
+}
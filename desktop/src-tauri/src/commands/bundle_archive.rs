@@ -0,0 +1,140 @@
+//! Bundle Archives
+//!
+//! The tar layout region and project bundles share: a `manifest.json` first,
+//! so a reader can reject a bundle before unpacking data, then the files it
+//! lists, each with its SHA-256. What the manifest says and where its files
+//! may go in the archive is up to each kind of bundle.
+
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use super::local_regions::sha256_file;
+use super::part_file_path;
+use crate::error::CommandError;
+
+const MANIFEST_NAME: &str = "manifest.json";
+
+/// A kind of bundle's manifest
+pub(super) trait Manifest: Serialize + DeserializeOwned {
+    /// What the bundle holds, e.g. `region`, for messages
+    const KIND: &'static str;
+
+    /// Each file's path inside the archive and its SHA-256
+    fn files(&self) -> Vec<(&str, &str)>;
+
+    /// Refuse a bundle this version of the app can't read
+    fn check_versions(&self) -> Result<(), CommandError>;
+
+    /// `name` as a relative path, if a file of this kind of bundle may be there
+    fn entry_path(name: &str) -> Option<PathBuf>;
+}
+
+/// SHA-256 and size of a file to list in a manifest
+pub(super) fn checksum(path: &Path) -> Result<(String, u64), CommandError> {
+    Ok((sha256_file(path)?, std::fs::metadata(path)?.len()))
+}
+
+/// Write `manifest` and `files`, each a file on disk and its path inside the
+/// archive, to a tar archive at `out`
+///
+/// The archive is written next to `out` and renamed into place, so a failed
+/// export never leaves a truncated bundle behind.
+pub(super) fn write<M: Manifest>(out: &Path, manifest: &M, files: &[(&Path, &str)]) -> Result<(), CommandError> {
+    let manifest_json = serde_json::to_vec_pretty(manifest)?;
+
+    let part = part_file_path(out);
+    let write = || -> std::io::Result<()> {
+        let mut builder = tar::Builder::new(std::fs::File::create(&part)?);
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, MANIFEST_NAME, manifest_json.as_slice())?;
+
+        for (path, name) in files {
+            builder.append_path_with_name(path, name)?;
+        }
+        builder.into_inner()?.sync_all()
+    };
+
+    if let Err(e) = write().and_then(|_| std::fs::rename(&part, out)) {
+        std::fs::remove_file(&part).ok();
+        return Err(CommandError::from(e).context("Failed to write bundle"));
+    }
+    Ok(())
+}
+
+/// Unpack a bundle into `staging` and check it against its manifest
+///
+/// Entries the manifest's kind doesn't allow are skipped; a listed file
+/// that's missing or doesn't match its checksum fails the whole bundle.
+pub(super) fn unpack<M: Manifest>(bundle: &Path, staging: &Path) -> Result<M, CommandError> {
+    let file = std::fs::File::open(bundle)?;
+    let mut archive = tar::Archive::new(file);
+    let mut manifest: Option<M> = None;
+
+    let corrupt = |e: std::io::Error| CommandError::invalid_file(format!("Corrupt {} bundle: {}", M::KIND, e));
+    for entry in archive.entries().map_err(|e| CommandError::invalid_file(format!("Not a {} bundle: {}", M::KIND, e)))? {
+        let mut entry = entry.map_err(corrupt)?;
+        let name = entry.path().map_err(corrupt)?.to_string_lossy().to_string();
+
+        if name == MANIFEST_NAME {
+            let parsed: M = serde_json::from_reader(&mut entry)
+                .map_err(|e| CommandError::invalid_file(format!("Invalid bundle manifest: {}", e)))?;
+            parsed.check_versions()?;
+            manifest = Some(parsed);
+            continue;
+        }
+
+        let Some(relative) = M::entry_path(&name) else {
+            warn!("Skipping unexpected bundle entry {:?}", name);
+            continue;
+        };
+        let dest = staging.join(relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        entry
+            .unpack(&dest)
+            .map_err(|e| CommandError::from(e).context(format!("Failed to unpack {}", name)))?;
+    }
+
+    let manifest = manifest.ok_or_else(|| {
+        CommandError::invalid_file(format!("Not a {} bundle: {} is missing", M::KIND, MANIFEST_NAME))
+    })?;
+
+    for (name, sha256) in manifest.files() {
+        let path = M::entry_path(name)
+            .map(|p| staging.join(p))
+            .ok_or_else(|| CommandError::invalid_file(format!("Invalid path in bundle manifest: {}", name)))?;
+        if !path.is_file() {
+            return Err(CommandError::invalid_file(format!("Bundle is incomplete: {} is missing", name)));
+        }
+        if sha256_file(&path)? != sha256 {
+            return Err(CommandError::invalid_file(format!("Bundle is corrupt: checksum mismatch for {}", name)));
+        }
+    }
+
+    Ok(manifest)
+}
+
+/// Write a bundle of `manifest` and `files` by hand, as something other
+/// than [`write`] might have, e.g. to tamper with it
+#[cfg(test)]
+pub(super) fn write_raw(out: &Path, manifest: &impl Serialize, files: &[(&Path, &str)]) {
+    let json = serde_json::to_vec(manifest).unwrap();
+    let mut builder = tar::Builder::new(std::fs::File::create(out).unwrap());
+    let mut header = tar::Header::new_gnu();
+    header.set_size(json.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, MANIFEST_NAME, json.as_slice()).unwrap();
+    for (path, name) in files {
+        builder.append_path_with_name(path, name).unwrap();
+    }
+    builder.finish().unwrap();
+}
@@ -1,11 +1,146 @@
-use crate::enrich::EnrichmentEngine;
-use crate::types::{EnrichRequest, EnrichResponse};
-use tauri::State;
+use crate::enrich::{stored_truth, EnrichCacheStats, EnrichVideoOptions, EnrichVideoSummary, EnrichmentEngine};
+use crate::error::CommandError;
+use crate::overrides::{self, PINNED_POI};
+use crate::scenes::SceneDescriptions;
+use crate::services::database::EventOverride;
+use crate::services::{Ffmpeg, LocalDatabase};
+use crate::state::AppState;
+use crate::types::{EnrichRequest, EnrichResponse, LocationInspection, TruthEvent};
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+
+/// Progress event payload for `enrich_video`
+#[derive(Debug, Clone, Serialize)]
+pub struct EnrichProgress {
+    pub video_id: String,
+    /// Points looked up so far
+    pub done: usize,
+    pub total: usize,
+}
 
 #[tauri::command]
 pub async fn enrich(
     request: EnrichRequest,
     engine: State<'_, EnrichmentEngine>,
-) -> Result<EnrichResponse, String> {
-    engine.enrich_point(request).await.map_err(|e| e.to_string())
+) -> Result<EnrichResponse, CommandError> {
+    Ok(engine.enrich_point(request).await?)
+}
+
+/// Enrich many points at once, e.g. samples along a track, in input order
+#[tauri::command]
+pub async fn enrich_points(
+    requests: Vec<EnrichRequest>,
+    engine: State<'_, EnrichmentEngine>,
+) -> Result<Vec<EnrichResponse>, CommandError> {
+    Ok(engine.enrich_points(requests).await?)
+}
+
+/// Everything known about a map position the user clicked: place names,
+/// the closest POIs, elevation, local time and the region covering it
+#[tauri::command]
+pub async fn inspect_location(
+    lat: f64,
+    lon: f64,
+    engine: State<'_, EnrichmentEngine>,
+) -> Result<LocationInspection, CommandError> {
+    Ok(engine.inspect_location(lat, lon).await?)
+}
+
+/// Enrich all of a video's located events at once, keeping the context and
+/// POIs in each event's bundle
+///
+/// Emits `enrich-progress` as points are looked up. The summary says what
+/// it cost: points looked up, cache hits and online calls.
+#[tauri::command]
+pub async fn enrich_video(
+    video_id: String,
+    options: Option<EnrichVideoOptions>,
+    engine: State<'_, EnrichmentEngine>,
+    db: State<'_, LocalDatabase>,
+    app: AppHandle,
+) -> Result<EnrichVideoSummary, CommandError> {
+    let on_progress = |done, total| {
+        let _ = app.emit("enrich-progress", EnrichProgress { video_id: video_id.clone(), done, total });
+    };
+    Ok(engine
+        .enrich_video(&db, &video_id, &options.unwrap_or_default(), on_progress)
+        .await?)
+}
+
+/// Set a context field of an event, e.g. its city, over what enrichment found
+///
+/// Kept apart from the event's bundle, and applied over it again whenever
+/// the event is enriched. Returns the event as it is now, the value
+/// attributed to the user.
+#[tauri::command]
+pub async fn override_event_context(
+    event_id: String,
+    field: String,
+    value: String,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<TruthEvent, CommandError> {
+    overrides::check_context(&field, &value).map_err(CommandError::invalid_input)?;
+    let value = value.trim().to_string();
+    db.put_event_override(&EventOverride { event_id: event_id.clone(), field, value }, true)
+        .await?;
+    Ok(overrides::rewrite_event(&db, &state, &event_id, None).await?)
+}
+
+/// Pin a POI to an event, so the event lists it, first, whatever enrichment finds
+#[tauri::command]
+pub async fn pin_poi_to_event(
+    event_id: String,
+    poi_id: String,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<TruthEvent, CommandError> {
+    // POIs the event lists already needn't be in the map data any more
+    let event = db.get_event(&event_id).await.map_err(CommandError::lookup(format!("Event {}", event_id)))?;
+    let listed = stored_truth(&event).is_some_and(|truth| truth.pois.iter().any(|poi| poi.id == poi_id));
+    if !listed {
+        db.get_poi(&poi_id).await.map_err(CommandError::lookup(format!("POI {}", poi_id)))?;
+    }
+
+    db.put_event_override(&EventOverride { event_id: event_id.clone(), field: PINNED_POI.to_string(), value: poi_id }, false)
+        .await?;
+    Ok(overrides::rewrite_event(&db, &state, &event_id, None).await?)
+}
+
+/// Drop an override of an event: a context field, or with `field` `poi` a
+/// pinned POI, by its id as `value` or else all of them
+///
+/// The field is left empty until the event is enriched again.
+#[tauri::command]
+pub async fn clear_override(
+    event_id: String,
+    field: String,
+    value: Option<String>,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<TruthEvent, CommandError> {
+    let removed = db.remove_event_overrides(&event_id, &field, value.as_deref()).await?;
+    if removed == 0 {
+        return Err(CommandError::not_found(format!("Event {} has no override of {}", event_id, field)));
+    }
+    Ok(overrides::rewrite_event(&db, &state, &event_id, Some((&field, value.as_deref()))).await?)
+}
+
+/// How often enrichments came from memory, the database or a live lookup
+#[tauri::command]
+pub fn get_enrichment_cache_stats(engine: State<'_, EnrichmentEngine>) -> EnrichCacheStats {
+    engine.cache_stats()
+}
+
+/// Describe what the camera sees at each of the given events of a video
+#[tauri::command]
+pub async fn describe_event_scenes(
+    video_id: String,
+    event_ids: Vec<String>,
+    engine: State<'_, EnrichmentEngine>,
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+) -> Result<SceneDescriptions, CommandError> {
+    Ok(engine.describe_event_scenes(&db, &ffmpeg, &video_id, &event_ids).await?)
 }
@@ -0,0 +1,373 @@
+//! Export Commands
+//!
+//! A video's track and events written out in formats other tools read.
+
+use chrono::Utc;
+use serde::Serialize;
+use tauri::State;
+use tracing::{info, warn};
+
+use crate::commands::ingest::track_point;
+use crate::error::CommandError;
+use crate::export::geojson::{self, GeoJsonContent};
+use crate::export::html_report::{self, EmbeddedImage, Moment};
+use crate::export::nle::{self, FrameRate, MarkerFormat};
+use crate::export::youtube_chapters::{self, TimedLine};
+use crate::export::{gpx, kml, markdown};
+use crate::services::database::{DatabaseError, Narration, Video};
+use crate::services::{GpsTrack, LocalDatabase};
+use crate::types::{Chapter, NarrateResponse};
+
+/// Write a video's GPS track, events or both to `path` as a GeoJSON
+/// FeatureCollection; returns the path written
+#[tauri::command]
+pub async fn export_geojson(
+    video_id: String,
+    what: GeoJsonContent,
+    path: String,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let track = stored_track(&db, &video).await?;
+    let events = db.get_video_events(&video_id).await?;
+
+    let collection = geojson::feature_collection(&video.filename, track.as_ref(), &events, what);
+    if collection["features"].as_array().map_or(true, |features| features.is_empty()) {
+        return Err(nothing_to_export(&video));
+    }
+
+    let json = serde_json::to_string_pretty(&collection)?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported video {} as GeoJSON to {}", video_id, path);
+    Ok(path)
+}
+
+/// Write a video's stored track to `path` as GPX 1.1, with its verified
+/// events as waypoints when `include_events` is set; returns the path written
+#[tauri::command]
+pub async fn export_gpx(
+    video_id: String,
+    path: String,
+    include_events: bool,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let track = stored_track(&db, &video)
+        .await?
+        .ok_or_else(|| CommandError::invalid_input(format!("{} has no GPS track to export", video.filename)))?;
+    let events = if include_events { db.get_video_events(&video_id).await? } else { Vec::new() };
+
+    let gpx = gpx::document(&video.filename, &track, &events, Utc::now());
+    tokio::fs::write(&path, gpx)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported {} GPS points of video {} as GPX to {}", track.points.len(), video_id, path);
+    Ok(path)
+}
+
+/// Write a video's trip to `path` as one HTML file to open in a browser:
+/// a map of the track, the chapters of its latest narration, frames of
+/// verified events and the facts they were verified against; returns the
+/// path written
+#[tauri::command]
+pub async fn export_html_report(
+    video_id: String,
+    path: String,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let track = stored_track(&db, &video).await?;
+    let events = db.get_video_events(&video_id).await?;
+    let chapters = latest_chapters(&db, &video_id).await?;
+
+    let geojson = geojson::feature_collection(&video.filename, track.as_ref(), &events, GeoJsonContent::Both);
+    let stats = track.as_ref().map(GpsTrack::stats);
+    let (images, images_left_out) = report_images(&html_report::moments(&events)).await;
+    let report = html_report::Report {
+        title: &video.filename,
+        recorded: track.as_ref().and_then(|t| t.start_time),
+        stats: stats.as_ref(),
+        geojson: &geojson,
+        chapters: &chapters,
+        events: &events,
+        images: &images,
+        images_left_out,
+    };
+    let html = html_report::document(&report, Utc::now());
+    tokio::fs::write(&path, html)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported an HTML report of video {} with {} frames to {}", video_id, images.len(), path);
+    Ok(path)
+}
+
+/// Write a video's trip to `path` as KML for Google Earth, zipped as KMZ
+/// when `kmz` is set; returns the path written
+///
+/// Chapter markers come from the video's latest narration.
+#[tauri::command]
+pub async fn export_kml(
+    video_id: String,
+    path: String,
+    kmz: bool,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let track = stored_track(&db, &video).await?;
+    let events = db.get_video_events(&video_id).await?;
+    if track.is_none() && events.iter().all(|e| e.lat.is_none() || e.lon.is_none()) {
+        return Err(nothing_to_export(&video));
+    }
+
+    let chapters = kml::chapter_markers(&latest_chapters(&db, &video_id).await?, &events);
+    let kml = kml::document(&video.filename, track.as_ref(), &events, &chapters);
+    let bytes = if kmz { kml::kmz(&kml)? } else { kml.into_bytes() };
+    tokio::fs::write(&path, bytes)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported video {} as {} to {}", video_id, if kmz { "KMZ" } else { "KML" }, path);
+    Ok(path)
+}
+
+/// Write a video's trip to `path` as Markdown for a blog or Notion: the
+/// chapters of a narration with their script and verified facts, and a
+/// table of the places passed; returns the path written
+///
+/// `narration_id` picks the narration, by default the video's latest; a
+/// video that hasn't been narrated gets a summary of its verified events.
+#[tauri::command]
+pub async fn export_markdown(
+    video_id: String,
+    narration_id: Option<String>,
+    path: String,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let narration = match narration_id.as_deref().map(str::trim).filter(|id| !id.is_empty()) {
+        Some(id) => {
+            let narration = db.get_narration(id).await.map_err(CommandError::lookup(format!("Narration {}", id)))?;
+            if narration.video_id.as_deref() != Some(video.id.as_str()) {
+                return Err(CommandError::invalid_input(format!("Narration {} isn't one of video {}", id, video.id)));
+            }
+            Some(narration)
+        }
+        None => db.get_narrations(&video_id).await?.into_iter().next(),
+    };
+    let response: Option<NarrateResponse> = match &narration {
+        Some(narration) => Some(
+            serde_json::from_str(&narration.response_json)
+                .map_err(|e| format!("Stored narration is unreadable: {}", e))?,
+        ),
+        None => None,
+    };
+
+    let track = stored_track(&db, &video).await?;
+    let events = db.get_video_events(&video_id).await?;
+    let stats = track.as_ref().map(GpsTrack::stats);
+    let trip = markdown::Trip {
+        title: &video.filename,
+        recorded: track.as_ref().and_then(|t| t.start_time),
+        stats: stats.as_ref(),
+        narration: narration.as_ref().zip(response.as_ref()).map(|(n, response)| (n.id.as_str(), response)),
+        events: &events,
+    };
+    let markdown = markdown::document(&trip);
+    tokio::fs::write(&path, markdown)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported video {} as Markdown to {}", video_id, path);
+    Ok(path)
+}
+
+/// Write a Final Cut Pro project of the video to `path` as FCPXML, with
+/// chapter markers from its latest narration and markers for its verified
+/// events; returns the path written
+///
+/// Markers land on whole frames at the video's frame rate, or at
+/// `fps_hint` when the rate wasn't probed.
+#[tauri::command]
+pub async fn export_fcpxml(
+    video_id: String,
+    path: String,
+    fps_hint: Option<f64>,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let (rate, duration) = timeline(&video, fps_hint)?;
+
+    let events = db.get_video_events(&video_id).await?;
+    let markers = nle::markers(&latest_chapters(&db, &video_id).await?, &events);
+    let xml = nle::fcpxml(&video, duration, rate, &markers);
+    tokio::fs::write(&path, xml)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported video {} as FCPXML with {} markers to {}", video_id, markers.len(), path);
+    Ok(path)
+}
+
+/// Write a video's chapters and verified events to `path` as markers for
+/// editing software: a CMX3600 EDL, or Premiere Pro's or DaVinci Resolve's
+/// marker CSV; returns the path written
+#[tauri::command]
+pub async fn export_markers(
+    video_id: String,
+    format: MarkerFormat,
+    path: String,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let (rate, duration) = timeline(&video, None)?;
+
+    let events = db.get_video_events(&video_id).await?;
+    let markers = nle::markers(&latest_chapters(&db, &video_id).await?, &events);
+    let text = nle::marker_file(format, &video.filename, duration, rate, &markers);
+    tokio::fs::write(&path, text)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported {} markers of video {} as {:?} to {}", markers.len(), video_id, format, path);
+    Ok(path)
+}
+
+/// Chapters for a YouTube description, and what was done to them
+#[derive(Debug, Clone, Serialize)]
+pub struct YoutubeChaptersExport {
+    pub narration_id: String,
+    /// The text to paste into the description
+    pub text: String,
+    pub chapters: Vec<TimedLine>,
+    /// Titles of chapters merged into the one before to keep each 10 seconds or longer
+    pub merged: Vec<String>,
+    /// Where the text was written, if it was
+    pub path: Option<String>,
+}
+
+/// A narration's chapters in the `00:00 Intro` form YouTube reads from a
+/// video's description, optionally followed by the places of verified
+/// events and written to `path`
+///
+/// `narration_id_or_video_id` is a narration, or a video whose latest
+/// narration is used.
+#[tauri::command]
+pub async fn export_youtube_chapters(
+    narration_id_or_video_id: String,
+    places: bool,
+    path: Option<String>,
+    db: State<'_, LocalDatabase>,
+) -> Result<YoutubeChaptersExport, CommandError> {
+    let id = narration_id_or_video_id.trim();
+    let narration = match db.get_narration(id).await {
+        Ok(narration) => narration,
+        Err(DatabaseError::NotFound) => latest_narration(&db, id).await?,
+        Err(e) => return Err(e.into()),
+    };
+    let response: NarrateResponse = serde_json::from_str(&narration.response_json)
+        .map_err(|e| format!("Stored narration is unreadable: {}", e))?;
+
+    let video = match &narration.video_id {
+        Some(video_id) => Some(db.get_video(video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?),
+        None => None,
+    };
+    let list = youtube_chapters::chapter_list(&response.chapters, video.as_ref().and_then(|v| v.duration_seconds))
+        .map_err(CommandError::invalid_input)?;
+    let places = match (&video, places) {
+        (Some(video), true) => youtube_chapters::places(&db.get_video_events(&video.id).await?),
+        _ => Vec::new(),
+    };
+    let text = youtube_chapters::description(&list.chapters, &places);
+
+    if let Some(path) = &path {
+        tokio::fs::write(path, &text)
+            .await
+            .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+        info!("Exported the YouTube chapters of narration {} to {}", narration.id, path);
+    }
+    Ok(YoutubeChaptersExport { narration_id: narration.id, text, chapters: list.chapters, merged: list.merged, path })
+}
+
+/// The latest narration of the video `video_id`
+async fn latest_narration(db: &LocalDatabase, video_id: &str) -> Result<Narration, CommandError> {
+    db.get_video(video_id).await.map_err(CommandError::lookup(format!("Narration or video {}", video_id)))?;
+    db.get_narrations(video_id)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| CommandError::not_found(format!("Video {} has no narration", video_id)))
+}
+
+/// The video's frame rate, or `fps_hint` when it wasn't probed, and its duration
+fn timeline(video: &Video, fps_hint: Option<f64>) -> Result<(FrameRate, f64), CommandError> {
+    let rate = video
+        .fps
+        .and_then(FrameRate::from_fps)
+        .or_else(|| fps_hint.and_then(FrameRate::from_fps))
+        .ok_or_else(|| CommandError::invalid_input(format!("The frame rate of {} isn't known", video.filename)))?;
+    let duration = video
+        .duration_seconds
+        .filter(|d| *d > 0.0)
+        .ok_or_else(|| CommandError::invalid_input(format!("The duration of {} isn't known", video.filename)))?;
+    Ok((rate, duration))
+}
+
+/// The frames of `moments` that fit in a report, and how many were too big
+///
+/// A frame over [`html_report::MAX_IMAGE_BYTES`], or one that would take
+/// the report past [`html_report::MAX_TOTAL_IMAGE_BYTES`], is left out; so
+/// is one that's gone, without counting.
+async fn report_images(moments: &[Moment]) -> (Vec<EmbeddedImage>, usize) {
+    let mut images = Vec::new();
+    let mut total = 0;
+    let mut left_out = 0;
+    for moment in moments {
+        let size = match tokio::fs::metadata(&moment.image_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                warn!("Leaving frame {:?} out of the report: {}", moment.image_path, e);
+                continue;
+            }
+        };
+        if size > html_report::MAX_IMAGE_BYTES || total + size > html_report::MAX_TOTAL_IMAGE_BYTES {
+            left_out += 1;
+            continue;
+        }
+        let bytes = match tokio::fs::read(&moment.image_path).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Leaving frame {:?} out of the report: {}", moment.image_path, e);
+                continue;
+            }
+        };
+        if let Some(data_uri) = html_report::data_uri(&moment.image_path, &bytes) {
+            total += size;
+            images.push(EmbeddedImage { seconds: moment.seconds, caption: moment.caption.clone(), data_uri });
+        }
+    }
+    (images, left_out)
+}
+
+/// The video's stored GPS points as a track; `None` without any
+async fn stored_track(db: &LocalDatabase, video: &Video) -> Result<Option<GpsTrack>, CommandError> {
+    let points = db.get_gps_points(&video.id).await?;
+    Ok((!points.is_empty())
+        .then(|| GpsTrack::from_points(video.filename.clone(), "stored", points.into_iter().map(track_point).collect())))
+}
+
+/// Chapters of the video's latest narration; none if it has no narration,
+/// or the latest can't be read
+async fn latest_chapters(db: &LocalDatabase, video_id: &str) -> Result<Vec<Chapter>, CommandError> {
+    let Some(narration) = db.get_narrations(video_id).await?.into_iter().next() else {
+        return Ok(Vec::new());
+    };
+    match serde_json::from_str::<NarrateResponse>(&narration.response_json) {
+        Ok(response) => Ok(response.chapters),
+        Err(e) => {
+            warn!("Narration {} is unreadable, exporting without chapters: {}", narration.id, e);
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn nothing_to_export(video: &Video) -> CommandError {
+    CommandError::invalid_input(format!("{} has nothing to export: no GPS track or located events", video.filename))
+}
@@ -0,0 +1,259 @@
+//! Health Report
+//!
+//! How each subsystem stands, for the dashboard: the GeoTruth API, the
+//! Gemini key, the sidecars, the database, map regions, offline storage
+//! and jobs. The checks run at once, each within [`CHECK_TIMEOUT`], so one
+//! that hangs can't stall the report. Reports are kept for [`REPORT_TTL`],
+//! so a UI polling the command doesn't keep calling the APIs.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::State;
+use tokio::sync::Mutex;
+
+use super::{storage, MAP_REGIONS};
+use crate::error::CommandError;
+use crate::gemini::{self, GeminiError};
+use crate::llm::LlmEngine;
+use crate::services::data_manager::ConnectivityMode;
+use crate::services::{Ffmpeg, LocalDatabase, Whisper};
+use crate::state::{AppState, JobStatus};
+use crate::{config, secrets, settings};
+
+/// How long a single check may take before it's reported as not answering
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long a report is reused before the checks run again
+const REPORT_TTL: Duration = Duration::from_secs(30);
+
+/// The last report and when it was made; held while a report is made, so
+/// calls arriving meanwhile wait for it rather than check again
+static LAST_REPORT: Lazy<Mutex<Option<(Instant, HealthReport)>>> = Lazy::new(|| Mutex::new(None));
+
+/// How a subsystem stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    /// Works, but with less than it could, or couldn't be checked
+    Degraded,
+    Failed,
+}
+
+/// One subsystem in the health report
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    /// `api`, `gemini`, `sidecars`, `database`, `map_regions`, `storage` or `jobs`
+    pub id: &'static str,
+    pub label: &'static str,
+    pub status: HealthStatus,
+    pub detail: String,
+}
+
+/// Status of every subsystem
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// The worst status of the checks
+    pub status: HealthStatus,
+    pub checked_at: DateTime<Utc>,
+    pub checks: Vec<HealthCheck>,
+}
+
+/// Check every subsystem, or return the report from the last 30 seconds
+#[tauri::command]
+pub async fn get_health_report(
+    state: State<'_, Arc<AppState>>,
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    whisper: State<'_, Arc<Whisper>>,
+) -> Result<HealthReport, CommandError> {
+    let mut last = LAST_REPORT.lock().await;
+    if let Some((made, report)) = last.as_ref() {
+        if made.elapsed() < REPORT_TTL {
+            return Ok(report.clone());
+        }
+    }
+
+    let (api, gemini, sidecars, database, regions, storage, jobs) = tokio::join!(
+        within("api", "GeoTruth API", check_api()),
+        within("gemini", "Gemini API key", check_gemini()),
+        within("sidecars", "FFmpeg and Whisper", async { check_sidecars(&ffmpeg, &whisper) }),
+        within("database", "Database", check_database(&db)),
+        within("map_regions", "Map regions", check_regions()),
+        within("storage", "Offline storage", check_storage()),
+        within("jobs", "Jobs", async { check_jobs(&state) }),
+    );
+    let report = report(vec![api, gemini, sidecars, database, regions, storage, jobs]);
+
+    *last = Some((Instant::now(), report.clone()));
+    Ok(report)
+}
+
+fn report(checks: Vec<HealthCheck>) -> HealthReport {
+    HealthReport {
+        status: checks.iter().map(|check| check.status).max().unwrap_or(HealthStatus::Ok),
+        checked_at: Utc::now(),
+        checks,
+    }
+}
+
+/// Run a check, reporting it degraded if it takes longer than [`CHECK_TIMEOUT`]
+async fn within(
+    id: &'static str,
+    label: &'static str,
+    check: impl std::future::Future<Output = (HealthStatus, String)>,
+) -> HealthCheck {
+    let (status, detail) = tokio::time::timeout(CHECK_TIMEOUT, check).await.unwrap_or_else(|_| {
+        (HealthStatus::Degraded, format!("Didn't answer within {} seconds", CHECK_TIMEOUT.as_secs()))
+    });
+    HealthCheck { id, label, status, detail }
+}
+
+async fn check_api() -> (HealthStatus, String) {
+    if settings::get().connectivity_mode == ConnectivityMode::Offline {
+        return (HealthStatus::Ok, "Not used in offline mode".to_string());
+    }
+    let url = format!("{}/v1/health", config::get_api_url());
+    let client = match reqwest::Client::builder()
+        .user_agent(concat!("GeoTruth/", env!("CARGO_PKG_VERSION")))
+        .timeout(CHECK_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return (HealthStatus::Failed, e.to_string()),
+    };
+
+    let started = Instant::now();
+    match client.get(&url).send().await {
+        Ok(response) if response.status().is_success() => {
+            (HealthStatus::Ok, format!("Answered in {} ms", started.elapsed().as_millis()))
+        }
+        Ok(response) => (HealthStatus::Degraded, format!("{} answered {}", url, response.status())),
+        Err(e) => (HealthStatus::Failed, format!("Can't reach {}: {}", url, e)),
+    }
+}
+
+async fn check_gemini() -> (HealthStatus, String) {
+    if settings::get().llm_engine == LlmEngine::Local {
+        return (HealthStatus::Ok, "Not used; narration runs on the local model".to_string());
+    }
+    let Some((key, _)) = secrets::gemini_api_key() else {
+        return match settings::get().llm_engine {
+            LlmEngine::Gemini => (HealthStatus::Failed, GeminiError::MissingKey.to_string()),
+            _ => (HealthStatus::Degraded, "No API key set; narration falls back to the local model".to_string()),
+        };
+    };
+    match gemini::list_models_with_key(&key).await {
+        Ok(_) => (HealthStatus::Ok, format!("Key {} accepted", secrets::mask_key(&key))),
+        Err(e @ GeminiError::InvalidKey) => (HealthStatus::Failed, e.to_string()),
+        Err(e) => (HealthStatus::Degraded, e.to_string()),
+    }
+}
+
+/// Whether the sidecar binaries are there; the setup check also runs them
+fn check_sidecars(ffmpeg: &Ffmpeg, whisper: &Whisper) -> (HealthStatus, String) {
+    let missing: Vec<&str> = [
+        ("FFmpeg", ffmpeg.ffmpeg_path()),
+        ("FFprobe", ffmpeg.ffprobe_path()),
+        ("Whisper", whisper.binary_path()),
+    ]
+    .into_iter()
+    .filter(|(_, path)| !path.is_file())
+    .map(|(name, _)| name)
+    .collect();
+
+    if missing.is_empty() {
+        (HealthStatus::Ok, "All installed".to_string())
+    } else {
+        (HealthStatus::Failed, format!("Missing: {}; run the setup check to fix", missing.join(", ")))
+    }
+}
+
+async fn check_database(db: &LocalDatabase) -> (HealthStatus, String) {
+    match db.db_stats().await {
+        Ok(stats) => (HealthStatus::Ok, format!("{} at {}", megabytes(stats.size_bytes), stats.path)),
+        Err(e) => (HealthStatus::Failed, format!("Database error: {}", e)),
+    }
+}
+
+async fn check_regions() -> (HealthStatus, String) {
+    let regions = MAP_REGIONS.read().await;
+    let downloaded = regions.iter().filter(|region| region.downloaded).count();
+    if downloaded == 0 {
+        (HealthStatus::Degraded, "No map regions downloaded; places come from the online API only".to_string())
+    } else {
+        (HealthStatus::Ok, format!("{} of {} regions downloaded", downloaded, regions.len()))
+    }
+}
+
+async fn check_storage() -> (HealthStatus, String) {
+    let report = storage::file_report().await;
+    let used = format!("{} of map data", megabytes(report.total_bytes));
+    if report.orphaned_bytes > 0 {
+        (
+            HealthStatus::Degraded,
+            format!("{}, {} of it left over from old downloads; clean up in Storage", used, megabytes(report.orphaned_bytes)),
+        )
+    } else {
+        (HealthStatus::Ok, used)
+    }
+}
+
+fn check_jobs(state: &AppState) -> (HealthStatus, String) {
+    let (mut running, mut failed) = (0, 0);
+    for job in state.active_jobs.iter() {
+        match job.status {
+            JobStatus::Pending | JobStatus::Processing { .. } => running += 1,
+            JobStatus::Failed { .. } => failed += 1,
+            _ => {}
+        }
+    }
+    let queued = state.processing_queue.status().items.len();
+    let detail = format!("{} running, {} videos in the processing queue, {} failed", running, queued, failed);
+    if failed > 0 {
+        (HealthStatus::Degraded, detail)
+    } else {
+        (HealthStatus::Ok, detail)
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hung_check_is_cut_off() {
+        let started = Instant::now();
+        let check = within("api", "GeoTruth API", async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            (HealthStatus::Ok, String::new())
+        })
+        .await;
+
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(check.status, HealthStatus::Degraded);
+        assert!(check.detail.starts_with("Didn't answer"));
+    }
+
+    #[test]
+    fn test_report_takes_worst_status() {
+        let check = |id, status| HealthCheck { id, label: "", status, detail: String::new() };
+        assert_eq!(report(vec![]).status, HealthStatus::Ok);
+        assert_eq!(
+            report(vec![check("api", HealthStatus::Ok), check("gemini", HealthStatus::Degraded)]).status,
+            HealthStatus::Degraded
+        );
+        assert_eq!(
+            report(vec![check("database", HealthStatus::Failed), check("jobs", HealthStatus::Degraded)]).status,
+            HealthStatus::Failed
+        );
+    }
+}
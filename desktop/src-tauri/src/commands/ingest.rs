@@ -2,13 +2,29 @@
 //!
 //! Tauri commands for importing and managing videos.
 
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
-use tauri::{State, AppHandle, Emitter};
-use tracing::{info, debug, error};
+use tauri::{State, AppHandle, Emitter, Manager};
+use tracing::{info, debug, error, warn};
 use tokio::sync::Mutex;
 
+use crate::crash;
+use crate::enrich::EnrichmentEngine;
+use crate::intake::{self, FileKind, IntakePlan, PlannedImport, SkippedFile, TimedFile};
+use crate::processor;
+use crate::error::CommandError;
 use crate::services::{Ffmpeg, parse_gps_file, LocalDatabase, GpsTrack};
+use crate::services::database::EventLocation;
+use crate::services::database;
+use crate::services::ffmpeg::VIDEO_FILE_EXTENSIONS;
+use crate::services::gps::{gps_file_extensions, haversine_distance, GpsPoint, TrackStats};
+use crate::services::sun::SunObservation;
+use crate::services::sync::{SunSyncSuggestion, SyncMethod, TimeSyncEngine};
+use crate::settings;
+use crate::trip_summary::{self, TripSummary};
+use std::sync::Arc;
 
 /// Application state
 #[allow(dead_code)]
@@ -25,6 +41,13 @@ pub struct ImportProgress {
     pub message: String,
 }
 
+/// File types import accepts, by extension without the dot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupportedFormats {
+    pub video: Vec<String>,
+    pub gps: Vec<String>,
+}
+
 /// Video import result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImportResult {
@@ -46,6 +69,15 @@ pub struct GpsTrackSummary {
     pub distance_km: Option<f64>,
 }
 
+/// Get the video and GPS file extensions import can read, for file dialog filters
+#[tauri::command]
+pub async fn get_supported_formats() -> SupportedFormats {
+    SupportedFormats {
+        video: VIDEO_FILE_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+        gps: gps_file_extensions().into_iter().map(str::to_string).collect(),
+    }
+}
+
 /// Import a video file with optional GPS track
 #[tauri::command]
 pub async fn import_video(
@@ -55,14 +87,25 @@ pub async fn import_video(
     project_id: String,
     video_path: String,
     gps_path: Option<String>,
-) -> Result<ImportResult, String> {
+) -> Result<ImportResult, CommandError> {
+    crash::catch_panic(import(app, db, ffmpeg_state, project_id, video_path, gps_path)).await
+}
+
+async fn import(
+    app: AppHandle,
+    db: State<'_, LocalDatabase>,
+    ffmpeg_state: State<'_, AppState>,
+    project_id: String,
+    video_path: String,
+    gps_path: Option<String>,
+) -> Result<ImportResult, CommandError> {
     info!("Importing video: {} to project {}", video_path, project_id);
     
     let video_path_buf = PathBuf::from(&video_path);
     
     // Check file exists
     if !video_path_buf.exists() {
-        return Err(format!("Video file not found: {:?}", video_path_buf));
+        return Err(CommandError::file_not_found(format!("Video file not found: {:?}", video_path_buf)));
     }
     
     // Emit: Starting
@@ -104,23 +147,10 @@ pub async fn import_video(
     });
     
     // Parse GPS track if provided
-    let gps_track = if let Some(gps_path_str) = gps_path {
+    let parsed_track = if let Some(gps_path_str) = gps_path {
         let gps_path = PathBuf::from(&gps_path_str);
         match parse_gps_file(&gps_path).await {
-            Ok(track) => {
-                let duration = match (&track.start_time, &track.end_time) {
-                    (Some(start), Some(end)) => {
-                        Some((*end - *start).num_seconds() as f64)
-                    }
-                    _ => None
-                };
-                
-                Some(GpsTrackSummary {
-                    point_count: track.point_count,
-                    duration_seconds: duration,
-                    distance_km: calculate_track_distance(&track),
-                })
-            }
+            Ok(track) => Some(track),
             Err(e) => {
                 error!("Failed to parse GPS: {}", e);
                 None
@@ -130,6 +160,21 @@ pub async fn import_video(
         None
     };
     
+    let gps_track = parsed_track.as_ref().map(|track| {
+        let duration = match (&track.start_time, &track.end_time) {
+            (Some(start), Some(end)) => {
+                Some((*end - *start).num_seconds() as f64)
+            }
+            _ => None
+        };
+        
+        GpsTrackSummary {
+            point_count: track.point_count,
+            duration_seconds: duration,
+            distance_km: calculate_track_distance(track),
+        }
+    });
+    
     // Emit: Database
     let _ = app.emit("import-progress", ImportProgress {
         stage: "database".into(),
@@ -154,17 +199,32 @@ pub async fn import_video(
             }
         });
         
-        match db.add_video(
+        db.add_video(
             &project_id,
             &filename,
             &video_path_buf.to_string_lossy(),
             video_metadata,
-        ).await {
-            Ok(video) => video.id,
-            Err(e) => return Err(format!("Database error: {}", e)),
-        }
+        ).await?.id
     };
     
+    // Keep the points so the video can be re-synced later without the file
+    if let Some(track) = &parsed_track {
+        db.replace_gps_points(&video_id, &track.points)
+            .await?;
+    }
+    
+    // The first video imported into a project provides its cover
+    if matches!(db.get_project_cover(&project_id).await, Ok(None)) {
+        let ffmpeg = ffmpeg_state.ffmpeg.lock().await.clone();
+        if let Some(ffmpeg) = ffmpeg {
+            let duration = metadata.as_ref().and_then(|m| m.duration_seconds);
+            let timestamp_ms = cover_timestamp_ms(&ffmpeg, &video_path_buf, duration).await;
+            if let Err(e) = save_project_cover(&app, &db, &ffmpeg, &project_id, &video_path_buf, timestamp_ms).await {
+                warn!("Failed to create project cover: {}", e);
+            }
+        }
+    }
+    
     let resolution = metadata.as_ref()
         .and_then(|m| {
             match (m.width, m.height) {
@@ -196,6 +256,531 @@ pub async fn import_video(
     })
 }
 
+/// What `confirm_intake` imported, and what it couldn't
+#[derive(Debug, Clone, Serialize)]
+pub struct IntakeOutcome {
+    pub imported: Vec<ImportResult>,
+    pub failed: Vec<SkippedFile>,
+}
+
+/// Sort dropped or opened files into videos and the GPS tracks that go
+/// with them, for the user to confirm
+///
+/// Folders are looked into, though not their subfolders. Files already in
+/// `project_id` are left out. Where a video's track can't be told, the
+/// plan asks. Nothing is imported until `confirm_intake`.
+#[tauri::command]
+pub async fn intake_files(
+    db: State<'_, LocalDatabase>,
+    ffmpeg_state: State<'_, AppState>,
+    paths: Vec<String>,
+    project_id: Option<String>,
+) -> Result<IntakePlan, CommandError> {
+    let existing: HashSet<String> = match &project_id {
+        Some(project_id) => db.get_project_videos(project_id).await?.into_iter().map(|v| v.file_path).collect(),
+        None => HashSet::new(),
+    };
+    let ffmpeg = ffmpeg_state.ffmpeg.lock().await.clone();
+
+    let mut videos = Vec::new();
+    let mut tracks = Vec::new();
+    let mut skipped = Vec::new();
+    for path in intake_paths(&paths) {
+        let display = path.to_string_lossy().to_string();
+        let mut skip = |reason: String| skipped.push(SkippedFile { path: display.clone(), reason });
+        if existing.contains(&display) {
+            skip("Already in this project".to_string());
+            continue;
+        }
+        let head = match read_head(&path) {
+            Ok(head) => head,
+            Err(e) => {
+                skip(format!("Can't be read: {}", e));
+                continue;
+            }
+        };
+        match intake::sniff(&path, &head) {
+            FileKind::Video => {
+                let Some(ffmpeg) = &ffmpeg else {
+                    // Imported all the same, just without its times to pair by
+                    videos.push((TimedFile { path, start: None, end: None }, None));
+                    continue;
+                };
+                match ffmpeg.extract_metadata(&path).await {
+                    Ok(metadata) => {
+                        let start = processor::recording_start(&metadata);
+                        let end = start
+                            .zip(metadata.duration_seconds)
+                            .map(|(start, seconds)| start + chrono::Duration::milliseconds((seconds * 1000.0) as i64));
+                        videos.push((TimedFile { path, start, end }, metadata.duration_seconds));
+                    }
+                    Err(e) => skip(format!("FFmpeg can't read this video: {}", e)),
+                }
+            }
+            FileKind::Gps => match parse_gps_file(&path).await {
+                Ok(track) => tracks.push(TimedFile { path, start: track.start_time, end: track.end_time }),
+                Err(e) => skip(format!("Not a GPS track that can be read: {}", e)),
+            },
+            FileKind::Subtitles => skip("Telemetry in subtitles isn't read; export the track as GPX".to_string()),
+            FileKind::Unknown => skip("Not a video or GPS file".to_string()),
+        }
+    }
+
+    let mut plan = intake::plan(project_id, &videos, &tracks);
+    plan.skipped = skipped;
+    info!(
+        "Intake of {} paths: {} videos, {} questions, {} skipped",
+        paths.len(),
+        plan.imports.len(),
+        plan.questions.len(),
+        plan.skipped.len()
+    );
+    Ok(plan)
+}
+
+/// Import what `intake_files` planned, with the user's answers filled in
+///
+/// Each video is imported as `import_video` would; one that fails doesn't
+/// stop the rest.
+#[tauri::command]
+pub async fn confirm_intake(
+    app: AppHandle,
+    db: State<'_, LocalDatabase>,
+    ffmpeg_state: State<'_, AppState>,
+    project_id: String,
+    imports: Vec<PlannedImport>,
+) -> Result<IntakeOutcome, CommandError> {
+    if imports.is_empty() {
+        return Err(CommandError::invalid_input("Nothing to import"));
+    }
+    if !db.get_projects().await?.iter().any(|p| p.id == project_id) {
+        return Err(CommandError::not_found(format!("Project {} not found", project_id)));
+    }
+
+    let mut outcome = IntakeOutcome { imported: Vec::new(), failed: Vec::new() };
+    for planned in imports {
+        let imported = crash::catch_panic(import(
+            app.clone(),
+            db.clone(),
+            ffmpeg_state.clone(),
+            project_id.clone(),
+            planned.video_path.clone(),
+            planned.gps_path,
+        ))
+        .await;
+        match imported {
+            Ok(result) => outcome.imported.push(result),
+            Err(e) => {
+                warn!("Failed to import {}: {}", planned.video_path, e.message);
+                outcome.failed.push(SkippedFile { path: planned.video_path, reason: e.message });
+            }
+        }
+    }
+    info!("Intake imported {} videos, {} failed", outcome.imported.len(), outcome.failed.len());
+    Ok(outcome)
+}
+
+/// Files among `paths` and in the folders among them, each once
+fn intake_paths(paths: &[String]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths.iter().map(PathBuf::from) {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(&path)
+                .map(|entries| entries.flatten().map(|e| e.path()).filter(|p| p.is_file()).collect())
+                .unwrap_or_default();
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path);
+        }
+    }
+    let mut seen = HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
+    files
+}
+
+/// The first bytes of a file, enough to tell what it is
+fn read_head(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut head = Vec::with_capacity(intake::SNIFF_BYTES);
+    std::fs::File::open(path)?.take(intake::SNIFF_BYTES as u64).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Result of re-synchronizing a video with its GPS track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResyncResult {
+    pub video_id: String,
+    pub offset_seconds: f64,
+    pub confidence: f64,
+    pub method: SyncMethod,
+    pub aligned_point_count: usize,
+    pub events_updated: usize,
+}
+
+/// Re-synchronize an imported video with a new GPS file and/or offset
+///
+/// Without `gps_path` the points stored at import time are reused. Without
+/// `offset` the automatic sync methods are used, lining the track up with
+/// the video file's creation time where it has one. Stored event locations
+/// are re-interpolated from the new alignment.
+#[tauri::command]
+pub async fn resync_video(
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    video_id: String,
+    gps_path: Option<String>,
+    offset: Option<f64>,
+) -> Result<ResyncResult, CommandError> {
+    crash::catch_panic(resync(db, ffmpeg, video_id, gps_path, offset)).await
+}
+
+async fn resync(
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    video_id: String,
+    gps_path: Option<String>,
+    offset: Option<f64>,
+) -> Result<ResyncResult, CommandError> {
+    info!("Re-syncing video: {} (gps: {:?}, offset: {:?})", video_id, gps_path, offset);
+    
+    let video = db.get_video(&video_id)
+        .await
+        .map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    
+    let track = match gps_path {
+        Some(gps_path_str) => {
+            let track = parse_gps_file(&PathBuf::from(&gps_path_str))
+                .await
+                .map_err(|e| CommandError::from(e).context("Failed to parse GPS"))?;
+            db.replace_gps_points(&video_id, &track.points)
+                .await?;
+            track
+        }
+        None => {
+            let stored = db.get_gps_points(&video_id)
+                .await?;
+            if stored.is_empty() {
+                return Err(CommandError::invalid_input("No GPS data stored for this video, please provide a GPS file"));
+            }
+            let points = stored.into_iter().map(track_point).collect();
+            GpsTrack::from_points(video.filename.clone(), "stored", points)
+        }
+    };
+    
+    // Without it only a manual offset or a track of the same length can align them
+    let video_start = match ffmpeg.extract_metadata(&PathBuf::from(&video.file_path)).await {
+        Ok(metadata) => processor::recording_start(&metadata),
+        Err(e) => {
+            warn!("Couldn't read the creation time of {}: {}", video.file_path, e);
+            None
+        }
+    };
+    
+    let engine = TimeSyncEngine::new(track, video.duration_seconds, video_start)
+        .with_policy(settings::get().interpolation);
+    let sync = match offset {
+        Some(offset) => engine.synchronize_with_offset(offset),
+        None => engine.synchronize(),
+    }?;
+    
+    // Move stored events to their new positions
+    let events = db.get_video_events(&video_id)
+        .await?;
+    
+    let updates: Vec<EventLocation> = events.into_iter().map(|event| {
+        let position = engine.interpolate_position(&sync, event.start_time_seconds);
+        EventLocation {
+            event_id: event.id,
+            lat: position.map(|(lat, _, _)| lat),
+            lon: position.map(|(_, lon, _)| lon),
+            heading_deg: position.and_then(|(_, _, heading)| heading),
+        }
+    }).collect();
+    
+    let events_updated = db.update_event_locations(&updates)
+        .await?;
+    
+    info!(
+        "Re-synced video {}: {:?} offset {}s, confidence {}, {} events updated",
+        video_id, sync.method, sync.offset_seconds, sync.confidence, events_updated
+    );
+    
+    Ok(ResyncResult {
+        video_id,
+        offset_seconds: sync.offset_seconds,
+        confidence: sync.confidence,
+        method: sync.method,
+        aligned_point_count: sync.aligned_points.len(),
+        events_updated,
+    })
+}
+
+/// Suggest a GPS offset from where the sun appears in a frame (experimental)
+///
+/// For footage with no usable clock: `observation` is the sun's direction
+/// and/or height as marked on the frame at `video_time_seconds`. The result
+/// is only a suggestion; accepting it means passing its offset to
+/// `resync_video`. Refused unless enabled in the settings.
+#[tauri::command]
+pub async fn suggest_sun_sync_offset(
+    db: State<'_, LocalDatabase>,
+    video_id: String,
+    video_time_seconds: f64,
+    observation: SunObservation,
+) -> Result<SunSyncSuggestion, CommandError> {
+    if !settings::get().experimental_sun_sync {
+        return Err(CommandError::invalid_input("Sun-based sync is experimental; enable it in the settings first"));
+    }
+    if !video_time_seconds.is_finite() || video_time_seconds < 0.0 {
+        return Err(CommandError::invalid_input("Frame time must be zero or more seconds"));
+    }
+
+    let video = db.get_video(&video_id)
+        .await
+        .map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let stored = db.get_gps_points(&video_id)
+        .await?;
+    if stored.is_empty() {
+        return Err(CommandError::invalid_input("No GPS data stored for this video"));
+    }
+    let camera_offset = db.camera_heading_offset(&video_id)
+        .await?;
+
+    let track = GpsTrack::from_points(video.filename.clone(), "stored", stored.into_iter().map(track_point).collect());
+    TimeSyncEngine::new(track, video.duration_seconds, None)
+        .suggest_offset_from_sun(video_time_seconds, &observation, camera_offset)
+        .map_err(|e| CommandError::from(e).context("Sun sync failed"))
+}
+
+/// Scene-change score above which a frame starts a new shot
+const COVER_SCENE_THRESHOLD: f32 = 0.4;
+
+/// How far into a video to look for the first scene change
+const COVER_SCAN_SECONDS: f64 = 120.0;
+
+/// Pick a representative frame: the first scene change, else the midpoint
+async fn cover_timestamp_ms(ffmpeg: &Ffmpeg, video_path: &PathBuf, duration_seconds: Option<f64>) -> u64 {
+    match ffmpeg.first_scene_change(video_path, COVER_SCENE_THRESHOLD, COVER_SCAN_SECONDS).await {
+        Ok(Some(seconds)) => return (seconds * 1000.0) as u64,
+        Ok(None) => {}
+        Err(e) => debug!("Scene detection for cover failed: {}", e),
+    }
+    
+    duration_seconds
+        .map(|d| (d * 500.0) as u64)
+        .unwrap_or(0)
+}
+
+/// Capture a frame as the project's cover image and store its path
+async fn save_project_cover(
+    app: &AppHandle,
+    db: &LocalDatabase,
+    ffmpeg: &Ffmpeg,
+    project_id: &str,
+    video_path: &PathBuf,
+    timestamp_ms: u64,
+) -> Result<String, CommandError> {
+    let covers_dir = app.path()
+        .app_data_dir()?
+        .join("covers");
+    
+    let previous = db.get_project_cover(project_id)
+        .await?;
+    
+    let format = settings::get().thumbnail_format;
+    let path = ffmpeg.save_frame(video_path, timestamp_ms, &covers_dir.join(project_id), format)
+        .await
+        .map_err(|e| CommandError::from(e).context("Failed to capture cover"))?;
+    let path = path.to_string_lossy().to_string();
+    
+    db.set_project_cover(project_id, &path)
+        .await?;
+    
+    // A format change leaves the old file under a different extension
+    if let Some(previous) = previous.filter(|p| *p != path) {
+        std::fs::remove_file(previous).ok();
+    }
+    
+    info!("Project {} cover set from {:?} at {}ms", project_id, video_path, timestamp_ms);
+    Ok(path)
+}
+
+/// Use a frame of one of the project's videos as the project cover
+///
+/// Returns the path of the stored cover image.
+#[tauri::command]
+pub async fn set_project_cover(
+    app: AppHandle,
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    project_id: String,
+    video_id: String,
+    timestamp_ms: u64,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id)
+        .await
+        .map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    if video.project_id != project_id {
+        return Err(CommandError::invalid_input(format!("Video {} does not belong to project {}", video_id, project_id)));
+    }
+    
+    save_project_cover(&app, &db, &ffmpeg, &project_id, &PathBuf::from(&video.file_path), timestamp_ms).await
+}
+
+/// Get the path of a project's cover image, if it has one
+#[tauri::command]
+pub async fn get_project_cover(
+    db: State<'_, LocalDatabase>,
+    project_id: String,
+) -> Result<Option<String>, CommandError> {
+    let cover = db.get_project_cover(&project_id)
+        .await?;
+    
+    // The image may have been removed from disk behind our back
+    Ok(cover.filter(|path| std::path::Path::new(path).exists()))
+}
+
+/// A camera heading offset in `[0, 360)`, or an error for a non-number
+fn normalize_heading_offset(offset_deg: Option<f64>) -> Result<Option<f64>, CommandError> {
+    match offset_deg {
+        Some(offset) if !offset.is_finite() => Err(CommandError::invalid_input("Camera heading offset must be a number of degrees")),
+        offset => Ok(offset.map(|o| o.rem_euclid(360.0))),
+    }
+}
+
+/// Set which way a project's cameras point relative to the direction of travel
+///
+/// Degrees clockwise: 90 for a right-facing side mount, 180 for a rear
+/// camera. Videos with their own offset keep it; `None` means facing forward.
+#[tauri::command]
+pub async fn set_project_camera_offset(
+    db: State<'_, LocalDatabase>,
+    project_id: String,
+    offset_deg: Option<f64>,
+) -> Result<(), CommandError> {
+    let offset_deg = normalize_heading_offset(offset_deg)?;
+    db.set_project_camera_offset(&project_id, offset_deg)
+        .await?;
+    
+    info!("Project {} camera heading offset set to {:?}", project_id, offset_deg);
+    Ok(())
+}
+
+/// Set which way a video's camera pointed relative to the direction of travel
+///
+/// Overrides the project's offset; `None` goes back to using it.
+#[tauri::command]
+pub async fn set_video_camera_offset(
+    db: State<'_, LocalDatabase>,
+    video_id: String,
+    offset_deg: Option<f64>,
+) -> Result<(), CommandError> {
+    let offset_deg = normalize_heading_offset(offset_deg)?;
+    db.set_video_camera_offset(&video_id, offset_deg)
+        .await?;
+    
+    info!("Video {} camera heading offset set to {:?}", video_id, offset_deg);
+    Ok(())
+}
+
+/// Point a video to where its file is now, after it was moved or renamed
+///
+/// What was kept for the video stays with it, so processing it again picks
+/// up after the stages it finished.
+#[tauri::command]
+pub async fn relink_video(
+    db: State<'_, LocalDatabase>,
+    video_id: String,
+    file_path: String,
+) -> Result<(), CommandError> {
+    if !PathBuf::from(&file_path).is_file() {
+        return Err(CommandError::file_not_found(format!("No video file at {}", file_path)));
+    }
+    db.set_video_file_path(&video_id, &file_path)
+        .await?;
+    
+    info!("Video {} relinked to {}", video_id, file_path);
+    Ok(())
+}
+
+/// Convert a stored GPS point back into a track point
+pub(crate) fn track_point(p: database::GpsPoint) -> GpsPoint {
+    GpsPoint {
+        timestamp: p.timestamp,
+        lat: p.lat,
+        lon: p.lon,
+        elevation_m: p.elevation_m,
+        speed_kmh: p.speed_kmh,
+        heading_deg: p.heading_deg,
+        accuracy_m: None,
+    }
+}
+
+/// A video's GPS track prepared for the map view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoTrack {
+    #[serde(flatten)]
+    pub track: GpsTrack,
+    /// Statistics of the full-resolution track
+    pub stats: TrackStats,
+    /// Point count before simplification
+    pub original_point_count: usize,
+}
+
+/// Get a video's stored GPS track for drawing on the map
+///
+/// With `simplify_tolerance_m`, points closer than that many metres to the
+/// simplified line are dropped to keep the payload small. Bounds and
+/// statistics always describe the full track.
+#[tauri::command]
+pub async fn get_video_track(
+    db: State<'_, LocalDatabase>,
+    video_id: String,
+    simplify_tolerance_m: Option<f64>,
+) -> Result<VideoTrack, CommandError> {
+    if simplify_tolerance_m.is_some_and(|t| !t.is_finite() || t < 0.0) {
+        return Err(CommandError::invalid_input("Simplification tolerance must be a non-negative number of metres"));
+    }
+    
+    let video = db.get_video(&video_id)
+        .await
+        .map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let stored = db.get_gps_points(&video_id)
+        .await?;
+    if stored.is_empty() {
+        return Err(CommandError::invalid_input("No GPS data stored for this video"));
+    }
+    
+    let full = GpsTrack::from_points(video.filename, "stored", stored.into_iter().map(track_point).collect());
+    let stats = full.stats();
+    let original_point_count = full.point_count;
+    let bounds = full.bounds.clone();
+    
+    let mut track = match simplify_tolerance_m {
+        Some(tolerance) => full.simplified(tolerance),
+        None => full,
+    };
+    // Keep the map framed on the whole route even if extremes were simplified away
+    track.bounds = bounds;
+    
+    debug!("Track for {}: {} of {} points", video_id, track.point_count, original_point_count);
+    
+    Ok(VideoTrack { track, stats, original_point_count })
+}
+
+/// Overview of a project's whole trip: totals over its videos' tracks,
+/// the countries and states crossed, the top POIs passed and the elevation
+/// profile, for the trip card
+#[tauri::command]
+pub async fn get_trip_summary(
+    db: State<'_, LocalDatabase>,
+    enrichment: State<'_, EnrichmentEngine>,
+    project_id: String,
+) -> Result<TripSummary, CommandError> {
+    Ok(trip_summary::summarize(&db, &enrichment, &project_id)
+        .await?)
+}
+
 /// Calculate total distance of GPS track in kilometers
 fn calculate_track_distance(track: &GpsTrack) -> Option<f64> {
     if track.points.len() < 2 {
@@ -213,33 +798,16 @@ fn calculate_track_distance(track: &GpsTrack) -> Option<f64> {
     Some(total_distance)
 }
 
-/// Calculate distance between two GPS points using Haversine formula
-fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
-    const R: f64 = 6371.0; // Earth radius in km
-    
-    let lat1_rad = lat1.to_radians();
-    let lat2_rad = lat2.to_radians();
-    let delta_lat = (lat2 - lat1).to_radians();
-    let delta_lon = (lon2 - lon1).to_radians();
-    
-    let a = (delta_lat / 2.0).sin().powi(2)
-        + lat1_rad.cos() * lat2_rad.cos() * (delta_lon / 2.0).sin().powi(2);
-    let c = 2.0 * a.sqrt().asin();
-    
-    R * c
-}
-
 /// Get project videos
 #[tauri::command]
 pub async fn get_project_videos(
     db: State<'_, LocalDatabase>,
     project_id: String,
-) -> Result<Vec<crate::services::database::Video>, String> {
+) -> Result<Vec<crate::services::database::Video>, CommandError> {
     debug!("Getting videos for project: {}", project_id);
     
-    db.get_project_videos(&project_id)
-        .await
-        .map_err(|e| format!("Database error: {}", e))
+    Ok(db.get_project_videos(&project_id)
+        .await?)
 }
 
 /// Create a new project
@@ -248,22 +816,20 @@ pub async fn create_project(
     db: State<'_, LocalDatabase>,
     name: String,
     description: Option<String>,
-) -> Result<crate::services::database::Project, String> {
+) -> Result<crate::services::database::Project, CommandError> {
     info!("Creating project: {}", name);
     
-    db.create_project(&name, description.as_deref())
-        .await
-        .map_err(|e| format!("Database error: {}", e))
+    Ok(db.create_project(&name, description.as_deref())
+        .await?)
 }
 
 /// Get all projects
 #[tauri::command]
 pub async fn get_projects(
     db: State<'_, LocalDatabase>,
-) -> Result<Vec<crate::services::database::Project>, String> {
+) -> Result<Vec<crate::services::database::Project>, CommandError> {
     debug!("Getting all projects");
     
-    db.get_projects()
-        .await
-        .map_err(|e| format!("Database error: {}", e))
+    Ok(db.get_projects()
+        .await?)
 }
@@ -0,0 +1,79 @@
+//! Background Job Commands
+//!
+//! Following and stopping long-running work started by other commands.
+
+use std::sync::Arc;
+use tauri::{AppHandle, State};
+
+use crate::error::CommandError;
+use crate::job_log::JobLogLine;
+use crate::jobs::{self, JobRecord};
+use crate::services::LocalDatabase;
+use crate::state::AppState;
+
+/// List the jobs of this run, running or finished, and those kept from
+/// earlier runs, most recently started first
+#[tauri::command]
+pub async fn list_jobs(db: State<'_, LocalDatabase>, state: State<'_, Arc<AppState>>) -> Result<Vec<JobRecord>, CommandError> {
+    Ok(jobs::list(&state, jobs::kept(&db).await))
+}
+
+/// Get a job, of this run or kept from an earlier one
+#[tauri::command]
+pub async fn get_job(
+    job_id: String,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<JobRecord, CommandError> {
+    if let Some(job) = state.active_jobs.get(&job_id) {
+        return Ok(job.clone());
+    }
+    jobs::kept(&db)
+        .await
+        .into_iter()
+        .find(|job| job.job_id == job_id)
+        .ok_or_else(|| CommandError::not_found(format!("Job not found: {}", job_id)))
+}
+
+/// Get the changes of a job numbered after `since_seq`, oldest first, so a
+/// view that wasn't listening to `job-updated` can catch up
+///
+/// Only the latest changes of this run's jobs are kept; a job kept from an
+/// earlier run has just its final record.
+#[tauri::command]
+pub async fn get_job_events(
+    job_id: String,
+    since_seq: Option<u64>,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+) -> Result<Vec<JobRecord>, CommandError> {
+    let since_seq = since_seq.unwrap_or(0);
+    if let Some(events) = jobs::events_since(&state, &job_id, since_seq) {
+        return Ok(events);
+    }
+    let job = jobs::kept(&db)
+        .await
+        .into_iter()
+        .find(|job| job.job_id == job_id)
+        .ok_or_else(|| CommandError::not_found(format!("Job not found: {}", job_id)))?;
+    Ok([job].into_iter().filter(|job| job.seq > since_seq).collect())
+}
+
+/// Get what a job logged, oldest first, to see why it failed
+///
+/// The last lines of each job are kept, with their level, time and the
+/// processing stage they came from.
+#[tauri::command]
+pub async fn get_job_log(job_id: String, db: State<'_, LocalDatabase>) -> Result<Vec<JobLogLine>, CommandError> {
+    jobs::log(&db, &job_id).await.ok_or_else(|| CommandError::not_found(format!("Job not found: {}", job_id)))
+}
+
+/// Stop a running job, such as one from `start_narration`
+///
+/// Its in-flight requests are abandoned and it reports `cancelled`. A map
+/// download is paused, keeping what it downloaded for the next attempt;
+/// importing a region can't be stopped.
+#[tauri::command]
+pub async fn cancel_job(job_id: String, state: State<'_, Arc<AppState>>, app: AppHandle) -> Result<(), CommandError> {
+    jobs::cancel(&state, &jobs::emitter(&app), &job_id)
+}
@@ -0,0 +1,266 @@
+//! Local Region Import
+//!
+//! Registers map data the user already has on disk, an OSM PBF extract or a
+//! PMTiles archive, as a region under a `local/<slug>` id. The file is
+//! hard-linked into the tiles directory when possible and copied otherwise.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, State};
+use tracing::{info, warn};
+
+use super::{
+    region_file_path_in, region_pmtiles_path_in, region_status, save_regions_to_disk, tiles_dir, unique_region_id,
+    RegionInfo, RegionStatus, LOCAL_REGION_PREFIX, MAP_REGIONS,
+};
+use crate::error::CommandError;
+use crate::geo::{self, GeoEngine};
+use crate::jobs;
+use crate::resources::{self, Priority, Resource};
+use crate::services::pbf;
+use crate::state::AppState;
+
+/// Recorded as the `source` of imported regions
+const LOCAL_SOURCE: &str = "local";
+
+/// Kind of map data found in an imported file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocalFormat {
+    Pbf,
+    PmTiles,
+}
+
+/// What we learned about a file before importing it
+#[derive(Debug, Clone)]
+struct InspectedFile {
+    format: LocalFormat,
+    bounds: (f64, f64, f64, f64),
+    /// When the data was produced, if the file says so
+    timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    checksum: String,
+    bytes: u64,
+}
+
+/// Import an OSM PBF extract or PMTiles archive from disk as a region
+///
+/// Importing a file whose contents match an already imported region returns
+/// that region instead of storing the data twice. The import runs as a
+/// `region-processing` job, which can't be cancelled.
+#[tauri::command]
+pub async fn import_local_region(
+    geo: State<'_, Arc<GeoEngine>>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+    path: String,
+    name: String,
+) -> Result<RegionInfo, CommandError> {
+    let emit = jobs::emitter(&app);
+    jobs::run(&state, emit, "region-processing", None, |_| import(&geo, path, name)).await
+}
+
+async fn import(geo: &GeoEngine, path: String, name: String) -> Result<RegionInfo, CommandError> {
+    let source = PathBuf::from(path.trim());
+    if !source.is_file() {
+        return Err(CommandError::file_not_found(format!("File not found: {}", source.display())));
+    }
+
+    let name = match name.trim() {
+        "" => default_name(&source),
+        name => name.to_string(),
+    };
+
+    info!("Importing local region {} from {:?}", name, source);
+
+    // Hashing a multi-gigabyte extract takes a while, keep it off the async runtime
+    let slot = resources::acquire(Resource::Io, Priority::Batch).await;
+    let inspect_path = source.clone();
+    let inspected = tokio::task::spawn_blocking(move || inspect_file(&inspect_path)).await??;
+
+    if let Some(existing) = find_by_checksum(&MAP_REGIONS.read().await, &inspected.checksum) {
+        info!("{:?} is already imported as {}", source, existing.id);
+        return Ok(existing);
+    }
+
+    let dir = tiles_dir();
+    std::fs::create_dir_all(&dir)?;
+
+    let id = unique_region_id(&MAP_REGIONS.read().await, LOCAL_REGION_PREFIX, &name);
+    let dest = match inspected.format {
+        LocalFormat::Pbf => region_file_path_in(&dir, &id),
+        LocalFormat::PmTiles => region_pmtiles_path_in(&dir, &id),
+    };
+
+    let (link_source, link_dest) = (source.clone(), dest.clone());
+    tokio::task::spawn_blocking(move || link_or_copy(&link_source, &link_dest)).await??;
+    drop(slot);
+
+    if inspected.format == LocalFormat::PmTiles {
+        if let Err(e) = geo.load_region(&dest).await {
+            std::fs::remove_file(&dest).ok();
+            return Err(CommandError::invalid_file(format!("Failed to load PMTiles archive: {:#}", e)));
+        }
+    }
+
+    let fingerprint = region_status::fingerprint_as(&dest, inspected.checksum)?;
+    let mut region = RegionInfo {
+        id,
+        name,
+        size_mb: inspected.bytes.div_ceil(1024 * 1024),
+        last_updated: Some(inspected.timestamp.unwrap_or_else(chrono::Utc::now).to_rfc3339()),
+        poi_count: 0,
+        bounds: inspected.bounds,
+        source: Some(LOCAL_SOURCE.to_string()),
+        status: RegionStatus::Ready,
+        ..Default::default()
+    };
+    region_status::record_data(&mut region, fingerprint);
+
+    let mut regions = MAP_REGIONS.write().await;
+
+    // Another import of the same file may have finished while we were copying
+    if let Some(existing) = find_by_checksum(&regions, region.checksum.as_deref().unwrap_or_default()) {
+        drop(regions);
+        if inspected.format == LocalFormat::PmTiles {
+            geo.unload_region(&dest).await;
+        }
+        std::fs::remove_file(&dest).ok();
+        return Ok(existing);
+    }
+
+    regions.push(region.clone());
+    save_regions_to_disk(&regions);
+
+    info!("Imported local region {} ({:?}, {} MB)", region.id, inspected.format, region.size_mb);
+    Ok(region)
+}
+
+fn find_by_checksum(regions: &[RegionInfo], checksum: &str) -> Option<RegionInfo> {
+    regions
+        .iter()
+        .find(|r| r.checksum.as_deref() == Some(checksum))
+        .cloned()
+}
+
+/// Region name from a file name, e.g. `monaco-latest.osm.pbf` -> `monaco-latest`
+fn default_name(path: &Path) -> String {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    file_name
+        .split('.')
+        .next()
+        .filter(|stem| !stem.is_empty())
+        .unwrap_or("Imported region")
+        .to_string()
+}
+
+/// Detect the file format, read its bounds and compute its checksum
+fn inspect_file(path: &Path) -> Result<InspectedFile, CommandError> {
+    let bytes = std::fs::metadata(path)?.len();
+
+    let (format, bounds, timestamp) = if geo::is_pmtiles(path) {
+        let bounds = geo::read_pmtiles_bounds(path).map_err(|e| CommandError::invalid_file(format!("{:#}", e)))?;
+        (LocalFormat::PmTiles, bounds, None)
+    } else {
+        let header = pbf::validate_pbf(path)
+            .map_err(|e| CommandError::from(e).context("Not an OSM PBF extract or PMTiles archive"))?;
+        let bounds = header.bbox.ok_or_else(|| {
+            CommandError::invalid_file(
+                "The extract has no bounding box in its header; re-export it with bounds (e.g. osmium extract)",
+            )
+        })?;
+        let timestamp = header
+            .replication_timestamp
+            .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0));
+        (LocalFormat::Pbf, bounds, timestamp)
+    };
+
+    // Fall back to the file's age when the data carries no timestamp
+    let timestamp = timestamp.or_else(|| {
+        std::fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .map(chrono::DateTime::<chrono::Utc>::from)
+    });
+
+    Ok(InspectedFile {
+        format,
+        bounds,
+        timestamp,
+        checksum: sha256_file(path)?,
+        bytes,
+    })
+}
+
+pub(super) fn sha256_file(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hard-link `source` to `dest`, copying when linking isn't possible
+/// (different volume, or a filesystem without hard links)
+fn link_or_copy(source: &Path, dest: &Path) -> Result<(), CommandError> {
+    if dest.exists() {
+        std::fs::remove_file(dest).map_err(|e| CommandError::from(e).context(format!("Failed to replace {:?}", dest)))?;
+    }
+
+    if let Err(e) = std::fs::hard_link(source, dest) {
+        warn!("Could not link {:?}, copying instead: {}", source, e);
+        std::fs::copy(source, dest).map_err(|e| CommandError::from(e).context("Failed to copy map data"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::fixtures;
+
+    #[test]
+    fn test_inspect_rejects_unknown_files() {
+        let dir = fixtures::temp_dir("import");
+        let path = dir.join("notes.txt");
+        std::fs::write(&path, b"definitely not map data").unwrap();
+
+        let err = inspect_file(&path).unwrap_err();
+        assert!(err.message.starts_with("Not an OSM PBF extract or PMTiles archive"));
+        assert_eq!(err.code, ErrorCode::InvalidFile);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_link_or_copy_and_checksum() {
+        let dir = fixtures::temp_dir("import");
+        let source = dir.join("monaco-latest.osm.pbf");
+        let dest = dir.join("local_monaco.osm.pbf");
+        std::fs::write(&source, b"abc").unwrap();
+        std::fs::write(&dest, b"stale").unwrap();
+
+        link_or_copy(&source, &dest).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"abc");
+        assert_eq!(
+            sha256_file(&dest).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(default_name(&source), "monaco-latest");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,74 @@
+//! Log Commands
+//!
+//! Where the log files are, changing how much gets logged, and crash
+//! reports, so users can send logs along with bug reports. Debug builds
+//! also show the recent model requests.
+
+use std::sync::Arc;
+use tauri::State;
+use tracing::info;
+
+use super::paths;
+use crate::crash::{self, CrashReport};
+use crate::error::CommandError;
+use crate::logging;
+use crate::request_history::RecordedRequest;
+use crate::settings;
+use crate::state::AppState;
+
+/// Get the directory holding the log files
+#[tauri::command]
+pub async fn get_log_path() -> String {
+    logging::log_dir().display().to_string()
+}
+
+/// The last `lines` lines of the log files, oldest first
+///
+/// `level_filter` such as `warn` keeps only lines at that level or more
+/// severe. Lines are redacted as they're written, so these can be shown
+/// or attached to a report as-is.
+#[tauri::command]
+pub async fn get_recent_logs(lines: usize, level_filter: Option<String>) -> Result<Vec<String>, CommandError> {
+    let min_level = level_filter
+        .as_deref()
+        .filter(|level| !level.trim().is_empty())
+        .map(logging::parse_level)
+        .transpose()
+        .map_err(CommandError::invalid_input)?;
+    Ok(tokio::task::spawn_blocking(move || logging::recent_lines(lines, min_level)).await??)
+}
+
+/// Open the log directory in the system file manager
+#[tauri::command]
+pub async fn open_logs_folder() -> Result<(), CommandError> {
+    paths::open_folder(&logging::log_dir())
+}
+
+/// Set the log filter, e.g. `debug` or `info,geotruth_lib=trace`
+///
+/// Applies immediately; `None` restores the default.
+#[tauri::command]
+pub async fn set_log_level(level: Option<String>) -> Result<(), CommandError> {
+    let level = level.map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+    logging::set_filter(level.as_deref())?;
+    settings::update(|s| s.log_level = level.clone());
+    info!("Log level set to {}", level.as_deref().unwrap_or(logging::DEFAULT_FILTER));
+    Ok(())
+}
+
+/// Get the most recent crash report, if the app has ever crashed
+#[tauri::command]
+pub async fn get_last_crash_report() -> Option<CrashReport> {
+    crash::last_report()
+}
+
+/// Recent narration and enrichment requests with their raw responses, newest first
+///
+/// Only debug builds keep them.
+#[tauri::command]
+pub async fn get_request_history(state: State<'_, Arc<AppState>>) -> Result<Vec<RecordedRequest>, CommandError> {
+    if !state.request_history.is_enabled() {
+        return Err("Request history is only kept in debug builds".into());
+    }
+    Ok(state.request_history.entries())
+}
@@ -0,0 +1,72 @@
+//! Database Maintenance Commands
+//!
+//! Size reporting and compaction of the local DuckDB file, which grows as
+//! rows are deleted and rewritten, the size of processing temp files, and
+//! the in-memory caches.
+
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::State;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::memory_cache::CacheStats;
+use crate::processor::VideoProcessor;
+use crate::services::database::{CompactResult, DbStats};
+use crate::services::temp_files::{self, TempUsage};
+use crate::services::LocalDatabase;
+use crate::state::AppState;
+
+/// How the in-memory caches are doing
+#[derive(Debug, Clone, Serialize)]
+pub struct MemoryCacheStats {
+    pub truth_bundles: CacheStats,
+    pub enrichments: CacheStats,
+}
+
+/// Entries dropped by `clear_caches`
+#[derive(Debug, Clone, Serialize)]
+pub struct ClearedCaches {
+    pub truth_bundles: usize,
+    pub enrichments: usize,
+}
+
+/// Get the database file's size and row counts per table
+#[tauri::command]
+pub async fn get_database_stats(db: State<'_, LocalDatabase>) -> Result<DbStats, CommandError> {
+    Ok(db.db_stats().await?)
+}
+
+/// Compact the database to reclaim space
+///
+/// Other database work pauses until this finishes, typically a few seconds.
+#[tauri::command]
+pub async fn compact_database(db: State<'_, LocalDatabase>) -> Result<CompactResult, CommandError> {
+    Ok(db.compact().await?)
+}
+
+/// Get how much disk the processing jobs' temp files take up
+#[tauri::command]
+pub async fn get_temp_usage(processor: State<'_, Arc<VideoProcessor>>) -> Result<TempUsage, CommandError> {
+    let root = processor.temp_dir().to_path_buf();
+    Ok(tauri::async_runtime::spawn_blocking(move || temp_files::usage(&root)).await?)
+}
+
+/// Get how full the in-memory caches are and how often they're hit
+///
+/// Their limits are set with `set_memory_cache_limits`.
+#[tauri::command]
+pub async fn get_cache_stats(state: State<'_, Arc<AppState>>) -> Result<MemoryCacheStats, CommandError> {
+    Ok(MemoryCacheStats { truth_bundles: state.truth_cache.stats(), enrichments: state.enrich_cache.stats() })
+}
+
+/// Empty the in-memory caches
+///
+/// Enrichments are still cached in the database, so they're read from
+/// there rather than looked up again.
+#[tauri::command]
+pub async fn clear_caches(state: State<'_, Arc<AppState>>) -> Result<ClearedCaches, CommandError> {
+    let cleared = ClearedCaches { truth_bundles: state.truth_cache.clear(), enrichments: state.enrich_cache.clear() };
+    info!("Cleared {} cached bundles and {} enrichments from memory", cleared.truth_bundles, cleared.enrichments);
+    Ok(cleared)
+}
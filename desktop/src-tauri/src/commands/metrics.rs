@@ -0,0 +1,48 @@
+//! Usage Metrics Commands
+//!
+//! Turning usage metrics on and off, showing what's been recorded, and
+//! writing it out for the user to share. Nothing here touches the network.
+
+use tauri::State;
+use tracing::info;
+
+use crate::error::CommandError;
+use crate::services::LocalDatabase;
+use crate::settings::{self, AppSettings};
+use crate::usage_metrics::{self, MetricsReport, UsageMetrics};
+
+/// Everything recorded so far, and whether recording is on
+#[tauri::command]
+pub async fn get_usage_metrics(db: State<'_, LocalDatabase>) -> Result<UsageMetrics, CommandError> {
+    let rows = db.get_metrics().await?;
+    Ok(usage_metrics::aggregate(&rows, settings::get().usage_metrics))
+}
+
+/// Start or stop recording usage metrics; what's recorded is kept either way
+#[tauri::command]
+pub async fn set_usage_metrics(enabled: bool) -> AppSettings {
+    info!("Usage metrics {}", if enabled { "enabled" } else { "disabled" });
+    settings::update(|s| s.usage_metrics = enabled)
+}
+
+/// Write what's been recorded to `path` as JSON, without when each thing
+/// happened; returns the path written
+#[tauri::command]
+pub async fn export_metrics_report(path: String, db: State<'_, LocalDatabase>) -> Result<String, CommandError> {
+    let rows = db.get_metrics().await?;
+    let report = MetricsReport::new(usage_metrics::aggregate(&rows, settings::get().usage_metrics));
+    let json = serde_json::to_string_pretty(&report)?;
+    tokio::fs::write(&path, json)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported the usage metrics report to {}", path);
+    Ok(path)
+}
+
+/// Forget everything recorded; returns how many metrics were removed
+#[tauri::command]
+pub async fn clear_usage_metrics(db: State<'_, LocalDatabase>) -> Result<usize, CommandError> {
+    let removed = db.clear_metrics().await?;
+    info!("Cleared {} usage metrics", removed);
+    Ok(removed)
+}
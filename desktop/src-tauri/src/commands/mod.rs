@@ -2,18 +2,56 @@
 //!
 //! All Tauri command modules for the desktop application.
 
+use std::io::Read;
+
+use tauri::{AppHandle, Emitter, Manager, State};
 use tracing::{debug, info, warn};
 
 use crate::config;
+use crate::error::{CommandError, ErrorCode};
+use crate::geo::GeoEngine;
+use crate::init::{self, InitStatus};
+use crate::resources::{self, Priority, Resource};
+use crate::services::extracts::{self, BoundingBox, ExtractProvider, OverpassProvider};
+use crate::services::mirrors::{self, DownloadProvider};
+use crate::services::pbf;
+use crate::services::LocalDatabase;
+use crate::state::AppState;
+use crate::usage_metrics::{self, Counter, Histogram};
 
 pub mod ingest;
+pub mod export;
 pub mod narrate;
+pub mod jobs;
+pub mod prompts;
 pub mod enrich;
 pub mod process;
+pub mod queue;
 pub mod video;
+pub mod settings;
+pub mod storage;
+pub mod local_regions;
+pub mod bundle_archive;
+pub mod region_bundle;
+pub mod project_bundle;
+pub mod truth_bundle;
+pub mod poi;
+pub mod maintenance;
+pub mod health;
+pub mod logs;
+pub mod metrics;
+pub mod paths;
+pub mod region_status;
+pub mod setup;
+pub mod sidecars;
+
+pub use region_status::RegionStatus;
+use region_status::{apply_data_check, check_data, reconcile_status, DataCheck, DataFingerprint};
 
 
 
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use once_cell::sync::Lazy;
@@ -32,6 +70,14 @@ pub fn get_version() -> String {
     version
 }
 
+/// Which parts of the app have come online since it started
+///
+/// For a frontend that loaded after some `app-ready` events were sent.
+#[tauri::command]
+pub fn get_init_status() -> InitStatus {
+    init::status()
+}
+
 /// Check if the API backend is reachable
 #[tauri::command]
 pub async fn check_api_connection() -> bool {
@@ -90,25 +136,69 @@ pub struct SystemInfo {
 
 
 /// Region data structure for frontend
-#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct RegionInfo {
     pub id: String,
     pub name: String,
     pub size_mb: u64,
+    /// The data file is there and matches what was recorded when it was stored
     pub downloaded: bool,
+    /// Date of the data: when the source published it, or else when it was stored
     pub last_updated: Option<String>,
     pub poi_count: u32,
     pub bounds: (f64, f64, f64, f64),
+    /// Server the data was downloaded from (Geofabrik or a mirror host)
+    #[serde(default)]
+    pub source: Option<String>,
+    /// SHA-256 of the data file, also used to spot the same file imported twice
+    #[serde(default)]
+    pub checksum: Option<String>,
+    /// Exact size of the data file when it was stored
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// Modification time of the data file when it was stored or last verified
+    #[serde(default)]
+    pub data_modified_ms: Option<u64>,
+    #[serde(default)]
+    pub status: RegionStatus,
+}
+
+/// Region search hit with its parent chain for disambiguation
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegionMatch {
+    #[serde(flatten)]
+    pub region: RegionInfo,
+    /// Human readable parent chain, e.g. "Europe > Germany > Bayern"
+    pub path: String,
 }
 
 /// Download progress structure
+///
+/// `total_bytes` and `progress_percent` are `None` (null) while the size is
+/// unknown, e.g. when the server sends no Content-Length.
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct DownloadProgress {
     pub region_id: String,
     pub bytes_downloaded: u64,
-    pub total_bytes: u64,
-    pub progress_percent: f64,
+    pub total_bytes: Option<u64>,
+    pub progress_percent: Option<f64>,
     pub status: String,
+    /// Transfer rate over the last few seconds
+    pub bytes_per_sec: f64,
+    /// Estimated time remaining, when the total size is known
+    pub eta_seconds: Option<u64>,
+}
+
+/// Catalog entry that hasn't been downloaded yet
+fn catalog_region(id: &str, name: &str, size_mb: u64, poi_count: u32, bounds: (f64, f64, f64, f64)) -> RegionInfo {
+    RegionInfo {
+        id: id.to_string(),
+        name: name.to_string(),
+        size_mb,
+        poi_count,
+        bounds,
+        ..Default::default()
+    }
 }
 
 /// Global map regions state
@@ -116,76 +206,123 @@ pub struct DownloadProgress {
 static AVAILABLE_REGIONS: Lazy<Vec<RegionInfo>> = Lazy::new(|| {
     vec![
         // USA
-        RegionInfo { id: "us/alabama".to_string(), name: "Alabama (US)".to_string(), size_mb: 250, downloaded: false, last_updated: None, poi_count: 50000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/alaska".to_string(), name: "Alaska (US)".to_string(), size_mb: 150, downloaded: false, last_updated: None, poi_count: 50000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/arizona".to_string(), name: "Arizona (US)".to_string(), size_mb: 200, downloaded: false, last_updated: None, poi_count: 80000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/arkansas".to_string(), name: "Arkansas (US)".to_string(), size_mb: 180, downloaded: false, last_updated: None, poi_count: 60000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/california".to_string(), name: "California (US)".to_string(), size_mb: 1100, downloaded: false, last_updated: None, poi_count: 450000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/colorado".to_string(), name: "Colorado (US)".to_string(), size_mb: 220, downloaded: false, last_updated: None, poi_count: 100000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/connecticut".to_string(), name: "Connecticut (US)".to_string(), size_mb: 80, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/delaware".to_string(), name: "Delaware (US)".to_string(), size_mb: 40, downloaded: false, last_updated: None, poi_count: 20000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/district-of-columbia".to_string(), name: "District of Columbia (US)".to_string(), size_mb: 30, downloaded: false, last_updated: None, poi_count: 15000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/florida".to_string(), name: "Florida (US)".to_string(), size_mb: 450, downloaded: false, last_updated: None, poi_count: 200000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/georgia".to_string(), name: "Georgia (US)".to_string(), size_mb: 300, downloaded: false, last_updated: None, poi_count: 120000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/hawaii".to_string(), name: "Hawaii (US)".to_string(), size_mb: 50, downloaded: false, last_updated: None, poi_count: 25000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/idaho".to_string(), name: "Idaho (US)".to_string(), size_mb: 150, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/illinois".to_string(), name: "Illinois (US)".to_string(), size_mb: 350, downloaded: false, last_updated: None, poi_count: 150000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/indiana".to_string(), name: "Indiana (US)".to_string(), size_mb: 200, downloaded: false, last_updated: None, poi_count: 80000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/iowa".to_string(), name: "Iowa (US)".to_string(), size_mb: 180, downloaded: false, last_updated: None, poi_count: 60000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/kansas".to_string(), name: "Kansas (US)".to_string(), size_mb: 160, downloaded: false, last_updated: None, poi_count: 50000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/kentucky".to_string(), name: "Kentucky (US)".to_string(), size_mb: 200, downloaded: false, last_updated: None, poi_count: 70000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/louisiana".to_string(), name: "Louisiana (US)".to_string(), size_mb: 220, downloaded: false, last_updated: None, poi_count: 80000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/maine".to_string(), name: "Maine (US)".to_string(), size_mb: 120, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/maryland".to_string(), name: "Maryland (US)".to_string(), size_mb: 150, downloaded: false, last_updated: None, poi_count: 60000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/massachusetts".to_string(), name: "Massachusetts (US)".to_string(), size_mb: 200, downloaded: false, last_updated: None, poi_count: 90000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/michigan".to_string(), name: "Michigan (US)".to_string(), size_mb: 350, downloaded: false, last_updated: None, poi_count: 140000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/minnesota".to_string(), name: "Minnesota (US)".to_string(), size_mb: 250, downloaded: false, last_updated: None, poi_count: 90000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/mississippi".to_string(), name: "Mississippi (US)".to_string(), size_mb: 160, downloaded: false, last_updated: None, poi_count: 50000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/missouri".to_string(), name: "Missouri (US)".to_string(), size_mb: 250, downloaded: false, last_updated: None, poi_count: 90000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/montana".to_string(), name: "Montana (US)".to_string(), size_mb: 180, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/nebraska".to_string(), name: "Nebraska (US)".to_string(), size_mb: 160, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/nevada".to_string(), name: "Nevada (US)".to_string(), size_mb: 120, downloaded: false, last_updated: None, poi_count: 30000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/new-hampshire".to_string(), name: "New Hampshire (US)".to_string(), size_mb: 80, downloaded: false, last_updated: None, poi_count: 30000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/new-jersey".to_string(), name: "New Jersey (US)".to_string(), size_mb: 180, downloaded: false, last_updated: None, poi_count: 80000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/new-mexico".to_string(), name: "New Mexico (US)".to_string(), size_mb: 150, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/new-york".to_string(), name: "New York (US)".to_string(), size_mb: 450, downloaded: false, last_updated: None, poi_count: 200000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/north-carolina".to_string(), name: "North Carolina (US)".to_string(), size_mb: 300, downloaded: false, last_updated: None, poi_count: 120000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/north-dakota".to_string(), name: "North Dakota (US)".to_string(), size_mb: 100, downloaded: false, last_updated: None, poi_count: 20000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/ohio".to_string(), name: "Ohio (US)".to_string(), size_mb: 350, downloaded: false, last_updated: None, poi_count: 140000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/oklahoma".to_string(), name: "Oklahoma (US)".to_string(), size_mb: 200, downloaded: false, last_updated: None, poi_count: 70000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/oregon".to_string(), name: "Oregon (US)".to_string(), size_mb: 250, downloaded: false, last_updated: None, poi_count: 90000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/pennsylvania".to_string(), name: "Pennsylvania (US)".to_string(), size_mb: 350, downloaded: false, last_updated: None, poi_count: 140000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/rhode-island".to_string(), name: "Rhode Island (US)".to_string(), size_mb: 40, downloaded: false, last_updated: None, poi_count: 15000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/south-carolina".to_string(), name: "South Carolina (US)".to_string(), size_mb: 200, downloaded: false, last_updated: None, poi_count: 70000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/south-dakota".to_string(), name: "South Dakota (US)".to_string(), size_mb: 120, downloaded: false, last_updated: None, poi_count: 30000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/tennessee".to_string(), name: "Tennessee (US)".to_string(), size_mb: 220, downloaded: false, last_updated: None, poi_count: 80000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/texas".to_string(), name: "Texas (US)".to_string(), size_mb: 850, downloaded: false, last_updated: None, poi_count: 350000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/utah".to_string(), name: "Utah (US)".to_string(), size_mb: 150, downloaded: false, last_updated: None, poi_count: 50000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/vermont".to_string(), name: "Vermont (US)".to_string(), size_mb: 80, downloaded: false, last_updated: None, poi_count: 20000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/virginia".to_string(), name: "Virginia (US)".to_string(), size_mb: 250, downloaded: false, last_updated: None, poi_count: 90000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/washington".to_string(), name: "Washington (US)".to_string(), size_mb: 300, downloaded: false, last_updated: None, poi_count: 120000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/west-virginia".to_string(), name: "West Virginia (US)".to_string(), size_mb: 120, downloaded: false, last_updated: None, poi_count: 40000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/wisconsin".to_string(), name: "Wisconsin (US)".to_string(), size_mb: 250, downloaded: false, last_updated: None, poi_count: 90000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "us/wyoming".to_string(), name: "Wyoming (US)".to_string(), size_mb: 120, downloaded: false, last_updated: None, poi_count: 30000, bounds: (0.0, 0.0, 0.0, 0.0) },
+        catalog_region("us/alabama", "Alabama (US)", 250, 50000, (30.14, -88.47, 35.01, -84.89)),
+        catalog_region("us/alaska", "Alaska (US)", 150, 50000, (51.21, -179.15, 71.39, -129.98)),
+        catalog_region("us/arizona", "Arizona (US)", 200, 80000, (31.33, -114.82, 37.0, -109.04)),
+        catalog_region("us/arkansas", "Arkansas (US)", 180, 60000, (33.0, -94.62, 36.5, -89.64)),
+        catalog_region("us/california", "California (US)", 1100, 450000, (32.53, -124.48, 42.01, -114.13)),
+        catalog_region("us/colorado", "Colorado (US)", 220, 100000, (36.99, -109.06, 41.0, -102.04)),
+        catalog_region("us/connecticut", "Connecticut (US)", 80, 40000, (40.95, -73.73, 42.05, -71.79)),
+        catalog_region("us/delaware", "Delaware (US)", 40, 20000, (38.45, -75.79, 39.84, -75.05)),
+        catalog_region("us/district-of-columbia", "District of Columbia (US)", 30, 15000, (38.79, -77.12, 39.0, -76.91)),
+        catalog_region("us/florida", "Florida (US)", 450, 200000, (24.4, -87.63, 31.0, -80.03)),
+        catalog_region("us/georgia", "Georgia (US)", 300, 120000, (30.36, -85.61, 35.0, -80.84)),
+        catalog_region("us/hawaii", "Hawaii (US)", 50, 25000, (18.91, -160.25, 22.24, -154.81)),
+        catalog_region("us/idaho", "Idaho (US)", 150, 40000, (41.99, -117.24, 49.0, -111.04)),
+        catalog_region("us/illinois", "Illinois (US)", 350, 150000, (36.97, -91.51, 42.51, -87.49)),
+        catalog_region("us/indiana", "Indiana (US)", 200, 80000, (37.77, -88.1, 41.76, -84.78)),
+        catalog_region("us/iowa", "Iowa (US)", 180, 60000, (40.38, -96.64, 43.5, -90.14)),
+        catalog_region("us/kansas", "Kansas (US)", 160, 50000, (36.99, -102.05, 40.0, -94.59)),
+        catalog_region("us/kentucky", "Kentucky (US)", 200, 70000, (36.5, -89.57, 39.15, -81.96)),
+        catalog_region("us/louisiana", "Louisiana (US)", 220, 80000, (28.93, -94.04, 33.02, -88.82)),
+        catalog_region("us/maine", "Maine (US)", 120, 40000, (43.06, -71.08, 47.46, -66.95)),
+        catalog_region("us/maryland", "Maryland (US)", 150, 60000, (37.91, -79.49, 39.72, -75.05)),
+        catalog_region("us/massachusetts", "Massachusetts (US)", 200, 90000, (41.24, -73.51, 42.89, -69.93)),
+        catalog_region("us/michigan", "Michigan (US)", 350, 140000, (41.7, -90.42, 48.31, -82.41)),
+        catalog_region("us/minnesota", "Minnesota (US)", 250, 90000, (43.5, -97.24, 49.38, -89.49)),
+        catalog_region("us/mississippi", "Mississippi (US)", 160, 50000, (30.17, -91.66, 35.0, -88.1)),
+        catalog_region("us/missouri", "Missouri (US)", 250, 90000, (35.99, -95.77, 40.61, -89.1)),
+        catalog_region("us/montana", "Montana (US)", 180, 40000, (44.36, -116.05, 49.0, -104.04)),
+        catalog_region("us/nebraska", "Nebraska (US)", 160, 40000, (40.0, -104.05, 43.0, -95.31)),
+        catalog_region("us/nevada", "Nevada (US)", 120, 30000, (35.0, -120.01, 42.0, -114.04)),
+        catalog_region("us/new-hampshire", "New Hampshire (US)", 80, 30000, (42.7, -72.56, 45.31, -70.61)),
+        catalog_region("us/new-jersey", "New Jersey (US)", 180, 80000, (38.93, -75.56, 41.36, -73.89)),
+        catalog_region("us/new-mexico", "New Mexico (US)", 150, 40000, (31.33, -109.05, 37.0, -103.0)),
+        catalog_region("us/new-york", "New York (US)", 450, 200000, (40.5, -79.76, 45.02, -71.86)),
+        catalog_region("us/north-carolina", "North Carolina (US)", 300, 120000, (33.84, -84.32, 36.59, -75.46)),
+        catalog_region("us/north-dakota", "North Dakota (US)", 100, 20000, (45.94, -104.05, 49.0, -96.55)),
+        catalog_region("us/ohio", "Ohio (US)", 350, 140000, (38.4, -84.82, 41.98, -80.52)),
+        catalog_region("us/oklahoma", "Oklahoma (US)", 200, 70000, (33.62, -103.0, 37.0, -94.43)),
+        catalog_region("us/oregon", "Oregon (US)", 250, 90000, (41.99, -124.57, 46.29, -116.46)),
+        catalog_region("us/pennsylvania", "Pennsylvania (US)", 350, 140000, (39.72, -80.52, 42.27, -74.69)),
+        catalog_region("us/rhode-island", "Rhode Island (US)", 40, 15000, (41.15, -71.91, 42.02, -71.12)),
+        catalog_region("us/south-carolina", "South Carolina (US)", 200, 70000, (32.03, -83.35, 35.22, -78.54)),
+        catalog_region("us/south-dakota", "South Dakota (US)", 120, 30000, (42.48, -104.06, 45.95, -96.44)),
+        catalog_region("us/tennessee", "Tennessee (US)", 220, 80000, (34.98, -90.31, 36.68, -81.65)),
+        catalog_region("us/texas", "Texas (US)", 850, 350000, (25.84, -106.65, 36.5, -93.51)),
+        catalog_region("us/utah", "Utah (US)", 150, 50000, (37.0, -114.05, 42.0, -109.04)),
+        catalog_region("us/vermont", "Vermont (US)", 80, 20000, (42.73, -73.44, 45.02, -71.46)),
+        catalog_region("us/virginia", "Virginia (US)", 250, 90000, (36.54, -83.68, 39.47, -75.24)),
+        catalog_region("us/washington", "Washington (US)", 300, 120000, (45.54, -124.85, 49.0, -116.92)),
+        catalog_region("us/west-virginia", "West Virginia (US)", 120, 40000, (37.2, -82.64, 40.64, -77.72)),
+        catalog_region("us/wisconsin", "Wisconsin (US)", 250, 90000, (42.49, -92.89, 47.31, -86.25)),
+        catalog_region("us/wyoming", "Wyoming (US)", 120, 30000, (40.99, -111.06, 45.01, -104.05)),
         // Europe Examples
-        RegionInfo { id: "europe/monaco".to_string(), name: "Monaco".to_string(), size_mb: 1, downloaded: false, last_updated: None, poi_count: 500, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "europe/france".to_string(), name: "France".to_string(), size_mb: 3500, downloaded: false, last_updated: None, poi_count: 1500000, bounds: (0.0, 0.0, 0.0, 0.0) },
-        RegionInfo { id: "europe/germany".to_string(), name: "Germany".to_string(), size_mb: 3200, downloaded: false, last_updated: None, poi_count: 1400000, bounds: (0.0, 0.0, 0.0, 0.0) },
+        catalog_region("europe/monaco", "Monaco", 1, 500, (43.72, 7.4, 43.76, 7.44)),
+        catalog_region("europe/france", "France", 3500, 1500000, (41.33, -5.14, 51.09, 9.56)),
+        catalog_region("europe/germany", "Germany", 3200, 1400000, (47.27, 5.87, 55.06, 15.04)),
     ]
 });
 
 /// Global map regions state (User added regions)
+///
+/// Statuses are reconciled with the tiles directory when first loaded, as
+/// nothing can be downloading yet and a crash may have left stale states.
 static MAP_REGIONS: Lazy<Arc<RwLock<Vec<RegionInfo>>>> = Lazy::new(|| {
-    let regions = load_regions_from_disk().unwrap_or_else(|| {
-        vec![
-            // Defaults if no file exists
-            AVAILABLE_REGIONS.iter().find(|r| r.id == "europe/monaco").unwrap().clone(),
-            AVAILABLE_REGIONS.iter().find(|r| r.id == "us/california").unwrap().clone(),
-        ]
+    let mut regions = load_regions_from_disk().unwrap_or_else(|| {
+        // Defaults if no file exists
+        ["europe/monaco", "us/california"]
+            .iter()
+            .filter_map(|id| AVAILABLE_REGIONS.iter().find(|r| r.id == *id).cloned())
+            .collect()
     });
+    
+    let dir = tiles_dir();
+    let mut changed = false;
+    for region in regions.iter_mut() {
+        let status = reconcile_status(region, &dir);
+        if status != region.status {
+            info!("Region {} status reconciled: {:?} -> {:?}", region.id, region.status, status);
+            region.status = status;
+            changed = true;
+        }
+    }
+    if changed {
+        save_regions_to_disk(&regions);
+    }
+    
     Arc::new(RwLock::new(regions))
 });
 
+/// Load and reconcile the map regions now, rather than on first use
+pub(crate) fn load_map_regions() {
+    Lazy::force(&MAP_REGIONS);
+}
+
+/// Move a region to `status` and persist it
+///
+/// Transitions not allowed by [`RegionStatus::can_transition_to`] are
+/// refused and logged. Returns whether the status changed.
+pub(crate) async fn set_region_status(region_id: &str, status: RegionStatus) -> bool {
+    let mut regions = MAP_REGIONS.write().await;
+    let Some(region) = regions.iter_mut().find(|r| r.id == region_id) else {
+        return false;
+    };
+    
+    if region.status == status {
+        return false;
+    }
+    if !region.status.can_transition_to(&status) {
+        warn!("Refusing status change of {}: {:?} -> {:?}", region_id, region.status, status);
+        return false;
+    }
+    
+    debug!("Region {} status: {:?} -> {:?}", region_id, region.status, status);
+    region.status = status;
+    save_regions_to_disk(&regions);
+    true
+}
+
 /// Helper to get persistence file path
 fn get_regions_file_path() -> std::path::PathBuf {
     dirs::data_dir()
@@ -239,15 +376,274 @@ static DOWNLOAD_PROGRESS: Lazy<Arc<RwLock<Option<DownloadProgress>>>> = Lazy::ne
     Arc::new(RwLock::new(None))
 });
 
-/// Get all available map regions from catalog
+/// Download speed cap in bytes per second (0 = unlimited)
+static DOWNLOAD_RATE_LIMIT: Lazy<AtomicU64> = Lazy::new(|| AtomicU64::new(crate::settings::get().download_rate_limit));
+
+/// Regions whose download should stop at the next chunk
+static PAUSED_DOWNLOADS: Lazy<Arc<RwLock<HashSet<String>>>> = Lazy::new(|| {
+    Arc::new(RwLock::new(HashSet::new()))
+});
+
+/// Regions with a download currently running
+static ACTIVE_DOWNLOADS: Lazy<std::sync::Mutex<HashSet<String>>> = Lazy::new(|| {
+    std::sync::Mutex::new(HashSet::new())
+});
+
+/// Marks a region download as running for as long as it is alive
+struct ActiveDownload(String);
+
+impl ActiveDownload {
+    fn start(region_id: &str) -> Self {
+        active_downloads().insert(region_id.to_string());
+        Self(region_id.to_string())
+    }
+}
+
+impl Drop for ActiveDownload {
+    fn drop(&mut self) {
+        active_downloads().remove(&self.0);
+    }
+}
+
+fn active_downloads() -> std::sync::MutexGuard<'static, HashSet<String>> {
+    ACTIVE_DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether any region download is in progress
+pub fn downloads_in_flight() -> bool {
+    !active_downloads().is_empty()
+}
+
+/// Default number of regions in a page of the catalog
+const DEFAULT_CATALOG_PAGE: usize = 100;
+
+/// Most regions returned in one page of the catalog
+const MAX_CATALOG_PAGE: usize = 1000;
+
+/// One page of the region catalog
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegionPage {
+    pub regions: Vec<RegionInfo>,
+    /// Regions matching the filters across all pages
+    pub total: usize,
+}
+
+/// Get a page of the available map regions from the catalog
+///
+/// `area` keeps the regions under a continent or country, given as an id
+/// prefix ("europe", "us") or a name from the region's parent chain
+/// ("North America"). `query` keeps regions whose name or id contains it.
+/// Both are case-insensitive. With no arguments the first
+/// [`DEFAULT_CATALOG_PAGE`] regions are returned.
+#[tauri::command]
+pub async fn get_available_regions(
+    area: Option<String>,
+    query: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> RegionPage {
+    catalog_page(&AVAILABLE_REGIONS, area.as_deref(), query.as_deref(), offset, limit)
+}
+
+fn catalog_page(
+    catalog: &[RegionInfo],
+    area: Option<&str>,
+    query: Option<&str>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> RegionPage {
+    let area = area.map(|a| a.trim().trim_matches('/').to_lowercase()).filter(|a| !a.is_empty());
+    let query = query.map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty());
+
+    let matching: Vec<&RegionInfo> = catalog
+        .iter()
+        .filter(|r| match &area {
+            Some(area) => region_in_area(r, area),
+            None => true,
+        })
+        .filter(|r| match &query {
+            Some(query) => r.name.to_lowercase().contains(query) || r.id.to_lowercase().contains(query),
+            None => true,
+        })
+        .collect();
+
+    let limit = limit.unwrap_or(DEFAULT_CATALOG_PAGE).min(MAX_CATALOG_PAGE);
+    RegionPage {
+        total: matching.len(),
+        regions: matching.into_iter().skip(offset.unwrap_or(0)).take(limit).cloned().collect(),
+    }
+}
+
+/// Whether a region lies under a lowercase area id prefix or parent name
+fn region_in_area(region: &RegionInfo, area: &str) -> bool {
+    let id = region.id.to_lowercase();
+    if id.starts_with(area) && id[area.len()..].starts_with('/') {
+        return true;
+    }
+    let name = area.replace('-', " ");
+    let mut chain = region_parent_chain(region);
+    chain.pop(); // The region itself
+    chain.iter().any(|parent| parent.to_lowercase() == name)
+}
+
+/// Default number of results returned by `search_regions`
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// Search the region catalog by name or id
+///
+/// Matching is case-insensitive. Results are ranked exact match first, then
+/// name prefix, word prefix, substring, and finally fuzzy-prefix matches where
+/// every query word is a prefix of some word in the region name ("n car").
+#[tauri::command]
+pub async fn search_regions(query: String, limit: Option<usize>) -> Vec<RegionMatch> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(u8, &RegionInfo)> = AVAILABLE_REGIONS
+        .iter()
+        .filter_map(|r| region_match_rank(r, &query).map(|rank| (rank, r)))
+        .collect();
+
+    scored.sort_by(|(a_rank, a), (b_rank, b)| {
+        a_rank.cmp(b_rank).then_with(|| a.name.cmp(&b.name))
+    });
+
+    debug!(query = %query, matches = scored.len(), "Region search");
+
+    scored
+        .into_iter()
+        .take(limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+        .map(|(_, r)| RegionMatch {
+            region: r.clone(),
+            path: region_parent_chain(r).join(" > "),
+        })
+        .collect()
+}
+
+/// Suggest catalog regions whose bounds contain the given coordinate
+///
+/// Smallest regions come first, since they are the cheapest download that
+/// still covers the point.
 #[tauri::command]
-pub async fn get_available_regions() -> Vec<RegionInfo> {
-    AVAILABLE_REGIONS.clone()
+pub async fn get_regions_covering(lat: f64, lon: f64) -> Vec<RegionMatch> {
+    let mut covering: Vec<&RegionInfo> = AVAILABLE_REGIONS
+        .iter()
+        .filter(|r| region_contains(r, lat, lon))
+        .collect();
+
+    covering.sort_by(|a, b| region_area(a).total_cmp(&region_area(b)));
+
+    covering
+        .into_iter()
+        .map(|r| RegionMatch {
+            region: r.clone(),
+            path: region_parent_chain(r).join(" > "),
+        })
+        .collect()
+}
+
+/// Rank how well a region matches a lowercase query (lower is better)
+fn region_match_rank(region: &RegionInfo, query: &str) -> Option<u8> {
+    let name = region_display_name(region).to_lowercase();
+    let id = region.id.to_lowercase();
+    let leaf_id = id.rsplit('/').next().unwrap_or(&id);
+
+    if name == query || id == query || leaf_id == query {
+        return Some(0);
+    }
+    if name.starts_with(query) || leaf_id.starts_with(query) {
+        return Some(1);
+    }
+
+    let words: Vec<&str> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    if words.iter().any(|w| w.starts_with(query)) {
+        return Some(2);
+    }
+    if name.contains(query) || id.contains(query) {
+        return Some(3);
+    }
+
+    // Fuzzy prefix: every query word must prefix some word of the name
+    let query_words: Vec<&str> = query.split_whitespace().collect();
+    if query_words.len() > 1
+        && query_words
+            .iter()
+            .all(|q| words.iter().any(|w| w.starts_with(q)))
+    {
+        return Some(4);
+    }
+
+    None
+}
+
+/// Region name without the trailing country hint, e.g. "California (US)" -> "California"
+fn region_display_name(region: &RegionInfo) -> &str {
+    match region.name.rfind(" (") {
+        Some(idx) if region.name.ends_with(')') => &region.name[..idx],
+        _ => &region.name,
+    }
+}
+
+/// Build the parent chain for a region from its id prefix
+fn region_parent_chain(region: &RegionInfo) -> Vec<String> {
+    let mut chain: Vec<String> = Vec::new();
+    let mut segments: Vec<&str> = region.id.split('/').collect();
+    segments.pop(); // The leaf is the region itself
+
+    for segment in segments {
+        match segment {
+            "us" => {
+                chain.push("North America".to_string());
+                chain.push("United States".to_string());
+            }
+            "europe" => chain.push("Europe".to_string()),
+            other => chain.push(title_case(other)),
+        }
+    }
+
+    chain.push(region_display_name(region).to_string());
+    chain
+}
+
+/// Turn an id segment like "north-carolina" into "North Carolina"
+fn title_case(segment: &str) -> String {
+    segment
+        .split('-')
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Check whether a region's bounds contain a coordinate
+fn region_contains(region: &RegionInfo, lat: f64, lon: f64) -> bool {
+    let (min_lat, min_lon, max_lat, max_lon) = region.bounds;
+    if region_area(region) <= 0.0 {
+        return false; // Unknown bounds
+    }
+    (min_lat..=max_lat).contains(&lat) && (min_lon..=max_lon).contains(&lon)
+}
+
+/// Approximate bounding-box area in square degrees
+fn region_area(region: &RegionInfo) -> f64 {
+    let (min_lat, min_lon, max_lat, max_lon) = region.bounds;
+    (max_lat - min_lat) * (max_lon - min_lon)
 }
 
 /// Add a region to my map packs
 #[tauri::command]
-pub async fn add_region(region_id: String) -> Result<(), String> {
+pub async fn add_region(region_id: String) -> Result<(), CommandError> {
     let mut regions = MAP_REGIONS.write().await;
     
     // Check if already added
@@ -257,71 +653,308 @@ pub async fn add_region(region_id: String) -> Result<(), String> {
 
     // Find in catalog
     if let Some(region) = AVAILABLE_REGIONS.iter().find(|r| r.id == region_id) {
-        regions.push(region.clone());
+        let mut region = region.clone();
+        // Data kept from an earlier removal makes the region ready right away
+        let dir = tiles_dir();
+        region.status = reconcile_status(&region, &dir);
+        if let Some(path) = region_status::data_file_path(&region.id, &dir) {
+            match stored_data(path.clone()).await {
+                Ok(fingerprint) => {
+                    region.last_updated = Some(data_date(&path).to_rfc3339());
+                    region_status::record_data(&mut region, fingerprint);
+                }
+                Err(e) => warn!("Failed to read kept data of {}: {}", region.id, e),
+            }
+        }
+        regions.push(region);
         // Save using current list
         save_regions_to_disk(&regions);
         Ok(())
     } else {
-        Err(format!("Region not found in catalog: {}", region_id))
+        Err(CommandError::not_found(format!("Region not found in catalog: {}", region_id)))
+    }
+}
+
+/// Directory holding downloaded region data
+fn tiles_dir() -> std::path::PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| std::path::PathBuf::from("."))
+        .join("com.geotruth.app")
+        .join("tiles")
+}
+
+/// On-disk path for a region's data (custom extracts are OSM XML, catalog regions PBF)
+fn region_file_path(region_id: &str) -> std::path::PathBuf {
+    region_file_path_in(&tiles_dir(), region_id)
+}
+
+fn region_file_path_in(dir: &std::path::Path, region_id: &str) -> std::path::PathBuf {
+    // sanitize id for filename (replace / with _)
+    let filename = region_id.replace("/", "_");
+    if region_id.starts_with(CUSTOM_REGION_PREFIX) {
+        dir.join(format!("{}.osm", filename))
+    } else {
+        dir.join(format!("{}.osm.pbf", filename))
+    }
+}
+
+/// Path of the PMTiles archive built for a region
+fn region_pmtiles_path_in(dir: &std::path::Path, region_id: &str) -> std::path::PathBuf {
+    dir.join(format!("{}.pmtiles", region_id.replace("/", "_")))
+}
+
+/// Id prefix for user-defined bounding-box regions
+const CUSTOM_REGION_PREFIX: &str = "custom/";
+
+/// Id prefix for regions imported from a local file
+const LOCAL_REGION_PREFIX: &str = "local/";
+
+/// `<prefix><slug of name>`, numbered if that id is already taken
+fn unique_region_id(regions: &[RegionInfo], prefix: &str, name: &str) -> String {
+    let base_slug = extracts::slugify(name);
+    let mut candidate = format!("{}{}", prefix, base_slug);
+    let mut n = 2;
+    while regions.iter().any(|r| r.id == candidate) {
+        candidate = format!("{}{}-{}", prefix, base_slug, n);
+        n += 1;
     }
+    candidate
+}
+
+/// Fingerprint a finished data file, off the async runtime since it reads all of it
+async fn stored_data(path: std::path::PathBuf) -> Result<DataFingerprint, CommandError> {
+    let _slot = resources::acquire(Resource::Io, Priority::Batch).await;
+    Ok(tokio::task::spawn_blocking(move || region_status::fingerprint(&path)).await??)
+}
+
+/// Date of data found on disk: the extract's replication timestamp, or else
+/// the file's modification time
+fn data_date(path: &std::path::Path) -> chrono::DateTime<chrono::Utc> {
+    pbf::validate_pbf(path)
+        .ok()
+        .and_then(|header| header.replication_timestamp)
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .or_else(|| {
+            std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .map(chrono::DateTime::<chrono::Utc>::from)
+        })
+        .unwrap_or_else(chrono::Utc::now)
 }
 
 /// Get my map regions
+///
+/// Each region's data file is checked against what was recorded when it was
+/// stored, and changes to `downloaded` (or to the status of damaged data)
+/// are persisted. Regions that are downloading are left as they are.
 #[tauri::command]
 pub async fn get_map_regions() -> Vec<RegionInfo> {
-    let regions = MAP_REGIONS.read().await;
+    let snapshot = MAP_REGIONS.read().await.clone();
+    let dir = tiles_dir();
     
-    let data_dir = dirs::data_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("com.geotruth.app")
-        .join("tiles");
-    
-    regions.iter().map(|r| {
-        let mut region = r.clone();
-        // sanitize id for filename (replace / with _)
-        let filename = r.id.replace("/", "_");
-        let path = data_dir.join(format!("{}.osm.pbf", filename));
-        region.downloaded = path.exists();
-        region
-    }).collect()
+    // A modified file is hashed, which takes a while for large extracts
+    let slot = resources::acquire(Resource::Io, Priority::Interactive).await;
+    let checks = tokio::task::spawn_blocking(move || {
+        snapshot
+            .into_iter()
+            .map(|region| {
+                let check = check_data(&region, &dir);
+                (region, check)
+            })
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap_or_default();
+    drop(slot);
+    
+    let mut regions = MAP_REGIONS.write().await;
+    let mut changed = false;
+    for (checked, check) in checks {
+        let Some(region) = regions.iter_mut().find(|r| r.id == checked.id) else {
+            continue;
+        };
+        // A download finishing meanwhile records a new file
+        let in_flight = matches!(
+            region.status,
+            RegionStatus::Queued | RegionStatus::Downloading | RegionStatus::Processing
+        );
+        if in_flight || region.size_bytes != checked.size_bytes || region.checksum != checked.checksum {
+            continue;
+        }
+        if let DataCheck::Damaged(reason) = &check {
+            warn!("Region {} data is damaged: {}", region.id, reason);
+        }
+        changed |= apply_data_check(region, check);
+    }
+    if changed {
+        save_regions_to_disk(&regions);
+    }
+    
+    regions.clone()
+}
+
+/// Attempts made for a catalog region download before giving up
+const DOWNLOAD_MAX_ATTEMPTS: u32 = 5;
+
+/// Base delay for exponential backoff between download attempts
+const DOWNLOAD_BASE_BACKOFF_MS: u64 = 1000;
+
+/// Whether a failed download is worth retrying
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DownloadErrorKind {
+    /// Network hiccups and server errors that may go away on their own
+    Retryable,
+    /// Missing files, denied access or local disk problems
+    Fatal,
+    /// Stopped on request; the partial file is kept for resuming
+    Paused,
+    /// Throughput stayed below the failover minimum; another source is tried
+    Stalled,
+}
+
+/// A classified download failure
+#[derive(Debug, Clone)]
+struct DownloadError {
+    kind: DownloadErrorKind,
+    message: String,
+}
+
+impl DownloadError {
+    fn retryable(message: impl Into<String>) -> Self {
+        Self { kind: DownloadErrorKind::Retryable, message: message.into() }
+    }
+
+    fn fatal(message: impl Into<String>) -> Self {
+        Self { kind: DownloadErrorKind::Fatal, message: message.into() }
+    }
+
+    fn paused() -> Self {
+        Self { kind: DownloadErrorKind::Paused, message: "Download paused".into() }
+    }
+
+    fn stalled(message: impl Into<String>) -> Self {
+        Self { kind: DownloadErrorKind::Stalled, message: message.into() }
+    }
+}
+
+/// Failures the network may get over read as network errors; a pause as
+/// a cancellation
+impl From<DownloadError> for CommandError {
+    fn from(e: DownloadError) -> Self {
+        let code = match e.kind {
+            DownloadErrorKind::Retryable | DownloadErrorKind::Stalled => ErrorCode::Network,
+            DownloadErrorKind::Fatal => ErrorCode::ServiceFailed,
+            DownloadErrorKind::Paused => ErrorCode::Cancelled,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            DownloadErrorKind::Retryable => write!(f, "{} (retryable)", self.message),
+            DownloadErrorKind::Fatal => write!(f, "{} (fatal)", self.message),
+            DownloadErrorKind::Paused => write!(f, "{}", self.message),
+            DownloadErrorKind::Stalled => write!(f, "{} (stalled)", self.message),
+        }
+    }
+}
+
+/// Payload of the `region-download-failed` event
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegionDownloadFailed {
+    pub region_id: String,
+    pub kind: DownloadErrorKind,
+    pub message: String,
+    pub attempts: u32,
 }
 
 /// Download a map region
+///
+/// Connection and transfer failures are retried with exponential backoff,
+/// resuming from the bytes already written. Emits `region-download-failed`
+/// with the error classification when the download gives up. The download
+/// runs as a `download` job; cancelling it, like pausing it, keeps the
+/// partial file.
 #[tauri::command]
-pub async fn download_map_region(region_id: String) -> Result<(), String> {
+pub async fn download_map_region(app: AppHandle, region_id: String) -> Result<(), CommandError> {
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    // Custom extracts are generated on the fly, so can't be picked up after
+    let stop = (!region_id.starts_with(CUSTOM_REGION_PREFIX)).then(|| {
+        let region_id = region_id.clone();
+        Box::new(move || {
+            let region_id = region_id.clone();
+            tauri::async_runtime::spawn(async move {
+                PAUSED_DOWNLOADS.write().await.insert(region_id);
+            });
+        }) as crate::jobs::StopHook
+    });
+    let cached = state.clone();
+    crate::jobs::run(&state, crate::jobs::emitter(&app), "download", stop, |job| async move {
+        tokio::select! {
+            result = fetch_map_region(app, region_id.clone()) => result?,
+            never = follow_download(&job, &region_id) => match never {},
+        };
+        if PAUSED_DOWNLOADS.read().await.contains(&region_id) {
+            job.cancelled();
+            return Ok(());
+        }
+        // What was worked out from the old data may not hold any more
+        if let Some(region) = MAP_REGIONS.read().await.iter().find(|r| r.id == region_id) {
+            cached.region_changed(region.bounds);
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Report a region's download progress to its job until the download ends
+async fn follow_download(job: &crate::jobs::JobContext, region_id: &str) -> std::convert::Infallible {
+    loop {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        let progress = DOWNLOAD_PROGRESS.read().await.clone().filter(|p| p.region_id == region_id);
+        if let Some(p) = progress {
+            job.progress(p.progress_percent.unwrap_or_default() as f32 / 100.0, p.status);
+        }
+    }
+}
+
+async fn fetch_map_region(app: AppHandle, region_id: String) -> Result<(), CommandError> {
     let regions = MAP_REGIONS.read().await;
     let region = regions.iter()
         .find(|r| r.id == region_id)
-        .ok_or_else(|| format!("Region not found: {}", region_id))?
+        .ok_or_else(|| CommandError::not_found(format!("Region not found: {}", region_id)))?
         .clone();
     drop(regions);
     
     info!("Starting download for region: {} ({})", region.name, region.id);
     
-    // Create data directory
-    let data_dir = dirs::data_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("com.geotruth.app")
-        .join("tiles");
-    std::fs::create_dir_all(&data_dir).map_err(|e| e.to_string())?;
-    
-    let file_path = data_dir.join(format!("{}.osm.pbf", region_id.replace("/", "_")));
-    
-    // Get download URL based on region
-    // Dynamic Geofabrik URL construction
-    let url = if region_id.starts_with("us/") {
-        let state = region_id.strip_prefix("us/").unwrap();
-        format!("https://download.geofabrik.de/north-america/us/{}-latest.osm.pbf", state)
-    } else if region_id.starts_with("europe/") {
-        let country = region_id.strip_prefix("europe/").unwrap();
-        format!("https://download.geofabrik.de/europe/{}-latest.osm.pbf", country)
-    } else {
-        match region_id.as_str() {
-            "monaco" => "https://download.geofabrik.de/europe/monaco-latest.osm.pbf".to_string(),
-            "california" => "https://download.geofabrik.de/north-america/us/california-latest.osm.pbf".to_string(), // Legacy fallback
-            _ => return Err(format!("Download logic not implemented for: {}", region_id)),
-        }
-    };
+    if region_id.starts_with(LOCAL_REGION_PREFIX) {
+        return Err(CommandError::invalid_input(
+            "Imported regions have no download source; import the file again to replace it",
+        ));
+    }
+    
+    // Custom regions are re-extracted from their stored bounds
+    if region_id.starts_with(CUSTOM_REGION_PREFIX) {
+        download_custom_extract(&region).await?;
+        return Ok(());
+    }
+    
+    // Done before the region shows as downloading, so failing here doesn't leave it so
+    std::fs::create_dir_all(tiles_dir())?;
+    let sources = mirrors::sources_for(&region_id, &crate::settings::get().download_mirrors)?;
+    
+    let file_path = region_file_path(&region_id);
+    let part_path = part_file_path(&file_path);
+    
+    // Waits for other downloads past the network limit
+    let _slot = resources::acquire(Resource::Network, Priority::Batch).await;
+    let _active = ActiveDownload::start(&region_id);
+    set_region_status(&region_id, RegionStatus::Downloading).await;
     
     // Initialize progress
     {
@@ -329,58 +962,112 @@ pub async fn download_map_region(region_id: String) -> Result<(), String> {
         *progress = Some(DownloadProgress {
             region_id: region_id.clone(),
             bytes_downloaded: 0,
-            total_bytes: region.size_mb * 1024 * 1024,
-            progress_percent: 0.0,
+            total_bytes: None,
+            progress_percent: None,
             status: "Connecting...".to_string(),
+            bytes_per_sec: 0.0,
+            eta_seconds: None,
         });
     }
     
-    // Download file with streaming for progress
-    use futures_util::StreamExt;
-    let client = reqwest::Client::new();
-    let response = client.get(url)
-        .send()
-        .await
-        .map_err(|e| format!("Download failed: {}", e))?;
-    
-    let total_size = response.content_length().unwrap_or(region.size_mb * 1024 * 1024);
+    // A partial file left by a pause or failed attempt is resumed below
+    PAUSED_DOWNLOADS.write().await.remove(&region_id);
     
-    {
-        let mut progress = DOWNLOAD_PROGRESS.write().await;
-        if let Some(p) = progress.as_mut() {
-            p.total_bytes = total_size;
-            p.status = "Downloading...".to_string();
-        }
-    }
+    let client = reqwest::Client::new();
+    // Only a rough size, good enough to spot truncated files but not for progress
+    let catalog_size = region.size_mb * 1024 * 1024;
     
-    let mut file = std::fs::File::create(&file_path).map_err(|e| format!("Failed to create file: {}", e))?;
-    let mut downloaded: u64 = 0;
-    let mut stream = response.bytes_stream();
+    // An HTML error page saved as .osm.pbf would look downloaded but break everything downstream
+    let result = download_from_sources(
+        &client,
+        &region_id,
+        &sources,
+        &part_path,
+        &FailoverPolicy::default(),
+        |path, downloaded| validate_region_file(path, downloaded, catalog_size),
+    )
+    .await;
     
-    while let Some(item) = stream.next().await {
-        let chunk = item.map_err(|e| format!("Error while downloading: {}", e))?;
-        std::io::Write::write_all(&mut file, &chunk).map_err(|e| format!("Error while writing to file: {}", e))?;
-        downloaded += chunk.len() as u64;
-        
-        {
+    let outcome = match result {
+        Ok(outcome) => outcome,
+        Err(failure) if failure.error.kind == DownloadErrorKind::Paused => {
+            info!("Download paused: {} ({:?} kept)", region_id, part_path);
+            set_region_status(&region_id, RegionStatus::interrupted("Paused")).await;
             let mut progress = DOWNLOAD_PROGRESS.write().await;
             if let Some(p) = progress.as_mut() {
-                p.bytes_downloaded = downloaded;
-                p.progress_percent = (downloaded as f64 / total_size as f64) * 100.0;
+                p.status = "Paused".to_string();
+                p.bytes_per_sec = 0.0;
+                p.eta_seconds = None;
             }
+            return Ok(());
         }
+        Err(DownloadFailure { error: e, attempts }) => {
+            std::fs::remove_file(&part_path).ok();
+            
+            let message = match e.kind {
+                DownloadErrorKind::Retryable => format!("Download failed after {} attempts: {}", attempts, e),
+                _ => format!("Download failed: {}", e),
+            };
+            warn!("{}", message);
+            
+            // A failed update leaves the previous data usable
+            let status = if file_path.exists() {
+                RegionStatus::Ready
+            } else {
+                RegionStatus::failed(message.clone())
+            };
+            set_region_status(&region_id, status).await;
+            
+            {
+                let mut progress = DOWNLOAD_PROGRESS.write().await;
+                if let Some(p) = progress.as_mut() {
+                    p.status = message.clone();
+                }
+            }
+            let _ = app.emit("region-download-failed", RegionDownloadFailed {
+                region_id: region_id.clone(),
+                kind: e.kind,
+                message: e.message.clone(),
+                attempts,
+            });
+            if let Some(db) = app.try_state::<LocalDatabase>() {
+                usage_metrics::count(&db, Counter::RegionDownloadsFailed, None).await;
+            }
+            
+            let details = serde_json::json!({ "kind": e.kind, "attempts": attempts });
+            return Err(CommandError { message, ..CommandError::from(e) }.with_details(details));
+        }
+    };
+    
+    set_region_status(&region_id, RegionStatus::Processing).await;
+    if let Err(e) = finalize_part_file(&part_path, &file_path) {
+        set_region_status(&region_id, RegionStatus::failed(e.message.clone())).await;
+        return Err(e);
     }
     
-    {
-        let mut progress = DOWNLOAD_PROGRESS.write().await;
-        if let Some(p) = progress.as_mut() {
-            p.bytes_downloaded = downloaded;
-            p.progress_percent = 100.0;
-            p.status = "Saving...".to_string();
+    info!("Download complete: {:?} ({} bytes from {})", file_path, outcome.bytes, outcome.source);
+    
+    let fingerprint = match stored_data(file_path.clone()).await {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            let e = CommandError::invalid_file(e.message).context("Downloaded map data can't be read");
+            set_region_status(&region_id, RegionStatus::failed(e.message.clone())).await;
+            {
+                let mut progress = DOWNLOAD_PROGRESS.write().await;
+                *progress = None;
+            }
+            return Err(e);
         }
-    }
+    };
     
-    info!("Download complete: {:?} ({} bytes)", file_path, downloaded);
+    // Remember which extract we have and where it came from so update checks can compare
+    let timestamp = outcome.source_timestamp.unwrap_or_else(chrono::Utc::now);
+    record_region_download(&region_id, timestamp, &outcome.source, fingerprint).await;
+    set_region_status(&region_id, RegionStatus::Ready).await;
+    if let Some(db) = app.try_state::<LocalDatabase>() {
+        usage_metrics::count(&db, Counter::RegionsDownloaded, None).await;
+        usage_metrics::observe(&db, Histogram::RegionDownloadMegabytes, outcome.bytes as f64 / (1024.0 * 1024.0)).await;
+    }
     
     // Clear progress
     {
@@ -391,22 +1078,904 @@ pub async fn download_map_region(region_id: String) -> Result<(), String> {
     Ok(())
 }
 
-/// Delete a downloaded map region
-#[tauri::command]
-pub async fn delete_map_region(region_id: String) -> Result<(), String> {
-    let data_dir = dirs::data_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join("com.geotruth.app")
-        .join("tiles");
-    
-    let file_path = data_dir.join(format!("{}.osm.pbf", region_id.replace("/", "_")));
+/// When to give up on a download source and move to the next one
+#[derive(Debug, Clone)]
+struct FailoverPolicy {
+    /// Attempts per source for retryable errors
+    max_attempts: u32,
+    /// Throughput below which a source counts as stalled (bytes/s)
+    min_bytes_per_sec: u64,
+    /// How long throughput must stay below the minimum before switching
+    slow_window: std::time::Duration,
+}
+
+impl Default for FailoverPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DOWNLOAD_MAX_ATTEMPTS,
+            min_bytes_per_sec: 50 * 1024,
+            slow_window: std::time::Duration::from_secs(30),
+        }
+    }
+}
+
+/// A finished download and where it came from
+struct DownloadOutcome {
+    bytes: u64,
+    source_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    source: String,
+}
+
+/// Final error after every source was tried
+struct DownloadFailure {
+    error: DownloadError,
+    attempts: u32,
+}
+
+/// Download a region from the first source that works
+///
+/// Each source gets `max_attempts` tries for retryable errors. Fatal errors,
+/// files that fail `validate`, and transfers stuck below the minimum
+/// throughput move on to the next source. The last source is never
+/// abandoned for being slow, since there is nothing left to switch to.
+async fn download_from_sources(
+    client: &reqwest::Client,
+    region_id: &str,
+    sources: &[mirrors::RegionSource],
+    part_path: &std::path::Path,
+    policy: &FailoverPolicy,
+    validate: impl Fn(&std::path::Path, u64) -> Result<(), DownloadError>,
+) -> Result<DownloadOutcome, DownloadFailure> {
+    let mut attempts = 0;
+    let mut failures = Vec::new();
+    let mut last_error = DownloadError::fatal(format!("No download sources for {}", region_id));
     
-    if file_path.exists() {
-        std::fs::remove_file(&file_path).map_err(|e| format!("Failed to delete: {}", e))?;
-        info!("Deleted map region: {}", region_id);
+    for (index, source) in sources.iter().enumerate() {
+        if index > 0 {
+            // Mirrors may carry a different extract version, so never splice files
+            std::fs::remove_file(part_path).ok();
+            info!("Switching download of {} to {}", region_id, source.name);
+            let mut progress = DOWNLOAD_PROGRESS.write().await;
+            if let Some(p) = progress.as_mut() {
+                p.status = format!("Trying mirror {}…", source.name);
+            }
+        }
+        
+        // A user rate limit below the threshold would look like a stalled server
+        let rate_limit = DOWNLOAD_RATE_LIMIT.load(Ordering::Relaxed);
+        let has_fallback = index + 1 < sources.len();
+        let min_throughput = (has_fallback && (rate_limit == 0 || rate_limit >= policy.min_bytes_per_sec))
+            .then_some((policy.min_bytes_per_sec, policy.slow_window));
+        
+        let mut attempt = 1;
+        let result = loop {
+            attempts += 1;
+            match fetch_region_attempt(client, region_id, &source.url, part_path, min_throughput).await {
+                Ok(done) => break validate(part_path, done.0).map(|_| done),
+                Err(e) if e.kind != DownloadErrorKind::Retryable || attempt == policy.max_attempts => break Err(e),
+                Err(e) => {
+                    let delay = backoff_with_jitter(attempt);
+                    attempt += 1;
+                    warn!("Download of {} from {} failed: {}; retrying in {:?}", region_id, source.name, e, delay);
+                    
+                    {
+                        let mut progress = DOWNLOAD_PROGRESS.write().await;
+                        if let Some(p) = progress.as_mut() {
+                            p.status = format!("Retrying (attempt {}/{})…", attempt, policy.max_attempts);
+                        }
+                    }
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        };
+        
+        match result {
+            Ok((bytes, source_timestamp)) => {
+                return Ok(DownloadOutcome { bytes, source_timestamp, source: source.name.clone() });
+            }
+            Err(e) if e.kind == DownloadErrorKind::Paused => {
+                return Err(DownloadFailure { error: e, attempts });
+            }
+            Err(e) => {
+                warn!("Download source {} failed for {}: {}", source.name, region_id, e);
+                failures.push(format!("{}: {}", source.name, e.message));
+                last_error = e;
+            }
+        }
     }
     
-    Ok(())
+    // Report every source's failure, keeping the last one's classification
+    if failures.len() > 1 {
+        last_error.message = format!("All sources failed ({})", failures.join("; "));
+    }
+    Err(DownloadFailure { error: last_error, attempts })
+}
+
+/// One download attempt, resuming from whatever is already in `part_path`
+///
+/// Returns the final file size and the source's Last-Modified timestamp.
+async fn fetch_region_attempt(
+    client: &reqwest::Client,
+    region_id: &str,
+    url: &str,
+    part_path: &std::path::Path,
+    min_throughput: Option<(u64, std::time::Duration)>,
+) -> Result<(u64, Option<chrono::DateTime<chrono::Utc>>), DownloadError> {
+    let offset = std::fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+    
+    let mut request = client.get(url);
+    if offset > 0 {
+        debug!("Resuming download at byte {}", offset);
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", offset));
+    }
+    
+    let response = request
+        .send()
+        .await
+        .map_err(|e| DownloadError::retryable(format!("Connection failed: {}", e)))?;
+    
+    let status = response.status();
+    let offset = match status {
+        reqwest::StatusCode::PARTIAL_CONTENT => offset,
+        // Server ignored the range, start over
+        s if s.is_success() => 0,
+        reqwest::StatusCode::RANGE_NOT_SATISFIABLE => {
+            std::fs::remove_file(part_path).ok();
+            return Err(DownloadError::retryable("Server rejected resume range"));
+        }
+        s => return Err(classify_status(s)),
+    };
+    
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.starts_with("text/html"))
+        .unwrap_or(false);
+    if is_html {
+        let body = response.text().await.unwrap_or_default();
+        return Err(DownloadError::fatal(format!(
+            "Server returned a web page instead of map data: {}",
+            html_excerpt(&body)
+        )));
+    }
+    
+    let total_size = response.content_length().map(|len| len + offset);
+    let source_timestamp = last_modified(&response);
+    
+    let downloaded = stream_to_part_file(response, part_path, offset, total_size, Some(region_id), min_throughput).await?;
+    
+    Ok((downloaded, source_timestamp))
+}
+
+/// Check that a finished download is a real OSM PBF of plausible size
+fn validate_region_file(
+    part_path: &std::path::Path,
+    downloaded: u64,
+    expected_size: u64,
+) -> Result<(), DownloadError> {
+    if let Err(e) = pbf::validate_pbf(part_path) {
+        let mut message = format!("Downloaded file is not valid map data: {}", e);
+        // An error page is small, a wrong file needn't be; only its start is read
+        let mut head = Vec::new();
+        let _ = std::fs::File::open(part_path).and_then(|file| file.take(4096).read_to_end(&mut head));
+        let head = String::from_utf8_lossy(&head);
+        if head.trim_start().starts_with('<') {
+            message.push_str(&format!(". Server said: {}", html_excerpt(&head)));
+        }
+        return Err(DownloadError::fatal(message));
+    }
+    
+    // Catalog sizes are rough, so only reject files that are wildly too small
+    if expected_size > 0 && downloaded < expected_size / 10 {
+        return Err(DownloadError::fatal(format!(
+            "Downloaded file is only {} KB, expected about {} MB",
+            downloaded / 1024,
+            expected_size / (1024 * 1024)
+        )));
+    }
+    
+    Ok(())
+}
+
+/// Visible text of an HTML page, shortened for error messages
+fn html_excerpt(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    text.chars().take(200).collect()
+}
+
+/// Classify an unsuccessful HTTP status
+fn classify_status(status: reqwest::StatusCode) -> DownloadError {
+    let message = format!("Server returned {}", status);
+    if status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || status == reqwest::StatusCode::REQUEST_TIMEOUT
+    {
+        DownloadError::retryable(message)
+    } else {
+        DownloadError::fatal(message)
+    }
+}
+
+/// Exponential backoff with up to 25% random jitter
+fn backoff_with_jitter(attempt: u32) -> std::time::Duration {
+    let base = DOWNLOAD_BASE_BACKOFF_MS << (attempt - 1).min(10);
+    // Cheap jitter source, no need for a real RNG here
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    let jitter = nanos % (base / 4 + 1);
+    std::time::Duration::from_millis(base + jitter)
+}
+
+/// Download map data for an arbitrary bounding box and register it as a region
+///
+/// The extract is stored like any catalog region under a synthetic
+/// `custom/<slug>` id, so it shows up in `get_map_regions` and can be
+/// re-downloaded or deleted the same way. It runs as a `download` job,
+/// which can't be cancelled.
+#[tauri::command]
+pub async fn download_custom_region(
+    app: AppHandle,
+    name: String,
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+) -> Result<RegionInfo, CommandError> {
+    let bbox = BoundingBox::new(min_lat, min_lon, max_lat, max_lon)?;
+
+    // The id is taken before the download, so another extract of the same
+    // name started meanwhile picks a different one
+    let region = {
+        let mut regions = MAP_REGIONS.write().await;
+        let region = RegionInfo {
+            id: unique_region_id(&regions, CUSTOM_REGION_PREFIX, &name),
+            name: name.trim().to_string(),
+            size_mb: 0,
+            downloaded: false,
+            last_updated: None,
+            poi_count: 0,
+            bounds: bbox.as_tuple(),
+            source: Some(OverpassProvider::default().name().to_string()),
+            checksum: None,
+            size_bytes: None,
+            data_modified_ms: None,
+            status: RegionStatus::Queued,
+        };
+        regions.push(region.clone());
+        region
+    };
+
+    info!("Starting custom extract: {} ({}, {:.0} km²)", region.name, region.id, bbox.area_km2());
+
+    let state = app.state::<Arc<AppState>>().inner().clone();
+    let extracted = &region;
+    let result = crate::jobs::run(&state, crate::jobs::emitter(&app), "download", None, |job| async move {
+        tokio::select! {
+            result = download_custom_extract(extracted) => result,
+            never = follow_download(&job, &extracted.id) => match never {},
+        }
+    })
+    .await;
+
+    let mut regions = MAP_REGIONS.write().await;
+    let fingerprint = match result {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            // Nothing was extracted, so there's no region to keep
+            regions.retain(|r| r.id != region.id);
+            save_regions_to_disk(&regions);
+            return Err(e);
+        }
+    };
+    // Recorded as ready, with its data, by the download
+    let region = regions
+        .iter_mut()
+        .find(|r| r.id == region.id)
+        .ok_or_else(|| CommandError::cancelled(format!("Region {} was removed while it downloaded", region.id)))?;
+    region.size_mb = fingerprint.bytes.div_ceil(1024 * 1024);
+    let region = region.clone();
+    save_regions_to_disk(&regions);
+
+    Ok(region)
+}
+
+/// Fetch a custom region's extract from its stored bounds, returning the
+/// fingerprint of the file written
+async fn download_custom_extract(region: &RegionInfo) -> Result<DataFingerprint, CommandError> {
+    let (min_lat, min_lon, max_lat, max_lon) = region.bounds;
+    let bbox = BoundingBox::new(min_lat, min_lon, max_lat, max_lon)?;
+    std::fs::create_dir_all(tiles_dir())?;
+
+    let _slot = resources::acquire(Resource::Network, Priority::Batch).await;
+    let _active = ActiveDownload::start(&region.id);
+    set_region_status(&region.id, RegionStatus::Downloading).await;
+    let file_path = region_file_path(&region.id);
+
+    {
+        let mut progress = DOWNLOAD_PROGRESS.write().await;
+        *progress = Some(DownloadProgress {
+            region_id: region.id.clone(),
+            bytes_downloaded: 0,
+            total_bytes: None,
+            progress_percent: None,
+            status: "Requesting extract...".to_string(),
+            bytes_per_sec: 0.0,
+            eta_seconds: None,
+        });
+    }
+
+    let provider = OverpassProvider::default();
+    let part_path = part_file_path(&file_path);
+    let result = match extracts::fetch_extract(&provider, &bbox).await {
+        Ok(response) => {
+            // Extract services generate on the fly, so size is usually unknown
+            let total_size = response.content_length();
+            match stream_to_part_file(response, &part_path, 0, total_size, None, None).await {
+                Ok(downloaded) => finalize_part_file(&part_path, &file_path).map(|_| downloaded),
+                Err(e) => {
+                    std::fs::remove_file(&part_path).ok();
+                    Err(e.into())
+                }
+            }
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    {
+        let mut progress = DOWNLOAD_PROGRESS.write().await;
+        *progress = None;
+    }
+
+    let downloaded = match result {
+        Ok(downloaded) => downloaded,
+        Err(e) => {
+            let status = if file_path.exists() {
+                RegionStatus::Ready
+            } else {
+                RegionStatus::failed(e.message.clone())
+            };
+            set_region_status(&region.id, status).await;
+            return Err(e);
+        }
+    };
+    set_region_status(&region.id, RegionStatus::Processing).await;
+    let fingerprint = match stored_data(file_path.clone()).await {
+        Ok(fingerprint) => fingerprint,
+        Err(e) => {
+            let e = CommandError::invalid_file(e.message).context("Extracted map data can't be read");
+            set_region_status(&region.id, RegionStatus::failed(e.message.clone())).await;
+            return Err(e);
+        }
+    };
+    record_region_download(&region.id, chrono::Utc::now(), provider.name(), fingerprint.clone()).await;
+    set_region_status(&region.id, RegionStatus::Ready).await;
+    info!("Custom extract complete: {:?} ({} bytes)", file_path, downloaded);
+    Ok(fingerprint)
+}
+
+/// Geofabrik download URL for a catalog region
+fn region_download_url(region_id: &str) -> Result<String, CommandError> {
+    mirrors::TemplateProvider::geofabrik()
+        .url(region_id)
+        .ok_or_else(|| CommandError::not_found(format!("Download logic not implemented for: {}", region_id)))
+}
+
+/// Parse the Last-Modified header of a response
+fn last_modified(response: &reqwest::Response) -> Option<chrono::DateTime<chrono::Utc>> {
+    let value = response.headers().get(reqwest::header::LAST_MODIFIED)?.to_str().ok()?;
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|dt| dt.with_timezone(&chrono::Utc))
+}
+
+/// Store the source timestamp, server and fingerprint of a region's new
+/// data and persist them
+async fn record_region_download(
+    region_id: &str,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    source: &str,
+    fingerprint: DataFingerprint,
+) {
+    let mut regions = MAP_REGIONS.write().await;
+    if let Some(region) = regions.iter_mut().find(|r| r.id == region_id) {
+        region.last_updated = Some(timestamp.to_rfc3339());
+        region.source = Some(source.to_string());
+        region_status::record_data(region, fingerprint);
+        save_regions_to_disk(&regions);
+    }
+}
+
+/// Update availability for a downloaded region
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegionUpdateStatus {
+    pub region_id: String,
+    pub name: String,
+    /// Timestamp of the extract we have on disk
+    pub local_timestamp: Option<String>,
+    /// Timestamp of the extract currently published upstream
+    pub remote_timestamp: Option<String>,
+    pub update_available: bool,
+    pub days_outdated: i64,
+}
+
+/// Payload of the `region-updates-available` event
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RegionUpdatesAvailable {
+    pub count: usize,
+    pub region_ids: Vec<String>,
+}
+
+/// Check downloaded regions against the published extracts
+///
+/// Sends a HEAD request per region and compares the upstream Last-Modified
+/// with the timestamp recorded at download time (falling back to the file's
+/// modification time). Emits `region-updates-available` with the outdated ids.
+#[tauri::command]
+pub async fn check_region_updates(app: AppHandle) -> Result<Vec<RegionUpdateStatus>, CommandError> {
+    let regions = MAP_REGIONS.read().await.clone();
+    let client = reqwest::Client::new();
+    let mut statuses = Vec::new();
+
+    for region in regions {
+        // Custom extracts and imported files have no published upstream file to compare against
+        if region.id.starts_with(CUSTOM_REGION_PREFIX) || region.id.starts_with(LOCAL_REGION_PREFIX) {
+            continue;
+        }
+
+        let file_path = region_file_path(&region.id);
+        if !file_path.exists() {
+            continue;
+        }
+
+        let Ok(url) = region_download_url(&region.id) else {
+            continue;
+        };
+
+        let local = region
+            .last_updated
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .map(|dt| dt.with_timezone(&chrono::Utc))
+            .or_else(|| {
+                std::fs::metadata(&file_path)
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(chrono::DateTime::<chrono::Utc>::from)
+            });
+
+        let remote = match client.head(&url).send().await {
+            Ok(response) if response.status().is_success() => last_modified(&response),
+            Ok(response) => {
+                warn!("Update check for {} returned {}", region.id, response.status());
+                None
+            }
+            Err(e) => {
+                warn!("Update check for {} failed: {}", region.id, e);
+                None
+            }
+        };
+
+        let (update_available, days_outdated) = match (local, remote) {
+            (Some(local), Some(remote)) if remote > local => (true, (remote - local).num_days()),
+            _ => (false, 0),
+        };
+        
+        // Leave regions that are mid-download alone
+        if region.status.has_data() {
+            let status = if update_available { RegionStatus::UpdateAvailable } else { RegionStatus::Ready };
+            set_region_status(&region.id, status).await;
+        }
+
+        statuses.push(RegionUpdateStatus {
+            region_id: region.id,
+            name: region.name,
+            local_timestamp: local.map(|t| t.to_rfc3339()),
+            remote_timestamp: remote.map(|t| t.to_rfc3339()),
+            update_available,
+            days_outdated,
+        });
+    }
+
+    let region_ids: Vec<String> = statuses
+        .iter()
+        .filter(|s| s.update_available)
+        .map(|s| s.region_id.clone())
+        .collect();
+
+    info!("Region update check: {} of {} outdated", region_ids.len(), statuses.len());
+
+    let _ = app.emit("region-updates-available", RegionUpdatesAvailable {
+        count: region_ids.len(),
+        region_ids,
+    });
+
+    Ok(statuses)
+}
+
+/// Re-download a region and replace its data once the new copy is complete
+///
+/// The existing file stays in place until the download finishes, so a failed
+/// update leaves the previous data usable. Emits `region-updated` on success.
+#[tauri::command]
+pub async fn update_region(app: AppHandle, region_id: String) -> Result<RegionInfo, CommandError> {
+    info!("Updating region: {}", region_id);
+
+    download_map_region(app.clone(), region_id.clone()).await?;
+
+    let region = get_map_regions()
+        .await
+        .into_iter()
+        .find(|r| r.id == region_id)
+        .ok_or_else(|| CommandError::not_found(format!("Region not found: {}", region_id)))?;
+
+    let _ = app.emit("region-updated", region.clone());
+
+    Ok(region)
+}
+
+/// Stream a response body into a partial file, updating the global download progress
+///
+/// Appends when `offset` is non-zero and honours the global rate limit. When
+/// `pause_id` is given, the transfer stops if that region gets paused. With
+/// `min_throughput` set to (bytes/s, window), the transfer is abandoned once
+/// the rate stays below that minimum for the whole window.
+/// Returns the total size of the partial file.
+async fn stream_to_part_file(
+    response: reqwest::Response,
+    part_path: &std::path::Path,
+    offset: u64,
+    total_size: Option<u64>,
+    pause_id: Option<&str>,
+    min_throughput: Option<(u64, std::time::Duration)>,
+) -> Result<u64, DownloadError> {
+    use futures_util::StreamExt;
+
+    {
+        let mut progress = DOWNLOAD_PROGRESS.write().await;
+        if let Some(p) = progress.as_mut() {
+            p.bytes_downloaded = offset;
+            p.total_bytes = total_size;
+            p.progress_percent = progress_percent(offset, total_size);
+            p.status = "Downloading...".to_string();
+        }
+    }
+    
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(offset > 0)
+        .truncate(offset == 0)
+        .open(part_path)
+        .map_err(|e| DownloadError::fatal(format!("Failed to create file: {}", e)))?;
+    let mut downloaded: u64 = offset;
+    let mut stream = response.bytes_stream();
+    let mut bucket = TokenBucket::new();
+    let mut meter = TransferMeter::new();
+    let mut slow_since: Option<std::time::Instant> = None;
+    
+    while let Some(item) = stream.next().await {
+        if let Some(id) = pause_id {
+            if PAUSED_DOWNLOADS.read().await.contains(id) {
+                return Err(DownloadError::paused());
+            }
+        }
+        
+        let chunk = item.map_err(|e| DownloadError::retryable(format!("Error while downloading: {}", e)))?;
+        // Local write failures (disk full, permissions) won't fix themselves
+        std::io::Write::write_all(&mut file, &chunk)
+            .map_err(|e| DownloadError::fatal(format!("Error while writing to file: {}", e)))?;
+        downloaded += chunk.len() as u64;
+        meter.record(chunk.len() as u64);
+        
+        if let Some((min_rate, window)) = min_throughput {
+            let rate = meter.rate();
+            if rate > 0.0 && rate < min_rate as f64 {
+                let since = *slow_since.get_or_insert_with(std::time::Instant::now);
+                if since.elapsed() >= window {
+                    return Err(DownloadError::stalled(format!(
+                        "Transfer stayed below {} KB/s for {}s",
+                        min_rate / 1024,
+                        window.as_secs()
+                    )));
+                }
+            } else {
+                slow_since = None;
+            }
+        }
+        
+        {
+            let mut progress = DOWNLOAD_PROGRESS.write().await;
+            if let Some(p) = progress.as_mut() {
+                p.bytes_downloaded = downloaded;
+                p.bytes_per_sec = meter.rate();
+                p.progress_percent = progress_percent(downloaded, total_size);
+                p.eta_seconds = total_size.and_then(|total| meter.eta(total.saturating_sub(downloaded)));
+            }
+        }
+        
+        bucket.throttle(chunk.len() as u64).await;
+    }
+    
+    {
+        let mut progress = DOWNLOAD_PROGRESS.write().await;
+        if let Some(p) = progress.as_mut() {
+            p.bytes_downloaded = downloaded;
+            p.total_bytes = Some(downloaded);
+            p.progress_percent = Some(100.0);
+            p.eta_seconds = None;
+            p.status = "Saving...".to_string();
+        }
+    }
+
+    Ok(downloaded)
+}
+
+/// Token bucket enforcing `DOWNLOAD_RATE_LIMIT`, read on every chunk so
+/// changes apply to downloads already in progress
+struct TokenBucket {
+    tokens: f64,
+    last_refill: std::time::Instant,
+}
+
+impl TokenBucket {
+    fn new() -> Self {
+        Self { tokens: 0.0, last_refill: std::time::Instant::now() }
+    }
+
+    /// Consume `bytes` tokens, sleeping if the bucket runs dry
+    async fn throttle(&mut self, bytes: u64) {
+        let now = std::time::Instant::now();
+        let rate = DOWNLOAD_RATE_LIMIT.load(Ordering::Relaxed) as f64;
+        if rate <= 0.0 {
+            self.tokens = 0.0;
+            self.last_refill = now;
+            return;
+        }
+
+        // Allow bursts of up to one second worth of data
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.last_refill = now;
+        self.tokens -= bytes as f64;
+
+        if self.tokens < 0.0 {
+            tokio::time::sleep(std::time::Duration::from_secs_f64(-self.tokens / rate)).await;
+        }
+    }
+}
+
+/// Percentage done, `None` while the total is unknown
+///
+/// Capped at 100 in case the server under-reported the size.
+fn progress_percent(downloaded: u64, total_size: Option<u64>) -> Option<f64> {
+    total_size
+        .filter(|total| *total > 0)
+        .map(|total| (downloaded as f64 / total as f64 * 100.0).min(100.0))
+}
+
+/// Span of recent chunks the transfer rate is averaged over
+const SPEED_WINDOW: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Transfer rate over a sliding window of recent chunks
+struct TransferMeter {
+    started: std::time::Instant,
+    samples: std::collections::VecDeque<(std::time::Instant, u64)>,
+    window_bytes: u64,
+}
+
+impl TransferMeter {
+    fn new() -> Self {
+        Self {
+            started: std::time::Instant::now(),
+            samples: std::collections::VecDeque::new(),
+            window_bytes: 0,
+        }
+    }
+
+    fn record(&mut self, bytes: u64) {
+        self.record_at(std::time::Instant::now(), bytes);
+    }
+
+    fn record_at(&mut self, now: std::time::Instant, bytes: u64) {
+        self.samples.push_back((now, bytes));
+        self.window_bytes += bytes;
+        while let Some(&(at, old)) = self.samples.front() {
+            if now.duration_since(at) <= SPEED_WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+            self.window_bytes -= old;
+        }
+    }
+
+    /// Bytes/s over the window, 0 until a second of data has been seen
+    fn rate(&self) -> f64 {
+        let Some(&(last, _)) = self.samples.back() else {
+            return 0.0;
+        };
+        // Early on the window is only as long as the transfer so far
+        let span = last.duration_since(self.started).min(SPEED_WINDOW).as_secs_f64();
+        if span < 1.0 {
+            return 0.0;
+        }
+        self.window_bytes as f64 / span
+    }
+
+    fn eta(&self, remaining_bytes: u64) -> Option<u64> {
+        let rate = self.rate();
+        (rate > 0.0).then(|| (remaining_bytes as f64 / rate).ceil() as u64)
+    }
+}
+
+/// Swap a completed partial file into place, so existing data is never
+/// replaced by a partial download
+fn finalize_part_file(part_path: &std::path::Path, file_path: &std::path::Path) -> Result<(), CommandError> {
+    std::fs::rename(part_path, file_path).map_err(|e| CommandError::from(e).context("Failed to finalize download"))
+}
+
+/// Temporary path used while a download is in flight
+fn part_file_path(file_path: &std::path::Path) -> std::path::PathBuf {
+    let mut name = file_path.as_os_str().to_os_string();
+    name.push(".part");
+    std::path::PathBuf::from(name)
+}
+
+/// Cap download speed for region downloads (0 = unlimited)
+#[tauri::command]
+pub async fn set_download_rate_limit(bytes_per_sec: u64) -> Result<(), CommandError> {
+    DOWNLOAD_RATE_LIMIT.store(bytes_per_sec, Ordering::Relaxed);
+    crate::settings::update(|s| s.download_rate_limit = bytes_per_sec);
+    info!("Download rate limit set to {} bytes/sec", bytes_per_sec);
+    Ok(())
+}
+
+/// Pause an active region download, keeping the partial file
+#[tauri::command]
+pub async fn pause_region_download(region_id: String) -> Result<(), CommandError> {
+    let active = DOWNLOAD_PROGRESS
+        .read()
+        .await
+        .as_ref()
+        .map(|p| p.region_id == region_id)
+        .unwrap_or(false);
+    
+    if !active {
+        return Err(CommandError::not_found(format!("No active download for region: {}", region_id)));
+    }
+    if region_id.starts_with(CUSTOM_REGION_PREFIX) {
+        return Err(CommandError::invalid_input("Custom region extracts can't be paused"));
+    }
+    
+    PAUSED_DOWNLOADS.write().await.insert(region_id.clone());
+    info!("Pausing download: {}", region_id);
+    Ok(())
+}
+
+/// Resume a paused region download from where it stopped
+#[tauri::command]
+pub async fn resume_region_download(app: AppHandle, region_id: String) -> Result<(), CommandError> {
+    info!("Resuming download: {}", region_id);
+    // download_map_region picks up the partial file via a Range request
+    download_map_region(app, region_id).await
+}
+
+/// Delete a downloaded map region, keeping it in my map packs
+#[tauri::command]
+pub async fn delete_map_region(
+    geo: State<'_, Arc<GeoEngine>>,
+    region_id: String,
+) -> Result<(), CommandError> {
+    let dir = tiles_dir();
+    
+    delete_region_files_in(&dir, &region_id)?;
+    geo.unload_region(region_pmtiles_path_in(&dir, &region_id)).await;
+    {
+        let mut regions = MAP_REGIONS.write().await;
+        if let Some(region) = regions.iter_mut().find(|r| r.id == region_id) {
+            region_status::clear_data(region);
+            save_regions_to_disk(&regions);
+        }
+    }
+    set_region_status(&region_id, RegionStatus::NotDownloaded).await;
+    info!("Deleted map region: {}", region_id);
+    
+    Ok(())
+}
+
+/// Remove a region from my map packs
+///
+/// With `delete_data` the downloaded extract, any partial download and the
+/// region's PMTiles are deleted too, and the tiles are unloaded from the
+/// `GeoEngine`. Without it the files stay on disk so the region can be
+/// re-added later without downloading again.
+#[tauri::command]
+pub async fn remove_region(
+    geo: State<'_, Arc<GeoEngine>>,
+    region_id: String,
+    delete_data: bool,
+) -> Result<(), CommandError> {
+    let dir = tiles_dir();
+    
+    {
+        let mut regions = MAP_REGIONS.write().await;
+        remove_region_in(&mut regions, &dir, &region_id, delete_data)?;
+        save_regions_to_disk(&regions);
+    }
+    
+    if delete_data {
+        geo.unload_region(region_pmtiles_path_in(&dir, &region_id)).await;
+    }
+    
+    info!("Removed region {} (delete_data: {})", region_id, delete_data);
+    Ok(())
+}
+
+/// Remove a region entry and, optionally, its files from `dir`
+fn remove_region_in(
+    regions: &mut Vec<RegionInfo>,
+    dir: &std::path::Path,
+    region_id: &str,
+    delete_data: bool,
+) -> Result<RegionInfo, CommandError> {
+    let index = regions
+        .iter()
+        .position(|r| r.id == region_id)
+        .ok_or_else(|| CommandError::not_found(format!("Region not found: {}", region_id)))?;
+    
+    if delete_data {
+        delete_region_files_in(dir, region_id)?;
+    }
+    
+    Ok(regions.remove(index))
+}
+
+/// Delete a region's extract, partial download and PMTiles from `dir`
+fn delete_region_files_in(dir: &std::path::Path, region_id: &str) -> Result<(), CommandError> {
+    let data_path = region_file_path_in(dir, region_id);
+    let paths = [
+        part_file_path(&data_path),
+        region_pmtiles_path_in(dir, region_id),
+        data_path,
+    ];
+    
+    for path in paths.iter().filter(|p| p.exists()) {
+        std::fs::remove_file(path).map_err(|e| CommandError::from(e).context("Failed to delete"))?;
+        debug!("Deleted {:?}", path);
+    }
+    
+    Ok(())
+}
+
+/// Stream a PMTiles archive from an HTTP(S) or S3 URL instead of downloading it
+///
+/// Tiles are fetched on demand with range requests, so lookups need a
+/// connection. Not available in offline mode.
+#[tauri::command]
+pub async fn load_remote_region(geo: State<'_, Arc<GeoEngine>>, url: String) -> Result<(), CommandError> {
+    Ok(geo.load_region_url(&url).await?)
+}
+
+/// Stop using a remote PMTiles archive
+#[tauri::command]
+pub async fn unload_remote_region(geo: State<'_, Arc<GeoEngine>>, url: String) -> Result<bool, CommandError> {
+    Ok(geo.unload_region_url(&url).await)
 }
 
 /// Get current download progress
@@ -414,3 +1983,336 @@ pub async fn delete_map_region(region_id: String) -> Result<(), String> {
 pub async fn get_download_progress() -> Option<DownloadProgress> {
     DOWNLOAD_PROGRESS.read().await.clone()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn regions() -> Vec<RegionInfo> {
+        AVAILABLE_REGIONS
+            .iter()
+            .filter(|r| r.id == "europe/monaco" || r.id == "us/california")
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_catalog_page_filters_and_pages() {
+        let all = catalog_page(&AVAILABLE_REGIONS, None, None, None, None);
+        assert_eq!(all.total, AVAILABLE_REGIONS.len());
+        assert!(all.regions.len() <= DEFAULT_CATALOG_PAGE);
+
+        let europe = catalog_page(&AVAILABLE_REGIONS, Some("Europe/"), None, None, None);
+        let ids: Vec<&str> = europe.regions.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(ids, ["europe/monaco", "europe/france", "europe/germany"]);
+
+        // By parent name, searched and paged
+        let page = catalog_page(&AVAILABLE_REGIONS, Some("north-america"), Some("NEW"), Some(1), Some(2));
+        let ids: Vec<&str> = page.regions.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(page.total, 4);
+        assert_eq!(ids, ["us/new-jersey", "us/new-mexico"]);
+
+        // A partial segment isn't an area
+        assert_eq!(catalog_page(&AVAILABLE_REGIONS, Some("eur"), None, None, None).total, 0);
+    }
+
+    #[test]
+    fn test_remove_region_deletes_data() {
+        let dir = fixtures::temp_dir("regions");
+        let data = region_file_path_in(&dir, "europe/monaco");
+        let tiles = region_pmtiles_path_in(&dir, "europe/monaco");
+        std::fs::write(&data, b"pbf").unwrap();
+        std::fs::write(&tiles, b"pmtiles").unwrap();
+
+        let mut list = regions();
+        let removed = remove_region_in(&mut list, &dir, "europe/monaco", true).unwrap();
+
+        assert_eq!(removed.id, "europe/monaco");
+        assert!(list.iter().all(|r| r.id != "europe/monaco"));
+        assert_eq!(list.len(), 1);
+        assert!(!data.exists());
+        assert!(!tiles.exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_remove_region_keeps_data() {
+        let dir = fixtures::temp_dir("regions");
+        let data = region_file_path_in(&dir, "us/california");
+        std::fs::write(&data, b"pbf").unwrap();
+
+        let mut list = regions();
+        remove_region_in(&mut list, &dir, "us/california", false).unwrap();
+
+        assert!(list.iter().all(|r| r.id != "us/california"));
+        assert!(data.exists());
+
+        // Never-downloaded entries can be removed too
+        remove_region_in(&mut list, &dir, "europe/monaco", true).unwrap();
+        assert!(list.is_empty());
+        let err = remove_region_in(&mut list, &dir, "europe/monaco", false).unwrap_err();
+        assert_eq!(err.code, ErrorCode::NotFound);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    /// Canned response served by the mock HTTP server
+    #[derive(Clone)]
+    struct MockResponse {
+        status: u16,
+        body: &'static [u8],
+        /// Delay between body bytes, to simulate a stalled server
+        byte_delay: Option<std::time::Duration>,
+        /// Whether to send Content-Length; without it the body ends at close
+        content_length: bool,
+    }
+
+    impl MockResponse {
+        fn ok(body: &'static [u8]) -> Self {
+            Self { status: 200, body, byte_delay: None, content_length: true }
+        }
+
+        fn status(status: u16) -> Self {
+            Self { status, body: b"", byte_delay: None, content_length: true }
+        }
+    }
+
+    /// Serve `response` to every request on a local port, returning the base URL
+    async fn mock_server(response: MockResponse) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let response = response.clone();
+                tokio::spawn(async move {
+                    let mut request = [0u8; 4096];
+                    let _ = socket.read(&mut request).await;
+
+                    let length = if response.content_length {
+                        format!("Content-Length: {}\r\n", response.body.len())
+                    } else {
+                        String::new()
+                    };
+                    let head = format!(
+                        "HTTP/1.1 {} Mock\r\n{}Content-Type: application/octet-stream\r\nConnection: close\r\n\r\n",
+                        response.status,
+                        length
+                    );
+                    let _ = socket.write_all(head.as_bytes()).await;
+
+                    match response.byte_delay {
+                        Some(delay) => {
+                            for byte in response.body {
+                                if socket.write_all(&[*byte]).await.is_err() {
+                                    return;
+                                }
+                                tokio::time::sleep(delay).await;
+                            }
+                        }
+                        None => {
+                            let _ = socket.write_all(response.body).await;
+                        }
+                    }
+                });
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    fn source(name: &str, base_url: &str) -> mirrors::RegionSource {
+        mirrors::RegionSource {
+            name: name.to_string(),
+            url: format!("{}/region.osm.pbf", base_url),
+        }
+    }
+
+    fn test_policy() -> FailoverPolicy {
+        FailoverPolicy {
+            max_attempts: 1,
+            min_bytes_per_sec: 1024 * 1024,
+            slow_window: std::time::Duration::ZERO,
+        }
+    }
+
+    fn accept_all(_: &std::path::Path, _: u64) -> Result<(), DownloadError> {
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_primary_error() {
+        let primary = mock_server(MockResponse::status(404)).await;
+        let mirror = mock_server(MockResponse::ok(b"mirror data")).await;
+        let sources = vec![source("primary", &primary), source("mirror", &mirror)];
+
+        let dir = fixtures::temp_dir("regions");
+        let part = dir.join("region.osm.pbf.part");
+        let client = reqwest::Client::new();
+
+        let outcome = download_from_sources(&client, "test/failover", &sources, &part, &test_policy(), accept_all)
+            .await
+            .unwrap_or_else(|f| panic!("download failed: {}", f.error));
+
+        assert_eq!(outcome.source, "mirror");
+        assert_eq!(outcome.bytes, 11);
+        assert_eq!(std::fs::read(&part).unwrap(), b"mirror data");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_invalid_file() {
+        let primary = mock_server(MockResponse::ok(b"<html>maintenance</html>")).await;
+        let mirror = mock_server(MockResponse::ok(b"PBF")).await;
+        let sources = vec![source("primary", &primary), source("mirror", &mirror)];
+
+        let dir = fixtures::temp_dir("regions");
+        let part = dir.join("region.osm.pbf.part");
+        let client = reqwest::Client::new();
+
+        let validate = |path: &std::path::Path, _: u64| {
+            if std::fs::read(path).unwrap_or_default() == b"PBF" {
+                Ok(())
+            } else {
+                Err(DownloadError::fatal("not map data"))
+            }
+        };
+
+        let outcome = download_from_sources(&client, "test/invalid", &sources, &part, &test_policy(), validate)
+            .await
+            .unwrap_or_else(|f| panic!("download failed: {}", f.error));
+
+        assert_eq!(outcome.source, "mirror");
+        assert_eq!(std::fs::read(&part).unwrap(), b"PBF");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_invalid_download_quotes_the_server() {
+        let dir = fixtures::temp_dir("regions");
+        let part = dir.join("region.osm.pbf.part");
+        let mut page = b"<html><body><h1>Rate limit exceeded</h1></body></html>".to_vec();
+        page.resize(64 * 1024, b' ');
+        std::fs::write(&part, &page).unwrap();
+
+        let error = validate_region_file(&part, page.len() as u64, 0).unwrap_err();
+        assert_eq!(error.kind, DownloadErrorKind::Fatal);
+        assert!(error.message.ends_with("Server said: Rate limit exceeded"), "{}", error.message);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failover_on_slow_source() {
+        let slow = MockResponse {
+            status: 200,
+            body: b"trickling along",
+            byte_delay: Some(std::time::Duration::from_millis(200)),
+            content_length: true,
+        };
+        let primary = mock_server(slow).await;
+        let mirror = mock_server(MockResponse::ok(b"fast")).await;
+        let sources = vec![source("primary", &primary), source("mirror", &mirror)];
+
+        let dir = fixtures::temp_dir("regions");
+        let part = dir.join("region.osm.pbf.part");
+        let client = reqwest::Client::new();
+
+        let outcome = download_from_sources(&client, "test/slow", &sources, &part, &test_policy(), accept_all)
+            .await
+            .unwrap_or_else(|f| panic!("download failed: {}", f.error));
+
+        assert_eq!(outcome.source, "mirror");
+        assert_eq!(std::fs::read(&part).unwrap(), b"fast");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_all_sources_failing() {
+        let primary = mock_server(MockResponse::status(404)).await;
+        let mirror = mock_server(MockResponse::status(503)).await;
+        let sources = vec![source("primary", &primary), source("mirror", &mirror)];
+
+        let dir = fixtures::temp_dir("regions");
+        let part = dir.join("region.osm.pbf.part");
+        let client = reqwest::Client::new();
+
+        let failure = match download_from_sources(&client, "test/down", &sources, &part, &test_policy(), accept_all).await {
+            Ok(outcome) => panic!("unexpected success from {}", outcome.source),
+            Err(failure) => failure,
+        };
+
+        assert_eq!(failure.attempts, 2);
+        assert_eq!(failure.error.kind, DownloadErrorKind::Retryable);
+        assert!(failure.error.message.contains("primary: Server returned 404"));
+        assert!(failure.error.message.contains("mirror: Server returned 503"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_download_without_content_length() {
+        let server = mock_server(MockResponse { content_length: false, ..MockResponse::ok(b"no length here") }).await;
+        let sources = vec![source("primary", &server)];
+
+        let dir = fixtures::temp_dir("regions");
+        let part = dir.join("region.osm.pbf.part");
+        let client = reqwest::Client::new();
+
+        let outcome = download_from_sources(&client, "test/no-length", &sources, &part, &test_policy(), accept_all)
+            .await
+            .unwrap_or_else(|f| panic!("download failed: {}", f.error));
+        assert_eq!(outcome.bytes, 14);
+
+        // The frontend shows an indeterminate bar for null fields
+        let progress = DownloadProgress {
+            region_id: "test/no-length".to_string(),
+            bytes_downloaded: 7,
+            total_bytes: None,
+            progress_percent: progress_percent(7, None),
+            status: "Downloading...".to_string(),
+            bytes_per_sec: 0.0,
+            eta_seconds: None,
+        };
+        let json = serde_json::to_value(&progress).unwrap();
+        assert!(json["total_bytes"].is_null());
+        assert!(json["progress_percent"].is_null());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_progress_percent() {
+        assert_eq!(progress_percent(50, Some(200)), Some(25.0));
+        assert_eq!(progress_percent(50, None), None);
+        assert_eq!(progress_percent(50, Some(0)), None);
+        // Servers that under-report the size don't push past 100%
+        assert_eq!(progress_percent(300, Some(200)), Some(100.0));
+    }
+
+    #[test]
+    fn test_transfer_meter_sliding_window() {
+        let mut meter = TransferMeter::new();
+        let start = meter.started;
+        let at = |secs: u64| start + std::time::Duration::from_secs(secs);
+
+        assert_eq!(meter.rate(), 0.0);
+        meter.record_at(at(1), 1000);
+        meter.record_at(at(2), 1000);
+        assert_eq!(meter.rate(), 1000.0);
+        assert_eq!(meter.eta(5000), Some(5));
+
+        // A burst long ago drops out of the window
+        for secs in 3..=10 {
+            meter.record_at(at(secs), 100);
+        }
+        assert_eq!(meter.rate(), 600.0 / 5.0);
+    }
+}
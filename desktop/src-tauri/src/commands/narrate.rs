@@ -1,11 +1,511 @@
-use crate::narrative::NarrativeEngine;
-use crate::types::{NarrateRequest, NarrateResponse};
-use tauri::State;
+use crate::error::CommandError;
+use crate::freshness::{self, BundleFingerprint, Freshness};
+use crate::jobs::{self, JobContext};
+use crate::llm::{self, Sampling};
+use crate::narration_variants::{self, NarrationComparison, VariantSummary};
+use crate::narrative::{NarrationProgress, NarrativeEngine};
+use crate::export::script::{self, ScriptFormat};
+use crate::services::database::Narration;
+use crate::services::LocalDatabase;
+use crate::settings;
+use crate::state::{AppState, JobStatus};
+use crate::export::subtitles::{self, SubtitleFormat};
+use crate::types::{NarrateRequest, NarrateResponse, NarrationOptions, NarrationRevision, SpeechInterval, TruthBundle};
+use crate::usage_metrics::{self, Counter, Histogram};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::{info, warn};
+use uuid::Uuid;
 
+/// A narration, with a summary of each variant generated alongside it
+#[derive(Debug, Clone, Serialize)]
+pub struct NarrateOutcome {
+    /// The first variant that succeeded, in full
+    #[serde(flatten)]
+    pub response: NarrateResponse,
+    /// Its id in the history, unless it couldn't be saved
+    pub narration_id: Option<String>,
+    /// Tags the stored variants when there's more than one
+    pub generation_group: Option<String>,
+    pub variants: Vec<VariantSummary>,
+}
+
+/// A narration chunk's chapters and lines, emitted as `narrate-progress`
+/// as soon as it's written; the job's result has the whole narration
+#[derive(Debug, Clone, Serialize)]
+pub struct NarrateProgress {
+    pub job_id: String,
+    #[serde(flatten)]
+    pub progress: NarrationProgress,
+}
+
+/// Finished narration jobs' results, until they're retrieved
+#[derive(Default)]
+pub struct NarrationResults(DashMap<String, NarrateOutcome>);
+
+/// Narrate a Truth Bundle, as several variants to choose from if the
+/// options ask for them (see [`narration_variants::requested`])
+///
+/// Waits for the narration; long videos are better narrated with
+/// [`start_narration`], which can be followed and cancelled.
 #[tauri::command]
 pub async fn narrate(
     request: NarrateRequest,
-    engine: State<'_, NarrativeEngine>,
-) -> Result<NarrateResponse, String> {
-    engine.generate_narration(request).await.map_err(|e| e.to_string())
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, NarrationResults>,
+    app: AppHandle,
+) -> Result<NarrateOutcome, CommandError> {
+    narrate_and_wait(request, &state, &results, app).await
+}
+
+async fn narrate_and_wait(
+    request: NarrateRequest,
+    state: &Arc<AppState>,
+    results: &NarrationResults,
+    app: AppHandle,
+) -> Result<NarrateOutcome, CommandError> {
+    let (job_id, task) = start_job(request, state, app)?;
+    if task.await.is_err() {
+        return Err(CommandError::cancelled("Narration was cancelled"));
+    }
+    match results.0.remove(&job_id) {
+        Some((_, outcome)) => Ok(outcome),
+        None => Err(job_error(state, &job_id)),
+    }
+}
+
+/// Start narrating in the background, returning the job's id
+///
+/// Progress is reported with `job-status` events as chunks complete; the
+/// job can be stopped with `cancel_job` and its narration fetched with
+/// [`get_narration_result`] once it's done.
+#[tauri::command]
+pub async fn start_narration(
+    request: NarrateRequest,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<String, CommandError> {
+    let (job_id, _) = start_job(request, &state, app)?;
+    Ok(job_id)
+}
+
+/// The narration of a finished job, or `None` while it's still running
+///
+/// A result can be retrieved once; a failed or cancelled job returns why.
+#[tauri::command]
+pub async fn get_narration_result(
+    job_id: String,
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, NarrationResults>,
+) -> Result<Option<NarrateOutcome>, CommandError> {
+    let status = state
+        .active_jobs
+        .get(&job_id)
+        .map(|job| job.status.clone())
+        .ok_or_else(|| CommandError::not_found(format!("Job not found: {}", job_id)))?;
+    match status {
+        JobStatus::Completed => results
+            .0
+            .remove(&job_id)
+            .map(|(_, outcome)| Some(outcome))
+            .ok_or_else(|| CommandError::not_found(format!("The narration of job {} was already retrieved", job_id))),
+        JobStatus::Pending | JobStatus::Processing { .. } => Ok(None),
+        _ => {
+            // Cancelled just as it finished
+            results.0.remove(&job_id);
+            Err(job_error(&state, &job_id))
+        }
+    }
+}
+
+/// Why a narration job didn't produce a narration
+fn job_error(state: &AppState, job_id: &str) -> CommandError {
+    match state.active_jobs.get(job_id).map(|job| job.status.clone()) {
+        Some(JobStatus::Failed { error, code }) => CommandError::new(code, error),
+        Some(JobStatus::Cancelled) => CommandError::cancelled("Narration was cancelled"),
+        _ => format!("Narration job {} has no result", job_id).into(),
+    }
+}
+
+/// Check the request's options and start narrating it as a job
+fn start_job(
+    request: NarrateRequest,
+    state: &Arc<AppState>,
+    app: AppHandle,
+) -> Result<(String, tokio::task::JoinHandle<()>), CommandError> {
+    let mut options = NarrationOptions::from_options(&request.options)
+        .map_err(|e| CommandError::invalid_input(format!("Invalid narration options: {}", e)))?;
+    let temperatures = narration_variants::requested(&request.options)
+        .map_err(|e| CommandError::invalid_input(format!("Invalid narration options: {}", e)))?;
+
+    // What the request leaves unset comes from the creativity setting, and
+    // there's always a seed, so the narration can be written again the same way
+    let creativity = settings::get().narration_creativity.map(Sampling::from_creativity);
+    options.sampling = options.sampling.or(creativity.unwrap_or_default());
+    options.sampling.seed.get_or_insert_with(llm::random_seed);
+    let sampling = narration_variants::sampling(options.sampling, &temperatures);
+
+    Ok(jobs::start(state, jobs::emitter(&app), "narration", move |job| async move {
+        let outcome = run_narration(request, options, sampling, &app, &job).await?;
+        app.state::<NarrationResults>().0.insert(job.job_id().to_string(), outcome);
+        Ok(())
+    }))
+}
+
+async fn run_narration(
+    mut request: NarrateRequest,
+    options: NarrationOptions,
+    sampling: Vec<Sampling>,
+    app: &AppHandle,
+    job: &JobContext,
+) -> Result<NarrateOutcome, CommandError> {
+    let engine = app.state::<NarrativeEngine>();
+    let db = app.state::<LocalDatabase>();
+    let project_id = request.truth_bundle.project_id.map(|id| id.to_string());
+    let video_id = request.truth_bundle.video_id.map(|id| id.to_string());
+    // And what it was written from, to tell when that changes
+    let fingerprint_json = serde_json::to_string(&BundleFingerprint::of(&request.truth_bundle)).ok();
+
+    // The stored transcript marks where the creator talks, unless the request says
+    if options.respect_dialogue && request.speech.is_empty() {
+        if let Some(video_id) = &video_id {
+            match db.get_transcription(video_id).await {
+                Ok(segments) => {
+                    request.speech = segments
+                        .iter()
+                        .map(|s| SpeechInterval {
+                            start_seconds: s.start_ms as f64 / 1000.0,
+                            end_seconds: s.end_ms as f64 / 1000.0,
+                        })
+                        .collect();
+                }
+                Err(e) => warn!("Failed to load the transcript of video {}: {}", video_id, e),
+            }
+        }
+    }
+
+    // Long videos are narrated a chunk at a time; each one finished is
+    // shown right away, rather than after the whole narration
+    let on_progress = |progress: NarrationProgress| {
+        let done = progress.chunk - usize::from(!progress.finished);
+        job.progress(done as f32 / progress.chunk_count.max(1) as f32, progress.message.clone());
+        if progress.finished {
+            let _ = app.emit("narrate-progress", NarrateProgress { job_id: job.job_id().to_string(), progress });
+        }
+    };
+    let started = Instant::now();
+    let results = engine.generate_variants(request, &sampling, &on_progress).await;
+    usage_metrics::observe(&db, Histogram::NarrationSeconds, started.elapsed().as_secs_f64()).await;
+
+    // Any variant that worked is worth keeping, even if others failed
+    let generation_group = (results.len() > 1).then(|| Uuid::new_v4().to_string());
+    let (mut first, mut first_error) = (None, None);
+    let mut variants = Vec::with_capacity(results.len());
+    for (i, (result, sampling)) in results.into_iter().zip(sampling).enumerate() {
+        let temperature = sampling.temperature;
+        match result {
+            Ok(response) => {
+                // Kept with the narration, sampling and all, so it can be regenerated the same way
+                let options_json = serde_json::to_string(&NarrationOptions { sampling, ..options.clone() }).ok();
+                let narration_id = record_narration(
+                    &db,
+                    project_id.as_deref(),
+                    video_id.as_deref(),
+                    &response,
+                    options_json.as_deref(),
+                    generation_group.as_deref(),
+                    fingerprint_json.as_deref(),
+                )
+                .await;
+                usage_metrics::count(&db, Counter::NarrationsGenerated, None).await;
+                variants.push(VariantSummary {
+                    variant: i + 1,
+                    narration_id: narration_id.clone(),
+                    temperature,
+                    ..narration_variants::summarize(&response)
+                });
+                first.get_or_insert((response, narration_id));
+            }
+            Err(e) => {
+                warn!("Narration variant {} failed: {:#}", i + 1, e);
+                usage_metrics::count(&db, Counter::NarrationsFailed, None).await;
+                variants.push(VariantSummary {
+                    variant: i + 1,
+                    temperature,
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                });
+                first_error.get_or_insert(CommandError::from(e));
+            }
+        }
+    }
+
+    match first {
+        Some((response, narration_id)) => Ok(NarrateOutcome { response, narration_id, generation_group, variants }),
+        None => Err(first_error.unwrap_or_else(|| "No narration variants were requested".into())),
+    }
+}
+
+/// Save a narration in the history, returning its id
+///
+/// History is a convenience; a narration the user already paid for isn't
+/// failed because it couldn't be saved.
+async fn record_narration(
+    db: &LocalDatabase,
+    project_id: Option<&str>,
+    video_id: Option<&str>,
+    response: &NarrateResponse,
+    options_json: Option<&str>,
+    generation_group: Option<&str>,
+    bundle_fingerprint: Option<&str>,
+) -> Option<String> {
+    let model = response.meta.get("model").cloned().unwrap_or_default();
+    let json = match serde_json::to_string(response) {
+        Ok(json) => json,
+        Err(e) => {
+            warn!("Failed to serialize narration: {}", e);
+            return None;
+        }
+    };
+    match db
+        .add_narration(project_id, video_id, &model, &json, options_json, generation_group, bundle_fingerprint)
+        .await {
+        Ok(narration) => Some(narration.id),
+        Err(e) => {
+            warn!("Failed to record narration: {}", e);
+            None
+        }
+    }
+}
+
+/// Narrations generated for a video, newest first
+#[tauri::command]
+pub async fn get_narration_history(
+    video_id: String,
+    db: State<'_, LocalDatabase>,
+) -> Result<Vec<Narration>, CommandError> {
+    Ok(db.get_narrations(&video_id).await?)
+}
+
+/// Write a narration's script to `path` for text-to-speech, as SSML,
+/// plain text or JSON; returns the path written
+#[tauri::command]
+pub async fn export_script(
+    narration_id: String,
+    format: ScriptFormat,
+    path: String,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let narration = db.get_narration(&narration_id).await.map_err(CommandError::lookup(format!("Narration {}", narration_id)))?;
+    let response: NarrateResponse = serde_json::from_str(&narration.response_json)
+        .map_err(|e| format!("Stored narration is unreadable: {}", e))?;
+    let segments = response
+        .script
+        .map(|script| script.segments)
+        .filter(|segments| !segments.is_empty())
+        .ok_or_else(|| CommandError::invalid_input("This narration has no script to export"))?;
+
+    let mut options: NarrationOptions = narration
+        .options_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    // The language actually written in, which a fallback may have changed
+    if let Some(language) = response.meta.get("language") {
+        options.language = language.clone();
+    }
+
+    let contents = script::export(&segments, &options, format)?;
+    tokio::fs::write(&path, contents)
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported narration {} script as {} to {}", narration_id, format.extension(), path);
+    Ok(path)
+}
+
+/// Write a narration's script to `path` as SRT or WebVTT subtitles, a cue
+/// per line of narration; returns the path written
+#[tauri::command]
+pub async fn export_narration_srt(
+    narration_id: String,
+    path: String,
+    format: SubtitleFormat,
+    db: State<'_, LocalDatabase>,
+) -> Result<String, CommandError> {
+    let narration = db.get_narration(&narration_id).await.map_err(CommandError::lookup(format!("Narration {}", narration_id)))?;
+    let response: NarrateResponse = serde_json::from_str(&narration.response_json)
+        .map_err(|e| format!("Stored narration is unreadable: {}", e))?;
+    let segments = response
+        .script
+        .map(|script| script.segments)
+        .filter(|segments| !segments.is_empty())
+        .ok_or_else(|| CommandError::invalid_input("This narration has no script to export"))?;
+
+    let options: NarrationOptions = narration
+        .options_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    // The last cue ends with the video, when it's still there to ask
+    let duration = match &narration.video_id {
+        Some(video_id) => db.get_video(video_id).await.ok().and_then(|video| video.duration_seconds),
+        None => None,
+    };
+
+    let cues = subtitles::cues(&segments, options.speech_rate_wpm, duration);
+    if cues.is_empty() {
+        return Err(CommandError::invalid_input("None of this narration's lines has a time code to place it at"));
+    }
+    tokio::fs::write(&path, subtitles::render(&cues, format))
+        .await
+        .map_err(|e| CommandError::from(e).context(format!("Failed to write {}", path)))?;
+    info!("Exported narration {} as {} {} cues to {}", narration_id, cues.len(), format.extension(), path);
+    Ok(path)
+}
+
+/// Narrations side by side, chapter by chapter, to choose between variants
+#[tauri::command]
+pub async fn compare_narrations(
+    narration_ids: Vec<String>,
+    db: State<'_, LocalDatabase>,
+) -> Result<NarrationComparison, CommandError> {
+    if narration_ids.len() < 2 {
+        return Err(CommandError::invalid_input("Choose at least two narrations to compare"));
+    }
+
+    let mut narrations = Vec::with_capacity(narration_ids.len());
+    for id in narration_ids {
+        let narration = db.get_narration(&id).await.map_err(CommandError::lookup(format!("Narration {}", id)))?;
+        let response: NarrateResponse = serde_json::from_str(&narration.response_json)
+            .map_err(|e| format!("Stored narration {} is unreadable: {}", id, e))?;
+        narrations.push((id, response));
+    }
+    Ok(narration_variants::compare(&narrations))
+}
+
+/// What's changed in the Truth Bundle since a narration was written from
+/// it, and which of its script lines cite what changed
+#[tauri::command]
+pub async fn check_narration_freshness(
+    narration_id: String,
+    truth_bundle: TruthBundle,
+    db: State<'_, LocalDatabase>,
+) -> Result<Freshness, CommandError> {
+    let (_, response, written_from) = fingerprinted_narration(&db, &narration_id).await?;
+    Ok(freshness::check(&written_from, &BundleFingerprint::of(&truth_bundle), &response))
+}
+
+/// Write again the parts of a narration whose lines cite events or POIs
+/// that changed in `truth_bundle`, keeping the rest as it was
+///
+/// The result is saved as a new narration, written from `truth_bundle`.
+/// Events added since aren't cited by any line, so they don't make a
+/// part stale on their own.
+#[tauri::command]
+pub async fn refresh_narration(
+    narration_id: String,
+    truth_bundle: TruthBundle,
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, NarrationResults>,
+    app: AppHandle,
+    db: State<'_, LocalDatabase>,
+) -> Result<NarrateOutcome, CommandError> {
+    let (narration, previous, written_from) = fingerprinted_narration(&db, &narration_id).await?;
+    let freshness = freshness::check(&written_from, &BundleFingerprint::of(&truth_bundle), &previous);
+    if freshness.affected_segments.is_empty() {
+        return Err(CommandError::invalid_input("Nothing this narration cites has changed"));
+    }
+    let changed_windows = freshness::affected_windows(&previous, &freshness.affected_segments);
+
+    let options: HashMap<String, serde_json::Value> = narration
+        .options_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    let video_duration_seconds = match &narration.video_id {
+        Some(video_id) => db.get_video(video_id).await.ok().and_then(|video| video.duration_seconds),
+        None => None,
+    };
+    info!(
+        "Refreshing {} of {} lines of narration {}",
+        freshness.affected_segments.len(),
+        previous.script.as_ref().map_or(0, |script| script.segments.len()),
+        narration_id
+    );
+
+    let request = NarrateRequest {
+        truth_bundle,
+        transcript: None,
+        scene_frames: Vec::new(),
+        video_duration_seconds,
+        speech: Vec::new(),
+        options,
+        revision: Some(NarrationRevision { previous, changed_windows }),
+    };
+    narrate_and_wait(request, &state, &results, app).await
+}
+
+/// Narrate again with a stored narration's sampling parameters, seed and all
+///
+/// With the same parameters what comes out differs only where the request
+/// or the prompts do, rather than by chance. `reroll` picks new ones as for
+/// a new narration instead. One take either way: the request's variants are
+/// ignored.
+#[tauri::command]
+pub async fn regenerate_narration(
+    narration_id: String,
+    mut request: NarrateRequest,
+    reroll: Option<bool>,
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, NarrationResults>,
+    app: AppHandle,
+    db: State<'_, LocalDatabase>,
+) -> Result<NarrateOutcome, CommandError> {
+    let narration = db.get_narration(&narration_id).await.map_err(CommandError::lookup(format!("Narration {}", narration_id)))?;
+    request.options.remove("variants");
+    request.options.remove("variant_temperatures");
+
+    if reroll.unwrap_or(false) {
+        request.options.remove("sampling");
+        info!("Regenerating narration {} with new sampling", narration_id);
+    } else {
+        let stored: NarrationOptions = narration
+            .options_json
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+        if stored.sampling.seed.is_none() {
+            warn!("Narration {} was saved without a seed; regenerating it won't be repeatable", narration_id);
+        }
+        info!("Regenerating narration {} with {:?}", narration_id, stored.sampling);
+        request.options.insert(
+            "sampling".to_string(),
+            serde_json::to_value(stored.sampling)?,
+        );
+    }
+    narrate_and_wait(request, &state, &results, app).await
+}
+
+/// A stored narration, read back, with the fingerprint of the bundle it
+/// was written from
+async fn fingerprinted_narration(
+    db: &LocalDatabase,
+    narration_id: &str,
+) -> Result<(Narration, NarrateResponse, BundleFingerprint), CommandError> {
+    let narration = db.get_narration(narration_id).await.map_err(CommandError::lookup(format!("Narration {}", narration_id)))?;
+    let response: NarrateResponse = serde_json::from_str(&narration.response_json)
+        .map_err(|e| format!("Stored narration is unreadable: {}", e))?;
+    let fingerprint = narration
+        .bundle_fingerprint
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .ok_or_else(|| {
+            "This narration was saved without what it was written from; narrate the video again to follow changes"
+                .to_string()
+        })?;
+    Ok((narration, response, fingerprint))
 }
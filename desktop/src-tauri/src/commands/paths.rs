@@ -0,0 +1,185 @@
+//! Path Commands
+//!
+//! Where the app keeps things, and revealing a file in Finder, Explorer or
+//! the Linux file manager. Only files under the app's own directories, or
+//! videos and audio the database knows about, can be revealed, so the
+//! command can't be used to open arbitrary paths.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use super::sidecars::BINARIES_DIR;
+use crate::error::{CommandError, ErrorCode};
+use crate::logging;
+use crate::services::LocalDatabase;
+
+/// Directories the app uses
+#[derive(Debug, Clone, Serialize)]
+pub struct AppPaths {
+    pub data_dir: PathBuf,
+    pub cache_dir: PathBuf,
+    pub logs_dir: PathBuf,
+    /// Downloaded sidecar binaries
+    pub binaries_dir: PathBuf,
+    /// Suggested folder for exported files
+    pub exports_dir: PathBuf,
+}
+
+impl AppPaths {
+    fn roots(&self) -> [&Path; 5] {
+        [&self.data_dir, &self.cache_dir, &self.logs_dir, &self.binaries_dir, &self.exports_dir]
+    }
+}
+
+/// Get the directories the app uses
+#[tauri::command]
+pub async fn get_app_paths(app: AppHandle) -> Result<AppPaths, CommandError> {
+    app_paths(&app)
+}
+
+/// Show a file or folder in the system file manager, selected where the
+/// platform supports it
+#[tauri::command]
+pub async fn reveal_in_file_manager(app: AppHandle, db: State<'_, LocalDatabase>, path: String) -> Result<(), CommandError> {
+    let requested = PathBuf::from(path.trim());
+    let target = requested
+        .canonicalize()
+        .map_err(|_| CommandError::file_not_found(format!("Not found: {}", requested.display())))?;
+
+    let roots: Vec<PathBuf> = app_paths(&app)?
+        .roots()
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .collect();
+    let (as_given, resolved) = (requested.to_string_lossy(), target.to_string_lossy());
+    let allowed = is_under_roots(&target, &roots)
+        || db
+            .is_video_path(&[as_given.as_ref(), resolved.as_ref()])
+            .await?;
+    if !allowed {
+        return Err(CommandError::new(
+            ErrorCode::PermissionDenied,
+            format!("{} isn't a file GeoTruth manages", requested.display()),
+        ));
+    }
+
+    reveal(&target)
+}
+
+/// Open a folder in the system file manager, creating it first if needed
+pub fn open_folder(dir: &Path) -> Result<(), CommandError> {
+    std::fs::create_dir_all(dir)?;
+
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(target_os = "windows")]
+    let program = "explorer";
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let program = "xdg-open";
+
+    std::process::Command::new(program)
+        .arg(dir)
+        .spawn()
+        .map_err(|e| CommandError::from(e).context(format!("Failed to open {}", dir.display())))?;
+    Ok(())
+}
+
+fn app_paths(app: &AppHandle) -> Result<AppPaths, CommandError> {
+    let data_dir = app.path().app_data_dir()?;
+    let cache_dir = app.path().app_cache_dir()?;
+    let exports_dir = dirs::document_dir()
+        .map(|documents| documents.join("GeoTruth"))
+        .unwrap_or_else(|| data_dir.join("exports"));
+    Ok(AppPaths {
+        binaries_dir: data_dir.join(BINARIES_DIR),
+        logs_dir: logging::log_dir(),
+        data_dir,
+        cache_dir,
+        exports_dir,
+    })
+}
+
+/// Whether `path` is one of `roots` or inside one
+///
+/// Both sides must be canonical, so `..` and symlinks can't lead out of a root.
+fn is_under_roots(path: &Path, roots: &[PathBuf]) -> bool {
+    roots.iter().any(|root| path.starts_with(root))
+}
+
+#[cfg(target_os = "macos")]
+fn reveal(path: &Path) -> Result<(), CommandError> {
+    spawn(std::process::Command::new("open").arg("-R").arg(path), path)
+}
+
+#[cfg(target_os = "windows")]
+fn reveal(path: &Path) -> Result<(), CommandError> {
+    // Explorer wants the switch and the path as one argument, and doesn't
+    // take the `\\?\` form `canonicalize` returns
+    let shown = path.to_string_lossy();
+    let select = format!("/select,{}", shown.strip_prefix(r"\\?\").unwrap_or(&shown));
+    spawn(std::process::Command::new("explorer").arg(select), path)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn reveal(path: &Path) -> Result<(), CommandError> {
+    // No common way to select a file, so open the folder holding it
+    let dir = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+    spawn(std::process::Command::new("xdg-open").arg(dir), path)
+}
+
+fn spawn(command: &mut std::process::Command, path: &Path) -> Result<(), CommandError> {
+    command
+        .spawn()
+        .map_err(|e| CommandError::from(e).context(format!("Failed to reveal {}", path.display())))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_only_paths_under_roots_are_allowed() {
+        let dir = fixtures::temp_dir("paths");
+        let data = dir.join("data");
+        let outside = dir.join("elsewhere");
+        std::fs::create_dir_all(data.join("artifacts")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(data.join("artifacts").join("clip.wav"), b"").unwrap();
+        std::fs::write(outside.join("secret.txt"), b"").unwrap();
+        let roots = vec![data.canonicalize().unwrap()];
+
+        let inside = data.join("artifacts").join("clip.wav").canonicalize().unwrap();
+        assert!(is_under_roots(&inside, &roots));
+        assert!(is_under_roots(&roots[0], &roots));
+
+        // `..` is resolved before the check
+        let escaped = data.join("..").join("elsewhere").join("secret.txt").canonicalize().unwrap();
+        assert!(!is_under_roots(&escaped, &roots));
+
+        // A sibling sharing the root's name as a prefix isn't inside it
+        std::fs::create_dir_all(dir.join("data-other")).unwrap();
+        assert!(!is_under_roots(&dir.join("data-other").canonicalize().unwrap(), &roots));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_out_of_root_is_not_allowed() {
+        let dir = fixtures::temp_dir("paths");
+        let data = dir.join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::write(dir.join("secret.txt"), b"").unwrap();
+        std::os::unix::fs::symlink(dir.join("secret.txt"), data.join("link.txt")).unwrap();
+        let roots = vec![data.canonicalize().unwrap()];
+
+        let linked = data.join("link.txt").canonicalize().unwrap();
+        assert!(!is_under_roots(&linked, &roots));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,188 @@
+//! POI Lookup
+//!
+//! "What's that?" lookups for a single coordinate, e.g. a click on the map.
+//! Unlike frame verification there's no heading or field of view: POIs are
+//! simply ranked by distance from the point.
+
+use serde::Serialize;
+use tauri::State;
+use tracing::debug;
+
+use super::MAP_REGIONS;
+use crate::error::CommandError;
+use crate::services::database::{DatabaseError, PoiRecord};
+use crate::services::truth_engine::{search_bounds, within_radius};
+use crate::services::LocalDatabase;
+use crate::types::{CoveringRegion, POI};
+
+/// Search radii tried in turn until enough POIs are found
+const SEARCH_RADII_M: &[f64] = &[250.0, 1_000.0, 5_000.0, 25_000.0];
+
+const DEFAULT_MAX_RESULTS: usize = 10;
+const MAX_RESULTS_LIMIT: usize = 100;
+
+/// Result of a nearest POI lookup
+#[derive(Debug, Clone, Serialize)]
+pub struct NearestPoiResult {
+    /// Closest first
+    pub pois: Vec<POI>,
+    /// False when no downloaded region covers the point, so an empty list
+    /// means "no data here" rather than "nothing nearby"
+    pub has_data: bool,
+}
+
+/// Find the POIs closest to a coordinate, with distance and bearing from it
+#[tauri::command]
+pub async fn nearest_poi(
+    db: State<'_, LocalDatabase>,
+    lat: f64,
+    lon: f64,
+    category_filter: Option<Vec<String>>,
+    max_results: Option<usize>,
+) -> Result<NearestPoiResult, CommandError> {
+    if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+        return Err(CommandError::invalid_input(format!("Invalid coordinate: {}, {}", lat, lon)));
+    }
+
+    let has_data = has_region_data(lat, lon).await;
+    if !has_data {
+        debug!("No downloaded region covers {}, {}", lat, lon);
+        return Ok(NearestPoiResult { pois: Vec::new(), has_data: false });
+    }
+
+    let categories = category_filter.unwrap_or_default();
+    let max_results = max_results.unwrap_or(DEFAULT_MAX_RESULTS).clamp(1, MAX_RESULTS_LIMIT);
+    let pois = nearest_pois(&db, lat, lon, &categories, max_results)
+        .await?;
+
+    Ok(NearestPoiResult { pois, has_data })
+}
+
+/// The `max_results` POIs of `categories` (empty = all) closest to a point,
+/// searching ever further out until there are enough
+pub(crate) async fn nearest_pois(
+    db: &LocalDatabase,
+    lat: f64,
+    lon: f64,
+    categories: &[String],
+    max_results: usize,
+) -> Result<Vec<POI>, DatabaseError> {
+    let mut pois = Vec::new();
+    for &radius_m in SEARCH_RADII_M {
+        let candidates = db.pois_in_bounds(search_bounds(lat, lon, radius_m), categories).await?;
+
+        pois = rank_nearest(lat, lon, candidates, radius_m, max_results);
+        if pois.len() >= max_results {
+            break;
+        }
+    }
+    Ok(pois)
+}
+
+/// Whether a downloaded region covers the point
+pub(crate) async fn has_region_data(lat: f64, lon: f64) -> bool {
+    MAP_REGIONS
+        .read()
+        .await
+        .iter()
+        .any(|r| r.status.has_data() && contains(r.bounds, lat, lon))
+}
+
+/// The downloaded region covering the point; the smallest, where several do
+pub(crate) async fn covering_region(lat: f64, lon: f64) -> Option<CoveringRegion> {
+    let regions = MAP_REGIONS.read().await;
+    smallest_containing(regions.iter().filter(|r| r.status.has_data()).map(|r| (r, r.bounds)), lat, lon)
+        .map(|r| CoveringRegion { id: r.id.clone(), name: r.name.clone() })
+}
+
+/// Whether `(min_lat, min_lon, max_lat, max_lon)` contains the point
+fn contains((min_lat, min_lon, max_lat, max_lon): (f64, f64, f64, f64), lat: f64, lon: f64) -> bool {
+    (min_lat..=max_lat).contains(&lat) && (min_lon..=max_lon).contains(&lon)
+}
+
+/// The item whose bounds contain the point and are the smallest
+fn smallest_containing<T>(items: impl Iterator<Item = (T, (f64, f64, f64, f64))>, lat: f64, lon: f64) -> Option<T> {
+    let area = |(min_lat, min_lon, max_lat, max_lon): (f64, f64, f64, f64)| (max_lat - min_lat) * (max_lon - min_lon);
+    items
+        .filter(|(_, bounds)| contains(*bounds, lat, lon))
+        .min_by(|(_, a), (_, b)| area(*a).total_cmp(&area(*b)))
+        .map(|(item, _)| item)
+}
+
+/// The `max_results` candidates within `radius_m` of the point, closest first
+fn rank_nearest(lat: f64, lon: f64, candidates: Vec<PoiRecord>, radius_m: f64, max_results: usize) -> Vec<POI> {
+    within_radius(lat, lon, candidates, radius_m)
+        .into_iter()
+        .take(max_results)
+        .map(POI::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, lat: f64, lon: f64) -> PoiRecord {
+        PoiRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            category: "landmark".to_string(),
+            lat,
+            lon,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rank_nearest() {
+        // Casino de Monte-Carlo, looking for things around it
+        let (lat, lon) = (43.7393, 7.4283);
+        let candidates = vec![
+            record("far", lat + 0.004, lon),
+            record("east", lat, lon + 0.001),
+            record("north", lat + 0.001, lon),
+            record("corner", lat + 0.0022, lon + 0.0031),
+        ];
+
+        let pois = rank_nearest(lat, lon, candidates, 250.0, 10);
+        let ids: Vec<&str> = pois.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["east", "north"]);
+
+        assert!((pois[0].distance_m - 80.4).abs() < 1.0);
+        assert!((pois[0].bearing_deg - 90.0).abs() < 0.1);
+        assert!((pois[1].distance_m - 111.2).abs() < 1.0);
+        assert!(pois[1].bearing_deg.abs() < 0.1);
+        assert!(pois.iter().all(|p| !p.in_fov));
+
+        let pois = rank_nearest(lat, lon, vec![record("a", lat, lon + 0.001), record("b", lat, lon - 0.0005)], 250.0, 1);
+        assert_eq!(pois.len(), 1);
+        assert_eq!(pois[0].id, "b");
+        assert!((pois[0].bearing_deg - 270.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_search_bounds() {
+        let (min_lat, min_lon, max_lat, max_lon) = search_bounds(43.7393, 7.4283, 1_000.0);
+        assert!((max_lat - min_lat - 2.0 / 111.32).abs() < 1e-6);
+        // Longitude degrees are shorter away from the equator
+        assert!(max_lon - min_lon > max_lat - min_lat);
+        assert!(contains((min_lat, min_lon, max_lat, max_lon), 43.7393, 7.4283));
+
+        assert_eq!(search_bounds(89.9999, 0.0, 1_000.0).1, -180.0);
+    }
+
+    #[test]
+    fn test_smallest_containing() {
+        let regions = [
+            ("europe", (34.0, -25.0, 72.0, 45.0)),
+            ("monaco", (43.72, 7.40, 43.76, 7.44)),
+            ("italy", (35.5, 6.6, 47.1, 18.5)),
+        ];
+        let smallest = |lat, lon| smallest_containing(regions.iter().copied(), lat, lon);
+
+        assert_eq!(smallest(43.7393, 7.4283), Some("monaco"));
+        assert_eq!(smallest(45.4642, 9.19), Some("italy"));
+        assert_eq!(smallest(51.5, -0.12), Some("europe"));
+        assert_eq!(smallest(40.71, -74.0), None);
+    }
+}
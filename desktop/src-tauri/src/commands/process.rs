@@ -1,19 +1,567 @@
-use crate::processor::VideoProcessor;
+use crate::crash;
+use crate::enrich::{self, EnrichmentEngine};
+use crate::error::CommandError;
+use crate::estimate::{self, EstimateInput, ProcessingEstimate};
+use crate::jobs;
+use crate::processor::{self, ProcessOptions, ProcessedVideo, VideoProcessor};
+use crate::services::database::{DatabaseError, ProcessingStatus, Video, VideoStatus};
+use crate::services::gps::GpsPoint;
+use crate::services::{weather, Ffmpeg, GpsTrack, LocalDatabase, Whisper};
+use crate::settings;
+use crate::state::{AppState, JobStatus};
 use crate::types::TruthBundle;
-use std::path::PathBuf;
-use tauri::State;
+use crate::usage_metrics::{self, Counter, Histogram};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager, State};
 use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+use uuid::Uuid;
 
+use super::ingest::track_point;
+use super::poi::has_region_data;
+
+/// Outcome of processing several videos
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatchProcessResult {
+    pub processed: Vec<String>,
+    /// Already complete, so not processed again
+    pub skipped: Vec<String>,
+    /// Video ID and why it failed
+    pub failed: Vec<(String, String)>,
+}
+
+/// How far processing a video has got, emitted as `processing-progress`
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingProgress {
+    pub job_id: String,
+    /// The stage reached, e.g. `transcribe`
+    pub stage: &'static str,
+    /// From 0 to 1
+    pub progress: f32,
+}
+
+/// Finished processing jobs' Truth Bundles, until they're retrieved
+#[derive(Default)]
+pub struct ProcessingResults(DashMap<String, TruthBundle>);
+
+/// Why an imported video can't be processed
+#[derive(Debug, Error)]
+pub enum VideoSourceError {
+    #[error("Video not found: {0}")]
+    NotFound(String),
+
+    /// Moved, renamed, or on a drive that isn't connected
+    #[error("The file of video {video_id} is no longer at {path}; point it to where it is now with relink_video")]
+    FileMissing { video_id: String, path: String },
+
+    /// Nothing processed could be kept for it
+    #[error("Video id {0} isn't a UUID")]
+    InvalidId(String),
+
+    #[error("Database error: {0}")]
+    Database(DatabaseError),
+}
+
+/// An imported video, with the ids its Truth Bundle goes by
+pub(crate) struct StoredVideo {
+    pub id: Uuid,
+    /// `None` if the project's id isn't a UUID
+    pub project_id: Option<Uuid>,
+    pub video: Video,
+}
+
+/// What a processing job works on
+enum Source {
+    /// An imported video, by id
+    Stored(String),
+    /// A file that may never have been imported
+    File { video_path: String, gps_path: Option<String> },
+}
+
+/// Process an imported video into a Truth Bundle
+///
+/// Its file, stored GPS track and camera offset come from the database, its
+/// status and stages are kept there as with `process_videos`, and a run
+/// picks up after the stages an earlier one finished. Without `options` the
+/// ones kept in the settings apply. Waits for the bundle; [`start_processing`]
+/// does the same in the background, where it can be followed and cancelled.
 #[tauri::command]
 pub async fn process_video(
+    video_id: String,
+    options: Option<ProcessOptions>,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, ProcessingResults>,
+    app: AppHandle,
+) -> Result<TruthBundle, CommandError> {
+    resolve_video(&db, &video_id).await?;
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    let (job_id, task) = start_job(Source::Stored(video_id), options, &state, app);
+    await_job(job_id, task, &state, &results).await
+}
+
+/// Process a video file by its path, as [`process_video`] does an imported one
+///
+/// Nothing is kept: the file has no video record to keep it with.
+#[tauri::command]
+pub async fn process_video_file(
     video_path: String,
     gps_path: Option<String>,
+    options: Option<ProcessOptions>,
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, ProcessingResults>,
+    app: AppHandle,
+) -> Result<TruthBundle, CommandError> {
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    let (job_id, task) = start_job(Source::File { video_path, gps_path }, options, &state, app);
+    await_job(job_id, task, &state, &results).await
+}
+
+/// Start processing an imported video in the background, returning the job's id
+///
+/// Each stage is reported with `processing-progress` and `job-status`
+/// events; the job can be stopped with `cancel_job` and its bundle fetched
+/// with [`get_processing_result`] once it's done.
+#[tauri::command]
+pub async fn start_processing(
+    video_id: String,
+    options: Option<ProcessOptions>,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<String, CommandError> {
+    resolve_video(&db, &video_id).await?;
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    let (job_id, _) = start_job(Source::Stored(video_id), options, &state, app);
+    Ok(job_id)
+}
+
+/// Start processing a video file by its path in the background, as
+/// [`start_processing`] does an imported one
+#[tauri::command]
+pub async fn start_processing_file(
+    video_path: String,
+    gps_path: Option<String>,
+    options: Option<ProcessOptions>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<String, CommandError> {
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    let (job_id, _) = start_job(Source::File { video_path, gps_path }, options, &state, app);
+    Ok(job_id)
+}
+
+/// Wait for a processing job and take its bundle
+async fn await_job(
+    job_id: String,
+    task: tokio::task::JoinHandle<()>,
+    state: &AppState,
+    results: &ProcessingResults,
+) -> Result<TruthBundle, CommandError> {
+    if task.await.is_err() {
+        return Err(CommandError::cancelled("Processing was cancelled"));
+    }
+    match results.0.remove(&job_id) {
+        Some((_, bundle)) => Ok(bundle),
+        None => Err(job_error(state, &job_id)),
+    }
+}
+
+/// The Truth Bundle of a finished processing job, or `None` while it's
+/// still running
+///
+/// A result can be retrieved once; a failed or cancelled job returns why.
+#[tauri::command]
+pub async fn get_processing_result(
+    job_id: String,
+    state: State<'_, Arc<AppState>>,
+    results: State<'_, ProcessingResults>,
+) -> Result<Option<TruthBundle>, CommandError> {
+    let status = state
+        .active_jobs
+        .get(&job_id)
+        .map(|job| job.status.clone())
+        .ok_or_else(|| CommandError::not_found(format!("Job not found: {}", job_id)))?;
+    match status {
+        JobStatus::Completed => results
+            .0
+            .remove(&job_id)
+            .map(|(_, bundle)| Some(bundle))
+            .ok_or_else(|| CommandError::not_found(format!("The bundle of job {} was already retrieved", job_id))),
+        JobStatus::Pending | JobStatus::Processing { .. } => Ok(None),
+        _ => {
+            // Cancelled just as it finished
+            results.0.remove(&job_id);
+            Err(job_error(&state, &job_id))
+        }
+    }
+}
+
+/// Why a processing job didn't produce a bundle
+fn job_error(state: &AppState, job_id: &str) -> CommandError {
+    match state.active_jobs.get(job_id).map(|job| job.status.clone()) {
+        Some(JobStatus::Failed { error, code }) => CommandError::new(code, error),
+        Some(JobStatus::Cancelled) => CommandError::cancelled("Processing was cancelled"),
+        _ => format!("Processing job {} has no result", job_id).into(),
+    }
+}
+
+/// Start processing a video as a job
+fn start_job(
+    source: Source,
+    options: ProcessOptions,
+    state: &Arc<AppState>,
+    app: AppHandle,
+) -> (String, tokio::task::JoinHandle<()>) {
+    jobs::start(state, jobs::emitter(&app), "processing", move |job| async move {
+        let report = |stage: &'static str, progress: f32| {
+            job.progress(progress, stage);
+            let _ = app.emit("processing-progress", ProcessingProgress { job_id: job.job_id().to_string(), stage, progress });
+        };
+        let bundle = crash::catch_panic(run_processing(source, options, &app, &report)).await?;
+        app.state::<ProcessingResults>().0.insert(job.job_id().to_string(), bundle);
+        report("persistence", 1.0);
+        Ok(())
+    })
+}
+
+async fn run_processing(
+    source: Source,
+    options: ProcessOptions,
+    app: &AppHandle,
+    report: &(impl Fn(&'static str, f32) + Send + Sync),
+) -> Result<TruthBundle, CommandError> {
+    let processor = app.state::<Arc<VideoProcessor>>();
+    let enrichment = app.state::<EnrichmentEngine>();
+    let weather = options.weather;
+
+    let stored_id = match &source {
+        Source::Stored(video_id) => Some(video_id.clone()),
+        Source::File { .. } => None,
+    };
+    let processed = match source {
+        Source::Stored(video_id) => {
+            let db = app.state::<LocalDatabase>();
+            process_stored(&db, &processor, &video_id, options, false, report).await?
+        }
+        Source::File { video_path, gps_path } => processor
+            .process_video(PathBuf::from(video_path), gps_path.map(PathBuf::from), options, report)
+            .await?,
+    };
+    let mut bundle = processed.bundle;
+    if weather {
+        enrichment.add_weather(&mut bundle).await;
+    }
+    // Online only; offline the POIs keep what the map data says of them
+    let language = processed.options.language.as_deref().unwrap_or("English");
+    enrichment.add_article_summaries(&mut bundle.events, language).await;
+    // The bundle is still worth having without them
+    match enrichment.summarize_route(&processed.route).await {
+        Ok(segments) => bundle.route_segments = segments,
+        Err(e) => warn!("Failed to summarize the route: {}", e),
+    }
+    if let Some(video_id) = stored_id {
+        keep_bundle(&app.state::<LocalDatabase>(), &video_id, &bundle).await;
+    }
+    report("verification", 0.95);
+    Ok(bundle)
+}
+
+/// Process imported videos one after another, recording each one's status
+///
+/// Videos already processed are skipped unless `force` is set. `options`
+/// apply to every video, and default to the ones kept in the settings.
+///
+/// A video whose last run failed picks up after the stages it finished,
+/// e.g. without transcribing again; `force` starts each video over.
+#[tauri::command]
+pub async fn process_videos(
+    db: State<'_, LocalDatabase>,
     processor: State<'_, Arc<VideoProcessor>>,
-) -> Result<TruthBundle, String> {
-    let video_path = PathBuf::from(video_path);
-    let gps_path = gps_path.map(PathBuf::from);
+    video_ids: Vec<String>,
+    force: Option<bool>,
+    options: Option<ProcessOptions>,
+) -> Result<BatchProcessResult, CommandError> {
+    let force = force.unwrap_or(false);
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    let mut result = BatchProcessResult::default();
+    
+    for video_id in video_ids {
+        let status = db.get_video_status(&video_id)
+            .await?;
+        if status.status == ProcessingStatus::Complete && !force {
+            result.skipped.push(video_id);
+            continue;
+        }
+        
+        match process_stored(&db, &processor, &video_id, options.clone(), force, |_, _| {}).await {
+            Ok(_) => result.processed.push(video_id),
+            Err(e) => {
+                warn!("Processing video {} failed: {}", video_id, e);
+                result.failed.push((video_id, e.message));
+            }
+        }
+    }
     
-    processor.process_video(video_path, gps_path)
+    info!(
+        "Batch processing done: {} processed, {} skipped, {} failed",
+        result.processed.len(), result.skipped.len(), result.failed.len()
+    );
+    Ok(result)
+}
+
+/// Estimate how long processing an imported video would take and what it needs
+///
+/// Without `options` the ones kept in the settings apply. Stages an earlier
+/// run kept the output of, made from the same inputs, are listed as reused
+/// rather than estimated. The first estimate with a Whisper model times it
+/// on a sample of the video, so takes a few seconds; the speed is kept for
+/// later ones.
+#[tauri::command]
+pub async fn estimate_processing(
+    video_id: String,
+    options: Option<ProcessOptions>,
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    whisper: State<'_, Arc<Whisper>>,
+    processor: State<'_, Arc<VideoProcessor>>,
+    enrichment: State<'_, EnrichmentEngine>,
+) -> Result<ProcessingEstimate, CommandError> {
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    options.validate().map_err(CommandError::invalid_input)?;
+    let StoredVideo { id, video, .. } = resolve_video(&db, &video_id).await?;
+    let video_path = PathBuf::from(&video.file_path);
+    let metadata = ffmpeg.extract_metadata(&video_path)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| CommandError::from(e).context(format!("Failed to read video {}", video_id)))?;
+    let duration = metadata.duration_seconds.or(video.duration_seconds).unwrap_or(0.0);
+
+    let points: Vec<GpsPoint> = db.get_gps_points(&video_id)
+        .await?
+        .into_iter()
+        .map(track_point)
+        .collect();
+    let track = (!points.is_empty()).then(|| GpsTrack::from_points(video.filename.clone(), "stored", points.clone()));
+    // As a run picking up after the last would, unless forced to start over
+    let reused = processor::reusable_stages(&*db, id, &video_path, track.as_ref(), &options)
+        .await
+        .unwrap_or_else(|e| {
+            warn!("Failed to read what earlier runs of video {} kept: {:#}", video_id, e);
+            Vec::new()
+        });
+    let samples = enrich::sampled_route(&points);
+    let mut covered_samples = 0;
+    for point in &samples {
+        covered_samples += usize::from(has_region_data(point.lat, point.lon).await);
+    }
+    // Events near each other in the same hour share a lookup
+    let weather_lookups = if enrichment.is_offline() {
+        0
+    } else {
+        samples.iter().map(|p| weather::cache_key(p.lat, p.lon, p.timestamp)).collect::<HashSet<_>>().len()
+    };
+    let input = EstimateInput {
+        duration_seconds: duration,
+        pixels: metadata.width.zip(metadata.height).map(|(w, h)| u64::from(w) * u64::from(h)),
+        has_audio: metadata.has_audio,
+        gps_points: points.len(),
+        route_samples: samples.len(),
+        covered_samples,
+        place_lookup_budget: enrichment.online_place_lookups(),
+        weather_lookups,
+        reused,
+    };
+
+    let speed = if options.transcription && metadata.has_audio && !input.reused.contains(&"transcribe") {
+        Some(estimate::whisper_speed(&ffmpeg, &whisper, &video_path, duration, &options, processor.temp_dir()).await)
+    } else {
+        None
+    };
+    let estimate = estimate::estimate(&options, &input, speed);
+    info!("Processing video {} should take about {:.0} seconds", video_id, estimate.total_seconds);
+    Ok(estimate)
+}
+
+/// Get whether a video has been processed
+#[tauri::command]
+pub async fn get_video_status(
+    db: State<'_, LocalDatabase>,
+    video_id: String,
+) -> Result<VideoStatus, CommandError> {
+    Ok(db.get_video_status(&video_id)
+        .await?)
+}
+
+/// An imported video from the database, if its file is still where it was imported from
+pub(crate) async fn resolve_video(db: &LocalDatabase, video_id: &str) -> Result<StoredVideo, VideoSourceError> {
+    let video = db.get_video(video_id).await.map_err(|e| match e {
+        DatabaseError::NotFound => VideoSourceError::NotFound(video_id.to_string()),
+        e => VideoSourceError::Database(e),
+    })?;
+    check_source(video)
+}
+
+fn check_source(video: Video) -> Result<StoredVideo, VideoSourceError> {
+    let id = Uuid::parse_str(&video.id).map_err(|_| VideoSourceError::InvalidId(video.id.clone()))?;
+    if !Path::new(&video.file_path).is_file() {
+        return Err(VideoSourceError::FileMissing { video_id: video.id, path: video.file_path });
+    }
+    let project_id = Uuid::parse_str(&video.project_id).ok();
+    Ok(StoredVideo { id, project_id, video })
+}
+
+/// Process an imported video with its stored GPS points, keeping its status up to date
+///
+/// Stages an earlier run finished are kept in the database and not run
+/// again, unless `force` starts over. A video whose file is missing is
+/// marked failed, saying how to relink it.
+pub(crate) async fn process_stored(
+    db: &LocalDatabase,
+    processor: &VideoProcessor,
+    video_id: &str,
+    options: ProcessOptions,
+    force: bool,
+    on_progress: impl Fn(&'static str, f32) + Send + Sync,
+) -> Result<ProcessedVideo, CommandError> {
+    let StoredVideo { id, project_id, video } = match resolve_video(db, video_id).await {
+        Ok(stored) => stored,
+        Err(e) => {
+            if matches!(e, VideoSourceError::FileMissing { .. }) {
+                if let Err(e) = db.set_video_status(video_id, ProcessingStatus::Failed, Some(&e.to_string()), None).await {
+                    warn!("Failed to record status of video {}: {}", video_id, e);
+                }
+            }
+            return Err(e.into());
+        }
+    };
+    let points = db.get_gps_points(video_id)
+        .await?;
+    let track = (!points.is_empty()).then(|| {
+        GpsTrack::from_points(video.filename.clone(), "stored", points.into_iter().map(track_point).collect())
+    });
+    
+    if force {
+        db.clear_completed_stages(video_id)
+            .await?;
+    }
+    db.set_video_status(video_id, ProcessingStatus::Processing, None, None)
+        .await?;
+    
+    // A failed run leaves the options of the last stage it kept, for the next to compare with
+    let mut options_json = None;
+    let started = Instant::now();
+    let result = crash::catch_panic(async {
+        processor.process_stored_video(id, PathBuf::from(&video.file_path), track, options, Some(db), on_progress)
+            .await
+            .map_err(CommandError::from)
+    })
+    .await;
+
+    // The processor stored the transcript as it went, so narration can stay
+    // out of the creator's way
+    let result = match result {
+        Ok(mut processed) => {
+            processed.bundle.project_id = project_id;
+            options_json = serde_json::to_string(&processed.options).ok();
+            keep_bundle(db, video_id, &processed.bundle).await;
+            // Audio is only extracted with a new transcript
+            if processed.options.transcription && !processed.resumed.contains(&"transcribe") {
+                let audio_path = processed.audio_path.as_ref().map(|path| path.to_string_lossy().to_string());
+                if let Err(e) = db.set_video_audio_path(video_id, audio_path.as_deref()).await {
+                    warn!("Failed to record the kept audio of video {}: {}", video_id, e);
+                }
+            }
+            Ok(processed)
+        }
+        Err(e) => Err(e),
+    };
+    
+    let (status, error) = match &result {
+        Ok(_) => (ProcessingStatus::Complete, None),
+        Err(e) => (ProcessingStatus::Failed, Some(e.message.as_str())),
+    };
+    if let Err(e) = db.set_video_status(video_id, status, error, options_json.as_deref()).await {
+        warn!("Failed to record status of video {}: {}", video_id, e);
+    }
+    count_run(db, &result, video.duration_seconds, started.elapsed().as_secs_f64()).await;
+    
+    result
+}
+
+/// Usage metrics of a processing run
+async fn count_run(db: &LocalDatabase, result: &Result<ProcessedVideo, CommandError>, duration: Option<f64>, seconds: f64) {
+    let Ok(processed) = result else {
+        usage_metrics::count(db, Counter::ProcessingFailed, None).await;
+        return;
+    };
+    usage_metrics::count(db, Counter::VideosProcessed, None).await;
+    if processed.options.transcription && !processed.resumed.contains(&"transcribe") {
+        let model = processed.options.whisper_model.map(|model| model.as_str());
+        usage_metrics::count(db, Counter::WhisperModel, model).await;
+    }
+    if let Some(duration) = duration {
+        usage_metrics::observe(db, Histogram::VideoDurationSeconds, duration).await;
+    }
+    usage_metrics::observe(db, Histogram::ProcessingSeconds, seconds).await;
+}
+
+/// Keep the Truth Bundle a video was processed into, for exports to start from
+async fn keep_bundle(db: &LocalDatabase, video_id: &str, bundle: &TruthBundle) {
+    let kept = match serde_json::to_string(bundle) {
+        Ok(json) => db.put_truth_bundle(video_id, &json).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = kept {
+        warn!("Failed to keep the Truth Bundle of video {}: {}", video_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use chrono::Utc;
+
+    fn video(file_path: &str) -> Video {
+        Video {
+            id: "6f1c2a8e-3b4d-4e5f-8a9b-0c1d2e3f4a5b".to_string(),
+            project_id: "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d".to_string(),
+            filename: "harbour.mp4".to_string(),
+            duration_seconds: Some(120.0),
+            fps: None,
+            width: None,
+            height: None,
+            codec: None,
+            file_size_bytes: None,
+            file_path: file_path.to_string(),
+            camera_heading_offset_deg: None,
+            audio_path: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_stored_video_is_resolved_by_its_ids() {
+        let dir = fixtures::temp_dir("video");
+        let file = dir.join("harbour.mp4");
+        std::fs::write(&file, b"mp4").unwrap();
+
+        let stored = check_source(video(file.to_str().unwrap())).unwrap();
+        assert_eq!(stored.id.to_string(), "6f1c2a8e-3b4d-4e5f-8a9b-0c1d2e3f4a5b");
+        assert_eq!(stored.project_id.unwrap().to_string(), "0a1b2c3d-4e5f-4a6b-8c7d-9e0f1a2b3c4d");
+
+        let invalid = Video { id: "clip-1".to_string(), ..video(file.to_str().unwrap()) };
+        assert!(matches!(check_source(invalid), Err(VideoSourceError::InvalidId(_))));
+        std::fs::remove_file(&file).unwrap();
+
+        let missing = check_source(video(file.to_str().unwrap())).err().unwrap();
+        assert!(matches!(missing, VideoSourceError::FileMissing { .. }));
+        assert!(missing.to_string().contains("relink_video"), "{}", missing);
+        std::fs::remove_dir_all(&dir).ok();
+    }
 }
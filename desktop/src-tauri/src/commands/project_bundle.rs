@@ -0,0 +1,605 @@
+//! Project Bundles
+//!
+//! A project bundle is a tar archive holding a project as it is stored: its
+//! rows (project, videos, GPS points, events, transcripts, narrations and
+//! processing state) as JSON, its cover image, and optionally the videos
+//! themselves, with a `manifest.json` describing them. Bundles move a project
+//! to another machine or keep it as a backup.
+//!
+//! Importing keeps the bundled ids unless they're already in use in this
+//! database, in which case those rows get new ids and everything pointing at
+//! them follows.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, State};
+use tracing::{info, warn};
+
+use super::bundle_archive::{self, Manifest};
+use crate::error::CommandError;
+use crate::resources::{self, Priority, Resource};
+use crate::services::database::{Project, ProjectSnapshot};
+use crate::services::LocalDatabase;
+
+/// Layout of the archive itself
+pub const PROJECT_BUNDLE_FORMAT_VERSION: u32 = 1;
+
+const ROWS_PATH: &str = "project.json";
+const ARTIFACTS_DIR: &str = "artifacts";
+const VIDEOS_DIR: &str = "videos";
+
+/// Describes a project bundle and its files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectBundleManifest {
+    pub format_version: u32,
+    pub project_id: String,
+    pub project_name: String,
+    pub created_at: String,
+    pub app_version: String,
+    pub files: Vec<ProjectBundleFile>,
+    /// Videos left out because their file was missing at export
+    #[serde(default)]
+    pub missing_videos: Vec<String>,
+}
+
+/// A file in the bundle, with its path inside the archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectBundleFile {
+    pub path: String,
+    pub content: ProjectContent,
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProjectContent {
+    /// The project's rows, a `ProjectSnapshot` as JSON
+    Rows,
+    /// The project's cover image
+    Cover,
+    /// A video's file
+    Video { video_id: String },
+}
+
+/// A file on disk to put into a bundle
+struct BundleSource {
+    content: ProjectContent,
+    path: PathBuf,
+    /// Path inside the archive
+    name: String,
+}
+
+/// What importing a project bundle did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectImportResult {
+    pub project: Project,
+    /// Rows given new ids because theirs were already in use
+    pub renamed_ids: usize,
+    /// Videos whose file isn't in the bundle or on this machine, by filename
+    pub missing_videos: Vec<String>,
+}
+
+/// Write a project, and with `include_videos` its video files, to a bundle at `dest_path`
+#[tauri::command]
+pub async fn export_project(
+    db: State<'_, LocalDatabase>,
+    project_id: String,
+    dest_path: String,
+    include_videos: Option<bool>,
+) -> Result<ProjectBundleManifest, CommandError> {
+    let snapshot = db
+        .project_snapshot(&project_id)
+        .await?;
+
+    let out = PathBuf::from(dest_path.trim());
+    let staging = out
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(format!(".export-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)?;
+
+    let include_videos = include_videos.unwrap_or(false);
+    let stage_dir = staging.clone();
+    let slot = resources::acquire(Resource::Io, Priority::Batch).await;
+    let result = tokio::task::spawn_blocking(move || {
+        let rows = stage_dir.join(ROWS_PATH);
+        let json = serde_json::to_vec_pretty(&snapshot)?;
+        std::fs::write(&rows, json)?;
+        write_bundle(&out, &snapshot, &rows, include_videos)
+    })
+    .await;
+    drop(slot);
+
+    std::fs::remove_dir_all(&staging).ok();
+
+    let manifest = result??;
+    info!(
+        "Exported project {} ({} files, {} videos missing)",
+        manifest.project_id,
+        manifest.files.len(),
+        manifest.missing_videos.len()
+    );
+    Ok(manifest)
+}
+
+/// Restore a project from a bundle into this database
+///
+/// Bundled videos and the cover are stored in the app's data directory;
+/// videos that weren't bundled keep their original path.
+#[tauri::command]
+pub async fn import_project(
+    app: AppHandle,
+    db: State<'_, LocalDatabase>,
+    path: String,
+) -> Result<ProjectImportResult, CommandError> {
+    let bundle = PathBuf::from(path.trim());
+    if !bundle.is_file() {
+        return Err(CommandError::file_not_found(format!("File not found: {}", bundle.display())));
+    }
+
+    let data_dir = app.path().app_data_dir()?;
+    let staging = data_dir.join(format!(".import-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)?;
+
+    let result = async {
+        let unpack_dir = staging.clone();
+        let slot = resources::acquire(Resource::Io, Priority::Batch).await;
+        let (manifest, mut snapshot) = tokio::task::spawn_blocking(move || unpack_bundle(&bundle, &unpack_dir)).await??;
+        drop(slot);
+
+        let renamed = assign_free_ids(&db, &mut snapshot).await?;
+
+        let mut installed = Vec::new();
+        let restored = match install_files(&manifest, &mut snapshot, &renamed, &staging, &data_dir, &mut installed) {
+            Ok(()) => db.restore_project(&snapshot).await.map_err(CommandError::from),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = restored {
+            for path in installed {
+                std::fs::remove_file(path).ok();
+            }
+            return Err(e);
+        }
+
+        let missing_videos = snapshot
+            .videos
+            .iter()
+            .filter(|v| !Path::new(&v.file_path).exists())
+            .map(|v| v.filename.clone())
+            .collect();
+
+        Ok::<_, CommandError>(ProjectImportResult {
+            project: snapshot.project,
+            renamed_ids: renamed.len(),
+            missing_videos,
+        })
+    }
+    .await;
+
+    std::fs::remove_dir_all(&staging).ok();
+
+    let result = result?;
+    info!(
+        "Imported project {} ({}, {} ids renamed)",
+        result.project.id, result.project.name, result.renamed_ids
+    );
+    Ok(result)
+}
+
+/// Give rows whose id is already in use in `db` a new one, returning the
+/// new id for each old one
+async fn assign_free_ids(
+    db: &LocalDatabase,
+    snapshot: &mut ProjectSnapshot,
+) -> Result<HashMap<String, String>, CommandError> {
+    let mut taken = HashSet::new();
+    for (table, ids) in [
+        ("projects", vec![snapshot.project.id.clone()]),
+        ("videos", snapshot.videos.iter().map(|v| v.id.clone()).collect()),
+        ("events", snapshot.events.iter().map(|e| e.id.clone()).collect()),
+        ("transcriptions", snapshot.transcriptions.iter().map(|t| t.id.clone()).collect()),
+        ("narrations", snapshot.narrations.iter().map(|n| n.id.clone()).collect()),
+    ] {
+        taken.extend(db.ids_in_use(table, &ids).await?);
+    }
+    Ok(reassign_ids(snapshot, &taken))
+}
+
+/// Replace every id in `taken` with a new one, updating the rows that refer
+/// to it; returns the new id for each old one
+fn reassign_ids(snapshot: &mut ProjectSnapshot, taken: &HashSet<String>) -> HashMap<String, String> {
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    let mut rename = |id: &mut String| {
+        if taken.contains(id.as_str()) {
+            let new_id = uuid::Uuid::new_v4().to_string();
+            renamed.insert(std::mem::replace(id, new_id.clone()), new_id);
+        }
+    };
+
+    rename(&mut snapshot.project.id);
+    snapshot.videos.iter_mut().for_each(|v| rename(&mut v.id));
+    snapshot.events.iter_mut().for_each(|e| rename(&mut e.id));
+    snapshot.transcriptions.iter_mut().for_each(|t| rename(&mut t.id));
+    snapshot.narrations.iter_mut().for_each(|n| rename(&mut n.id));
+
+    let follow = |id: &mut String| {
+        if let Some(new_id) = renamed.get(id.as_str()) {
+            *id = new_id.clone();
+        }
+    };
+    snapshot.videos.iter_mut().for_each(|v| follow(&mut v.project_id));
+    snapshot.gps_points.iter_mut().for_each(|p| follow(&mut p.video_id));
+    snapshot.events.iter_mut().for_each(|e| follow(&mut e.video_id));
+    snapshot.transcriptions.iter_mut().for_each(|t| follow(&mut t.video_id));
+    snapshot.video_statuses.iter_mut().for_each(|s| follow(&mut s.video_id));
+    for narration in &mut snapshot.narrations {
+        narration.project_id.iter_mut().for_each(follow);
+        narration.video_id.iter_mut().for_each(follow);
+    }
+
+    renamed
+}
+
+/// Move the bundled cover and videos from `staging` into `data_dir` and
+/// point the rows at them, adding each file installed to `installed`
+///
+/// Files are listed under the ids they were exported with, which `renamed`
+/// maps to the ones they're imported with.
+fn install_files(
+    manifest: &ProjectBundleManifest,
+    snapshot: &mut ProjectSnapshot,
+    renamed: &HashMap<String, String>,
+    staging: &Path,
+    data_dir: &Path,
+    installed: &mut Vec<PathBuf>,
+) -> Result<(), CommandError> {
+    let project_id = snapshot.project.id.clone();
+
+    let mut install = |file: &ProjectBundleFile, dest: PathBuf| -> Result<String, CommandError> {
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let source = staging.join(&file.path);
+        if std::fs::rename(&source, &dest).is_err() {
+            std::fs::copy(&source, &dest)
+                .map_err(|e| CommandError::from(e).context(format!("Failed to install {}", file.path)))?;
+        }
+        installed.push(dest.clone());
+        Ok(dest.to_string_lossy().to_string())
+    };
+
+    let mut has_cover = false;
+    for file in &manifest.files {
+        match &file.content {
+            ProjectContent::Rows => {}
+            ProjectContent::Cover => {
+                let extension = Path::new(&file.path)
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let dest = data_dir.join("covers").join(&project_id).with_extension(extension);
+                snapshot.project.cover_image_path = Some(install(file, dest)?);
+                has_cover = true;
+            }
+            ProjectContent::Video { video_id } => {
+                let video_id = renamed.get(video_id).unwrap_or(video_id);
+                let Some(video) = snapshot.videos.iter_mut().find(|v| v.id == *video_id) else {
+                    warn!("Bundle has a file for unknown video {}", video_id);
+                    continue;
+                };
+                let dest = data_dir
+                    .join("projects")
+                    .join(&project_id)
+                    .join(VIDEOS_DIR)
+                    .join(&video.id)
+                    .join(file_name(&file.path)?);
+                video.file_path = install(file, dest)?;
+            }
+        }
+    }
+
+    // A cover from another machine points nowhere here
+    if !has_cover {
+        snapshot.project.cover_image_path = None;
+    }
+
+    Ok(())
+}
+
+fn file_name(path: &str) -> Result<String, CommandError> {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| CommandError::invalid_file(format!("Invalid path in bundle manifest: {}", path)))
+}
+
+/// Write the rows at `rows`, the cover and optionally the videos of
+/// `snapshot` to a bundle at `out`
+fn write_bundle(
+    out: &Path,
+    snapshot: &ProjectSnapshot,
+    rows: &Path,
+    include_videos: bool,
+) -> Result<ProjectBundleManifest, CommandError> {
+    let mut sources = vec![BundleSource {
+        content: ProjectContent::Rows,
+        path: rows.to_path_buf(),
+        name: ROWS_PATH.to_string(),
+    }];
+
+    if let Some(cover) = snapshot.project.cover_image_path.as_deref().map(Path::new).filter(|p| p.is_file()) {
+        sources.push(BundleSource {
+            content: ProjectContent::Cover,
+            path: cover.to_path_buf(),
+            name: format!("{}/{}", ARTIFACTS_DIR, file_name(&cover.to_string_lossy())?),
+        });
+    }
+
+    let mut missing_videos = Vec::new();
+    if include_videos {
+        for video in &snapshot.videos {
+            let path = Path::new(&video.file_path);
+            if !path.is_file() {
+                warn!("Video {} is missing at {:?}, leaving it out of the bundle", video.id, path);
+                missing_videos.push(video.filename.clone());
+                continue;
+            }
+            sources.push(BundleSource {
+                content: ProjectContent::Video { video_id: video.id.clone() },
+                path: path.to_path_buf(),
+                name: format!("{}/{}/{}", VIDEOS_DIR, video.id, file_name(&video.file_path)?),
+            });
+        }
+    }
+
+    let mut files = Vec::new();
+    for source in &sources {
+        let (sha256, bytes) = bundle_archive::checksum(&source.path)?;
+        files.push(ProjectBundleFile {
+            path: source.name.clone(),
+            content: source.content.clone(),
+            sha256,
+            bytes,
+        });
+    }
+
+    let manifest = ProjectBundleManifest {
+        format_version: PROJECT_BUNDLE_FORMAT_VERSION,
+        project_id: snapshot.project.id.clone(),
+        project_name: snapshot.project.name.clone(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        files,
+        missing_videos,
+    };
+    let entries: Vec<(&Path, &str)> = sources.iter().map(|s| (s.path.as_path(), s.name.as_str())).collect();
+    bundle_archive::write(out, &manifest, &entries)?;
+
+    Ok(manifest)
+}
+
+/// Unpack a bundle into `staging`, check it against its manifest and read its rows
+fn unpack_bundle(bundle: &Path, staging: &Path) -> Result<(ProjectBundleManifest, ProjectSnapshot), CommandError> {
+    let manifest = bundle_archive::unpack::<ProjectBundleManifest>(bundle, staging)?;
+
+    let rows = std::fs::read(staging.join(ROWS_PATH))
+        .map_err(|_| CommandError::invalid_file("Bundle is incomplete: project.json is missing"))?;
+    let snapshot: ProjectSnapshot = serde_json::from_slice(&rows)
+        .map_err(|e| CommandError::invalid_file(format!("Invalid project data: {}", e)))?;
+
+    Ok((manifest, snapshot))
+}
+
+impl Manifest for ProjectBundleManifest {
+    const KIND: &'static str = "project";
+
+    fn files(&self) -> Vec<(&str, &str)> {
+        self.files.iter().map(|f| (f.path.as_str(), f.sha256.as_str())).collect()
+    }
+
+    fn check_versions(&self) -> Result<(), CommandError> {
+        if self.format_version > PROJECT_BUNDLE_FORMAT_VERSION {
+            return Err(CommandError::invalid_file(format!(
+                "This bundle uses format version {}, but this app supports up to {}. Update the app to import it (bundle created by version {}).",
+                self.format_version, PROJECT_BUNDLE_FORMAT_VERSION, self.app_version
+            )));
+        }
+        Ok(())
+    }
+
+    /// `project.json`, `artifacts/<file>` or `videos/<id>/<file>`, anything
+    /// else is rejected
+    fn entry_path(name: &str) -> Option<PathBuf> {
+        let path = Path::new(name);
+        let components: Vec<_> = path.components().collect();
+        let valid = match components.as_slice() {
+            [Component::Normal(file)] => *file == ROWS_PATH,
+            [Component::Normal(dir), Component::Normal(_)] => *dir == ARTIFACTS_DIR,
+            [Component::Normal(dir), Component::Normal(_), Component::Normal(_)] => *dir == VIDEOS_DIR,
+            _ => false,
+        };
+        valid.then(|| path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use crate::fixtures;
+    use crate::services::database::{
+        Event, GpsPoint, Narration, ProcessingStatus, TranscriptionRow, Video, VideoStatus,
+    };
+
+    /// A project with one video of everything, its files in `dir`
+    fn snapshot(dir: &Path) -> ProjectSnapshot {
+        let cover = dir.join("cover.jpg");
+        let video = dir.join("ride.mp4");
+        std::fs::write(&cover, b"jpeg").unwrap();
+        std::fs::write(&video, b"mp4 data").unwrap();
+        let now = chrono::Utc::now();
+
+        ProjectSnapshot {
+            project: Project {
+                id: "trip".into(),
+                name: "Coast trip".into(),
+                description: None,
+                created_at: now,
+                updated_at: now,
+                video_count: 1,
+                cover_image_path: Some(cover.to_string_lossy().to_string()),
+                camera_heading_offset_deg: None,
+            },
+            videos: vec![Video {
+                id: "v1".into(),
+                project_id: "trip".into(),
+                filename: "ride.mp4".into(),
+                duration_seconds: Some(60.0),
+                fps: None,
+                width: None,
+                height: None,
+                codec: None,
+                file_size_bytes: Some(8),
+                file_path: video.to_string_lossy().to_string(),
+                camera_heading_offset_deg: None,
+                audio_path: None,
+                created_at: now,
+            }],
+            gps_points: vec![GpsPoint {
+                id: 1,
+                video_id: "v1".into(),
+                timestamp: now,
+                lat: 43.7,
+                lon: 7.4,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: None,
+            }],
+            events: vec![Event {
+                id: "e1".into(),
+                video_id: "v1".into(),
+                event_type: "poi".into(),
+                start_time_seconds: 5.0,
+                end_time_seconds: None,
+                lat: None,
+                lon: None,
+                heading_deg: None,
+                verified: true,
+                verification_mode: None,
+                truth_bundle_json: None,
+                created_at: now,
+            }],
+            transcriptions: vec![TranscriptionRow {
+                id: "t1".into(),
+                video_id: "v1".into(),
+                start_ms: 0,
+                end_ms: 2000,
+                text: "Here we go".into(),
+                language: Some("en".into()),
+            }],
+            narrations: vec![Narration {
+                id: "n1".into(),
+                project_id: Some("trip".into()),
+                video_id: Some("v1".into()),
+                model: "gemini".into(),
+                response_json: "{}".into(),
+                options_json: None,
+                generation_group: None,
+                bundle_fingerprint: None,
+                created_at: now,
+            }],
+            video_statuses: vec![VideoStatus {
+                video_id: "v1".into(),
+                status: ProcessingStatus::Complete,
+                processed_at: Some(now),
+                error: None,
+                options_json: None,
+                completed_stages: Vec::new(),
+                inputs_json: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip_with_colliding_ids() {
+        let source = fixtures::temp_dir("project");
+        let original = snapshot(&source);
+        let rows = source.join(ROWS_PATH);
+        std::fs::write(&rows, serde_json::to_vec(&original).unwrap()).unwrap();
+
+        let bundle = source.join("trip.tar");
+        let manifest = write_bundle(&bundle, &original, &rows, true).unwrap();
+        let contents: Vec<&ProjectContent> = manifest.files.iter().map(|f| &f.content).collect();
+        assert_eq!(
+            contents,
+            [&ProjectContent::Rows, &ProjectContent::Cover, &ProjectContent::Video { video_id: "v1".into() }]
+        );
+
+        let staging = fixtures::temp_dir("project");
+        let (manifest, mut restored) = unpack_bundle(&bundle, &staging).unwrap();
+        assert_eq!(restored.project.name, "Coast trip");
+        assert_eq!(restored.transcriptions[0].text, "Here we go");
+
+        // The project and its video already exist here, the rest is free
+        let taken = HashSet::from(["trip".to_string(), "v1".to_string()]);
+        let renamed = reassign_ids(&mut restored, &taken);
+        assert_eq!(renamed.len(), 2);
+        let (project_id, video_id) = (restored.project.id.clone(), restored.videos[0].id.clone());
+        assert_ne!(project_id, "trip");
+        assert_ne!(video_id, "v1");
+        assert_eq!(restored.videos[0].project_id, project_id);
+        assert_eq!(restored.gps_points[0].video_id, video_id);
+        assert_eq!(restored.events[0].video_id, video_id);
+        assert_eq!(restored.transcriptions[0].video_id, video_id);
+        assert_eq!(restored.video_statuses[0].video_id, video_id);
+        assert_eq!(restored.narrations[0].project_id.as_deref(), Some(project_id.as_str()));
+        assert_eq!(restored.narrations[0].video_id.as_deref(), Some(video_id.as_str()));
+        assert_eq!((restored.events[0].id.as_str(), restored.narrations[0].id.as_str()), ("e1", "n1"));
+
+        let data_dir = fixtures::temp_dir("project");
+        let mut installed = Vec::new();
+        install_files(&manifest, &mut restored, &renamed, &staging, &data_dir, &mut installed).unwrap();
+        assert_eq!(installed.len(), 2);
+        assert_eq!(std::fs::read(&restored.videos[0].file_path).unwrap(), b"mp4 data");
+        let cover = restored.project.cover_image_path.clone().unwrap();
+        assert!(cover.starts_with(&*data_dir.join("covers").to_string_lossy()), "{}", cover);
+        assert_eq!(std::fs::read(cover).unwrap(), b"jpeg");
+
+        for dir in [source, staging, data_dir] {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+
+    #[test]
+    fn test_tampered_bundle_is_rejected() {
+        let source = fixtures::temp_dir("project");
+        let original = snapshot(&source);
+        let rows = source.join(ROWS_PATH);
+        std::fs::write(&rows, serde_json::to_vec(&original).unwrap()).unwrap();
+
+        // Without videos only the rows and cover go in
+        let bundle = source.join("trip.tar");
+        let mut manifest = write_bundle(&bundle, &original, &rows, false).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+
+        // Rewrite the bundle with a manifest that doesn't match the rows
+        manifest.files[0].sha256 = "0".repeat(64);
+        let cover = source.join("cover.jpg");
+        bundle_archive::write_raw(&bundle, &manifest, &[(&rows, ROWS_PATH), (&cover, "artifacts/cover.jpg")]);
+
+        let staging = fixtures::temp_dir("project");
+        let err = unpack_bundle(&bundle, &staging).unwrap_err();
+        assert!(err.message.contains("checksum mismatch"), "{}", err);
+        assert_eq!(err.code, ErrorCode::InvalidFile);
+
+        assert!(ProjectBundleManifest::entry_path("../project.json").is_none());
+        assert!(ProjectBundleManifest::entry_path("videos/v1/ride.mp4").is_some());
+        assert!(ProjectBundleManifest::entry_path("videos/ride.mp4").is_none());
+
+        for dir in [source, staging] {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+}
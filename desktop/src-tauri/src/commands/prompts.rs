@@ -0,0 +1,23 @@
+//! Prompt Template Commands
+//!
+//! Viewing and customizing the prompts sent to the model. Saved templates
+//! apply from the next request on.
+
+use crate::error::CommandError;
+use crate::prompts::{self, PromptName, PromptTemplate};
+
+/// Get the template in use for `name`, customized or default
+#[tauri::command]
+pub async fn get_prompt_template(name: PromptName) -> PromptTemplate {
+    prompts::load(name)
+}
+
+/// Customize the template for `name`
+///
+/// Templates missing a required placeholder such as `{events}`, or using
+/// one the template doesn't have, are refused. `None` or blank text
+/// restores the default.
+#[tauri::command]
+pub async fn set_prompt_template(name: PromptName, text: Option<String>) -> Result<PromptTemplate, CommandError> {
+    Ok(prompts::save(name, text.as_deref())?)
+}
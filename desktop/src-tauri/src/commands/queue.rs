@@ -0,0 +1,242 @@
+//! Processing Queue Commands
+//!
+//! Queueing imported videos to be processed in the background, and
+//! following, reordering and cancelling them. Each video runs as its own
+//! `processing` job, reporting `processing-progress` and `job-status`
+//! events; the queue as a whole is reported with `processing-queue`.
+
+use serde::Serialize;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tracing::{info, warn};
+
+use crate::error::CommandError;
+use crate::jobs;
+use crate::processing_queue::{ProcessingQueue, QueueStatus, QueuedVideo, DEFAULT_CONCURRENCY};
+use crate::processor::{ProcessOptions, VideoProcessor};
+use crate::services::database::ProcessingStatus;
+use crate::services::LocalDatabase;
+use crate::settings;
+use crate::state::AppState;
+
+use super::process::{process_stored, ProcessingProgress};
+
+/// Outcome of queueing videos
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QueueResult {
+    pub queued: Vec<String>,
+    /// Already complete or already in the queue, so not queued again
+    pub skipped: Vec<String>,
+}
+
+/// Queue imported videos to be processed in the background
+///
+/// Videos already processed are skipped unless `force` is set. `options`
+/// apply to every video, and default to the ones kept in the settings. How
+/// many run at once is set with `set_processing_concurrency`.
+#[tauri::command]
+pub async fn queue_processing(
+    video_ids: Vec<String>,
+    options: Option<ProcessOptions>,
+    force: Option<bool>,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<QueueResult, CommandError> {
+    let force = force.unwrap_or(false);
+    let options = options.unwrap_or_else(|| settings::get().processing);
+    options.validate().map_err(CommandError::invalid_input)?;
+    let queue = &state.processing_queue;
+    let mut result = QueueResult::default();
+
+    let mut videos = Vec::new();
+    for video_id in video_ids {
+        let status = db.get_video_status(&video_id)
+            .await?;
+        let already_queued = queue.contains(&video_id) || videos.iter().any(|v: &QueuedVideo| v.video_id == video_id);
+        if already_queued || (status.status == ProcessingStatus::Complete && !force) {
+            result.skipped.push(video_id);
+            continue;
+        }
+        // Before it's in the queue, where it could start straight away
+        db.set_video_status(&video_id, ProcessingStatus::Queued, None, None)
+            .await?;
+        videos.push(QueuedVideo {
+            video_id,
+            options: options.clone(),
+            force,
+            previous_status: status.status,
+            previous_error: status.error,
+        });
+    }
+
+    result.queued = queue.enqueue(videos);
+    queue.persist(&db).await;
+    emit_status(&app, queue);
+    info!("Queued {} videos for processing, skipped {}", result.queued.len(), result.skipped.len());
+    Ok(result)
+}
+
+/// Get the videos in the processing queue and how far it has got
+#[tauri::command]
+pub async fn get_processing_queue(state: State<'_, Arc<AppState>>) -> Result<QueueStatus, CommandError> {
+    Ok(state.processing_queue.status())
+}
+
+/// Change the order waiting videos start in
+///
+/// Videos not listed follow those that are; running ones carry on.
+#[tauri::command]
+pub async fn reorder_processing_queue(
+    video_ids: Vec<String>,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<QueueStatus, CommandError> {
+    let queue = &state.processing_queue;
+    queue.reorder(&video_ids);
+    queue.persist(&db).await;
+    emit_status(&app, queue);
+    Ok(queue.status())
+}
+
+/// Take a video off the processing queue, stopping it if it's running
+///
+/// It goes back to the status it had before it was queued; a stopped run
+/// keeps the stages it finished for the next one to pick up after.
+#[tauri::command]
+pub async fn cancel_queued_processing(
+    video_id: String,
+    db: State<'_, LocalDatabase>,
+    state: State<'_, Arc<AppState>>,
+    app: AppHandle,
+) -> Result<(), CommandError> {
+    let queue = &state.processing_queue;
+    if let Some(video) = queue.remove_waiting(&video_id) {
+        restore_status(&db, &video).await;
+        queue.persist(&db).await;
+        emit_status(&app, queue);
+        info!("Took video {} off the processing queue", video_id);
+        return Ok(());
+    }
+    // Its job's guard takes it off the queue as it's dropped
+    match queue.running_job(&video_id) {
+        Some(job_id) => jobs::cancel(&state, &jobs::emitter(&app), &job_id),
+        None => Err(CommandError::not_found(format!("Video {} isn't in the processing queue", video_id))),
+    }
+}
+
+/// Start processing queued videos as they reach the front, beginning with
+/// the queue the last run left
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let state = app.state::<Arc<AppState>>().inner().clone();
+        let queue = state.processing_queue.clone();
+        resume(&app.state::<LocalDatabase>(), &queue).await;
+        emit_status(&app, &queue);
+
+        loop {
+            let limit = settings::get().processing_concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+            while let Some(video) = queue.start_next(limit) {
+                start_video(&app, &state, video);
+            }
+            // Woken when a video is queued or finishes, or the limit changes
+            queue.wake.notified().await;
+        }
+    });
+}
+
+/// Queue again what the last run left waiting or running
+async fn resume(db: &LocalDatabase, queue: &ProcessingQueue) {
+    let videos = ProcessingQueue::load(db).await;
+    if videos.is_empty() {
+        return;
+    }
+    for video in &videos {
+        // Left as processing if the app closed while it ran
+        if let Err(e) = db.set_video_status(&video.video_id, ProcessingStatus::Queued, None, None).await {
+            warn!("Failed to record status of video {}: {}", video.video_id, e);
+        }
+    }
+    info!("Resuming the processing queue with {} videos", videos.len());
+    queue.enqueue(videos);
+}
+
+fn start_video(app: &AppHandle, state: &Arc<AppState>, video: QueuedVideo) {
+    let queue = state.processing_queue.clone();
+    let video_id = video.video_id.clone();
+    let handle = app.clone();
+    let (job_id, _) = jobs::start(state, jobs::emitter(app), "processing", move |job| async move {
+        let app = handle;
+        let guard = QueueSlot { app: app.clone(), video: Some(video.clone()) };
+        let queue = app.state::<Arc<AppState>>().processing_queue.clone();
+        let report = |stage: &'static str, progress: f32| {
+            job.progress(progress, stage);
+            queue.set_progress(&video.video_id, progress);
+            let _ = app.emit("processing-progress", ProcessingProgress { job_id: job.job_id().to_string(), stage, progress });
+            emit_status(&app, &queue);
+        };
+
+        let db = app.state::<LocalDatabase>();
+        let processor = app.state::<Arc<VideoProcessor>>();
+        let result = process_stored(&db, &processor, &video.video_id, video.options.clone(), video.force, report).await;
+        guard.finish(result.is_ok());
+        result.map(|_| ())
+    });
+    queue.started(&video_id, &job_id);
+    emit_status(app, &queue);
+}
+
+/// A running video's place in the queue, given up however its job ends;
+/// dropped without [`QueueSlot::finish`], the job was cancelled
+struct QueueSlot {
+    app: AppHandle,
+    video: Option<QueuedVideo>,
+}
+
+impl QueueSlot {
+    fn finish(mut self, succeeded: bool) {
+        if let Some(video) = self.video.take() {
+            finished(&self.app, video, Some(succeeded));
+        }
+    }
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        if let Some(video) = self.video.take() {
+            finished(&self.app, video, None);
+        }
+    }
+}
+
+fn finished(app: &AppHandle, video: QueuedVideo, succeeded: Option<bool>) {
+    let queue = app.state::<Arc<AppState>>().processing_queue.clone();
+    queue.finish(&video.video_id, succeeded);
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let db = app.state::<LocalDatabase>();
+        if succeeded.is_none() {
+            restore_status(&db, &video).await;
+            info!("Stopped processing queued video {}", video.video_id);
+        }
+        queue.persist(&db).await;
+        emit_status(&app, &queue);
+    });
+}
+
+/// Put a video taken off the queue back the way it was before it was queued
+async fn restore_status(db: &LocalDatabase, video: &QueuedVideo) {
+    let status = match video.previous_status {
+        // Not ones a video can be left in outside the queue
+        ProcessingStatus::Queued | ProcessingStatus::Processing => ProcessingStatus::Pending,
+        status => status,
+    };
+    if let Err(e) = db.set_video_status(&video.video_id, status, video.previous_error.as_deref(), None).await {
+        warn!("Failed to record status of video {}: {}", video.video_id, e);
+    }
+}
+
+fn emit_status(app: &AppHandle, queue: &ProcessingQueue) {
+    let _ = app.emit("processing-queue", queue.status());
+}
@@ -0,0 +1,412 @@
+//! Region Bundles
+//!
+//! A bundle is a tar archive holding everything needed to use a region
+//! offline: the extract and PMTiles archive, the region's rows of the derived
+//! tables as Parquet, and a `manifest.json` describing them. Bundles let a
+//! region be moved to another machine without any network access.
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use tracing::{info, warn};
+
+use super::bundle_archive::{self, Manifest};
+use super::{
+    region_file_path_in, region_pmtiles_path_in, region_status, save_regions_to_disk,
+    tiles_dir, RegionInfo, RegionStatus, MAP_REGIONS,
+};
+use crate::error::{CommandError, ErrorCode};
+use crate::geo::GeoEngine;
+use crate::resources::{self, Priority, Resource};
+use crate::services::LocalDatabase;
+
+/// Layout of the archive itself
+pub const BUNDLE_FORMAT_VERSION: u32 = 1;
+
+/// Layout of the region tables; bump when their columns change
+pub const REGION_DATA_SCHEMA_VERSION: u32 = 1;
+
+const DATA_DIR: &str = "data";
+const TABLES_DIR: &str = "tables";
+
+/// Describes a bundle's region and files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub schema_version: u32,
+    pub region: RegionInfo,
+    /// When the bundled data was produced upstream
+    pub data_version: Option<String>,
+    pub created_at: String,
+    pub app_version: String,
+    pub files: Vec<BundleFile>,
+}
+
+/// A file in the bundle, with its path inside the archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub path: String,
+    pub content: BundleContent,
+    pub sha256: String,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BundleContent {
+    /// OSM extract (PBF, or OSM XML for custom regions)
+    Extract,
+    /// PMTiles archive
+    Tiles,
+    /// Parquet export of a region table
+    Table { name: String },
+}
+
+/// A file on disk to put into a bundle
+struct BundleSource {
+    content: BundleContent,
+    path: PathBuf,
+}
+
+/// Package a downloaded region into a bundle at `out_path`
+#[tauri::command]
+pub async fn export_region_bundle(
+    db: State<'_, LocalDatabase>,
+    region_id: String,
+    out_path: String,
+) -> Result<BundleManifest, CommandError> {
+    let region = MAP_REGIONS
+        .read()
+        .await
+        .iter()
+        .find(|r| r.id == region_id)
+        .cloned()
+        .ok_or_else(|| CommandError::not_found(format!("Region not found: {}", region_id)))?;
+
+    let dir = tiles_dir();
+    let mut sources = Vec::new();
+    for (content, path) in [
+        (BundleContent::Extract, region_file_path_in(&dir, &region.id)),
+        (BundleContent::Tiles, region_pmtiles_path_in(&dir, &region.id)),
+    ] {
+        if path.exists() {
+            sources.push(BundleSource { content, path });
+        }
+    }
+    if sources.is_empty() {
+        return Err(CommandError::new(ErrorCode::CoverageMissing, format!("Region {} has not been downloaded", region.name)));
+    }
+
+    let staging = dir.join(format!(".export-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)?;
+
+    let result = async {
+        let tables = db.export_region_tables(&region.id, &staging).await?;
+        sources.extend(tables.into_iter().map(|(name, path)| BundleSource {
+            content: BundleContent::Table { name },
+            path,
+        }));
+
+        let out = PathBuf::from(out_path.trim());
+        let _slot = resources::acquire(Resource::Io, Priority::Batch).await;
+        tokio::task::spawn_blocking(move || write_bundle(&out, &region, &sources)).await?
+    }
+    .await;
+
+    std::fs::remove_dir_all(&staging).ok();
+
+    let manifest = result?;
+    info!("Exported region {} ({} files)", manifest.region.id, manifest.files.len());
+    Ok(manifest)
+}
+
+/// Validate a bundle and install its region, replacing any existing copy
+#[tauri::command]
+pub async fn import_region_bundle(
+    db: State<'_, LocalDatabase>,
+    geo: State<'_, Arc<GeoEngine>>,
+    path: String,
+) -> Result<RegionInfo, CommandError> {
+    let bundle = PathBuf::from(path.trim());
+    if !bundle.is_file() {
+        return Err(CommandError::file_not_found(format!("File not found: {}", bundle.display())));
+    }
+
+    let dir = tiles_dir();
+    let staging = dir.join(format!(".import-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&staging)?;
+
+    let result = async {
+        let unpack_dir = staging.clone();
+        let slot = resources::acquire(Resource::Io, Priority::Batch).await;
+        let manifest = tokio::task::spawn_blocking(move || bundle_archive::unpack::<BundleManifest>(&bundle, &unpack_dir)).await??;
+        drop(slot);
+
+        // Tables first: if they fail, the previous copy of the region stays intact
+        let tables: Vec<(String, PathBuf)> = manifest
+            .files
+            .iter()
+            .filter_map(|f| match &f.content {
+                BundleContent::Table { name } => Some((name.clone(), staging.join(&f.path))),
+                _ => None,
+            })
+            .collect();
+        db.import_region_tables(&manifest.region.id, &tables).await?;
+
+        let tiles = region_pmtiles_path_in(&dir, &manifest.region.id);
+        if tiles.exists() {
+            geo.unload_region(&tiles).await;
+        }
+
+        let region = install_bundle_files(&manifest, &staging, &dir)?;
+
+        if tiles.exists() {
+            if let Err(e) = geo.load_region(&tiles).await {
+                warn!("Failed to load PMTiles for {}: {}", region.id, e);
+            }
+        }
+
+        Ok::<_, CommandError>(region)
+    }
+    .await;
+
+    std::fs::remove_dir_all(&staging).ok();
+    let region = result?;
+
+    let mut regions = MAP_REGIONS.write().await;
+    match regions.iter_mut().find(|r| r.id == region.id) {
+        Some(existing) => *existing = region.clone(),
+        None => regions.push(region.clone()),
+    }
+    save_regions_to_disk(&regions);
+
+    info!("Imported region bundle {} ({})", region.id, region.name);
+    Ok(region)
+}
+
+/// Write `sources` and their manifest to a bundle at `out`
+fn write_bundle(out: &Path, region: &RegionInfo, sources: &[BundleSource]) -> Result<BundleManifest, CommandError> {
+    let mut files = Vec::new();
+    for source in sources {
+        let file_name = source
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| CommandError::invalid_input(format!("Invalid bundle source: {:?}", source.path)))?;
+        let dir = match source.content {
+            BundleContent::Table { .. } => TABLES_DIR,
+            _ => DATA_DIR,
+        };
+        let (sha256, bytes) = bundle_archive::checksum(&source.path)?;
+        files.push(BundleFile {
+            path: format!("{}/{}", dir, file_name),
+            content: source.content.clone(),
+            sha256,
+            bytes,
+        });
+    }
+
+    let manifest = BundleManifest {
+        format_version: BUNDLE_FORMAT_VERSION,
+        schema_version: REGION_DATA_SCHEMA_VERSION,
+        region: RegionInfo {
+            status: RegionStatus::Ready,
+            ..region.clone()
+        },
+        data_version: region.last_updated.clone(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        files,
+    };
+    let entries: Vec<(&Path, &str)> = sources
+        .iter()
+        .zip(&manifest.files)
+        .map(|(source, file)| (source.path.as_path(), file.path.as_str()))
+        .collect();
+    bundle_archive::write(out, &manifest, &entries)?;
+
+    Ok(manifest)
+}
+
+impl Manifest for BundleManifest {
+    const KIND: &'static str = "region";
+
+    fn files(&self) -> Vec<(&str, &str)> {
+        self.files.iter().map(|f| (f.path.as_str(), f.sha256.as_str())).collect()
+    }
+
+    fn check_versions(&self) -> Result<(), CommandError> {
+        let check = |what: &str, found: u32, supported: u32| match found.cmp(&supported) {
+            std::cmp::Ordering::Equal => Ok(()),
+            std::cmp::Ordering::Greater => Err(CommandError::invalid_file(format!(
+                "This bundle uses {} version {}, but this app supports up to {}. Update the app to import it (bundle created by version {}).",
+                what, found, supported, self.app_version
+            ))),
+            std::cmp::Ordering::Less => Err(CommandError::invalid_file(format!(
+                "This bundle uses {} version {}, which this app no longer supports (expected {}). Re-export it from an up-to-date app.",
+                what, found, supported
+            ))),
+        };
+
+        check("bundle format", self.format_version, BUNDLE_FORMAT_VERSION)?;
+        check("data schema", self.schema_version, REGION_DATA_SCHEMA_VERSION)
+    }
+
+    /// `data/<file>` or `tables/<file>`, anything else is rejected
+    fn entry_path(name: &str) -> Option<PathBuf> {
+        let path = Path::new(name);
+        let components: Vec<_> = path.components().collect();
+        match components.as_slice() {
+            [Component::Normal(dir), Component::Normal(_)]
+                if *dir == DATA_DIR || *dir == TABLES_DIR =>
+            {
+                Some(path.to_path_buf())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Move a verified bundle's map data from `staging` into `dir`
+///
+/// Returns the region as it should be registered.
+fn install_bundle_files(manifest: &BundleManifest, staging: &Path, dir: &Path) -> Result<RegionInfo, CommandError> {
+    let region_id = &manifest.region.id;
+    std::fs::create_dir_all(dir)?;
+
+    // The region's data, fingerprinted with the checksum it was verified against
+    let mut data = None;
+    for file in &manifest.files {
+        let dest = match file.content {
+            BundleContent::Extract => region_file_path_in(dir, region_id),
+            BundleContent::Tiles => region_pmtiles_path_in(dir, region_id),
+            BundleContent::Table { .. } => continue,
+        };
+        let source = staging.join(&file.path);
+        if std::fs::rename(&source, &dest).is_err() {
+            std::fs::copy(&source, &dest)
+                .map_err(|e| CommandError::from(e).context(format!("Failed to install {}", file.path)))?;
+        }
+        if data.is_none() || file.content == BundleContent::Extract {
+            data = Some(region_status::fingerprint_as(&dest, file.sha256.clone())?);
+        }
+    }
+
+    let mut region = RegionInfo {
+        downloaded: true,
+        status: RegionStatus::Ready,
+        last_updated: manifest.data_version.clone().or_else(|| manifest.region.last_updated.clone()),
+        ..manifest.region.clone()
+    };
+    if let Some(fingerprint) = data {
+        region_status::record_data(&mut region, fingerprint);
+    }
+    Ok(region)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::part_file_path;
+    use crate::fixtures;
+
+    fn monaco() -> RegionInfo {
+        RegionInfo {
+            id: "europe/monaco".into(),
+            name: "Monaco".into(),
+            size_mb: 1,
+            downloaded: true,
+            last_updated: Some("2024-06-01T00:00:00+00:00".into()),
+            poi_count: 1200,
+            bounds: (43.72, 7.40, 43.75, 7.44),
+            ..Default::default()
+        }
+    }
+
+    /// Bundle a fake Monaco extract, tiles and POI table from `source_dir`
+    fn export_monaco(source_dir: &Path, out: &Path) -> BundleManifest {
+        let extract = region_file_path_in(source_dir, "europe/monaco");
+        let tiles = region_pmtiles_path_in(source_dir, "europe/monaco");
+        let pois = source_dir.join("pois.parquet");
+        std::fs::write(&extract, b"monaco pbf").unwrap();
+        std::fs::write(&tiles, b"monaco tiles").unwrap();
+        std::fs::write(&pois, b"monaco pois").unwrap();
+
+        let sources = [
+            BundleSource { content: BundleContent::Extract, path: extract },
+            BundleSource { content: BundleContent::Tiles, path: tiles },
+            BundleSource { content: BundleContent::Table { name: "pois".into() }, path: pois },
+        ];
+        write_bundle(out, &monaco(), &sources).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = fixtures::temp_dir("bundle");
+        let (source, staging, target) = (dir.join("source"), dir.join("staging"), dir.join("target"));
+        std::fs::create_dir_all(&source).unwrap();
+        let bundle = dir.join("monaco.gtbundle");
+
+        let exported = export_monaco(&source, &bundle);
+        assert_eq!(exported.files.len(), 3);
+        assert!(!part_file_path(&bundle).exists());
+
+        let manifest = bundle_archive::unpack::<BundleManifest>(&bundle, &staging).unwrap();
+        assert_eq!(manifest.files, exported.files);
+        assert_eq!(manifest.data_version.as_deref(), Some("2024-06-01T00:00:00+00:00"));
+        assert_eq!(std::fs::read(staging.join("tables/pois.parquet")).unwrap(), b"monaco pois");
+
+        let region = install_bundle_files(&manifest, &staging, &target).unwrap();
+        assert_eq!(region.id, "europe/monaco");
+        assert_eq!(region.status, RegionStatus::Ready);
+        assert!(region.downloaded);
+        assert_eq!(region.size_bytes, Some(b"monaco pbf".len() as u64));
+        assert_eq!(region.bounds, monaco().bounds);
+        assert_eq!(
+            std::fs::read(region_file_path_in(&target, "europe/monaco")).unwrap(),
+            b"monaco pbf"
+        );
+        assert_eq!(
+            std::fs::read(region_pmtiles_path_in(&target, "europe/monaco")).unwrap(),
+            b"monaco tiles"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rejects_incompatible_and_corrupt_bundles() {
+        let dir = fixtures::temp_dir("bundle");
+        let source = dir.join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        let mut manifest = export_monaco(&source, &dir.join("monaco.gtbundle"));
+
+        manifest.schema_version = REGION_DATA_SCHEMA_VERSION + 1;
+        assert!(manifest.check_versions().unwrap_err().message.contains("Update the app"));
+        manifest.schema_version = REGION_DATA_SCHEMA_VERSION;
+        manifest.format_version = 0;
+        assert!(manifest.check_versions().unwrap_err().message.contains("Re-export"));
+
+        // Same manifest, different extract contents
+        let tampered = dir.join("tampered.gtbundle");
+        let extract = region_file_path_in(&source, "europe/monaco");
+        let original = write_bundle(&tampered, &monaco(), &[BundleSource {
+            content: BundleContent::Extract,
+            path: extract.clone(),
+        }])
+        .unwrap();
+        std::fs::write(&extract, b"monaco pbf, edited").unwrap();
+        bundle_archive::write_raw(&tampered, &original, &[(&extract, &original.files[0].path)]);
+
+        let err = bundle_archive::unpack::<BundleManifest>(&tampered, &dir.join("staging")).unwrap_err();
+        assert!(err.message.contains("checksum mismatch"), "{}", err);
+        assert_eq!(err.code, ErrorCode::InvalidFile);
+
+        assert_eq!(BundleManifest::entry_path("data/../../etc/passwd"), None);
+        assert_eq!(BundleManifest::entry_path("/tmp/x"), None);
+        assert!(BundleManifest::entry_path("tables/pois.parquet").is_some());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,322 @@
+//! Region Status
+//!
+//! Lifecycle of a region's map data, persisted with the region metadata.
+//! All status changes go through [`RegionStatus::can_transition_to`], and
+//! stored statuses are reconciled against the files on disk at startup.
+//! The size, modification time and checksum of a region's data file are
+//! recorded when it's stored, so data that was truncated or replaced since
+//! doesn't count as downloaded.
+
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use super::local_regions::sha256_file;
+use super::{part_file_path, region_file_path_in, region_pmtiles_path_in, RegionInfo};
+
+/// Where a region is in its download lifecycle
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RegionStatus {
+    #[default]
+    NotDownloaded,
+    /// Waiting for another download to finish
+    Queued,
+    Downloading,
+    /// Download finished, being verified and registered
+    Processing,
+    Ready,
+    /// `resumable` when a partial download is kept on disk
+    Failed {
+        reason: String,
+        #[serde(default)]
+        resumable: bool,
+    },
+    /// Ready, with a newer extract published upstream
+    UpdateAvailable,
+}
+
+impl RegionStatus {
+    /// Failed status for a download that can pick up where it stopped
+    pub fn interrupted(reason: impl Into<String>) -> Self {
+        Self::Failed { reason: reason.into(), resumable: true }
+    }
+
+    /// Failed status for a download that has to start over
+    pub fn failed(reason: impl Into<String>) -> Self {
+        Self::Failed { reason: reason.into(), resumable: false }
+    }
+
+    /// Whether the region has complete data that can be used
+    pub fn has_data(&self) -> bool {
+        matches!(self, Self::Ready | Self::UpdateAvailable)
+    }
+
+    /// Whether moving from this status to `next` is allowed
+    ///
+    /// Deleting data (`NotDownloaded`) is always allowed, as is replacing a
+    /// status with another of the same kind (e.g. a new failure reason).
+    pub fn can_transition_to(&self, next: &RegionStatus) -> bool {
+        use RegionStatus::*;
+
+        if std::mem::discriminant(self) == std::mem::discriminant(next) {
+            return true;
+        }
+
+        matches!(
+            (self, next),
+            (_, NotDownloaded)
+                | (NotDownloaded, Queued | Downloading)
+                | (Queued, Downloading | Failed { .. })
+                // An abandoned update leaves the previous data in place
+                | (Downloading, Processing | Failed { .. } | Ready)
+                | (Processing, Ready | Failed { .. })
+                | (Ready, Queued | Downloading | UpdateAvailable)
+                | (UpdateAvailable, Queued | Downloading | Ready)
+                // Data found damaged on disk
+                | (Ready | UpdateAvailable, Failed { .. })
+                | (Failed { .. }, Queued | Downloading)
+        )
+    }
+}
+
+/// Status implied by the files of `region` in `dir`
+///
+/// Used at startup, when no download can be running: anything caught
+/// mid-download by a crash becomes resumable or falls back to the complete
+/// data still on disk.
+pub(super) fn reconcile_status(region: &RegionInfo, dir: &Path) -> RegionStatus {
+    let data_path = region_file_path_in(dir, &region.id);
+    let has_data = data_path.exists() || region_pmtiles_path_in(dir, &region.id).exists();
+    let has_partial = part_file_path(&data_path).exists();
+
+    match (&region.status, has_data, has_partial) {
+        (RegionStatus::UpdateAvailable, true, _) => RegionStatus::UpdateAvailable,
+        (_, true, _) => RegionStatus::Ready,
+        (RegionStatus::Failed { reason, .. }, false, true) => RegionStatus::interrupted(reason.clone()),
+        (_, false, true) => RegionStatus::interrupted("Download was interrupted"),
+        // Keep the reason a download failed for good
+        (RegionStatus::Failed { reason, resumable: false }, false, false) => RegionStatus::failed(reason.clone()),
+        (_, false, false) => RegionStatus::NotDownloaded,
+    }
+}
+
+/// The file holding a region's data: its extract, or for regions imported
+/// as tiles only, its PMTiles archive
+pub(super) fn data_file_path(region_id: &str, dir: &Path) -> Option<PathBuf> {
+    [region_file_path_in(dir, region_id), region_pmtiles_path_in(dir, region_id)]
+        .into_iter()
+        .find(|path| path.exists())
+}
+
+/// What a data file was like when it was stored
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct DataFingerprint {
+    pub bytes: u64,
+    pub modified_ms: Option<u64>,
+    pub sha256: String,
+}
+
+/// Fingerprint of the file at `path`; reads all of it
+pub(super) fn fingerprint(path: &Path) -> std::io::Result<DataFingerprint> {
+    fingerprint_as(path, sha256_file(path)?)
+}
+
+/// Fingerprint of the file at `path`, already known to hash to `sha256`
+pub(super) fn fingerprint_as(path: &Path, sha256: String) -> std::io::Result<DataFingerprint> {
+    let metadata = std::fs::metadata(path)?;
+    Ok(DataFingerprint {
+        bytes: metadata.len(),
+        modified_ms: modified_ms(&metadata),
+        sha256,
+    })
+}
+
+fn modified_ms(metadata: &std::fs::Metadata) -> Option<u64> {
+    let since_epoch = metadata.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(since_epoch.as_millis() as u64)
+}
+
+/// Record `fingerprint` as the region's data
+pub(super) fn record_data(region: &mut RegionInfo, fingerprint: DataFingerprint) {
+    region.downloaded = true;
+    region.size_bytes = Some(fingerprint.bytes);
+    region.data_modified_ms = fingerprint.modified_ms;
+    region.checksum = Some(fingerprint.sha256);
+}
+
+/// Forget a region's data after it was deleted
+pub(super) fn clear_data(region: &mut RegionInfo) {
+    region.downloaded = false;
+    region.last_updated = None;
+    region.size_bytes = None;
+    region.data_modified_ms = None;
+    region.checksum = None;
+}
+
+/// How a region's data file compares with what was recorded
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum DataCheck {
+    Missing,
+    Intact,
+    /// Touched since it was recorded, but with the same contents
+    Touched { modified_ms: Option<u64> },
+    Damaged(String),
+}
+
+/// Check the data of `region` in `dir` against its recorded fingerprint
+///
+/// A file of the recorded size and modification time is taken as intact
+/// without reading it; one modified since is hashed. Regions stored before
+/// fingerprints were recorded only need the file to exist.
+pub(super) fn check_data(region: &RegionInfo, dir: &Path) -> DataCheck {
+    let Some(path) = data_file_path(&region.id, dir) else {
+        return DataCheck::Missing;
+    };
+    let Ok(metadata) = std::fs::metadata(&path) else {
+        return DataCheck::Missing;
+    };
+
+    if let Some(expected) = region.size_bytes {
+        if metadata.len() != expected {
+            return DataCheck::Damaged(format!(
+                "Map data is {} bytes instead of {}; download it again",
+                metadata.len(),
+                expected
+            ));
+        }
+    }
+
+    let modified = modified_ms(&metadata);
+    if region.data_modified_ms.is_none() || modified == region.data_modified_ms {
+        return DataCheck::Intact;
+    }
+    match (&region.checksum, sha256_file(&path)) {
+        (Some(expected), Ok(actual)) if *expected != actual => {
+            DataCheck::Damaged("Map data has changed since it was downloaded; download it again".to_string())
+        }
+        (_, Err(e)) => DataCheck::Damaged(format!("Map data can't be read: {}", e)),
+        _ => DataCheck::Touched { modified_ms: modified },
+    }
+}
+
+/// Bring `downloaded` (and for damaged data, the status) in line with
+/// `check`, returning whether anything changed
+pub(super) fn apply_data_check(region: &mut RegionInfo, check: DataCheck) -> bool {
+    let before = (region.downloaded, region.data_modified_ms, region.status.clone());
+    match check {
+        DataCheck::Missing => region.downloaded = false,
+        DataCheck::Intact => region.downloaded = true,
+        DataCheck::Touched { modified_ms } => {
+            region.downloaded = true;
+            region.data_modified_ms = modified_ms;
+        }
+        DataCheck::Damaged(reason) => {
+            region.downloaded = false;
+            let failed = RegionStatus::failed(reason);
+            if region.status.has_data() && region.status.can_transition_to(&failed) {
+                region.status = failed;
+            }
+        }
+    }
+    before != (region.downloaded, region.data_modified_ms, region.status.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_transitions() {
+        use RegionStatus::*;
+
+        assert!(NotDownloaded.can_transition_to(&Downloading));
+        assert!(Downloading.can_transition_to(&Processing));
+        assert!(Processing.can_transition_to(&Ready));
+        assert!(Ready.can_transition_to(&UpdateAvailable));
+        assert!(RegionStatus::failed("404").can_transition_to(&Downloading));
+        assert!(RegionStatus::failed("404").can_transition_to(&RegionStatus::interrupted("Paused")));
+        assert!(Ready.can_transition_to(&NotDownloaded));
+
+        assert!(!NotDownloaded.can_transition_to(&Ready));
+        assert!(!Queued.can_transition_to(&Ready));
+        assert!(!Ready.can_transition_to(&Processing));
+        assert!(!RegionStatus::failed("404").can_transition_to(&Ready));
+    }
+
+    #[test]
+    fn test_reconcile_against_files() {
+        let dir = fixtures::temp_dir("status");
+        let data = region_file_path_in(&dir, "europe/monaco");
+
+        let mut region = RegionInfo {
+            id: "europe/monaco".into(),
+            status: RegionStatus::Downloading,
+            ..Default::default()
+        };
+
+        // Crash mid-download: the partial file must not read as ready
+        std::fs::write(part_file_path(&data), b"partial").unwrap();
+        assert_eq!(reconcile_status(&region, &dir), RegionStatus::interrupted("Download was interrupted"));
+
+        // Crash mid-update: the previous complete file is still usable
+        std::fs::write(&data, b"pbf").unwrap();
+        assert_eq!(reconcile_status(&region, &dir), RegionStatus::Ready);
+
+        region.status = RegionStatus::Ready;
+        std::fs::remove_file(&data).unwrap();
+        std::fs::remove_file(part_file_path(&data)).unwrap();
+        assert_eq!(reconcile_status(&region, &dir), RegionStatus::NotDownloaded);
+
+        region.status = RegionStatus::failed("Server returned 404");
+        assert_eq!(reconcile_status(&region, &dir), RegionStatus::failed("Server returned 404"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_data_checked_against_fingerprint() {
+        let dir = fixtures::temp_dir("status");
+        let data = region_file_path_in(&dir, "europe/monaco");
+        let mut region = RegionInfo {
+            id: "europe/monaco".into(),
+            status: RegionStatus::Ready,
+            ..Default::default()
+        };
+        assert_eq!(check_data(&region, &dir), DataCheck::Missing);
+
+        // Stored before fingerprints were recorded: existing is enough
+        std::fs::write(&data, b"pbf data").unwrap();
+        assert_eq!(check_data(&region, &dir), DataCheck::Intact);
+
+        record_data(&mut region, fingerprint(&data).unwrap());
+        assert_eq!(region.size_bytes, Some(8));
+        assert_eq!(check_data(&region, &dir), DataCheck::Intact);
+
+        // Same contents written again only moves the recorded time
+        region.data_modified_ms = Some(0);
+        std::fs::write(&data, b"pbf data").unwrap();
+        let check = check_data(&region, &dir);
+        assert!(matches!(check, DataCheck::Touched { .. }), "{:?}", check);
+        assert!(apply_data_check(&mut region, check));
+        assert_eq!(check_data(&region, &dir), DataCheck::Intact);
+
+        // A truncated file or different contents no longer count
+        std::fs::write(&data, b"pbf").unwrap();
+        let check = check_data(&region, &dir);
+        assert!(matches!(check, DataCheck::Damaged(_)), "{:?}", check);
+        std::fs::write(&data, b"PBF DATA").unwrap();
+        region.data_modified_ms = Some(0);
+        let check = check_data(&region, &dir);
+        assert!(matches!(check, DataCheck::Damaged(_)), "{:?}", check);
+
+        assert!(apply_data_check(&mut region, check));
+        assert!(!region.downloaded);
+        assert!(matches!(region.status, RegionStatus::Failed { resumable: false, .. }));
+
+        clear_data(&mut region);
+        assert_eq!((region.size_bytes, region.checksum.as_deref()), (None, None));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
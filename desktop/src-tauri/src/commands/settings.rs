@@ -0,0 +1,523 @@
+//! Settings Commands
+//!
+//! Tauri commands for reading and changing user settings.
+
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::State;
+use tracing::{info, warn};
+
+use crate::error::{CommandError, ErrorCode};
+use crate::geo::GeoEngine;
+use crate::gemini::{
+    self, GeminiClient, GeminiError, GeminiModelInfo, GeminiModels, GeminiPurpose, NetworkSettings, RetryPolicy,
+    SafetySettings,
+};
+use crate::llm::{LlmBackend, LlmEngine, LocalBackend, RoutedBackend, Sampling};
+use crate::llm_cache::{LlmCache, LlmUsage};
+use crate::llm_queue::{self, LlmQueueStatus, RateLimit};
+use crate::local_llm::LocalLlmSettings;
+use crate::logging;
+use crate::memory_cache::CacheLimits;
+use crate::narrative::NarrationChunking;
+use crate::processing_queue::DEFAULT_CONCURRENCY;
+use crate::processor::ProcessOptions;
+use crate::resources::{Resource, ResourceLimits, ResourceStatus};
+use crate::secrets::{self, KeySource};
+use crate::services::data_manager::ConnectivityMode;
+use crate::services::ffmpeg::ImageFormat;
+use crate::services::sync::InterpolationPolicy;
+use crate::services::mirrors;
+use crate::settings::{self, AppSettings};
+use crate::state::AppState;
+use crate::updater::UpdatePolicy;
+
+/// Get the current user settings
+#[tauri::command]
+pub async fn get_settings() -> AppSettings {
+    settings::get()
+}
+
+/// Change several settings at once, e.g. `{"processing": {"transcription": false}}`
+///
+/// `changes` is merged into the settings, `null` restoring a setting's
+/// default. Nothing is changed unless every setting is still valid
+/// afterwards. Applies straight away, as the commands for each setting do.
+#[tauri::command]
+pub async fn update_settings(
+    changes: serde_json::Value,
+    state: State<'_, Arc<AppState>>,
+    geo: State<'_, Arc<GeoEngine>>,
+) -> Result<AppSettings, CommandError> {
+    let mut before = None;
+    let after = settings::try_update(|s| {
+        before = Some(s.clone());
+        *s = settings::patched(s, changes)?;
+        Ok(())
+    })?;
+    if let Some(before) = before {
+        apply(&before, &after, &state, &geo).await;
+    }
+    info!("Settings updated");
+    Ok(after)
+}
+
+/// Restore the default settings
+///
+/// What was timed on this machine for processing estimates is kept, and
+/// the stored Gemini API key is left alone.
+#[tauri::command]
+pub async fn reset_settings(state: State<'_, Arc<AppState>>, geo: State<'_, Arc<GeoEngine>>) -> Result<AppSettings, CommandError> {
+    let before = settings::get();
+    let after = settings::reset();
+    apply(&before, &after, &state, &geo).await;
+    info!("Settings reset to their defaults");
+    Ok(after)
+}
+
+/// Put changed settings into effect where they're read once and held on to
+async fn apply(before: &AppSettings, after: &AppSettings, state: &AppState, geo: &GeoEngine) {
+    state.resources.set_limits(after.resource_limits);
+    if before.memory_cache != after.memory_cache {
+        state.truth_cache.set_limits(after.memory_cache);
+        state.enrich_cache.set_limits(after.memory_cache);
+    }
+    if before.gemini_rate_limit != after.gemini_rate_limit {
+        llm_queue::shared().set_limits(after.gemini_rate_limit);
+    }
+    if before.processing_concurrency != after.processing_concurrency {
+        state.processing_queue.wake.notify_one();
+    }
+    super::DOWNLOAD_RATE_LIMIT.store(after.download_rate_limit, std::sync::atomic::Ordering::Relaxed);
+    if before.log_level != after.log_level {
+        if let Err(e) = logging::set_filter(after.log_level.as_deref()) {
+            warn!("Failed to apply the log level: {}", e);
+        }
+    }
+    if after.connectivity_mode == ConnectivityMode::Offline {
+        geo.unload_remote_regions().await;
+    }
+}
+
+/// Set the maximum number of concurrent FFmpeg/Whisper processes
+///
+/// These are the resource governor's CPU slots; `None` restores the
+/// CPU-based default. Returns the effective limit.
+#[tauri::command]
+pub async fn set_sidecar_concurrency(limit: Option<usize>, state: State<'_, Arc<AppState>>) -> Result<usize, CommandError> {
+    if limit == Some(0) {
+        return Err(CommandError::invalid_input("Limit must be at least 1"));
+    }
+
+    let settings = settings::update(|s| s.resource_limits.cpu = limit);
+
+    let effective = settings.resource_limits.effective(Resource::Cpu);
+    state.resources.set_limit(Resource::Cpu, effective);
+    info!("Sidecar concurrency set to {}", effective);
+
+    Ok(state.resources.limit(Resource::Cpu))
+}
+
+/// Set the slots for CPU-heavy work, IO-heavy work and network transfers
+///
+/// Limits left out restore this machine's defaults. Work already running
+/// carries on when a limit is lowered; new work waits until it's under it.
+#[tauri::command]
+pub async fn set_resource_limits(limits: ResourceLimits, state: State<'_, Arc<AppState>>) -> Result<AppSettings, CommandError> {
+    limits.validate().map_err(CommandError::invalid_input)?;
+
+    state.resources.set_limits(limits);
+    info!("Resource limits set to {:?}", limits);
+    Ok(settings::update(|s| s.resource_limits = limits))
+}
+
+/// Get how many slots of each kind of heavy work are taken, and how much
+/// work is waiting for one
+#[tauri::command]
+pub async fn get_resource_status(state: State<'_, Arc<AppState>>) -> Result<ResourceStatus, CommandError> {
+    Ok(state.resources.status())
+}
+
+/// Set the default image format for captured frames and thumbnails
+#[tauri::command]
+pub async fn set_thumbnail_format(format: ImageFormat) -> AppSettings {
+    info!("Thumbnail format set to {:?}", format);
+    settings::update(|s| s.thumbnail_format = format)
+}
+
+/// Set the alternate servers used when a region download from Geofabrik fails
+///
+/// Each entry is a URL template containing `{path}`, `{id}` or `{name}`,
+/// e.g. `https://mirror.example/osm/{path}-latest.osm.pbf`. Order matters.
+#[tauri::command]
+pub async fn set_download_mirrors(urls: Vec<String>) -> Result<AppSettings, CommandError> {
+    let urls: Vec<String> = urls
+        .into_iter()
+        .map(|u| u.trim().to_string())
+        .filter(|u| !u.is_empty())
+        .collect();
+
+    for url in &urls {
+        mirrors::validate_template(url)?;
+    }
+
+    info!("Download mirrors set to {:?}", urls);
+    Ok(settings::update(|s| s.download_mirrors = urls))
+}
+
+/// Get the background region update policy
+#[tauri::command]
+pub async fn get_update_policy() -> UpdatePolicy {
+    settings::get().update_policy
+}
+
+/// Set the background region update policy
+///
+/// Takes effect on the scheduler's next wake-up.
+#[tauri::command]
+pub async fn set_update_policy(policy: UpdatePolicy) -> Result<UpdatePolicy, CommandError> {
+    policy.validate().map_err(CommandError::invalid_input)?;
+
+    info!("Update policy set to {:?}", policy);
+    Ok(settings::update(|s| s.update_policy = policy).update_policy)
+}
+
+/// Set whether online services and remote map data may be used
+///
+/// Going offline also unloads the remote map archives already loaded, so
+/// they aren't read from again.
+#[tauri::command]
+pub async fn set_connectivity_mode(mode: ConnectivityMode, geo: State<'_, Arc<GeoEngine>>) -> Result<AppSettings, CommandError> {
+    info!("Connectivity mode set to {:?}", mode);
+    let after = settings::update(|s| s.connectivity_mode = mode);
+    if mode == ConnectivityMode::Offline {
+        geo.unload_remote_regions().await;
+    }
+    Ok(after)
+}
+
+/// Set how many online calls one enrichment run may make, or `None` for the default
+///
+/// Points a run reaches once they're spent are left uncovered rather than
+/// looked up online.
+#[tauri::command]
+pub async fn set_enrichment_call_budget(budget: Option<usize>) -> AppSettings {
+    info!("Enrichment call budget set to {:?}", budget);
+    settings::update(|s| s.enrichment_call_budget = budget)
+}
+
+/// Turn the experimental sun-based clock offset suggestion on or off
+#[tauri::command]
+pub async fn set_experimental_sun_sync(enabled: bool) -> AppSettings {
+    info!("Experimental sun sync {}", if enabled { "enabled" } else { "disabled" });
+    settings::update(|s| s.experimental_sun_sync = enabled)
+}
+
+/// Set how far GPS positions may be interpolated or extrapolated
+///
+/// Applies to videos processed or re-synced afterwards.
+#[tauri::command]
+pub async fn set_interpolation_policy(policy: InterpolationPolicy) -> Result<AppSettings, CommandError> {
+    policy.validate().map_err(CommandError::invalid_input)?;
+
+    info!("Interpolation policy set to {:?}", policy);
+    Ok(settings::update(|s| s.interpolation = policy))
+}
+
+/// Set how much each in-memory cache may hold, and for how long
+///
+/// Applies straight away, evicting what no longer fits.
+#[tauri::command]
+pub async fn set_memory_cache_limits(limits: CacheLimits, state: State<'_, Arc<AppState>>) -> Result<AppSettings, CommandError> {
+    limits.validate().map_err(CommandError::invalid_input)?;
+    info!("Memory cache limits set to {:?}", limits);
+    state.truth_cache.set_limits(limits);
+    state.enrich_cache.set_limits(limits);
+    Ok(settings::update(|s| s.memory_cache = limits))
+}
+
+/// Set how videos are processed when no options are given
+///
+/// The audio track and sync offset belong to one video, so they can't be kept here.
+#[tauri::command]
+pub async fn set_processing_options(options: ProcessOptions) -> Result<AppSettings, CommandError> {
+    options.validate().map_err(CommandError::invalid_input)?;
+    if options.audio_stream_index.is_some() || options.sync_offset_seconds.is_some() {
+        return Err(CommandError::invalid_input("The audio track and sync offset are chosen per video, not kept as defaults"));
+    }
+
+    info!("Processing options set to {:?}", options);
+    Ok(settings::update(|s| s.processing = options))
+}
+
+/// Set how many queued videos are processed at once, or `None` for one at a time
+///
+/// Raising it starts waiting videos straight away; lowering it lets running
+/// ones finish. Returns the effective limit.
+#[tauri::command]
+pub async fn set_processing_concurrency(limit: Option<usize>, state: State<'_, Arc<AppState>>) -> Result<usize, CommandError> {
+    if limit == Some(0) {
+        return Err(CommandError::invalid_input("Limit must be at least 1"));
+    }
+
+    settings::update(|s| s.processing_concurrency = limit);
+    let effective = limit.unwrap_or(DEFAULT_CONCURRENCY);
+    info!("Processing concurrency set to {}", effective);
+    state.processing_queue.wake.notify_one();
+
+    Ok(effective)
+}
+
+/// Set how much of a long video is narrated in each request
+///
+/// Pro models have room for larger chunks, which keep more of the trip in view at once.
+#[tauri::command]
+pub async fn set_narration_chunking(chunking: NarrationChunking) -> Result<AppSettings, CommandError> {
+    chunking.validate().map_err(CommandError::invalid_input)?;
+
+    info!("Narration chunking set to {:?}", chunking);
+    Ok(settings::update(|s| s.narration_chunking = chunking))
+}
+
+/// Set how freely narrations are written, from 0 to 1, or `None` for the model's defaults
+///
+/// Maps onto the temperature and top-p of narrations that don't set them;
+/// see [`Sampling::from_creativity`].
+#[tauri::command]
+pub async fn set_narration_creativity(creativity: Option<f32>) -> Result<AppSettings, CommandError> {
+    if creativity.is_some_and(|c| !(0.0..=1.0).contains(&c)) {
+        return Err(CommandError::invalid_input("Creativity must be from 0 to 1"));
+    }
+
+    info!("Narration creativity set to {:?} ({:?})", creativity, creativity.map(Sampling::from_creativity));
+    Ok(settings::update(|s| s.narration_creativity = creativity))
+}
+
+/// Set how rate-limited or overloaded Gemini requests are retried
+#[tauri::command]
+pub async fn set_gemini_retry_policy(policy: RetryPolicy) -> Result<AppSettings, CommandError> {
+    policy.validate().map_err(CommandError::invalid_input)?;
+
+    info!("Gemini retry policy set to {:?}", policy);
+    Ok(settings::update(|s| s.gemini_retry = policy))
+}
+
+/// Set the Gemini request rate and concurrency caps
+///
+/// Requests already queued are re-checked against the new limits.
+#[tauri::command]
+pub async fn set_gemini_rate_limit(limit: RateLimit) -> Result<AppSettings, CommandError> {
+    limit.validate().map_err(CommandError::invalid_input)?;
+
+    llm_queue::shared().set_limits(limit);
+    Ok(settings::update(|s| s.gemini_rate_limit = limit))
+}
+
+/// Set how readily Gemini blocks responses in each harm category
+#[tauri::command]
+pub async fn set_gemini_safety(safety: SafetySettings) -> AppSettings {
+    info!("Gemini safety settings set to {:?}", safety);
+    settings::update(|s| s.gemini_safety = safety)
+}
+
+/// Get how many Gemini requests are queued and in flight
+#[tauri::command]
+pub async fn get_llm_queue_status() -> LlmQueueStatus {
+    llm_queue::shared().status()
+}
+
+/// Choose whether narration and enrichment use Gemini or a local model
+///
+/// `auto` uses the local model when offline or without a Gemini API key.
+#[tauri::command]
+pub async fn set_llm_engine(engine: LlmEngine) -> AppSettings {
+    info!("LLM engine set to {:?}", engine);
+    settings::update(|s| s.llm_engine = engine)
+}
+
+/// Set the local model server (Ollama or llama.cpp) and model to use
+#[tauri::command]
+pub async fn set_local_llm(local: LocalLlmSettings) -> Result<AppSettings, CommandError> {
+    local.validate().map_err(CommandError::invalid_input)?;
+
+    info!("Local model set to {} at {} ({}s timeout)", local.model, local.url, local.timeout_secs);
+    Ok(settings::update(|s| s.local_llm = local))
+}
+
+/// Get Gemini request and response cache statistics
+#[tauri::command]
+pub async fn get_llm_usage(cache: State<'_, Arc<LlmCache>>) -> Result<LlmUsage, CommandError> {
+    Ok(cache.usage().await?)
+}
+
+/// Remove every cached Gemini response; returns how many were removed
+#[tauri::command]
+pub async fn clear_llm_cache(cache: State<'_, Arc<LlmCache>>) -> Result<usize, CommandError> {
+    Ok(cache.clear().await?)
+}
+
+/// Set the timeouts and proxy for Gemini requests
+#[tauri::command]
+pub async fn set_gemini_network(network: NetworkSettings) -> Result<AppSettings, CommandError> {
+    let network = NetworkSettings {
+        proxy_url: network.proxy_url.map(|u| u.trim().to_string()).filter(|u| !u.is_empty()),
+        ..network
+    };
+    network.validate().map_err(CommandError::invalid_input)?;
+
+    info!(
+        "Gemini network set to {}s connect, {}s request timeout, proxy: {}",
+        network.connect_timeout_secs,
+        network.request_timeout_secs,
+        if network.proxy_url.is_some() { "custom" } else { "from environment" }
+    );
+    Ok(settings::update(|s| s.gemini_network = network))
+}
+
+/// Outcome of a test request to the LLM engine in use
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmConnectionTest {
+    /// `gemini` or `local`
+    pub engine: &'static str,
+    pub model: String,
+    pub ok: bool,
+    /// Time until the response or the failure
+    pub latency_ms: u64,
+    /// Kind of failure, e.g. `timeout` or `invalid_key`
+    pub error_kind: Option<&'static str>,
+    /// What went wrong and what to do about it
+    pub message: Option<String>,
+}
+
+/// Send a minimal request to the LLM engine the settings select and time it
+#[tauri::command]
+pub async fn test_llm_connection() -> LlmConnectionTest {
+    let backend = RoutedBackend::new(
+        Arc::new(GeminiClient::new_for(GeminiPurpose::Enrichment)),
+        Arc::new(LocalBackend::new()),
+    );
+    let (engine, model) = (backend.engine(), backend.model());
+
+    let started = Instant::now();
+    let result = backend.generate_multimodal("Reply with the word OK.", vec![], None, false).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(_) => {
+            info!("LLM connection test succeeded ({} {}, {} ms)", engine, model, latency_ms);
+            LlmConnectionTest { engine, model, ok: true, latency_ms, error_kind: None, message: None }
+        }
+        Err(e) => {
+            warn!("LLM connection test failed ({} {}): {:?}", engine, model, e);
+            LlmConnectionTest {
+                engine,
+                model,
+                ok: false,
+                latency_ms,
+                error_kind: Some(e.kind()),
+                message: Some(e.to_string()),
+            }
+        }
+    }
+}
+
+/// List the Gemini models the API key can generate content with
+#[tauri::command]
+pub async fn list_gemini_models() -> Result<Vec<GeminiModelInfo>, CommandError> {
+    Ok(gemini::list_models().await?)
+}
+
+/// Set the Gemini model used for narration, enrichment and vision requests
+///
+/// Every model is checked against the models the API key can use first.
+#[tauri::command]
+pub async fn set_gemini_models(models: GeminiModels) -> Result<AppSettings, CommandError> {
+    let models = GeminiModels {
+        narration_model: models.narration_model.trim().to_string(),
+        enrichment_model: models.enrichment_model.trim().to_string(),
+        vision_model: models.vision_model.trim().to_string(),
+    };
+
+    let available = gemini::list_models()
+        .await
+        .map_err(|e| CommandError::from(e).context("Couldn't check the available models"))?;
+
+    for model in [&models.narration_model, &models.enrichment_model, &models.vision_model] {
+        if !available.iter().any(|m| m.id == *model) {
+            return Err(CommandError::invalid_input(format!(
+                "Unknown Gemini model '{}'; pick one from the model list",
+                model
+            )));
+        }
+    }
+
+    info!("Gemini models set to {:?}", models);
+    Ok(settings::update(|s| s.gemini_models = models))
+}
+
+/// Whether a Gemini API key is configured, without revealing it
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKeyStatus {
+    pub configured: bool,
+    /// Last characters of the key, e.g. `••••abcd`
+    pub masked: Option<String>,
+    pub source: Option<KeySource>,
+    /// `None` when the key couldn't be checked (e.g. offline)
+    pub valid: Option<bool>,
+    /// Why the key is invalid or couldn't be checked
+    pub message: Option<String>,
+}
+
+impl ApiKeyStatus {
+    fn current(check: Option<&Result<Vec<GeminiModelInfo>, GeminiError>>) -> Self {
+        let key = secrets::gemini_api_key();
+        let (valid, message) = match check {
+            Some(Ok(_)) => (Some(true), None),
+            Some(Err(GeminiError::InvalidKey)) => (Some(false), Some(GeminiError::InvalidKey.to_string())),
+            Some(Err(e)) => (None, Some(e.to_string())),
+            None => (None, None),
+        };
+
+        Self {
+            configured: key.is_some(),
+            masked: key.as_ref().map(|(k, _)| secrets::mask_key(k)),
+            source: key.map(|(_, source)| source),
+            valid,
+            message,
+        }
+    }
+}
+
+/// Get whether a Gemini API key is set and whether the API accepts it
+#[tauri::command]
+pub async fn get_gemini_api_key_status() -> ApiKeyStatus {
+    match secrets::gemini_api_key() {
+        Some((key, _)) => ApiKeyStatus::current(Some(&gemini::list_models_with_key(&key).await)),
+        None => ApiKeyStatus::current(None),
+    }
+}
+
+/// Check a Gemini API key against the API and store it securely
+#[tauri::command]
+pub async fn set_gemini_api_key(key: String) -> Result<ApiKeyStatus, CommandError> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(CommandError::new(ErrorCode::ApiKeyMissing, "API key is empty"));
+    }
+
+    let check = gemini::list_models_with_key(key).await;
+    if let Err(e) = &check {
+        return Err(e.clone().into());
+    }
+
+    secrets::store_gemini_api_key(key)?;
+    Ok(ApiKeyStatus::current(Some(&check)))
+}
+
+/// Remove the stored Gemini API key
+#[tauri::command]
+pub async fn clear_gemini_api_key() -> ApiKeyStatus {
+    secrets::clear_gemini_api_key();
+    ApiKeyStatus::current(None)
+}
@@ -0,0 +1,333 @@
+//! Setup Check
+//!
+//! Whether what processing needs is in place: the FFmpeg, FFprobe and
+//! Whisper binaries, and that each actually runs here (a binary built for
+//! another platform, without its execute bit, or quarantined by macOS
+//! Gatekeeper fails only when started), at least one Whisper model, and
+//! data and cache directories the app can write to. Each item comes with a
+//! hint on how to fix it.
+//!
+//! The check runs at startup and its result is emitted as `setup-status`,
+//! so the UI can walk new users through setup rather than fail mid-job.
+//! A sidecar that's missing or doesn't run is offered as a download, where
+//! there's a known-good build of it for this platform.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use super::sidecars::{self, Sidecar};
+use crate::error::CommandError;
+use crate::resources::Priority;
+use crate::services::{sidecar, Ffmpeg, Whisper};
+
+/// How long a sidecar gets to answer the trivial invocation
+const RUN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How an item of the setup check stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SetupItemStatus {
+    Ok,
+    /// Not there at all
+    Missing,
+    /// There, but it doesn't work
+    Broken,
+}
+
+/// An item of the setup check
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupItem {
+    /// `ffmpeg`, `ffprobe`, `whisper`, `whisper_model`, `data_dir` or `cache_dir`
+    pub id: &'static str,
+    pub label: &'static str,
+    pub status: SetupItemStatus,
+    /// What was found, e.g. the version or the error
+    pub detail: Option<String>,
+    /// How to fix it, when it isn't ok
+    pub remediation: Option<String>,
+    /// The sidecar `download_sidecar` can fetch to fix it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download: Option<Sidecar>,
+}
+
+/// Result of the setup check, emitted as `setup-status`
+#[derive(Debug, Clone, Serialize)]
+pub struct SetupStatus {
+    /// Every item is ok
+    pub ready: bool,
+    pub os: &'static str,
+    pub arch: &'static str,
+    pub items: Vec<SetupItem>,
+}
+
+/// Check that the sidecars run, a Whisper model is installed and the app's
+/// directories are writable
+#[tauri::command]
+pub async fn run_setup_check(
+    app: AppHandle,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    whisper: State<'_, Arc<Whisper>>,
+) -> Result<SetupStatus, CommandError> {
+    Ok(check(&app, &ffmpeg, &whisper).await)
+}
+
+/// Run the setup check in the background and emit its result as `setup-status`
+pub fn check_at_startup(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let ffmpeg = app.state::<Arc<Ffmpeg>>().inner().clone();
+        let whisper = app.state::<Arc<Whisper>>().inner().clone();
+        let status = check(&app, &ffmpeg, &whisper).await;
+        if let Err(e) = app.emit("setup-status", &status) {
+            warn!("Failed to emit setup status: {}", e);
+        }
+    });
+}
+
+async fn check(app: &AppHandle, ffmpeg: &Ffmpeg, whisper: &Whisper) -> SetupStatus {
+    let slot = sidecar::acquire(Priority::Interactive).await;
+    let mut items = vec![
+        offer_download(Sidecar::Ffmpeg, check_sidecar("ffmpeg", "FFmpeg", &ffmpeg.ffmpeg_path(), &["-version"]).await),
+        offer_download(Sidecar::Ffprobe, check_sidecar("ffprobe", "FFprobe", &ffmpeg.ffprobe_path(), &["-version"]).await),
+        offer_download(Sidecar::Whisper, check_sidecar("whisper", "Whisper", &whisper.binary_path(), &["--help"]).await),
+        check_models(whisper),
+    ];
+    drop(slot);
+
+    let dirs = [
+        ("data_dir", "Data directory", app.path().app_data_dir()),
+        ("cache_dir", "Cache directory", app.path().app_cache_dir()),
+    ];
+    for (id, label, dir) in dirs {
+        items.push(match dir {
+            Ok(dir) => tauri::async_runtime::spawn_blocking(move || check_writable(id, label, &dir))
+                .await
+                .unwrap_or_else(|e| broken(id, label, e.to_string(), "Restart GeoTruth and run the check again".to_string())),
+            Err(e) => broken(id, label, e.to_string(), "This system has no standard place for app data".to_string()),
+        });
+    }
+
+    let status = SetupStatus {
+        ready: items.iter().all(|item| item.status == SetupItemStatus::Ok),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        items,
+    };
+    let failing: Vec<&str> = status.items.iter().filter(|i| i.status != SetupItemStatus::Ok).map(|i| i.id).collect();
+    if failing.is_empty() {
+        info!("Setup check passed");
+    } else {
+        warn!("Setup check found problems with: {}", failing.join(", "));
+    }
+    status
+}
+
+/// Check a sidecar is there and starts, by running it with `args`
+///
+/// Any exit code will do, as long as the process ran; one killed by a
+/// signal was likely stopped by the OS, as macOS does quarantined binaries.
+async fn check_sidecar(id: &'static str, label: &'static str, path: &Path, args: &[&str]) -> SetupItem {
+    if !path.is_file() {
+        return SetupItem {
+            id,
+            label,
+            status: SetupItemStatus::Missing,
+            detail: Some(format!("Not found at {}", path.display())),
+            remediation: Some(format!("Reinstall GeoTruth, or place the {} binary at {}", label, path.display())),
+            download: None,
+        };
+    }
+
+    let run = Command::new(path).args(args).kill_on_drop(true).output();
+    let output = match tokio::time::timeout(RUN_TIMEOUT, run).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => return broken(id, label, e.to_string(), start_remediation(path, &e)),
+        Err(_) => {
+            return broken(
+                id,
+                label,
+                format!("Didn't answer within {} seconds", RUN_TIMEOUT.as_secs()),
+                format!("Check that {} isn't blocked by security software", path.display()),
+            )
+        }
+    };
+    if output.status.code().is_none() {
+        return broken(id, label, format!("Stopped by the system ({})", output.status), killed_remediation(path));
+    }
+
+    // The first line names the version, e.g. "ffmpeg version 7.0.1 Copyright ..."
+    let text = if output.stdout.is_empty() { &output.stderr } else { &output.stdout };
+    let first_line = String::from_utf8_lossy(text).lines().next().unwrap_or_default().trim().to_string();
+    SetupItem {
+        id,
+        label,
+        status: SetupItemStatus::Ok,
+        detail: Some(if first_line.is_empty() { path.display().to_string() } else { first_line }),
+        remediation: None,
+        download: None,
+    }
+}
+
+/// Offer a download for a sidecar that isn't ok, if one can be had
+fn offer_download(sidecar: Sidecar, mut item: SetupItem) -> SetupItem {
+    if item.status != SetupItemStatus::Ok {
+        if let Some(build) = sidecars::downloadable(sidecar) {
+            item.remediation = Some(match item.remediation.take() {
+                Some(hint) if item.status == SetupItemStatus::Broken => {
+                    format!("{}, or download {} {} for this system", hint, item.label, build.version)
+                }
+                _ => format!("Download {} {} for this system", item.label, build.version),
+            });
+            item.download = Some(sidecar);
+        }
+    }
+    item
+}
+
+/// How to fix a sidecar that couldn't be started
+fn start_remediation(path: &Path, error: &std::io::Error) -> String {
+    if error.kind() == std::io::ErrorKind::PermissionDenied {
+        if cfg!(windows) {
+            format!("Allow {} to run in your security software's settings", path.display())
+        } else {
+            format!("Make it executable: chmod +x \"{}\"", path.display())
+        }
+    } else {
+        format!(
+            "It may be built for another system; reinstall the GeoTruth build for {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )
+    }
+}
+
+/// How to fix a sidecar the OS stopped as it started
+fn killed_remediation(path: &Path) -> String {
+    if cfg!(target_os = "macos") {
+        format!("macOS blocked it; remove the quarantine flag: xattr -d com.apple.quarantine \"{}\"", path.display())
+    } else {
+        format!("Check that {} isn't blocked by security software", path.display())
+    }
+}
+
+/// Check at least one Whisper model is installed
+fn check_models(whisper: &Whisper) -> SetupItem {
+    let models = whisper.available_models();
+    if models.is_empty() {
+        return SetupItem {
+            id: "whisper_model",
+            label: "Whisper model",
+            status: SetupItemStatus::Missing,
+            detail: Some(format!("No models in {}", whisper.models_dir().display())),
+            remediation: Some(format!(
+                "Download a Whisper model (e.g. {}) into {}",
+                crate::services::WhisperModel::Base.filename(),
+                whisper.models_dir().display()
+            )),
+            download: None,
+        };
+    }
+    let names: Vec<&str> = models.iter().map(|m| m.as_str()).collect();
+    SetupItem {
+        id: "whisper_model",
+        label: "Whisper model",
+        status: SetupItemStatus::Ok,
+        detail: Some(names.join(", ")),
+        remediation: None,
+        download: None,
+    }
+}
+
+/// Check a directory exists, or can be created, and takes a file
+fn check_writable(id: &'static str, label: &'static str, dir: &Path) -> SetupItem {
+    let probe = dir.join(format!(".setup-check-{}", uuid::Uuid::new_v4()));
+    let written = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&probe, b"ok"));
+    std::fs::remove_file(&probe).ok();
+    match written {
+        Ok(()) => SetupItem {
+            id,
+            label,
+            status: SetupItemStatus::Ok,
+            detail: Some(dir.display().to_string()),
+            remediation: None,
+            download: None,
+        },
+        Err(e) => broken(
+            id,
+            label,
+            format!("Can't write to {}: {}", dir.display(), e),
+            format!("Give your user account write access to {}, or free up disk space", dir.display()),
+        ),
+    }
+}
+
+fn broken(id: &'static str, label: &'static str, detail: String, remediation: String) -> SetupItem {
+    SetupItem {
+        id,
+        label,
+        status: SetupItemStatus::Broken,
+        detail: Some(detail),
+        remediation: Some(remediation),
+        download: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[tokio::test]
+    async fn test_missing_sidecar_says_where_it_belongs() {
+        let dir = fixtures::temp_dir("setup");
+        let item = check_sidecar("ffmpeg", "FFmpeg", &dir.join("ffmpeg"), &["-version"]).await;
+        assert_eq!(item.status, SetupItemStatus::Missing);
+        assert!(item.remediation.unwrap().contains(&*dir.to_string_lossy()));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_sidecar_must_start() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = fixtures::temp_dir("setup");
+        let path = dir.join("ffmpeg");
+        std::fs::write(&path, "#!/bin/sh\necho 'ffmpeg version 7.0.1 Copyright'\n").unwrap();
+
+        // Not executable yet
+        let item = check_sidecar("ffmpeg", "FFmpeg", &path, &["-version"]).await;
+        assert_eq!(item.status, SetupItemStatus::Broken);
+        assert!(item.remediation.unwrap().contains("chmod +x"));
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let item = check_sidecar("ffmpeg", "FFmpeg", &path, &["-version"]).await;
+        assert_eq!(item.status, SetupItemStatus::Ok);
+        assert_eq!(item.detail.as_deref(), Some("ffmpeg version 7.0.1 Copyright"));
+
+        // Stopped by a signal, as a quarantined binary is
+        std::fs::write(&path, "#!/bin/sh\nkill -9 $$\n").unwrap();
+        let item = check_sidecar("ffmpeg", "FFmpeg", &path, &["-version"]).await;
+        assert_eq!(item.status, SetupItemStatus::Broken);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_writable_directory() {
+        let dir = fixtures::temp_dir("setup");
+        let item = check_writable("data_dir", "Data directory", &dir.join("nested"));
+        assert_eq!(item.status, SetupItemStatus::Ok);
+        assert_eq!(std::fs::read_dir(dir.join("nested")).unwrap().count(), 0);
+
+        // A file where the directory should be
+        std::fs::write(dir.join("file"), b"").unwrap();
+        let item = check_writable("cache_dir", "Cache directory", &dir.join("file"));
+        assert_eq!(item.status, SetupItemStatus::Broken);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,419 @@
+//! Sidecar Downloads
+//!
+//! Fetches FFmpeg, FFprobe and Whisper for installs that came without them,
+//! rather than bundling every platform's builds into every installer. The
+//! builds come from `sidecars.json`, a manifest of known-good static builds
+//! per platform compiled into the app, each pinned by its SHA-256: a build
+//! without a pinned checksum isn't downloaded, and a download that doesn't
+//! match is thrown away.
+//!
+//! Downloads go to the app data `binaries/` directory, laid out like the
+//! bundled binaries, and the running services are pointed at them straight
+//! away. At startup, downloaded binaries stand in for bundled ones that
+//! are missing.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager, State};
+use tracing::{info, warn};
+
+use super::part_file_path;
+use crate::error::{CommandError, ErrorCode};
+use crate::jobs::{self, JobContext};
+use crate::resources::{self, Priority, Resource};
+use crate::services::{Ffmpeg, Whisper};
+use crate::state::AppState;
+
+/// Under the app data directory
+pub const BINARIES_DIR: &str = "binaries";
+
+/// A sidecar binary the app runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sidecar {
+    Ffmpeg,
+    Ffprobe,
+    Whisper,
+}
+
+impl Sidecar {
+    pub const ALL: [Sidecar; 3] = [Sidecar::Ffmpeg, Sidecar::Ffprobe, Sidecar::Whisper];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sidecar::Ffmpeg => "ffmpeg",
+            Sidecar::Ffprobe => "ffprobe",
+            Sidecar::Whisper => "whisper",
+        }
+    }
+
+    /// Where the binary sits in a binaries directory, as the services look for it
+    pub fn path_in(&self, binaries_dir: &Path) -> PathBuf {
+        let exe = if cfg!(windows) { ".exe" } else { "" };
+        match self {
+            Sidecar::Ffmpeg => binaries_dir.join(format!("ffmpeg{}", exe)),
+            Sidecar::Ffprobe => binaries_dir.join(format!("ffprobe{}", exe)),
+            Sidecar::Whisper => binaries_dir.join("whisper").join(format!("main{}", exe)),
+        }
+    }
+}
+
+/// How a build is packed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Archive {
+    /// The binary itself
+    #[default]
+    None,
+    /// The binary, gzipped
+    Gzip,
+    /// Unpacked flat next to the binary, so libraries it ships with sit beside it
+    Zip,
+}
+
+/// A known-good build of a sidecar
+#[derive(Debug, Clone, Deserialize)]
+pub struct SidecarBuild {
+    pub sidecar: Sidecar,
+    /// `<os>-<arch>` as Rust names them, e.g. `macos-aarch64`
+    pub platform: String,
+    pub version: String,
+    pub url: String,
+    #[serde(default)]
+    pub archive: Archive,
+    /// The file in a zip archive that is the binary, when it isn't named like it
+    #[serde(default)]
+    pub member: Option<String>,
+    /// Hex SHA-256 of the download, pinned by `scripts/pin-sidecars.sh`;
+    /// builds without one aren't downloaded
+    pub sha256: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    builds: Vec<SidecarBuild>,
+}
+
+static MANIFEST: Lazy<Vec<SidecarBuild>> = Lazy::new(|| {
+    serde_json::from_str::<Manifest>(include_str!("../../sidecars.json"))
+        .map(|manifest| manifest.builds)
+        .unwrap_or_else(|e| {
+            warn!("Invalid sidecar manifest: {}", e);
+            Vec::new()
+        })
+});
+
+/// A sidecar put in place by [`download_sidecar`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SidecarInstall {
+    pub sidecar: Sidecar,
+    pub version: String,
+    pub path: String,
+}
+
+fn platform() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// This platform's build of `sidecar`, if one can be downloaded and verified
+pub fn downloadable(sidecar: Sidecar) -> Option<&'static SidecarBuild> {
+    MANIFEST
+        .iter()
+        .find(|build| build.sidecar == sidecar && build.platform == platform())
+        .filter(|build| build.sha256.is_some())
+}
+
+/// Download a known-good build of a sidecar and start using it
+///
+/// Runs as a `download` job, whose progress is reported with `job-status`
+/// events. The services use the new binary as soon as it's in place.
+#[tauri::command]
+pub async fn download_sidecar(
+    app: AppHandle,
+    name: Sidecar,
+    state: State<'_, Arc<AppState>>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+    whisper: State<'_, Arc<Whisper>>,
+) -> Result<SidecarInstall, CommandError> {
+    let platform = platform();
+    let build = MANIFEST
+        .iter()
+        .find(|build| build.sidecar == name && build.platform == platform)
+        .ok_or_else(|| {
+            CommandError::not_found(format!(
+                "There's no {} build to download for {}; reinstall GeoTruth instead",
+                name.as_str(),
+                platform
+            ))
+        })?
+        .clone();
+    let Some(sha256) = build.sha256.clone() else {
+        return Err(format!("The {} build for {} has no pinned checksum, so it can't be verified", name.as_str(), platform).into());
+    };
+
+    let dir = app.path().app_data_dir()?.join(BINARIES_DIR);
+    let target = name.path_in(&dir);
+    let job_build = build.clone();
+    let job_target = target.clone();
+    jobs::run(&state, jobs::emitter(&app), "download", None, |job| async move {
+        let download = job_target.with_extension("download");
+        let fetched = fetch(&job, &job_build, &download).await.and_then(|actual| {
+            if actual.eq_ignore_ascii_case(&sha256) {
+                Ok(())
+            } else {
+                Err(CommandError::new(
+                    ErrorCode::ServiceFailed,
+                    format!("The downloaded {} doesn't match its checksum (got {})", name.as_str(), actual),
+                ))
+            }
+        });
+        if let Err(e) = fetched {
+            std::fs::remove_file(&download).ok();
+            return Err(e);
+        }
+
+        job.progress(1.0, format!("Installing {}", name.as_str()));
+        tauri::async_runtime::spawn_blocking(move || {
+            let installed = install(&job_build, &download, &job_target);
+            std::fs::remove_file(&download).ok();
+            installed
+        })
+        .await?
+    })
+    .await?;
+
+    use_binary(name, target.clone(), &ffmpeg, &whisper);
+    info!("Installed {} {} at {:?}", name.as_str(), build.version, target);
+    Ok(SidecarInstall { sidecar: name, version: build.version, path: target.display().to_string() })
+}
+
+/// Use downloaded binaries in place of bundled ones that are missing
+pub fn use_downloaded(app_data_dir: &Path, ffmpeg: &Ffmpeg, whisper: &Whisper) {
+    let dir = app_data_dir.join(BINARIES_DIR);
+    let current = [ffmpeg.ffmpeg_path(), ffmpeg.ffprobe_path(), whisper.binary_path()];
+    for (sidecar, current) in Sidecar::ALL.into_iter().zip(current) {
+        let downloaded = sidecar.path_in(&dir);
+        if !current.exists() && downloaded.is_file() {
+            use_binary(sidecar, downloaded, ffmpeg, whisper);
+        }
+    }
+}
+
+fn use_binary(sidecar: Sidecar, path: PathBuf, ffmpeg: &Ffmpeg, whisper: &Whisper) {
+    match sidecar {
+        Sidecar::Ffmpeg => ffmpeg.set_ffmpeg_path(path),
+        Sidecar::Ffprobe => ffmpeg.set_ffprobe_path(path),
+        Sidecar::Whisper => whisper.set_binary_path(path),
+    }
+}
+
+/// Download a build to `path`, returning the hex SHA-256 of what was downloaded
+async fn fetch(job: &JobContext, build: &SidecarBuild, path: &Path) -> Result<String, CommandError> {
+    let _slot = resources::acquire(Resource::Network, Priority::Interactive).await;
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("GeoTruth/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let response = client
+        .get(&build.url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| CommandError::from(e).context(format!("Failed to download {}", build.sidecar.as_str())))?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::File::create(path)
+        .map_err(|e| CommandError::from(e).context(format!("Failed to create {}", path.display())))?;
+    let total = response.content_length();
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0u64;
+    let mut reported = 0;
+    let mut stream = response.bytes_stream();
+    let message = format!("Downloading {} {}", build.sidecar.as_str(), build.version);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk
+            .map_err(|e| CommandError::from(e).context(format!("Error while downloading {}", build.sidecar.as_str())))?;
+        std::io::Write::write_all(&mut file, &chunk)
+            .map_err(|e| CommandError::from(e).context(format!("Error while writing {}", path.display())))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        // Every percent, not every chunk
+        if let Some(total) = total.filter(|total| *total > 0) {
+            let percent = downloaded * 100 / total;
+            if percent > reported {
+                reported = percent;
+                job.progress(downloaded as f32 / total as f32, message.clone());
+            }
+        }
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Unpack a verified download into place as `target`, ready to run
+///
+/// The binary is written next to `target` first and renamed over it, so a
+/// failed install leaves the old binary, if any, working.
+fn install(build: &SidecarBuild, download: &Path, target: &Path) -> Result<PathBuf, CommandError> {
+    let dir = target.parent().ok_or("The binary has no directory")?;
+    std::fs::create_dir_all(dir)?;
+    let staged = part_file_path(target);
+    let name = target.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+    let unpacked = match build.archive {
+        Archive::None => std::fs::copy(download, &staged).map(|_| ()).map_err(CommandError::from),
+        Archive::Gzip => std::fs::File::open(download)
+            .and_then(|file| {
+                let mut out = std::fs::File::create(&staged)?;
+                std::io::copy(&mut flate2::read::GzDecoder::new(file), &mut out).map(|_| ())
+            })
+            .map_err(|e| CommandError::invalid_file(format!("Failed to unpack {}: {}", build.sidecar.as_str(), e))),
+        Archive::Zip => unzip_flat(download, dir, build.member.as_deref().unwrap_or(&name), &staged),
+    };
+    if let Err(e) = unpacked {
+        std::fs::remove_file(&staged).ok();
+        return Err(e);
+    }
+
+    make_runnable(&staged)?;
+    std::fs::rename(&staged, target)
+        .map_err(|e| CommandError::from(e).context(format!("Failed to install {}", target.display())))?;
+    Ok(target.to_path_buf())
+}
+
+/// Unpack a zip's files into `dir` by file name alone, the file named
+/// `member` going to `binary` instead
+fn unzip_flat(archive: &Path, dir: &Path, member: &str, binary: &Path) -> Result<(), CommandError> {
+    let invalid = |e: &dyn std::fmt::Display| CommandError::invalid_file(format!("Invalid archive: {}", e));
+    let file = std::fs::File::open(archive)?;
+    let mut zip = zip::ZipArchive::new(file).map_err(|e| invalid(&e))?;
+    let mut found = false;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index).map_err(|e| invalid(&e))?;
+        // Taking only the file name also keeps entries from escaping `dir`
+        let Some(name) = entry.enclosed_name().and_then(|path| path.file_name().map(|name| name.to_os_string())) else {
+            continue;
+        };
+        if !entry.is_file() {
+            continue;
+        }
+        let out = if name == member {
+            found = true;
+            binary.to_path_buf()
+        } else {
+            dir.join(&name)
+        };
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes).map_err(|e| invalid(&e))?;
+        std::fs::write(&out, bytes).map_err(|e| CommandError::from(e).context(format!("Failed to write {}", out.display())))?;
+    }
+    if found {
+        Ok(())
+    } else {
+        Err(CommandError::invalid_file(format!("The archive has no {}", member)))
+    }
+}
+
+/// Mark a binary executable and, on macOS, clear the quarantine flag that
+/// would stop it from starting
+fn make_runnable(path: &Path) -> Result<(), CommandError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+            .map_err(|e| CommandError::from(e).context(format!("Failed to make {} executable", path.display())))?;
+    }
+    #[cfg(target_os = "macos")]
+    {
+        // Fails harmlessly when the flag isn't set
+        std::process::Command::new("xattr").args(["-d", "com.apple.quarantine"]).arg(path).output().ok();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::io::Write;
+
+    fn build(archive: Archive, member: Option<&str>) -> SidecarBuild {
+        SidecarBuild {
+            sidecar: Sidecar::Whisper,
+            platform: platform(),
+            version: "1.7.2".to_string(),
+            url: "https://example.com/whisper.zip".to_string(),
+            archive,
+            member: member.map(str::to_string),
+            sha256: Some("0".repeat(64)),
+        }
+    }
+
+    #[test]
+    fn test_manifest_parses() {
+        assert!(!MANIFEST.is_empty());
+        for build in MANIFEST.iter() {
+            assert!(build.url.starts_with("https://"), "{}", build.url);
+            let sha256 = build.sha256.as_deref().unwrap_or_default();
+            assert!(
+                sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit()),
+                "{} has no pinned checksum",
+                build.url
+            );
+        }
+    }
+
+    #[test]
+    fn test_zip_is_unpacked_flat_beside_the_binary() {
+        let dir = fixtures::temp_dir("sidecars");
+        let archive = dir.join("whisper-bin.zip");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("Release/main", options).unwrap();
+        zip.write_all(b"#!/bin/sh\n").unwrap();
+        zip.start_file("Release/whisper.dll", options).unwrap();
+        zip.write_all(b"library").unwrap();
+        zip.start_file("../escape.txt", options).unwrap();
+        zip.write_all(b"nope").unwrap();
+        zip.finish().unwrap();
+
+        let binaries = dir.join(BINARIES_DIR);
+        let target = Sidecar::Whisper.path_in(&binaries);
+        let installed = install(&build(Archive::Zip, Some("main")), &archive, &target).unwrap();
+        assert_eq!(installed, target);
+        assert_eq!(std::fs::read(&target).unwrap(), b"#!/bin/sh\n");
+        assert_eq!(std::fs::read(binaries.join("whisper").join("whisper.dll")).unwrap(), b"library");
+        assert!(!dir.join("escape.txt").exists());
+        assert!(!part_file_path(&target).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o755);
+        }
+
+        let err = install(&build(Archive::Zip, Some("whisper-cli")), &archive, &target).unwrap_err();
+        assert!(err.message.contains("no whisper-cli"), "{}", err);
+        assert_eq!(err.code, ErrorCode::InvalidFile);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_gzipped_binary_is_unpacked() {
+        let dir = fixtures::temp_dir("sidecars");
+        let download = dir.join("ffmpeg.gz");
+        let mut gz = flate2::write::GzEncoder::new(std::fs::File::create(&download).unwrap(), flate2::Compression::default());
+        gz.write_all(b"ffmpeg binary").unwrap();
+        gz.finish().unwrap();
+
+        let target = Sidecar::Ffmpeg.path_in(&dir.join(BINARIES_DIR));
+        install(&build(Archive::Gzip, None), &download, &target).unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"ffmpeg binary");
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
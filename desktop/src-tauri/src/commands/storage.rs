@@ -0,0 +1,261 @@
+//! Region Storage Commands
+//!
+//! Disk usage per region and cleanup of files in the tiles directory that no
+//! longer belong to any region (leftover partial downloads, superseded
+//! extracts, artifacts of deleted regions).
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+use tracing::{info, warn};
+
+use super::{
+    active_downloads, part_file_path, region_file_path_in, region_pmtiles_path_in, tiles_dir,
+    RegionInfo, MAP_REGIONS,
+};
+use crate::error::CommandError;
+use crate::services::LocalDatabase;
+
+/// Rough on-disk size of one derived POI row, for estimates
+const ESTIMATED_POI_ROW_BYTES: u64 = 256;
+
+/// Disk usage of a single region
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionStorage {
+    pub region_id: String,
+    pub name: String,
+    /// Raw extract (`.osm.pbf`, or `.osm` for custom regions)
+    pub pbf_bytes: u64,
+    /// Unfinished download kept for resuming
+    pub partial_bytes: u64,
+    pub pmtiles_bytes: u64,
+    /// Derived POI rows, `None` until POI tables exist
+    pub poi_rows: Option<u64>,
+    pub poi_bytes_estimate: Option<u64>,
+    pub total_bytes: u64,
+}
+
+/// A file in the tiles directory not belonging to any known region
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanFile {
+    pub path: String,
+    pub bytes: u64,
+}
+
+/// Storage breakdown of the tiles directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageReport {
+    pub regions: Vec<RegionStorage>,
+    pub orphans: Vec<OrphanFile>,
+    pub total_bytes: u64,
+    pub orphaned_bytes: u64,
+}
+
+/// Result of an orphan cleanup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleanupResult {
+    pub dry_run: bool,
+    /// Files deleted, or that would be deleted on a dry run
+    pub files: Vec<OrphanFile>,
+    pub bytes: u64,
+}
+
+/// Per-region storage usage plus files no region accounts for
+#[tauri::command]
+pub async fn get_region_storage_report(db: State<'_, LocalDatabase>) -> Result<StorageReport, CommandError> {
+    let regions = MAP_REGIONS.read().await.clone();
+
+    // A broken POI query shouldn't hide the file sizes
+    let poi_counts = db.region_poi_counts().await.unwrap_or_else(|e| {
+        warn!("Failed to count region POIs: {}", e);
+        HashMap::new()
+    });
+
+    let region_ids: Vec<String> = regions.iter().map(|r| r.id.clone()).collect();
+    let orphans = find_orphans_in(&tiles_dir(), &owned_ids(&region_ids));
+
+    Ok(storage_report_in(&tiles_dir(), &regions, orphans, &poi_counts))
+}
+
+/// Storage of the tiles directory from file sizes alone, without counting POIs
+pub(super) async fn file_report() -> StorageReport {
+    let regions = MAP_REGIONS.read().await.clone();
+    let region_ids: Vec<String> = regions.iter().map(|r| r.id.clone()).collect();
+    let orphans = find_orphans_in(&tiles_dir(), &owned_ids(&region_ids));
+    storage_report_in(&tiles_dir(), &regions, orphans, &HashMap::new())
+}
+
+/// List (`dry_run`) or delete files in the tiles directory that belong to no region
+///
+/// Files of regions that are currently downloading are never touched, even
+/// if the region isn't registered yet.
+#[tauri::command]
+pub async fn cleanup_orphaned_region_files(dry_run: bool) -> Result<CleanupResult, CommandError> {
+    let region_ids: Vec<String> = MAP_REGIONS.read().await.iter().map(|r| r.id.clone()).collect();
+    let dir = tiles_dir();
+
+    let orphans = find_orphans_in(&dir, &owned_ids(&region_ids));
+    let mut files = Vec::new();
+
+    for orphan in orphans {
+        if !dry_run {
+            // Re-check right before deleting in case a download started meanwhile
+            if !find_orphans_in(&dir, &owned_ids(&region_ids)).contains(&orphan) {
+                continue;
+            }
+            if let Err(e) = std::fs::remove_file(&orphan.path) {
+                warn!("Failed to delete orphaned file {}: {}", orphan.path, e);
+                continue;
+            }
+        }
+        files.push(orphan);
+    }
+
+    let bytes = files.iter().map(|f| f.bytes).sum();
+    info!(
+        "Orphan cleanup{}: {} files, {} bytes",
+        if dry_run { " (dry run)" } else { "" },
+        files.len(),
+        bytes
+    );
+
+    Ok(CleanupResult { dry_run, files, bytes })
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Every file name a region may own in the tiles directory
+fn region_file_names(dir: &Path, region_id: &str) -> Vec<PathBuf> {
+    let data = region_file_path_in(dir, region_id);
+    vec![part_file_path(&data), region_pmtiles_path_in(dir, region_id), data]
+}
+
+fn storage_report_in(
+    dir: &Path,
+    regions: &[RegionInfo],
+    orphans: Vec<OrphanFile>,
+    poi_counts: &HashMap<String, u64>,
+) -> StorageReport {
+    let regions: Vec<RegionStorage> = regions
+        .iter()
+        .map(|region| {
+            let data = region_file_path_in(dir, &region.id);
+            let pbf_bytes = file_size(&data);
+            let partial_bytes = file_size(&part_file_path(&data));
+            let pmtiles_bytes = file_size(&region_pmtiles_path_in(dir, &region.id));
+            let poi_rows = poi_counts.get(&region.id).copied();
+            let poi_bytes_estimate = poi_rows.map(|rows| rows * ESTIMATED_POI_ROW_BYTES);
+
+            RegionStorage {
+                region_id: region.id.clone(),
+                name: region.name.clone(),
+                pbf_bytes,
+                partial_bytes,
+                pmtiles_bytes,
+                poi_rows,
+                poi_bytes_estimate,
+                total_bytes: pbf_bytes + partial_bytes + pmtiles_bytes + poi_bytes_estimate.unwrap_or(0),
+            }
+        })
+        .collect();
+
+    let orphaned_bytes = orphans.iter().map(|o| o.bytes).sum();
+    let total_bytes = regions.iter().map(|r| r.total_bytes).sum::<u64>() + orphaned_bytes;
+
+    StorageReport { regions, orphans, total_bytes, orphaned_bytes }
+}
+
+/// Region ids whose files must be kept: known regions plus running downloads
+fn owned_ids(region_ids: &[String]) -> HashSet<String> {
+    let busy = active_downloads().clone();
+    region_ids.iter().cloned().chain(busy).collect()
+}
+
+/// Files in `dir` that belong to none of the `owned` region ids
+fn find_orphans_in(dir: &Path, owned: &HashSet<String>) -> Vec<OrphanFile> {
+    let owned_paths: HashSet<PathBuf> = owned
+        .iter()
+        .flat_map(|id| region_file_names(dir, id))
+        .collect();
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut orphans: Vec<OrphanFile> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().map(|t| t.is_file()).unwrap_or(false))
+        .map(|e| e.path())
+        .filter(|path| !owned_paths.contains(path))
+        .map(|path| OrphanFile {
+            bytes: file_size(&path),
+            path: path.to_string_lossy().to_string(),
+        })
+        .collect();
+
+    orphans.sort_by(|a, b| a.path.cmp(&b.path));
+    orphans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_orphans_exclude_known_and_busy_regions() {
+        let dir = fixtures::temp_dir("storage");
+        std::fs::write(dir.join("europe_monaco.osm.pbf"), b"pbf").unwrap();
+        std::fs::write(dir.join("europe_monaco.pmtiles"), b"tiles").unwrap();
+        std::fs::write(dir.join("custom_trip.osm.part"), b"partial").unwrap();
+        std::fs::write(dir.join("us_texas.osm.pbf.part"), b"stale partial").unwrap();
+        std::fs::write(dir.join("europe_monaco-2023.osm.pbf"), b"old").unwrap();
+        std::fs::create_dir_all(dir.join("cache")).unwrap();
+
+        let owned: HashSet<String> = ["europe/monaco", "custom/trip"].iter().map(|s| s.to_string()).collect();
+        let orphans = find_orphans_in(&dir, &owned);
+
+        let names: Vec<String> = orphans
+            .iter()
+            .map(|o| Path::new(&o.path).file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["europe_monaco-2023.osm.pbf", "us_texas.osm.pbf.part"]);
+        assert_eq!(orphans[1].bytes, 13);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_storage_report_sizes() {
+        let dir = fixtures::temp_dir("storage");
+        std::fs::write(dir.join("europe_monaco.osm.pbf"), vec![0u8; 1000]).unwrap();
+        std::fs::write(dir.join("europe_monaco.osm.pbf.part"), vec![0u8; 10]).unwrap();
+        std::fs::write(dir.join("europe_monaco.pmtiles"), vec![0u8; 500]).unwrap();
+
+        let region = RegionInfo {
+            id: "europe/monaco".into(),
+            name: "Monaco".into(),
+            size_mb: 1,
+            downloaded: true,
+            bounds: (43.72, 7.4, 43.76, 7.44),
+            ..Default::default()
+        };
+        let poi_counts = HashMap::from([("europe/monaco".to_string(), 4)]);
+        let orphan = OrphanFile { path: "old.osm.pbf".into(), bytes: 7 };
+
+        let report = storage_report_in(&dir, &[region], vec![orphan], &poi_counts);
+        let monaco = &report.regions[0];
+        assert_eq!(monaco.pbf_bytes, 1000);
+        assert_eq!(monaco.partial_bytes, 10);
+        assert_eq!(monaco.pmtiles_bytes, 500);
+        assert_eq!(monaco.poi_rows, Some(4));
+        assert_eq!(monaco.total_bytes, 1510 + 4 * ESTIMATED_POI_ROW_BYTES);
+        assert_eq!(report.orphaned_bytes, 7);
+        assert_eq!(report.total_bytes, monaco.total_bytes + 7);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
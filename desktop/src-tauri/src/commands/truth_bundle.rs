@@ -0,0 +1,367 @@
+//! Truth Bundle Files
+//!
+//! A video's Truth Bundle exported as a JSON file, to keep with the footage
+//! or take to another machine. Next to the bundle the file holds a manifest
+//! saying which app and bundle schema wrote it, and an HMAC-SHA256 signature
+//! over the canonical JSON of both, keyed with this installation's signing
+//! key (see [`secrets::signing_key`]).
+//!
+//! The key never leaves the machine, so only files exported here can be
+//! verified; one from another installation is reported as such and still
+//! imported. A file whose signature doesn't match under this installation's
+//! key was changed after it was exported, and is refused.
+
+use std::path::PathBuf;
+
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tauri::State;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::bundle_schema::TRUTH_BUNDLE_SCHEMA_VERSION;
+use crate::error::{CommandError, ErrorCode};
+use crate::resources::{self, Priority, Resource};
+use crate::secrets;
+use crate::services::LocalDatabase;
+use crate::types::TruthBundle;
+
+/// Layout of the file itself
+pub const TRUTH_BUNDLE_FILE_VERSION: u32 = 1;
+
+const SIGNATURE_ALGORITHM: &str = "hmac-sha256";
+
+/// Describes an exported Truth Bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TruthBundleManifest {
+    pub format_version: u32,
+    pub schema_version: u32,
+    pub app_version: String,
+    /// When the bundle was generated, not exported
+    pub generated_at: DateTime<Utc>,
+    pub exported_at: DateTime<Utc>,
+    pub video_id: String,
+    /// Filename of the video the bundle was generated from
+    pub video_filename: String,
+}
+
+/// Signature over a file's manifest and bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleSignature {
+    pub algorithm: String,
+    /// Names the signing key without giving it away
+    pub key_id: String,
+    /// Base64
+    pub value: String,
+}
+
+/// An exported Truth Bundle, as written to the file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TruthBundleFile {
+    pub manifest: TruthBundleManifest,
+    pub bundle: TruthBundle,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<BundleSignature>,
+}
+
+/// What checking an imported file's signature found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignatureStatus {
+    /// Signed here and unchanged since
+    Verified,
+    /// Signed by another installation, whose key isn't here to check with
+    OtherInstallation,
+    /// Not signed at all
+    Unsigned,
+    /// Changed since it was signed, or the signature is malformed
+    Invalid,
+}
+
+/// What importing a Truth Bundle file did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TruthBundleImport {
+    /// The video the bundle is now kept with
+    pub video_id: String,
+    pub manifest: TruthBundleManifest,
+    pub signature: SignatureStatus,
+    pub events: usize,
+}
+
+/// Write the Truth Bundle kept for a video to a signed JSON file at `path`
+///
+/// The bundle is the one the video's last processing run produced, or the
+/// last one imported for it.
+#[tauri::command]
+pub async fn export_truth_bundle(
+    db: State<'_, LocalDatabase>,
+    video_id: String,
+    path: String,
+) -> Result<TruthBundleManifest, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let bundle = stored_bundle(&db, &video_id).await?;
+
+    let manifest = TruthBundleManifest {
+        format_version: TRUTH_BUNDLE_FILE_VERSION,
+        schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        generated_at: bundle.generated_at,
+        exported_at: Utc::now(),
+        video_id: video.id,
+        video_filename: video.filename,
+    };
+    let file = TruthBundleFile { manifest: manifest.clone(), bundle, signature: None };
+    let key = secrets::signing_key()?;
+    let json = signed_json(&file, &key)?;
+
+    let out = PathBuf::from(path.trim());
+    let _slot = resources::acquire(Resource::Io, Priority::Interactive).await;
+    std::fs::write(&out, json).map_err(|e| CommandError::from(e).context(format!("Failed to write {}", out.display())))?;
+
+    info!("Exported the Truth Bundle of video {} to {:?}", video_id, out);
+    Ok(manifest)
+}
+
+/// Read a Truth Bundle file, check its signature, and keep the bundle as `video_id`'s
+///
+/// The bundle takes the ids of the video it's attached to. A file changed
+/// since it was signed here is refused; the result says whether the rest
+/// could be verified.
+#[tauri::command]
+pub async fn import_truth_bundle(
+    db: State<'_, LocalDatabase>,
+    path: String,
+    video_id: String,
+) -> Result<TruthBundleImport, CommandError> {
+    let source = PathBuf::from(path.trim());
+    if !source.is_file() {
+        return Err(CommandError::file_not_found(format!("File not found: {}", source.display())));
+    }
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+
+    let text = std::fs::read_to_string(&source)?;
+    let key = secrets::signing_key()?;
+    let (file, signature) = read_signed(&text, &key)?;
+    if signature == SignatureStatus::Invalid {
+        return Err(CommandError::new(
+            ErrorCode::InvalidFile,
+            "The Truth Bundle file was changed after it was exported; its signature doesn't match",
+        ));
+    }
+
+    let TruthBundleFile { manifest, mut bundle, .. } = file;
+    bundle.video_id = Uuid::parse_str(&video.id).ok();
+    bundle.project_id = Uuid::parse_str(&video.project_id).ok();
+    let json = serde_json::to_string(&bundle)?;
+    db.put_truth_bundle(&video.id, &json)
+        .await?;
+
+    info!(
+        "Imported a Truth Bundle for video {} from {:?} ({:?}, exported from video {})",
+        video.id, source, signature, manifest.video_id
+    );
+    Ok(TruthBundleImport { video_id: video.id, manifest, signature, events: bundle.events.len() })
+}
+
+/// The Truth Bundle kept for a video
+pub(crate) async fn stored_bundle(db: &LocalDatabase, video_id: &str) -> Result<TruthBundle, CommandError> {
+    let json = db
+        .get_truth_bundle(video_id)
+        .await?
+        .ok_or_else(|| CommandError::not_found(format!("Video {} has no Truth Bundle yet; process it first", video_id)))?;
+    serde_json::from_str(&json)
+        .map_err(|e| CommandError::from(e).context(format!("The kept Truth Bundle of video {} can't be read", video_id)))
+}
+
+/// `file` as pretty JSON, signed with `key`
+fn signed_json(file: &TruthBundleFile, key: &[u8]) -> Result<String, CommandError> {
+    let mut value = serde_json::to_value(file)?;
+    let signature = BundleSignature {
+        algorithm: SIGNATURE_ALGORITHM.to_string(),
+        key_id: key_id(key),
+        value: general_purpose::STANDARD.encode(mac(key, &value).finalize().into_bytes()),
+    };
+    if let Value::Object(map) = &mut value {
+        map.insert("signature".to_string(), serde_json::to_value(signature)?);
+    }
+    Ok(serde_json::to_string_pretty(&value)?)
+}
+
+/// Parse a Truth Bundle file and check its signature against `key`
+fn read_signed(text: &str, key: &[u8]) -> Result<(TruthBundleFile, SignatureStatus), CommandError> {
+    let invalid = |e: serde_json::Error| CommandError::invalid_file(format!("Invalid Truth Bundle file: {}", e));
+    let value: Value = serde_json::from_str(text).map_err(invalid)?;
+    let file: TruthBundleFile = serde_json::from_value(value.clone()).map_err(invalid)?;
+    if file.manifest.format_version > TRUTH_BUNDLE_FILE_VERSION || file.manifest.schema_version > TRUTH_BUNDLE_SCHEMA_VERSION {
+        return Err(CommandError::invalid_file(format!(
+            "This Truth Bundle uses format version {} and schema version {}, but this app supports up to {} and {}. Update the app to import it (exported by version {}).",
+            file.manifest.format_version,
+            file.manifest.schema_version,
+            TRUTH_BUNDLE_FILE_VERSION,
+            TRUTH_BUNDLE_SCHEMA_VERSION,
+            file.manifest.app_version
+        )));
+    }
+
+    let status = match &file.signature {
+        None => SignatureStatus::Unsigned,
+        Some(signature) if signature.algorithm != SIGNATURE_ALGORITHM => SignatureStatus::Invalid,
+        Some(signature) if signature.key_id != key_id(key) => SignatureStatus::OtherInstallation,
+        Some(signature) => match general_purpose::STANDARD.decode(&signature.value) {
+            Ok(bytes) if mac(key, &value).verify_slice(&bytes).is_ok() => SignatureStatus::Verified,
+            _ => SignatureStatus::Invalid,
+        },
+    };
+    Ok((file, status))
+}
+
+/// HMAC of a file's canonical JSON, leaving out its signature
+fn mac(key: &[u8], file: &Value) -> Hmac<Sha256> {
+    let mut signed = file.clone();
+    if let Value::Object(map) = &mut signed {
+        map.remove("signature");
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(canonical_json(&signed).as_bytes());
+    mac
+}
+
+/// Names a signing key by the start of its hash
+fn key_id(key: &[u8]) -> String {
+    Sha256::digest(key)[..8].iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// JSON with object keys sorted and no whitespace, the form signatures cover
+fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            out.push('{');
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EventKind, LocationResult, TruthEvent};
+
+    fn file() -> TruthBundleFile {
+        let generated_at = Utc::now();
+        let event = TruthEvent {
+            id: "e1".to_string(),
+            kind: EventKind::NearestPass,
+            timestamp: generated_at,
+            local_time: None,
+            time_uncertain: false,
+            duration_seconds: Some(12.5),
+            location: Some(LocationResult { lat: 43.29571234567891, lon: 5.37420987654321 }),
+            heading_deg: Some(271.3),
+            pois: vec![],
+            detected_objects: vec![],
+            image_path: None,
+            sun: None,
+            weather: None,
+            context: None,
+        };
+        TruthBundleFile {
+            manifest: TruthBundleManifest {
+                format_version: TRUTH_BUNDLE_FILE_VERSION,
+                schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
+                app_version: "0.1.4".to_string(),
+                generated_at,
+                exported_at: generated_at,
+                video_id: "v1".to_string(),
+                video_filename: "harbour.mp4".to_string(),
+            },
+            bundle: TruthBundle {
+                schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
+                project_id: None,
+                video_id: Some(Uuid::new_v4()),
+                events: vec![event],
+                verification_mode: "offline".to_string(),
+                confidence: 0.8,
+                generated_at,
+                track_stats: None,
+                meta: Default::default(),
+                route_segments: Vec::new(),
+            },
+            signature: None,
+        }
+    }
+
+    #[test]
+    fn test_signed_file_verifies_until_tampered_with() {
+        let key = [7u8; 32];
+        let json = signed_json(&file(), &key).unwrap();
+
+        let (read, status) = read_signed(&json, &key).unwrap();
+        assert_eq!(status, SignatureStatus::Verified);
+        assert_eq!(read.bundle.events.len(), 1);
+        assert_eq!(read.signature.unwrap().key_id, key_id(&key));
+
+        // Key order and whitespace don't matter, the content does
+        let reordered: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(read_signed(&serde_json::to_string(&reordered).unwrap(), &key).unwrap().1, SignatureStatus::Verified);
+
+        let tampered = json.replace("\"offline\"", "\"online\"");
+        assert_ne!(tampered, json);
+        assert_eq!(read_signed(&tampered, &key).unwrap().1, SignatureStatus::Invalid);
+
+        let mut value: Value = serde_json::from_str(&json).unwrap();
+        value["signature"]["value"] = Value::String("not base64!".to_string());
+        assert_eq!(read_signed(&value.to_string(), &key).unwrap().1, SignatureStatus::Invalid);
+
+        // Another installation's file can't be checked here, and an unsigned one isn't
+        assert_eq!(read_signed(&json, &[9u8; 32]).unwrap().1, SignatureStatus::OtherInstallation);
+        value.as_object_mut().unwrap().remove("signature");
+        assert_eq!(read_signed(&value.to_string(), &key).unwrap().1, SignatureStatus::Unsigned);
+    }
+
+    #[test]
+    fn test_newer_files_are_refused() {
+        let mut newer = file();
+        newer.manifest.schema_version = TRUTH_BUNDLE_SCHEMA_VERSION + 1;
+        let json = signed_json(&newer, &[7u8; 32]).unwrap();
+        let err = read_signed(&json, &[7u8; 32]).unwrap_err();
+        assert!(err.message.contains("Update the app"), "{}", err);
+        assert_eq!(err.code, ErrorCode::InvalidFile);
+    }
+
+    #[test]
+    fn test_canonical_json_sorts_keys() {
+        let value = serde_json::json!({ "b": [1, { "d": null, "c": "x\"y" }], "a": 0.5 });
+        assert_eq!(canonical_json(&value), r#"{"a":0.5,"b":[1,{"c":"x\"y","d":null}]}"#);
+    }
+}
@@ -1,26 +1,49 @@
-use crate::services::Ffmpeg;
+use crate::error::CommandError;
+use crate::resources::Priority;
+use crate::services::{Ffmpeg, LocalDatabase};
+use crate::services::ffmpeg::{ImageFormat, StreamProbe, VideoChapter};
+use crate::settings;
+use crate::types::Chapter;
 use std::path::PathBuf;
 use tauri::{State, Manager}; // Import Manager
 use std::sync::Arc;
 
 /// Capture a frame from a video at the specified timestamp in milliseconds.
-/// Returns a base64 encoded data URI string of the image (JPEG).
+/// Returns a base64 encoded data URI string of the image, in `format` or
+/// the thumbnail format from settings (JPEG if the encoder is unavailable).
 #[tauri::command]
 pub async fn capture_frame(
     video_path: String,
     timestamp_ms: u64,
+    format: Option<ImageFormat>,
     ffmpeg: State<'_, Arc<Ffmpeg>>,
-) -> Result<String, String> {
+) -> Result<String, CommandError> {
     let video_path = PathBuf::from(video_path);
     
     // Check if file exists
     if !video_path.exists() {
-        return Err(format!("Video file not found: {:?}", video_path));
+        return Err(CommandError::file_not_found(format!("Video file not found: {:?}", video_path)));
     }
 
-    ffmpeg.capture_frame(&video_path, timestamp_ms)
-        .await
-        .map_err(|e| e.to_string())
+    let format = format.unwrap_or_else(|| settings::get().thumbnail_format);
+    Ok(ffmpeg.capture_frame(&video_path, timestamp_ms, format, Priority::Interactive)
+        .await?)
+}
+
+/// List a video's streams and whether any carry embedded GPS telemetry,
+/// so the import flow knows whether to try extracting it
+#[tauri::command]
+pub async fn probe_streams(
+    video_path: String,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+) -> Result<StreamProbe, CommandError> {
+    let video_path = PathBuf::from(video_path);
+    if !video_path.exists() {
+        return Err(CommandError::file_not_found(format!("Video file not found: {:?}", video_path)));
+    }
+
+    Ok(ffmpeg.probe_streams(&video_path)
+        .await?)
 }
 
 #[derive(serde::Serialize)]
@@ -33,27 +56,28 @@ pub struct ScannedMoment {
 #[tauri::command]
 pub async fn auto_scan_moments(
     video_path: String,
+    format: Option<ImageFormat>,
     ffmpeg: State<'_, Arc<Ffmpeg>>,
     app_handle: tauri::AppHandle,
-) -> Result<Vec<ScannedMoment>, String> {
+) -> Result<Vec<ScannedMoment>, CommandError> {
     let video_path = PathBuf::from(video_path);
     if !video_path.exists() {
-        return Err(format!("Video file not found: {:?}", video_path));
+        return Err(CommandError::file_not_found(format!("Video file not found: {:?}", video_path)));
     }
 
     // Create a unique directory for this scan in temp or app_cache
     let file_stem = video_path.file_stem().unwrap_or_default().to_string_lossy();
-    let cache_dir = app_handle.path().app_cache_dir().map_err(|e: tauri::Error| e.to_string())?;
+    let cache_dir = app_handle.path().app_cache_dir()?;
     let output_dir = cache_dir.join("moments").join(&*file_stem);
     
     if !output_dir.exists() {
-        std::fs::create_dir_all(&output_dir).map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&output_dir)?;
     }
 
     // Extract key moments using scene detection (threshold 0.4)
-    let thumbnails = ffmpeg.extract_key_moments(&video_path, &output_dir, 0.4)
-        .await
-        .map_err(|e| e.to_string())?;
+    let format = format.unwrap_or_else(|| settings::get().thumbnail_format);
+    let thumbnails = ffmpeg.extract_key_moments(&video_path, &output_dir, 0.4, format)
+        .await?;
 
     // Map paths to moments
     let moments = thumbnails.into_iter().map(|m| ScannedMoment {
@@ -63,3 +87,79 @@ pub async fn auto_scan_moments(
 
     Ok(moments)
 }
+
+/// Write a copy of a video with `chapters` embedded, next to the original
+///
+/// Chapter times use the narration's `MM:SS` (or `HH:MM:SS`) time codes.
+/// Returns the path of the new file.
+#[tauri::command]
+pub async fn embed_chapters(
+    video_id: String,
+    chapters: Vec<Chapter>,
+    db: State<'_, LocalDatabase>,
+    ffmpeg: State<'_, Arc<Ffmpeg>>,
+) -> Result<String, CommandError> {
+    let video = db.get_video(&video_id).await.map_err(CommandError::lookup(format!("Video {}", video_id)))?;
+    let input = PathBuf::from(&video.file_path);
+    if !input.exists() {
+        return Err(CommandError::file_not_found(format!("Video file not found: {:?}", input)));
+    }
+
+    let chapters = chapters
+        .iter()
+        .map(|c| {
+            let start_seconds = parse_time_code(&c.time_code)
+                .ok_or_else(|| {
+                    CommandError::invalid_input(format!("Invalid time code '{}' for chapter '{}'", c.time_code, c.title))
+                })?;
+            Ok(VideoChapter { start_seconds, title: c.title.clone() })
+        })
+        .collect::<Result<Vec<_>, CommandError>>()?;
+
+    let output = chapters_output_path(&input);
+    ffmpeg.add_chapters(&input, &output, &chapters)
+        .await?;
+
+    Ok(output.to_string_lossy().to_string())
+}
+
+/// Seconds from `SS`, `MM:SS` or `HH:MM:SS`, with optional fractional seconds
+fn parse_time_code(time_code: &str) -> Option<f64> {
+    let parts: Vec<&str> = time_code.trim().split(':').collect();
+    if parts.is_empty() || parts.len() > 3 {
+        return None;
+    }
+
+    let (seconds, whole) = parts.split_last()?;
+    let seconds: f64 = seconds.parse().ok().filter(|s: &f64| s.is_finite() && *s >= 0.0)?;
+    whole.iter().try_fold(0.0, |acc, part| {
+        part.parse::<u32>().ok().map(|v| acc * 60.0 + v as f64)
+    }).map(|minutes| minutes * 60.0 + seconds)
+}
+
+/// `trip.mp4` -> `trip.chapters.mp4`
+fn chapters_output_path(input: &std::path::Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = input.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_else(|| "mp4".to_string());
+    input.with_file_name(format!("{}.chapters.{}", stem, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_code() {
+        assert_eq!(parse_time_code("00:00"), Some(0.0));
+        assert_eq!(parse_time_code("03:25"), Some(205.0));
+        assert_eq!(parse_time_code("1:02:03.5"), Some(3723.5));
+        assert_eq!(parse_time_code("42"), Some(42.0));
+        assert_eq!(parse_time_code("ab:cd"), None);
+        assert_eq!(parse_time_code("1:2:3:4"), None);
+
+        assert_eq!(
+            chapters_output_path(std::path::Path::new("/videos/trip.MOV")),
+            PathBuf::from("/videos/trip.chapters.MOV")
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! Truth Bundle Confidence
+//!
+//! One score from 0 to 1 for how well a bundle's events are backed by
+//! evidence, shown in the UI as e.g. "85% verified". It's a weighted mean of
+//! the checks below:
+//!
+//! | Evidence     | Weight | Score                                              |
+//! |--------------|--------|----------------------------------------------------|
+//! | GPS coverage | 0.35   | share of events with a location                    |
+//! | Sync         | 0.25   | confidence of the GPS/video time alignment         |
+//! | POI matches  | 0.20   | mean confidence of each located event's best POI   |
+//! | GPS accuracy | 0.10   | 1 at 5 m reported accuracy or better, 0 at 50 m   |
+//! | Geocoding    | 0.10   | share of located events that were reverse geocoded |
+//!
+//! A check that didn't run, such as accuracy for a track that doesn't report
+//! it, is left out and the remaining weights are scaled up to compensate.
+
+use std::collections::HashMap;
+
+use crate::services::gps::GpsPoint;
+use crate::types::TruthEvent;
+
+const GPS_COVERAGE_WEIGHT: f64 = 0.35;
+const SYNC_WEIGHT: f64 = 0.25;
+const POI_MATCH_WEIGHT: f64 = 0.20;
+const GPS_ACCURACY_WEIGHT: f64 = 0.10;
+const GEOCODE_WEIGHT: f64 = 0.10;
+
+/// Reported GPS accuracy that scores full marks, in meters
+const GOOD_ACCURACY_M: f64 = 5.0;
+
+/// Reported GPS accuracy that scores nothing, in meters
+const POOR_ACCURACY_M: f64 = 50.0;
+
+/// Scores from 0 to 1 for each kind of evidence; `None` if the check didn't run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Evidence {
+    pub gps_coverage: f64,
+    pub sync: Option<f64>,
+    pub poi_match: Option<f64>,
+    pub gps_accuracy: Option<f64>,
+    pub geocode: Option<f64>,
+}
+
+impl Evidence {
+    /// Coverage and POI matches from the events; the rest is left unchecked
+    pub fn from_events(events: &[TruthEvent]) -> Self {
+        let located: Vec<&TruthEvent> = events.iter().filter(|e| e.location.is_some()).collect();
+        let gps_coverage = if events.is_empty() { 0.0 } else { located.len() as f64 / events.len() as f64 };
+
+        // No POIs anywhere means there were none to look up, not that they didn't match
+        let poi_match = (!located.is_empty() && located.iter().any(|e| !e.pois.is_empty())).then(|| {
+            let best = |event: &&TruthEvent| event.pois.iter().map(|p| p.confidence.clamp(0.0, 1.0)).fold(0.0, f64::max);
+            located.iter().map(best).sum::<f64>() / located.len() as f64
+        });
+
+        Self { gps_coverage, poi_match, ..Default::default() }
+    }
+
+    /// Overall confidence, from 0 to 1
+    pub fn score(&self) -> f64 {
+        let checks = [
+            (Some(self.gps_coverage), GPS_COVERAGE_WEIGHT),
+            (self.sync, SYNC_WEIGHT),
+            (self.poi_match, POI_MATCH_WEIGHT),
+            (self.gps_accuracy, GPS_ACCURACY_WEIGHT),
+            (self.geocode, GEOCODE_WEIGHT),
+        ];
+
+        let (weighted, total_weight) = checks
+            .iter()
+            .filter_map(|(score, weight)| score.map(|s| (s.clamp(0.0, 1.0) * weight, weight)))
+            .fold((0.0, 0.0), |(sum, total), (s, w)| (sum + s, total + w));
+        weighted / total_weight
+    }
+
+    /// Bundle meta with the score of each check that ran
+    pub fn to_meta(&self) -> HashMap<String, String> {
+        [
+            ("confidence.gps_coverage", Some(self.gps_coverage)),
+            ("confidence.sync", self.sync),
+            ("confidence.poi_match", self.poi_match),
+            ("confidence.gps_accuracy", self.gps_accuracy),
+            ("confidence.geocode", self.geocode),
+        ]
+        .into_iter()
+        .filter_map(|(key, score)| score.map(|s| (key.to_string(), format!("{:.2}", s))))
+        .collect()
+    }
+}
+
+/// Score for the mean accuracy the GPS device reported, if it reported any
+pub fn gps_accuracy_score(points: &[GpsPoint]) -> Option<f64> {
+    let accuracies: Vec<f64> = points
+        .iter()
+        .filter_map(|p| p.accuracy_m)
+        .filter(|a| a.is_finite() && *a >= 0.0)
+        .collect();
+    if accuracies.is_empty() {
+        return None;
+    }
+
+    let mean = accuracies.iter().sum::<f64>() / accuracies.len() as f64;
+    Some(((POOR_ACCURACY_M - mean) / (POOR_ACCURACY_M - GOOD_ACCURACY_M)).clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EventKind, LocationResult, POI};
+    use chrono::Utc;
+
+    fn event(located: bool, poi_confidence: Option<f64>) -> TruthEvent {
+        TruthEvent {
+            id: "event".to_string(),
+            kind: EventKind::Speech,
+            timestamp: Utc::now(),
+            local_time: None,
+            time_uncertain: false,
+            duration_seconds: None,
+            location: located.then_some(LocationResult { lat: 43.7384, lon: 7.4246 }),
+            heading_deg: None,
+            pois: poi_confidence
+                .map(|confidence| POI {
+                    id: "poi".to_string(),
+                    name: "Prince's Palace".to_string(),
+                    name_local: None,
+                    category: "attraction".to_string(),
+                    subcategory: None,
+                    lat: 43.7315,
+                    lon: 7.4197,
+                    distance_m: 120.0,
+                    bearing_deg: 210.0,
+                    in_fov: true,
+                    confidence,
+                    facts: None,
+                    pinned: false,
+                })
+                .into_iter()
+                .collect(),
+            detected_objects: vec![],
+            image_path: None,
+            sun: None,
+            weather: None,
+            context: None,
+        }
+    }
+
+    fn point(accuracy_m: Option<f64>) -> GpsPoint {
+        GpsPoint {
+            timestamp: Utc::now(),
+            lat: 43.7384,
+            lon: 7.4246,
+            elevation_m: None,
+            speed_kmh: None,
+            heading_deg: None,
+            accuracy_m,
+        }
+    }
+
+    #[test]
+    fn test_evidence_beats_no_evidence() {
+        let mut good = Evidence::from_events(&[event(true, Some(0.9)), event(true, Some(0.8))]);
+        good.sync = Some(0.9);
+        good.gps_accuracy = gps_accuracy_score(&[point(Some(4.0)), point(Some(6.0))]);
+
+        // GPS supplied but for another clip: no locations, failed sync, no POIs
+        let mut none = Evidence::from_events(&[event(false, None), event(false, None)]);
+        none.sync = Some(0.0);
+
+        assert!(good.score() > 0.85, "{}", good.score());
+        assert_eq!(none.score(), 0.0);
+        assert_eq!(Evidence::from_events(&[]).score(), 0.0);
+    }
+
+    #[test]
+    fn test_unchecked_evidence_is_left_out() {
+        // Full coverage and sync, nothing else checked
+        let evidence = Evidence { gps_coverage: 1.0, sync: Some(1.0), ..Default::default() };
+        assert_eq!(evidence.score(), 1.0);
+
+        // Located events without a matching POI count against the match score
+        let evidence = Evidence::from_events(&[event(true, Some(1.0)), event(true, None)]);
+        assert_eq!(evidence.poi_match, Some(0.5));
+        assert_eq!(Evidence::from_events(&[event(true, None)]).poi_match, None);
+
+        assert_eq!(evidence.to_meta()["confidence.poi_match"], "0.50");
+        assert!(!evidence.to_meta().contains_key("confidence.sync"));
+    }
+
+    #[test]
+    fn test_gps_accuracy_score() {
+        assert_eq!(gps_accuracy_score(&[point(Some(3.0))]), Some(1.0));
+        assert_eq!(gps_accuracy_score(&[point(Some(27.5))]), Some(0.5));
+        assert_eq!(gps_accuracy_score(&[point(Some(80.0)), point(None)]), Some(0.0));
+        assert_eq!(gps_accuracy_score(&[point(None)]), None);
+    }
+}
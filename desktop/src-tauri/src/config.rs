@@ -1,15 +1,20 @@
 //! Application Configuration
 //!
 //! Handles environment-based configuration for the GeoTruth desktop app.
+//! The environment takes precedence; what it leaves out comes from the
+//! user's settings, then the built-in defaults.
 
 use std::env;
 
 /// Default API URL for local Docker backend
 const DEFAULT_API_URL: &str = "http://localhost:8000";
 
-/// Get the API URL from environment or use default
+/// Get the API URL from the environment, else the settings, else the default
 pub fn get_api_url() -> String {
-    env::var("GEOTRUTH_API_URL").unwrap_or_else(|_| DEFAULT_API_URL.to_string())
+    env::var("GEOTRUTH_API_URL")
+        .ok()
+        .or_else(|| crate::settings::get().api_url)
+        .unwrap_or_else(|| DEFAULT_API_URL.to_string())
 }
 
 /// Check if running in development mode
@@ -19,6 +24,10 @@ pub fn is_development() -> bool {
 }
 
 /// Get Gemini API Key
+///
+/// The key stored from Settings, or else the `GEMINI_API_KEY` environment variable.
 pub fn get_gemini_api_key() -> String {
-    env::var("GEMINI_API_KEY").unwrap_or_default()
+    crate::secrets::gemini_api_key()
+        .map(|(key, _)| key)
+        .unwrap_or_default()
 }
@@ -0,0 +1,191 @@
+//! Crash Reports
+//!
+//! A panic hook that logs the panic with a backtrace and saves it as a
+//! crash report under the log directory, then tells the frontend. Long
+//! running commands also catch panics so the frontend gets an error back
+//! instead of a call that never returns.
+
+use futures_util::FutureExt;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter};
+use tracing::error;
+
+use crate::logging;
+
+/// Report files kept before the oldest are deleted
+const MAX_REPORTS: usize = 10;
+
+/// A panic, as saved to disk and sent with the `app-crashed` event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub message: String,
+    /// `file:line:column` of the panic
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub backtrace: String,
+    pub app_version: String,
+    pub occurred_at: String,
+    pub report_path: Option<String>,
+}
+
+static APP: OnceCell<AppHandle> = OnceCell::new();
+
+/// Install the panic hook; the default hook still runs afterwards
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let mut report = CrashReport {
+            message: payload_message(info.payload()),
+            location: info.location().map(|l| l.to_string()),
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            occurred_at: chrono::Utc::now().to_rfc3339(),
+            report_path: None,
+        };
+
+        error!(
+            location = report.location.as_deref().unwrap_or("unknown"),
+            "Panic: {}\n{}", report.message, report.backtrace
+        );
+
+        match save_report(&crash_dir(), &mut report) {
+            Ok(path) => error!("Crash report saved to {:?}", path),
+            Err(e) => error!("Failed to save crash report: {}", e),
+        }
+
+        if let Some(app) = APP.get() {
+            let _ = app.emit("app-crashed", report);
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// Send crash reports to the frontend from now on
+pub fn attach(app: AppHandle) {
+    APP.set(app).ok();
+}
+
+/// Run a command's future, turning a panic into an error for the frontend
+///
+/// The panic itself is still reported by the hook.
+pub async fn catch_panic<T, E, F>(future: F) -> Result<T, E>
+where
+    E: From<String>,
+    F: Future<Output = Result<T, E>>,
+{
+    AssertUnwindSafe(future).catch_unwind().await.unwrap_or_else(|payload| {
+        Err(E::from(format!(
+            "Internal error: {}. A crash report was saved to the log folder.",
+            payload_message(payload.as_ref())
+        )))
+    })
+}
+
+/// The most recent saved crash report, e.g. from a previous session
+pub fn last_report() -> Option<CrashReport> {
+    let path = report_files(&crash_dir()).pop()?;
+    let json = std::fs::read_to_string(&path).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+/// Directory holding the crash reports
+fn crash_dir() -> PathBuf {
+    logging::log_dir().join("crashes")
+}
+
+/// The panic message, when the payload is a string (as for `panic!` and `unwrap`)
+fn payload_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Write `report` to `dir`, pruning old reports, and record where it went
+fn save_report(dir: &Path, report: &mut CrashReport) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let path = dir.join(format!("crash-{}.json", chrono::Utc::now().format("%Y%m%dT%H%M%S%.9fZ")));
+    report.report_path = Some(path.display().to_string());
+
+    let json = serde_json::to_string_pretty(report).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| e.to_string())?;
+
+    let files = report_files(dir);
+    for old in &files[..files.len().saturating_sub(MAX_REPORTS)] {
+        std::fs::remove_file(old).ok();
+    }
+    Ok(path)
+}
+
+/// Crash report files in `dir`, oldest first
+fn report_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("crash-") && n.ends_with(".json"))
+        })
+        .collect();
+    // Timestamped names sort chronologically
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[tokio::test]
+    async fn test_catch_panic() {
+        let ok = catch_panic(async { Ok::<_, String>(42) }).await;
+        assert_eq!(ok, Ok(42));
+
+        let err = catch_panic(async {
+            let empty: Vec<u32> = Vec::new();
+            Ok::<_, String>(empty[3])
+        })
+        .await
+        .unwrap_err();
+        assert!(err.starts_with("Internal error: index out of bounds"), "{}", err);
+    }
+
+    #[test]
+    fn test_save_report_prunes_old_reports() {
+        let dir = fixtures::temp_dir("crash");
+        let mut report = CrashReport {
+            message: "called `Option::unwrap()` on a `None` value".to_string(),
+            location: Some("src/services/sync.rs:42:17".to_string()),
+            thread: Some("tokio-runtime-worker".to_string()),
+            backtrace: String::new(),
+            app_version: "0.1.4".to_string(),
+            occurred_at: chrono::Utc::now().to_rfc3339(),
+            report_path: None,
+        };
+
+        for _ in 0..MAX_REPORTS + 2 {
+            save_report(&dir, &mut report).unwrap();
+        }
+        let files = report_files(&dir);
+        assert_eq!(files.len(), MAX_REPORTS);
+        let latest = PathBuf::from(report.report_path.clone().unwrap());
+        assert!(files.contains(&latest));
+
+        let saved: CrashReport = serde_json::from_str(&std::fs::read_to_string(&latest).unwrap()).unwrap();
+        assert_eq!(saved.location, report.location);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
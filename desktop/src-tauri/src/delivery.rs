@@ -0,0 +1,88 @@
+//! Delivery Hints
+//!
+//! A script line can carry a hint for how it's read, like `whisper` or
+//! `pause_before`, for whoever voices or edits the narration. The model is
+//! asked to pick from a fixed set; what it writes is matched onto the set
+//! and anything else dropped, so exporters only ever see known hints.
+
+use crate::types::ScriptSegment;
+
+/// The hints a line can have
+pub const HINTS: &[&str] = &[
+    "whisper",
+    "excited",
+    "calm",
+    "serious",
+    "slow",
+    "fast",
+    "emphatic",
+    "pause_before",
+    "pause_after",
+];
+
+/// `hint` as one of [`HINTS`], whatever its case and however its words are
+/// joined, e.g. `Pause before` as `pause_before`
+pub fn normalize(hint: &str) -> Option<&'static str> {
+    let key = hint.trim().to_lowercase().replace([' ', '-'], "_");
+    HINTS.iter().copied().find(|known| *known == key)
+}
+
+/// Keep only known hints on `segments`, or none at all when hints are off;
+/// returns the hints dropped for not being known
+pub fn check(segments: &mut [ScriptSegment], enabled: bool) -> Vec<String> {
+    let mut unknown = Vec::new();
+    for segment in segments {
+        let Some(hint) = segment.delivery.take().filter(|_| enabled) else {
+            continue;
+        };
+        match normalize(&hint) {
+            Some(known) => segment.delivery = Some(known.to_string()),
+            None if hint.trim().is_empty() => {}
+            None => unknown.push(hint),
+        }
+    }
+    unknown
+}
+
+/// Prompt line asking for hints where a line should be read differently
+pub fn instruction() -> String {
+    format!(
+        "- Delivery: give a line a \"delivery\" hint only where it should be read differently from the rest, \
+         one of {}; leave it out everywhere else",
+        HINTS.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(delivery: Option<&str>) -> ScriptSegment {
+        ScriptSegment {
+            time_code: "00:00".to_string(),
+            narration: "And there it is.".to_string(),
+            delivery: delivery.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_hints_are_matched_onto_the_allowlist() {
+        let mut segments = vec![
+            segment(Some("Pause before")),
+            segment(Some("WHISPER")),
+            segment(Some("sarcastic")),
+            segment(Some(" ")),
+            segment(None),
+        ];
+        let unknown = check(&mut segments, true);
+        assert_eq!(unknown, ["sarcastic"]);
+        let hints: Vec<Option<&str>> = segments.iter().map(|s| s.delivery.as_deref()).collect();
+        assert_eq!(hints, [Some("pause_before"), Some("whisper"), None, None, None]);
+
+        // Off, no line keeps one
+        let mut segments = vec![segment(Some("excited"))];
+        assert!(check(&mut segments, false).is_empty());
+        assert_eq!(segments[0].delivery, None);
+    }
+}
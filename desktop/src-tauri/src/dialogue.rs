@@ -0,0 +1,266 @@
+//! Dialogue Gaps
+//!
+//! Narration goes where the creator isn't talking. The transcript's
+//! segments and any voice activity regions mark the video as occupied, and
+//! the silences between them long enough to say something in are the gaps
+//! the model is asked to narrate in. The script is checked afterwards: a
+//! line that runs into speech moves to the next gap if that's close by, is
+//! cut back to the sentences that fit, or is dropped and reported.
+
+use crate::pacing;
+use crate::types::{ScriptSegment, SpeechInterval};
+
+/// Shortest silence narrated in unless the options say otherwise
+pub const DEFAULT_MIN_GAP_SECONDS: f64 = 2.0;
+
+/// Furthest a line is moved to get clear of speech; later than this it
+/// would be talking about something else
+const MAX_SHIFT_SECONDS: f64 = 15.0;
+
+/// Most gaps listed in one prompt
+const MAX_PROMPT_GAPS: usize = 40;
+
+/// A silence to narrate in, in seconds into the video
+///
+/// `start` is on a whole second, as time codes are; `end` is infinite for
+/// the silence after the last speech in a video of unknown length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gap {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// What checking a script against the speech changed
+#[derive(Debug, Default)]
+pub struct Placement {
+    /// Lines moved or shortened to fit a gap
+    pub adjusted: usize,
+    /// Lines dropped for lack of a gap, as `time code: narration`
+    pub unplaceable: Vec<String>,
+}
+
+/// Speech in time order, with overlapping and touching stretches joined
+/// and unreadable ones left out
+fn merge(speech: &[SpeechInterval]) -> Vec<SpeechInterval> {
+    let mut sorted: Vec<SpeechInterval> = speech
+        .iter()
+        .filter(|s| s.start_seconds.is_finite() && s.end_seconds.is_finite() && s.end_seconds > s.start_seconds)
+        .copied()
+        .collect();
+    sorted.sort_by(|a, b| a.start_seconds.total_cmp(&b.start_seconds));
+
+    let mut merged: Vec<SpeechInterval> = Vec::with_capacity(sorted.len());
+    for interval in sorted {
+        match merged.last_mut() {
+            Some(last) if interval.start_seconds <= last.end_seconds => {
+                last.end_seconds = last.end_seconds.max(interval.end_seconds);
+            }
+            _ => merged.push(interval),
+        }
+    }
+    merged
+}
+
+/// Silences of at least `min_gap` seconds between the speech, up to the
+/// end of the video when its length is known
+pub fn free_gaps(speech: &[SpeechInterval], video_duration_seconds: Option<f64>, min_gap: f64) -> Vec<Gap> {
+    let end_of_video = video_duration_seconds.filter(|d| d.is_finite() && *d > 0.0).unwrap_or(f64::INFINITY);
+    let mut gaps = Vec::new();
+    let mut silence_from = 0.0_f64;
+    for interval in merge(speech) {
+        gaps.push(Gap { start: silence_from.ceil(), end: interval.start_seconds.min(end_of_video) });
+        silence_from = silence_from.max(interval.end_seconds);
+    }
+    gaps.push(Gap { start: silence_from.ceil(), end: end_of_video });
+    gaps.retain(|gap| gap.end - gap.start >= min_gap);
+    gaps
+}
+
+/// Gaps overlapping `from..to`
+pub fn gaps_within(gaps: &[Gap], from: f64, to: f64) -> Vec<Gap> {
+    gaps.iter().filter(|gap| gap.end > from && gap.start < to).copied().collect()
+}
+
+/// Prompt section asking for the narration to go in `gaps`
+pub fn gaps_note(gaps: &[Gap]) -> String {
+    if gaps.is_empty() {
+        return "\n## Dialogue\nThe creator is talking throughout this part; don't write narration for it.\n".to_string();
+    }
+
+    let mut note = String::from(
+        "\n## Dialogue\nThe creator is talking at other times. Start every narration line inside one of these \
+         silent gaps and keep it short enough to finish before the gap ends:\n",
+    );
+    for gap in gaps.iter().take(MAX_PROMPT_GAPS) {
+        if gap.end.is_finite() {
+            note.push_str(&format!(
+                "- {} to {}\n",
+                pacing::format_time_code(gap.start),
+                pacing::format_time_code(gap.end)
+            ));
+        } else {
+            note.push_str(&format!("- {} to the end\n", pacing::format_time_code(gap.start)));
+        }
+    }
+    note
+}
+
+/// Fit each segment into a gap, as spoken at `wpm`
+///
+/// A segment stays where it is if it finishes before speech starts, and
+/// otherwise goes to the start of the next gap at most
+/// [`MAX_SHIFT_SECONDS`] later; if it's too long for its gap, it keeps
+/// only the sentences that fit. Segments that fit nowhere are dropped.
+/// The time codes must already be canonical.
+pub fn place(segments: &mut Vec<ScriptSegment>, gaps: &[Gap], wpm: u32) -> Placement {
+    let mut placement = Placement::default();
+    let mut kept = Vec::with_capacity(segments.len());
+
+    for mut segment in segments.drain(..) {
+        let Some(start) = pacing::time_code_seconds(&segment.time_code) else {
+            kept.push(segment);
+            continue;
+        };
+        let spoken = pacing::spoken_seconds(&segment.narration, wpm);
+
+        let fitted = gaps
+            .iter()
+            .filter(|gap| gap.end > start && gap.start <= start + MAX_SHIFT_SECONDS)
+            .find_map(|gap| {
+                let at = start.max(gap.start);
+                let room = gap.end - at;
+                if spoken <= room {
+                    Some((at, None))
+                } else {
+                    fit_sentences(&segment.narration, room, wpm).map(|text| (at, Some(text)))
+                }
+            });
+
+        match fitted {
+            Some((at, shortened)) => {
+                if at != start || shortened.is_some() {
+                    placement.adjusted += 1;
+                }
+                segment.time_code = pacing::format_time_code(at);
+                if let Some(text) = shortened {
+                    segment.narration = text;
+                }
+                kept.push(segment);
+            }
+            None => placement.unplaceable.push(format!("{}: {}", segment.time_code, segment.narration.trim())),
+        }
+    }
+
+    // Moving lines later can put them past the ones after
+    kept.sort_by(|a, b| {
+        let (a, b) = (pacing::time_code_seconds(&a.time_code), pacing::time_code_seconds(&b.time_code));
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    *segments = kept;
+    placement
+}
+
+/// The leading sentences of `text` that can be spoken in `seconds`, if any
+fn fit_sentences(text: &str, seconds: f64, wpm: u32) -> Option<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let at_break = match chars.peek() {
+            Some((_, next)) => next.is_whitespace(),
+            None => true,
+        };
+        if matches!(c, '.' | '!' | '?') && at_break {
+            let end = i + c.len_utf8();
+            sentences.push(&text[start..end]);
+            start = end;
+        }
+    }
+    sentences.push(&text[start..]);
+
+    let mut fitted = String::new();
+    for sentence in sentences.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        let candidate = if fitted.is_empty() { sentence.to_string() } else { format!("{} {}", fitted, sentence) };
+        if pacing::spoken_seconds(&candidate, wpm) > seconds {
+            break;
+        }
+        fitted = candidate;
+    }
+    (!fitted.is_empty()).then_some(fitted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speech(intervals: &[(f64, f64)]) -> Vec<SpeechInterval> {
+        intervals
+            .iter()
+            .map(|&(start_seconds, end_seconds)| SpeechInterval { start_seconds, end_seconds })
+            .collect()
+    }
+
+    fn segment(time_code: &str, narration: &str) -> ScriptSegment {
+        ScriptSegment {
+            time_code: time_code.to_string(),
+            narration: narration.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_no_narration_over_speech_after_placement() {
+        // A synthetic transcript: the creator talks at 0-5, 12.5-30 (in two
+        // segments) and 34-60 in a 90 second video
+        let transcript = speech(&[(0.0, 5.0), (12.5, 20.0), (19.0, 30.0), (34.0, 60.0)]);
+        let gaps = free_gaps(&transcript, Some(90.0), DEFAULT_MIN_GAP_SECONDS);
+        assert_eq!(gaps, [Gap { start: 5.0, end: 12.5 }, Gap { start: 30.0, end: 34.0 }, Gap { start: 60.0, end: 90.0 }]);
+
+        let wpm = 150;
+        let mut segments = vec![
+            // Over the opening words; moved to the gap after them
+            segment("00:00", "We set off early from the harbour."),
+            // Eight seconds of narration in four and a half; keeps its first sentence
+            segment("00:08", "The old town climbs the hill from here. Its walls date back to the twelfth century and still stand."),
+            // Mid-speech, with a gap close enough to move to
+            segment("00:15", "Then the market opens."),
+            // Mid-speech, with the next gap too far away
+            segment("00:40", "The cathedral comes into view across the square."),
+            segment("01:10", "And finally the view from the top."),
+        ];
+        let placement = place(&mut segments, &gaps, wpm);
+
+        let time_codes: Vec<&str> = segments.iter().map(|s| s.time_code.as_str()).collect();
+        assert_eq!(time_codes, ["00:05", "00:08", "00:30", "01:10"]);
+        assert_eq!(segments[1].narration, "The old town climbs the hill from here.");
+        assert_eq!(placement.adjusted, 3);
+        assert_eq!(placement.unplaceable, ["00:40: The cathedral comes into view across the square."]);
+
+        for segment in &segments {
+            let start = pacing::time_code_seconds(&segment.time_code).unwrap();
+            let end = start + pacing::spoken_seconds(&segment.narration, wpm);
+            for talk in &transcript {
+                assert!(
+                    end <= talk.start_seconds || start >= talk.end_seconds,
+                    "'{}' at {}-{} overlaps speech at {}-{}",
+                    segment.narration, start, end, talk.start_seconds, talk.end_seconds
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_gaps_and_prompt_note() {
+        // Silence starting mid-second starts at the next whole one; short ones are skipped
+        let gaps = free_gaps(&speech(&[(3.4, 10.2), (11.0, 20.0)]), None, 2.0);
+        assert_eq!(gaps, [Gap { start: 0.0, end: 3.4 }, Gap { start: 20.0, end: f64::INFINITY }]);
+
+        let note = gaps_note(&gaps);
+        assert!(note.contains("- 00:00 to 00:03\n"));
+        assert!(note.contains("- 00:20 to the end\n"));
+        assert!(gaps_note(&[]).contains("talking throughout"));
+
+        assert_eq!(gaps_within(&gaps, 5.0, 15.0), []);
+        assert_eq!(gaps_within(&gaps, 0.0, 15.0).len(), 1);
+    }
+}
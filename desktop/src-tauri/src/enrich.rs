@@ -1,64 +1,398 @@
-use crate::geo::GeoEngine;
-use crate::gemini::GeminiClient;
+use crate::geo::{GeoEngine, PlaceCandidate, PlaceKind};
+use crate::gemini::{GeminiClient, GeminiPurpose};
+use crate::llm::{ImagePart, LlmBackend, LocalBackend, RoutedBackend};
+use crate::llm_cache::LlmCache;
+use crate::prompts::{self, PromptName};
+use crate::request_history::RecordingBackend;
+use crate::resources::Priority;
+use crate::scenes::{self, SceneDescriptions};
+use crate::services::ffmpeg::ImageFormat;
+use crate::commands::poi::{covering_region, has_region_data, nearest_pois};
+use crate::services::data_manager::ConnectivityMode;
+use crate::services::database::{AdminArea, Event, PoiRecord};
+use crate::services::gps::{haversine_distance, is_valid_coordinate, GpsPoint};
+use crate::services::{online_pois, weather, wikipedia};
+use crate::services::truth_engine::{self, camera_bearing, in_fov, search_bounds, LocalPOI, DEFAULT_FOV_DEG};
+use crate::services::{Ffmpeg, LocalDatabase};
+use crate::settings;
 use crate::state::AppState;
-use crate::types::{EnrichRequest, EnrichResponse, LocationResult, LocationContext, POI};
-use anyhow::Result;
+use crate::narrative::extract_json;
+use crate::overrides;
+use crate::processor::RECORDED_AT_META;
+use crate::types::{
+    ArticleSummary, EnrichDebug, EnrichRequest, EnrichResponse, EnrichmentSource, FactSource, LocationContext,
+    LocationInspection, LocationResult, RouteSegment, TruthBundle, TruthEvent, WeatherObservation, POI,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use futures_util::stream::{self, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
 use tracing::{info, debug, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 
+/// Points enriched at once by `enrich_points`
+const MAX_CONCURRENT_ENRICHMENTS: usize = 4;
 
+/// How long an enrichment the model came up with is kept; ones from the
+/// offline map data are kept until cleared
+const LLM_ENRICHMENT_TTL_DAYS: i64 = 30;
 
+/// Confidence in context from the offline map data
+const LOCAL_CONFIDENCE: f64 = 0.9;
+
+/// Confidence in context the model came up with, which nothing has checked
+const LLM_CONFIDENCE: f64 = 0.3;
+
+/// How far around a point POIs are listed unless the request says
+const DEFAULT_POI_RADIUS_M: f64 = 500.0;
+
+/// Furthest around a point POIs are listed
+const MAX_POI_RADIUS_M: f64 = 5_000.0;
+
+/// Most POIs listed for a point, the closest ones
+const MAX_POIS: usize = 20;
+
+/// POIs listed when inspecting a map position
+const INSPECTED_POIS: usize = 10;
+
+/// How far a recorded GPS point may be from an inspected position to give its elevation
+const ELEVATION_RADIUS_M: f64 = 200.0;
+
+/// How far away a neighbourhood, suburb or town, and else a city, may be
+/// and still name a point
+const LOCAL_PLACE_RADIUS_M: f64 = 2_000.0;
+const CITY_RADIUS_M: f64 = 20_000.0;
+
+/// Roads further away than this aren't the one a point is on
+const ROAD_RADIUS_M: f64 = 100.0;
+
+/// Events closer than this to an event being enriched share its lookup
+const DEFAULT_MERGE_RADIUS_M: f64 = 50.0;
+
+/// Samples in a row a route needs in another area before it counts as having entered it
+const MIN_AREA_RUN: usize = 3;
+
+/// Closest spacing of the points looked up along a route, and most looked up
+const ROUTE_SAMPLE_SPACING_M: f64 = 250.0;
+const MAX_ROUTE_SAMPLES: usize = 100;
+
+/// Days a kept article summary is used before it's fetched again
+const ARTICLE_MAX_AGE_DAYS: i64 = 90;
+
+/// Online lookups one enrichment run may make unless the settings say otherwise
+const DEFAULT_CALL_BUDGET: usize = 50;
+
+/// Longest place or road name taken from the model; longer is a sentence
+const MAX_NAME_CHARS: usize = 80;
+
+const SCENE_PROMPT: &str = "These {count} images are frames from travel footage, each taken at a \
+different moment of the trip. For each one, describe in one or two sentences what the camera sees: \
+the landscape, landmarks, road and weather. Only describe what is visible.";
+
+
+/// Enrichment cache lookups since the app started, for diagnostics
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EnrichCacheStats {
+    /// Answered from memory
+    pub memory_hits: u64,
+    /// Answered from the database, e.g. for points enriched in an earlier session
+    pub database_hits: u64,
+    /// Looked up live
+    pub misses: u64,
+    /// Share of lookups answered from either cache, 0 to 1
+    pub hit_rate: f64,
+    pub memory_entries: usize,
+    /// Requests made to the model or to online POI, weather and article sources
+    pub online_calls: u64,
+}
+
+/// How to enrich the events of a video
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct EnrichVideoOptions {
+    /// How far around each event to list POIs (`None` = 500 m)
+    pub radius_m: Option<f64>,
+    /// Camera field of view (`None` = a dashcam's)
+    pub fov_deg: Option<f64>,
+    /// POI categories to list (empty = all)
+    pub categories: Vec<String>,
+    /// Events closer than this to one being looked up share its result (`None` = 50 m)
+    pub merge_radius_m: Option<f64>,
+    /// Narration language, to read POI articles in where they exist in it (`None` = English)
+    pub language: Option<String>,
+}
+
+/// What enriching a video's events took
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct EnrichVideoSummary {
+    pub video_id: String,
+    /// Events given a context and POIs
+    pub events_enriched: usize,
+    /// Events without a location, or whose stored bundle couldn't be read
+    pub events_skipped: usize,
+    /// Points looked up once close events were merged
+    pub points_enriched: usize,
+    /// POIs given the summary of their article
+    pub pois_summarized: usize,
+    /// Points left without a context since the model wasn't asked: the app
+    /// is offline, or the run's online lookups were spent
+    pub points_uncovered: usize,
+    /// Lookups whose context came from the cache
+    pub cache_hits: u64,
+    /// Requests made to the model or to online POI, weather and article sources
+    pub online_calls: u64,
+}
+
+/// Online lookups a run may still make, shared by its concurrent lookups
+struct CallBudget {
+    remaining: AtomicUsize,
+}
+
+impl CallBudget {
+    fn new(calls: usize) -> Self {
+        Self { remaining: AtomicUsize::new(calls) }
+    }
+
+    /// For lookups made one at a time, which aren't part of a run
+    fn unlimited() -> Self {
+        Self::new(usize::MAX)
+    }
+
+    /// Use up a lookup; `false` once there are none left
+    fn take(&self) -> bool {
+        self.remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
 
 pub struct EnrichmentEngine {
     geo: Arc<GeoEngine>,
-    #[allow(dead_code)]
     state: Arc<AppState>,
-    gemini: GeminiClient,
+    llm: Arc<dyn LlmBackend>,
+    /// Keeps enrichments across sessions; without it they're only kept in memory
+    db: Option<LocalDatabase>,
+    memory_hits: AtomicU64,
+    database_hits: AtomicU64,
+    misses: AtomicU64,
+    online_calls: AtomicU64,
+    /// Overrides the connectivity setting
+    connectivity: Option<ConnectivityMode>,
+    /// Overrides the enrichment call budget setting
+    call_budget: Option<usize>,
 }
 
 impl EnrichmentEngine {
-    pub fn new(geo: Arc<GeoEngine>, state: Arc<AppState>) -> Self {
-        Self { 
-            geo, 
+    pub fn new(geo: Arc<GeoEngine>, state: Arc<AppState>, cache: Arc<LlmCache>) -> Self {
+        let gemini = GeminiClient::new_for(GeminiPurpose::Enrichment).with_cache(cache);
+        let routed = Arc::new(RoutedBackend::new(Arc::new(gemini), Arc::new(LocalBackend::new())));
+        let llm = Arc::new(RecordingBackend::new(routed, state.request_history.clone(), "enrich"));
+        Self::with_backend(geo, state, llm)
+    }
+
+    /// Create an engine on top of a specific generation backend
+    pub fn with_backend(geo: Arc<GeoEngine>, state: Arc<AppState>, llm: Arc<dyn LlmBackend>) -> Self {
+        Self {
+            geo,
             state,
-            gemini: GeminiClient::new(),
+            llm,
+            db: None,
+            memory_hits: AtomicU64::new(0),
+            database_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            online_calls: AtomicU64::new(0),
+            connectivity: None,
+            call_budget: None,
+        }
+    }
+
+    /// Keep enrichments in `db` too, so they outlast the session
+    pub fn with_database(mut self, db: LocalDatabase) -> Self {
+        self.db = Some(db);
+        self
+    }
+
+    /// Go by `mode` rather than the connectivity setting
+    #[cfg(test)]
+    pub fn with_connectivity(mut self, mode: ConnectivityMode) -> Self {
+        self.connectivity = Some(mode);
+        self
+    }
+
+    /// Allow each run `calls` online lookups rather than the number in the settings
+    #[cfg(test)]
+    pub fn with_call_budget(mut self, calls: usize) -> Self {
+        self.call_budget = Some(calls);
+        self
+    }
+
+    pub(crate) fn is_offline(&self) -> bool {
+        self.connectivity.unwrap_or_else(|| settings::get().connectivity_mode) == ConnectivityMode::Offline
+    }
+
+    /// The online lookups a new run may make
+    fn run_budget(&self) -> CallBudget {
+        CallBudget::new(self.run_calls())
+    }
+
+    fn run_calls(&self) -> usize {
+        self.call_budget
+            .or(settings::get().enrichment_call_budget)
+            .unwrap_or(DEFAULT_CALL_BUDGET)
+    }
+
+    /// Most places one run may ask a model over the internet about; none
+    /// offline or with a local model
+    pub(crate) fn online_place_lookups(&self) -> usize {
+        if self.is_offline() || !self.llm.is_remote() {
+            return 0;
         }
+        self.run_calls()
     }
 
+    /// Enrich a point, with its context from the cache if it or a point
+    /// within about 10 m was enriched before
+    ///
+    /// Context is looked up in memory, then in the database, and only then
+    /// live; a live result is kept in both, unless nothing was found. POIs
+    /// depend on the request's radius, heading and categories, so they're
+    /// always listed afresh.
     pub async fn enrich_point(&self, request: EnrichRequest) -> Result<EnrichResponse> {
-        let _cache_key = format!("enrich:{:.4}:{:.4}", request.lat, request.lon);
-        
-        debug!("Enriching point: {}, {}", request.lat, request.lon);
+        self.enrich_within(&request, &CallBudget::unlimited()).await
+    }
 
-        // 1. Try Local GeoEngine (PMTiles)
-        let places = self.geo.reverse_geocode(request.lat, request.lon).await?;
-        let local_result = places.first().map(|s| s.as_str()).unwrap_or("Unknown");
-
-        // 2. Hybrid Fallback: If unknown, ask Gemini
-        let (country, city, road) = if local_result == "Unknown Location" || local_result == "Unknown" {
-            debug!("Local geocoding failed, falling back to Gemini...");
-            match self.ask_gemini_location(request.lat, request.lon).await {
-                Ok(ctx) => ctx,
+    /// [`enrich_point`](Self::enrich_point), going online only while `budget` lasts
+    async fn enrich_within(&self, request: &EnrichRequest, budget: &CallBudget) -> Result<EnrichResponse> {
+        let mut response = self.context_at(request, budget).await?;
+        response.pois = self.nearby_pois(request, budget).await;
+        if request.verbose {
+            let place_candidates = self.geo.reverse_geocode(request.lat, request.lon).await?;
+            response.debug = Some(EnrichDebug { place_candidates });
+        }
+        Ok(response)
+    }
+
+    /// Everything known about a map position, e.g. one the user clicked
+    ///
+    /// The context comes the way [`enrich_point`](Self::enrich_point) gets
+    /// it, so only from local data in offline mode; with a downloaded region
+    /// loaded nothing else is asked. The elevation is that of the nearest
+    /// point of a recorded track.
+    pub async fn inspect_location(&self, lat: f64, lon: f64) -> Result<LocationInspection> {
+        let request = EnrichRequest::at(lat, lon);
+        let budget = CallBudget::unlimited();
+        let response = self.context_at(&request, &budget).await?;
+        let place_candidates = self.geo.reverse_geocode(lat, lon).await?;
+
+        let (mut pois, mut elevation_m) = (Vec::new(), None);
+        if let Some(db) = &self.db {
+            pois = match nearest_pois(db, lat, lon, &[], INSPECTED_POIS).await {
+                Ok(pois) => pois,
                 Err(e) => {
-                    warn!("Gemini fallback failed: {}", e);
-                    ("United States".to_string(), "Unknown City".to_string(), None)
+                    warn!("POI lookup failed for {}, {}: {}", lat, lon, e);
+                    Vec::new()
                 }
+            };
+            elevation_m = match db.recorded_elevation(lat, lon, search_bounds(lat, lon, ELEVATION_RADIUS_M)).await {
+                Ok(elevation) => elevation,
+                Err(e) => {
+                    warn!("Elevation lookup failed for {}, {}: {}", lat, lon, e);
+                    None
+                }
+            };
+        }
+        if pois.is_empty() {
+            // Online, where no region covers the point and the app isn't offline
+            pois = self.nearby_pois(&request, &budget).await;
+            pois.truncate(INSPECTED_POIS);
+        }
+
+        let context = response.context;
+        Ok(LocationInspection {
+            location: response.location,
+            elevation_m: elevation_m.or(context.elevation_m),
+            local_time: context.timezone.as_deref().and_then(|tz| local_time(tz, Utc::now())),
+            context,
+            source: response.source,
+            place_candidates,
+            pois,
+            region: covering_region(lat, lon).await,
+        })
+    }
+
+    /// The requested point's context, from the cache or else looked up, without POIs
+    async fn context_at(&self, request: &EnrichRequest, budget: &CallBudget) -> Result<EnrichResponse> {
+        if !is_valid_coordinate(request.lat, request.lon) {
+            anyhow::bail!("Coordinates out of range: {}, {}", request.lat, request.lon);
+        }
+        let cache_key = enrich_cache_key(request);
+        let mut response = match self.cached(&cache_key).await {
+            Some(response) => {
+                debug!("Enrichment cache hit for {}, {}", request.lat, request.lon);
+                response
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.look_up_context(request, cache_key, budget).await?
             }
-        } else {
-             ("United States".to_string(), local_result.to_string(), None)
         };
+        response.location = LocationResult { lat: request.lat, lon: request.lon };
+        Ok(response)
+    }
 
-        // Match Context
-        let context = LocationContext {
-            country: Some(country), 
-            timezone: Some("America/Los_Angeles".to_string()), // Placeholder
-            elevation_m: None,
-            state: None,
-            county: None,
-            city: Some(city),
-            road,
-            region: None,
-            population: None,
+    /// Where a point is, from the offline map data or else the model
+    ///
+    /// A model reached over the internet is never asked in offline mode, nor
+    /// once `budget` is spent; the point is left uncovered then, and not
+    /// cached, so a later lookup can still ask.
+    async fn look_up_context(&self, request: &EnrichRequest, cache_key: String, budget: &CallBudget) -> Result<EnrichResponse> {
+        debug!("Enriching point: {}, {}", request.lat, request.lon);
+
+        // 1. Try Local GeoEngine (PMTiles)
+        let candidates = self.geo.reverse_geocode(request.lat, request.lon).await?;
+        let names = place_names(&candidates);
+
+        // 2. Hybrid Fallback: If unknown, the regions' boundaries, then ask the LLM (Gemini or local)
+        let mut cacheable = true;
+        let areas = match names.place {
+            Some(_) => None,
+            None => self.admin_areas_at(request.lat, request.lon).await,
+        };
+        let (context, source, confidence) = if let Some(city) = names.place {
+            let mut context = LocationContext { city: Some(city), road: names.road, ..Default::default() };
+            context.attribute(FactSource::MapData, LOCAL_CONFIDENCE);
+            (context, EnrichmentSource::Local, LOCAL_CONFIDENCE)
+        } else if let Some(mut context) = areas {
+            context.road = names.road;
+            context.attribute(FactSource::MapData, LOCAL_CONFIDENCE);
+            (context, EnrichmentSource::Local, LOCAL_CONFIDENCE)
+        } else if self.llm.is_remote() && (self.is_offline() || !budget.take()) {
+            debug!(
+                "Leaving {}, {} uncovered: {}",
+                request.lat, request.lon,
+                if self.is_offline() { "offline" } else { "out of online lookups for this run" }
+            );
+            cacheable = false;
+            (LocationContext::default(), EnrichmentSource::Uncovered, 0.0)
+        } else {
+            debug!("Local geocoding failed, falling back to {}...", self.llm.engine());
+            match self.ask_llm_location(request.lat, request.lon).await {
+                Ok(mut context) => {
+                    context.attribute(FactSource::Llm, LLM_CONFIDENCE);
+                    (context, EnrichmentSource::Llm, LLM_CONFIDENCE)
+                }
+                Err(e) => {
+                    // Not worth failing the lookup over, the message says what to fix
+                    warn!("LLM fallback failed: {}", e);
+                    // A later lookup may well succeed
+                    cacheable = false;
+                    // Nothing is known; an empty context beats a made-up one
+                    (LocationContext::default(), EnrichmentSource::Partial, 0.0)
+                }
+            }
         };
 
         // Location Result
@@ -68,39 +402,1435 @@ impl EnrichmentEngine {
              // matched: None
         };
 
-        // Find POIs (Stub)
-        let pois: Vec<POI> = Vec::new();
-
+        // POIs are listed per request, not cached
         let response = EnrichResponse {
             location,
             context,
-            pois,
+            pois: Vec::new(),
+            source,
+            confidence,
+            debug: None,
         };
 
         info!("Enrichment complete for {}, {}", request.lat, request.lon);
         
+        if cacheable {
+            self.remember(cache_key, &response).await;
+        }
         Ok(response)
     }
 
-    async fn ask_gemini_location(&self, lat: f64, lon: f64) -> Result<(String, String, Option<String>)> {
-        let prompt = format!(
-            "Identify the location at latitude {} longitude {}. Return a JSON object with 'country', 'city', and 'road' (optional). Return ONLY JSON.",
-            lat, lon
+    /// Country, state, county, city and population from the boundaries of
+    /// the downloaded regions; `None` when they don't cover the point
+    async fn admin_areas_at(&self, lat: f64, lon: f64) -> Option<LocationContext> {
+        match self.db.as_ref()?.admin_areas_at(lat, lon).await {
+            Ok(areas) => context_from_areas(&areas),
+            Err(e) => {
+                warn!("Boundary lookup failed for {}, {}: {}", lat, lon, e);
+                None
+            }
+        }
+    }
+
+    /// POIs around the requested point, closest first, from the downloaded
+    /// regions
+    ///
+    /// Where no downloaded region covers the point they're looked up online,
+    /// unless the app is offline or `budget` is spent, and kept with the
+    /// regions' POIs. Failures only cost the POIs.
+    async fn nearby_pois(&self, request: &EnrichRequest, budget: &CallBudget) -> Vec<POI> {
+        let Some(db) = &self.db else {
+            return Vec::new();
+        };
+        let (lat, lon) = (request.lat, request.lon);
+        let radius_m = request
+            .radius_m
+            .filter(|r| r.is_finite() && *r > 0.0)
+            .unwrap_or(DEFAULT_POI_RADIUS_M)
+            .min(MAX_POI_RADIUS_M);
+
+        let mut pois = match truth_engine::nearby_pois(db, lat, lon, radius_m, &request.categories).await {
+            Ok(pois) => pois,
+            Err(e) => {
+                warn!("POI lookup failed for {}, {}: {}", lat, lon, e);
+                Vec::new()
+            }
+        };
+        if pois.is_empty() && !self.is_offline() && !has_region_data(lat, lon).await && budget.take() {
+            pois = self.online_pois(db, request, radius_m).await;
+        }
+
+        let fov_deg = request.fov_deg.filter(|f| f.is_finite() && *f > 0.0).unwrap_or(DEFAULT_FOV_DEG);
+        truth_engine::mark_in_fov(&mut pois, request.heading_deg, fov_deg);
+        pois.into_iter().take(MAX_POIS).map(POI::from).collect()
+    }
+
+    /// POIs around the requested point from the online source, kept in `db`
+    async fn online_pois(&self, db: &LocalDatabase, request: &EnrichRequest, radius_m: f64) -> Vec<LocalPOI> {
+        let (lat, lon) = (request.lat, request.lon);
+        self.online_calls.fetch_add(1, Ordering::Relaxed);
+        let records = match online_pois::fetch_nearby(lat, lon, radius_m).await {
+            Ok(records) => records,
+            Err(e) => {
+                warn!("Online POI lookup failed for {}, {}: {}", lat, lon, e);
+                return Vec::new();
+            }
+        };
+        if let Err(e) = db.add_online_pois(&records).await {
+            warn!("Failed to keep online POIs: {}", e);
+        }
+
+        let records: Vec<PoiRecord> = records.into_iter().filter(|r| in_categories(r, &request.categories)).collect();
+        truth_engine::within_radius(lat, lon, records, radius_m)
+    }
+
+    /// Weather at a point in the hour nearest `time`, from the database or
+    /// else the weather service
+    ///
+    /// A lookup keeps the whole day in the database, so other times that
+    /// day are answered from there. `None` when the app is offline, and when
+    /// the service failed or recorded nothing for the hour.
+    pub async fn weather_at(&self, lat: f64, lon: f64, time: DateTime<Utc>) -> Option<WeatherObservation> {
+        if self.is_offline() {
+            return None;
+        }
+        let key = weather::cache_key(lat, lon, time);
+        if let Some(db) = &self.db {
+            match db.weather_cache_get(&key).await {
+                Ok(Some(json)) => match serde_json::from_str(&json) {
+                    Ok(observed) => return Some(observed),
+                    Err(e) => warn!("Cached weather for {} can't be read, looking it up again: {}", key, e),
+                },
+                Ok(None) => {}
+                Err(e) => warn!("Weather cache lookup failed: {}", e),
+            }
+        }
+
+        let hour = weather::nearest_hour(time);
+        self.online_calls.fetch_add(1, Ordering::Relaxed);
+        let hours = match weather::fetch_day(lat, lon, hour.date_naive()).await {
+            Ok(hours) => hours,
+            Err(e) => {
+                warn!("Weather lookup failed for {}, {} at {}: {}", lat, lon, hour, e);
+                return None;
+            }
+        };
+        if let Some(db) = &self.db {
+            let entries: Vec<(String, String)> = hours
+                .iter()
+                .filter_map(|(at, observed)| Some((weather::cache_key(lat, lon, *at), serde_json::to_string(observed).ok()?)))
+                .collect();
+            if let Err(e) = db.weather_cache_put(&entries).await {
+                warn!("Failed to keep weather: {}", e);
+            }
+        }
+        hours.into_iter().find(|(at, _)| *at == hour).map(|(_, observed)| observed)
+    }
+
+    /// Give a bundle's located events the weather around them, where they
+    /// have none yet; returns how many were given it
+    ///
+    /// Only bundles that know when they were recorded qualify, since the
+    /// events of the others are timed by when they were processed. Events
+    /// in the same hour and near each other share a lookup. Nothing is
+    /// looked up when the app is offline.
+    pub async fn add_weather(&self, bundle: &mut TruthBundle) -> usize {
+        if !bundle.meta.contains_key(RECORDED_AT_META) {
+            debug!("Bundle doesn't say when it was recorded, not looking up weather");
+            return 0;
+        }
+        if self.is_offline() {
+            debug!("Offline, not looking up weather");
+            return 0;
+        }
+
+        let mut looked_up: HashMap<String, Option<WeatherObservation>> = HashMap::new();
+        let mut added = 0;
+        for event in bundle.events.iter_mut().filter(|e| e.weather.is_none()) {
+            let Some(location) = &event.location else {
+                continue;
+            };
+            let key = weather::cache_key(location.lat, location.lon, event.timestamp);
+            let observed = match looked_up.get(&key) {
+                Some(observed) => observed.clone(),
+                None => {
+                    let observed = self.weather_at(location.lat, location.lon, event.timestamp).await;
+                    looked_up.insert(key, observed.clone());
+                    observed
+                }
+            };
+            added += usize::from(observed.is_some());
+            event.weather = observed;
+        }
+        info!("Added weather to {} of {} events at {} hours and places", added, bundle.events.len(), looked_up.len());
+        added
+    }
+
+    /// Give events' POIs the summary of their Wikipedia article, where their
+    /// map data names one and they have none yet; returns how many POIs
+    /// were given one
+    ///
+    /// Articles are read in the narration `language` where they exist in
+    /// it. Each is fetched once however many events see its POI, and kept
+    /// in the database. Nothing is fetched when the app is offline.
+    pub async fn add_article_summaries(&self, events: &mut [TruthEvent], language: &str) -> usize {
+        if self.is_offline() {
+            debug!("Offline, not fetching POI articles");
+            return 0;
+        }
+        let language = wikipedia::language_code(language).unwrap_or_else(|| "en".to_string());
+        let unsummarized = |poi: &POI| {
+            let facts = poi.facts.as_ref().filter(|facts| facts.summary.is_none())?;
+            let key = wikipedia::article_key(facts.wikipedia.as_deref(), facts.wikidata.as_deref(), &language)?;
+            Some((key, (facts.wikipedia.clone(), facts.wikidata.clone())))
+        };
+        let wanted: HashMap<String, (Option<String>, Option<String>)> =
+            events.iter().flat_map(|event| &event.pois).filter_map(unsummarized).collect();
+        if wanted.is_empty() {
+            return 0;
+        }
+
+        let language = language.as_str();
+        let summaries: HashMap<String, ArticleSummary> = stream::iter(wanted)
+            .map(|(key, (wikipedia, wikidata))| async move {
+                let summary = self.article_summary(&key, wikipedia.as_deref(), wikidata.as_deref(), language).await;
+                summary.map(|summary| (key, summary))
+            })
+            .buffer_unordered(MAX_CONCURRENT_ENRICHMENTS)
+            .filter_map(|found| async move { found })
+            .collect()
+            .await;
+
+        let mut added = 0;
+        for poi in events.iter_mut().flat_map(|event| event.pois.iter_mut()) {
+            let Some((key, _)) = unsummarized(poi) else {
+                continue;
+            };
+            if let (Some(summary), Some(facts)) = (summaries.get(&key), poi.facts.as_mut()) {
+                facts.summary = Some(summary.clone());
+                added += 1;
+            }
+        }
+        info!("Gave {} POIs the summary of one of {} articles", added, summaries.len());
+        added
+    }
+
+    /// The summary of a POI's article, kept in the database or else fetched
+    async fn article_summary(
+        &self,
+        key: &str,
+        wikipedia: Option<&str>,
+        wikidata: Option<&str>,
+        language: &str,
+    ) -> Option<ArticleSummary> {
+        if let Some(db) = &self.db {
+            match db.article_summary_get(key, Duration::days(ARTICLE_MAX_AGE_DAYS)).await {
+                Ok(Some(json)) => match serde_json::from_str(&json) {
+                    Ok(summary) => return Some(summary),
+                    Err(e) => warn!("Kept summary of {} can't be read, fetching it again: {}", key, e),
+                },
+                Ok(None) => {}
+                Err(e) => warn!("Article summary lookup failed: {}", e),
+            }
+        }
+
+        self.online_calls.fetch_add(1, Ordering::Relaxed);
+        let summary = match wikipedia::fetch_summary(wikipedia, wikidata, language).await {
+            Ok(summary) => summary?,
+            Err(e) => {
+                warn!("Fetching the article {} failed: {}", key, e);
+                return None;
+            }
+        };
+        if let Some(db) = &self.db {
+            let stored = match serde_json::to_string(&summary) {
+                Ok(json) => db.article_summary_put(key, &json).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = stored {
+                warn!("Failed to keep the summary of {}: {}", key, e);
+            }
+        }
+        Some(summary)
+    }
+
+    /// The cached enrichment for `key`, from memory or else the database
+    async fn cached(&self, key: &str) -> Option<EnrichResponse> {
+        if let Some(cached) = self.state.enrich_cache.get(key) {
+            self.memory_hits.fetch_add(1, Ordering::Relaxed);
+            return Some(cached);
+        }
+
+        // A failing lookup is a miss; the point is just enriched live
+        let json = match self.db.as_ref()?.enrichment_cache_get(key).await {
+            Ok(json) => json?,
+            Err(e) => {
+                warn!("Enrichment cache lookup failed: {}", e);
+                return None;
+            }
+        };
+        let mut response: EnrichResponse = match serde_json::from_str(&json) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Ignoring unreadable cached enrichment {}: {}", key, e);
+                return None;
+            }
+        };
+        attribute_to_source(&mut response);
+        self.database_hits.fetch_add(1, Ordering::Relaxed);
+        self.remember_in_memory(key.to_string(), response.clone());
+        Some(response)
+    }
+
+    /// Keep an enrichment in memory and in the database
+    ///
+    /// What the model came up with expires after [`LLM_ENRICHMENT_TTL_DAYS`];
+    /// it may do better later, or the map data may cover the point by then.
+    async fn remember(&self, key: String, response: &EnrichResponse) {
+        if let Some(db) = &self.db {
+            let expires_at = (response.source == EnrichmentSource::Llm)
+                .then(|| Utc::now() + Duration::days(LLM_ENRICHMENT_TTL_DAYS));
+            let stored = match serde_json::to_string(response) {
+                Ok(json) => db.enrichment_cache_put(&key, &json, expires_at).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = stored {
+                warn!("Failed to cache enrichment {}: {}", key, e);
+            }
+        }
+        self.remember_in_memory(key, response.clone());
+    }
+
+    fn remember_in_memory(&self, key: String, response: EnrichResponse) {
+        self.state.enrich_cache.insert(key, response);
+    }
+
+    /// Drop expired enrichments from the database
+    pub async fn prune_cache(&self) {
+        let Some(db) = &self.db else {
+            return;
+        };
+        match db.enrichment_cache_prune().await {
+            Ok(0) => {}
+            Ok(removed) => debug!("Pruned {} expired enrichments", removed),
+            Err(e) => warn!("Failed to prune the enrichment cache: {}", e),
+        }
+    }
+
+    pub fn cache_stats(&self) -> EnrichCacheStats {
+        let memory_hits = self.memory_hits.load(Ordering::Relaxed);
+        let database_hits = self.database_hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = memory_hits + database_hits + misses;
+        EnrichCacheStats {
+            memory_hits,
+            database_hits,
+            misses,
+            hit_rate: if lookups == 0 { 0.0 } else { (memory_hits + database_hits) as f64 / lookups as f64 },
+            memory_entries: self.state.enrich_cache.len(),
+            online_calls: self.online_calls.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Enrich many points, returning the results in input order
+    ///
+    /// Points within the same cache cell asking for the same POIs are
+    /// enriched once, and at most a few lookups run at a time. The run makes
+    /// no more online lookups than the budget allows; points beyond it are
+    /// left uncovered. Fails with the first point that can't be enriched.
+    pub async fn enrich_points(&self, requests: Vec<EnrichRequest>) -> Result<Vec<EnrichResponse>> {
+        let mut unique: HashMap<String, EnrichRequest> = HashMap::new();
+        for request in &requests {
+            unique.entry(request_key(request)).or_insert_with(|| request.clone());
+        }
+        info!("Enriching {} points ({} distinct)", requests.len(), unique.len());
+
+        let budget = &self.run_budget();
+        let enriched: HashMap<String, EnrichResponse> = stream::iter(unique)
+            .map(|(key, request)| async move {
+                let response = self
+                    .enrich_within(&request, budget)
+                    .await
+                    .with_context(|| format!("Failed to enrich {}, {}", request.lat, request.lon))?;
+                Ok::<_, anyhow::Error>((key, response))
+            })
+            .buffer_unordered(MAX_CONCURRENT_ENRICHMENTS)
+            .try_collect()
+            .await?;
+        let uncovered = enriched.values().filter(|r| r.source == EnrichmentSource::Uncovered).count();
+        if uncovered > 0 {
+            info!("{} of {} distinct points left uncovered", uncovered, enriched.len());
+        }
+
+        Ok(requests
+            .iter()
+            .map(|request| {
+                let mut response = enriched[&request_key(request)].clone();
+                response.location = LocationResult { lat: request.lat, lon: request.lon };
+                response
+            })
+            .collect())
+    }
+
+    /// The areas a route passes through, in order, from the context of
+    /// points along it
+    ///
+    /// Points are sampled every 250 m, or further apart on long routes, and
+    /// looked up like any other, within one run's online lookups. GPS jitter near a border puts single
+    /// samples on the other side of it, so the route only counts as having
+    /// entered another area once [`MIN_AREA_RUN`] samples in a row are in
+    /// it; samples in shorter runs, and those nothing is known about, stay
+    /// in the area before them. Empty when nothing is known about any.
+    pub async fn summarize_route(&self, points: &[GpsPoint]) -> Result<Vec<RouteSegment>> {
+        let points: Vec<&GpsPoint> = points.iter().filter(|p| is_valid_coordinate(p.lat, p.lon)).collect();
+        let along = distances_along(&points);
+        let samples = route_samples(&along);
+
+        let budget = &self.run_budget();
+        // Built up front, so the lookups can run on a spawned task
+        let requests: Vec<EnrichRequest> = samples.iter().map(|&i| EnrichRequest::at(points[i].lat, points[i].lon)).collect();
+        let contexts: Vec<EnrichResponse> = stream::iter(requests)
+            .map(|request| async move { self.context_at(&request, budget).await })
+            .buffered(MAX_CONCURRENT_ENRICHMENTS)
+            .try_collect()
+            .await?;
+        let areas: Vec<Option<RouteArea>> = contexts.iter().map(|response| RouteArea::of(&response.context)).collect();
+        let segments = route_segments(&points, &along, &samples, &smooth_areas(&areas, MIN_AREA_RUN));
+        info!("Route of {} points passes through {} areas ({} samples)", points.len(), segments.len(), samples.len());
+        Ok(segments)
+    }
+
+    /// Enrich every located event of a video and keep the context and POIs
+    /// in each event's stored bundle
+    ///
+    /// Events within `merge_radius_m` of one already being looked up share
+    /// its lookup; whether a POI is in view is still worked out from each
+    /// event's own heading. Points past the run's online lookups are left
+    /// uncovered. The user's overrides of each event are applied over the
+    /// result. `on_progress` is told how many of the lookups
+    /// are done after each one. Cache hits and online calls are counted
+    /// across the engine, so lookups running alongside are included.
+    pub async fn enrich_video(
+        &self,
+        db: &LocalDatabase,
+        video_id: &str,
+        options: &EnrichVideoOptions,
+        on_progress: impl Fn(usize, usize),
+    ) -> Result<EnrichVideoSummary> {
+        let events = db.get_video_events(video_id).await?;
+        let heading_offset = db.camera_heading_offset(video_id).await?;
+        let merge_radius_m = options
+            .merge_radius_m
+            .filter(|r| r.is_finite() && *r >= 0.0)
+            .unwrap_or(DEFAULT_MERGE_RADIUS_M);
+        let fov_deg = options.fov_deg.filter(|f| f.is_finite() && *f > 0.0).unwrap_or(DEFAULT_FOV_DEG);
+
+        let located: Vec<(&Event, (f64, f64))> = events
+            .iter()
+            .filter_map(|e| Some((e, (e.lat?, e.lon?))))
+            .filter(|(_, (lat, lon))| is_valid_coordinate(*lat, *lon))
+            .collect();
+        let (points, lookup_of) = merge_nearby(located.iter().map(|(_, point)| *point), merge_radius_m);
+        info!("Enriching {} events of video {} at {} points", located.len(), video_id, points.len());
+
+        let before = self.cache_stats();
+        let total = points.len();
+        let budget = &self.run_budget();
+        let mut responses: Vec<Option<EnrichResponse>> = vec![None; total];
+        let mut lookups = stream::iter(points.iter().copied().enumerate())
+            .map(|(i, (lat, lon))| async move {
+                let request = EnrichRequest {
+                    radius_m: options.radius_m,
+                    fov_deg: options.fov_deg,
+                    categories: options.categories.clone(),
+                    ..EnrichRequest::at(lat, lon)
+                };
+                (i, self.enrich_within(&request, budget).await)
+            })
+            .buffer_unordered(MAX_CONCURRENT_ENRICHMENTS);
+        let mut done = 0;
+        while let Some((i, response)) = lookups.next().await {
+            let (lat, lon) = points[i];
+            responses[i] = Some(response.with_context(|| format!("Failed to enrich {}, {}", lat, lon))?);
+            done += 1;
+            on_progress(done, total);
+        }
+        drop(lookups);
+
+        // Applied last, so enriching again never undoes what the user set
+        let user_overrides = db.get_video_event_overrides(video_id).await?;
+        let pinned = overrides::pinned_pois(db, &user_overrides).await;
+        let mut enriched = Vec::with_capacity(located.len());
+        for ((event, _), lookup) in located.iter().zip(lookup_of) {
+            let Some(mut truth) = stored_truth(event) else {
+                warn!("Event {} has a stored bundle that can't be read, leaving it as it is", event.id);
+                continue;
+            };
+            let Some(response) = &responses[lookup] else {
+                continue;
+            };
+            let camera = event.heading_deg.map(|h| camera_bearing(h, heading_offset));
+            attach_enrichment(&mut truth, response, camera, fov_deg);
+            overrides::apply(&mut truth, &user_overrides, &pinned);
+            enriched.push(truth);
+        }
+        let language = options.language.as_deref().unwrap_or("English");
+        let pois_summarized = self.add_article_summaries(&mut enriched, language).await;
+
+        let updates = enriched
+            .iter()
+            .map(|truth| Ok((truth.id.clone(), serde_json::to_string(truth)?)))
+            .collect::<Result<Vec<_>>>()?;
+        db.set_event_truth(&updates).await?;
+
+        let after = self.cache_stats();
+        let summary = EnrichVideoSummary {
+            video_id: video_id.to_string(),
+            events_enriched: updates.len(),
+            events_skipped: events.len() - updates.len(),
+            points_enriched: total,
+            pois_summarized,
+            points_uncovered: responses.iter().flatten().filter(|r| r.source == EnrichmentSource::Uncovered).count(),
+            cache_hits: (after.memory_hits + after.database_hits) - (before.memory_hits + before.database_hits),
+            online_calls: after.online_calls - before.online_calls,
+        };
+        info!(
+            "Enriched {} events of video {} ({} skipped): {} points ({} uncovered), {} cache hits, {} online calls",
+            summary.events_enriched, video_id, summary.events_skipped, summary.points_enriched,
+            summary.points_uncovered, summary.cache_hits, summary.online_calls
         );
-        
-        let text = self.gemini.generate_content(&prompt).await?;
-        
-        // Very basic parsing for demo
-        // In real app, use serde_json::from_str with specific struct
-        // For now, assuming somewhat structured text or just extracting blindly
-        // or just return dummy to prove flow
-        if text.contains("json") {
-             // strip and parse
+        Ok(summary)
+    }
+
+    /// Describe what the camera sees at each of a video's events
+    ///
+    /// One frame per event, from the middle of the event, is sent to the
+    /// model in batches. Fails if an event doesn't belong to the video.
+    pub async fn describe_event_scenes(
+        &self,
+        db: &LocalDatabase,
+        ffmpeg: &Ffmpeg,
+        video_id: &str,
+        event_ids: &[String],
+    ) -> Result<SceneDescriptions> {
+        let video = db.get_video(video_id).await.context("Video not found")?;
+        let events = db.get_video_events(video_id).await?;
+        let video_path = PathBuf::from(&video.file_path);
+
+        let mut frames = Vec::with_capacity(event_ids.len());
+        for (index, event_id) in event_ids.iter().enumerate() {
+            let event = events
+                .iter()
+                .find(|e| e.id == *event_id)
+                .ok_or_else(|| anyhow::anyhow!("Event {} doesn't belong to video {}", event_id, video_id))?;
+            let end = event.end_time_seconds.unwrap_or(event.start_time_seconds);
+            let timestamp_ms = ((event.start_time_seconds + end) / 2.0 * 1000.0).max(0.0) as u64;
+
+            let frame = ffmpeg
+                .capture_frame(&video_path, timestamp_ms, ImageFormat::Jpeg, Priority::Batch)
+                .await
+                .with_context(|| format!("Failed to capture the frame for event {}", event_id))?;
+            frames.push((event_id.clone(), ImagePart::from_base64(index, &frame)?));
+        }
+
+        debug!("Describing {} event scene(s) of video {}", frames.len(), video_id);
+        Ok(scenes::describe_frames_batch(self.llm.as_ref(), frames, SCENE_PROMPT).await?)
+    }
+
+    /// The model's idea of where a point is; fields it didn't know are `None`
+    async fn ask_llm_location(&self, lat: f64, lon: f64) -> Result<LocationContext> {
+        let prompt = prompts::load(PromptName::Enrichment)
+            .render(&[("lat", &lat.to_string()), ("lon", &lon.to_string())]);
+
+        self.online_calls.fetch_add(1, Ordering::Relaxed);
+        let text = self.llm.generate_content(&prompt).await?.text;
+        let answer = match parse_location(&text) {
+            Ok(answer) => answer,
+            Err(e) => {
+                // Once more in JSON mode, which leaves no room for prose around it
+                debug!("Unusable location answer ({:#}), asking again for JSON only", e);
+                self.online_calls.fetch_add(1, Ordering::Relaxed);
+                let text = self.llm.generate_multimodal(&prompt, vec![], Some(location_schema()), true).await?.text;
+                parse_location(&text)?
+            }
+        };
+
+        Ok(LocationContext {
+            country: known(answer.country),
+            state: known(answer.state),
+            county: known(answer.county),
+            city: known(answer.city),
+            road: known(answer.road),
+            population: answer.population.as_ref().and_then(population),
+            ..Default::default()
+        })
+    }
+}
+
+/// The names a point goes by, picked from the map features around it
+#[derive(Debug, Default, PartialEq)]
+struct PlaceNames {
+    /// The closest neighbourhood, suburb or town within [`LOCAL_PLACE_RADIUS_M`],
+    /// else the closest city within [`CITY_RADIUS_M`]
+    place: Option<String>,
+    /// The closest road within [`ROAD_RADIUS_M`]
+    road: Option<String>,
+}
+
+/// Pick the place and road names from a point's candidates
+///
+/// The nearest feature is often something no one would name a place by,
+/// like a parking aisle, so only the kinds of feature that name places are
+/// considered, and the part of town beats the city around it.
+fn place_names(candidates: &[PlaceCandidate]) -> PlaceNames {
+    let nearest = |wanted: fn(PlaceKind) -> bool, radius_m: f64| {
+        candidates
+            .iter()
+            .filter(|c| wanted(c.kind) && c.distance_m <= radius_m && !c.name.trim().is_empty())
+            .min_by(|a, b| a.distance_m.total_cmp(&b.distance_m))
+            .map(|c| c.name.trim().to_string())
+    };
+    PlaceNames {
+        place: nearest(PlaceKind::is_local, LOCAL_PLACE_RADIUS_M)
+            .or_else(|| nearest(|kind| kind == PlaceKind::City, CITY_RADIUS_M)),
+        road: nearest(|kind| kind == PlaceKind::Road, ROAD_RADIUS_M),
+    }
+}
+
+/// Context from the areas containing a point, as ordered by
+/// `admin_areas_at`; `None` without a country or state
+///
+/// Each field takes the tightest area of its level. The population and
+/// time zone are the most local ones known, from the town up.
+fn context_from_areas(areas: &[AdminArea]) -> Option<LocationContext> {
+    let at = |levels: &[i32]| {
+        levels
+            .iter()
+            .find_map(|level| areas.iter().find(|a| a.admin_level == *level))
+            .map(|a| a.name.clone())
+    };
+    let most_local = |a: &&AdminArea, b: &&AdminArea| b.admin_level.cmp(&a.admin_level).then(a.extent.total_cmp(&b.extent));
+    let context = LocationContext {
+        country: at(&[2]),
+        region: at(&[3, 5]),
+        state: at(&[4]),
+        county: at(&[6]),
+        city: at(&[8, 7, 9]),
+        population: areas
+            .iter()
+            .filter(|a| a.population.is_some_and(|p| p > 0))
+            .min_by(most_local)
+            .and_then(|a| a.population),
+        timezone: areas.iter().filter(|a| a.timezone.is_some()).min_by(most_local).and_then(|a| a.timezone.clone()),
+        ..Default::default()
+    };
+    (context.country.is_some() || context.state.is_some()).then_some(context)
+}
+
+/// The time `now` in an IANA time zone, e.g. `Europe/Monaco`; `None` for
+/// a zone that isn't known
+fn local_time(timezone: &str, now: DateTime<Utc>) -> Option<String> {
+    let tz: chrono_tz::Tz = timezone.trim().parse().ok()?;
+    Some(now.with_timezone(&tz).to_rfc3339())
+}
+
+/// Points to look up for `points`, each point further than `radius_m` from
+/// those before it, and which of them each of `points` is answered by
+fn merge_nearby(points: impl Iterator<Item = (f64, f64)>, radius_m: f64) -> (Vec<(f64, f64)>, Vec<usize>) {
+    let mut lookups: Vec<(f64, f64)> = Vec::new();
+    let assigned = points
+        .map(|(lat, lon)| {
+            let near = lookups
+                .iter()
+                .position(|&(l_lat, l_lon)| haversine_distance(lat, lon, l_lat, l_lon) * 1000.0 <= radius_m);
+            near.unwrap_or_else(|| {
+                lookups.push((lat, lon));
+                lookups.len() - 1
+            })
+        })
+        .collect();
+    (lookups, assigned)
+}
+
+/// The event's stored Truth Event, or one made from its row when it has
+/// none; `None` when what's stored isn't a Truth Event
+pub(crate) fn stored_truth(event: &Event) -> Option<TruthEvent> {
+    if let Some(json) = &event.truth_bundle_json {
+        return serde_json::from_str(json).ok();
+    }
+    let kind = serde_json::from_value(serde_json::Value::String(event.event_type.clone())).unwrap_or_default();
+    Some(TruthEvent {
+        id: event.id.clone(),
+        kind,
+        timestamp: event.created_at,
+        local_time: None,
+        time_uncertain: false,
+        duration_seconds: event.end_time_seconds.map(|end| end - event.start_time_seconds),
+        location: event.lat.zip(event.lon).map(|(lat, lon)| LocationResult { lat, lon }),
+        heading_deg: event.heading_deg,
+        pois: vec![],
+        detected_objects: vec![],
+        image_path: None,
+        sun: None,
+        weather: None,
+        context: None,
+    })
+}
+
+/// Give an event the enriched context and the POIs it didn't have yet,
+/// in view or not from `camera_bearing`
+fn attach_enrichment(truth: &mut TruthEvent, response: &EnrichResponse, camera_bearing: Option<f64>, fov_deg: f64) {
+    truth.location.get_or_insert_with(|| response.location.clone());
+    truth.context = Some(response.context.clone());
+    truth.localize_time();
+    for poi in &response.pois {
+        if truth.pois.iter().any(|p| p.id == poi.id) {
+            continue;
+        }
+        let mut poi = poi.clone();
+        poi.in_fov = camera_bearing.is_some_and(|camera| in_fov(camera, poi.bearing_deg, fov_deg));
+        truth.pois.push(poi);
+    }
+}
+
+/// Where a route sample is, as far as route segments go
+#[derive(Debug, Clone, PartialEq)]
+struct RouteArea {
+    country: Option<String>,
+    region: Option<String>,
+    city: Option<String>,
+}
+
+impl RouteArea {
+    /// `None` when the context doesn't say
+    fn of(context: &LocationContext) -> Option<Self> {
+        let area = RouteArea {
+            country: context.country.clone(),
+            region: context.region.clone().or_else(|| context.state.clone()),
+            city: context.city.clone(),
+        };
+        (area.country.is_some() || area.region.is_some() || area.city.is_some()).then_some(area)
+    }
+}
+
+/// Kilometres from the first point to each one
+fn distances_along(points: &[&GpsPoint]) -> Vec<f64> {
+    let mut total = 0.0;
+    let mut along = Vec::with_capacity(points.len());
+    for (i, point) in points.iter().enumerate() {
+        if i > 0 {
+            total += haversine_distance(points[i - 1].lat, points[i - 1].lon, point.lat, point.lon);
+        }
+        along.push(total);
+    }
+    along
+}
+
+/// Indexes of the points to look up: the first, then one each
+/// [`ROUTE_SAMPLE_SPACING_M`] further, or enough further for at most
+/// [`MAX_ROUTE_SAMPLES`], and the last
+/// The points of a route [`EnrichmentEngine::summarize_route`] looks up
+pub(crate) fn sampled_route(points: &[GpsPoint]) -> Vec<&GpsPoint> {
+    let points: Vec<&GpsPoint> = points.iter().filter(|p| is_valid_coordinate(p.lat, p.lon)).collect();
+    route_samples(&distances_along(&points)).into_iter().map(|i| points[i]).collect()
+}
+
+fn route_samples(along: &[f64]) -> Vec<usize> {
+    let Some(&total) = along.last() else {
+        return Vec::new();
+    };
+    let spacing_km = (ROUTE_SAMPLE_SPACING_M / 1000.0).max(total / (MAX_ROUTE_SAMPLES - 1) as f64);
+    let mut samples = vec![0];
+    for (i, &distance) in along.iter().enumerate().skip(1) {
+        if distance - along[samples[samples.len() - 1]] >= spacing_km {
+            samples.push(i);
+        }
+    }
+    let last = along.len() - 1;
+    if samples[samples.len() - 1] != last {
+        // Just short of the spacing would make one sample too many
+        if samples.len() == MAX_ROUTE_SAMPLES {
+            samples.pop();
+        }
+        samples.push(last);
+    }
+    samples
+}
+
+/// Each sample's area once runs shorter than `min_run` are taken for
+/// jitter: they, and samples with no area, keep the area before them
+///
+/// Before the first run that's long enough, samples take its area, or the
+/// first known one if none is.
+fn smooth_areas(areas: &[Option<RouteArea>], min_run: usize) -> Vec<Option<RouteArea>> {
+    let mut runs: Vec<(&Option<RouteArea>, usize)> = Vec::new();
+    for area in areas {
+        match runs.last_mut() {
+            Some((current, count)) if *current == area => *count += 1,
+            _ => runs.push((area, 1)),
+        }
+    }
+
+    let known = runs.iter().filter(|(area, _)| area.is_some());
+    let mut current = known.clone().find(|(_, count)| *count >= min_run).or(known.clone().next()).map(|(area, _)| *area);
+    let mut smoothed = Vec::with_capacity(areas.len());
+    for (area, count) in runs {
+        if area.is_some() && count >= min_run {
+            current = Some(area);
+        }
+        smoothed.resize(smoothed.len() + count, current.cloned().flatten());
+    }
+    smoothed
+}
+
+/// Consecutive samples in the same area as one segment, from its first
+/// sample to the next segment's first, or the end of the route
+fn route_segments(
+    points: &[&GpsPoint],
+    along: &[f64],
+    samples: &[usize],
+    areas: &[Option<RouteArea>],
+) -> Vec<RouteSegment> {
+    let mut starts: Vec<usize> = Vec::new();
+    for i in 0..areas.len() {
+        if i == 0 || areas[i] != areas[i - 1] {
+            starts.push(i);
         }
-        
-        Ok(("AI Country".to_string(), "AI City".to_string(), None))
     }
+
+    starts
+        .iter()
+        .enumerate()
+        .filter_map(|(n, &first)| {
+            let area = areas[first].as_ref()?;
+            let from = if n == 0 { 0 } else { samples[first] };
+            let to = starts.get(n + 1).map_or(points.len() - 1, |&next| samples[next]);
+            Some(RouteSegment {
+                start_time: points[from].timestamp,
+                end_time: points[to].timestamp,
+                country: area.country.clone(),
+                region: area.region.clone(),
+                city: area.city.clone(),
+                distance_km: along[to] - along[from],
+            })
+        })
+        .collect()
+}
+
+/// Attribute the values of an enrichment kept before values were
+/// attributed; its whole context came from the one source
+fn attribute_to_source(response: &mut EnrichResponse) {
+    match response.source {
+        EnrichmentSource::Local => response.context.attribute(FactSource::MapData, LOCAL_CONFIDENCE),
+        EnrichmentSource::Llm => response.context.attribute(FactSource::Llm, LLM_CONFIDENCE),
+        EnrichmentSource::Partial | EnrichmentSource::Uncovered => {}
+    }
+}
+
+/// Location as the enrichment prompt asks the model for it
+#[derive(Deserialize)]
+struct LlmLocation {
+    country: Option<String>,
+    #[serde(default)]
+    state: Option<String>,
+    #[serde(default)]
+    county: Option<String>,
+    city: Option<String>,
+    road: Option<String>,
+    /// A number, though models write some as text, e.g. "38,000"
+    #[serde(default)]
+    population: Option<serde_json::Value>,
+}
+
+/// A positive population, from a number or digits with separators
+fn population(value: &serde_json::Value) -> Option<i64> {
+    let population = match value {
+        serde_json::Value::Number(n) => n.as_i64().or_else(|| n.as_f64().map(|f| f.round() as i64))?,
+        serde_json::Value::String(s) => {
+            let digits: String = s.chars().filter(|c| !matches!(c, ',' | '.' | ' ' | '_')).collect();
+            digits.parse().ok()?
+        }
+        _ => return None,
+    };
+    (population > 0).then_some(population)
+}
+
+fn parse_location(text: &str) -> Result<LlmLocation> {
+    let json = extract_json(text).context("No JSON in the model's location answer")?;
+    serde_json::from_str(&json).context("Unexpected location answer from the model")
 }
 
-// Helper for String ownership
+/// Response schema for [`LlmLocation`]
+fn location_schema() -> serde_json::Value {
+    let name = || serde_json::json!({ "type": "STRING", "nullable": true });
+    serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "country": name(),
+            "state": name(),
+            "county": name(),
+            "city": name(),
+            "road": name(),
+            "population": { "type": "INTEGER", "nullable": true }
+        },
+        "propertyOrdering": ["country", "state", "county", "city", "road", "population"]
+    })
+}
+
+/// The value, unless the model left it blank, said it doesn't know or
+/// wrote more than a name
+fn known(value: Option<String>) -> Option<String> {
+    value.map(|v| v.trim().to_string()).filter(|v| {
+        !v.is_empty()
+            && !v.eq_ignore_ascii_case("unknown")
+            && !v.eq_ignore_ascii_case("null")
+            && v.chars().count() <= MAX_NAME_CHARS
+    })
+}
+
+/// Cache key for a point, rounded to 4 decimal places (about 10 m)
+fn enrich_cache_key(request: &EnrichRequest) -> String {
+    format!("enrich:{:.4}:{:.4}", request.lat, request.lon)
+}
+
+/// The cache key plus what decides the POIs and details, for requests answered alike
+fn request_key(request: &EnrichRequest) -> String {
+    format!(
+        "{}:{:?}:{:?}:{:?}:{}:{}",
+        enrich_cache_key(request),
+        request.radius_m,
+        request.heading_deg,
+        request.fov_deg,
+        request.categories.join(","),
+        request.verbose
+    )
+}
+
+/// Whether a POI is of one of the categories, or there are none to match
+fn in_categories(poi: &PoiRecord, categories: &[String]) -> bool {
+    categories.is_empty() || categories.iter().any(|c| c.eq_ignore_ascii_case(&poi.category))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::mock::MockGemini;
+    use crate::gemini::GeminiError;
+    use crate::llm::BackendFuture;
+    use crate::services::truth_engine::VerificationConfidence;
+    use chrono::TimeZone;
+
+    /// A remote model that fails the test if it's asked anything
+    struct PanickingBackend;
+
+    impl LlmBackend for PanickingBackend {
+        fn generate_with_system<'a>(
+            &'a self,
+            _system_instruction: Option<&'a str>,
+            prompt: &'a str,
+            _images: Vec<ImagePart>,
+            _response_schema: Option<serde_json::Value>,
+            _allow_cache: bool,
+        ) -> BackendFuture<'a> {
+            panic!("The model was asked: {}", prompt)
+        }
+
+        fn model(&self) -> String {
+            "none".to_string()
+        }
+
+        fn engine(&self) -> &'static str {
+            "gemini"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_location_falls_back_to_gemini() {
+        let mock = Arc::new(MockGemini::new().with_text(r#"{"country": "Monaco", "city": "Monaco"}"#));
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        );
+
+        let response = engine.enrich_point(EnrichRequest::at(43.7384, 7.4246)).await.unwrap();
+
+        assert_eq!(mock.call_count(), 1);
+        assert!(mock.prompts()[0].contains("43.7384"));
+        assert_eq!(response.context.city.as_deref(), Some("Monaco"));
+        assert_eq!(response.context.road, None);
+        assert_eq!(response.source, EnrichmentSource::Llm);
+        assert!(response.confidence < LOCAL_CONFIDENCE);
 
+        // Each value says it's a guess; fields the model didn't know have no entry
+        let city = &response.context.attribution["city"];
+        assert_eq!((city.value.as_str(), city.source), ("Monaco", FactSource::Llm));
+        assert_eq!(city.confidence, LLM_CONFIDENCE);
+        assert!(!response.context.attribution.contains_key("road"));
+        assert!(response.context.unattributed().is_empty());
+    }
+
+    /// Named features in and around Monaco, as the map data has them
+    const MONACO_FEATURES: &[(&str, PlaceKind, f64, f64)] = &[
+        ("Monaco", PlaceKind::City, 43.7384, 7.4246),
+        ("Monte-Carlo", PlaceKind::Neighbourhood, 43.7396, 7.4275),
+        ("Monaco-Ville", PlaceKind::Neighbourhood, 43.7311, 7.4197),
+        ("La Condamine", PlaceKind::Neighbourhood, 43.7347, 7.4197),
+        ("Fontvieille", PlaceKind::Neighbourhood, 43.7275, 7.4160),
+        ("Beausoleil", PlaceKind::Town, 43.7425, 7.4237),
+        ("Nice", PlaceKind::City, 43.7034, 7.2663),
+        ("Avenue de Monte-Carlo", PlaceKind::Road, 43.7390, 7.4278),
+        ("Boulevard Albert 1er", PlaceKind::Road, 43.7357, 7.4217),
+        ("Quai Antoine 1er", PlaceKind::Road, 43.7330, 7.4225),
+        ("Parking des Pêcheurs", PlaceKind::Other, 43.7318, 7.4240),
+    ];
+
+    /// The fixture's features as a point's candidates, nearest first
+    fn monaco_candidates(lat: f64, lon: f64) -> Vec<PlaceCandidate> {
+        let mut candidates: Vec<PlaceCandidate> = MONACO_FEATURES
+            .iter()
+            .map(|&(name, kind, f_lat, f_lon)| PlaceCandidate {
+                name: name.to_string(),
+                kind,
+                distance_m: haversine_distance(lat, lon, f_lat, f_lon) * 1000.0,
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+        candidates
+    }
+
+    #[test]
+    fn test_monaco_place_names() {
+        let points = [
+            ("Casino square", 43.7393, 7.4283),
+            ("Fishermen's car park", 43.7318, 7.4240),
+            ("Stade Louis II", 43.7276, 7.4156),
+            ("Beausoleil", 43.7430, 7.4240),
+            ("Offshore", 43.7000, 7.5500),
+            ("Alps", 44.5000, 8.5000),
+        ];
+        let names: Vec<String> = points
+            .iter()
+            .map(|&(label, lat, lon)| {
+                let names = place_names(&monaco_candidates(lat, lon));
+                let or_none = |name: Option<String>| name.unwrap_or_else(|| "-".to_string());
+                format!("{}: {} / {}", label, or_none(names.place), or_none(names.road))
+            })
+            .collect();
+
+        assert_eq!(
+            names,
+            [
+                "Casino square: Monte-Carlo / Avenue de Monte-Carlo",
+                // The car park is nearest, but no one calls the place that
+                "Fishermen's car park: Monaco-Ville / -",
+                "Stade Louis II: Fontvieille / -",
+                "Beausoleil: Beausoleil / -",
+                // No part of town within reach, but the city is
+                "Offshore: Monaco / -",
+                "Alps: - / -",
+            ]
+        );
+        assert_eq!(monaco_candidates(43.7318, 7.4240)[0].kind, PlaceKind::Other);
+    }
+
+    #[tokio::test]
+    async fn test_verbose_requests_list_place_candidates() {
+        let location = r#"{"country": "Monaco", "city": "Monaco"}"#;
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            Arc::new(MockGemini::new().with_text(location)),
+        );
+
+        let verbose = EnrichRequest { verbose: true, ..EnrichRequest::at(43.7384, 7.4246) };
+        let response = engine.enrich_point(verbose).await.unwrap();
+        assert!(response.debug.is_some_and(|debug| debug.place_candidates.is_empty()));
+
+        // Answered from the cache, without the details
+        let response = engine.enrich_point(EnrichRequest::at(43.7384, 7.4246)).await.unwrap();
+        assert!(response.debug.is_none());
+        assert_eq!(response.context.city.as_deref(), Some("Monaco"));
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_request_is_an_error() {
+        let mock = Arc::new(MockGemini::new());
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        );
+
+        for (lat, lon) in [(9999.0, 7.4246), (43.7384, -180.5), (f64::NAN, 7.4246)] {
+            let error = engine.enrich_point(EnrichRequest::at(lat, lon)).await.unwrap_err();
+            assert!(error.to_string().contains("out of range"), "{}", error);
+        }
+        assert_eq!(mock.call_count(), 0);
+        assert_eq!(engine.cache_stats().misses, 0);
+    }
+
+    #[test]
+    fn test_requests_for_other_pois_are_not_merged() {
+        let point = EnrichRequest::at(43.7384, 7.4246);
+        let nearby = EnrichRequest::at(43.73841, 7.42462);
+        let facing_east = EnrichRequest { heading_deg: Some(90.0), ..point.clone() };
+        let castles = EnrichRequest { categories: vec!["castle".to_string()], ..point.clone() };
+
+        assert_eq!(request_key(&point), request_key(&nearby));
+        assert_ne!(request_key(&point), request_key(&facing_east));
+        assert_ne!(request_key(&point), request_key(&castles));
+        // They still share the location context
+        assert_eq!(enrich_cache_key(&point), enrich_cache_key(&castles));
+
+        let record = PoiRecord { id: "1".into(), name: "Fort".into(), category: "Castle".into(), ..Default::default() };
+        assert!(in_categories(&record, &[]));
+        assert!(in_categories(&record, &castles.categories));
+        assert!(!in_categories(&record, &["museum".to_string()]));
+    }
+
+    #[test]
+    fn test_local_time_in_a_time_zone() {
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap();
+        assert_eq!(local_time("Europe/Monaco", now).as_deref(), Some("2024-07-01T14:00:00+02:00"));
+        assert_eq!(local_time("America/New_York", now - Duration::days(180)).as_deref(), Some("2024-01-03T07:00:00-05:00"));
+        assert_eq!(local_time("Mars/Olympus_Mons", now), None);
+    }
+
+    #[test]
+    fn test_monaco_boundaries_give_country_and_population() {
+        let area = |name: &str, admin_level: i32, population: Option<i64>, extent: f64| AdminArea {
+            name: name.to_string(),
+            admin_level,
+            population,
+            extent,
+            timezone: None,
+        };
+        // As `admin_areas_at` orders them for the Casino de Monte-Carlo;
+        // France's box is the larger of the two countries'
+        let areas = [
+            AdminArea { timezone: Some("Europe/Monaco".to_string()), ..area("Monaco", 2, Some(38_682), 0.0006) },
+            AdminArea { timezone: Some("Europe/Paris".to_string()), ..area("France", 2, Some(68_000_000), 200.0) },
+            area("Provence-Alpes-Côte d'Azur", 4, None, 8.0),
+            area("Monte-Carlo", 8, None, 0.0001),
+            area("Monte-Carlo Quarter", 10, Some(0), 0.00005),
+        ];
+
+        let context = context_from_areas(&areas).unwrap();
+        assert_eq!(context.country.as_deref(), Some("Monaco"));
+        assert_eq!(context.population, Some(38_682));
+        assert_eq!(context.city.as_deref(), Some("Monte-Carlo"));
+        assert_eq!(context.state.as_deref(), Some("Provence-Alpes-Côte d'Azur"));
+        assert_eq!(context.county, None);
+        assert_eq!(context.timezone.as_deref(), Some("Europe/Monaco"));
+
+        // Towns alone don't say where a point is
+        assert!(context_from_areas(&areas[3..]).is_none());
+        assert!(context_from_areas(&[]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_llm_answer_fills_admin_areas_and_population() {
+        let answer = r#"{"country": "Monaco", "state": null, "county": "unknown", "city": "Monaco",
+                         "road": null, "population": "38,682"}"#;
+        let mock = Arc::new(MockGemini::new().with_text(answer));
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        );
+
+        let response = engine.enrich_point(EnrichRequest::at(43.7384, 7.4246)).await.unwrap();
+        assert_eq!(response.context.country.as_deref(), Some("Monaco"));
+        assert_eq!(response.context.population, Some(38_682));
+        assert_eq!(response.context.county, None);
+        assert_eq!(response.context.attribution["population"].source, FactSource::Llm);
+        assert!(mock.prompts()[0].contains("population"));
+        // Every value is marked as the model's, and no more than medium confidence
+        assert!(response.context.unattributed().is_empty());
+        assert!(response.context.attribution.values().all(|a| a.confidence <= VerificationConfidence::Medium.as_f64()));
+
+        assert_eq!(population(&serde_json::json!(38682.4)), Some(38_682));
+        assert_eq!(population(&serde_json::json!(-5)), None);
+        assert_eq!(population(&serde_json::json!("about forty thousand")), None);
+    }
+
+    #[test]
+    fn test_enrichments_kept_before_attribution_are_attributed() {
+        let json = r#"{"location": {"lat": 43.7384, "lon": 7.4246},
+            "context": {"country": "Monaco", "city": "Monte-Carlo", "road": null, "region": null,
+                        "population": 38682, "timezone": "Europe/Monaco", "elevation_m": 65.0},
+            "pois": [], "source": "local", "confidence": 0.9}"#;
+        let mut response: EnrichResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.context.unattributed(), ["country", "city", "population", "timezone", "elevation_m"]);
+
+        attribute_to_source(&mut response);
+        assert!(response.context.unattributed().is_empty());
+        let elevation = &response.context.attribution["elevation_m"];
+        assert_eq!((elevation.value.as_str(), elevation.source), ("65", FactSource::MapData));
+        assert_eq!(elevation.confidence, LOCAL_CONFIDENCE);
+    }
+
+    #[test]
+    fn test_border_jitter_is_smoothed_out() {
+        let area = |country: &str| Some(RouteArea { country: Some(country.to_string()), region: None, city: None });
+        let (monaco, france) = (area("Monaco"), area("France"));
+        // Monaco, a blip into France and a sample nothing is known about, then France for good
+        let areas = [
+            france.clone(), monaco.clone(), monaco.clone(), monaco.clone(), france.clone(), None,
+            monaco.clone(), france.clone(), france.clone(), france.clone(), france.clone(),
+        ];
+
+        let smoothed = smooth_areas(&areas, 3);
+        assert_eq!(smoothed, [vec![monaco.clone(); 7], vec![france.clone(); 4]].concat());
+
+        // No run is long enough: the first area known holds
+        assert_eq!(smooth_areas(&[None, france.clone(), monaco.clone()], 3), vec![france.clone(); 3]);
+        assert_eq!(smooth_areas(&[None, None], 3), vec![None, None]);
+    }
+
+    #[test]
+    fn test_route_segments_split_where_the_area_changes() {
+        let start = Utc::now();
+        // One point a minute, 0.001° (about 111 m) further north each time
+        let points: Vec<GpsPoint> = (0..41)
+            .map(|i| GpsPoint {
+                timestamp: start + Duration::minutes(i),
+                lat: 43.70 + i as f64 * 0.001,
+                lon: 7.42,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: None,
+                accuracy_m: None,
+            })
+            .collect();
+        let points: Vec<&GpsPoint> = points.iter().collect();
+        let along = distances_along(&points);
+        let samples = route_samples(&along);
+        assert_eq!(samples[..3], [0, 3, 6]);
+        assert_eq!(samples.last(), Some(&40));
+
+        let area = |city: &str| Some(RouteArea { country: Some("Monaco".into()), region: None, city: Some(city.into()) });
+        let mut areas = vec![area("Monaco"); samples.len()];
+        let entered = samples.len() / 2;
+        areas[entered..].fill(area("Monte-Carlo"));
+
+        let segments = route_segments(&points, &along, &samples, &areas);
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].start_time, start);
+        assert_eq!(segments[0].end_time, segments[1].start_time);
+        assert_eq!(segments[1].end_time, start + Duration::minutes(40));
+        assert_eq!(segments[1].city.as_deref(), Some("Monte-Carlo"));
+        let total: f64 = segments.iter().map(|s| s.distance_km).sum();
+        assert!((total - 4.45).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_close_points_share_a_lookup() {
+        // 0.0003 degrees of latitude is about 33 m
+        let points = [(43.7384, 7.4246), (43.7387, 7.4246), (43.7400, 7.4246), (43.7388, 7.4246), (43.7384, 7.4246)];
+
+        let (lookups, assigned) = merge_nearby(points.into_iter(), 50.0);
+        assert_eq!(lookups, [(43.7384, 7.4246), (43.7400, 7.4246)]);
+        assert_eq!(assigned, [0, 0, 1, 0, 0]);
+
+        let (lookups, assigned) = merge_nearby(points.into_iter(), 0.0);
+        assert_eq!(lookups.len(), 4);
+        assert_eq!(assigned, [0, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_enrichment_is_attached_to_events() {
+        let row = Event {
+            id: "e1".to_string(),
+            video_id: "v1".to_string(),
+            event_type: "nearest_pass".to_string(),
+            start_time_seconds: 10.0,
+            end_time_seconds: Some(12.5),
+            lat: Some(43.7384),
+            lon: Some(7.4246),
+            heading_deg: Some(90.0),
+            verified: false,
+            verification_mode: None,
+            truth_bundle_json: None,
+            created_at: Utc::now(),
+        };
+        let mut truth = stored_truth(&row).unwrap();
+        assert_eq!(truth.kind, crate::types::EventKind::NearestPass);
+        assert_eq!(truth.duration_seconds, Some(2.5));
+        assert_eq!(truth.location.as_ref().map(|l| l.lat), Some(43.7384));
+
+        let poi = |id: &str, bearing_deg: f64| POI {
+            id: id.to_string(),
+            name: id.to_string(),
+            name_local: None,
+            category: "landmark".to_string(),
+            subcategory: None,
+            lat: 43.74,
+            lon: 7.43,
+            distance_m: 100.0,
+            bearing_deg,
+            in_fov: false,
+            confidence: 1.0,
+            facts: None,
+            pinned: false,
+        };
+        truth.pois.push(poi("casino", 180.0));
+        let response = EnrichResponse {
+            location: LocationResult { lat: 43.7385, lon: 7.4247 },
+            context: LocationContext { country: Some("Monaco".to_string()), ..Default::default() },
+            pois: vec![poi("casino", 90.0), poi("palace", 95.0), poi("port", 270.0)],
+            source: EnrichmentSource::Llm,
+            confidence: LLM_CONFIDENCE,
+            debug: None,
+        };
+
+        attach_enrichment(&mut truth, &response, Some(90.0), 60.0);
+        assert_eq!(truth.context.as_ref().and_then(|c| c.country.as_deref()), Some("Monaco"));
+        assert_eq!(truth.location.as_ref().map(|l| l.lat), Some(43.7384));
+        let pois: Vec<(&str, bool)> = truth.pois.iter().map(|p| (p.id.as_str(), p.in_fov)).collect();
+        assert_eq!(pois, [("casino", false), ("palace", true), ("port", false)]);
+
+        // Stored bundles are kept, unless they can't be read
+        let stored = Event { truth_bundle_json: Some(serde_json::to_string(&truth).unwrap()), ..row.clone() };
+        assert_eq!(stored_truth(&stored).unwrap().pois.len(), 3);
+        assert!(stored_truth(&Event { truth_bundle_json: Some("{}".to_string()), ..row }).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_enrich_points_dedupes_and_keeps_order() {
+        let location = r#"{"country": "Monaco", "city": "Monaco"}"#;
+        let mock = Arc::new(MockGemini::new().with_text(location).with_text(location));
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        );
+
+        let points = [(43.7384, 7.4246), (43.7311, 7.4197), (43.73841, 7.42462), (43.7384, 7.4246)];
+        let requests = points.iter().map(|&(lat, lon)| EnrichRequest::at(lat, lon)).collect();
+        let responses = engine.enrich_points(requests).await.unwrap();
+
+        // Two distinct cells, one lookup each
+        assert_eq!(mock.call_count(), 2);
+        let returned: Vec<(f64, f64)> = responses.iter().map(|r| (r.location.lat, r.location.lon)).collect();
+        assert_eq!(returned, points);
+
+        // Already cached
+        engine.enrich_point(EnrichRequest::at(43.7311, 7.4197)).await.unwrap();
+        assert_eq!(mock.call_count(), 2);
+        let stats = engine.cache_stats();
+        assert_eq!((stats.memory_hits, stats.database_hits, stats.misses), (1, 0, 2));
+        assert_eq!((stats.hit_rate * 3.0).round(), 1.0);
+        assert_eq!(stats.memory_entries, 2);
+    }
+
+    #[tokio::test]
+    async fn test_offline_mode_never_asks_a_remote_model() {
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            Arc::new(PanickingBackend),
+        )
+        .with_connectivity(ConnectivityMode::Offline);
+
+        let requests = vec![EnrichRequest::at(43.7384, 7.4246), EnrichRequest::at(48.8584, 2.2945)];
+        let responses = engine.enrich_points(requests).await.unwrap();
+        assert!(responses.iter().all(|r| r.source == EnrichmentSource::Uncovered));
+        assert!(responses.iter().all(|r| r.context.country.is_none() && r.confidence == 0.0));
+
+        let response = engine.enrich_point(EnrichRequest::at(43.7311, 7.4197)).await.unwrap();
+        assert_eq!(response.source, EnrichmentSource::Uncovered);
+
+        // Not cached, so the points are looked up properly once back online
+        let stats = engine.cache_stats();
+        assert_eq!((stats.memory_entries, stats.online_calls), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_points_past_the_call_budget_are_uncovered() {
+        let location = r#"{"country": "Monaco", "city": "Monaco"}"#;
+        let mock = Arc::new(MockGemini::new().with_text(location).with_text(location).with_text(location));
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        )
+        .with_call_budget(2);
+
+        let points = [(43.7384, 7.4246), (43.7311, 7.4197), (43.7450, 7.4300)];
+        let requests = points.iter().map(|&(lat, lon)| EnrichRequest::at(lat, lon)).collect();
+        let responses = engine.enrich_points(requests).await.unwrap();
+
+        assert_eq!(mock.call_count(), 2);
+        let count = |source| responses.iter().filter(|r| r.source == source).count();
+        assert_eq!((count(EnrichmentSource::Llm), count(EnrichmentSource::Uncovered)), (2, 1));
+
+        // The budget is per run; the next one may go online again
+        let uncovered = responses.iter().find(|r| r.source == EnrichmentSource::Uncovered).unwrap();
+        let requests = vec![EnrichRequest::at(uncovered.location.lat, uncovered.location.lon)];
+        let responses = engine.enrich_points(requests).await.unwrap();
+        assert_eq!(mock.call_count(), 3);
+        assert_eq!(responses[0].source, EnrichmentSource::Llm);
+    }
+
+    #[tokio::test]
+    async fn test_gemini_failure_still_returns_response() {
+        let mock = Arc::new(MockGemini::new().with_error(GeminiError::Network("network down".to_string())));
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock,
+        );
+
+        let response = engine.enrich_point(EnrichRequest::at(43.7384, 7.4246)).await.unwrap();
+        assert_eq!(response.location.lat, 43.7384);
+
+        // Nothing made up: no country, no city, no confidence
+        assert_eq!(response.source, EnrichmentSource::Partial);
+        assert_eq!(response.confidence, 0.0);
+        assert_eq!(response.context.country, None);
+        assert_eq!(response.context.city, None);
+        assert_eq!(response.context.timezone, None);
+        assert!(response.context.attribution.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unusable_llm_answer_is_partial() {
+        let prose = "Somewhere near the coast, probably France.";
+        let mock = Arc::new(MockGemini::new().with_text(prose).with_text(prose));
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        );
+
+        let response = engine.enrich_point(EnrichRequest::at(43.7384, 7.4246)).await.unwrap();
+        assert_eq!(response.source, EnrichmentSource::Partial);
+        assert_eq!(response.context.country, None);
+        // Asked again for JSON only before giving up
+        assert_eq!(mock.call_count(), 2);
+        assert_eq!(mock.schemas()[1], Some(location_schema()));
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_answer_is_asked_again_as_json() {
+        let mock = Arc::new(
+            MockGemini::new()
+                .with_text("It's Monaco, on the harbour road.")
+                .with_text(&format!(r#"{{"country": "Monaco", "city": "Monaco", "road": "{}"}}"#, "Quai ".repeat(20))),
+        );
+        let engine = EnrichmentEngine::with_backend(
+            Arc::new(GeoEngine::new()),
+            Arc::new(AppState::new()),
+            mock.clone(),
+        );
+
+        let response = engine.enrich_point(EnrichRequest::at(43.7384, 7.4246)).await.unwrap();
+        assert_eq!(mock.schemas(), [None, Some(location_schema())]);
+        assert_eq!(response.source, EnrichmentSource::Llm);
+        assert_eq!(response.context.country.as_deref(), Some("Monaco"));
+        // A hundred characters is no road name
+        assert_eq!(response.context.road, None);
+    }
+}
@@ -0,0 +1,477 @@
+//! Command Errors
+//!
+//! What a failed command returns to the frontend: a machine-readable
+//! [`ErrorCode`] to decide what to show, a message fit to show the user,
+//! and optional structured details. Each subsystem's error converts into
+//! it with the code that fits, so commands can use `?` on them directly;
+//! a plain `String` error becomes [`ErrorCode::Internal`].
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::commands::process::VideoSourceError;
+use crate::gemini::GeminiError;
+use crate::llm::ImageError;
+use crate::prompts::PromptError;
+use crate::services::data_manager::DataError;
+use crate::services::database::DatabaseError;
+use crate::services::extracts::ExtractError;
+use crate::services::ffmpeg::FfmpegError;
+use crate::services::gps::GpsError;
+use crate::services::mirrors::MirrorError;
+use crate::services::pbf::PbfError;
+use crate::services::sync::SyncError;
+use crate::services::truth_engine::TruthEngineError;
+use crate::services::whisper::WhisperError;
+
+/// What kind of failure a command ran into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Nothing more specific applies
+    #[default]
+    Internal,
+    /// An argument was missing, malformed or out of range
+    InvalidInput,
+    /// A project, video, region or other record doesn't exist
+    NotFound,
+    /// A file on disk isn't where it was expected
+    FileNotFound,
+    PermissionDenied,
+    /// A file was there but couldn't be read as what it should be
+    InvalidFile,
+    /// The database isn't open, or another instance holds it
+    DatabaseUnavailable,
+    DatabaseError,
+    /// FFmpeg, FFprobe or Whisper isn't installed
+    SidecarMissing,
+    /// A sidecar ran but failed
+    SidecarFailed,
+    /// No Whisper model of the wanted size is installed
+    ModelMissing,
+    ApiKeyMissing,
+    ApiKeyInvalid,
+    RateLimited,
+    /// A server couldn't be reached
+    Network,
+    Timeout,
+    /// The work needs the network and the app is in offline mode
+    Offline,
+    /// A service answered, but with an error or something unusable
+    ServiceFailed,
+    Cancelled,
+    /// No downloaded map region covers the place
+    CoverageMissing,
+    /// The GPS track and the video can't be lined up
+    SyncFailed,
+    /// Invoked while the app is still starting up
+    NotReady,
+}
+
+/// A failed command, as the frontend receives it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CommandError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl CommandError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), details: None }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Put what was being done in front of the message, keeping the code
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    pub fn invalid_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidInput, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn file_not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::FileNotFound, message)
+    }
+
+    pub fn invalid_file(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidFile, message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Cancelled, message)
+    }
+
+    /// For `map_err` on a database lookup of `what`, e.g. `Video v1`:
+    /// a missing record says what wasn't found, other errors are kept
+    pub fn lookup(what: impl fmt::Display) -> impl FnOnce(DatabaseError) -> Self {
+        move |e| match e {
+            DatabaseError::NotFound => Self::not_found(format!("{} not found", what)),
+            e => e.into(),
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<tauri::Error> for CommandError {
+    fn from(e: tauri::Error) -> Self {
+        Self::new(ErrorCode::Internal, e.to_string())
+    }
+}
+
+/// Engine errors carry their context; the message has all of it, and the
+/// code comes from the first cause a code is known for
+impl From<anyhow::Error> for CommandError {
+    fn from(e: anyhow::Error) -> Self {
+        let message = format!("{:#}", e);
+        for cause in e.chain() {
+            if let Some(gemini) = cause.downcast_ref::<GeminiError>() {
+                return Self { message, ..Self::from(gemini.clone()) };
+            }
+            if let Some(database) = cause.downcast_ref::<DatabaseError>() {
+                return Self::new(database_code(database), message);
+            }
+            if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+                return Self::new(io_code(io), message);
+            }
+        }
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<tokio::task::JoinError> for CommandError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        Self::new(ErrorCode::Internal, e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for CommandError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::new(ErrorCode::Internal, e.to_string())
+    }
+}
+
+impl From<std::io::Error> for CommandError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new(io_code(&e), e.to_string())
+    }
+}
+
+/// A server that answered with an error status failed; one that couldn't
+/// be reached, or took too long, is a network problem
+impl From<reqwest::Error> for CommandError {
+    fn from(e: reqwest::Error) -> Self {
+        let code = if e.is_timeout() {
+            ErrorCode::Timeout
+        } else if e.is_builder() {
+            ErrorCode::Internal
+        } else if e.is_status() {
+            ErrorCode::ServiceFailed
+        } else {
+            ErrorCode::Network
+        };
+        let error = Self::new(code, e.to_string());
+        match e.status() {
+            Some(status) => error.with_details(json!({ "status": status.as_u16() })),
+            None => error,
+        }
+    }
+}
+
+impl From<DatabaseError> for CommandError {
+    fn from(e: DatabaseError) -> Self {
+        Self::new(database_code(&e), e.to_string())
+    }
+}
+
+impl From<GeminiError> for CommandError {
+    fn from(e: GeminiError) -> Self {
+        let (code, details) = match &e {
+            GeminiError::MissingKey => (ErrorCode::ApiKeyMissing, None),
+            GeminiError::InvalidKey => (ErrorCode::ApiKeyInvalid, None),
+            GeminiError::RateLimited { retry_after } => (
+                ErrorCode::RateLimited,
+                retry_after.map(|after| json!({ "retry_after_secs": after.as_secs() })),
+            ),
+            GeminiError::Network(_) | GeminiError::LocalUnavailable(_) => (ErrorCode::Network, None),
+            GeminiError::Timeout | GeminiError::LocalTimeout(_) => (ErrorCode::Timeout, None),
+            GeminiError::OfflineMode => (ErrorCode::Offline, None),
+            GeminiError::SafetyBlocked { categories } => {
+                (ErrorCode::ServiceFailed, Some(json!({ "safety_categories": categories })))
+            }
+            GeminiError::Api { status, .. } | GeminiError::Local { status, .. } => {
+                (ErrorCode::ServiceFailed, Some(json!({ "status": status })))
+            }
+            GeminiError::Overloaded | GeminiError::MalformedResponse(_) => (ErrorCode::ServiceFailed, None),
+        };
+        Self { code, message: e.to_string(), details }
+    }
+}
+
+impl From<FfmpegError> for CommandError {
+    fn from(e: FfmpegError) -> Self {
+        match e {
+            FfmpegError::BinaryNotFound(ref path) => {
+                Self::new(ErrorCode::SidecarMissing, e.to_string()).with_details(json!({ "path": path }))
+            }
+            FfmpegError::IoError(e) => e.into(),
+            FfmpegError::ExecutionFailed(_) | FfmpegError::ParseError(_) => {
+                Self::new(ErrorCode::SidecarFailed, e.to_string())
+            }
+            FfmpegError::InvalidChapters(_) => Self::invalid_input(e.to_string()),
+        }
+    }
+}
+
+impl From<WhisperError> for CommandError {
+    fn from(e: WhisperError) -> Self {
+        match e {
+            WhisperError::BinaryNotFound(ref path) => {
+                Self::new(ErrorCode::SidecarMissing, e.to_string()).with_details(json!({ "path": path }))
+            }
+            WhisperError::ModelNotFound(ref path) => {
+                Self::new(ErrorCode::ModelMissing, e.to_string()).with_details(json!({ "path": path }))
+            }
+            WhisperError::IoError(e) => e.into(),
+            WhisperError::ExecutionFailed(_) | WhisperError::ParseError(_) => {
+                Self::new(ErrorCode::SidecarFailed, e.to_string())
+            }
+            WhisperError::UnknownModel(..) => Self::invalid_input(e.to_string()),
+        }
+    }
+}
+
+impl From<GpsError> for CommandError {
+    fn from(e: GpsError) -> Self {
+        match e {
+            GpsError::IoError(e) => e.into(),
+            _ => Self::new(ErrorCode::InvalidFile, e.to_string()),
+        }
+    }
+}
+
+impl From<SyncError> for CommandError {
+    fn from(e: SyncError) -> Self {
+        Self::new(ErrorCode::SyncFailed, e.to_string())
+    }
+}
+
+impl From<TruthEngineError> for CommandError {
+    fn from(e: TruthEngineError) -> Self {
+        match e {
+            TruthEngineError::TilesNotFound(_) => Self::new(ErrorCode::CoverageMissing, e.to_string()),
+            TruthEngineError::InvalidCoordinates { .. } => Self::invalid_input(e.to_string()),
+            TruthEngineError::IoError(e) => e.into(),
+            TruthEngineError::VerificationFailed(_) => Self::new(ErrorCode::Internal, e.to_string()),
+        }
+    }
+}
+
+impl From<DataError> for CommandError {
+    fn from(e: DataError) -> Self {
+        match e {
+            DataError::RegionNotAvailable(_) => Self::new(ErrorCode::CoverageMissing, e.to_string()),
+            DataError::DownloadFailed(_) => Self::new(ErrorCode::Network, e.to_string()),
+            DataError::IoError(e) => e.into(),
+            DataError::CacheError(_) => Self::new(ErrorCode::Internal, e.to_string()),
+        }
+    }
+}
+
+impl From<ExtractError> for CommandError {
+    fn from(e: ExtractError) -> Self {
+        let code = match e {
+            ExtractError::InvalidBounds(_) | ExtractError::AreaTooLarge { .. } => ErrorCode::InvalidInput,
+            ExtractError::RateLimited => ErrorCode::RateLimited,
+            ExtractError::RequestFailed(_) => ErrorCode::ServiceFailed,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<PbfError> for CommandError {
+    fn from(e: PbfError) -> Self {
+        match e {
+            PbfError::IoError(e) => e.into(),
+            PbfError::InvalidFormat(_) | PbfError::UnsupportedFeatures(_) => {
+                Self::new(ErrorCode::InvalidFile, e.to_string())
+            }
+        }
+    }
+}
+
+impl From<PromptError> for CommandError {
+    fn from(e: PromptError) -> Self {
+        let code = match e {
+            PromptError::MissingPlaceholder { .. } | PromptError::UnknownPlaceholder { .. } => ErrorCode::InvalidInput,
+            PromptError::Io { .. } => ErrorCode::Internal,
+        };
+        Self::new(code, e.to_string())
+    }
+}
+
+impl From<MirrorError> for CommandError {
+    fn from(e: MirrorError) -> Self {
+        match e {
+            MirrorError::InvalidTemplate(..) => Self::invalid_input(e.to_string()),
+            MirrorError::UnknownRegion(_) => Self::not_found(e.to_string()),
+        }
+    }
+}
+
+impl From<ImageError> for CommandError {
+    fn from(e: ImageError) -> Self {
+        Self::invalid_input(e.to_string())
+    }
+}
+
+impl From<VideoSourceError> for CommandError {
+    fn from(e: VideoSourceError) -> Self {
+        match e {
+            VideoSourceError::NotFound(_) => Self::not_found(e.to_string()),
+            VideoSourceError::FileMissing { ref video_id, ref path } => {
+                let details = json!({ "video_id": video_id, "path": path });
+                Self::new(ErrorCode::FileNotFound, e.to_string()).with_details(details)
+            }
+            VideoSourceError::InvalidId(_) => Self::invalid_input(e.to_string()),
+            VideoSourceError::Database(e) => e.into(),
+        }
+    }
+}
+
+fn io_code(e: &std::io::Error) -> ErrorCode {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => ErrorCode::FileNotFound,
+        std::io::ErrorKind::PermissionDenied => ErrorCode::PermissionDenied,
+        _ => ErrorCode::Internal,
+    }
+}
+
+fn database_code(e: &DatabaseError) -> ErrorCode {
+    match e {
+        DatabaseError::NotInitialized | DatabaseError::Locked => ErrorCode::DatabaseUnavailable,
+        DatabaseError::NotFound => ErrorCode::NotFound,
+        DatabaseError::DuckDb(_) | DatabaseError::Serialization(_) => ErrorCode::DatabaseError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_serializes_code_message_and_details() {
+        let error = CommandError::from(GeminiError::RateLimited { retry_after: Some(Duration::from_secs(30)) });
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({
+                "code": "rate_limited",
+                "message": "Gemini's rate limit was reached. Wait a minute and try again.",
+                "details": { "retry_after_secs": 30 },
+            })
+        );
+
+        // No details, no field
+        let error = CommandError::from("Something broke".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            json!({ "code": "internal", "message": "Something broke" })
+        );
+    }
+
+    #[test]
+    fn test_subsystem_errors_get_their_codes() {
+        assert_eq!(CommandError::from(DatabaseError::Locked).code, ErrorCode::DatabaseUnavailable);
+        assert_eq!(CommandError::from(DatabaseError::NotFound).code, ErrorCode::NotFound);
+        assert_eq!(CommandError::from(GeminiError::InvalidKey).code, ErrorCode::ApiKeyInvalid);
+        assert_eq!(CommandError::from(GeminiError::OfflineMode).code, ErrorCode::Offline);
+        assert_eq!(
+            CommandError::from(FfmpegError::BinaryNotFound("/opt/ffmpeg".into())).code,
+            ErrorCode::SidecarMissing
+        );
+        assert_eq!(
+            CommandError::from(WhisperError::ModelNotFound("/models/base.bin".into())).code,
+            ErrorCode::ModelMissing
+        );
+        assert_eq!(
+            CommandError::from(DataError::RegionNotAvailable("europe/monaco".into())).code,
+            ErrorCode::CoverageMissing
+        );
+        assert_eq!(
+            CommandError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "gone")).code,
+            ErrorCode::FileNotFound
+        );
+
+        let error = CommandError::from(VideoSourceError::FileMissing {
+            video_id: "v1".to_string(),
+            path: "/videos/trip.mp4".to_string(),
+        });
+        assert_eq!(error.code, ErrorCode::FileNotFound);
+        assert_eq!(error.details, Some(json!({ "video_id": "v1", "path": "/videos/trip.mp4" })));
+    }
+
+    #[test]
+    fn test_engine_errors_keep_context_and_code() {
+        let error = CommandError::from(anyhow::Error::new(GeminiError::InvalidKey).context("Failed to narrate video v1"));
+        assert_eq!(error.code, ErrorCode::ApiKeyInvalid);
+        assert_eq!(error.message, format!("Failed to narrate video v1: {}", GeminiError::InvalidKey));
+
+        let error = CommandError::from(anyhow::anyhow!("No events to narrate"));
+        assert_eq!(error.code, ErrorCode::Internal);
+        assert_eq!(error.message, "No events to narrate");
+    }
+
+    #[test]
+    fn test_lookup_says_what_was_missing() {
+        let error = CommandError::lookup("Video v1")(DatabaseError::NotFound);
+        assert_eq!(error, CommandError::not_found("Video v1 not found"));
+        assert_eq!(CommandError::lookup("Video v1")(DatabaseError::Locked).code, ErrorCode::DatabaseUnavailable);
+
+        let error = CommandError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "gone")).context("Failed to write a.srt");
+        assert_eq!(error.code, ErrorCode::FileNotFound);
+        assert_eq!(error.message, "Failed to write a.srt: gone");
+    }
+
+    #[test]
+    fn test_message_is_the_user_facing_text() {
+        let error = CommandError::from(GeminiError::MissingKey);
+        assert_eq!(error.to_string(), GeminiError::MissingKey.to_string());
+        assert_eq!(
+            serde_json::to_value(CommandError::cancelled("Processing was cancelled")).unwrap()["code"],
+            "cancelled"
+        );
+    }
+}
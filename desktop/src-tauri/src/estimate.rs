@@ -0,0 +1,305 @@
+//! Processing Estimates
+//!
+//! How long processing a video should take and what it needs, worked out
+//! before it starts, so a two-hour run isn't begun on a laptop battery by
+//! surprise. Transcription usually takes longest and depends on the machine
+//! more than anything, so Whisper's speed is timed on a 30-second sample of
+//! the video the first time a model is estimated for, and kept in the
+//! settings. The other stages go by rough rates for a typical laptop.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, warn};
+
+use crate::processor::ProcessOptions;
+use crate::services::temp_files::JobDir;
+use crate::services::whisper::{self, WhisperModel};
+use crate::services::{Ffmpeg, Whisper};
+use crate::settings;
+
+/// Audio transcribed to time Whisper
+const SAMPLE_SECONDS: f64 = 30.0;
+/// Shortest sample worth timing; Whisper loading its model would swamp less
+const MIN_SAMPLE_SECONDS: f64 = 5.0;
+
+/// Reading the container with FFprobe, whatever the video's length
+const METADATA_SECONDS: f64 = 1.0;
+/// Seconds per second of video to decode its audio
+const AUDIO_EXTRACT_RATE: f64 = 0.02;
+/// Seconds per second of 1080p video to look for scene changes; FFmpeg
+/// decodes every frame, so it scales with the picture size
+const SCENE_RATE_1080P: f64 = 0.1;
+const PIXELS_1080P: f64 = 1920.0 * 1080.0;
+/// Seconds per GPS point to read the track and check it for landmarks
+const GPS_RATE: f64 = 0.0001;
+const VERIFICATION_RATE: f64 = 0.002;
+/// Seconds per online request, with several in flight at once
+const ONLINE_CALL_SECONDS: f64 = 0.5;
+/// 16 kHz, 16-bit mono, as audio is extracted for Whisper
+const WAV_BYTES_PER_SECOND: f64 = 32_000.0;
+
+/// How much of the work one stage should take
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StageEstimate {
+    /// As in `processing-progress`, e.g. `transcribe`
+    pub stage: &'static str,
+    pub seconds: f64,
+}
+
+/// What processing a video should take
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProcessingEstimate {
+    /// The stages that would run, in order
+    pub stages: Vec<StageEstimate>,
+    /// Stages whose output an earlier run kept, made from the same inputs
+    /// going by the same options, so they won't run again
+    pub reused: Vec<&'static str>,
+    pub total_seconds: f64,
+    /// Most disk the run's temp files take at once
+    pub peak_temp_bytes: u64,
+    /// Requests to online services: places along the route the map data
+    /// doesn't name, and the weather. Article summaries depend on the
+    /// landmarks found, so aren't counted.
+    pub online_calls: usize,
+    /// Share of the route in downloaded regions, from 0 to 1; `None` without GPS
+    pub region_coverage: Option<f64>,
+    /// `None` without transcription
+    pub whisper: Option<WhisperSpeed>,
+}
+
+/// How fast Whisper transcribes with a model
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WhisperSpeed {
+    pub model: WhisperModel,
+    /// Seconds per second of audio
+    pub rate: f64,
+    /// Whether it was timed on this machine, rather than a typical laptop's
+    pub calibrated: bool,
+}
+
+/// What's known of a video and its route, for [`estimate`]
+#[derive(Debug, Clone, Default)]
+pub struct EstimateInput {
+    pub duration_seconds: f64,
+    /// Width times height, when known
+    pub pixels: Option<u64>,
+    pub has_audio: bool,
+    pub gps_points: usize,
+    /// Points the route's summary looks up, and those in downloaded regions
+    pub route_samples: usize,
+    pub covered_samples: usize,
+    /// Places one run may ask a model over the internet about
+    pub place_lookup_budget: usize,
+    /// Distinct hours and places along the route, when the weather can be
+    /// looked up; 0 offline
+    pub weather_lookups: usize,
+    /// Stages an earlier run kept the output of that the run would use
+    /// (see `processor::reusable_stages`)
+    pub reused: Vec<&'static str>,
+}
+
+/// Estimate processing a video with `options`
+///
+/// `whisper` is needed when the video's audio is to be transcribed; without
+/// it transcription is left out.
+pub fn estimate(options: &ProcessOptions, input: &EstimateInput, whisper: Option<WhisperSpeed>) -> ProcessingEstimate {
+    let duration = input.duration_seconds.max(0.0);
+    let reused = |stage: &'static str| input.reused.contains(&stage);
+    let whisper = whisper.filter(|_| options.transcription && input.has_audio && !reused("transcribe"));
+    // Scene changes make events, which are kept with the verified ones
+    let verifying = !reused("verification");
+    let mut stages = vec![StageEstimate { stage: "metadata", seconds: METADATA_SECONDS }];
+    let mut peak_temp_bytes = 0;
+
+    if let Some(speed) = whisper {
+        stages.push(StageEstimate { stage: "audio_extract", seconds: duration * AUDIO_EXTRACT_RATE });
+        stages.push(StageEstimate { stage: "transcribe", seconds: duration * speed.rate });
+        // Kept audio goes with the app's data rather than the job's temp files
+        if !options.keep_audio {
+            peak_temp_bytes = (duration * WAV_BYTES_PER_SECOND) as u64;
+        }
+    }
+    if input.gps_points > 0 {
+        stages.push(StageEstimate { stage: "gps", seconds: input.gps_points as f64 * GPS_RATE });
+    }
+    if options.scene_moments && verifying {
+        let scale = input.pixels.map_or(1.0, |pixels| pixels as f64 / PIXELS_1080P);
+        stages.push(StageEstimate { stage: "scene_changes", seconds: duration * SCENE_RATE_1080P * scale });
+    }
+    if options.truth_verification && input.gps_points > 0 && verifying {
+        stages.push(StageEstimate { stage: "verification", seconds: input.gps_points as f64 * VERIFICATION_RATE });
+    }
+
+    let uncovered = input.route_samples.saturating_sub(input.covered_samples);
+    let place_lookups = uncovered.min(input.place_lookup_budget);
+    let weather_lookups = if options.weather { input.weather_lookups } else { 0 };
+    let online_calls = place_lookups + weather_lookups;
+    if online_calls > 0 {
+        stages.push(StageEstimate { stage: "enrichment", seconds: online_calls as f64 * ONLINE_CALL_SECONDS });
+    }
+
+    let region_coverage =
+        (input.route_samples > 0).then(|| input.covered_samples.min(input.route_samples) as f64 / input.route_samples as f64);
+    ProcessingEstimate {
+        total_seconds: stages.iter().map(|s| s.seconds).sum(),
+        stages,
+        reused: input.reused.clone(),
+        peak_temp_bytes,
+        online_calls,
+        region_coverage,
+        whisper,
+    }
+}
+
+/// How fast Whisper transcribes with the model `options` would use
+///
+/// Taken from the settings if it was timed before; otherwise it's timed on a
+/// sample from the middle of the video, under `temp_root`, and kept. A typical
+/// laptop's speed stands in when it can't be timed.
+pub async fn whisper_speed(
+    ffmpeg: &Ffmpeg,
+    whisper: &Whisper,
+    video_path: &Path,
+    duration_seconds: f64,
+    options: &ProcessOptions,
+    temp_root: &Path,
+) -> WhisperSpeed {
+    let model = options.whisper_model.unwrap_or_else(|| whisper.auto_model());
+    if let Some(&rate) = settings::get().whisper_speed.get(model.as_str()) {
+        return WhisperSpeed { model, rate, calibrated: true };
+    }
+    let typical = WhisperSpeed { model, rate: typical_rate(model), calibrated: false };
+    if !whisper.has_model(model) || duration_seconds < MIN_SAMPLE_SECONDS {
+        return typical;
+    }
+
+    match time_sample(ffmpeg, whisper, video_path, duration_seconds, options, model, temp_root).await {
+        Ok(rate) => {
+            info!("Whisper {} model transcribes at {:.2} seconds per second of audio", model, rate);
+            settings::update(|s| {
+                s.whisper_speed.insert(model.as_str().to_string(), rate);
+            });
+            WhisperSpeed { model, rate, calibrated: true }
+        }
+        Err(e) => {
+            warn!("Failed to time the Whisper {} model: {:#}", model, e);
+            typical
+        }
+    }
+}
+
+async fn time_sample(
+    ffmpeg: &Ffmpeg,
+    whisper: &Whisper,
+    video_path: &Path,
+    duration_seconds: f64,
+    options: &ProcessOptions,
+    model: WhisperModel,
+    temp_root: &Path,
+) -> Result<f64> {
+    let job_dir = JobDir::create(temp_root).context("Failed to create a temp directory")?;
+    let sample = SAMPLE_SECONDS.min(duration_seconds);
+    // Past the quiet of the first seconds, where there's less to transcribe
+    let start = (duration_seconds - sample) / 2.0;
+    let audio_path = job_dir.path().join("sample.wav");
+    ffmpeg
+        .extract_audio_clip(&PathBuf::from(video_path), &audio_path, options.audio_stream_index, start, sample)
+        .await
+        .context("Failed to extract a sample of the audio")?;
+
+    let language = options.language.as_deref().unwrap_or(whisper::AUTO_LANGUAGE);
+    let started = Instant::now();
+    whisper.transcribe(&audio_path, model, Some(language)).await.context("Failed to transcribe the sample")?;
+    Ok(started.elapsed().as_secs_f64() / sample)
+}
+
+/// Seconds per second of audio on a typical laptop's CPU
+fn typical_rate(model: WhisperModel) -> f64 {
+    match model {
+        WhisperModel::Tiny | WhisperModel::TinyEn => 0.05,
+        WhisperModel::Base | WhisperModel::BaseEn => 0.1,
+        WhisperModel::Small | WhisperModel::SmallEn => 0.3,
+        WhisperModel::Medium | WhisperModel::MediumEn => 0.8,
+        WhisperModel::Large => 1.6,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input() -> EstimateInput {
+        EstimateInput {
+            duration_seconds: 3600.0,
+            pixels: Some(1920 * 1080),
+            has_audio: true,
+            gps_points: 3600,
+            route_samples: 100,
+            covered_samples: 80,
+            place_lookup_budget: 50,
+            weather_lookups: 2,
+            reused: Vec::new(),
+        }
+    }
+
+    fn stage(estimate: &ProcessingEstimate, name: &str) -> Option<f64> {
+        estimate.stages.iter().find(|s| s.stage == name).map(|s| s.seconds)
+    }
+
+    #[test]
+    fn test_an_hour_of_video_is_estimated_stage_by_stage() {
+        let small = WhisperSpeed { model: WhisperModel::Small, rate: 0.5, calibrated: true };
+        let options = ProcessOptions { weather: true, scene_moments: true, ..Default::default() };
+        let estimate = estimate(&options, &input(), Some(small));
+
+        assert_eq!(stage(&estimate, "transcribe"), Some(1800.0));
+        assert_eq!(stage(&estimate, "scene_changes"), Some(360.0));
+        let stages: f64 = estimate.stages.iter().map(|s| s.seconds).sum();
+        assert_eq!(estimate.total_seconds, stages);
+        assert!(estimate.total_seconds > 2160.0);
+        // The whole soundtrack as 16 kHz mono
+        assert_eq!(estimate.peak_temp_bytes, 115_200_000);
+        // Uncovered places along the route, and the weather
+        assert_eq!(estimate.online_calls, 22);
+        assert_eq!(estimate.region_coverage, Some(0.8));
+        assert_eq!(estimate.whisper, Some(small));
+    }
+
+    #[test]
+    fn test_skipped_stages_cost_nothing() {
+        let speed = WhisperSpeed { model: WhisperModel::Base, rate: typical_rate(WhisperModel::Base), calibrated: false };
+        let silent = EstimateInput { has_audio: false, gps_points: 0, route_samples: 0, covered_samples: 0, ..input() };
+        let estimate = estimate(&ProcessOptions::default(), &silent, Some(speed));
+
+        assert_eq!(stage(&estimate, "transcribe"), None);
+        assert_eq!(stage(&estimate, "verification"), None);
+        assert_eq!((estimate.peak_temp_bytes, estimate.online_calls), (0, 0));
+        assert_eq!((estimate.region_coverage, estimate.whisper), (None, None));
+
+        // Kept audio isn't temporary, and no more places are asked about than a run may
+        let kept = ProcessOptions { keep_audio: true, ..Default::default() };
+        let uncovered = EstimateInput { covered_samples: 0, ..input() };
+        let estimate = super::estimate(&kept, &uncovered, Some(speed));
+        assert_eq!(stage(&estimate, "transcribe"), Some(360.0));
+        assert_eq!((estimate.peak_temp_bytes, estimate.online_calls), (0, 50));
+    }
+
+    #[test]
+    fn test_reused_stages_are_left_out() {
+        let speed = WhisperSpeed { model: WhisperModel::Base, rate: 0.1, calibrated: true };
+        // As after a corrected GPS track
+        let new_gps = EstimateInput { reused: vec!["transcribe"], ..input() };
+        let estimate = estimate(&ProcessOptions::default(), &new_gps, Some(speed));
+        assert_eq!(stage(&estimate, "transcribe"), None);
+        assert_eq!(stage(&estimate, "audio_extract"), None);
+        assert!(stage(&estimate, "verification").is_some());
+        assert_eq!((estimate.reused.as_slice(), estimate.whisper, estimate.peak_temp_bytes), (["transcribe"].as_slice(), None, 0));
+
+        let unchanged = EstimateInput { reused: vec!["transcribe", "verification"], ..input() };
+        let estimate = super::estimate(&ProcessOptions::default(), &unchanged, Some(speed));
+        assert_eq!(stage(&estimate, "scene_changes"), None);
+        assert_eq!(stage(&estimate, "verification"), None);
+    }
+}
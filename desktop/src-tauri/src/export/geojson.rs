@@ -0,0 +1,211 @@
+//! GeoJSON Export
+//!
+//! A video's GPS track and events as a GeoJSON FeatureCollection (RFC
+//! 7946) for GIS tools and web maps. Positions are longitude first, as the
+//! format wants, and rounded to 6 decimals, about 10 cm.
+//!
+//! The track is a LineString with the time of each fix in its properties'
+//! `coordinateProperties.times`, the convention togeojson and the tools
+//! built on it read. Each event with a location is a Point.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::enrich::stored_truth;
+use crate::services::database::Event;
+use crate::services::gps::GpsBounds;
+use crate::services::GpsTrack;
+
+/// Most POI names listed with an event
+const POI_NAMES: usize = 3;
+
+/// What to put in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeoJsonContent {
+    Track,
+    Events,
+    Both,
+}
+
+impl GeoJsonContent {
+    fn track(self) -> bool {
+        matches!(self, Self::Track | Self::Both)
+    }
+
+    fn events(self) -> bool {
+        matches!(self, Self::Events | Self::Both)
+    }
+}
+
+/// The FeatureCollection of `track` and `events`, as `content` asks
+///
+/// `bbox` is the track's bounds, grown to take in any event off it, and
+/// left out when there's nothing to bound.
+pub fn feature_collection(name: &str, track: Option<&GpsTrack>, events: &[Event], content: GeoJsonContent) -> Value {
+    let mut features = Vec::new();
+    let mut bounds: Option<GpsBounds> = None;
+
+    if let Some(track) = track.filter(|t| content.track() && t.points.len() >= 2) {
+        features.push(track_feature(name, track));
+        bounds = track.bounds.clone();
+    }
+    if content.events() {
+        for event in events {
+            let Some((lat, lon)) = event.lat.zip(event.lon) else {
+                continue;
+            };
+            features.push(event_feature(event, lat, lon));
+            bounds = Some(extend(bounds, lat, lon));
+        }
+    }
+
+    let mut collection = Map::new();
+    collection.insert("type".to_string(), json!("FeatureCollection"));
+    if let Some(b) = bounds {
+        collection.insert(
+            "bbox".to_string(),
+            json!([round(b.min_lon), round(b.min_lat), round(b.max_lon), round(b.max_lat)]),
+        );
+    }
+    collection.insert("features".to_string(), Value::Array(features));
+    Value::Object(collection)
+}
+
+fn track_feature(name: &str, track: &GpsTrack) -> Value {
+    // A third coordinate only if every fix has one, so positions stay alike
+    let with_elevation = track.points.iter().all(|p| p.elevation_m.is_some());
+    let coordinates: Vec<Value> = track
+        .points
+        .iter()
+        .map(|p| match p.elevation_m.filter(|_| with_elevation) {
+            Some(elevation) => json!([round(p.lon), round(p.lat), round(elevation)]),
+            None => json!([round(p.lon), round(p.lat)]),
+        })
+        .collect();
+    let times: Vec<String> = track.points.iter().map(|p| p.timestamp.to_rfc3339()).collect();
+
+    json!({
+        "type": "Feature",
+        "geometry": { "type": "LineString", "coordinates": coordinates },
+        "properties": {
+            "name": name,
+            "kind": "track",
+            "start_time": track.start_time.map(|t| t.to_rfc3339()),
+            "end_time": track.end_time.map(|t| t.to_rfc3339()),
+            "coordinateProperties": { "times": times },
+        },
+    })
+}
+
+fn event_feature(event: &Event, lat: f64, lon: f64) -> Value {
+    // Only a stored Truth Event has the time it happened, rather than when
+    // it was processed
+    let truth = event.truth_bundle_json.as_ref().and_then(|_| stored_truth(event));
+    let pois: Vec<&str> = truth
+        .iter()
+        .flat_map(|t| t.pois.iter().take(POI_NAMES))
+        .map(|poi| poi.name.as_str())
+        .collect();
+
+    json!({
+        "type": "Feature",
+        "id": event.id,
+        "geometry": { "type": "Point", "coordinates": [round(lon), round(lat)] },
+        "properties": {
+            "kind": "event",
+            "event_type": event.event_type,
+            "timestamp": truth.as_ref().map(|t| t.timestamp.to_rfc3339()),
+            "time_uncertain": truth.as_ref().is_some_and(|t| t.time_uncertain),
+            "video_seconds": round(event.start_time_seconds),
+            "verified": event.verified,
+            "pois": pois,
+        },
+    })
+}
+
+fn extend(bounds: Option<GpsBounds>, lat: f64, lon: f64) -> GpsBounds {
+    match bounds {
+        Some(b) => GpsBounds {
+            min_lat: b.min_lat.min(lat),
+            max_lat: b.max_lat.max(lat),
+            min_lon: b.min_lon.min(lon),
+            max_lon: b.max_lon.max(lon),
+        },
+        None => GpsBounds { min_lat: lat, max_lat: lat, min_lon: lon, max_lon: lon },
+    }
+}
+
+/// To 6 decimals, about 10 cm in a coordinate
+fn round(value: f64) -> f64 {
+    (value * 1e6).round() / 1e6
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, poi};
+    use crate::services::gps::GpsPoint;
+
+    fn track() -> GpsTrack {
+        let points = [(46.5190123456, 6.6323456789, Some(372.25)), (46.52, 6.64, Some(380.0)), (46.53, 6.65, Some(395.5))]
+            .iter()
+            .enumerate()
+            .map(|(i, &(lat, lon, elevation_m))| GpsPoint { elevation_m, ..fixtures::gps_point(60 * i as i64, lat, lon) })
+            .collect();
+        fixtures::track(points)
+    }
+
+    #[test]
+    fn test_export_parses_back_as_geojson() {
+        let truth = fixtures::truth(65, ["Castle", "Lake", "Bridge", "Tower"].map(poi).to_vec());
+        // Off the track, to the west
+        let events = [
+            Event { lon: Some(6.61), ..fixtures::event(65.5, Some(&truth)) },
+            Event { lat: None, lon: None, ..fixtures::event(65.5, None) },
+        ];
+        let text = serde_json::to_string(&feature_collection("GH010123.MP4", Some(&track()), &events, GeoJsonContent::Both)).unwrap();
+
+        let parsed: geojson::GeoJson = text.parse().unwrap();
+        let geojson::GeoJson::FeatureCollection(collection) = parsed else {
+            panic!("not a FeatureCollection: {}", text);
+        };
+        assert_eq!(collection.bbox, Some(vec![6.61, 46.519012, 6.65, 46.53]));
+        assert_eq!(collection.features.len(), 2);
+
+        let line = &collection.features[0];
+        let Some(geojson::Value::LineString(coordinates)) = line.geometry.as_ref().map(|g| g.value.clone()) else {
+            panic!("track isn't a LineString");
+        };
+        // Longitude first, 6 decimals
+        assert_eq!(coordinates[0], vec![6.632346, 46.519012, 372.25]);
+        let times = &line.properties.as_ref().unwrap()["coordinateProperties"]["times"];
+        assert_eq!(times.as_array().unwrap().len(), coordinates.len());
+        assert_eq!(times[1], "2026-06-12T09:01:00+00:00");
+
+        let point = &collection.features[1];
+        let properties = point.properties.as_ref().unwrap();
+        assert_eq!(properties["event_type"], "nearest_pass");
+        assert_eq!(properties["verified"], true);
+        assert_eq!(properties["timestamp"], "2026-06-12T09:01:05+00:00");
+        assert_eq!(properties["pois"], json!(["Castle", "Lake", "Bridge"]));
+    }
+
+    #[test]
+    fn test_export_has_only_what_was_asked_for() {
+        let events = [fixtures::event(65.5, None)];
+
+        let track_only = feature_collection("clip.mp4", Some(&track()), &events, GeoJsonContent::Track);
+        assert_eq!(track_only["features"].as_array().unwrap().len(), 1);
+        assert_eq!(track_only["features"][0]["geometry"]["type"], "LineString");
+
+        let events_only = feature_collection("clip.mp4", Some(&track()), &events, GeoJsonContent::Events);
+        assert_eq!(events_only["features"].as_array().unwrap().len(), 1);
+        assert_eq!(events_only["features"][0]["properties"]["timestamp"], Value::Null);
+        assert_eq!(events_only["bbox"], json!([6.645, 46.525, 6.645, 46.525]));
+
+        let empty = feature_collection("clip.mp4", None, &[], GeoJsonContent::Both);
+        assert!(empty.get("bbox").is_none());
+        assert_eq!(empty["features"], json!([]));
+    }
+}
@@ -0,0 +1,187 @@
+//! GPX Export
+//!
+//! A video's stored track, as cleaned up on import, written back out as
+//! GPX 1.1 for Strava, Garmin Connect or an archive. Speed and heading go
+//! in Garmin's TrackPointExtension, which those services read; verified
+//! events can be added as waypoints named after their top POI.
+
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Utc};
+
+use crate::enrich::stored_truth;
+use crate::services::database::Event;
+use crate::services::GpsTrack;
+use super::escape_xml;
+
+const GPX_NS: &str = "http://www.topografix.com/GPX/1/1";
+const TPX_NS: &str = "http://www.garmin.com/xmlschemas/TrackPointExtension/v2";
+
+/// The GPX document for `track`, with waypoints for the verified events
+/// among `events`
+pub fn document(name: &str, track: &GpsTrack, events: &[Event], created: DateTime<Utc>) -> String {
+    let mut gpx = String::new();
+    gpx.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        gpx,
+        "<gpx version=\"1.1\" creator=\"GeoTruth {}\" xmlns=\"{}\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\" \
+         xmlns:gpxtpx=\"{}\" xsi:schemaLocation=\"{} http://www.topografix.com/GPX/1/1/gpx.xsd {} \
+         https://www8.garmin.com/xmlschemas/TrackPointExtensionv2.xsd\">",
+        env!("CARGO_PKG_VERSION"),
+        GPX_NS,
+        TPX_NS,
+        GPX_NS,
+        TPX_NS
+    );
+
+    // The schema fixes the order: metadata, waypoints, routes, tracks
+    gpx.push_str("  <metadata>\n");
+    let _ = writeln!(gpx, "    <name>{}</name>", escape_xml(name));
+    let _ = writeln!(gpx, "    <time>{}</time>", gpx_time(created));
+    if let Some(b) = &track.bounds {
+        let _ = writeln!(
+            gpx,
+            "    <bounds minlat=\"{:.7}\" minlon=\"{:.7}\" maxlat=\"{:.7}\" maxlon=\"{:.7}\"/>",
+            b.min_lat, b.min_lon, b.max_lat, b.max_lon
+        );
+    }
+    gpx.push_str("  </metadata>\n");
+
+    for event in events.iter().filter(|e| e.verified) {
+        push_waypoint(&mut gpx, event);
+    }
+
+    gpx.push_str("  <trk>\n");
+    let _ = writeln!(gpx, "    <name>{}</name>", escape_xml(name));
+    gpx.push_str("    <trkseg>\n");
+    for point in &track.points {
+        let _ = writeln!(gpx, "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">", point.lat, point.lon);
+        if let Some(elevation) = point.elevation_m {
+            let _ = writeln!(gpx, "        <ele>{:.1}</ele>", elevation);
+        }
+        let _ = writeln!(gpx, "        <time>{}</time>", gpx_time(point.timestamp));
+        if point.speed_kmh.is_some() || point.heading_deg.is_some() {
+            gpx.push_str("        <extensions><gpxtpx:TrackPointExtension>");
+            // Speed before course, as the extension's schema orders them
+            if let Some(speed) = point.speed_kmh {
+                let _ = write!(gpx, "<gpxtpx:speed>{:.2}</gpxtpx:speed>", speed.max(0.0) / 3.6);
+            }
+            if let Some(heading) = point.heading_deg {
+                let _ = write!(gpx, "<gpxtpx:course>{:.1}</gpxtpx:course>", heading.rem_euclid(360.0));
+            }
+            gpx.push_str("</gpxtpx:TrackPointExtension></extensions>\n");
+        }
+        gpx.push_str("      </trkpt>\n");
+    }
+    gpx.push_str("    </trkseg>\n  </trk>\n</gpx>\n");
+    gpx
+}
+
+fn push_waypoint(gpx: &mut String, event: &Event) {
+    let (Some(lat), Some(lon)) = (event.lat, event.lon) else {
+        return;
+    };
+    // Only a stored Truth Event has the POIs and when it happened
+    let truth = event.truth_bundle_json.as_ref().and_then(|_| stored_truth(event));
+    let poi = truth.as_ref().and_then(|t| t.pois.first());
+
+    let _ = writeln!(gpx, "  <wpt lat=\"{:.7}\" lon=\"{:.7}\">", lat, lon);
+    if let Some(truth) = truth.as_ref().filter(|t| !t.time_uncertain) {
+        let _ = writeln!(gpx, "    <time>{}</time>", gpx_time(truth.timestamp));
+    }
+    let name = poi.map(|poi| poi.name.clone()).unwrap_or_else(|| event.event_type.replace('_', " "));
+    let _ = writeln!(gpx, "    <name>{}</name>", escape_xml(&name));
+    if let Some(poi) = poi {
+        let _ = writeln!(gpx, "    <desc>{}, {:.0} m from the route</desc>", escape_xml(&poi.category), poi.distance_m);
+    }
+    let _ = writeln!(gpx, "    <type>{}</type>", escape_xml(&event.event_type));
+    gpx.push_str("  </wpt>\n");
+}
+
+/// UTC to the second, as GPX times usually are; fractions only when there are some
+fn gpx_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::services::gps::GpsPoint;
+    use crate::services::parse_gps_file;
+    use crate::types::POI;
+    use chrono::TimeZone;
+
+    fn track() -> GpsTrack {
+        let points = (0..5)
+            .map(|i| GpsPoint {
+                elevation_m: (i != 2).then_some(380.0 + i as f64),
+                speed_kmh: (i > 0).then_some(36.0),
+                heading_deg: (i > 1).then_some(45.0),
+                ..fixtures::gps_point(i, 46.52 + i as f64 * 0.0001, 6.63 + i as f64 * 0.0001)
+            })
+            .collect();
+        fixtures::track(points)
+    }
+
+    fn event(verified: bool, poi: &str) -> Event {
+        let poi = POI { category: "castle".to_string(), distance_m: 80.0, ..fixtures::poi(poi) };
+        Event { verified, ..fixtures::event(2.0, Some(&fixtures::truth(2, vec![poi]))) }
+    }
+
+    #[test]
+    fn test_document_follows_the_gpx_schema() {
+        let created = Utc.with_ymd_and_hms(2026, 7, 1, 12, 0, 0).unwrap();
+        let gpx = document("Ride <1>", &track(), &[event(true, "Fish & Chips"), event(false, "Rumour")], created);
+        let document = roxmltree::Document::parse(&gpx).unwrap_or_else(|e| panic!("{}\n{}", e, gpx));
+
+        let root = document.root_element();
+        assert!(root.has_tag_name((GPX_NS, "gpx")));
+        assert_eq!(root.attribute("version"), Some("1.1"));
+        assert!(root.attribute("creator").is_some());
+
+        let children = |node: roxmltree::Node| -> Vec<String> {
+            node.children().filter(|n| n.is_element()).map(|n| n.tag_name().name().to_string()).collect()
+        };
+        // xsd:sequence orders of gpxType, metadataType, wptType and the extension
+        assert_eq!(children(root), ["metadata", "wpt", "trk"]);
+        assert_eq!(children(root.first_element_child().unwrap()), ["name", "time", "bounds"]);
+        let waypoint = root.children().find(|n| n.has_tag_name((GPX_NS, "wpt"))).unwrap();
+        assert_eq!(children(waypoint), ["time", "name", "desc", "type"]);
+        assert_eq!(waypoint.children().find(|n| n.has_tag_name((GPX_NS, "name"))).unwrap().text(), Some("Fish & Chips"));
+
+        let points: Vec<roxmltree::Node> = root.descendants().filter(|n| n.has_tag_name((GPX_NS, "trkpt"))).collect();
+        assert_eq!(points.len(), 5);
+        assert_eq!(children(points[0]), ["ele", "time"]);
+        assert_eq!(children(points[2]), ["time", "extensions"]);
+        let extension = points[4].descendants().find(|n| n.has_tag_name((TPX_NS, "TrackPointExtension"))).unwrap();
+        assert_eq!(children(extension), ["speed", "course"]);
+        for point in points {
+            let lat: f64 = point.attribute("lat").unwrap().parse().unwrap();
+            let lon: f64 = point.attribute("lon").unwrap().parse().unwrap();
+            assert!((-90.0..=90.0).contains(&lat) && (-180.0..180.0).contains(&lon));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_document_round_trips_through_the_gpx_parser() {
+        let original = track();
+        let gpx = document("ride", &original, &[event(true, "Chillon")], Utc::now());
+        let dir = fixtures::temp_dir("export");
+        let path = dir.join("ride.gpx");
+        std::fs::write(&path, gpx).unwrap();
+
+        let parsed = parse_gps_file(&path).await.unwrap();
+        // The waypoint isn't taken for a track point
+        assert_eq!(parsed.point_count, original.points.len());
+        for (read, written) in parsed.points.iter().zip(&original.points) {
+            assert_eq!(read.timestamp, written.timestamp);
+            assert!((read.lat - written.lat).abs() < 1e-7 && (read.lon - written.lon).abs() < 1e-7);
+            assert_eq!(read.elevation_m, written.elevation_m);
+            assert_eq!(read.speed_kmh.map(|s| s.round()), written.speed_kmh);
+            assert_eq!(read.heading_deg, written.heading_deg);
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
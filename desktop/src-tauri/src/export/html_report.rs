@@ -0,0 +1,417 @@
+//! HTML Trip Report
+//!
+//! A video's trip as one HTML file that opens in any browser: a map of
+//! the track, the chapters of its latest narration, frames captured at
+//! verified events, and the places and facts those events were verified
+//! against. Everything is inlined (styles, script, the track as GeoJSON,
+//! images as data URIs), so the only requests made when it's viewed are
+//! for map tiles; without them the route is drawn on its own with a note.
+//!
+//! The page is `trip_report.html`, compiled in, with `{{name}}`
+//! slots filled by [`document`]. The map is a small script of its own
+//! rather than a map library, to keep the file light.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::enrich::stored_truth;
+use crate::pacing;
+use crate::services::database::Event;
+use crate::services::gps::TrackStats;
+use crate::types::{Chapter, POI};
+use super::escape_xml;
+
+const TEMPLATE: &str = include_str!("trip_report.html");
+
+/// Largest image file embedded; larger frames are left out
+pub const MAX_IMAGE_BYTES: u64 = 256 * 1024;
+
+/// Most image data embedded in one report
+pub const MAX_TOTAL_IMAGE_BYTES: u64 = 4 * 1024 * 1024;
+
+/// A frame captured at a verified event
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moment {
+    pub seconds: f64,
+    pub caption: String,
+    pub image_path: PathBuf,
+}
+
+/// An image read for the report, ready to embed
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedImage {
+    pub seconds: f64,
+    pub caption: String,
+    pub data_uri: String,
+}
+
+/// What goes into a report
+#[derive(Debug, Clone)]
+pub struct Report<'a> {
+    pub title: &'a str,
+    /// When the trip started, if the track says
+    pub recorded: Option<DateTime<Utc>>,
+    pub stats: Option<&'a TrackStats>,
+    /// Track and events, as from `geojson::feature_collection`
+    pub geojson: &'a Value,
+    pub chapters: &'a [Chapter],
+    pub events: &'a [Event],
+    pub images: &'a [EmbeddedImage],
+    /// Frames not embedded to keep the file small
+    pub images_left_out: usize,
+}
+
+/// The frames captured at verified events, in the order they're seen
+pub fn moments(events: &[Event]) -> Vec<Moment> {
+    let mut moments: Vec<Moment> = events
+        .iter()
+        .filter(|e| e.verified)
+        .filter_map(|event| {
+            let truth = stored_truth(event)?;
+            let image_path = PathBuf::from(truth.image_path.as_deref()?);
+            let caption = truth.pois.first().map(|poi| poi.name.clone()).unwrap_or_else(|| kind_name(event));
+            Some(Moment { seconds: event.start_time_seconds.max(0.0), caption, image_path })
+        })
+        .collect();
+    moments.sort_by(|a, b| a.seconds.total_cmp(&b.seconds));
+    moments
+}
+
+/// `bytes` of the image at `path` as a data URI; `None` for a file type
+/// browsers can't be relied on to show
+pub fn data_uri(path: &Path, bytes: &[u8]) -> Option<String> {
+    use base64::{engine::general_purpose, Engine as _};
+    let mime = match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "webp" => "image/webp",
+        _ => return None,
+    };
+    Some(format!("data:{};base64,{}", mime, general_purpose::STANDARD.encode(bytes)))
+}
+
+/// The report page, stamped with `generated`
+pub fn document(report: &Report, generated: DateTime<Utc>) -> String {
+    // `</` can't appear in an inline script; `<\/` is the same JSON
+    let geojson = report.geojson.to_string().replace("</", "<\\/");
+    fill(
+        TEMPLATE,
+        &[
+            ("title", &escape_xml(report.title)),
+            ("summary", &escape_xml(&summary(report))),
+            ("chapters", &chapter_table(report.chapters)),
+            ("moments", &moment_grid(report.images, report.images_left_out)),
+            ("places", &places(report.events)),
+            ("geojson", &geojson),
+            ("version", env!("CARGO_PKG_VERSION")),
+            ("generated", &generated.format("%Y-%m-%d").to_string()),
+        ],
+    )
+}
+
+/// `template` with each `{{name}}` replaced by its value in one pass, so
+/// braces in a value are never taken for a slot
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+        filled.push_str(&rest[..start]);
+        match values.iter().find(|(slot, _)| *slot == name) {
+            Some((_, value)) => filled.push_str(value),
+            None => filled.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// `12 June 2026 · 42.3 km · 1 h 05 min · 6 verified events`
+fn summary(report: &Report) -> String {
+    let mut parts = Vec::new();
+    if let Some(recorded) = report.recorded {
+        parts.push(recorded.format("%-d %B %Y").to_string());
+    }
+    if let Some(stats) = report.stats {
+        parts.push(format!("{:.1} km", stats.distance_km));
+        if let Some(seconds) = stats.duration_seconds {
+            parts.push(duration(seconds));
+        }
+    }
+    let verified = report.events.iter().filter(|e| e.verified).count();
+    parts.push(match verified {
+        1 => "1 verified event".to_string(),
+        n => format!("{} verified events", n),
+    });
+    parts.join(" · ")
+}
+
+fn duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).round() as u64;
+    match minutes {
+        0..=59 => format!("{} min", minutes),
+        _ => format!("{} h {:02} min", minutes / 60, minutes % 60),
+    }
+}
+
+fn chapter_table(chapters: &[Chapter]) -> String {
+    if chapters.is_empty() {
+        return "<p class=\"empty\">There are no chapters until the video is narrated.</p>".to_string();
+    }
+    let rows: String = chapters
+        .iter()
+        .map(|chapter| {
+            format!(
+                "\n    <tr><td class=\"time\">{}</td><td>{}</td><td>{}</td></tr>",
+                escape_xml(chapter.time_code.trim()),
+                escape_xml(&chapter.title),
+                escape_xml(chapter.description.as_deref().unwrap_or_default())
+            )
+        })
+        .collect();
+    format!(
+        "<table>\n    <tr><th>Time</th><th>Chapter</th><th>Description</th></tr>{}\n  </table>",
+        rows
+    )
+}
+
+fn moment_grid(images: &[EmbeddedImage], left_out: usize) -> String {
+    let mut html = String::new();
+    if images.is_empty() {
+        html.push_str("<p class=\"empty\">No frames were captured at verified events.</p>");
+    } else {
+        html.push_str("<div class=\"moments\">");
+        for image in images {
+            html.push_str(&format!(
+                "\n    <figure><img src=\"{}\" alt=\"{}\" loading=\"lazy\"><figcaption>{} {}</figcaption></figure>",
+                image.data_uri,
+                escape_xml(&image.caption),
+                pacing::format_time_code(image.seconds),
+                escape_xml(&image.caption)
+            ));
+        }
+        html.push_str("\n  </div>");
+    }
+    match left_out {
+        0 => {}
+        1 => html.push_str("\n  <p class=\"note\">1 more frame was left out to keep this file small.</p>"),
+        n => html.push_str(&format!("\n  <p class=\"note\">{} more frames were left out to keep this file small.</p>", n)),
+    }
+    html
+}
+
+/// Each POI of a verified event once, at the first time it's passed
+fn places(events: &[Event]) -> String {
+    let mut verified: Vec<&Event> = events.iter().filter(|e| e.verified).collect();
+    verified.sort_by(|a, b| a.start_time_seconds.total_cmp(&b.start_time_seconds));
+
+    let mut seen = HashSet::new();
+    let mut html = String::new();
+    for event in verified {
+        let Some(truth) = stored_truth(event) else {
+            continue;
+        };
+        for poi in truth.pois.iter().filter(|poi| seen.insert(poi.id.clone())) {
+            html.push_str(&place(poi, event.start_time_seconds));
+        }
+    }
+    if html.is_empty() {
+        return "<p class=\"empty\">No places were verified along the route.</p>".to_string();
+    }
+    html
+}
+
+fn place(poi: &POI, seconds: f64) -> String {
+    let category = match &poi.subcategory {
+        Some(subcategory) => format!("{}, {}", poi.category, subcategory),
+        None => poi.category.clone(),
+    };
+    let mut html = format!(
+        "\n  <div class=\"place\">\n    <h3>{}{}</h3>\n    <p class=\"meta\">{} · passed at {} · {:.0} m from the route · {:.5}, {:.5}</p>",
+        escape_xml(&poi.name),
+        badge(poi.confidence),
+        escape_xml(&category.replace('_', " ")),
+        pacing::format_time_code(seconds),
+        poi.distance_m,
+        poi.lat,
+        poi.lon
+    );
+
+    let mut facts = Vec::new();
+    if let Some(poi_facts) = &poi.facts {
+        if let Some(established) = &poi_facts.established {
+            facts.push(format!("Established {}", escape_xml(established)));
+        }
+        if poi_facts.unesco_site == Some(true) {
+            facts.push("UNESCO World Heritage Site".to_string());
+        }
+        if let Some(depth) = poi_facts.depth_m {
+            facts.push(format!("{:.0} m deep", depth));
+        }
+    }
+    if !facts.is_empty() {
+        html.push_str("\n    <ul>");
+        for fact in facts {
+            html.push_str(&format!("<li>{}</li>", fact));
+        }
+        html.push_str("</ul>");
+    }
+    if let Some(summary) = poi.facts.as_ref().and_then(|f| f.summary.as_ref()) {
+        let source = match &summary.url {
+            Some(url) => format!("<a href=\"{}\">{}</a>", escape_xml(url), escape_xml(&summary.source)),
+            None => escape_xml(&summary.source),
+        };
+        html.push_str(&format!(
+            "\n    <blockquote>{} <cite>({}, retrieved {})</cite></blockquote>",
+            escape_xml(&summary.extract),
+            source,
+            summary.retrieved_on
+        ));
+    }
+    html.push_str("\n  </div>");
+    html
+}
+
+/// How sure the match is, as a coloured label
+fn badge(confidence: f64) -> String {
+    let level = match confidence {
+        c if c >= 0.8 => "high",
+        c if c >= 0.5 => "medium",
+        _ => "low",
+    };
+    format!(
+        "<span class=\"badge {}\" title=\"Match confidence\">{} {:.0}%</span>",
+        level,
+        level,
+        confidence.clamp(0.0, 1.0) * 100.0
+    )
+}
+
+fn kind_name(event: &Event) -> String {
+    event.event_type.replace('_', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::types::{ArticleSummary, POIFacts, TruthEvent};
+    use chrono::{NaiveDate, TimeZone};
+    use serde_json::json;
+
+    fn event(seconds: f64, verified: bool, poi: &str, image: Option<&str>) -> Event {
+        let summary = ArticleSummary {
+            title: poi.to_string(),
+            language: "en".to_string(),
+            extract: "A water castle on Lake Geneva.".to_string(),
+            url: Some("https://en.wikipedia.org/wiki/Chillon_Castle".to_string()),
+            source: "Wikipedia".to_string(),
+            retrieved_on: NaiveDate::from_ymd_opt(2026, 6, 10).unwrap(),
+        };
+        let castle = POI {
+            category: "historic".to_string(),
+            subcategory: Some("castle".to_string()),
+            confidence: 0.92,
+            facts: Some(POIFacts { established: Some("1150".to_string()), summary: Some(summary), ..Default::default() }),
+            ..fixtures::poi(poi)
+        };
+        let truth = TruthEvent { image_path: image.map(String::from), ..fixtures::truth(0, vec![castle]) };
+        Event { verified, ..fixtures::event(seconds, Some(&truth)) }
+    }
+
+    /// The JSON embedded in the page's route script
+    fn embedded_geojson(html: &str) -> Value {
+        let start = html.find("id=\"route\">").unwrap() + "id=\"route\">".len();
+        let end = start + html[start..].find("</script>").unwrap();
+        serde_json::from_str(&html[start..end]).unwrap()
+    }
+
+    #[test]
+    fn test_report_is_self_contained() {
+        let events = [event(95.0, true, "Château de Chillon", Some("frames/a.jpg")), event(30.0, false, "Rumour </script>", None)];
+        let geojson = json!({"type": "FeatureCollection", "bbox": [6.9, 46.4, 6.95, 46.42], "features": [
+            {"type": "Feature", "geometry": {"type": "Point", "coordinates": [6.9275, 46.4142]},
+             "properties": {"kind": "event", "pois": ["</script><script>alert(1)</script>"]}},
+        ]});
+        let stats = TrackStats {
+            distance_km: 42.34,
+            duration_seconds: Some(3900.0),
+            avg_speed_kmh: None,
+            max_speed_kmh: None,
+            elevation_gain_m: None,
+            elevation_loss_m: None,
+        };
+        let chapters = [Chapter { description: Some("Montreux & on".to_string()), ..fixtures::chapter("00:00", "Along the <lake>") }];
+        let images = [EmbeddedImage {
+            seconds: 95.0,
+            caption: "Château de Chillon".to_string(),
+            data_uri: data_uri(Path::new("frames/a.JPG"), b"jpeg").unwrap(),
+        }];
+        let report = Report {
+            title: "Lake <Geneva>",
+            recorded: Some(Utc.with_ymd_and_hms(2026, 6, 12, 9, 0, 0).unwrap()),
+            stats: Some(&stats),
+            geojson: &geojson,
+            chapters: &chapters,
+            events: &events,
+            images: &images,
+            images_left_out: 2,
+        };
+        let html = document(&report, Utc.with_ymd_and_hms(2026, 7, 1, 0, 0, 0).unwrap());
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(!html.contains("{{"), "unfilled slot");
+        // Nothing is fetched but tiles: no external scripts, styles or images
+        assert!(!html.contains("<script src") && !html.contains("<link") && !html.contains("src=\"http"));
+        assert_eq!(html.matches("</script>").count(), 2);
+        assert_eq!(embedded_geojson(&html), geojson);
+
+        assert!(html.contains("<title>Lake &lt;Geneva&gt;</title>"));
+        assert!(html.contains("12 June 2026 · 42.3 km · 1 h 05 min · 1 verified event"));
+        assert!(html.contains("<td class=\"time\">00:00</td><td>Along the &lt;lake&gt;</td><td>Montreux &amp; on</td>"));
+        assert!(html.contains("<img src=\"data:image/jpeg;base64,anBlZw==\""));
+        assert!(html.contains("2 more frames were left out"));
+        assert!(html.contains("<span class=\"badge high\" title=\"Match confidence\">high 92%</span>"));
+        assert!(html.contains("Established 1150"));
+        assert!(!html.contains("Rumour"), "unverified events aren't listed as verified places");
+    }
+
+    #[test]
+    fn test_moments_come_from_verified_events_with_frames() {
+        let events = [
+            event(95.0, true, "Chillon", Some("frames/b.webp")),
+            event(12.5, true, "Montreux", Some("frames/a.jpg")),
+            event(40.0, true, "Vevey", None),
+            event(50.0, false, "Rumour", Some("frames/c.jpg")),
+        ];
+        let found = moments(&events);
+        let captions: Vec<&str> = found.iter().map(|m| m.caption.as_str()).collect();
+        assert_eq!(captions, ["Montreux", "Chillon"]);
+        assert_eq!(found[0].image_path, PathBuf::from("frames/a.jpg"));
+        assert_eq!(data_uri(Path::new("frames/clip.mov"), b"x"), None);
+
+        let empty = json!({"type": "FeatureCollection", "features": []});
+        let report = Report {
+            title: "Nothing yet",
+            recorded: None,
+            stats: None,
+            geojson: &empty,
+            chapters: &[],
+            events: &[],
+            images: &[],
+            images_left_out: 0,
+        };
+        let html = document(&report, Utc::now());
+        assert!(html.contains("There are no chapters until the video is narrated."));
+        assert!(html.contains("No frames were captured at verified events."));
+        assert!(html.contains("No places were verified along the route."));
+        assert!(html.contains("<p class=\"summary\">0 verified events</p>"));
+    }
+}
@@ -0,0 +1,323 @@
+//! KML Export
+//!
+//! A video's trip as a KML document for Google Earth, which can fly along
+//! it. The track is a timestamped `gx:Track`, so the time slider plays it
+//! back; events are Placemarks described by their POIs and facts, styled
+//! by whether they were verified, and chapter markers sit in a folder of
+//! their own. KMZ is the same document zipped as `doc.kml`.
+//!
+//! Chapters only have a time in the video, so each is placed at the
+//! located event nearest to it in the video.
+
+use std::fmt::Write as _;
+use std::io::Write as _;
+
+use crate::enrich::stored_truth;
+use crate::pacing;
+use crate::services::database::Event;
+use crate::services::GpsTrack;
+use crate::types::{Chapter, TruthEvent};
+use super::escape_xml;
+
+/// Most POIs described with an event
+const POI_LINES: usize = 5;
+
+/// Longest article extract quoted in an event's description
+const EXTRACT_CHARS: usize = 280;
+
+/// Shared styles: a red track line, green pins for verified events,
+/// yellow for unverified ones and blue stars for chapters
+const STYLES: &str = r##"    <Style id="track"><LineStyle><color>ff2f2fd6</color><width>4</width></LineStyle></Style>
+    <Style id="verified"><IconStyle><color>ff3cb44b</color><Icon><href>http://maps.google.com/mapfiles/kml/paddle/grn-circle.png</href></Icon></IconStyle></Style>
+    <Style id="unverified"><IconStyle><color>ff19e1ff</color><Icon><href>http://maps.google.com/mapfiles/kml/paddle/ylw-circle.png</href></Icon></IconStyle></Style>
+    <Style id="chapter"><IconStyle><Icon><href>http://maps.google.com/mapfiles/kml/paddle/blu-stars.png</href></Icon></IconStyle></Style>
+"##;
+
+/// A chapter placed on the map
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChapterMarker {
+    pub title: String,
+    pub time_code: String,
+    pub description: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// `chapters` placed at the located event nearest to each in the video;
+/// none without located events, or for a chapter whose time code can't be read
+pub fn chapter_markers(chapters: &[Chapter], events: &[Event]) -> Vec<ChapterMarker> {
+    let located: Vec<(f64, f64, f64)> = events
+        .iter()
+        .filter_map(|e| Some((e.start_time_seconds, e.lat?, e.lon?)))
+        .collect();
+    chapters
+        .iter()
+        .filter_map(|chapter| {
+            let seconds = pacing::time_code_seconds(&chapter.time_code)?;
+            let &(_, lat, lon) = located
+                .iter()
+                .min_by(|a, b| (a.0 - seconds).abs().total_cmp(&(b.0 - seconds).abs()))?;
+            Some(ChapterMarker {
+                title: chapter.title.clone(),
+                time_code: chapter.time_code.clone(),
+                description: chapter.description.clone(),
+                lat,
+                lon,
+            })
+        })
+        .collect()
+}
+
+/// The KML document for a video named `name`
+pub fn document(name: &str, track: Option<&GpsTrack>, events: &[Event], chapters: &[ChapterMarker]) -> String {
+    let mut kml = String::new();
+    kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\" xmlns:gx=\"http://www.google.com/kml/ext/2.2\">\n");
+    kml.push_str("  <Document>\n");
+    let _ = writeln!(kml, "    <name>{}</name>", escape_xml(name));
+    kml.push_str(STYLES);
+
+    if let Some(track) = track.filter(|t| t.points.len() >= 2) {
+        push_track(&mut kml, name, track);
+    }
+
+    let located: Vec<&Event> = events.iter().filter(|e| e.lat.is_some() && e.lon.is_some()).collect();
+    if !located.is_empty() {
+        kml.push_str("    <Folder>\n      <name>Events</name>\n");
+        for event in located {
+            push_event(&mut kml, event);
+        }
+        kml.push_str("    </Folder>\n");
+    }
+
+    if !chapters.is_empty() {
+        kml.push_str("    <Folder>\n      <name>Chapters</name>\n");
+        for chapter in chapters {
+            let description = match &chapter.description {
+                Some(description) => format!("{} · {}", chapter.time_code, description),
+                None => chapter.time_code.clone(),
+            };
+            push_placemark(&mut kml, &chapter.title, None, "chapter", &description, chapter.lat, chapter.lon);
+        }
+        kml.push_str("    </Folder>\n");
+    }
+
+    kml.push_str("  </Document>\n</kml>\n");
+    kml
+}
+
+/// `kml` zipped as a KMZ, the document named `doc.kml` as Google Earth expects
+pub fn kmz(kml: &str) -> Result<Vec<u8>, String> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    zip.start_file("doc.kml", zip::write::SimpleFileOptions::default())
+        .map_err(|e| format!("Failed to start the KMZ: {}", e))?;
+    zip.write_all(kml.as_bytes()).map_err(|e| format!("Failed to write the KMZ: {}", e))?;
+    let cursor = zip.finish().map_err(|e| format!("Failed to finish the KMZ: {}", e))?;
+    Ok(cursor.into_inner())
+}
+
+fn push_track(kml: &mut String, name: &str, track: &GpsTrack) {
+    kml.push_str("    <Placemark>\n");
+    let _ = writeln!(kml, "      <name>{}</name>", escape_xml(name));
+    kml.push_str("      <styleUrl>#track</styleUrl>\n");
+    kml.push_str("      <gx:Track>\n        <altitudeMode>clampToGround</altitudeMode>\n");
+    // Every <when> first, then every <gx:coord> in the same order
+    for point in &track.points {
+        let _ = writeln!(kml, "        <when>{}</when>", point.timestamp.to_rfc3339());
+    }
+    for point in &track.points {
+        let _ = writeln!(
+            kml,
+            "        <gx:coord>{:.6} {:.6} {:.1}</gx:coord>",
+            point.lon,
+            point.lat,
+            point.elevation_m.unwrap_or(0.0)
+        );
+    }
+    kml.push_str("      </gx:Track>\n    </Placemark>\n");
+}
+
+fn push_event(kml: &mut String, event: &Event) {
+    let (Some(lat), Some(lon)) = (event.lat, event.lon) else {
+        return;
+    };
+    // Only a stored Truth Event has the POIs and when it happened
+    let truth = event.truth_bundle_json.as_ref().and_then(|_| stored_truth(event));
+    let name = truth
+        .as_ref()
+        .and_then(|t| t.pois.first())
+        .map(|poi| poi.name.clone())
+        .unwrap_or_else(|| event.event_type.replace('_', " "));
+    let style = if event.verified { "verified" } else { "unverified" };
+    let when = truth.as_ref().filter(|t| !t.time_uncertain).map(|t| t.timestamp.to_rfc3339());
+    push_placemark(kml, &name, when.as_deref(), style, &event_description(event, truth.as_ref()), lat, lon);
+}
+
+fn push_placemark(kml: &mut String, name: &str, when: Option<&str>, style: &str, description: &str, lat: f64, lon: f64) {
+    kml.push_str("      <Placemark>\n");
+    let _ = writeln!(kml, "        <name>{}</name>", escape_xml(name));
+    if let Some(when) = when {
+        let _ = writeln!(kml, "        <TimeStamp><when>{}</when></TimeStamp>", when);
+    }
+    let _ = writeln!(kml, "        <styleUrl>#{}</styleUrl>", style);
+    let _ = writeln!(kml, "        <description>{}</description>", escape_xml(description));
+    let _ = writeln!(kml, "        <Point><coordinates>{:.6},{:.6}</coordinates></Point>", lon, lat);
+    kml.push_str("      </Placemark>\n");
+}
+
+/// The balloon text of an event, as HTML; escaped again as a whole when
+/// it's written into the document
+fn event_description(event: &Event, truth: Option<&TruthEvent>) -> String {
+    let mut html = format!(
+        "<p><b>{}</b> at {} in the video · {}</p>",
+        escape_xml(&event.event_type.replace('_', " ")),
+        video_time(event.start_time_seconds),
+        if event.verified { "verified" } else { "not verified" }
+    );
+    let Some(truth) = truth else {
+        return html;
+    };
+
+    if !truth.pois.is_empty() {
+        html.push_str("<ul>");
+        for poi in truth.pois.iter().take(POI_LINES) {
+            let _ = write!(
+                html,
+                "<li>{} ({}, {:.0} m)</li>",
+                escape_xml(&poi.name),
+                escape_xml(&poi.category),
+                poi.distance_m
+            );
+        }
+        html.push_str("</ul>");
+    }
+
+    let facts = truth.pois.iter().find_map(|poi| poi.facts.as_ref().map(|facts| (poi, facts)));
+    if let Some((poi, facts)) = facts {
+        if let Some(established) = &facts.established {
+            let _ = write!(html, "<p>{} was established in {}.</p>", escape_xml(&poi.name), escape_xml(established));
+        }
+        if facts.unesco_site == Some(true) {
+            let _ = write!(html, "<p>{} is a UNESCO World Heritage Site.</p>", escape_xml(&poi.name));
+        }
+        if let Some(summary) = &facts.summary {
+            let extract: String = summary.extract.chars().take(EXTRACT_CHARS).collect();
+            let ellipsis = if summary.extract.chars().count() > EXTRACT_CHARS { "…" } else { "" };
+            let _ = write!(html, "<p>{}{} ({})</p>", escape_xml(&extract), ellipsis, escape_xml(&summary.source));
+        }
+    }
+    html
+}
+
+/// `M:SS`, or `H:MM:SS` from an hour
+fn video_time(seconds: f64) -> String {
+    let total = seconds.max(0.0).round() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, chapter};
+    use crate::services::gps::GpsPoint;
+    use crate::types::{POIFacts, POI};
+    use std::io::Read as _;
+
+    const KML_NS: &str = "http://www.opengis.net/kml/2.2";
+    const GX_NS: &str = "http://www.google.com/kml/ext/2.2";
+
+    fn track() -> GpsTrack {
+        let points = (0..3)
+            .map(|i| GpsPoint {
+                elevation_m: (i > 0).then_some(380.0),
+                ..fixtures::gps_point(60 * i, 46.52 + i as f64 * 0.01, 6.63 + i as f64 * 0.01)
+            })
+            .collect();
+        fixtures::track(points)
+    }
+
+    /// Spread out along the route by time into the video
+    fn event(seconds: f64, verified: bool, truth: Option<&TruthEvent>) -> Event {
+        Event { verified, lon: Some(6.645 + seconds / 1000.0), ..fixtures::event(seconds, truth) }
+    }
+
+    fn truth(poi_name: &str) -> TruthEvent {
+        let poi = POI {
+            category: "food".to_string(),
+            distance_m: 42.0,
+            facts: Some(POIFacts { established: Some("1887".to_string()), ..Default::default() }),
+            ..fixtures::poi(poi_name)
+        };
+        fixtures::truth(65, vec![poi])
+    }
+
+    #[test]
+    fn test_document_round_trips_through_an_xml_parser() {
+        let events = [event(65.0, true, Some(&truth("Fish & Chips <Joe's>"))), event(300.0, false, None)];
+        let chapters = chapter_markers(&[chapter("00:00", "Leaving \"town\""), chapter("04:50", "The lake")], &events);
+        let kml = document("GH010123 & co.MP4", Some(&track()), &events, &chapters);
+
+        let document = roxmltree::Document::parse(&kml).unwrap_or_else(|e| panic!("{}\n{}", e, kml));
+        let root = document.root_element();
+        assert_eq!(root.tag_name().namespace(), Some(KML_NS));
+        let name = root.descendants().find(|n| n.has_tag_name((KML_NS, "name"))).unwrap();
+        assert_eq!(name.text(), Some("GH010123 & co.MP4"));
+
+        let gx_track = root.descendants().find(|n| n.has_tag_name((GX_NS, "Track"))).unwrap();
+        let whens = gx_track.children().filter(|n| n.has_tag_name((KML_NS, "when"))).count();
+        let coords: Vec<&str> = gx_track.children().filter(|n| n.has_tag_name((GX_NS, "coord"))).filter_map(|n| n.text()).collect();
+        assert_eq!((whens, coords.len()), (3, 3));
+        assert_eq!(coords[0], "6.630000 46.520000 0.0");
+
+        let folders: Vec<roxmltree::Node> = root.descendants().filter(|n| n.has_tag_name((KML_NS, "Folder"))).collect();
+        let placemarks = |folder: &roxmltree::Node| -> Vec<(String, String)> {
+            folder
+                .children()
+                .filter(|n| n.has_tag_name((KML_NS, "Placemark")))
+                .map(|p| {
+                    let child = |tag: &str| p.children().find(|n| n.has_tag_name((KML_NS, tag))).and_then(|n| n.text()).unwrap_or_default().to_string();
+                    (child("name"), child("styleUrl"))
+                })
+                .collect()
+        };
+        assert_eq!(
+            placemarks(&folders[0]),
+            [("Fish & Chips <Joe's>".to_string(), "#verified".to_string()), ("nearest pass".to_string(), "#unverified".to_string())]
+        );
+        assert_eq!(
+            placemarks(&folders[1]),
+            [("Leaving \"town\"".to_string(), "#chapter".to_string()), ("The lake".to_string(), "#chapter".to_string())]
+        );
+
+        // The description is HTML, its own text escaped within it
+        let description = folders[0].descendants().find(|n| n.has_tag_name((KML_NS, "description"))).unwrap().text().unwrap();
+        assert!(description.contains("<li>Fish &amp; Chips &lt;Joe&apos;s&gt; (food, 42 m)</li>"), "{}", description);
+        assert!(description.contains("established in 1887"), "{}", description);
+        assert!(description.contains("at 1:05 in the video · verified"), "{}", description);
+    }
+
+    #[test]
+    fn test_chapters_sit_at_the_nearest_located_event() {
+        let events = [event(10.0, true, None), event(200.0, true, None)];
+        let chapters = [chapter("00:00", "Start"), chapter("03:00", "Later"), chapter("later", "Unreadable")];
+        let markers = chapter_markers(&chapters, &events);
+        assert_eq!(markers.len(), 2);
+        assert_eq!((markers[0].lon, markers[1].lon), (events[0].lon.unwrap(), events[1].lon.unwrap()));
+        assert!(chapter_markers(&chapters, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_kmz_holds_the_document() {
+        let kml = document("clip.mp4", Some(&track()), &[], &[]);
+        let bytes = kmz(&kml).unwrap();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let mut unzipped = String::new();
+        archive.by_name("doc.kml").unwrap().read_to_string(&mut unzipped).unwrap();
+        assert_eq!(unzipped, kml);
+    }
+}
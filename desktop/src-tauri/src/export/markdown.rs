@@ -0,0 +1,379 @@
+//! Markdown Export
+//!
+//! A video's trip as Markdown for blogs and Notion: front matter with the
+//! title, date, distance and duration, then a section for each chapter of
+//! the narration holding its script, with a "Verified facts" blockquote on
+//! the places the script cites, and a table of the places passed with
+//! links to them on OpenStreetMap. A video that hasn't been narrated gets
+//! a summary of its verified events instead of the chapters.
+//!
+//! Names and text from users, the narration and map data are escaped, so
+//! a place called `*NSYNC Café` or a title with a `|` reads as written.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Utc};
+
+use crate::enrich::stored_truth;
+use crate::pacing;
+use crate::services::database::Event;
+use crate::services::gps::TrackStats;
+use crate::types::{Chapter, NarrateResponse, ScriptSegment, POI};
+
+/// What goes into the document
+#[derive(Debug, Clone)]
+pub struct Trip<'a> {
+    pub title: &'a str,
+    /// When the trip started
+    pub recorded: Option<DateTime<Utc>>,
+    pub stats: Option<&'a TrackStats>,
+    /// The narration to write out, with its id
+    pub narration: Option<(&'a str, &'a NarrateResponse)>,
+    pub events: &'a [Event],
+}
+
+/// A POI of a verified event, at the first time it's passed
+struct Place {
+    seconds: f64,
+    poi: POI,
+}
+
+/// What the verified events back up
+struct Verified {
+    /// In the order they're passed, each once
+    places: Vec<Place>,
+    /// POI ids of each Truth Event, by its id, as scripts cite them
+    by_event: HashMap<String, Vec<String>>,
+    /// Time and landmarks of each verified event, in order
+    events: Vec<(f64, String)>,
+}
+
+/// The Markdown document for `trip`
+pub fn document(trip: &Trip) -> String {
+    let verified = verified(trip.events);
+    let mut md = front_matter(trip, &verified);
+    let _ = writeln!(md, "\n# {}", escape(trip.title));
+
+    let narration = trip
+        .narration
+        .map(|(_, response)| response)
+        .filter(|response| !response.chapters.is_empty() || !segments(response).is_empty());
+    match narration {
+        Some(response) => {
+            for (chapter, lines) in sections(response) {
+                md.push_str(&section(chapter, &lines, &verified));
+            }
+        }
+        None => md.push_str(&event_summary(&verified)),
+    }
+
+    md.push_str(&place_table(&verified.places));
+    md
+}
+
+fn verified(events: &[Event]) -> Verified {
+    let mut events: Vec<&Event> = events.iter().filter(|e| e.verified).collect();
+    events.sort_by(|a, b| a.start_time_seconds.total_cmp(&b.start_time_seconds));
+
+    let mut verified = Verified { places: Vec::new(), by_event: HashMap::new(), events: Vec::new() };
+    let mut seen = HashSet::new();
+    for event in events {
+        let Some(truth) = stored_truth(event) else {
+            continue;
+        };
+        let seconds = event.start_time_seconds.max(0.0);
+        let landmarks: Vec<&str> = truth.pois.iter().map(|poi| poi.name.as_str()).collect();
+        let label = match landmarks.is_empty() {
+            true => event.event_type.replace('_', " "),
+            false => format!("Near {}", landmarks.join(", ")),
+        };
+        verified.events.push((seconds, label));
+        verified.by_event.insert(truth.id.clone(), truth.pois.iter().map(|poi| poi.id.clone()).collect());
+        for poi in truth.pois {
+            if seen.insert(poi.id.clone()) {
+                verified.places.push(Place { seconds, poi });
+            }
+        }
+    }
+    verified
+}
+
+fn front_matter(trip: &Trip, verified: &Verified) -> String {
+    let mut md = String::from("---\n");
+    // A JSON string is a YAML double-quoted scalar
+    let _ = writeln!(md, "title: {}", serde_json::Value::from(trip.title));
+    if let Some(recorded) = trip.recorded {
+        let _ = writeln!(md, "date: {}", recorded.format("%Y-%m-%d"));
+    }
+    if let Some(stats) = trip.stats {
+        let _ = writeln!(md, "distance_km: {:.1}", stats.distance_km);
+        if let Some(seconds) = stats.duration_seconds {
+            let total = seconds.max(0.0).round() as u64;
+            let _ = writeln!(md, "duration: \"{:02}:{:02}:{:02}\"", total / 3600, total / 60 % 60, total % 60);
+        }
+    }
+    if let Some((id, _)) = trip.narration {
+        let _ = writeln!(md, "narration: {}", serde_json::Value::from(id));
+    }
+    let _ = writeln!(md, "verified_events: {}", verified.events.len());
+    md.push_str("---\n");
+    md
+}
+
+fn segments(response: &NarrateResponse) -> &[ScriptSegment] {
+    response.script.as_ref().map_or(&[], |script| script.segments.as_slice())
+}
+
+/// Each chapter with the script lines from its start to the next one's;
+/// lines before the first chapter go with it, and without chapters all
+/// lines make one section
+fn sections(response: &NarrateResponse) -> Vec<(Option<&Chapter>, Vec<&ScriptSegment>)> {
+    let mut chapters: Vec<(f64, &Chapter)> = response
+        .chapters
+        .iter()
+        .filter_map(|c| Some((pacing::time_code_seconds(&c.time_code)?, c)))
+        .collect();
+    chapters.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut sections: Vec<(Option<&Chapter>, Vec<&ScriptSegment>)> = match chapters.is_empty() {
+        true => vec![(None, Vec::new())],
+        false => chapters.iter().map(|(_, c)| (Some(*c), Vec::new())).collect(),
+    };
+    for segment in segments(response) {
+        let seconds = pacing::time_code_seconds(&segment.time_code).unwrap_or(0.0);
+        let index = chapters.iter().rposition(|(start, _)| *start <= seconds).unwrap_or(0);
+        sections[index].1.push(segment);
+    }
+    sections
+}
+
+fn section(chapter: Option<&Chapter>, lines: &[&ScriptSegment], verified: &Verified) -> String {
+    let mut md = String::new();
+    match chapter {
+        Some(chapter) => {
+            let _ = writeln!(md, "\n## {} · {}", escape(chapter.time_code.trim()), escape(&chapter.title));
+            if let Some(description) = chapter.description.as_deref().filter(|d| !d.trim().is_empty()) {
+                let _ = writeln!(md, "\n*{}*", escape(description));
+            }
+        }
+        None => md.push_str("\n## Narration\n"),
+    }
+    for line in lines.iter().filter(|line| !line.narration.trim().is_empty()) {
+        let _ = writeln!(md, "\n{}", escape(&line.narration));
+    }
+
+    // The places the lines cite, directly or through their events
+    let mut cited: Vec<&str> = Vec::new();
+    for id in lines.iter().flat_map(|line| &line.source_refs) {
+        match verified.by_event.get(id) {
+            Some(pois) => cited.extend(pois.iter().map(String::as_str)),
+            None => cited.push(id),
+        }
+    }
+    let mut seen = HashSet::new();
+    let places: Vec<&Place> = cited
+        .into_iter()
+        .filter(|id| seen.insert(*id))
+        .filter_map(|id| verified.places.iter().find(|place| place.poi.id == id))
+        .collect();
+    md.push_str(&fact_quote(&places));
+    md
+}
+
+fn event_summary(verified: &Verified) -> String {
+    let mut md = String::from("\n*This video hasn't been narrated yet; here is what was verified along the way.*\n\n## Events\n");
+    if verified.events.is_empty() {
+        md.push_str("\nNo events were verified.\n");
+        return md;
+    }
+    md.push('\n');
+    for (seconds, label) in &verified.events {
+        let _ = writeln!(md, "- {} · {}", pacing::format_time_code(*seconds), escape(label));
+    }
+    md.push_str(&fact_quote(&verified.places.iter().collect::<Vec<_>>()));
+    md
+}
+
+/// A "Verified facts" blockquote with a line for each place
+fn fact_quote(places: &[&Place]) -> String {
+    if places.is_empty() {
+        return String::new();
+    }
+    let mut md = String::from("\n> **Verified facts**\n>\n");
+    for place in places {
+        let _ = writeln!(md, "> - {}", fact_line(&place.poi));
+    }
+    md
+}
+
+/// `**Name**, kind, established 1191. Source: OpenStreetMap`
+fn fact_line(poi: &POI) -> String {
+    let mut facts = vec![escape(&poi.category.replace('_', " "))];
+    let mut sources = vec!["OpenStreetMap".to_string()];
+    let mut extract = None;
+    if let Some(poi_facts) = &poi.facts {
+        if let Some(established) = &poi_facts.established {
+            facts.push(format!("established {}", escape(established)));
+        }
+        if poi_facts.unesco_site == Some(true) {
+            facts.push("a UNESCO World Heritage Site".to_string());
+        }
+        if let Some(depth) = poi_facts.depth_m {
+            facts.push(format!("{} m deep", number(depth)));
+        }
+        if let Some(summary) = &poi_facts.summary {
+            extract = Some(escape(&summary.extract));
+            sources.push(match &summary.url {
+                Some(url) => format!("[{}]({})", escape(&summary.source), link_target(url)),
+                None => escape(&summary.source),
+            });
+        }
+        if let Some(wikidata) = poi_facts.wikidata.as_deref().filter(|id| id.starts_with('Q')) {
+            sources.push(format!("[Wikidata](https://www.wikidata.org/wiki/{})", link_target(wikidata)));
+        }
+    }
+
+    let mut line = format!("**{}**, {}.", escape(&poi.name), facts.join(", "));
+    if let Some(extract) = extract {
+        let _ = write!(line, " {}", extract);
+    }
+    let _ = write!(line, " {}: {}", if sources.len() == 1 { "Source" } else { "Sources" }, sources.join(", "));
+    line
+}
+
+fn place_table(places: &[Place]) -> String {
+    if places.is_empty() {
+        return String::new();
+    }
+    let mut md = String::from("\n## Places\n\n| Time | Place | Kind | Coordinates | Map |\n| --- | --- | --- | --- | --- |\n");
+    for place in places {
+        let (lat, lon) = (place.poi.lat, place.poi.lon);
+        let _ = writeln!(
+            md,
+            "| {} | {} | {} | {:.5}, {:.5} | [OpenStreetMap](https://www.openstreetmap.org/?mlat={:.5}&mlon={:.5}#map=17/{:.5}/{:.5}) |",
+            pacing::format_time_code(place.seconds),
+            escape(&place.poi.name),
+            escape(&place.poi.category.replace('_', " ")),
+            lat,
+            lon,
+            lat,
+            lon,
+            lat,
+            lon
+        );
+    }
+    md
+}
+
+/// `text` on one line, with the characters Markdown would act on escaped
+pub fn escape(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    // What would make a list item or a heading underline at the start of a line
+    if escaped.starts_with(['-', '+', '=']) {
+        escaped.insert(0, '\\');
+    } else {
+        let digits = escaped.chars().take_while(char::is_ascii_digit).count();
+        if digits > 0 && escaped[digits..].starts_with(['.', ')']) {
+            escaped.insert(digits, '\\');
+        }
+    }
+    escaped
+}
+
+/// A URL made safe to put between a link's parentheses
+fn link_target(url: &str) -> String {
+    url.trim().replace(' ', "%20").replace('(', "%28").replace(')', "%29")
+}
+
+/// `12.5`, or `12` for a whole number
+fn number(value: f64) -> String {
+    let rounded = format!("{:.1}", value);
+    rounded.strip_suffix(".0").map(str::to_string).unwrap_or(rounded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, assert_golden};
+    use chrono::TimeZone;
+    use serde_json::Value;
+
+    /// The events of the offline fixture bundle, as kept for a video
+    fn fixture_events() -> Vec<Event> {
+        let bundle: Value = serde_json::from_str(include_str!("../testdata/offline_bundle.json")).unwrap();
+        let events = bundle["events"].as_array().unwrap();
+        let start: DateTime<Utc> = events[0]["timestamp"].as_str().unwrap().parse().unwrap();
+        events
+            .iter()
+            .map(|truth| {
+                let timestamp: DateTime<Utc> = truth["timestamp"].as_str().unwrap().parse().unwrap();
+                Event {
+                    id: truth["id"].as_str().unwrap().to_string(),
+                    event_type: "speech".to_string(),
+                    lat: truth["location"]["lat"].as_f64(),
+                    lon: truth["location"]["lon"].as_f64(),
+                    verification_mode: Some("offline".to_string()),
+                    truth_bundle_json: Some(truth.to_string()),
+                    created_at: timestamp,
+                    ..fixtures::event((timestamp - start).num_seconds() as f64, None)
+                }
+            })
+            .collect()
+    }
+
+    fn stats() -> TrackStats {
+        TrackStats {
+            distance_km: 9.84,
+            duration_seconds: Some(1300.0),
+            avg_speed_kmh: None,
+            max_speed_kmh: None,
+            elevation_gain_m: None,
+            elevation_loss_m: None,
+        }
+    }
+
+    #[test]
+    fn test_narrated_trip_matches_golden() {
+        // The fixture narration keeps its script as the list of lines
+        let mut narration: Value = serde_json::from_str(include_str!("../testdata/offline_narration_en.json")).unwrap();
+        narration["script"] = serde_json::json!({ "segments": narration["script"].take() });
+        let response: NarrateResponse = serde_json::from_value(narration).unwrap();
+        let events = fixture_events();
+        let stats = stats();
+        let trip = Trip {
+            title: "Monaco & the *Riviera*",
+            recorded: Some(Utc.with_ymd_and_hms(2024, 5, 4, 8, 0, 0).unwrap()),
+            stats: Some(&stats),
+            narration: Some(("narration-1", &response)),
+            events: &events,
+        };
+        assert_golden("trip_summary.md", include_str!("../testdata/trip_summary.md"), &document(&trip));
+    }
+
+    #[test]
+    fn test_trip_without_narration_summarises_events() {
+        let mut events = fixture_events();
+        events[1].verified = false;
+        let trip = Trip { title: "Monaco", recorded: None, stats: None, narration: None, events: &events };
+        assert_golden("trip_summary_events.md", include_str!("../testdata/trip_summary_events.md"), &document(&trip));
+    }
+
+    #[test]
+    fn test_escape_keeps_text_literal() {
+        assert_eq!(escape("*NSYNC [live] at <Café_Noir>"), "\\*NSYNC \\[live\\] at \\<Café\\_Noir\\>");
+        assert_eq!(escape("a | b #1\n  `code`"), "a \\| b \\#1 \\`code\\`");
+        assert_eq!(escape("- not a list"), "\\- not a list");
+        assert_eq!(escape("1991. A good year"), "1991\\. A good year");
+        assert_eq!(escape("Route 66) and on"), "Route 66) and on");
+        assert_eq!(escape("Saint-Tropez, 3.5 km"), "Saint-Tropez, 3.5 km");
+    }
+}
@@ -0,0 +1,38 @@
+//! Exports
+//!
+//! A video's track, events and narration written out in the formats other
+//! tools read: maps and GPS services, editing software, video platforms,
+//! subtitles, speech synthesis and documents to share. Each format is a
+//! module of pure functions from stored records to text or bytes; the
+//! commands that gather the records and write the files are in
+//! `commands::export` and `commands::narrate`.
+
+pub mod geojson;
+pub mod gpx;
+pub mod html_report;
+pub mod kml;
+pub mod markdown;
+pub mod nle;
+pub mod script;
+pub mod subtitles;
+pub mod youtube_chapters;
+
+/// `text` safe to put between tags or in an attribute
+///
+/// Control characters XML doesn't allow at all are dropped.
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
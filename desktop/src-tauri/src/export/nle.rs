@@ -0,0 +1,501 @@
+//! NLE Export
+//!
+//! Chapters and verified events as markers for editing software, placed
+//! on whole frames of the source clip. FCPXML describes the clip itself
+//! with its markers on it, for Final Cut Pro to open as a project; a
+//! CMX3600 EDL and the marker CSVs of Premiere Pro and DaVinci Resolve
+//! carry the markers alone, at timecodes.
+//!
+//! Times in FCPXML are rational seconds in multiples of a frame's
+//! duration, so NTSC rates are kept exact: a frame at 29.97 fps is
+//! `1001/30000s`, not a rounded decimal that drifts over a long clip.
+//! Timecodes at 29.97 and 59.94 are drop-frame, skipping frame numbers
+//! as NTSC does so they keep up with the clock.
+
+use std::fmt::Write as _;
+
+use serde::{Deserialize, Serialize};
+
+use crate::enrich::stored_truth;
+use crate::pacing;
+use crate::services::database::{Event, Video};
+use crate::types::{Chapter, TruthEvent};
+use super::escape_xml;
+
+/// Longest article extract in a marker's note
+const NOTE_EXTRACT_CHARS: usize = 200;
+
+/// A video frame rate as the duration of one frame, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    /// Frame duration is `numerator / denominator` seconds
+    pub numerator: u64,
+    pub denominator: u64,
+    /// Whole frames per second a timecode counts, e.g. 30 for 29.97
+    pub timebase: u64,
+    /// NTSC 29.97 or 59.94, whose timecode drops frame numbers to keep
+    /// up with the clock
+    pub drop_frame: bool,
+}
+
+impl FrameRate {
+    /// The rate of `fps`, recognising NTSC rates (23.976, 29.97, 59.94) as
+    /// the exact 1000/1001 fractions they stand for; `None` if it isn't a rate
+    pub fn from_fps(fps: f64) -> Option<Self> {
+        if !fps.is_finite() || fps < 1.0 {
+            return None;
+        }
+        for timebase in [24, 30, 60] {
+            if (fps - timebase as f64 * 1000.0 / 1001.0).abs() < 0.01 {
+                return Some(Self { numerator: 1001, denominator: timebase * 1000, timebase, drop_frame: timebase != 24 });
+            }
+        }
+        let timebase = fps.round() as u64;
+        Some(Self { numerator: 100, denominator: timebase * 100, timebase, drop_frame: false })
+    }
+
+    /// The frame `seconds` falls nearest to
+    pub fn frames(&self, seconds: f64) -> u64 {
+        (seconds.max(0.0) * self.denominator as f64 / self.numerator as f64).round() as u64
+    }
+
+    /// `frames` as an FCPXML time, e.g. `3003/1000s`
+    pub fn rational(&self, frames: u64) -> String {
+        if frames == 0 {
+            return "0s".to_string();
+        }
+        let (numerator, denominator) = (frames * self.numerator, self.denominator);
+        let divisor = gcd(numerator, denominator);
+        if divisor == denominator {
+            format!("{}s", numerator / divisor)
+        } else {
+            format!("{}/{}s", numerator / divisor, denominator / divisor)
+        }
+    }
+}
+
+impl FrameRate {
+    /// `frames` as a timecode, `HH:MM:SS:FF`, or `HH:MM:SS;FF` when it's
+    /// drop-frame
+    ///
+    /// Drop-frame timecode skips the first frame numbers of each minute,
+    /// two at 29.97 and four at 59.94, except every tenth minute.
+    pub fn timecode(&self, frames: u64) -> String {
+        let mut number = frames;
+        if self.drop_frame {
+            let dropped = self.timebase / 15;
+            let per_ten_minutes = self.timebase * 600 - dropped * 9;
+            let per_minute = self.timebase * 60 - dropped;
+            let (tens, rest) = (frames / per_ten_minutes, frames % per_ten_minutes);
+            number += dropped * 9 * tens;
+            if rest > dropped {
+                number += dropped * ((rest - dropped) / per_minute);
+            }
+        }
+        let (seconds, frame) = (number / self.timebase, number % self.timebase);
+        let separator = if self.drop_frame { ';' } else { ':' };
+        format!("{:02}:{:02}:{:02}{}{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60, separator, frame)
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    Chapter,
+    Event,
+}
+
+impl MarkerKind {
+    /// Chapters blue, events green, in the colour names editors know
+    fn color(self) -> &'static str {
+        match self {
+            MarkerKind::Chapter => "Blue",
+            MarkerKind::Event => "Green",
+        }
+    }
+}
+
+/// A marker file format for editing software
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerFormat {
+    /// CMX3600 EDL with a `* LOC:` locator per marker
+    Edl,
+    /// Premiere Pro's marker columns
+    PremiereCsv,
+    /// DaVinci Resolve's marker columns
+    ResolveCsv,
+}
+
+/// A point on the clip's timeline to mark
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub seconds: f64,
+    pub name: String,
+    pub note: String,
+}
+
+/// Markers for `chapters` and the verified events among `events`, in
+/// timeline order
+///
+/// Chapters are named by their titles and events by their top POI; an
+/// event's note comes from that POI's facts. Chapters whose time code
+/// can't be read are left out.
+pub fn markers(chapters: &[Chapter], events: &[Event]) -> Vec<Marker> {
+    let mut markers: Vec<Marker> = chapters
+        .iter()
+        .filter_map(|chapter| {
+            Some(Marker {
+                kind: MarkerKind::Chapter,
+                seconds: pacing::time_code_seconds(&chapter.time_code)?,
+                name: chapter.title.trim().to_string(),
+                note: chapter.description.clone().unwrap_or_default(),
+            })
+        })
+        .collect();
+    for event in events.iter().filter(|e| e.verified) {
+        // Only a stored Truth Event has the POIs
+        let truth = event.truth_bundle_json.as_ref().and_then(|_| stored_truth(event));
+        let name = truth
+            .as_ref()
+            .and_then(|t| t.pois.first())
+            .map(|poi| poi.name.clone())
+            .unwrap_or_else(|| event.event_type.replace('_', " "));
+        markers.push(Marker {
+            kind: MarkerKind::Event,
+            seconds: event.start_time_seconds.max(0.0),
+            name,
+            note: truth.as_ref().map(fact_note).unwrap_or_default(),
+        });
+    }
+    markers.sort_by(|a, b| a.seconds.total_cmp(&b.seconds));
+    markers
+}
+
+/// What's known about an event's top POI, in a sentence or two
+fn fact_note(truth: &TruthEvent) -> String {
+    let Some(poi) = truth.pois.first() else {
+        return String::new();
+    };
+    let mut note = format!("{} ({}, {:.0} m away)", poi.name, poi.category, poi.distance_m);
+    if let Some(facts) = &poi.facts {
+        if let Some(established) = &facts.established {
+            let _ = write!(note, ". Established {}", established);
+        }
+        if facts.unesco_site == Some(true) {
+            note.push_str(". UNESCO World Heritage Site");
+        }
+        if let Some(summary) = &facts.summary {
+            let extract: String = summary.extract.chars().take(NOTE_EXTRACT_CHARS).collect();
+            let ellipsis = if summary.extract.chars().count() > NOTE_EXTRACT_CHARS { "…" } else { "" };
+            let _ = write!(note, ". {}{}", extract.trim_end(), ellipsis);
+        }
+    }
+    note
+}
+
+/// An FCPXML 1.9 document with `video` as a one-clip project carrying `markers`
+///
+/// Chapters become chapter markers and events standard markers, each one
+/// frame long; markers past the end of the clip are left out.
+pub fn fcpxml(video: &Video, duration_seconds: f64, rate: FrameRate, markers: &[Marker]) -> String {
+    let clip_frames = rate.frames(duration_seconds).max(1);
+    let duration = rate.rational(clip_frames);
+    let frame = rate.rational(1);
+    let tc_format = if rate.drop_frame { "DF" } else { "NDF" };
+    let name = escape_xml(&video.filename);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE fcpxml>\n");
+    xml.push_str("<fcpxml version=\"1.9\">\n  <resources>\n");
+    let _ = write!(xml, "    <format id=\"r1\" frameDuration=\"{}\"", frame);
+    if let (Some(width), Some(height)) = (video.width, video.height) {
+        let _ = write!(xml, " width=\"{}\" height=\"{}\"", width, height);
+    }
+    xml.push_str("/>\n");
+    let _ = writeln!(
+        xml,
+        "    <asset id=\"r2\" name=\"{}\" start=\"0s\" duration=\"{}\" hasVideo=\"1\" hasAudio=\"1\" format=\"r1\">",
+        name, duration
+    );
+    let _ = writeln!(
+        xml,
+        "      <media-rep kind=\"original-media\" src=\"{}\"/>\n    </asset>",
+        escape_xml(&file_url(&video.file_path))
+    );
+    xml.push_str("  </resources>\n  <library>\n    <event name=\"GeoTruth\">\n");
+    let _ = writeln!(xml, "      <project name=\"{}\">", name);
+    let _ = writeln!(
+        xml,
+        "        <sequence format=\"r1\" duration=\"{}\" tcStart=\"0s\" tcFormat=\"{}\">\n          <spine>",
+        duration, tc_format
+    );
+    let _ = writeln!(
+        xml,
+        "            <asset-clip ref=\"r2\" offset=\"0s\" name=\"{}\" start=\"0s\" duration=\"{}\" tcFormat=\"{}\">",
+        name, duration, tc_format
+    );
+    for marker in markers {
+        let start = rate.frames(marker.seconds);
+        if start >= clip_frames {
+            continue;
+        }
+        let (start, value) = (rate.rational(start), escape_xml(&marker.name));
+        match marker.kind {
+            MarkerKind::Chapter => {
+                let _ = write!(
+                    xml,
+                    "              <chapter-marker start=\"{}\" duration=\"{}\" value=\"{}\" posterOffset=\"0s\"",
+                    start, frame, value
+                );
+            }
+            MarkerKind::Event => {
+                let _ = write!(xml, "              <marker start=\"{}\" duration=\"{}\" value=\"{}\"", start, frame, value);
+            }
+        }
+        if !marker.note.is_empty() {
+            let _ = write!(xml, " note=\"{}\"", escape_xml(&marker.note));
+        }
+        xml.push_str("/>\n");
+    }
+    xml.push_str("            </asset-clip>\n          </spine>\n        </sequence>\n      </project>\n");
+    xml.push_str("    </event>\n  </library>\n</fcpxml>\n");
+    xml
+}
+
+/// `markers` in `format`, for the clip `name` that runs `duration_seconds`
+///
+/// Markers past the end of the clip are left out, and line breaks in
+/// names and notes become spaces.
+pub fn marker_file(format: MarkerFormat, name: &str, duration_seconds: f64, rate: FrameRate, markers: &[Marker]) -> String {
+    let clip_frames = rate.frames(duration_seconds).max(1);
+    let timed = markers
+        .iter()
+        .map(|marker| (rate.frames(marker.seconds), marker))
+        .filter(|(frames, _)| *frames < clip_frames);
+
+    let mut text = String::new();
+    match format {
+        MarkerFormat::Edl => {
+            let _ = writeln!(text, "TITLE: {}", one_line(name));
+            let _ = writeln!(text, "FCM: {}\n", if rate.drop_frame { "DROP FRAME" } else { "NON-DROP FRAME" });
+            let (start, end) = (rate.timecode(0), rate.timecode(clip_frames));
+            let _ = writeln!(text, "001  AX       V     C        {} {} {} {}", start, end, start, end);
+            let _ = writeln!(text, "* FROM CLIP NAME: {}", one_line(name));
+            for (frames, marker) in timed {
+                let color = marker.kind.color().to_uppercase();
+                let _ = writeln!(text, "* LOC: {} {:<7} {}", rate.timecode(frames), color, one_line(&marker.name));
+            }
+        }
+        MarkerFormat::PremiereCsv => {
+            text.push_str("Marker Name,Description,In,Out,Duration,Marker Type,Color\n");
+            let duration = rate.timecode(1);
+            for (frames, marker) in timed {
+                let kind = match marker.kind {
+                    MarkerKind::Chapter => "Chapter",
+                    MarkerKind::Event => "Comment",
+                };
+                let fields = [
+                    csv_field(&marker.name),
+                    csv_field(&marker.note),
+                    rate.timecode(frames),
+                    rate.timecode(frames + 1),
+                    duration.clone(),
+                    kind.to_string(),
+                    marker.kind.color().to_string(),
+                ];
+                let _ = writeln!(text, "{}", fields.join(","));
+            }
+        }
+        MarkerFormat::ResolveCsv => {
+            text.push_str("Timecode,Name,Notes,Color,Duration\n");
+            for (frames, marker) in timed {
+                let fields = [
+                    rate.timecode(frames),
+                    csv_field(&marker.name),
+                    csv_field(&marker.note),
+                    marker.kind.color().to_string(),
+                    "1".to_string(),
+                ];
+                let _ = writeln!(text, "{}", fields.join(","));
+            }
+        }
+    }
+    text
+}
+
+/// `text` with line breaks as spaces
+fn one_line(text: &str) -> String {
+    text.split(['\r', '\n']).filter(|part| !part.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+/// `text` as a CSV field, quoted when it holds a comma or a quote
+fn csv_field(text: &str) -> String {
+    let text = one_line(text);
+    if text.contains([',', '"']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// `path` as a `file://` URL, with what URLs can't hold percent-encoded
+fn file_url(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut url = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => url.push(byte as char),
+            _ => {
+                let _ = write!(url, "%{:02X}", byte);
+            }
+        }
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, assert_golden, chapter};
+    use crate::types::{POIFacts, POI};
+    use chrono::Utc;
+
+    fn video() -> Video {
+        Video {
+            id: "v1".to_string(),
+            project_id: "p1".to_string(),
+            filename: "Ride & \"Lake\".mp4".to_string(),
+            duration_seconds: Some(600.0),
+            fps: Some(29.97),
+            width: Some(1920),
+            height: Some(1080),
+            codec: None,
+            file_size_bytes: None,
+            file_path: "/Videos/Ride & \"Lake\".mp4".to_string(),
+            camera_heading_offset_deg: None,
+            audio_path: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn event(seconds: f64, verified: bool) -> Event {
+        let castle = POI {
+            category: "castle".to_string(),
+            facts: Some(POIFacts { established: Some("1160".to_string()), unesco_site: Some(true), ..Default::default() }),
+            ..fixtures::poi("Château de Chillon")
+        };
+        Event { verified, ..fixtures::event(seconds, Some(&fixtures::truth(0, vec![castle]))) }
+    }
+
+    #[test]
+    fn test_ntsc_rates_are_exact_fractions() {
+        let ntsc = FrameRate::from_fps(29.97002997).unwrap();
+        assert_eq!((ntsc.numerator, ntsc.denominator, ntsc.timebase, ntsc.drop_frame), (1001, 30000, 30, true));
+        assert_eq!(ntsc.rational(1), "1001/30000s");
+        // Ten seconds is 299.7 frames; the nearest is 300, a hair past ten seconds
+        assert_eq!(ntsc.frames(10.0), 300);
+        assert_eq!(ntsc.rational(300), "1001/100s");
+        assert_eq!(ntsc.rational(0), "0s");
+
+        let film = FrameRate::from_fps(23.976).unwrap();
+        assert_eq!((film.denominator, film.drop_frame), (24000, false));
+
+        let pal = FrameRate::from_fps(25.0).unwrap();
+        assert_eq!((pal.rational(1), pal.rational(50)), ("1/25s".to_string(), "2s".to_string()));
+        assert!(FrameRate::from_fps(f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_fcpxml_follows_the_dtd_structure() {
+        let rate = FrameRate::from_fps(29.97).unwrap();
+        let markers = markers(
+            &[chapter("00:00", "Leaving <town>"), chapter("05:00", "The lake"), chapter("20:00", "Past the end")],
+            &[event(65.5, true), event(90.0, false)],
+        );
+        assert_eq!(markers.len(), 4);
+        let xml = fcpxml(&video(), 600.0, rate, &markers);
+
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+        let document = roxmltree::Document::parse_with_options(&xml, options).unwrap_or_else(|e| panic!("{}\n{}", e, xml));
+        let root = document.root_element();
+        assert_eq!((root.tag_name().name(), root.attribute("version")), ("fcpxml", Some("1.9")));
+        let children: Vec<&str> = root.children().filter(|n| n.is_element()).map(|n| n.tag_name().name()).collect();
+        assert_eq!(children, ["resources", "library"]);
+
+        // Every reference resolves to a resource
+        let ids: Vec<&str> = root.descendants().filter_map(|n| n.attribute("id")).collect();
+        assert_eq!(ids, ["r1", "r2"]);
+        for node in root.descendants() {
+            for reference in ["ref", "format"].iter().filter_map(|a| node.attribute(*a)) {
+                assert!(ids.contains(&reference), "{} refers to {}", node.tag_name().name(), reference);
+            }
+        }
+
+        let path: Vec<&str> = root
+            .descendants()
+            .find(|n| n.has_tag_name("asset-clip"))
+            .unwrap()
+            .ancestors()
+            .filter(|n| n.is_element())
+            .map(|n| n.tag_name().name())
+            .collect();
+        assert_eq!(path, ["asset-clip", "spine", "sequence", "project", "event", "library", "fcpxml"]);
+        let media = root.descendants().find(|n| n.has_tag_name("media-rep")).unwrap();
+        assert_eq!(media.attribute("src"), Some("file:///Videos/Ride%20%26%20%22Lake%22.mp4"));
+
+        let clip = root.descendants().find(|n| n.has_tag_name("asset-clip")).unwrap();
+        assert_eq!((clip.attribute("duration"), clip.attribute("tcFormat")), (Some("2999997/5000s"), Some("DF")));
+        let marks: Vec<(&str, &str, &str)> = clip
+            .children()
+            .filter(|n| n.is_element())
+            .map(|n| (n.tag_name().name(), n.attribute("start").unwrap(), n.attribute("value").unwrap()))
+            .collect();
+        assert_eq!(
+            marks,
+            [
+                ("chapter-marker", "0s", "Leaving <town>"),
+                ("marker", "1964963/30000s", "Château de Chillon"),
+                ("chapter-marker", "2999997/10000s", "The lake"),
+            ]
+        );
+        let note = clip.children().find(|n| n.has_tag_name("marker")).unwrap().attribute("note").unwrap();
+        assert_eq!(note, "Château de Chillon (castle, 120 m away). Established 1160. UNESCO World Heritage Site");
+    }
+
+    #[test]
+    fn test_drop_frame_timecode_skips_frame_numbers() {
+        let ntsc = FrameRate::from_fps(29.97).unwrap();
+        assert_eq!(ntsc.timecode(1799), "00:00:59;29");
+        assert_eq!(ntsc.timecode(1800), "00:01:00;02");
+        assert_eq!(ntsc.timecode(17982), "00:10:00;00");
+        assert_eq!(ntsc.timecode(107892), "01:00:00;00");
+        assert_eq!(FrameRate::from_fps(59.94).unwrap().timecode(3600), "00:01:00;04");
+        assert_eq!(FrameRate::from_fps(23.976).unwrap().timecode(1440), "00:01:00:00");
+        assert_eq!(FrameRate::from_fps(25.0).unwrap().timecode(90_061), "01:00:02:11");
+    }
+
+    #[test]
+    fn test_marker_files_match_golden_files() {
+        let rate = FrameRate::from_fps(29.97).unwrap();
+        let chapters = [chapter("00:00", "Leaving Nice"), chapter("10:00", "The coast, \"at last\"\nfinally"), chapter("16:00", "Past the end")];
+        let markers = markers(&chapters, &[event(65.5, true), event(90.0, false), event(700.0, true)]);
+        let cases = [
+            (MarkerFormat::Edl, "markers.edl", include_str!("../testdata/markers.edl")),
+            (MarkerFormat::PremiereCsv, "markers_premiere.csv", include_str!("../testdata/markers_premiere.csv")),
+            (MarkerFormat::ResolveCsv, "markers_resolve.csv", include_str!("../testdata/markers_resolve.csv")),
+        ];
+        for (format, name, golden) in cases {
+            let actual = marker_file(format, "Ride.mp4", 900.0, rate, &markers);
+            assert_golden(name, golden, &actual);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Script Export
+//!
+//! Writes a narration's script out for text-to-speech. SSML keeps the
+//! timing: the silence between one line ending and the next starting
+//! becomes `<break>` elements, and the tone becomes a `<prosody>` hint, as
+//! do the lines' delivery hints.
+//! Plain text suits TTS tools without SSML, with each line's time code in
+//! a comment line, and JSON carries everything for other tools.
+
+use serde::{Deserialize, Serialize};
+
+use crate::pacing;
+use crate::types::{NarrationOptions, NarrationTone, ScriptSegment};
+use super::escape_xml;
+
+/// Longest single `<break>`; longer silences are several in a row, since
+/// TTS services cap each one
+const MAX_BREAK_MS: u64 = 10_000;
+
+/// Silences shorter than this are left to the voice's own pacing
+const MIN_BREAK_MS: u64 = 250;
+
+/// Pause for a `pause_before` or `pause_after` delivery hint, taken out of
+/// the silence between the lines so the timing holds
+const PAUSE_MS: u64 = 750;
+
+/// Export format for a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScriptFormat {
+    Ssml,
+    Plain,
+    Json,
+}
+
+impl ScriptFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ScriptFormat::Ssml => "ssml",
+            ScriptFormat::Plain => "txt",
+            ScriptFormat::Json => "json",
+        }
+    }
+}
+
+/// `segments` as a file in `format`; `options` are the narration's own
+pub fn export(segments: &[ScriptSegment], options: &NarrationOptions, format: ScriptFormat) -> Result<String, String> {
+    match format {
+        ScriptFormat::Ssml => Ok(to_ssml(segments, options)),
+        ScriptFormat::Plain => Ok(to_plain(segments)),
+        ScriptFormat::Json => serde_json::to_string_pretty(&serde_json::json!({ "segments": segments }))
+            .map_err(|e| format!("Failed to serialize the script: {}", e)),
+    }
+}
+
+fn to_ssml(segments: &[ScriptSegment], options: &NarrationOptions) -> String {
+    let mut ssml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    ssml.push_str(&format!(
+        "<speak version=\"1.0\" xmlns=\"http://www.w3.org/2001/10/synthesis\" xml:lang=\"{}\">\n",
+        language_tag(&options.language)
+    ));
+    let prosody = prosody(options.tone);
+    if let Some(attributes) = prosody {
+        ssml.push_str(&format!("<prosody {}>\n", attributes));
+    }
+
+    // Hints are left out of the file when the narration is meant to be plain
+    let hints: Vec<Option<&str>> =
+        segments.iter().map(|s| s.delivery.as_deref().filter(|_| options.delivery_hints)).collect();
+    for (i, segment) in segments.iter().enumerate() {
+        ssml.push_str(&format!("<!-- {} -->\n", comment_text(&segment.time_code)));
+        if hints[i] == Some("pause_before") {
+            ssml.push_str(&format!("<break time=\"{}ms\"/>\n", PAUSE_MS));
+        }
+        ssml.push_str(&format!("<p>{}</p>\n", delivered(&escape_xml(segment.narration.trim()), hints[i])));
+        if hints[i] == Some("pause_after") {
+            ssml.push_str(&format!("<break time=\"{}ms\"/>\n", PAUSE_MS));
+        }
+        if let Some(next) = segments.get(i + 1) {
+            let pauses = [hints[i] == Some("pause_after"), hints[i + 1] == Some("pause_before")];
+            let mut silence = silence_ms(segment, next, options.speech_rate_wpm)
+                .saturating_sub(pauses.iter().filter(|p| **p).count() as u64 * PAUSE_MS);
+            while silence >= MIN_BREAK_MS {
+                let length = silence.min(MAX_BREAK_MS);
+                ssml.push_str(&format!("<break time=\"{}ms\"/>\n", length));
+                silence -= length;
+            }
+        }
+    }
+
+    if prosody.is_some() {
+        ssml.push_str("</prosody>\n");
+    }
+    ssml.push_str("</speak>\n");
+    ssml
+}
+
+/// Each line under a `# time code` comment line, separated by blank lines
+fn to_plain(segments: &[ScriptSegment]) -> String {
+    segments
+        .iter()
+        .map(|s| format!("# {}\n{}\n", s.time_code.trim(), s.narration.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Milliseconds from `segment` finishing, as spoken at `wpm`, to `next` starting
+fn silence_ms(segment: &ScriptSegment, next: &ScriptSegment, wpm: u32) -> u64 {
+    let (Some(start), Some(next_start)) = (
+        pacing::time_code_seconds(&segment.time_code),
+        pacing::time_code_seconds(&next.time_code),
+    ) else {
+        return 0;
+    };
+    let spoken = segment
+        .estimated_duration_seconds
+        .unwrap_or_else(|| pacing::spoken_seconds(&segment.narration, wpm));
+    let silence = next_start - start - spoken;
+    // To the tenth of a second; finer than a voice keeps time anyway
+    (silence.max(0.0) * 10.0).round() as u64 * 100
+}
+
+/// `text` marked up to be read as its delivery hint says; pauses are
+/// `<break>`s of their own around the line
+fn delivered(text: &str, hint: Option<&str>) -> String {
+    let prosody = |attributes: &str| format!("<prosody {}>{}</prosody>", attributes, text);
+    match hint {
+        Some("whisper") => prosody("volume=\"x-soft\" rate=\"-10%\""),
+        Some("excited") => prosody("rate=\"+10%\" pitch=\"+15%\""),
+        Some("calm") => prosody("rate=\"-10%\" volume=\"soft\""),
+        Some("serious") => prosody("pitch=\"-10%\""),
+        Some("slow") => prosody("rate=\"slow\""),
+        Some("fast") => prosody("rate=\"fast\""),
+        Some("emphatic") => format!("<emphasis level=\"strong\">{}</emphasis>", text),
+        _ => text.to_string(),
+    }
+}
+
+/// `<prosody>` attributes suiting `tone`, or `None` for the voice's own delivery
+fn prosody(tone: NarrationTone) -> Option<&'static str> {
+    match tone {
+        NarrationTone::Documentary => Some("rate=\"-5%\""),
+        NarrationTone::Casual => None,
+        NarrationTone::Energetic => Some("rate=\"+10%\" pitch=\"+5%\""),
+        NarrationTone::Poetic => Some("rate=\"-10%\" pitch=\"-5%\""),
+    }
+}
+
+/// BCP 47 tag for a narration language like `English` or `Español`
+///
+/// A tag given as the language is used as it is; anything else unknown is
+/// read as English.
+fn language_tag(language: &str) -> String {
+    let language = language.trim();
+    let tag = match language.to_lowercase().as_str() {
+        "english" => "en-US",
+        "español" | "espanol" | "spanish" => "es-ES",
+        "français" | "francais" | "french" => "fr-FR",
+        "deutsch" | "german" => "de-DE",
+        "italiano" | "italian" => "it-IT",
+        "português" | "portugues" | "portuguese" => "pt-PT",
+        "nederlands" | "dutch" => "nl-NL",
+        "日本語" | "japanese" => "ja-JP",
+        _ => {
+            let is_tag = language.split('-').enumerate().all(|(i, part)| {
+                let letters = if i == 0 { 2..=3 } else { 2..=8 };
+                letters.contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+            return if is_tag { language.to_string() } else { "en-US".to_string() };
+        }
+    };
+    tag.to_string()
+}
+
+/// `text` safe to put in an XML comment, which can't contain `--`
+fn comment_text(text: &str) -> String {
+    escape_xml(text.trim()).replace("--", "- -").trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::segment;
+
+    #[test]
+    fn test_ssml_is_well_formed_and_round_trips() {
+        let segments = vec![
+            segment("00:00", "Fish & chips <fresh> at \"Joe's\"\u{1}."),
+            // Two seconds to speak, then 23 seconds of silence
+            segment("00:05", "one two three four five"),
+            segment("00:30", "The end --> or is it?"),
+            segment("--", "An unreadable time code gets no break."),
+        ];
+        let options = NarrationOptions { tone: NarrationTone::Energetic, language: "Español".to_string(), ..Default::default() };
+        let ssml = export(&segments, &options, ScriptFormat::Ssml).unwrap();
+
+        let document = roxmltree::Document::parse(&ssml).unwrap_or_else(|e| panic!("{}\n{}", e, ssml));
+        let speak = document.root_element();
+        assert_eq!(speak.tag_name().name(), "speak");
+        assert_eq!(speak.attribute(("http://www.w3.org/XML/1998/namespace", "lang")), Some("es-ES"));
+
+        let prosody = speak.first_element_child().unwrap();
+        assert_eq!((prosody.tag_name().name(), prosody.attribute("rate")), ("prosody", Some("+10%")));
+
+        let lines: Vec<&str> = prosody.children().filter(|n| n.has_tag_name("p")).filter_map(|n| n.text()).collect();
+        assert_eq!(lines, ["Fish & chips <fresh> at \"Joe's\".", "one two three four five", "The end --> or is it?", "An unreadable time code gets no break."]);
+
+        let breaks: Vec<&str> = prosody.children().filter(|n| n.has_tag_name("break")).filter_map(|n| n.attribute("time")).collect();
+        // Six words take 2.4 s at the default 150 wpm; the long silence comes in pieces
+        assert_eq!(breaks, ["2600ms", "10000ms", "10000ms", "3000ms"]);
+
+        let comments: Vec<&str> = prosody.children().filter(|n| n.is_comment()).filter_map(|n| n.text()).collect();
+        assert_eq!(comments, [" 00:00 ", " 00:05 ", " 00:30 ", " -  "]);
+    }
+
+    #[test]
+    fn test_delivery_hints_become_markup() {
+        let hinted = |time_code: &str, narration: &str, delivery: &str| ScriptSegment {
+            delivery: Some(delivery.to_string()),
+            ..segment(time_code, narration)
+        };
+        let segments = vec![
+            hinted("00:00", "Quiet now.", "whisper"),
+            hinted("00:05", "one two three four five", "pause_after"),
+            hinted("00:30", "There!", "emphatic"),
+        ];
+        let options = NarrationOptions { tone: NarrationTone::Casual, ..Default::default() };
+        let ssml = export(&segments, &options, ScriptFormat::Ssml).unwrap();
+        let document = roxmltree::Document::parse(&ssml).unwrap_or_else(|e| panic!("{}\n{}", e, ssml));
+
+        let paragraphs: Vec<roxmltree::Node> = document.root_element().children().filter(|n| n.has_tag_name("p")).collect();
+        let whisper = paragraphs[0].first_element_child().unwrap();
+        assert_eq!((whisper.tag_name().name(), whisper.attribute("volume"), whisper.text()), ("prosody", Some("x-soft"), Some("Quiet now.")));
+        assert_eq!(paragraphs[1].text(), Some("one two three four five"));
+        let emphasis = paragraphs[2].first_element_child().unwrap();
+        assert_eq!((emphasis.tag_name().name(), emphasis.attribute("level")), ("emphasis", Some("strong")));
+
+        // The pause comes out of the silence that follows, keeping the next line on time
+        let breaks: Vec<&str> = document.root_element().children().filter(|n| n.has_tag_name("break")).filter_map(|n| n.attribute("time")).collect();
+        assert_eq!(breaks, ["4200ms", "750ms", "10000ms", "10000ms", "2250ms"]);
+
+        // Plain narration has no hints in it
+        let plain = NarrationOptions { delivery_hints: false, ..options };
+        let ssml = export(&segments, &plain, ScriptFormat::Ssml).unwrap();
+        assert!(!ssml.contains("<prosody") && !ssml.contains("<emphasis") && !ssml.contains("750ms"));
+    }
+
+    #[test]
+    fn test_plain_and_json_exports() {
+        let segments = vec![segment("00:00", " Hello. "), segment("01:05", "Goodbye.")];
+        let options = NarrationOptions::default();
+
+        let plain = export(&segments, &options, ScriptFormat::Plain).unwrap();
+        assert_eq!(plain, "# 00:00\nHello.\n\n# 01:05\nGoodbye.\n");
+
+        let json: serde_json::Value = serde_json::from_str(&export(&segments, &options, ScriptFormat::Json).unwrap()).unwrap();
+        assert_eq!(json["segments"][1]["narration"], "Goodbye.");
+
+        // Casual narration is left to the voice
+        let casual = NarrationOptions { tone: NarrationTone::Casual, ..Default::default() };
+        let ssml = export(&segments, &casual, ScriptFormat::Ssml).unwrap();
+        assert!(!ssml.contains("<prosody"));
+        assert_eq!(language_tag("en-GB"), "en-GB");
+        assert_eq!(language_tag("Klingon"), "en-US");
+    }
+}
@@ -0,0 +1,299 @@
+//! Subtitle Export
+//!
+//! A narration's script as SRT or WebVTT cues, for captioning tools and
+//! TTS services that read subtitle files. Each line starts at its time
+//! code and runs until the next one starts, or until it would have been
+//! spoken, whichever comes first.
+//!
+//! Lines too long for the screen are split into several cues at sentence
+//! ends, then clause breaks, then words, sharing the line's time by
+//! length. Lengths count characters, so scripts written without spaces
+//! (Chinese, Japanese, Thai) are broken between characters instead.
+
+use serde::{Deserialize, Serialize};
+
+use crate::pacing;
+use crate::types::ScriptSegment;
+
+/// Longest line of a cue, as subtitle guidelines usually have it
+const MAX_LINE_CHARS: usize = 42;
+
+/// Lines shown at once
+const MAX_LINES: usize = 2;
+
+/// Characters a viewer reads in a second; a cue stays up at least this
+/// long even when its words are quick to say
+const READING_CHARS_PER_SECOND: f64 = 17.0;
+
+/// Shortest a cue is shown, if there's room before the next
+const MIN_CUE_SECONDS: f64 = 1.0;
+
+/// Where a long line is best broken, most preferred first
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', '…', '。', '！', '？', '؟', '।'];
+const CLAUSE_BREAKS: &[char] = &[',', ';', ':', '、', '，', '；', '：', '،'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SubtitleFormat::Srt => "srt",
+            SubtitleFormat::Vtt => "vtt",
+        }
+    }
+}
+
+/// A piece of text on screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+    /// At most [`MAX_LINES`] lines
+    pub lines: Vec<String>,
+}
+
+/// Cues for `segments`, spoken at `wpm`
+///
+/// Segments sharing a time code are shown as one line of narration.
+/// Segments whose time code can't be read are left out. The last runs for
+/// as long as it takes to speak, within `duration_seconds` if that's known.
+pub fn cues(segments: &[ScriptSegment], wpm: u32, duration_seconds: Option<f64>) -> Vec<Cue> {
+    let mut lines: Vec<(f64, String)> = Vec::new();
+    for segment in segments {
+        let Some(start) = pacing::time_code_seconds(&segment.time_code) else {
+            continue;
+        };
+        let text = segment.narration.split_whitespace().collect::<Vec<_>>().join(" ");
+        if text.is_empty() {
+            continue;
+        }
+        lines.push((start, text));
+    }
+    // Stable, so lines sharing a time code stay in script order wherever they are
+    lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+    lines.dedup_by(|(start, text), (previous, joined)| {
+        let same = (*previous - *start).abs() < f64::EPSILON;
+        if same {
+            joined.push(' ');
+            joined.push_str(text);
+        }
+        same
+    });
+
+    let mut cues = Vec::new();
+    for (i, (start, text)) in lines.iter().enumerate() {
+        let limit = lines.get(i + 1).map(|(next, _)| *next).or(duration_seconds);
+        let shown = pacing::spoken_seconds(text, wpm).max(text.chars().count() as f64 / READING_CHARS_PER_SECOND);
+        let mut end = start + shown.max(MIN_CUE_SECONDS);
+        if let Some(limit) = limit.filter(|limit| *limit > *start) {
+            end = end.min(limit);
+        }
+        split(*start, end, text, &mut cues);
+    }
+    cues
+}
+
+/// `text` shown from `start` to `end`, as one cue or several that fit the screen
+fn split(start: f64, end: f64, text: &str, cues: &mut Vec<Cue>) {
+    let pieces = pieces(text, MAX_LINE_CHARS * MAX_LINES);
+    let total: usize = pieces.iter().map(|p| p.chars().count()).sum();
+    let mut at = start;
+    for piece in pieces {
+        let share = (end - start) * piece.chars().count() as f64 / total.max(1) as f64;
+        cues.push(Cue { start_seconds: at, end_seconds: at + share, lines: wrap(&piece) });
+        at += share;
+    }
+}
+
+/// `text` broken into pieces of at most `limit` characters, at the best
+/// breaks there are
+fn pieces(text: &str, limit: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = text.trim();
+    while rest.chars().count() > limit {
+        let at = break_point(rest, limit);
+        let (piece, remainder) = rest.split_at(at);
+        pieces.push(piece.trim().to_string());
+        rest = remainder.trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces
+}
+
+/// Byte index to break `text` at, within its first `limit` characters:
+/// after the last sentence end, else the last clause break, else the last
+/// space, else right at the limit
+fn break_point(text: &str, limit: usize) -> usize {
+    let head_end = text.char_indices().nth(limit).map_or(text.len(), |(i, _)| i);
+    let head = &text[..head_end];
+    // Not so early that the piece is a word or two
+    let earliest = head_end / 3;
+    let after = |marks: &[char]| {
+        head.char_indices()
+            .rev()
+            .find(|(i, c)| marks.contains(c) && *i >= earliest)
+            .map(|(i, c)| i + c.len_utf8())
+    };
+    after(SENTENCE_ENDS)
+        .or_else(|| after(CLAUSE_BREAKS))
+        .or_else(|| head.rfind(' ').filter(|i| *i > 0))
+        .unwrap_or(head_end)
+}
+
+/// A cue's text as up to two lines of at most [`MAX_LINE_CHARS`], broken
+/// at the space nearest the middle
+fn wrap(text: &str) -> Vec<String> {
+    let length = text.chars().count();
+    if length <= MAX_LINE_CHARS {
+        return vec![text.to_string()];
+    }
+    let middle = length / 2;
+    let space = text
+        .char_indices()
+        .enumerate()
+        .filter(|(_, (_, c))| *c == ' ')
+        .filter(|(n, _)| *n <= MAX_LINE_CHARS && length - n - 1 <= MAX_LINE_CHARS)
+        .min_by_key(|(n, _)| n.abs_diff(middle))
+        .map(|(_, (i, _))| i);
+    match space {
+        Some(i) => vec![text[..i].to_string(), text[i + 1..].to_string()],
+        None => {
+            let i = text.char_indices().nth(middle).map_or(text.len(), |(i, _)| i);
+            vec![text[..i].to_string(), text[i..].to_string()]
+        }
+    }
+}
+
+/// `cues` as an SRT or WebVTT file, numbered from 1
+pub fn render(cues: &[Cue], format: SubtitleFormat) -> String {
+    let mut text = String::new();
+    if format == SubtitleFormat::Vtt {
+        text.push_str("WEBVTT\n\n");
+    }
+    for (i, cue) in cues.iter().enumerate() {
+        let separator = if format == SubtitleFormat::Srt { ',' } else { '.' };
+        text.push_str(&format!(
+            "{}\n{} --> {}\n",
+            i + 1,
+            timestamp(cue.start_seconds, separator),
+            timestamp(cue.end_seconds, separator)
+        ));
+        for line in &cue.lines {
+            match format {
+                SubtitleFormat::Srt => text.push_str(line),
+                SubtitleFormat::Vtt => text.push_str(&line.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")),
+            }
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// `HH:MM:SS,mmm`, with `.` before the milliseconds for WebVTT
+fn timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    let (hours, minutes, seconds, millis) = (millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000);
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, seconds, separator, millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::segment;
+    use crate::services::Whisper;
+
+    fn reparsed(srt: &str) -> Vec<(i64, i64, String)> {
+        let whisper = Whisper::new(std::env::temp_dir().join("geotruth-no-whisper")).unwrap();
+        whisper.parse_srt(srt).unwrap().into_iter().map(|s| (s.start_ms, s.end_ms, s.text)).collect()
+    }
+
+    #[test]
+    fn test_cues_end_at_the_next_line_or_when_spoken() {
+        let segments = [
+            segment("00:00", "We leave Nice at dawn."),
+            segment("00:02", "The road climbs."),
+            segment("00:02", "Fast."),
+            segment("nope", "Unreadable time code."),
+            segment("00:30", "Ten words to say here at a steady pace of one hundred fifty."),
+        ];
+        let cues = cues(&segments, 150, Some(33.0));
+        let millis = |seconds: f64| (seconds * 1000.0).round() as i64;
+        let times: Vec<(i64, i64)> = cues.iter().map(|c| (millis(c.start_seconds), millis(c.end_seconds))).collect();
+        // The first runs into the second's start; the shared time code is one cue;
+        // the last is cut off by the end of the video
+        assert_eq!(times, [(0, 2000), (2000, 3600), (30000, 33000)]);
+        assert_eq!(cues[1].lines, ["The road climbs. Fast."]);
+
+        let srt = render(&cues, SubtitleFormat::Srt);
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:02,000\nWe leave Nice at dawn.\n\n2\n"), "{}", srt);
+        assert_eq!(
+            reparsed(&srt),
+            [
+                (0, 2000, "We leave Nice at dawn.".to_string()),
+                (2000, 3600, "The road climbs. Fast.".to_string()),
+                (30000, 33000, "Ten words to say here at a steady pace of one hundred fifty.".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shared_time_codes_join_when_apart_in_the_script() {
+        let segments = [
+            segment("00:05", "The harbour opens up."),
+            segment("00:00", "We leave Nice at dawn."),
+            segment("00:05", "Boats everywhere."),
+        ];
+        let cues = cues(&segments, 150, None);
+        let millis = |seconds: f64| (seconds * 1000.0).round() as i64;
+        let times: Vec<(i64, i64)> = cues.iter().map(|c| (millis(c.start_seconds), millis(c.end_seconds))).collect();
+        // One cue at 00:05, which the first stops short of
+        assert_eq!(times, [(0, 2000), (5000, 7400)]);
+        assert_eq!(cues[1].lines, ["The harbour opens up. Boats everywhere."]);
+    }
+
+    #[test]
+    fn test_long_lines_split_at_sentences_and_wrap() {
+        let narration = "The Col de Turini sits at 1,607 metres, and every January the Monte Carlo Rally races over it at night. \
+                         Locals line the hairpins with torches, waiting for hours in the cold.";
+        let cues = cues(&[segment("01:00", narration)], 150, None);
+        assert_eq!(cues.len(), 3);
+        assert!(cues.iter().all(|c| c.lines.len() <= MAX_LINES && c.lines.iter().all(|l| l.chars().count() <= MAX_LINE_CHARS)));
+        assert!(cues[1].lines.last().unwrap().ends_with("at night."), "{:?}", cues);
+        assert_eq!(cues[0].start_seconds, 60.0);
+        // Back to back, each as long as its share of the text
+        assert!(cues.windows(2).all(|w| (w[0].end_seconds - w[1].start_seconds).abs() < 1e-9));
+        let words = pacing::spoken_seconds(narration, 150);
+        assert!((cues[2].end_seconds - 60.0 - words).abs() < 1e-9);
+
+        let reparsed = reparsed(&render(&cues, SubtitleFormat::Srt));
+        let text: Vec<String> = reparsed.into_iter().map(|(_, _, text)| text).collect();
+        assert_eq!(text.join(" "), narration.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+
+    #[test]
+    fn test_text_without_spaces_breaks_between_characters() {
+        let narration = "私たちは朝早くニースを出発して、海沿いの道を走りました。山の上にある小さな村で昼食をとり、午後には峠を越えてイタリアへ向かいました。夕方、国境の町で美しい夕日を眺めながら旅の一日を振り返りました。";
+        let cues = cues(&[segment("00:10", narration)], 150, None);
+        assert!(cues.len() >= 2);
+        assert!(cues[0].lines.concat().ends_with('。'), "{:?}", cues);
+        assert_eq!(cues.iter().map(|c| c.lines.concat()).collect::<String>(), narration);
+        // Read, not spoken: a run of characters counts as one word
+        let shown = cues.last().unwrap().end_seconds - 10.0;
+        assert!((shown - narration.chars().count() as f64 / READING_CHARS_PER_SECOND).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_vtt_has_a_header_and_escapes_markup() {
+        let cues = cues(&[segment("01:02:03", "Fish & chips <fresh>")], 150, None);
+        let vtt = render(&cues, SubtitleFormat::Vtt);
+        assert!(vtt.starts_with("WEBVTT\n\n1\n01:02:03.000 --> 01:02:04.600\nFish &amp; chips &lt;fresh&gt;\n"), "{}", vtt);
+    }
+}
@@ -0,0 +1,161 @@
+//! YouTube Chapters
+//!
+//! A narration's chapters as the lines YouTube reads from a video's
+//! description, e.g. `00:00 Leaving Nice`. YouTube only shows chapters when
+//! the first starts at 00:00, there are at least three, and each runs for
+//! 10 seconds or more, so chapters too close to the one before are merged
+//! into it. An optional "Places in this video" block lists the landmarks
+//! of verified events with the time each is passed.
+
+use serde::Serialize;
+
+use crate::enrich::stored_truth;
+use crate::pacing;
+use crate::services::database::Event;
+use crate::types::Chapter;
+
+/// Shortest chapter YouTube accepts
+pub const MIN_CHAPTER_SECONDS: f64 = 10.0;
+
+/// Fewest chapters YouTube shows
+pub const MIN_CHAPTERS: usize = 3;
+
+/// A line of the description: a time in the video and what's there
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimedLine {
+    pub start_seconds: f64,
+    pub title: String,
+}
+
+impl TimedLine {
+    /// `00:00 Title`, or `01:02:03 Title` from an hour on
+    pub fn line(&self) -> String {
+        format!("{} {}", pacing::format_time_code(self.start_seconds), self.title)
+    }
+}
+
+/// Chapters ready for YouTube, and the titles merged away to get there
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChapterList {
+    pub chapters: Vec<TimedLine>,
+    /// Chapters that started too soon after the one before, or too close to
+    /// the end of the video, and were folded into the one before
+    pub merged: Vec<String>,
+}
+
+/// `chapters` made to follow YouTube's rules
+///
+/// Chapters without a readable time code or a title are left out, the
+/// first is moved to 00:00, and a chapter under [`MIN_CHAPTER_SECONDS`]
+/// long is merged into the one before; the last one too, when the video's
+/// duration is known. Fails when fewer than [`MIN_CHAPTERS`] are left.
+pub fn chapter_list(chapters: &[Chapter], duration_seconds: Option<f64>) -> Result<ChapterList, String> {
+    let mut timed: Vec<TimedLine> = chapters
+        .iter()
+        .filter(|c| !c.title.trim().is_empty())
+        .filter_map(|c| {
+            let start_seconds = pacing::time_code_seconds(&c.time_code)?.floor();
+            Some(TimedLine { start_seconds, title: c.title.trim().to_string() })
+        })
+        .collect();
+    timed.sort_by(|a, b| a.start_seconds.total_cmp(&b.start_seconds));
+    if let Some(first) = timed.first_mut() {
+        first.start_seconds = 0.0;
+    }
+
+    let mut kept: Vec<TimedLine> = Vec::with_capacity(timed.len());
+    let mut merged = Vec::new();
+    for line in timed {
+        match kept.last() {
+            Some(previous) if line.start_seconds - previous.start_seconds < MIN_CHAPTER_SECONDS => merged.push(line.title),
+            _ => kept.push(line),
+        }
+    }
+    let last_too_short = duration_seconds
+        .zip(kept.last())
+        .is_some_and(|(duration, last)| duration - last.start_seconds < MIN_CHAPTER_SECONDS);
+    if last_too_short && kept.len() > 1 {
+        merged.extend(kept.pop().map(|line| line.title));
+    }
+
+    if kept.len() < MIN_CHAPTERS {
+        return Err(format!(
+            "YouTube needs at least {} chapters of {} seconds or more; this narration has {}",
+            MIN_CHAPTERS, MIN_CHAPTER_SECONDS, kept.len()
+        ));
+    }
+    Ok(ChapterList { chapters: kept, merged })
+}
+
+/// The top POI of each verified event, in the order they're passed; a
+/// place passed again isn't listed twice
+pub fn places(events: &[Event]) -> Vec<TimedLine> {
+    let mut events: Vec<&Event> = events.iter().filter(|e| e.verified && e.truth_bundle_json.is_some()).collect();
+    events.sort_by(|a, b| a.start_time_seconds.total_cmp(&b.start_time_seconds));
+
+    let mut places: Vec<TimedLine> = Vec::new();
+    for event in events {
+        let Some(name) = stored_truth(event).and_then(|t| t.pois.into_iter().next()).map(|poi| poi.name) else {
+            continue;
+        };
+        if places.iter().any(|p| p.title.eq_ignore_ascii_case(&name)) {
+            continue;
+        }
+        places.push(TimedLine { start_seconds: event.start_time_seconds.max(0.0), title: name });
+    }
+    places
+}
+
+/// The text to paste into the description: the chapters, then the places
+/// under a heading when there are any
+pub fn description(chapters: &[TimedLine], places: &[TimedLine]) -> String {
+    let mut text: Vec<String> = chapters.iter().map(TimedLine::line).collect();
+    if !places.is_empty() {
+        text.push(String::new());
+        text.push("Places in this video:".to_string());
+        text.extend(places.iter().map(TimedLine::line));
+    }
+    text.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, chapter};
+
+    fn event(seconds: f64, verified: bool, poi: &str) -> Event {
+        Event { verified, ..fixtures::event(seconds, Some(&fixtures::truth(0, vec![fixtures::poi(poi)]))) }
+    }
+
+    #[test]
+    fn test_chapters_follow_youtube_rules() {
+        let chapters = [
+            chapter("00:04", "Leaving Nice"),
+            chapter("00:09", "Too soon"),
+            chapter("02:30", "The coast road"),
+            chapter("nope", "Unreadable"),
+            chapter("01:05:00", "  "),
+            chapter("01:02:03", "Col de Turini"),
+            chapter("01:10:55", "Almost over"),
+        ];
+        let list = chapter_list(&chapters, Some(4262.0)).unwrap();
+        let lines: Vec<String> = list.chapters.iter().map(TimedLine::line).collect();
+        assert_eq!(lines, ["00:00 Leaving Nice", "02:30 The coast road", "01:02:03 Col de Turini"]);
+        assert_eq!(list.merged, ["Too soon", "Almost over"]);
+    }
+
+    #[test]
+    fn test_too_few_chapters_is_an_error() {
+        let chapters = [chapter("00:00", "Start"), chapter("00:05", "Blink"), chapter("03:00", "End")];
+        assert!(chapter_list(&chapters, None).unwrap_err().contains("has 2"));
+    }
+
+    #[test]
+    fn test_description_lists_verified_places_once() {
+        let chapters = chapter_list(&[chapter("00:00", "A"), chapter("01:00", "B"), chapter("02:00", "C")], None).unwrap();
+        let events = [event(95.5, true, "Lake Geneva"), event(30.0, true, "Chillon Castle"), event(60.0, false, "Rumour Hill"), event(120.0, true, "lake geneva")];
+        let text = description(&chapters.chapters, &places(&events));
+        assert_eq!(text, "00:00 A\n01:00 B\n02:00 C\n\nPlaces in this video:\n00:30 Chillon Castle\n01:35 Lake Geneva\n");
+        assert_eq!(description(&chapters.chapters, &[]), "00:00 A\n01:00 B\n02:00 C\n");
+    }
+}
@@ -0,0 +1,120 @@
+//! Test Fixtures
+//!
+//! Records the tests of several modules build theirs from, the golden
+//! files in `testdata` they're compared with, and scratch directories. Fixture tracks and events
+//! happen on 12 June 2026 from 09:00 UTC, along Lake Geneva; a test that
+//! cares about a value sets it over the fixture's.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::{json, Value};
+
+use crate::services::database::Event;
+use crate::services::gps::GpsPoint;
+use crate::services::GpsTrack;
+use crate::types::{Chapter, ScriptSegment, TruthEvent, POI};
+
+/// `seconds` after fixture time starts
+pub fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 6, 12, 9, 0, 0).unwrap() + Duration::seconds(seconds)
+}
+
+/// A fix `seconds` into the track with nothing known but where it is
+pub fn gps_point(seconds: i64, lat: f64, lon: f64) -> GpsPoint {
+    GpsPoint {
+        timestamp: at(seconds),
+        lat,
+        lon,
+        elevation_m: None,
+        speed_kmh: None,
+        heading_deg: None,
+        accuracy_m: None,
+    }
+}
+
+/// `points` as a video's stored track
+pub fn track(points: Vec<GpsPoint>) -> GpsTrack {
+    GpsTrack::from_points("ride.gpx".to_string(), "stored", points)
+}
+
+/// A POI in view 120 m east of the route, without facts
+pub fn poi(name: &str) -> POI {
+    POI {
+        id: name.to_lowercase(),
+        name: name.to_string(),
+        name_local: None,
+        category: "tourism".to_string(),
+        subcategory: None,
+        lat: 46.525,
+        lon: 6.645,
+        distance_m: 120.0,
+        bearing_deg: 90.0,
+        in_fov: true,
+        confidence: 0.9,
+        facts: None,
+        pinned: false,
+    }
+}
+
+/// A Truth Event `seconds` into the track that passed `pois`
+pub fn truth(seconds: i64, pois: Vec<POI>) -> TruthEvent {
+    serde_json::from_value(json!({ "id": "e", "timestamp": at(seconds), "pois": pois })).unwrap()
+}
+
+/// A verified nearest pass `seconds` into video `v1`, with `truth` stored
+pub fn event(seconds: f64, truth: Option<&TruthEvent>) -> Event {
+    Event {
+        id: format!("e{}", seconds),
+        video_id: "v1".to_string(),
+        event_type: "nearest_pass".to_string(),
+        start_time_seconds: seconds,
+        end_time_seconds: None,
+        lat: Some(46.525),
+        lon: Some(6.645),
+        heading_deg: None,
+        verified: true,
+        verification_mode: None,
+        truth_bundle_json: truth.map(|t| serde_json::to_string(t).unwrap()),
+        created_at: Utc::now(),
+    }
+}
+
+pub fn chapter(time_code: &str, title: &str) -> Chapter {
+    Chapter { time_code: time_code.to_string(), title: title.to_string(), description: None }
+}
+
+pub fn segment(time_code: &str, narration: &str) -> ScriptSegment {
+    ScriptSegment { time_code: time_code.to_string(), narration: narration.to_string(), ..Default::default() }
+}
+
+/// A new, empty directory `geotruth-{prefix}-…` in the system temp directory
+pub fn temp_dir(prefix: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("geotruth-{}-{}", prefix, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    std::path::Path::new(file!()).with_file_name("testdata").join(name)
+}
+
+/// Compare `actual` with a golden file's `expected` content; with
+/// `UPDATE_GOLDEN` set, rewrite the file `name` in `testdata` instead
+pub fn assert_golden(name: &str, expected: &str, actual: &str) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(golden_path(name), actual).unwrap();
+        return;
+    }
+    assert_eq!(actual, expected, "{} differs; rerun with UPDATE_GOLDEN=1 if the change is intended", name);
+}
+
+/// [`assert_golden`] for JSON, compared as values rather than as text
+pub fn assert_golden_json(name: &str, expected: &str, actual: &Value) {
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(golden_path(name), serde_json::to_string_pretty(actual).unwrap() + "\n").unwrap();
+        return;
+    }
+    let expected: Value = serde_json::from_str(expected).unwrap();
+    assert_eq!(actual, &expected, "{} differs; rerun with UPDATE_GOLDEN=1 if the change is intended", name);
+}
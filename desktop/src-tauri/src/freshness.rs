@@ -0,0 +1,286 @@
+//! Narration Freshness
+//!
+//! A narration is written from the Truth Bundle as it was at the time.
+//! Events get verified, rejected or re-synced afterwards, and the lines
+//! citing them may no longer be true. Each narration keeps a fingerprint of
+//! its bundle, one hash per event and per POI, so the lines whose sources
+//! changed can be found by their citations and written again on their own.
+
+use crate::pacing;
+use crate::types::{NarrateResponse, ScriptSegment, TimeWindow, TruthBundle, POI};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Hashes of what a narration was written from
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BundleFingerprint {
+    /// Of all the events, in order
+    pub hash: String,
+    /// Each event's, by id
+    pub events: BTreeMap<String, String>,
+    /// Each POI's, by id, from what it is and where; not how far it was
+    /// from the camera, which differs between events
+    pub pois: BTreeMap<String, String>,
+}
+
+impl BundleFingerprint {
+    pub fn of(bundle: &TruthBundle) -> Self {
+        let mut events = BTreeMap::new();
+        let mut pois = BTreeMap::new();
+        let mut whole = Sha256::new();
+        for event in &bundle.events {
+            let digest = hash(&serde_json::to_value(event).unwrap_or_default());
+            whole.update(event.id.as_bytes());
+            whole.update(digest.as_bytes());
+            events.insert(event.id.clone(), digest);
+            for poi in &event.pois {
+                pois.entry(poi.id.clone()).or_insert_with(|| hash(&identity(poi)));
+            }
+        }
+        Self { hash: format!("{:x}", whole.finalize()), events, pois }
+    }
+}
+
+/// What changed in the bundle since a narration was written, and which of
+/// its lines that affects
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Freshness {
+    /// Nothing in the bundle has changed
+    pub fresh: bool,
+    pub changed_events: Vec<String>,
+    pub removed_events: Vec<String>,
+    /// Events the narration was written without; no line cites them yet
+    pub added_events: Vec<String>,
+    pub changed_pois: Vec<String>,
+    pub removed_pois: Vec<String>,
+    /// Script lines citing a changed or removed event or POI, by index
+    pub affected_segments: Vec<usize>,
+}
+
+/// Compare the bundle `narration` was written from with the current one
+pub fn check(written_from: &BundleFingerprint, current: &BundleFingerprint, narration: &NarrateResponse) -> Freshness {
+    let (changed_events, removed_events) = differences(&written_from.events, &current.events);
+    let (changed_pois, removed_pois) = differences(&written_from.pois, &current.pois);
+    let added_events = current.events.keys().filter(|id| !written_from.events.contains_key(*id)).cloned().collect();
+
+    let stale = |id: &String| {
+        changed_events.contains(id)
+            || removed_events.contains(id)
+            || changed_pois.contains(id)
+            || removed_pois.contains(id)
+    };
+    let affected_segments = script(narration)
+        .iter()
+        .enumerate()
+        .filter(|(_, segment)| segment.source_refs.iter().any(stale))
+        .map(|(index, _)| index)
+        .collect();
+
+    Freshness {
+        fresh: written_from.hash == current.hash,
+        changed_events,
+        removed_events,
+        added_events,
+        changed_pois,
+        removed_pois,
+        affected_segments,
+    }
+}
+
+/// Where the `affected` lines of `narration` are spoken, to narrate again
+///
+/// Each window is just the line's start; a revision widens it to the
+/// chapter, or the line, it falls in.
+pub fn affected_windows(narration: &NarrateResponse, affected: &[usize]) -> Vec<TimeWindow> {
+    let segments = script(narration);
+    affected
+        .iter()
+        .filter_map(|&index| segments.get(index))
+        .filter_map(|segment| pacing::time_code_seconds(&segment.time_code))
+        .map(|seconds| TimeWindow { start_seconds: seconds, end_seconds: seconds })
+        .collect()
+}
+
+fn script(narration: &NarrateResponse) -> &[ScriptSegment] {
+    narration.script.as_ref().map(|script| script.segments.as_slice()).unwrap_or_default()
+}
+
+/// Ids whose hash differs between `before` and `after`, and ids gone from `after`
+fn differences(before: &BTreeMap<String, String>, after: &BTreeMap<String, String>) -> (Vec<String>, Vec<String>) {
+    let mut changed = Vec::new();
+    let mut removed = Vec::new();
+    for (id, hash) in before {
+        match after.get(id) {
+            Some(current) if current != hash => changed.push(id.clone()),
+            Some(_) => {}
+            None => removed.push(id.clone()),
+        }
+    }
+    (changed, removed)
+}
+
+/// What a POI is and where, without where the camera was
+fn identity(poi: &POI) -> serde_json::Value {
+    serde_json::json!({
+        "name": poi.name,
+        "name_local": poi.name_local,
+        "category": poi.category,
+        "subcategory": poi.subcategory,
+        "lat": poi.lat,
+        "lon": poi.lon,
+        "facts": poi.facts,
+    })
+}
+
+/// Hash of `value` with object keys in order, so maps built in any order
+/// hash the same
+fn hash(value: &serde_json::Value) -> String {
+    format!("{:x}", Sha256::digest(canonical(value).to_string().as_bytes()))
+}
+
+fn canonical(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<(&String, &serde_json::Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            serde_json::Value::Object(entries.into_iter().map(|(k, v)| (k.clone(), canonical(v))).collect())
+        }
+        serde_json::Value::Array(items) => serde_json::Value::Array(items.iter().map(canonical).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle_schema::TRUTH_BUNDLE_SCHEMA_VERSION;
+    use crate::types::{EventKind, LocationResult, NarrateScript, TruthEvent};
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    fn poi(id: &str, distance_m: f64) -> POI {
+        POI {
+            id: id.to_string(),
+            name: "Lighthouse".to_string(),
+            name_local: None,
+            category: "lighthouse".to_string(),
+            subcategory: None,
+            lat: 43.69,
+            lon: 7.29,
+            distance_m,
+            bearing_deg: 90.0,
+            in_fov: true,
+            confidence: 0.9,
+            facts: None,
+            pinned: false,
+        }
+    }
+
+    fn event(id: &str, seconds: i64, pois: Vec<POI>) -> TruthEvent {
+        TruthEvent {
+            id: id.to_string(),
+            kind: EventKind::Speech,
+            timestamp: Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap(),
+            local_time: None,
+            time_uncertain: false,
+            duration_seconds: None,
+            location: Some(LocationResult { lat: 43.7, lon: 7.26 }),
+            heading_deg: None,
+            pois,
+            detected_objects: vec![],
+            image_path: None,
+            sun: None,
+            weather: None,
+            context: None,
+        }
+    }
+
+    fn bundle(events: Vec<TruthEvent>) -> TruthBundle {
+        TruthBundle {
+            schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
+            project_id: None,
+            video_id: None,
+            events,
+            verification_mode: "offline".to_string(),
+            confidence: 0.8,
+            generated_at: Utc::now(),
+            track_stats: None,
+            meta: HashMap::new(),
+            route_segments: vec![],
+        }
+    }
+
+    fn narration(lines: &[(&str, &[&str])]) -> NarrateResponse {
+        NarrateResponse {
+            chapters: vec![],
+            script: Some(NarrateScript {
+                segments: lines
+                    .iter()
+                    .map(|(time_code, refs)| ScriptSegment {
+                        time_code: time_code.to_string(),
+                        narration: "…".to_string(),
+                        source_refs: refs.iter().map(|r| r.to_string()).collect(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            }),
+            evidence: HashMap::new(),
+            meta: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_lines_citing_changed_sources_are_affected() {
+        let before = bundle(vec![
+            event("event-0", 0, vec![]),
+            event("event-1", 60, vec![poi("poi-light", 800.0)]),
+            event("event-2", 120, vec![]),
+            event("event-3", 180, vec![poi("poi-light", 200.0)]),
+        ]);
+        let narration = narration(&[
+            ("00:00", &["event-0"]),
+            ("01:00", &["event-1"]),
+            ("02:00", &["event-2"]),
+            ("03:00", &["poi-light"]),
+            ("03:30", &[]),
+        ]);
+        let written_from = BundleFingerprint::of(&before);
+        assert!(check(&written_from, &BundleFingerprint::of(&before), &narration).fresh);
+
+        // event-0 re-synced, event-2 rejected, a new event verified
+        let mut after = before.clone();
+        after.events[0].location = Some(LocationResult { lat: 43.71, lon: 7.26 });
+        after.events.remove(2);
+        after.events.push(event("event-4", 240, vec![]));
+        let freshness = check(&written_from, &BundleFingerprint::of(&after), &narration);
+        assert!(!freshness.fresh);
+        assert_eq!(freshness.changed_events, ["event-0"]);
+        assert_eq!(freshness.removed_events, ["event-2"]);
+        assert_eq!(freshness.added_events, ["event-4"]);
+        // The lighthouse's distance differs between events, but it hasn't changed
+        assert!(freshness.changed_pois.is_empty());
+        assert_eq!(freshness.affected_segments, [0, 2]);
+
+        // Renaming the lighthouse affects the lines citing it, and its events
+        let mut renamed = before.clone();
+        for event in [1, 3] {
+            renamed.events[event].pois[0].name = "Phare du Cap".to_string();
+        }
+        let freshness = check(&written_from, &BundleFingerprint::of(&renamed), &narration);
+        assert_eq!(freshness.changed_pois, ["poi-light"]);
+        assert_eq!(freshness.changed_events, ["event-1", "event-3"]);
+        assert_eq!(freshness.affected_segments, [1, 3]);
+
+        let windows = affected_windows(&narration, &[0, 3]);
+        assert_eq!(windows.iter().map(|w| (w.start_seconds, w.end_seconds)).collect::<Vec<_>>(), [(0.0, 0.0), (180.0, 180.0)]);
+    }
+
+    #[test]
+    fn test_map_order_does_not_change_the_hash() {
+        let a: serde_json::Value = serde_json::from_str(r#"{"b": 1, "a": {"d": [1, 2], "c": null}}"#).unwrap();
+        let b: serde_json::Value = serde_json::from_str(r#"{"a": {"c": null, "d": [1, 2]}, "b": 1}"#).unwrap();
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&serde_json::json!({"a": {"c": null, "d": [2, 1]}, "b": 1})));
+    }
+}
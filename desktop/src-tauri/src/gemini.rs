@@ -1,93 +1,910 @@
 use crate::config;
-use anyhow::{bail, Result};
-use reqwest::Client;
+use crate::llm_cache::{self, LlmCache};
+use crate::llm::{BackendFuture, FinishReason, Generation, ImagePart, LlmBackend, Sampling, TokenUsage};
+use crate::llm_queue::{self, RateLimiter};
+use crate::scenes::{self, SceneDescriptions};
+use crate::secrets;
+use crate::services::data_manager::ConnectivityMode;
+use crate::settings;
+use once_cell::sync::Lazy;
+use reqwest::{Client, Proxy};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, error, info, warn};
 
 const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta/models";
 
+/// The API key goes in this header rather than the `key` query parameter,
+/// which would put it in every URL that ends up in an error or a log
+const API_KEY_HEADER: &str = "x-goog-api-key";
+
+/// Model used for every purpose unless the settings say otherwise
+pub const DEFAULT_MODEL: &str = "gemini-3.0-flash";
+
+/// What a request is for, each with its own configurable model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeminiPurpose {
+    /// Narration scripts and chapters
+    Narration,
+    /// Location lookups when local data has no answer
+    Enrichment,
+    /// Narration that includes scene frames
+    Vision,
+}
+
+/// Model to use for each [`GeminiPurpose`], by API name (e.g. `gemini-3.0-flash`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeminiModels {
+    pub narration_model: String,
+    pub enrichment_model: String,
+    pub vision_model: String,
+}
+
+impl Default for GeminiModels {
+    fn default() -> Self {
+        Self {
+            narration_model: DEFAULT_MODEL.to_string(),
+            enrichment_model: DEFAULT_MODEL.to_string(),
+            vision_model: DEFAULT_MODEL.to_string(),
+        }
+    }
+}
+
+impl GeminiModels {
+    pub fn model_for(&self, purpose: GeminiPurpose) -> &str {
+        match purpose {
+            GeminiPurpose::Narration => &self.narration_model,
+            GeminiPurpose::Enrichment => &self.enrichment_model,
+            GeminiPurpose::Vision => &self.vision_model,
+        }
+    }
+}
+
+/// A model offered by the API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeminiModelInfo {
+    /// Name to put in settings, without the `models/` prefix
+    pub id: String,
+    pub display_name: String,
+    pub description: Option<String>,
+    pub input_token_limit: Option<u64>,
+}
+
+/// Why a Gemini request failed
+///
+/// The `Display` text is meant for the user: it says what went wrong and
+/// what they can do about it, without the raw API response.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum GeminiError {
+    #[error("Gemini API key is missing. Add it in Settings to use AI features.")]
+    MissingKey,
+
+    #[error("Your Gemini API key was rejected. Check that it is correct and has access to the Gemini API.")]
+    InvalidKey,
+
+    #[error("Gemini's rate limit was reached. Wait a minute and try again.")]
+    RateLimited { retry_after: Option<Duration> },
+
+    #[error("Gemini declined to respond because the request was flagged by its safety filters{}", flagged_categories(.categories))]
+    SafetyBlocked { categories: Vec<String> },
+
+    #[error("Gemini is temporarily overloaded. Try again in a few minutes.")]
+    Overloaded,
+
+    #[error("Couldn't reach Gemini ({0}). Check your internet connection.")]
+    Network(String),
+
+    #[error("Gemini didn't respond in time. Check your internet connection, or allow longer timeouts in Settings.")]
+    Timeout,
+
+    #[error("Gemini isn't available in offline mode. Switch to online or hybrid mode in Settings, or use a local model.")]
+    OfflineMode,
+
+    #[error("Gemini returned an unexpected response: {0}")]
+    MalformedResponse(String),
+
+    #[error("Gemini request failed ({status}): {message}")]
+    Api { status: u16, message: String },
+
+    #[error("No local model server is running at {0}. Start Ollama or llama.cpp, or add a Gemini API key in Settings.")]
+    LocalUnavailable(String),
+
+    #[error("The local model server failed ({status}): {message}")]
+    Local { status: u16, message: String },
+
+    #[error("The local model didn't respond within {0} seconds. Check that the server is still running, or allow a longer timeout in Settings.")]
+    LocalTimeout(u64),
+}
+
+impl GeminiError {
+    /// Whether trying the same request again later can succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::Overloaded)
+    }
+
+    /// Whether the model couldn't be reached at all, rather than answering with an error
+    pub fn is_unreachable(&self) -> bool {
+        matches!(
+            self,
+            Self::Network(_) | Self::Timeout | Self::OfflineMode | Self::LocalUnavailable(_) | Self::LocalTimeout(_)
+        )
+    }
+
+    /// Short name for the kind of failure, e.g. `rate_limited`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::MissingKey => "missing_key",
+            Self::InvalidKey => "invalid_key",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::SafetyBlocked { .. } => "safety_blocked",
+            Self::Overloaded => "overloaded",
+            Self::Network(_) => "network",
+            Self::Timeout => "timeout",
+            Self::OfflineMode => "offline_mode",
+            Self::MalformedResponse(_) => "malformed_response",
+            Self::Api { .. } => "api",
+            Self::LocalUnavailable(_) => "local_unavailable",
+            Self::Local { .. } => "local",
+            Self::LocalTimeout(_) => "local_timeout",
+        }
+    }
+}
+
+/// Classify a failed HTTP request
+fn network_error(e: reqwest::Error) -> GeminiError {
+    if e.is_timeout() {
+        GeminiError::Timeout
+    } else {
+        GeminiError::Network(e.without_url().to_string())
+    }
+}
+
+/// Fail right away in offline mode, rather than waiting on the network
+fn ensure_online() -> Result<(), GeminiError> {
+    if settings::get().connectivity_mode == ConnectivityMode::Offline {
+        return Err(GeminiError::OfflineMode);
+    }
+    Ok(())
+}
+
+fn flagged_categories(categories: &[String]) -> String {
+    if categories.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", categories.join(", "))
+    }
+}
+
+/// How often to retry rate-limited or overloaded requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first
+    pub max_attempts: u32,
+    /// Wait before the first retry, doubled after each one
+    pub initial_backoff_ms: u64,
+    /// Longest wait between attempts; a rate limit asking for longer fails right away
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 30_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("Attempts must be at least 1".to_string());
+        }
+        if self.initial_backoff_ms > self.max_backoff_ms {
+            return Err("Initial backoff can't be longer than the maximum backoff".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How readily Gemini blocks a response for a harm category
+///
+/// Serialized as the API's own threshold names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlockThreshold {
+    #[serde(rename = "BLOCK_NONE")]
+    None,
+    #[serde(rename = "BLOCK_ONLY_HIGH")]
+    OnlyHigh,
+    #[serde(rename = "BLOCK_MEDIUM_AND_ABOVE")]
+    MediumAndAbove,
+    #[serde(rename = "BLOCK_LOW_AND_ABOVE")]
+    LowAndAbove,
+}
+
+/// Block threshold per harm category, sent with every request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafetySettings {
+    pub harassment: BlockThreshold,
+    pub hate_speech: BlockThreshold,
+    pub sexually_explicit: BlockThreshold,
+    pub dangerous_content: BlockThreshold,
+}
+
+impl Default for SafetySettings {
+    fn default() -> Self {
+        // The API's defaults, except that travel footage routinely touches on
+        // cliff roads, wildlife or border crossings, which the dangerous
+        // content filter flags at medium probability
+        Self {
+            harassment: BlockThreshold::MediumAndAbove,
+            hate_speech: BlockThreshold::MediumAndAbove,
+            sexually_explicit: BlockThreshold::MediumAndAbove,
+            dangerous_content: BlockThreshold::OnlyHigh,
+        }
+    }
+}
+
+impl SafetySettings {
+    fn to_request(self) -> Vec<SafetySetting> {
+        [
+            ("HARM_CATEGORY_HARASSMENT", self.harassment),
+            ("HARM_CATEGORY_HATE_SPEECH", self.hate_speech),
+            ("HARM_CATEGORY_SEXUALLY_EXPLICIT", self.sexually_explicit),
+            ("HARM_CATEGORY_DANGEROUS_CONTENT", self.dangerous_content),
+        ]
+        .into_iter()
+        .map(|(category, threshold)| SafetySetting { category: category.to_string(), threshold })
+        .collect()
+    }
+}
+
+/// Timeouts and proxy for Gemini requests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkSettings {
+    /// Time allowed to open a connection
+    pub connect_timeout_secs: u64,
+    /// Time allowed for a whole request, long enough for a narration with frames
+    pub request_timeout_secs: u64,
+    /// Proxy for all requests, e.g. `http://proxy.corp:8080`; without one the
+    /// `HTTPS_PROXY`/`HTTP_PROXY`/`NO_PROXY` environment variables apply
+    pub proxy_url: Option<String>,
+}
+
+impl Default for NetworkSettings {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            request_timeout_secs: 120,
+            proxy_url: None,
+        }
+    }
+}
+
+impl NetworkSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.connect_timeout_secs == 0 || self.request_timeout_secs == 0 {
+            return Err("Timeouts must be at least 1 second".to_string());
+        }
+        if let Some(url) = self.proxy_url.as_deref().filter(|u| !u.trim().is_empty()) {
+            if Proxy::all(url.trim()).is_err() {
+                return Err(format!("Invalid proxy URL: {}", url));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// HTTP client configured from `network`
+pub fn http_client(network: &NetworkSettings) -> Result<Client, GeminiError> {
+    let mut builder = Client::builder()
+        .connect_timeout(Duration::from_secs(network.connect_timeout_secs))
+        .timeout(Duration::from_secs(network.request_timeout_secs));
+    if let Some(url) = network.proxy_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        let proxy = Proxy::all(url.trim()).map_err(|e| GeminiError::Network(format!("invalid proxy URL: {}", e)))?;
+        builder = builder.proxy(proxy);
+    }
+    builder.build().map_err(network_error)
+}
+
+/// Client for the current network settings, with the settings it was built for
+static SHARED_CLIENT: Lazy<Mutex<Option<(NetworkSettings, Client)>>> = Lazy::new(|| Mutex::new(None));
+
+/// The HTTP client Gemini requests share, rebuilt when the network settings change
+fn shared_client() -> Result<Client, GeminiError> {
+    let network = settings::get().gemini_network;
+    let mut shared = SHARED_CLIENT.lock().unwrap_or_else(|e| e.into_inner());
+    match shared.as_ref() {
+        Some((built_for, client)) if *built_for == network => Ok(client.clone()),
+        _ => {
+            let client = http_client(&network)?;
+            *shared = Some((network, client.clone()));
+            Ok(client)
+        }
+    }
+}
+
 pub struct GeminiClient {
-    client: Client,
-    api_key: String,
-    model: String,
+    /// Fixed HTTP client, instead of the shared one from the network settings
+    client: Option<Client>,
+    purpose: GeminiPurpose,
+    base_url: String,
+    /// Fixed key, instead of the one from the key store
+    api_key: Option<String>,
+    limiter: Arc<RateLimiter>,
+    cache: Option<Arc<LlmCache>>,
 }
 
 impl GeminiClient {
-    pub fn new() -> Self {
-        let api_key = config::get_gemini_api_key();
+    /// Client using the model configured in the settings for `purpose`
+    ///
+    /// The model and API key are looked up on every request, so changes apply
+    /// right away. Requests go through the app-wide rate limiter.
+    pub fn new_for(purpose: GeminiPurpose) -> Self {
         Self {
-            client: Client::new(),
-            api_key,
-            model: "gemini-3.0-flash".to_string(),
+            client: None,
+            purpose,
+            base_url: GEMINI_API_BASE.to_string(),
+            api_key: None,
+            limiter: llm_queue::shared(),
+            cache: None,
         }
     }
 
-    pub async fn generate_content(&self, prompt: &str) -> Result<String> {
-        self.generate_multimodal(prompt, vec![]).await
+    /// Reuse responses to identical requests from `cache`
+    pub fn with_cache(mut self, cache: Arc<LlmCache>) -> Self {
+        self.cache = Some(cache);
+        self
     }
 
-    pub async fn generate_multimodal(&self, prompt: &str, images_base64: Vec<String>) -> Result<String> {
-        if self.api_key.is_empty() {
-             bail!("Gemini API Key is missing. Please configure it.");
+    pub fn model(&self) -> String {
+        settings::get().gemini_models.model_for(self.purpose).to_string()
+    }
+
+    /// Generate text, retrying rate-limited and overloaded requests with backoff
+    ///
+    /// Models that don't support structured output are asked again without
+    /// the schema. When the client has a cache, a fresh response to the same
+    /// request is returned from it if `allow_cache` is set; new complete
+    /// responses are stored either way.
+    pub async fn generate_with_system(
+        &self,
+        system_instruction: Option<&str>,
+        prompt: &str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> Result<Generation, GeminiError> {
+        self.generate_sampled(Sampling::default(), system_instruction, prompt, images, response_schema, allow_cache)
+            .await
+    }
+
+    /// [`generate_with_system`](Self::generate_with_system), with the
+    /// `sampling` parameters that are set
+    pub async fn generate_sampled(
+        &self,
+        sampling: Sampling,
+        system_instruction: Option<&str>,
+        prompt: &str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> Result<Generation, GeminiError> {
+        let model = self.model();
+        let key = self.cache.as_ref().map(|_| {
+            llm_cache::cache_key(&model, system_instruction, prompt, &images, response_schema.as_ref(), &sampling)
+        });
+
+        if let (Some(cache), Some(key), true) = (&self.cache, &key, allow_cache) {
+            if let Some(cached) = cache.get(key).await {
+                return Ok(Generation::complete(cached));
+            }
         }
 
-        let url = format!("{}/{}:generateContent?key={}", GEMINI_API_BASE, self.model, self.api_key);
-        
-        // Build parts
-        let mut parts = vec![Part {
-            text: Some(prompt.to_string()),
-            inline_data: None,
-        }];
+        // Cached responses are still served offline
+        ensure_online()?;
 
-        // Add images
-        for img in images_base64 {
-            parts.push(Part {
-                text: None,
-                inline_data: Some(InlineData {
-                    mime_type: "image/jpeg".to_string(), // Assuming JPEG for now
-                    data: img,
-                }),
-            });
+        let policy = settings::get().gemini_retry;
+        let (policy, model, images) = (&policy, model.as_str(), &images);
+        let generation = with_schema_fallback(response_schema, |schema| async move {
+            with_retry(policy, || {
+                self.request(model, sampling, system_instruction, prompt, images.clone(), schema.clone())
+            })
+            .await
+        })
+        .await?;
+
+        // A truncated response shouldn't come back for the retry meant to fix it
+        if let (Some(cache), Some(key), FinishReason::Stop) = (&self.cache, &key, &generation.finish_reason) {
+            cache.put(key, model, &generation.text).await;
         }
-        
-        let request = GenerateContentRequest {
-            contents: vec![Content {
-                role: "user".to_string(),
-                parts,
-            }],
+        Ok(generation)
+    }
+
+    /// Describe the image of each `(event_id, image)` pair, several images per request
+    ///
+    /// See [`scenes::describe_frames_batch`]; the engines call that directly
+    /// with their routed backend, so the local model works too.
+    #[allow(dead_code)]
+    pub async fn describe_frames_batch(
+        &self,
+        frames: Vec<(String, ImagePart)>,
+        prompt_template: &str,
+    ) -> Result<SceneDescriptions, GeminiError> {
+        scenes::describe_frames_batch(self, frames, prompt_template).await
+    }
+
+    async fn request(
+        &self,
+        model: &str,
+        sampling: Sampling,
+        system_instruction: Option<&str>,
+        prompt: &str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+    ) -> Result<Generation, GeminiError> {
+        let api_key = self.api_key.clone().unwrap_or_else(config::get_gemini_api_key);
+        if api_key.is_empty() {
+            return Err(GeminiError::MissingKey);
+        }
+
+        let safety = settings::get().gemini_safety;
+        let request = request_body(system_instruction, prompt, images, response_schema, sampling, safety);
+
+        let _permit = self.limiter.acquire().await;
+        if let Some(cache) = &self.cache {
+            cache.record_request();
+        }
+
+        let client = match &self.client {
+            Some(client) => client.clone(),
+            None => shared_client()?,
         };
 
-        debug!("Sending request to Gemini API...");
-        let response = self.client.post(&url)
+        debug!("Sending request to Gemini API ({})...", model);
+        let response = generate_request(&client, &self.base_url, model, &api_key)
             .json(&request)
             .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            error!("Gemini API Error: {}", error_text);
-            bail!("Gemini API request failed: {}", error_text);
-        }
-
-        let result: GenerateContentResponse = response.json().await?;
-        
-        if let Some(candidate) = result.candidates.first() {
-            if let Some(part) = candidate.content.parts.first() {
-                // Return text content from response
-                if let Some(text) = &part.text {
-                     info!("Gemini response received successfully");
-                     return Ok(text.clone());
-                }
+            .await
+            .map_err(network_error)?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .map(Duration::from_secs);
+            let error_text = response
+                .text()
+                .await
+                .map_err(network_error)?;
+            error!("Gemini API Error ({}): {}", status, secrets::redact(&error_text));
+            let err = classify_error(status.as_u16(), retry_after, &error_text);
+            if let GeminiError::RateLimited { retry_after: Some(delay) } = &err {
+                self.limiter.pause_for(*delay);
             }
+            return Err(err);
+        }
+
+        let result: GenerateContentResponse = response
+            .json()
+            .await
+            .map_err(|e| GeminiError::MalformedResponse(e.without_url().to_string()))?;
+        let generation = response_text(result)?;
+
+        if generation.is_truncated() {
+            warn!("Gemini response was cut off at the output token limit");
+        } else {
+            info!("Gemini response received successfully ({})", generation.finish_reason.as_str());
+        }
+        Ok(generation)
+    }
+}
+
+impl LlmBackend for GeminiClient {
+    fn generate_with_system<'a>(
+        &'a self,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        Box::pin(GeminiClient::generate_with_system(
+            self,
+            system_instruction,
+            prompt,
+            images,
+            response_schema,
+            allow_cache,
+        ))
+    }
+
+    fn generate_with_sampling<'a>(
+        &'a self,
+        sampling: Sampling,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        Box::pin(GeminiClient::generate_sampled(
+            self,
+            sampling,
+            system_instruction,
+            prompt,
+            images,
+            response_schema,
+            allow_cache,
+        ))
+    }
+
+    fn model(&self) -> String {
+        GeminiClient::model(self)
+    }
+
+    fn engine(&self) -> &'static str {
+        "gemini"
+    }
+}
+
+/// Models the configured API key can use to generate content
+pub async fn list_models() -> Result<Vec<GeminiModelInfo>, GeminiError> {
+    list_models_with_key(&config::get_gemini_api_key()).await
+}
+
+/// Models `api_key` can use to generate content
+///
+/// Also the cheapest way to check that a key is valid.
+pub async fn list_models_with_key(api_key: &str) -> Result<Vec<GeminiModelInfo>, GeminiError> {
+    if api_key.is_empty() {
+        return Err(GeminiError::MissingKey);
+    }
+    ensure_online()?;
+
+    let client = shared_client()?;
+    let mut models = Vec::new();
+    let mut page_token: Option<String> = None;
+
+    loop {
+        let mut request = client
+            .get(GEMINI_API_BASE)
+            .header(API_KEY_HEADER, api_key)
+            .query(&[("pageSize", "1000")]);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token.as_str())]);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(network_error)?;
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(network_error)?;
+        if !status.is_success() {
+            error!("Gemini model list failed ({}): {}", status, secrets::redact(&body));
+            return Err(classify_error(status.as_u16(), None, &body));
+        }
+
+        let page: ListModelsResponse = serde_json::from_str(&body)
+            .map_err(|e| GeminiError::MalformedResponse(e.to_string()))?;
+        models.extend(generation_models(page.models));
+
+        match page.next_page_token.filter(|t| !t.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => break,
+        }
+    }
+
+    debug!("{} Gemini models support generateContent", models.len());
+    Ok(models)
+}
+
+/// Keep the models that support `generateContent`
+fn generation_models(models: Vec<ApiModel>) -> Vec<GeminiModelInfo> {
+    models
+        .into_iter()
+        .filter(|m| m.supported_generation_methods.iter().any(|g| g == "generateContent"))
+        .map(|m| GeminiModelInfo {
+            id: m.name.trim_start_matches("models/").to_string(),
+            display_name: m.display_name.unwrap_or_else(|| m.name.clone()),
+            description: m.description,
+            input_token_limit: m.input_token_limit,
+        })
+        .collect()
+}
+
+/// Run `attempt` until it succeeds, fails for good, or runs out of attempts
+///
+/// Waits double after each retry, starting from the policy's initial backoff.
+/// A rate limit that says when to come back is honored instead, unless that's
+/// longer than the policy's maximum wait.
+async fn with_retry<T, F, Fut>(policy: &RetryPolicy, mut attempt: F) -> Result<T, GeminiError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, GeminiError>>,
+{
+    let max_backoff = Duration::from_millis(policy.max_backoff_ms);
+    let mut backoff = Duration::from_millis(policy.initial_backoff_ms).min(max_backoff);
+    let mut attempts = 1;
+
+    loop {
+        let err = match attempt().await {
+            Err(e) if e.is_retryable() && attempts < policy.max_attempts => e,
+            result => return result,
+        };
+
+        let delay = match &err {
+            GeminiError::RateLimited { retry_after: Some(after) } => *after,
+            _ => backoff,
+        };
+        if delay > max_backoff {
+            warn!("Gemini asked to wait {:?}, longer than the maximum backoff", delay);
+            return Err(err);
         }
 
-        bail!("No content generated from Gemini API");
+        warn!("Gemini attempt {} of {} failed ({:?}), retrying in {:?}", attempts, policy.max_attempts, err, delay);
+        tokio::time::sleep(delay).await;
+        backoff = (backoff * 2).min(max_backoff);
+        attempts += 1;
+    }
+}
+
+/// Run `generate` with the schema, and once more without it if the model rejects it
+async fn with_schema_fallback<T, F, Fut>(schema: Option<serde_json::Value>, mut generate: F) -> Result<T, GeminiError>
+where
+    F: FnMut(Option<serde_json::Value>) -> Fut,
+    Fut: Future<Output = Result<T, GeminiError>>,
+{
+    let Some(schema) = schema else {
+        return generate(None).await;
+    };
+
+    match generate(Some(schema)).await {
+        Err(e) if rejects_schema(&e) => {
+            warn!("Model doesn't support structured output ({}), retrying without a schema", e);
+            generate(None).await
+        }
+        result => result,
+    }
+}
+
+/// Whether a request failed because the model can't do structured output
+///
+/// e.g. "JSON mode is not enabled for models/gemini-pro-vision"
+fn rejects_schema(error: &GeminiError) -> bool {
+    match error {
+        GeminiError::Api { status: 400, message } => {
+            let message = message.to_lowercase();
+            message.contains("json mode")
+                || message.contains("schema")
+                || message.contains("response_mime_type")
+                || message.contains("responsemimetype")
+        }
+        _ => false,
+    }
+}
+
+/// Map an error response to a [`GeminiError`]
+fn classify_error(status: u16, retry_after: Option<Duration>, body: &str) -> GeminiError {
+    let error = serde_json::from_str::<ApiErrorResponse>(body)
+        .map(|r| r.error)
+        .unwrap_or_default();
+
+    let reason = |key: &str| {
+        error
+            .details
+            .iter()
+            .find_map(|d| d.get(key).and_then(|v| v.as_str()).map(str::to_string))
+    };
+    let key_rejected = reason("reason").is_some_and(|r| r.starts_with("API_KEY"))
+        || error.message.contains("API key");
+
+    match status {
+        401 | 403 => GeminiError::InvalidKey,
+        400 if key_rejected => GeminiError::InvalidKey,
+        429 => GeminiError::RateLimited {
+            retry_after: retry_after.or_else(|| reason("retryDelay").as_deref().and_then(parse_retry_delay)),
+        },
+        500 | 502 | 503 | 504 => GeminiError::Overloaded,
+        _ => GeminiError::Api {
+            status,
+            message: secrets::redact(if error.message.is_empty() {
+                &error.status
+            } else {
+                &error.message
+            }),
+        },
+    }
+}
+
+/// Parse a protobuf duration like `"37s"` or `"1.5s"`
+fn parse_retry_delay(delay: &str) -> Option<Duration> {
+    let seconds: f64 = delay.strip_suffix('s')?.parse().ok()?;
+    (seconds.is_finite() && seconds >= 0.0).then(|| Duration::from_secs_f64(seconds))
+}
+
+/// Extract the generated text and finish reason from an API response
+///
+/// A response cut off at the token limit is returned, possibly with empty
+/// text, so the caller can decide how to retry; blocked ones are errors.
+fn response_text(result: GenerateContentResponse) -> Result<Generation, GeminiError> {
+    if let Some(feedback) = result.prompt_feedback.filter(|f| f.block_reason.is_some()) {
+        return Err(GeminiError::SafetyBlocked {
+            categories: flagged(&feedback.safety_ratings),
+        });
+    }
+
+    let usage = result.usage_metadata.map(|u| TokenUsage {
+        prompt_tokens: u.prompt_token_count,
+        output_tokens: u.candidates_token_count,
+    });
+    let Some(candidate) = result.candidates.into_iter().next() else {
+        return Err(GeminiError::MalformedResponse("no candidates in response".to_string()));
+    };
+
+    let text = candidate
+        .content
+        .and_then(|content| content.parts.into_iter().next())
+        .and_then(|part| part.text);
+
+    match (text, candidate.finish_reason.as_deref()) {
+        // Text stopped by a filter partway through isn't worth using either
+        (_, Some("SAFETY" | "PROHIBITED_CONTENT" | "BLOCKLIST" | "SPII")) => Err(GeminiError::SafetyBlocked {
+            categories: flagged(&candidate.safety_ratings),
+        }),
+        (text, Some("MAX_TOKENS")) => Ok(Generation {
+            text: text.unwrap_or_default(),
+            finish_reason: FinishReason::MaxTokens,
+            usage,
+        }),
+        (Some(text), reason) => Ok(Generation {
+            text,
+            finish_reason: match reason {
+                None | Some("STOP") => FinishReason::Stop,
+                Some(other) => FinishReason::Other(other.to_lowercase()),
+            },
+            usage,
+        }),
+        (None, reason) => Err(GeminiError::MalformedResponse(format!(
+            "no content generated (finish reason: {})",
+            reason.unwrap_or("none")
+        ))),
+    }
+}
+
+/// Readable names of the categories a response was blocked for,
+/// e.g. `HARM_CATEGORY_DANGEROUS_CONTENT` -> `dangerous content`
+fn flagged(ratings: &[SafetyRating]) -> Vec<String> {
+    ratings
+        .iter()
+        .filter(|r| r.blocked || matches!(r.probability.as_str(), "MEDIUM" | "HIGH"))
+        .map(|r| {
+            r.category
+                .trim_start_matches("HARM_CATEGORY_")
+                .to_lowercase()
+                .replace('_', " ")
+        })
+        .collect()
+}
+
+/// `generateContent` call to `model`, authenticated with `api_key`
+fn generate_request(client: &reqwest::Client, base_url: &str, model: &str, api_key: &str) -> reqwest::RequestBuilder {
+    client
+        .post(format!("{}/{}:generateContent", base_url, model))
+        .header(API_KEY_HEADER, api_key)
+}
+
+/// Build a `generateContent` request for a prompt and images
+fn request_body(
+    system_instruction: Option<&str>,
+    prompt: &str,
+    images: Vec<ImagePart>,
+    response_schema: Option<serde_json::Value>,
+    sampling: Sampling,
+    safety: SafetySettings,
+) -> GenerateContentRequest {
+    // Build parts
+    let mut parts = vec![Part {
+        text: Some(prompt.to_string()),
+        inline_data: None,
+    }];
+
+    // Add images, each after its label
+    for image in images {
+        if let Some(label) = image.label {
+            parts.push(Part {
+                text: Some(label),
+                inline_data: None,
+            });
+        }
+        parts.push(Part {
+            text: None,
+            inline_data: Some(InlineData {
+                mime_type: image.mime_type.to_string(),
+                data: image.data,
+            }),
+        });
+    }
+
+    GenerateContentRequest {
+        system_instruction: system_instruction.map(|text| SystemInstruction {
+            parts: vec![Part {
+                text: Some(text.to_string()),
+                inline_data: None,
+            }],
+        }),
+        contents: vec![Content {
+            role: "user".to_string(),
+            parts,
+        }],
+        generation_config: (response_schema.is_some() || !sampling.is_default()).then(|| GenerationConfig {
+            response_mime_type: response_schema.as_ref().map(|_| "application/json".to_string()),
+            response_schema,
+            temperature: sampling.temperature,
+            top_p: sampling.top_p,
+            top_k: sampling.top_k,
+            seed: sampling.seed,
+            max_output_tokens: sampling.max_output_tokens,
+        }),
+        safety_settings: safety.to_request(),
     }
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct GenerateContentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<SystemInstruction>,
     contents: Vec<Content>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GenerationConfig>,
+    safety_settings: Vec<SafetySetting>,
+}
+
+/// Instructions kept apart from the conversation; unlike [`Content`], without a role
+#[derive(Serialize)]
+struct SystemInstruction {
+    parts: Vec<Part>,
+}
+
+#[derive(Serialize)]
+struct SafetySetting {
+    category: String,
+    threshold: BlockThreshold,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerationConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_schema: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -113,11 +930,589 @@ struct InlineData {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GenerateContentResponse {
+    // Absent when the prompt itself was blocked
+    #[serde(default)]
     candidates: Vec<Candidate>,
+    prompt_feedback: Option<PromptFeedback>,
+    usage_metadata: Option<UsageMetadata>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    // Absent when nothing was generated
+    #[serde(default)]
+    candidates_token_count: u64,
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Candidate {
-    content: Content,
+    // Absent when the candidate was blocked by safety filters
+    content: Option<Content>,
+    finish_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+    #[serde(default)]
+    safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Deserialize)]
+struct SafetyRating {
+    category: String,
+    #[serde(default)]
+    probability: String,
+    #[serde(default)]
+    blocked: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListModelsResponse {
+    #[serde(default)]
+    models: Vec<ApiModel>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiModel {
+    name: String,
+    display_name: Option<String>,
+    description: Option<String>,
+    input_token_limit: Option<u64>,
+    #[serde(default)]
+    supported_generation_methods: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ApiErrorResponse {
+    error: ApiErrorBody,
+}
+
+/// `error` object of a Google API error response
+#[derive(Deserialize, Default)]
+struct ApiErrorBody {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
+}
+
+/// Canned-response backend for tests
+#[cfg(test)]
+pub mod mock {
+    use super::GeminiError;
+    use crate::llm::{BackendFuture, FinishReason, Generation, ImagePart, LlmBackend, Sampling};
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    /// Returns queued responses in order and records every prompt it receives
+    #[derive(Default)]
+    pub struct MockGemini {
+        responses: Mutex<VecDeque<Result<Generation, GeminiError>>>,
+        prompts: Mutex<Vec<String>>,
+        system_instructions: Mutex<Vec<Option<String>>>,
+        schemas: Mutex<Vec<Option<serde_json::Value>>>,
+        allow_cache: Mutex<Vec<bool>>,
+        samplings: Mutex<Vec<Sampling>>,
+        images: Mutex<Vec<Vec<ImagePart>>>,
+        model: Option<String>,
+        engine: Option<&'static str>,
+    }
+
+    impl MockGemini {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Queue a successful text response
+        pub fn with_text(self, text: &str) -> Self {
+            self.responses.lock().unwrap().push_back(Ok(Generation::complete(text)));
+            self
+        }
+
+        /// Queue a response as the backend would return it
+        pub fn with_generation(self, generation: Generation) -> Self {
+            self.responses.lock().unwrap().push_back(Ok(generation));
+            self
+        }
+
+        /// Queue a response cut off at the output token limit
+        pub fn with_truncated(self, text: &str) -> Self {
+            self.responses.lock().unwrap().push_back(Ok(Generation {
+                text: text.to_string(),
+                finish_reason: FinishReason::MaxTokens,
+                usage: None,
+            }));
+            self
+        }
+
+        /// Report `model` as the model in use
+        pub fn with_model(mut self, model: &str) -> Self {
+            self.model = Some(model.to_string());
+            self
+        }
+
+        /// Report `engine` as the backend kind, instead of Gemini
+        pub fn with_engine(mut self, engine: &'static str) -> Self {
+            self.engine = Some(engine);
+            self
+        }
+
+        /// Queue a failed request
+        pub fn with_error(self, error: GeminiError) -> Self {
+            self.responses.lock().unwrap().push_back(Err(error));
+            self
+        }
+
+        /// Prompts received so far
+        pub fn prompts(&self) -> Vec<String> {
+            self.prompts.lock().unwrap().clone()
+        }
+
+        /// System instructions received so far, one per request
+        pub fn system_instructions(&self) -> Vec<Option<String>> {
+            self.system_instructions.lock().unwrap().clone()
+        }
+
+        /// Response schemas received so far, one per request
+        pub fn schemas(&self) -> Vec<Option<serde_json::Value>> {
+            self.schemas.lock().unwrap().clone()
+        }
+
+        /// Images received so far, one list per request
+        pub fn images(&self) -> Vec<Vec<ImagePart>> {
+            self.images.lock().unwrap().clone()
+        }
+
+        /// Whether each request allowed a cached response
+        pub fn allow_cache(&self) -> Vec<bool> {
+            self.allow_cache.lock().unwrap().clone()
+        }
+
+        /// Sampling parameters of each request
+        pub fn samplings(&self) -> Vec<Sampling> {
+            self.samplings.lock().unwrap().clone()
+        }
+
+        /// Sampling temperature of each request, `None` for the default
+        pub fn temperatures(&self) -> Vec<Option<f32>> {
+            self.samplings().iter().map(|s| s.temperature).collect()
+        }
+
+        pub fn call_count(&self) -> usize {
+            self.prompts.lock().unwrap().len()
+        }
+    }
+
+    impl LlmBackend for MockGemini {
+        fn generate_with_system<'a>(
+            &'a self,
+            system_instruction: Option<&'a str>,
+            prompt: &'a str,
+            images: Vec<ImagePart>,
+            response_schema: Option<serde_json::Value>,
+            allow_cache: bool,
+        ) -> BackendFuture<'a> {
+            self.generate_with_sampling(Sampling::default(), system_instruction, prompt, images, response_schema, allow_cache)
+        }
+
+        fn generate_with_sampling<'a>(
+            &'a self,
+            sampling: Sampling,
+            system_instruction: Option<&'a str>,
+            prompt: &'a str,
+            images: Vec<ImagePart>,
+            response_schema: Option<serde_json::Value>,
+            allow_cache: bool,
+        ) -> BackendFuture<'a> {
+            self.samplings.lock().unwrap().push(sampling);
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            self.system_instructions.lock().unwrap().push(system_instruction.map(str::to_string));
+            self.schemas.lock().unwrap().push(response_schema);
+            self.allow_cache.lock().unwrap().push(allow_cache);
+            self.images.lock().unwrap().push(images);
+            let next = self.responses.lock().unwrap().pop_front();
+            Box::pin(async move {
+                next.unwrap_or_else(|| Err(GeminiError::MalformedResponse("MockGemini has no queued response".to_string())))
+            })
+        }
+
+        fn model(&self) -> String {
+            self.model.clone().unwrap_or_else(|| "mock-gemini".to_string())
+        }
+
+        fn engine(&self) -> &'static str {
+            self.engine.unwrap_or("gemini")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_text_extracts_first_part() {
+        let json = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"hello"}]}}]}"#;
+        let response: GenerateContentResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response_text(response).unwrap(), Generation::complete("hello"));
+
+        let json = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"hi"}]},"finishReason":"STOP"}],
+            "usageMetadata":{"promptTokenCount":1290,"candidatesTokenCount":12,"totalTokenCount":1302}}"#;
+        let response: GenerateContentResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response_text(response).unwrap().usage, Some(TokenUsage { prompt_tokens: 1290, output_tokens: 12 }));
+    }
+
+    #[test]
+    fn test_api_key_is_sent_in_a_header() {
+        let request = generate_request(&reqwest::Client::new(), GEMINI_API_BASE, "gemini-3.0-flash", "AIzaSyExampleKey1234")
+            .build()
+            .unwrap();
+        assert_eq!(request.url().as_str(), format!("{}/gemini-3.0-flash:generateContent", GEMINI_API_BASE));
+        assert_eq!(request.url().query(), None);
+        assert_eq!(request.headers()[API_KEY_HEADER], "AIzaSyExampleKey1234");
+    }
+
+    #[test]
+    fn test_response_text_finish_reasons() {
+        let truncated = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"{\"chapters\": ["}]},"finishReason":"MAX_TOKENS"}]}"#;
+        let response: GenerateContentResponse = serde_json::from_str(truncated).unwrap();
+        let generation = response_text(response).unwrap();
+        assert!(generation.is_truncated());
+        assert_eq!(generation.usage, None);
+        assert_eq!(generation.text, r#"{"chapters": ["#);
+
+        // Thinking can use up the budget before any text is written
+        let empty = r#"{"candidates":[{"finishReason":"MAX_TOKENS"}]}"#;
+        let response: GenerateContentResponse = serde_json::from_str(empty).unwrap();
+        assert_eq!(response_text(response).unwrap().finish_reason, FinishReason::MaxTokens);
+
+        let recitation = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"hi"}]},"finishReason":"RECITATION"}]}"#;
+        let response: GenerateContentResponse = serde_json::from_str(recitation).unwrap();
+        assert_eq!(response_text(response).unwrap().finish_reason.as_str(), "recitation");
+    }
+
+    #[test]
+    fn test_response_text_safety_blocked() {
+        // Blocked prompts come back without candidates, blocked candidates without content
+        let blocked_prompt = r#"{"promptFeedback":{"blockReason":"SAFETY"}}"#;
+        let response: GenerateContentResponse = serde_json::from_str(blocked_prompt).unwrap();
+        assert_eq!(response_text(response).unwrap_err(), GeminiError::SafetyBlocked { categories: vec![] });
+
+        let blocked_candidate = r#"{"candidates":[{"finishReason":"SAFETY","safetyRatings":[
+            {"category":"HARM_CATEGORY_HARASSMENT","probability":"NEGLIGIBLE"},
+            {"category":"HARM_CATEGORY_DANGEROUS_CONTENT","probability":"HIGH","blocked":true}
+        ]}]}"#;
+        let response: GenerateContentResponse = serde_json::from_str(blocked_candidate).unwrap();
+        let err = response_text(response).unwrap_err();
+        assert_eq!(err, GeminiError::SafetyBlocked { categories: vec!["dangerous content".to_string()] });
+        assert!(err.to_string().ends_with("(dangerous content)"));
+    }
+
+    #[test]
+    fn test_classify_error_responses() {
+        let bad_key = r#"{"error":{"code":400,"message":"API key not valid. Please pass a valid API key.","status":"INVALID_ARGUMENT",
+            "details":[{"@type":"type.googleapis.com/google.rpc.ErrorInfo","reason":"API_KEY_INVALID"}]}}"#;
+        assert_eq!(classify_error(400, None, bad_key), GeminiError::InvalidKey);
+        assert!(!classify_error(400, None, bad_key).to_string().contains("INVALID_ARGUMENT"));
+
+        let quota = r#"{"error":{"code":429,"message":"Resource has been exhausted","status":"RESOURCE_EXHAUSTED",
+            "details":[{"@type":"type.googleapis.com/google.rpc.RetryInfo","retryDelay":"37s"}]}}"#;
+        assert_eq!(
+            classify_error(429, None, quota),
+            GeminiError::RateLimited { retry_after: Some(Duration::from_secs(37)) }
+        );
+        assert_eq!(
+            classify_error(429, Some(Duration::from_secs(5)), quota),
+            GeminiError::RateLimited { retry_after: Some(Duration::from_secs(5)) }
+        );
+
+        assert_eq!(classify_error(503, None, "<html>Service Unavailable</html>"), GeminiError::Overloaded);
+        assert_eq!(
+            classify_error(404, None, r#"{"error":{"code":404,"message":"models/x is not found","status":"NOT_FOUND"}}"#),
+            GeminiError::Api { status: 404, message: "models/x is not found".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_generation_models_filtered() {
+        let json = r#"{"models":[
+            {"name":"models/gemini-3.0-flash","displayName":"Gemini 3.0 Flash","inputTokenLimit":1048576,
+             "supportedGenerationMethods":["generateContent","countTokens"]},
+            {"name":"models/text-embedding-004","displayName":"Text Embedding 004",
+             "supportedGenerationMethods":["embedContent"]}
+        ]}"#;
+        let page: ListModelsResponse = serde_json::from_str(json).unwrap();
+        let models = generation_models(page.models);
+
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "gemini-3.0-flash");
+        assert_eq!(models[0].input_token_limit, Some(1_048_576));
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff() {
+        let policy = RetryPolicy { max_attempts: 3, initial_backoff_ms: 0, max_backoff_ms: 10 };
+
+        // Retryable errors are retried until success
+        let mut calls = 0;
+        let result = with_retry(&policy, || {
+            calls += 1;
+            let outcome = if calls < 3 { Err(GeminiError::Overloaded) } else { Ok("done") };
+            async move { outcome }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(calls, 3);
+
+        // ...up to the attempt limit
+        let mut calls = 0;
+        let result = with_retry(&policy, || {
+            calls += 1;
+            async { Err::<(), _>(GeminiError::RateLimited { retry_after: None }) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+
+        // Others fail right away, as do rate limits asking to wait too long
+        for error in [
+            GeminiError::InvalidKey,
+            GeminiError::RateLimited { retry_after: Some(Duration::from_secs(60)) },
+        ] {
+            let mut calls = 0;
+            let result = with_retry(&policy, || {
+                calls += 1;
+                let error = error.clone();
+                async move { Err::<(), _>(error) }
+            })
+            .await;
+            assert_eq!(result.unwrap_err(), error);
+            assert_eq!(calls, 1);
+        }
+    }
+
+    #[test]
+    fn test_request_body_structured_output() {
+        let schema = serde_json::json!({"type": "OBJECT", "properties": {"title": {"type": "STRING"}}});
+        let body = serde_json::to_value(request_body(
+            None,
+            "Describe",
+            vec![ImagePart { mime_type: "image/png", data: "AAAA".to_string(), label: None }],
+            Some(schema.clone()),
+            Sampling::default(),
+            SafetySettings::default(),
+        )).unwrap();
+        assert_eq!(body["generationConfig"]["responseMimeType"], "application/json");
+        assert_eq!(body["generationConfig"]["responseSchema"], schema);
+        assert!(body["generationConfig"].get("temperature").is_none());
+        assert_eq!(body["contents"][0]["parts"][1]["inlineData"]["mimeType"], "image/png");
+        assert!(body.get("systemInstruction").is_none());
+
+        let body = serde_json::to_value(request_body(
+            Some("You are a narrator."),
+            "Describe",
+            vec![],
+            None,
+            Sampling::default(),
+            SafetySettings::default(),
+        )).unwrap();
+        assert!(body.get("generationConfig").is_none());
+        assert_eq!(body["systemInstruction"], serde_json::json!({"parts": [{"text": "You are a narrator."}]}));
+        assert_eq!(
+            body["safetySettings"][3],
+            serde_json::json!({"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_ONLY_HIGH"})
+        );
+
+        // Sampling alone still needs a generation config, without JSON mode
+        let sampling = Sampling::at_temperature(1.25);
+        let body = serde_json::to_value(request_body(None, "Describe", vec![], None, sampling, SafetySettings::default()))
+            .unwrap();
+        assert_eq!(body["generationConfig"], serde_json::json!({"temperature": 1.25}));
+
+        let sampling = Sampling { top_p: Some(0.5), top_k: Some(40), seed: Some(7), max_output_tokens: Some(2048), ..sampling };
+        let body = serde_json::to_value(request_body(None, "Describe", vec![], None, sampling, SafetySettings::default()))
+            .unwrap();
+        assert_eq!(
+            body["generationConfig"],
+            serde_json::json!({"temperature": 1.25, "topP": 0.5, "topK": 40, "seed": 7, "maxOutputTokens": 2048})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_schema_fallback() {
+        let schema = serde_json::json!({"type": "OBJECT"});
+        let structured = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"{\"title\":\"Monaco\"}"}]}}]}"#;
+        let unsupported = r#"{"error":{"code":400,"message":"JSON mode is not enabled for models/gemini-pro-vision","status":"INVALID_ARGUMENT"}}"#;
+
+        // Schema accepted: one request, with the schema
+        let mut sent = Vec::new();
+        let result = with_schema_fallback(Some(schema.clone()), |s| {
+            sent.push(s);
+            let response: GenerateContentResponse = serde_json::from_str(structured).unwrap();
+            async move { response_text(response) }
+        })
+        .await;
+        assert_eq!(result.unwrap().text, r#"{"title":"Monaco"}"#);
+        assert_eq!(sent, vec![Some(schema.clone())]);
+
+        // Schema rejected: asked again without it
+        let mut sent = Vec::new();
+        let result = with_schema_fallback(Some(schema.clone()), |s| {
+            let outcome = match s {
+                Some(_) => Err(classify_error(400, None, unsupported)),
+                None => Ok("```json\n{}\n```".to_string()),
+            };
+            sent.push(s);
+            async move { outcome }
+        })
+        .await;
+        assert_eq!(result.unwrap(), "```json\n{}\n```");
+        assert_eq!(sent, vec![Some(schema.clone()), None]);
+
+        // Other errors aren't retried without the schema
+        let mut calls = 0;
+        let result = with_schema_fallback(Some(schema), |_| {
+            calls += 1;
+            async { Err::<(), _>(GeminiError::InvalidKey) }
+        })
+        .await;
+        assert_eq!(result.unwrap_err(), GeminiError::InvalidKey);
+        assert_eq!(calls, 1);
+    }
+
+    /// Answers every `generateContent` call after `delay`, recording when each
+    /// arrived and how many were in flight at once
+    async fn mock_server(delay: Duration) -> (String, Arc<std::sync::Mutex<Vec<std::time::Instant>>>, Arc<std::sync::atomic::AtomicUsize>) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1beta/models", listener.local_addr().unwrap());
+        let arrivals = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let (active, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+
+        let (server_arrivals, server_peak) = (arrivals.clone(), peak.clone());
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let (arrivals, active, peak) = (server_arrivals.clone(), active.clone(), server_peak.clone());
+                tokio::spawn(async move {
+                    // Read the headers and body
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    loop {
+                        let n = socket.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                        let text = String::from_utf8_lossy(&request);
+                        if let Some(end) = text.find("\r\n\r\n") {
+                            let length = text[..end]
+                                .lines()
+                                .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                                .unwrap_or(0);
+                            if request.len() >= end + 4 + length {
+                                break;
+                            }
+                        }
+                        if n == 0 {
+                            return;
+                        }
+                    }
+
+                    arrivals.lock().unwrap().push(std::time::Instant::now());
+                    let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now_active, Ordering::SeqCst);
+                    tokio::time::sleep(delay).await;
+                    active.fetch_sub(1, Ordering::SeqCst);
+
+                    let body = r#"{"candidates":[{"content":{"role":"model","parts":[{"text":"ok"}]}}]}"#;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    socket.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        (base_url, arrivals, peak)
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        use crate::llm_queue::RateLimit;
+
+        let (base_url, _, _) = mock_server(Duration::from_secs(5)).await;
+        let network = NetworkSettings { request_timeout_secs: 1, ..Default::default() };
+        let client = GeminiClient {
+            client: Some(http_client(&network).unwrap()),
+            purpose: GeminiPurpose::Enrichment,
+            base_url,
+            api_key: Some("test-key".to_string()),
+            limiter: Arc::new(RateLimiter::new(RateLimit::default(), Duration::from_secs(60))),
+            cache: None,
+        };
+
+        let started = std::time::Instant::now();
+        let err = client.request("gemini-3.0-flash", Sampling::default(), None, "hello", vec![], None).await.unwrap_err();
+        assert_eq!(err, GeminiError::Timeout);
+        assert_eq!(err.kind(), "timeout");
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        let network = NetworkSettings { proxy_url: Some("not a url".to_string()), ..Default::default() };
+        assert!(matches!(http_client(&network), Err(GeminiError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn test_client_respects_rate_limit() {
+        use crate::llm_queue::RateLimit;
+        use std::sync::atomic::Ordering;
+
+        let window = Duration::from_millis(500);
+        let limits = RateLimit { requests_per_minute: 4, max_concurrent: 2 };
+        let (base_url, arrivals, peak) = mock_server(Duration::from_millis(40)).await;
+        let client = Arc::new(GeminiClient {
+            client: Some(Client::new()),
+            purpose: GeminiPurpose::Enrichment,
+            base_url,
+            api_key: Some("test-key".to_string()),
+            limiter: Arc::new(RateLimiter::new(limits, window)),
+            cache: None,
+        });
+
+        let requests: Vec<_> = (0..10)
+            .map(|i| {
+                let client = client.clone();
+                tokio::spawn(async move { client.generate_multimodal(&format!("point {}", i), vec![], None, true).await })
+            })
+            .collect();
+        for request in requests {
+            assert_eq!(request.await.unwrap().unwrap().text, "ok");
+        }
+
+        let arrivals = arrivals.lock().unwrap().clone();
+        assert_eq!(arrivals.len(), 10);
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+        // Requests reach the server a little after they're released; allow for that
+        let span = window - Duration::from_millis(200);
+        for (i, start) in arrivals.iter().enumerate() {
+            let in_span = arrivals[i..].iter().filter(|t| t.duration_since(*start) < span).count();
+            assert!(in_span <= 4, "{} requests within {:?}", in_span, span);
+        }
+    }
 }
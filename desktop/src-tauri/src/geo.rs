@@ -1,21 +1,99 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use pmtiles::async_reader::AsyncPmTilesReader;
-use std::path::Path;
+use pmtiles::{HttpBackend, MmapBackend};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+use crate::services::data_manager::ConnectivityMode;
+use crate::settings;
+
 macro_rules! wts {
     ($rwlock:expr) => {
         $rwlock.write().await
     };
 }
 
+/// What a named map feature is, as far as naming a point goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaceKind {
+    Neighbourhood,
+    Suburb,
+    Village,
+    Town,
+    City,
+    Road,
+    /// Anything else with a name, e.g. a parking aisle or a building
+    Other,
+}
+
+impl PlaceKind {
+    /// The kind of a feature by its OpenMapTiles `class`
+    #[allow(dead_code)]
+    pub fn from_class(class: &str) -> Self {
+        match class {
+            "neighbourhood" | "quarter" => Self::Neighbourhood,
+            "suburb" => Self::Suburb,
+            "village" | "hamlet" => Self::Village,
+            "town" => Self::Town,
+            "city" => Self::City,
+            "motorway" | "trunk" | "primary" | "secondary" | "tertiary" | "minor" => Self::Road,
+            _ => Self::Other,
+        }
+    }
+
+    /// Whether it names the part of town a point is in, rather than the whole city
+    pub fn is_local(self) -> bool {
+        matches!(self, Self::Neighbourhood | Self::Suburb | Self::Village | Self::Town)
+    }
+}
+
+/// A named feature near a point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaceCandidate {
+    pub name: String,
+    pub kind: PlaceKind,
+    /// From the point to the feature, 0 inside it
+    pub distance_m: f64,
+}
+
+/// Where a loaded archive is read from
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RegionLocation {
+    File(PathBuf),
+    Url(String),
+}
+
+/// Reader over either a memory-mapped file or ranged HTTP requests
 #[allow(dead_code)]
+enum TileReader {
+    Local(AsyncPmTilesReader<MmapBackend>),
+    Remote(AsyncPmTilesReader<HttpBackend>),
+}
+
+impl TileReader {
+    fn header(&self) -> &pmtiles::Header {
+        match self {
+            Self::Local(reader) => reader.get_header(),
+            Self::Remote(reader) => reader.get_header(),
+        }
+    }
+}
+
+/// A PMTiles archive loaded into the engine
+struct LoadedRegion {
+    location: RegionLocation,
+    #[allow(dead_code)]
+    reader: TileReader,
+}
+
 #[allow(dead_code)]
 pub struct GeoEngine {
     // We might have multiple regions loaded
-    readers: Arc<RwLock<Vec<AsyncPmTilesReader<pmtiles::MmapBackend>>>>,
+    readers: Arc<RwLock<Vec<LoadedRegion>>>,
 }
 
 impl GeoEngine {
@@ -38,28 +116,206 @@ impl GeoEngine {
         // let backend = MmapBackend::try_from(path).context("Failed to open PMTiles file")?;
         let reader = AsyncPmTilesReader::new_with_path(path).await.context("Failed to load PMTiles from path")?;
         
+        let reader = TileReader::Local(reader);
+        
         // Verify we can read the header/metadata
-        let _header = reader.get_header();
+        let _header = reader.header();
         
-        wts!(self.readers).push(reader);
+        wts!(self.readers).push(LoadedRegion { location: RegionLocation::File(path.to_path_buf()), reader });
         info!("Map region loaded successfully");
         
         Ok(())
     }
 
+    /// Load a remote PMTiles archive, reading tiles on demand via HTTP range requests
+    ///
+    /// Accepts `http(s)://` URLs and `s3://bucket/key` for publicly readable
+    /// S3 objects. Refused in offline mode, and unloaded on going offline.
+    pub async fn load_region_url(&self, url: &str) -> Result<()> {
+        if settings::get().connectivity_mode == ConnectivityMode::Offline {
+            bail!("Remote map data is unavailable in offline mode");
+        }
+
+        let url = remote_archive_url(url)?;
+        let location = RegionLocation::Url(url.clone());
+        if self.readers.read().await.iter().any(|r| r.location == location) {
+            return Ok(());
+        }
+
+        info!("Loading remote map region from {}", url);
+        let reader = AsyncPmTilesReader::new_with_url(reqwest::Client::new(), url.as_str())
+            .await
+            .with_context(|| format!("Failed to load PMTiles from {}", url))?;
+        let reader = TileReader::Remote(reader);
+
+        // Fetching the header proves the server supports range requests
+        let _header = reader.header();
+
+        wts!(self.readers).push(LoadedRegion { location, reader });
+        info!("Remote map region loaded successfully");
+
+        Ok(())
+    }
+
+    /// Drop a previously loaded remote archive, returning whether it was loaded
+    pub async fn unload_region_url(&self, url: &str) -> bool {
+        let Ok(url) = remote_archive_url(url) else {
+            return false;
+        };
+        let mut readers = wts!(self.readers);
+        let before = readers.len();
+        readers.retain(|r| r.location != RegionLocation::Url(url.clone()));
+        readers.len() != before
+    }
+
+    /// Drop every remote archive, returning how many were loaded
+    pub async fn unload_remote_regions(&self) -> usize {
+        let mut readers = wts!(self.readers);
+        let before = readers.len();
+        readers.retain(|r| !matches!(r.location, RegionLocation::Url(_)));
+
+        let unloaded = before - readers.len();
+        if unloaded > 0 {
+            info!("Unloaded {} remote map region(s)", unloaded);
+        }
+        unloaded
+    }
+
+    /// Drop a previously loaded PMTiles file, returning whether it was loaded
+    pub async fn unload_region<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = path.as_ref();
+        let mut readers = wts!(self.readers);
+        let before = readers.len();
+        readers.retain(|r| r.location != RegionLocation::File(path.to_path_buf()));
+        
+        let unloaded = readers.len() != before;
+        if unloaded {
+            info!("Unloaded map region {:?}", path);
+        }
+        unloaded
+    }
+
     /// Find features at a specific coordinate (reverse geocoding)
     /// This is a simplified implementation that would query vector tiles
-    pub async fn reverse_geocode(&self, _lat: f64, _lon: f64) -> Result<Vec<String>> {
+    ///
+    /// Every named feature around the point, nearest first; which of them
+    /// names the point is up to the caller.
+    pub async fn reverse_geocode(&self, _lat: f64, _lon: f64) -> Result<Vec<PlaceCandidate>> {
         // In a real implementation, we would:
         // 1. Calculate the tile ID for the given lat/lon at a high zoom level (e.g., z14)
         // 2. Fetch the tile data from the reader
         // 3. Decode the vector tile (using a crate like `vector-tile`)
         // 4. Check for polygon containment or point proximity
         
-        // For now, we stub this with a placeholder as we set up the infrastructure
-        Ok(vec!["Unknown Location".to_string()])
+        // Until then nothing is known here; callers fall back to other sources
+        Ok(Vec::new())
     }
 }
 
-// Helper macro to write lock
+/// PMTiles v3 headers are a fixed 127 bytes
+const PMTILES_HEADER_LEN: usize = 127;
+
+/// Whether a file starts with the PMTiles magic bytes
+pub fn is_pmtiles(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0u8; 7];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|_| &magic == b"PMTiles")
+        .unwrap_or(false)
+}
+
+/// Bounds (min_lat, min_lon, max_lat, max_lon) stored in a PMTiles v3 header
+pub fn read_pmtiles_bounds(path: &Path) -> Result<(f64, f64, f64, f64)> {
+    use std::io::Read;
+
+    let mut header = [0u8; PMTILES_HEADER_LEN];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut header))
+        .with_context(|| format!("Failed to read PMTiles header from {:?}", path))?;
+
+    if &header[..7] != b"PMTiles" {
+        bail!("Not a PMTiles archive");
+    }
+    if header[7] != 3 {
+        bail!("Unsupported PMTiles version {}", header[7]);
+    }
+
+    // Positions are stored as little-endian i32 in units of 1e-7 degrees
+    let e7 = |offset: usize| {
+        let bytes: [u8; 4] = header[offset..offset + 4].try_into().unwrap_or_default();
+        i32::from_le_bytes(bytes) as f64 / 10_000_000.0
+    };
+    let (min_lon, min_lat, max_lon, max_lat) = (e7(102), e7(106), e7(110), e7(114));
+
+    if min_lat >= max_lat || min_lon >= max_lon {
+        bail!("PMTiles archive has no usable bounds");
+    }
+    Ok((min_lat, min_lon, max_lat, max_lon))
+}
+
+/// Normalize a remote archive location to an HTTP(S) URL
+///
+/// `s3://bucket/key` maps to the bucket's public virtual-hosted endpoint.
+fn remote_archive_url(url: &str) -> Result<String> {
+    if let Some(rest) = url.strip_prefix("s3://") {
+        let (bucket, key) = rest
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .with_context(|| format!("Invalid S3 location: {}", url))?;
+        return Ok(format!("https://{}.s3.amazonaws.com/{}", bucket, key));
+    }
+
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(parsed.to_string()),
+        scheme => bail!("Unsupported PMTiles URL scheme: {}", scheme),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_remote_archive_url() {
+        assert_eq!(
+            remote_archive_url("s3://tiles-bucket/planet/v1.pmtiles").unwrap(),
+            "https://tiles-bucket.s3.amazonaws.com/planet/v1.pmtiles"
+        );
+        assert_eq!(
+            remote_archive_url("https://example.com/region.pmtiles").unwrap(),
+            "https://example.com/region.pmtiles"
+        );
+        assert!(remote_archive_url("s3://bucket-only").is_err());
+        assert!(remote_archive_url("file:///tmp/region.pmtiles").is_err());
+    }
+
+    #[test]
+    fn test_read_pmtiles_bounds() {
+        let dir = fixtures::temp_dir("pmtiles");
+        let path = dir.join("monaco.pmtiles");
+        let mut header = vec![0u8; PMTILES_HEADER_LEN];
+        header[..7].copy_from_slice(b"PMTiles");
+        header[7] = 3;
+        for (offset, degrees) in [(102, 7.40), (106, 43.72), (110, 7.44), (114, 43.76)] {
+            let e7 = (degrees * 10_000_000.0f64).round() as i32;
+            header[offset..offset + 4].copy_from_slice(&e7.to_le_bytes());
+        }
+        std::fs::write(&path, &header).unwrap();
+
+        assert!(is_pmtiles(&path));
+        let (min_lat, min_lon, max_lat, max_lon) = read_pmtiles_bounds(&path).unwrap();
+        assert!((min_lat - 43.72).abs() < 1e-6 && (max_lon - 7.44).abs() < 1e-6);
+        assert!(min_lon < max_lon && min_lat < max_lat);
+
+        header[7] = 2;
+        std::fs::write(&path, &header).unwrap();
+        assert!(read_pmtiles_bounds(&path).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
 
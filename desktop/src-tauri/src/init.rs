@@ -0,0 +1,156 @@
+//! Startup Readiness
+//!
+//! The window opens before the database, engines and sidecars are set up;
+//! they come online in the background, each announced with an `app-ready`
+//! event. A command invoked before what it needs is ready fails with
+//! [`ErrorCode::NotReady`] instead of reaching for state that isn't there.
+
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tauri::ipc::Invoke;
+use tauri::{AppHandle, Emitter, Runtime};
+use tracing::{info, warn};
+
+use crate::error::{CommandError, ErrorCode};
+
+/// A part of the app that's set up after the window opens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    /// FFmpeg, FFprobe and Whisper located
+    Sidecars,
+    /// Database opened and migrated
+    Database,
+    /// Narration, enrichment and video processing
+    Engines,
+    /// Map regions reconciled with the files on disk
+    Regions,
+}
+
+impl Component {
+    pub const ALL: [Component; 4] = [Self::Sidecars, Self::Database, Self::Engines, Self::Regions];
+
+    /// What commands need before they can run; regions load on first use
+    /// when asked for early, so nothing waits on them
+    const REQUIRED: [Component; 3] = [Self::Sidecars, Self::Database, Self::Engines];
+}
+
+/// Commands that need nothing set up in the background
+const EARLY_COMMANDS: &[&str] = &[
+    "get_init_status",
+    "get_version",
+    "get_system_info",
+    "get_settings",
+    "update_settings",
+    "reset_settings",
+    "get_log_path",
+    "get_recent_logs",
+    "open_logs_folder",
+    "set_log_level",
+    "get_last_crash_report",
+    "get_request_history",
+];
+
+/// How far startup has got, as `get_init_status` returns it
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct InitStatus {
+    /// In the order they came online
+    pub ready: Vec<Component>,
+    /// Everything is ready
+    pub complete: bool,
+    /// Why startup stopped, if it did; the app exits once the user has read it
+    pub failed: Option<String>,
+}
+
+impl InitStatus {
+    fn is_complete(&self) -> bool {
+        Component::ALL.iter().all(|c| self.ready.contains(c))
+    }
+
+    /// What `command` is still waiting for; empty once it can run
+    fn waiting_for(&self, command: &str) -> Vec<Component> {
+        if EARLY_COMMANDS.contains(&command) {
+            return Vec::new();
+        }
+        Component::REQUIRED.into_iter().filter(|c| !self.ready.contains(c)).collect()
+    }
+}
+
+/// Payload of `app-ready`
+#[derive(Debug, Clone, Serialize)]
+pub struct AppReady {
+    /// Every component ready so far, the one just set up last
+    pub components: Vec<Component>,
+}
+
+static STATUS: Lazy<RwLock<InitStatus>> = Lazy::new(Default::default);
+
+pub fn status() -> InitStatus {
+    STATUS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Record `component` as ready and tell the frontend
+pub fn mark_ready<R: Runtime>(app: &AppHandle<R>, component: Component) {
+    let components = {
+        let mut status = STATUS.write().unwrap_or_else(|e| e.into_inner());
+        if !status.ready.contains(&component) {
+            status.ready.push(component);
+            status.complete = status.is_complete();
+        }
+        status.ready.clone()
+    };
+    info!("Ready: {:?}", component);
+    if let Err(e) = app.emit("app-ready", AppReady { components }) {
+        warn!("Failed to emit app-ready: {}", e);
+    }
+}
+
+/// Record that startup stopped, so waiting commands say why
+pub fn mark_failed(message: &str) {
+    STATUS.write().unwrap_or_else(|e| e.into_inner()).failed = Some(message.to_string());
+}
+
+/// Wrap the app's command handler so commands invoked before what they
+/// need is ready are refused with `NotReady`
+pub fn gated<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let status = status();
+        let waiting = status.waiting_for(invoke.message.command());
+        if waiting.is_empty() {
+            return handler(invoke);
+        }
+        let message = match &status.failed {
+            Some(failed) => format!("GeoTruth couldn't start: {}", failed),
+            None => "GeoTruth is still starting up; try again in a moment".to_string(),
+        };
+        let error = CommandError::new(ErrorCode::NotReady, message).with_details(serde_json::json!({ "waiting_for": waiting }));
+        invoke.resolver.reject(error);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands_wait_for_what_they_need() {
+        let mut status = InitStatus::default();
+        assert_eq!(status.waiting_for("import_video"), Component::REQUIRED.to_vec());
+        assert!(status.waiting_for("get_settings").is_empty());
+
+        status.ready = vec![Component::Sidecars, Component::Database];
+        assert_eq!(status.waiting_for("import_video"), vec![Component::Engines]);
+
+        // Regions aren't waited for, but startup isn't complete without them
+        status.ready.push(Component::Engines);
+        assert!(status.waiting_for("import_video").is_empty());
+        assert!(!status.is_complete());
+        status.ready.push(Component::Regions);
+        assert!(status.is_complete());
+    }
+}
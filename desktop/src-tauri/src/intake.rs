@@ -0,0 +1,369 @@
+//! File Intake
+//!
+//! Sorting out a mixed set of dropped or opened files: which are videos,
+//! which are GPS tracks, and which track goes with which video. Nothing is
+//! imported here; the plan is shown for the user to confirm, and where the
+//! track of a video can't be told the user is asked rather than guessed for.
+//!
+//! A track is paired with a video by name first (`GH010123.MP4` and
+//! `GH010123.gpx`), then by the time it covers overlapping the video's.
+//! One track may cover several videos, as a ride recorded in clips does.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Bytes read from the start of a file to tell what it is
+pub const SNIFF_BYTES: usize = 512;
+
+/// What a file turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Video,
+    Gps,
+    /// Subtitles, which some drones write their telemetry as
+    Subtitles,
+    Unknown,
+}
+
+/// Tell what a file is from its extension and its first bytes
+///
+/// The contents win over the extension: a `.mp4` that isn't a video is
+/// unknown, and a GPX file saved as `.xml` is still a track.
+pub fn sniff(path: &Path, head: &[u8]) -> FileKind {
+    if is_video_container(head) {
+        return FileKind::Video;
+    }
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    // Text from here on; binary files with a text extension aren't
+    if head.contains(&0) {
+        return FileKind::Unknown;
+    }
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.contains("<gpx") {
+        return FileKind::Gps;
+    }
+    if text.starts_with("$GP") || text.starts_with("$GN") {
+        return FileKind::Gps;
+    }
+    if extension == "srt" || is_subtitles(text) {
+        return FileKind::Subtitles;
+    }
+    // CSV has no signature to check; the parser says if it's a track
+    if extension == "csv" && !text.is_empty() {
+        return FileKind::Gps;
+    }
+    FileKind::Unknown
+}
+
+/// ISO BMFF (MP4, MOV, M4V), Matroska/WebM, AVI and MPEG transport streams
+fn is_video_container(head: &[u8]) -> bool {
+    const TS_PACKET: usize = 188;
+    // Three sync bytes a packet apart, which text won't have by chance
+    let ts_at = |offset: usize, packet: usize| (0..3).all(|i| head.get(offset + i * packet) == Some(&0x47));
+    head.get(4..8) == Some(b"ftyp")
+        || head.starts_with(&[0x1A, 0x45, 0xDF, 0xA3])
+        || (head.starts_with(b"RIFF") && head.get(8..12) == Some(b"AVI "))
+        || ts_at(0, TS_PACKET)
+        // M2TS packets carry a 4-byte timecode first
+        || ts_at(4, TS_PACKET + 4)
+}
+
+/// A numbered cue followed by a `-->` timing line
+fn is_subtitles(text: &str) -> bool {
+    let mut lines = text.lines().map(str::trim);
+    let numbered = lines.next().is_some_and(|line| !line.is_empty() && line.chars().all(|c| c.is_ascii_digit()));
+    numbered && lines.next().is_some_and(|line| line.contains("-->"))
+}
+
+/// A file's name and the time it covers, for pairing
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedFile {
+    pub path: PathBuf,
+    pub start: Option<DateTime<Utc>>,
+    /// `None` with a `start` for a video of unknown length
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// How a track was matched to a video
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PairedBy {
+    /// Same file name but for the extension
+    Name,
+    /// The track covers the time the video was recorded
+    Time,
+}
+
+/// The track for one video, by index into the tracks
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pairing {
+    Paired { track: usize, by: PairedBy },
+    /// More than one track could be the video's, or none can be ruled out;
+    /// the user chooses
+    Ambiguous { candidates: Vec<usize> },
+    Unpaired,
+}
+
+/// The track of each video, in the order of `videos`
+pub fn pair(videos: &[TimedFile], tracks: &[TimedFile]) -> Vec<Pairing> {
+    // A track named after a video is that video's, whatever its times
+    let named: Vec<bool> = tracks
+        .iter()
+        .map(|track| videos.iter().any(|video| same_stem(&video.path, &track.path)))
+        .collect();
+    videos.iter().map(|video| pair_one(video, tracks, &named)).collect()
+}
+
+fn pair_one(video: &TimedFile, tracks: &[TimedFile], named: &[bool]) -> Pairing {
+    let by_name: Vec<usize> = (0..tracks.len()).filter(|&i| same_stem(&video.path, &tracks[i].path)).collect();
+    match by_name.len() {
+        0 => {}
+        1 => return Pairing::Paired { track: by_name[0], by: PairedBy::Name },
+        _ => return Pairing::Ambiguous { candidates: by_name },
+    }
+
+    // Tracks that overlap the video, and tracks that can't be compared with it
+    let mut overlapping = Vec::new();
+    let mut untimed = Vec::new();
+    for (i, track) in tracks.iter().enumerate().filter(|&(i, _)| !named[i]) {
+        match overlaps(video, track) {
+            Some(true) => overlapping.push(i),
+            Some(false) => {}
+            None => untimed.push(i),
+        }
+    }
+    match overlapping.len() {
+        1 => Pairing::Paired { track: overlapping[0], by: PairedBy::Time },
+        0 if untimed.is_empty() => Pairing::Unpaired,
+        0 => Pairing::Ambiguous { candidates: untimed },
+        _ => Pairing::Ambiguous { candidates: overlapping },
+    }
+}
+
+fn same_stem(a: &Path, b: &Path) -> bool {
+    match (a.file_stem(), b.file_stem()) {
+        (Some(a), Some(b)) => a.to_string_lossy().eq_ignore_ascii_case(&b.to_string_lossy()),
+        _ => false,
+    }
+}
+
+/// Whether the times of two files overlap, `None` if either's isn't known
+fn overlaps(video: &TimedFile, track: &TimedFile) -> Option<bool> {
+    let video_start = video.start?;
+    let video_end = video.end.unwrap_or(video_start);
+    let (track_start, track_end) = (track.start?, track.end?);
+    Some(video_start <= track_end && track_start <= video_end)
+}
+
+/// What `intake_files` proposes to import
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IntakePlan {
+    pub project_id: Option<String>,
+    /// A video with a question has no `gps_path` until it's answered
+    pub imports: Vec<PlannedImport>,
+    pub questions: Vec<PairingQuestion>,
+    pub skipped: Vec<SkippedFile>,
+    /// GPS files no video was paired with
+    pub unused_tracks: Vec<String>,
+}
+
+/// One video to import, and the track to import with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedImport {
+    pub video_path: String,
+    pub gps_path: Option<String>,
+    #[serde(default)]
+    pub paired_by: Option<PairedBy>,
+    #[serde(default)]
+    pub duration_seconds: Option<f64>,
+}
+
+/// Which of `candidates` is the track of `video_path`, if any
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PairingQuestion {
+    pub video_path: String,
+    pub candidates: Vec<String>,
+}
+
+/// A file left out of the plan, and why
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// The plan for videos and tracks already classified and timed
+pub fn plan(project_id: Option<String>, videos: &[(TimedFile, Option<f64>)], tracks: &[TimedFile]) -> IntakePlan {
+    let timed: Vec<TimedFile> = videos.iter().map(|(video, _)| video.clone()).collect();
+    let display = |path: &Path| path.to_string_lossy().to_string();
+
+    let mut plan = IntakePlan { project_id, ..Default::default() };
+    let mut used = vec![false; tracks.len()];
+    for ((video, duration_seconds), pairing) in videos.iter().zip(pair(&timed, tracks)) {
+        let (gps_path, paired_by) = match pairing {
+            Pairing::Paired { track, by } => {
+                used[track] = true;
+                (Some(display(&tracks[track].path)), Some(by))
+            }
+            Pairing::Ambiguous { candidates } => {
+                candidates.iter().for_each(|&i| used[i] = true);
+                plan.questions.push(PairingQuestion {
+                    video_path: display(&video.path),
+                    candidates: candidates.iter().map(|&i| display(&tracks[i].path)).collect(),
+                });
+                (None, None)
+            }
+            Pairing::Unpaired => (None, None),
+        };
+        plan.imports.push(PlannedImport {
+            video_path: display(&video.path),
+            gps_path,
+            paired_by,
+            duration_seconds: *duration_seconds,
+        });
+    }
+    plan.unused_tracks = tracks
+        .iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(track, _)| display(&track.path))
+        .collect();
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 6, 12, hour, minute, 0).unwrap()
+    }
+
+    fn file(path: &str, span: Option<((u32, u32), (u32, u32))>) -> TimedFile {
+        TimedFile {
+            path: PathBuf::from(path),
+            start: span.map(|(start, _)| at(start.0, start.1)),
+            end: span.map(|(_, end)| at(end.0, end.1)),
+        }
+    }
+
+    #[test]
+    fn test_sniff_prefers_contents_over_extension() {
+        let mut mp4 = vec![0, 0, 0, 0x20];
+        mp4.extend_from_slice(b"ftypisom");
+        assert_eq!(sniff(Path::new("GH010123.MP4"), &mp4), FileKind::Video);
+        assert_eq!(sniff(Path::new("clip"), &mp4), FileKind::Video);
+        assert_eq!(sniff(Path::new("notes.mp4"), b"just some text"), FileKind::Unknown);
+        assert_eq!(sniff(Path::new("clip.mkv"), &[0x1A, 0x45, 0xDF, 0xA3, 0x01]), FileKind::Video);
+        let mut mts = vec![0xFF; SNIFF_BYTES];
+        for packet in 0..3 {
+            mts[packet * 192 + 4] = 0x47;
+        }
+        assert_eq!(sniff(Path::new("00001.MTS"), &mts), FileKind::Video);
+
+        let gpx = b"<?xml version=\"1.0\"?>\n<gpx version=\"1.1\" creator=\"Garmin\">";
+        assert_eq!(sniff(Path::new("ride.gpx"), gpx), FileKind::Gps);
+        assert_eq!(sniff(Path::new("ride.xml"), gpx), FileKind::Gps);
+        assert_eq!(sniff(Path::new("ride.log"), b"$GPRMC,123519,A,4807.038,N"), FileKind::Gps);
+        assert_eq!(sniff(Path::new("ride.txt"), b"shopping list"), FileKind::Unknown);
+        assert_eq!(sniff(Path::new("ride.csv"), b"time,lat,lon\n"), FileKind::Gps);
+
+        let srt = b"1\n00:00:00,000 --> 00:00:00,033\nFrameCnt: 1 [latitude: 47.1]";
+        assert_eq!(sniff(Path::new("DJI_0001.SRT"), srt), FileKind::Subtitles);
+        assert_eq!(sniff(Path::new("DJI_0001.txt"), srt), FileKind::Subtitles);
+    }
+
+    #[test]
+    fn test_tracks_pair_by_name_before_time() {
+        let videos = [file("/trip/GH010123.MP4", Some(((9, 0), (9, 10))))];
+        let tracks = [
+            file("/trip/ride.gpx", Some(((8, 0), (12, 0)))),
+            file("/gps/gh010123.gpx", None),
+        ];
+        assert_eq!(pair(&videos, &tracks), vec![Pairing::Paired { track: 1, by: PairedBy::Name }]);
+    }
+
+    #[test]
+    fn test_one_track_covering_several_clips_pairs_with_each() {
+        let videos = [
+            file("/trip/GH010123.MP4", Some(((9, 0), (9, 10)))),
+            file("/trip/GH020123.MP4", Some(((10, 30), (10, 45)))),
+            file("/trip/GH030123.MP4", Some(((18, 0), (18, 5)))),
+        ];
+        let tracks = [file("/trip/morning.gpx", Some(((8, 0), (12, 0))))];
+        assert_eq!(
+            pair(&videos, &tracks),
+            vec![
+                Pairing::Paired { track: 0, by: PairedBy::Time },
+                Pairing::Paired { track: 0, by: PairedBy::Time },
+                Pairing::Unpaired,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_pairings_are_questions() {
+        // Two overlapping tracks, e.g. a phone's and a bike computer's
+        let videos = [file("/trip/clip.mp4", Some(((9, 0), (9, 10))))];
+        let tracks = [
+            file("/trip/phone.gpx", Some(((8, 0), (10, 0)))),
+            file("/trip/garmin.gpx", Some(((8, 30), (9, 30)))),
+        ];
+        assert_eq!(pair(&videos, &tracks), vec![Pairing::Ambiguous { candidates: vec![0, 1] }]);
+
+        // Two tracks with the video's name
+        let tracks = [file("/trip/clip.gpx", None), file("/trip/clip.csv", None)];
+        assert_eq!(pair(&videos, &tracks), vec![Pairing::Ambiguous { candidates: vec![0, 1] }]);
+
+        // Times that can't be compared don't rule a track out
+        let videos = [file("/trip/clip.mp4", None)];
+        let tracks = [file("/trip/ride.gpx", Some(((8, 0), (10, 0))))];
+        assert_eq!(pair(&videos, &tracks), vec![Pairing::Ambiguous { candidates: vec![0] }]);
+    }
+
+    #[test]
+    fn test_video_of_unknown_length_pairs_by_its_start() {
+        let video = TimedFile { end: None, ..file("/trip/clip.mp4", Some(((9, 0), (9, 0)))) };
+        let tracks = [
+            file("/trip/before.gpx", Some(((7, 0), (8, 0)))),
+            file("/trip/during.gpx", Some(((8, 30), (9, 30)))),
+        ];
+        assert_eq!(pair(&[video], &tracks), vec![Pairing::Paired { track: 1, by: PairedBy::Time }]);
+    }
+
+    #[test]
+    fn test_plan_lists_questions_and_unused_tracks() {
+        let videos = [
+            (file("/trip/a.mp4", Some(((9, 0), (9, 10)))), Some(600.0)),
+            (file("/trip/b.mp4", None), None),
+        ];
+        let tracks = [
+            file("/trip/a.gpx", None),
+            file("/trip/evening.gpx", Some(((19, 0), (20, 0)))),
+        ];
+        let plan = plan(Some("p1".to_string()), &videos, &tracks);
+
+        assert_eq!(plan.imports.len(), 2);
+        assert_eq!(plan.imports[0].gps_path.as_deref(), Some("/trip/a.gpx"));
+        assert_eq!(plan.imports[0].paired_by, Some(PairedBy::Name));
+        assert_eq!(plan.imports[0].duration_seconds, Some(600.0));
+        // b's time is unknown, so either track could be its own
+        assert_eq!(plan.imports[1].gps_path, None);
+        assert_eq!(
+            plan.questions,
+            vec![PairingQuestion {
+                video_path: "/trip/b.mp4".to_string(),
+                candidates: vec!["/trip/evening.gpx".to_string()],
+            }]
+        );
+        assert!(plan.unused_tracks.is_empty());
+    }
+}
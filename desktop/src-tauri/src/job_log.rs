@@ -0,0 +1,152 @@
+//! Job Logs
+//!
+//! What each job logged, kept apart from the app's log, so a failed job
+//! shows why without digging through the log files. Jobs run inside a
+//! `job` span carrying their id ([`span`]); [`JobLogLayer`] picks out the
+//! events logged within one, along with the processing stage they came
+//! from, and keeps the last [`MAX_LINES`] of each job. Lines are redacted
+//! like the rest of the log.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::secrets;
+
+/// Lines kept of each job
+pub const MAX_LINES: usize = 500;
+
+/// Longest message kept whole; a sidecar's stderr can run long
+const MAX_MESSAGE_CHARS: usize = 8 * 1024;
+
+/// A line of a job's log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobLogLine {
+    /// `ERROR`, `WARN`, `INFO`, `DEBUG` or `TRACE`
+    pub level: String,
+    pub timestamp: DateTime<Utc>,
+    /// The processing stage it was logged in, e.g. `audio_extract`
+    pub stage: Option<String>,
+    pub message: String,
+}
+
+/// This run's job logs, by job id
+static LOGS: Lazy<DashMap<String, VecDeque<JobLogLine>>> = Lazy::new(DashMap::new);
+
+/// The span a job runs in, so what it logs goes to its log
+pub fn span(job_id: &str) -> tracing::Span {
+    tracing::info_span!("job", job_id = %job_id)
+}
+
+/// What a job logged this run, oldest first
+pub fn lines(job_id: &str) -> Option<Vec<JobLogLine>> {
+    LOGS.get(job_id).map(|lines| lines.iter().cloned().collect())
+}
+
+/// Stop keeping a job's log, once it's kept with the job's record
+pub fn forget(job_id: &str) {
+    LOGS.remove(job_id);
+}
+
+fn push(job_id: &str, line: JobLogLine) {
+    let mut lines = LOGS.entry(job_id.to_string()).or_default();
+    if lines.len() == MAX_LINES {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// Sends events logged within a job's span to its log
+pub struct JobLogLayer;
+
+/// The fields of a span that say which job and stage it's part of
+#[derive(Default)]
+struct SpanFields {
+    job_id: Option<String>,
+    stage: Option<String>,
+}
+
+impl Visit for SpanFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "job_id" => self.job_id = Some(value.to_string()),
+            "stage" => self.stage = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if matches!(field.name(), "job_id" | "stage") {
+            self.record_str(field, &format!("{:?}", value));
+        }
+    }
+}
+
+/// An event's message, followed by its other fields as `name=value`
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.0);
+            let _ = write!(self.0, "{:?}{}", value, fields);
+        } else {
+            let _ = write!(self.0, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+impl<S> Layer<S> for JobLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = SpanFields::default();
+        attrs.record(&mut fields);
+        if fields.job_id.is_some() || fields.stage.is_some() {
+            if let Some(span) = ctx.span(id) {
+                span.extensions_mut().insert(fields);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(scope) = ctx.event_scope(event) else {
+            return;
+        };
+        // Innermost first, so a nested stage wins
+        let (mut job_id, mut stage) = (None, None);
+        for span in scope {
+            if let Some(fields) = span.extensions().get::<SpanFields>() {
+                job_id = job_id.or_else(|| fields.job_id.clone());
+                stage = stage.or_else(|| fields.stage.clone());
+            }
+        }
+        let Some(job_id) = job_id else {
+            return;
+        };
+
+        let mut message = Message::default();
+        event.record(&mut message);
+        let mut message = secrets::redact(&message.0);
+        if let Some((cut, _)) = message.char_indices().nth(MAX_MESSAGE_CHARS) {
+            message.truncate(cut);
+            message.push('…');
+        }
+        push(&job_id, JobLogLine {
+            level: event.metadata().level().to_string(),
+            timestamp: Utc::now(),
+            stage,
+            message,
+        });
+    }
+}
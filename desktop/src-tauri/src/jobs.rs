@@ -0,0 +1,549 @@
+//! Background Jobs
+//!
+//! Work that can take minutes runs as a job: it gets a generated id, its
+//! record is kept in `AppState::active_jobs` and reported with `job-updated`
+//! events, and it can be cancelled. Most jobs run as a task of their own,
+//! and cancelling aborts it, which drops whatever it was waiting on,
+//! in-flight HTTP requests included. Work a command runs itself, like a map
+//! download, is tracked with [`run`] and stopped by a hook it supplies.
+//!
+//! Whoever takes a job's handle out of `AppState::job_tasks` sets its final
+//! status: the job itself when it finishes, or [`cancel`]. Finished jobs
+//! are kept in the database, the last [`KEPT_JOBS`] of them, so they can
+//! still be looked at after a restart.
+//!
+//! Each change of a job is numbered, and the last [`KEPT_EVENTS`] of them
+//! are kept in `AppState::job_events`, so a view that wasn't listening can
+//! catch up on what it missed with [`events_since`].
+//!
+//! Jobs run in a [`job_log::span`], so what they log is kept with them and
+//! stored along with their record once they finish.
+
+use std::future::Future;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::{debug, info, warn, Instrument};
+use uuid::Uuid;
+
+use crate::error::CommandError;
+use crate::job_log::{self, JobLogLine};
+use crate::services::LocalDatabase;
+use crate::state::{AppState, JobStatus};
+
+/// Finished jobs kept in the database
+pub const KEPT_JOBS: usize = 100;
+
+/// Changes kept of each of this run's jobs
+pub const KEPT_EVENTS: usize = 200;
+
+/// A job as it's listed, and as `job-updated` events report it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job_id: String,
+    /// What the job does: `processing`, `narration`, `download` or `region-processing`
+    pub kind: String,
+    pub status: JobStatus,
+    /// From 0 to 1, as far as the job got
+    pub progress: f32,
+    /// What the job is doing, or was doing when it stopped
+    pub message: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Counts the job's changes, from 1; each `job-updated` event has the next
+    #[serde(default)]
+    pub seq: u64,
+}
+
+impl JobRecord {
+    fn new(job_id: &str, kind: &str) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            kind: kind.to_string(),
+            status: JobStatus::Pending,
+            progress: 0.0,
+            message: None,
+            started_at: Utc::now(),
+            finished_at: None,
+            seq: 0,
+        }
+    }
+
+    fn update(&mut self, status: JobStatus, message: Option<String>) {
+        match status {
+            JobStatus::Processing { progress } => self.progress = progress,
+            JobStatus::Completed => self.progress = 1.0,
+            _ => {}
+        }
+        if status.is_finished() {
+            self.finished_at = Some(Utc::now());
+        }
+        self.status = status;
+        if message.is_some() {
+            self.message = message;
+        }
+        self.seq += 1;
+    }
+}
+
+/// Payload of the `job-status` event, which `job-updated` replaces
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobStatusEvent {
+    pub job_id: String,
+    /// What the job does, e.g. `narration`
+    pub kind: String,
+    pub status: JobStatus,
+    /// What the job is doing right now
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<&JobRecord> for JobStatusEvent {
+    fn from(job: &JobRecord) -> Self {
+        let running = matches!(job.status, JobStatus::Processing { .. });
+        Self {
+            job_id: job.job_id.clone(),
+            kind: job.kind.clone(),
+            status: job.status.clone(),
+            message: job.message.clone().filter(|_| running),
+        }
+    }
+}
+
+/// Where a job's changes are sent
+pub type JobEmitter = Arc<dyn Fn(JobRecord) + Send + Sync>;
+
+/// Asks work tracked with [`run`] to stop
+pub type StopHook = Box<dyn Fn() + Send + Sync>;
+
+/// How a running job is stopped
+pub enum JobHandle {
+    /// Aborted, dropping whatever it was waiting on
+    Task(tokio::task::AbortHandle),
+    /// Asked to stop; `None` for work that can't be
+    Stop(Option<StopHook>),
+}
+
+/// Send changes to the frontend as `job-updated` events, and as the
+/// `job-status` events it listened for before; finished jobs are kept
+pub fn emitter(app: &AppHandle) -> JobEmitter {
+    let app = app.clone();
+    Arc::new(move |job: JobRecord| {
+        let _ = app.emit("job-status", JobStatusEvent::from(&job));
+        let _ = app.emit("job-updated", &job);
+        if job.status.is_finished() {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                if let Some(db) = app.try_state::<LocalDatabase>() {
+                    keep(&db, &job).await;
+                }
+            });
+        }
+    })
+}
+
+/// A finished job as it's kept in the database, with its log
+#[derive(Serialize, Deserialize)]
+struct KeptJob {
+    #[serde(flatten)]
+    job: JobRecord,
+    #[serde(default)]
+    log: Vec<JobLogLine>,
+}
+
+/// Keep a finished job and its log in the database, dropping the oldest
+/// beyond [`KEPT_JOBS`]
+async fn keep(db: &LocalDatabase, job: &JobRecord) {
+    let finished_at = job.finished_at.unwrap_or_else(Utc::now);
+    let kept = KeptJob { job: job.clone(), log: job_log::lines(&job.job_id).unwrap_or_default() };
+    let kept = match serde_json::to_string(&kept) {
+        Ok(json) => db.add_job_record(&job.job_id, finished_at, &json, KEPT_JOBS).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match kept {
+        // Read back from the database from now on
+        Ok(()) => job_log::forget(&job.job_id),
+        Err(e) => warn!("Failed to keep the record of job {}: {}", job.job_id, e),
+    }
+}
+
+/// The jobs kept in the database, newest first; ones that can't be read are skipped
+pub async fn kept(db: &LocalDatabase) -> Vec<JobRecord> {
+    match db.get_job_records().await {
+        Ok(records) => records.iter().filter_map(|json| serde_json::from_str(json).ok()).collect(),
+        Err(e) => {
+            warn!("Failed to read the kept jobs: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// What a job logged, from this run or kept in the database; `None` for a
+/// job that's neither
+pub async fn log(db: &LocalDatabase, job_id: &str) -> Option<Vec<JobLogLine>> {
+    if let Some(lines) = job_log::lines(job_id) {
+        return Some(lines);
+    }
+    let records = match db.get_job_records().await {
+        Ok(records) => records,
+        Err(e) => {
+            warn!("Failed to read the kept jobs: {}", e);
+            return None;
+        }
+    };
+    records
+        .iter()
+        .filter_map(|json| serde_json::from_str::<KeptJob>(json).ok())
+        .find(|kept| kept.job.job_id == job_id)
+        .map(|kept| kept.log)
+}
+
+/// This run's jobs and those `kept` from earlier ones, most recently
+/// started first; a job in both is listed as this run has it
+pub fn list(state: &AppState, kept: Vec<JobRecord>) -> Vec<JobRecord> {
+    let mut jobs: Vec<JobRecord> = state.active_jobs.iter().map(|job| job.clone()).collect();
+    jobs.extend(kept.into_iter().filter(|job| !state.active_jobs.contains_key(&job.job_id)));
+    jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+    jobs
+}
+
+/// This run's changes of a job numbered after `since_seq`, oldest first;
+/// `None` for a job this run hasn't seen
+///
+/// Changes before the last [`KEPT_EVENTS`] are gone, but each one carries
+/// the whole record, so the last is where the job is now.
+pub fn events_since(state: &AppState, job_id: &str, since_seq: u64) -> Option<Vec<JobRecord>> {
+    let events = state.job_events.get(job_id)?;
+    Some(events.iter().filter(|job| job.seq > since_seq).cloned().collect())
+}
+
+/// A running job's view of itself, to report progress
+#[derive(Clone)]
+pub struct JobContext {
+    state: Arc<AppState>,
+    emit: JobEmitter,
+    job_id: String,
+    kind: &'static str,
+}
+
+impl JobContext {
+    pub fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// Report `progress` (0 to 1), unless the job was cancelled meanwhile
+    pub fn progress(&self, progress: f32, message: impl Into<String>) {
+        let status = JobStatus::Processing { progress: progress.clamp(0.0, 1.0) };
+        if self.state.job_tasks.contains_key(&self.job_id) {
+            set_status(&self.state, &self.emit, &self.job_id, self.kind, status, Some(message.into()));
+        }
+    }
+
+    /// Mark the job cancelled, for work that stopped short without an error
+    pub fn cancelled(&self) {
+        if self.state.job_tasks.remove(&self.job_id).is_some() {
+            set_status(&self.state, &self.emit, &self.job_id, self.kind, JobStatus::Cancelled, None);
+            info!("Job {} stopped short", self.job_id);
+        }
+    }
+}
+
+/// Start `work` as a job of `kind`, returning its id and a handle that
+/// finishes with the job (or errors if it's cancelled)
+///
+/// A job that returns an error is marked failed with its message and code.
+pub fn start<F, Fut>(
+    state: &Arc<AppState>,
+    emit: JobEmitter,
+    kind: &'static str,
+    work: F,
+) -> (String, tokio::task::JoinHandle<()>)
+where
+    F: FnOnce(JobContext) -> Fut,
+    Fut: Future<Output = Result<(), CommandError>> + Send + 'static,
+{
+    let job_id = Uuid::new_v4().to_string();
+    let context = JobContext { state: state.clone(), emit: emit.clone(), job_id: job_id.clone(), kind };
+    set_status(state, &emit, &job_id, kind, JobStatus::Pending, None);
+
+    let work = work(context);
+    // The job waits until it can be cancelled, so it can't finish before its handle is stored
+    let (registered, is_registered) = tokio::sync::oneshot::channel::<()>();
+    let task = {
+        let (state, job_id) = (state.clone(), job_id.clone());
+        let span = job_log::span(&job_id);
+        tokio::spawn(async move {
+            let _ = is_registered.await;
+            let result = work.await;
+            if state.job_tasks.remove(&job_id).is_none() {
+                // Cancelled as it finished
+                return;
+            }
+            let status = match result {
+                Ok(()) => JobStatus::Completed,
+                Err(error) => {
+                    warn!("Job {} failed: {}", job_id, error);
+                    JobStatus::Failed { error: error.message, code: error.code }
+                }
+            };
+            set_status(&state, &emit, &job_id, kind, status, None);
+        }.instrument(span))
+    };
+    state.job_tasks.insert(job_id.clone(), (kind, JobHandle::Task(task.abort_handle())));
+    let _ = registered.send(());
+
+    info!("Started {} job {}", kind, job_id);
+    (job_id, task)
+}
+
+/// Run `work` here as a job of `kind`, so it's listed and reported like
+/// one, returning what it does
+///
+/// Cancelling the job calls `stop`, which should have `work` end soon;
+/// without it the job can't be cancelled.
+pub async fn run<T, F, Fut>(
+    state: &Arc<AppState>,
+    emit: JobEmitter,
+    kind: &'static str,
+    stop: Option<StopHook>,
+    work: F,
+) -> Result<T, CommandError>
+where
+    F: FnOnce(JobContext) -> Fut,
+    Fut: Future<Output = Result<T, CommandError>>,
+{
+    let job_id = Uuid::new_v4().to_string();
+    let context = JobContext { state: state.clone(), emit: emit.clone(), job_id: job_id.clone(), kind };
+    set_status(state, &emit, &job_id, kind, JobStatus::Pending, None);
+    state.job_tasks.insert(job_id.clone(), (kind, JobHandle::Stop(stop)));
+    info!("Started {} job {}", kind, job_id);
+
+    let span = job_log::span(&job_id);
+    let result = work(context).instrument(span.clone()).await;
+    // Otherwise cancelled, and marked so already
+    if state.job_tasks.remove(&job_id).is_some() {
+        let status = match &result {
+            Ok(_) => JobStatus::Completed,
+            Err(error) => {
+                warn!(parent: &span, "Job {} failed: {}", job_id, error);
+                JobStatus::Failed { error: error.message.clone(), code: error.code }
+            }
+        };
+        set_status(state, &emit, &job_id, kind, status, None);
+    }
+    result
+}
+
+/// Stop a running job
+pub fn cancel(state: &AppState, emit: &JobEmitter, job_id: &str) -> Result<(), CommandError> {
+    if let Some(entry) = state.job_tasks.get(job_id) {
+        if matches!(entry.1, JobHandle::Stop(None)) {
+            return Err(CommandError::invalid_input(format!("{} jobs can't be cancelled", entry.0)));
+        }
+    }
+    let Some((_, (kind, handle))) = state.job_tasks.remove(job_id) else {
+        return match state.active_jobs.get(job_id).map(|job| job.status.clone()) {
+            Some(status) if status.is_finished() => {
+                Err(CommandError::invalid_input(format!("Job {} has already finished", job_id)))
+            }
+            _ => Err(CommandError::not_found(format!("Job not found: {}", job_id))),
+        };
+    };
+    match handle {
+        JobHandle::Task(task) => task.abort(),
+        JobHandle::Stop(stop) => stop.iter().for_each(|stop| stop()),
+    }
+    set_status(state, emit, job_id, kind, JobStatus::Cancelled, None);
+    info!("Cancelled job {}", job_id);
+    Ok(())
+}
+
+fn set_status(
+    state: &AppState,
+    emit: &JobEmitter,
+    job_id: &str,
+    kind: &'static str,
+    status: JobStatus,
+    message: Option<String>,
+) {
+    debug!("Job {} is now {:?}", job_id, status);
+    let job = {
+        let mut job = state.active_jobs.entry(job_id.to_string()).or_insert_with(|| JobRecord::new(job_id, kind));
+        job.update(status, message);
+        // Kept while the record is locked, so the changes stay in order
+        let mut events = state.job_events.entry(job_id.to_string()).or_default();
+        if events.len() == KEPT_EVENTS {
+            events.pop_front();
+        }
+        events.push_back(job.clone());
+        job.clone()
+    };
+    emit(job);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorCode;
+    use std::sync::Mutex;
+
+    fn recorder() -> (JobEmitter, Arc<Mutex<Vec<JobRecord>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        (Arc::new(move |event| sink.lock().unwrap().push(event)), events)
+    }
+
+    fn states(events: &Mutex<Vec<JobRecord>>) -> Vec<JobStatus> {
+        events.lock().unwrap().iter().map(|e| e.status.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_job_reports_progress_and_finishes() {
+        let state = Arc::new(AppState::new());
+        let (emit, events) = recorder();
+
+        let (job_id, task) = start(&state, emit, "test", |job| async move {
+            job.progress(0.5, "Halfway");
+            Ok(())
+        });
+        task.await.unwrap();
+
+        assert_eq!(
+            states(&events),
+            [JobStatus::Pending, JobStatus::Processing { progress: 0.5 }, JobStatus::Completed]
+        );
+        assert_eq!(events.lock().unwrap()[1].message.as_deref(), Some("Halfway"));
+        let job = state.active_jobs.get(&job_id).unwrap().clone();
+        assert_eq!((job.status, job.progress, job.kind.as_str()), (JobStatus::Completed, 1.0, "test"));
+        assert!(job.finished_at.is_some_and(|finished| finished >= job.started_at));
+        assert!(state.job_tasks.is_empty());
+
+        let (job_id, task) = start(&state, recorder().0, "test", |job| async move {
+            job.progress(0.25, "Reading");
+            Err(CommandError::new(ErrorCode::Offline, "No luck"))
+        });
+        task.await.unwrap();
+        let job = state.active_jobs.get(&job_id).unwrap().clone();
+        assert_eq!(job.status, JobStatus::Failed { error: "No luck".into(), code: ErrorCode::Offline });
+        // Where it got to before it failed
+        assert_eq!((job.progress, job.message.as_deref()), (0.25, Some("Reading")));
+        assert_eq!(JobStatusEvent::from(&job).message, None);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_the_job() {
+        let state = Arc::new(AppState::new());
+        let (emit, events) = recorder();
+        let (dropped, was_dropped) = tokio::sync::oneshot::channel::<()>();
+
+        let (job_id, task) = start(&state, emit.clone(), "test", |_| async move {
+            // Stands in for an HTTP request that never answers
+            let _request = dropped;
+            std::future::pending::<()>().await;
+            Ok(())
+        });
+        tokio::task::yield_now().await;
+
+        cancel(&state, &emit, &job_id).unwrap();
+        assert!(task.await.unwrap_err().is_cancelled());
+        // Whatever the job was waiting on is dropped with it
+        assert!(was_dropped.await.is_err());
+        assert_eq!(states(&events).last(), Some(&JobStatus::Cancelled));
+
+        let err = cancel(&state, &emit, &job_id).unwrap_err();
+        assert!(err.message.contains("already finished"), "{}", err);
+        assert_eq!(cancel(&state, &emit, "nope").unwrap_err().code, ErrorCode::NotFound);
+    }
+
+    #[tokio::test]
+    async fn test_tracked_work_is_cancelled_through_its_hook() {
+        let state = Arc::new(AppState::new());
+        let (emit, events) = recorder();
+        let (stop, stopped) = tokio::sync::watch::channel(false);
+        let stop: StopHook = Box::new(move || {
+            let _ = stop.send(true);
+        });
+
+        // As a download does, ending without an error once paused
+        let download = run(&state, emit.clone(), "download", Some(stop), |job| {
+            let mut stopped = stopped.clone();
+            async move {
+                job.progress(0.4, "Downloading");
+                let _ = stopped.wait_for(|stopped| *stopped).await;
+                Ok(job.job_id().to_string())
+            }
+        });
+        let canceller = async {
+            tokio::task::yield_now().await;
+            let job_id = state.job_tasks.iter().next().map(|entry| entry.key().clone()).unwrap();
+            cancel(&state, &emit, &job_id).unwrap();
+        };
+        let (job_id, ()) = tokio::join!(download, canceller);
+
+        let job_id = job_id.unwrap();
+        assert_eq!(states(&events).last(), Some(&JobStatus::Cancelled));
+        assert_eq!(state.active_jobs.get(&job_id).unwrap().progress, 0.4);
+
+        // Work without a hook runs to the end
+        let import = run(&state, emit.clone(), "region-processing", None, |job| async move {
+            let err = cancel(&job.state, &job.emit, job.job_id()).unwrap_err();
+            assert!(err.message.contains("can't be cancelled"), "{}", err);
+            Err::<(), _>("Not a map file".into())
+        });
+        assert!(import.await.is_err());
+        let failed = JobStatus::Failed { error: "Not a map file".into(), code: ErrorCode::Internal };
+        assert_eq!(states(&events).last(), Some(&failed));
+    }
+
+    #[tokio::test]
+    async fn test_catching_up_returns_the_missed_changes_in_order() {
+        let state = Arc::new(AppState::new());
+        let (emit, events) = recorder();
+
+        let (job_id, task) = start(&state, emit, "test", |job| async move {
+            for step in 1..=4 {
+                job.progress(step as f32 / 5.0, format!("Step {}", step));
+            }
+            Ok(())
+        });
+        task.await.unwrap();
+
+        let emitted = events.lock().unwrap().clone();
+        let seqs: Vec<u64> = emitted.iter().map(|job| job.seq).collect();
+        assert_eq!(seqs, [1, 2, 3, 4, 5, 6]);
+        // A view that saw the first two changes, then went away
+        assert_eq!(events_since(&state, &job_id, 2).unwrap(), emitted[2..]);
+        assert_eq!(events_since(&state, &job_id, 0).unwrap(), emitted);
+        assert!(events_since(&state, &job_id, 6).unwrap().is_empty());
+        assert_eq!(events_since(&state, "nope", 0), None);
+
+        // Only the latest changes of a long job are kept
+        let emit: JobEmitter = Arc::new(|_| {});
+        for step in 0..KEPT_EVENTS + 10 {
+            set_status(&state, &emit, "long", "test", JobStatus::Processing { progress: 0.0 }, Some(step.to_string()));
+        }
+        let kept = events_since(&state, "long", 0).unwrap();
+        assert_eq!(kept.len(), KEPT_EVENTS);
+        assert_eq!((kept[0].seq, kept.last().unwrap().seq), (11, KEPT_EVENTS as u64 + 10));
+        assert_eq!(events_since(&state, "long", KEPT_EVENTS as u64 + 5).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_jobs_kept_from_earlier_runs_are_listed_after_newer_ones() {
+        let state = AppState::new();
+        let emit: JobEmitter = Arc::new(|_| {});
+        set_status(&state, &emit, "today", "narration", JobStatus::Processing { progress: 0.5 }, None);
+        let yesterday = JobRecord {
+            started_at: Utc::now() - chrono::Duration::days(1),
+            ..JobRecord::new("yesterday", "processing")
+        };
+        // Kept as it finished, and still in this run's jobs
+        let mut finished = JobRecord::new("today", "narration");
+        finished.update(JobStatus::Completed, None);
+
+        let jobs = list(&state, vec![finished, yesterday]);
+        let listed: Vec<_> = jobs.iter().map(|job| (job.job_id.as_str(), job.status.clone())).collect();
+        assert_eq!(listed, [("today", JobStatus::Processing { progress: 0.5 }), ("yesterday", JobStatus::Pending)]);
+    }
+}
@@ -3,21 +3,60 @@
 //! This module contains the core Tauri application logic and commands
 //! that bridge the React frontend with the Rust backend.
 
-use tauri::Manager;
-use tracing::{info, warn};
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tracing::{error, info, warn};
 
 mod commands;
 mod config;
 mod services;
 mod db;
 mod state;
+mod jobs;
+mod job_log;
 mod geo;
 mod gemini;
+mod llm;
+mod llm_queue;
+mod llm_cache;
+mod memory_cache;
+mod resources;
+mod local_llm;
 mod types;
+mod bundle_schema;
+mod confidence;
+mod citations;
+mod delivery;
+mod prompts;
+mod request_history;
+mod export;
 mod narrative;
+mod narration_variants;
+mod revision;
+mod chapter_titles;
+mod freshness;
+mod pacing;
+mod dialogue;
+mod template_narration;
+mod scenes;
 mod enrich;
+mod estimate;
+mod intake;
+mod overrides;
+mod trip_summary;
 mod processor;
+mod processing_queue;
+mod settings;
+mod secrets;
+mod single_instance;
+mod logging;
+mod crash;
+mod error;
+mod init;
+mod updater;
+mod usage_metrics;
+#[cfg(test)]
+mod fixtures;
 
 use state::AppState;
 use geo::GeoEngine;
@@ -26,40 +65,27 @@ use narrative::NarrativeEngine;
 use enrich::EnrichmentEngine;
 use std::sync::Arc;
 
-/// Initialize structured logging with JSON output in production
-fn init_logging() {
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new("info,geotruth_lib=debug"));
-
-    #[cfg(debug_assertions)]
-    {
-        // Pretty output for development
-        tracing_subscriber::registry()
-            .with(filter)
-            .with(
-                fmt::layer()
-                    .with_target(true)
-                    .with_thread_ids(false)
-                    .with_file(true)
-                    .with_line_number(true),
-            )
-            .init();
-    }
-
-    #[cfg(not(debug_assertions))]
-    {
-        // JSON output for production
-        tracing_subscriber::registry()
-            .with(filter)
-            .with(fmt::layer().json())
-            .init();
+/// Tell the user why the app can't start, and exit once they've read it
+///
+/// Hides the main window so the half-initialized app can't be used meanwhile.
+fn show_startup_error(app: &AppHandle, title: &str, message: &str) {
+    init::mark_failed(message);
+    if let Some(window) = app.get_webview_window("main") {
+        window.hide().ok();
     }
+    let handle = app.clone();
+    app.dialog()
+        .message(message)
+        .title(title)
+        .kind(MessageDialogKind::Error)
+        .show(move |_| handle.exit(1));
 }
 
 /// Run the Tauri application
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    init_logging();
+    logging::init();
+    crash::install();
 
     info!(
         version = env!("CARGO_PKG_VERSION"),
@@ -67,142 +93,174 @@ pub fn run() {
     );
 
     tauri::Builder::default()
+        .plugin(single_instance::plugin())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(init::gated(tauri::generate_handler![
             commands::get_version,
+            commands::get_init_status,
             commands::check_api_connection,
+            commands::health::get_health_report,
             commands::get_system_info,
             commands::get_map_regions,
             commands::get_available_regions,
+            commands::search_regions,
+            commands::get_regions_covering,
             commands::add_region,
             commands::download_map_region,
+            commands::download_custom_region,
+            commands::check_region_updates,
+            commands::update_region,
             commands::delete_map_region,
             commands::get_download_progress,
+            commands::remove_region,
+            commands::set_download_rate_limit,
+            commands::pause_region_download,
+            commands::resume_region_download,
+            commands::load_remote_region,
+            commands::unload_remote_region,
+            commands::storage::get_region_storage_report,
+            commands::storage::cleanup_orphaned_region_files,
+            commands::local_regions::import_local_region,
+            commands::region_bundle::export_region_bundle,
+            commands::region_bundle::import_region_bundle,
+            commands::poi::nearest_poi,
+            commands::maintenance::get_database_stats,
+            commands::maintenance::compact_database,
+            commands::maintenance::get_temp_usage,
+            commands::maintenance::get_cache_stats,
+            commands::maintenance::clear_caches,
+            commands::logs::get_log_path,
+            commands::logs::open_logs_folder,
+            commands::logs::get_recent_logs,
+            commands::paths::get_app_paths,
+            commands::paths::reveal_in_file_manager,
+            commands::logs::set_log_level,
+            commands::logs::get_last_crash_report,
+            commands::logs::get_request_history,
+            commands::metrics::get_usage_metrics,
+            commands::metrics::set_usage_metrics,
+            commands::metrics::export_metrics_report,
+            commands::metrics::clear_usage_metrics,
+            commands::setup::run_setup_check,
+            commands::sidecars::download_sidecar,
+            commands::settings::get_settings,
+            commands::settings::update_settings,
+            commands::settings::reset_settings,
+            commands::settings::set_sidecar_concurrency,
+            commands::settings::set_resource_limits,
+            commands::settings::get_resource_status,
+            commands::settings::set_thumbnail_format,
+            commands::settings::set_download_mirrors,
+            commands::settings::get_update_policy,
+            commands::settings::set_update_policy,
+            commands::settings::set_connectivity_mode,
+            commands::settings::set_enrichment_call_budget,
+            commands::settings::set_interpolation_policy,
+            commands::settings::set_memory_cache_limits,
+            commands::settings::set_processing_options,
+            commands::settings::set_processing_concurrency,
+            commands::settings::set_experimental_sun_sync,
+            commands::settings::set_narration_chunking,
+            commands::settings::set_narration_creativity,
+            commands::settings::set_gemini_retry_policy,
+            commands::settings::set_gemini_rate_limit,
+            commands::settings::set_gemini_safety,
+            commands::settings::get_llm_queue_status,
+            commands::settings::set_llm_engine,
+            commands::settings::set_local_llm,
+            commands::settings::get_llm_usage,
+            commands::settings::clear_llm_cache,
+            commands::settings::set_gemini_network,
+            commands::settings::test_llm_connection,
+            commands::settings::list_gemini_models,
+            commands::settings::set_gemini_models,
+            commands::settings::get_gemini_api_key_status,
+            commands::settings::set_gemini_api_key,
+            commands::settings::clear_gemini_api_key,
+            commands::ingest::get_supported_formats,
             commands::ingest::import_video,
+            commands::ingest::intake_files,
+            commands::ingest::confirm_intake,
             commands::ingest::get_project_videos,
             commands::ingest::create_project,
             commands::ingest::get_projects,
+            commands::ingest::resync_video,
+            commands::ingest::suggest_sun_sync_offset,
+            commands::ingest::get_video_track,
+            commands::ingest::get_trip_summary,
+            commands::ingest::set_project_cover,
+            commands::ingest::get_project_cover,
+            commands::ingest::set_project_camera_offset,
+            commands::ingest::set_video_camera_offset,
+            commands::ingest::relink_video,
+            commands::project_bundle::export_project,
+            commands::project_bundle::import_project,
+            commands::truth_bundle::export_truth_bundle,
+            commands::truth_bundle::import_truth_bundle,
+            commands::export::export_geojson,
+            commands::export::export_gpx,
+            commands::export::export_html_report,
+            commands::export::export_kml,
+            commands::export::export_markdown,
+            commands::export::export_youtube_chapters,
+            commands::export::export_fcpxml,
+            commands::export::export_markers,
             commands::narrate::narrate,
+            commands::narrate::start_narration,
+            commands::narrate::get_narration_result,
+            commands::jobs::list_jobs,
+            commands::jobs::get_job,
+            commands::jobs::get_job_events,
+            commands::jobs::get_job_log,
+            commands::jobs::cancel_job,
+            commands::narrate::get_narration_history,
+            commands::narrate::export_script,
+            commands::narrate::export_narration_srt,
+            commands::narrate::compare_narrations,
+            commands::narrate::check_narration_freshness,
+            commands::narrate::refresh_narration,
+            commands::narrate::regenerate_narration,
+            commands::prompts::get_prompt_template,
+            commands::prompts::set_prompt_template,
             commands::enrich::enrich,
+            commands::enrich::enrich_points,
+            commands::enrich::inspect_location,
+            commands::enrich::enrich_video,
+            commands::enrich::get_enrichment_cache_stats,
+            commands::enrich::describe_event_scenes,
+            commands::enrich::override_event_context,
+            commands::enrich::pin_poi_to_event,
+            commands::enrich::clear_override,
             commands::process::process_video,
+            commands::process::process_video_file,
+            commands::process::start_processing,
+            commands::process::start_processing_file,
+            commands::process::get_processing_result,
+            commands::process::process_videos,
+            commands::process::get_video_status,
+            commands::process::estimate_processing,
+            commands::queue::queue_processing,
+            commands::queue::get_processing_queue,
+            commands::queue::reorder_processing_queue,
+            commands::queue::cancel_queued_processing,
             commands::video::capture_frame,
+            commands::video::probe_streams,
             commands::video::auto_scan_moments,
-        ])
+            commands::video::embed_chapters,
+        ]))
         .setup(|app| {
             info!("Application setup complete");
+            crash::attach(app.handle().clone());
 
-            // Initialize Database
-            use services::database::LocalDatabase;
-            let app_data_dir = app.path().app_data_dir().expect("Failed to get app data dir");
-            let db_path = app_data_dir.join("geotruth_v1.duckdb");
-            
-            let db = LocalDatabase::open(db_path).expect("Failed to initialize database");
-            
-            // Run async init
-            tauri::async_runtime::block_on(async {
-                db.init().await.expect("Failed to run database migrations");
-            });
-            
-            app.manage(db);
-
-            // Initialize Global App State
-            let app_state = Arc::new(AppState::new());
-            app.manage(app_state.clone());
-
-            // Initialize Geo Engine
-            let geo_engine = Arc::new(GeoEngine::new());
-            app.manage(geo_engine.clone());
-            
-            // Initialize Narrative Engine
-            let narrative_engine = NarrativeEngine::new();
-            app.manage(narrative_engine);
-            
-            // Initialize Enrichment Engine
-            let enrichment_engine = EnrichmentEngine::new(geo_engine, app_state);
-            app.manage(enrichment_engine);
-
-            // Initialize Services
-            // In production (bundle), binaries should be in resource_dir.
-            use crate::services::{Ffmpeg, Whisper};
-            use crate::processor::VideoProcessor;
-
-            // Initialize Services
-            // In production (bundle), binaries should be in resource_dir.
-            // In dev (debug), they are likely in ../binaries (relative to src-tauri).
-             let mut binaries_dir = app.path().resource_dir()
-                .unwrap_or(std::path::PathBuf::from("."));
-            
-            #[cfg(debug_assertions)]
-            {
-                // Verify if binaries exist in the default location, if not try dev path
-                let has_ffmpeg = binaries_dir.join("ffmpeg").exists() || binaries_dir.join("ffmpeg.exe").exists();
-                
-                if !has_ffmpeg {
-                    // Try looking in ../binaries relative to CWD (usually src-tauri)
-                    let dev_path = std::env::current_dir()
-                        .map(|p| p.join("../binaries"))
-                        .unwrap_or_else(|_| std::path::PathBuf::from("../binaries"));
-                    
-                    if dev_path.exists() {
-                        info!("Using development binaries directory: {:?}", dev_path);
-                        binaries_dir = dev_path;
-                    } else {
-                        warn!("Could not find binaries in {:?} or {:?}", binaries_dir, dev_path);
-                    }
-                }
-            }
-            
-            let ffmpeg = Arc::new(Ffmpeg::new(binaries_dir.clone()).unwrap_or_else(|e| {
-                warn!("FFmpeg init failed: {}", e);
-                 Ffmpeg::new(std::path::PathBuf::from(".")).unwrap() 
-            }));
-            let whisper = Arc::new(Whisper::new(binaries_dir.clone()).unwrap_or_else(|e| {
-                 warn!("Whisper init failed: {}", e);
-                 Whisper::new(std::path::PathBuf::from(".")).unwrap()
-            }));
-
-            // Register Services as Managed State
-            app.manage(ffmpeg.clone());
-            app.manage(whisper.clone());
-
-            // Initialize Legacy Ingest State with ACTUAL FFmpeg
-            use commands::ingest::AppState as IngestState;
-            use tokio::sync::Mutex;
-            // The ingest::AppState expects Mutex<Option<Ffmpeg>> (not Arc). 
-            // We need to clone the Ffmpeg inner struct, but Ffmpeg definition might not be cloneable or we might need to wrap it differently.
-            // Looking at `ingest.rs`, AppState has `ffmpeg: Mutex<Option<Ffmpeg>>`. 
-            // And `Ffmpeg` struct in `services/ffmpeg.rs` needs to be checked if it is cloneable.
-            // Assuming Ffmpeg is lightweight (just paths), we can clone it if it derives Clone.
-            // If not, we might need to adjust ingest.rs to take Arc<Ffmpeg> or similar.
-            
-            // Checking: ingest.rs: `pub struct AppState { pub ffmpeg: Mutex<Option<Ffmpeg>>, ... }`
-            // Let's assume we can clone because we saw `ffmpeg` variable above is `Arc<Ffmpeg>`.
-            // Wait, we need to pass a `Ffmpeg` instance, not `Arc`. 
-            // Let's check if Ffmpeg implements Clone. If not, we might fail compilation.
-            // Safe bet: The logic in ingest.rs is outdated because it uses `Mutex<Option<Ffmpeg>>`.
-            // Ideally `ingest.rs` should just use `State<'_, Arc<Ffmpeg>>`.
-            // BUT to minimize changes and "fix" the existing logic:
-            // We will deref the Arc to get a clone if possible.
-            
-            // Let's rely on standard Rust pattern here. 
-            // Actually, let's inject Arc<Ffmpeg> as a managed state and update ingest.rs to use that instead of the custom struct if possible.
-            // BUT `import_video` signature is `ffmpeg_state: State<'_, AppState>`.
-            // So we MUST populate AppState.
-            
-            app.manage(IngestState {
-                db: Mutex::new(None), // We use global DB state now
-                // We need to unwrap the Arc or clone the inner. 
-                // Since Ffmpeg holds PathBufs, it should be cloneable. 
-                ffmpeg: Mutex::new(Some((*ffmpeg).clone())), 
-            });
-
-            
-            // Initialize Video Processor
-            let temp_dir = std::env::temp_dir();
-            let video_processor = Arc::new(VideoProcessor::new(ffmpeg.clone(), whisper, temp_dir));
-            app.manage(video_processor);
+            // Cheap state the rest is built on; everything that reads the
+            // disk is set up in the background so the window shows at once
+            app.manage(Arc::new(AppState::new()));
+            app.manage(Arc::new(GeoEngine::new()));
+            app.manage(commands::narrate::NarrationResults::default());
+            app.manage(commands::process::ProcessingResults::default());
+            tauri::async_runtime::spawn(initialize(app.handle().clone()));
 
             // Log window info
             if let Some(window) = app.get_webview_window("main") {
@@ -214,6 +272,216 @@ pub fn run() {
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                if let Some(scheduler) = app.try_state::<updater::UpdateScheduler>() {
+                    tauri::async_runtime::block_on(scheduler.stop());
+                }
+            }
+        });
+}
+
+/// Set up everything that reads the disk, after the window has opened
+///
+/// Each part is announced with `app-ready` as it comes online. A database
+/// that can't be opened stops the rest and tells the user why.
+async fn initialize(app: AppHandle) {
+    let app_data_dir = app.path().app_data_dir().unwrap_or_else(|e| {
+        warn!("Failed to get app data dir ({}), using the default location", e);
+        dirs::data_dir()
+            .unwrap_or_else(|| std::path::PathBuf::from("."))
+            .join("com.geotruth.app")
+    });
+
+    // Nothing else needs the map regions, so they're checked alongside
+    let handle = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = tauri::async_runtime::spawn_blocking(commands::load_map_regions).await {
+            warn!("Failed to load map regions: {}", e);
+        }
+        init::mark_ready(&handle, init::Component::Regions);
+    });
+
+    let sidecars = {
+        let (app, app_data_dir) = (app.clone(), app_data_dir.clone());
+        tauri::async_runtime::spawn_blocking(move || locate_sidecars(&app, &app_data_dir))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|located| located)
+    };
+    let (ffmpeg, whisper) = match sidecars {
+        Ok(sidecars) => sidecars,
+        Err(e) => {
+            error!("Failed to set up sidecars: {}", e);
+            show_startup_error(&app, "GeoTruth couldn't start", &format!("FFmpeg and Whisper couldn't be set up: {}", e));
+            return;
+        }
+    };
+
+    // Register Services as Managed State
+    app.manage(ffmpeg.clone());
+    app.manage(whisper.clone());
+
+    // Initialize Legacy Ingest State with ACTUAL FFmpeg
+    use commands::ingest::AppState as IngestState;
+    use tokio::sync::Mutex;
+    // The ingest::AppState expects Mutex<Option<Ffmpeg>> (not Arc). 
+    // We need to clone the Ffmpeg inner struct, but Ffmpeg definition might not be cloneable or we might need to wrap it differently.
+    // Looking at `ingest.rs`, AppState has `ffmpeg: Mutex<Option<Ffmpeg>>`. 
+    // And `Ffmpeg` struct in `services/ffmpeg.rs` needs to be checked if it is cloneable.
+    // Assuming Ffmpeg is lightweight (just paths), we can clone it if it derives Clone.
+    // If not, we might need to adjust ingest.rs to take Arc<Ffmpeg> or similar.
+    
+    // Checking: ingest.rs: `pub struct AppState { pub ffmpeg: Mutex<Option<Ffmpeg>>, ... }`
+    // Let's assume we can clone because we saw `ffmpeg` variable above is `Arc<Ffmpeg>`.
+    // Wait, we need to pass a `Ffmpeg` instance, not `Arc`. 
+    // Let's check if Ffmpeg implements Clone. If not, we might fail compilation.
+    // Safe bet: The logic in ingest.rs is outdated because it uses `Mutex<Option<Ffmpeg>>`.
+    // Ideally `ingest.rs` should just use `State<'_, Arc<Ffmpeg>>`.
+    // BUT to minimize changes and "fix" the existing logic:
+    // We will deref the Arc to get a clone if possible.
+    
+    // Let's rely on standard Rust pattern here. 
+    // Actually, let's inject Arc<Ffmpeg> as a managed state and update ingest.rs to use that instead of the custom struct if possible.
+    // BUT `import_video` signature is `ffmpeg_state: State<'_, AppState>`.
+    // So we MUST populate AppState.
+    
+    app.manage(IngestState {
+        db: Mutex::new(None), // We use global DB state now
+        // We need to unwrap the Arc or clone the inner. 
+        // Since Ffmpeg holds PathBufs, it should be cloneable. 
+        ffmpeg: Mutex::new(Some((*ffmpeg).clone())), 
+    });
+    init::mark_ready(&app, init::Component::Sidecars);
+
+    // Initialize Database
+    use services::database::{DatabaseError, LocalDatabase};
+    let db_path = app_data_dir.join("geotruth_v1.duckdb");
+    let opened = tauri::async_runtime::spawn_blocking(move || LocalDatabase::open(db_path)).await;
+    let db = match opened {
+        Ok(Ok(db)) => db,
+        Ok(Err(DatabaseError::Locked)) => {
+            // Another copy running from elsewhere, which the single-instance
+            // check doesn't see; tell the user instead of crashing
+            warn!("Database is locked by another instance, exiting");
+            show_startup_error(
+                &app,
+                "GeoTruth is already running",
+                "GeoTruth is already running. Switch to the open window, or close it and try again.",
+            );
+            return;
+        }
+        Ok(Err(e)) => {
+            error!("Failed to open database: {}", e);
+            show_startup_error(&app, "GeoTruth couldn't start", &format!("The local database couldn't be opened: {}", e));
+            return;
+        }
+        Err(e) => {
+            error!("Failed to open database: {}", e);
+            show_startup_error(&app, "GeoTruth couldn't start", &format!("The local database couldn't be opened: {}", e));
+            return;
+        }
+    };
+    
+    // Run async init
+    if let Err(e) = db.init().await {
+        error!("Failed to run database migrations: {}", e);
+        show_startup_error(&app, "GeoTruth couldn't start", &format!("The local database couldn't be updated: {}", e));
+        return;
+    }
+    
+    // Responses to repeated Gemini requests, shared by the engines
+    let llm_cache = Arc::new(llm_cache::LlmCache::new(db.clone()));
+    llm_cache.prune().await;
+    app.manage(llm_cache.clone());
+
+    app.manage(db.clone());
+    init::mark_ready(&app, init::Component::Database);
+
+    let app_state = app.state::<Arc<AppState>>().inner().clone();
+    let geo_engine = app.state::<Arc<GeoEngine>>().inner().clone();
+
+    // Initialize Narrative Engine
+    let narrative_engine = NarrativeEngine::new(llm_cache.clone(), app_state.request_history.clone());
+    app.manage(narrative_engine);
+    
+    // Initialize Enrichment Engine
+    let enrichment_engine = EnrichmentEngine::new(geo_engine, app_state, llm_cache).with_database(db.clone());
+    enrichment_engine.prune_cache().await;
+    app.manage(enrichment_engine);
+
+    // Initialize Video Processor, its jobs' temp files where the settings say, else under the app cache
+    use crate::processor::VideoProcessor;
+    let temp_dir = settings::get()
+        .temp_dir
+        .or_else(|| app.path().app_cache_dir().ok())
+        .unwrap_or_else(std::env::temp_dir)
+        .join("jobs");
+    let sweep_dir = temp_dir.clone();
+    // Whatever a crash left behind; nothing is running yet
+    tauri::async_runtime::spawn_blocking(move || {
+        services::temp_files::sweep(&sweep_dir, services::temp_files::STALE_AFTER)
+    });
+    let video_processor = Arc::new(
+        VideoProcessor::new(ffmpeg.clone(), whisper, temp_dir)
+            .with_pois(db)
+            .with_artifact_dir(app_data_dir.join("artifacts")),
+    );
+    app.manage(video_processor);
+    init::mark_ready(&app, init::Component::Engines);
+
+    // Tell the UI early if sidecars or models are missing, rather than mid-job
+    commands::setup::check_at_startup(app.clone());
+
+    // Pick up the processing queue where the last run left it
+    commands::queue::start(app.clone());
+
+    // Keep downloaded regions fresh according to the user's policy
+    app.manage(updater::UpdateScheduler::start(app.clone()));
+}
+
+/// FFmpeg and Whisper from the bundled binaries, or ones downloaded earlier
+fn locate_sidecars(app: &AppHandle, app_data_dir: &std::path::Path) -> Result<(Arc<services::Ffmpeg>, Arc<services::Whisper>), String> {
+    use crate::services::{Ffmpeg, Whisper};
+
+    // Initialize Services
+    // In production (bundle), binaries should be in resource_dir.
+    // In dev (debug), they are likely in ../binaries (relative to src-tauri).
+     let mut binaries_dir = app.path().resource_dir()
+        .unwrap_or(std::path::PathBuf::from("."));
+    
+    #[cfg(debug_assertions)]
+    {
+        // Verify if binaries exist in the default location, if not try dev path
+        let has_ffmpeg = binaries_dir.join("ffmpeg").exists() || binaries_dir.join("ffmpeg.exe").exists();
+        
+        if !has_ffmpeg {
+            // Try looking in ../binaries relative to CWD (usually src-tauri)
+            let dev_path = std::env::current_dir()
+                .map(|p| p.join("../binaries"))
+                .unwrap_or_else(|_| std::path::PathBuf::from("../binaries"));
+            
+            if dev_path.exists() {
+                info!("Using development binaries directory: {:?}", dev_path);
+                binaries_dir = dev_path;
+            } else {
+                warn!("Could not find binaries in {:?} or {:?}", binaries_dir, dev_path);
+            }
+        }
+    }
+    
+    let ffmpeg = Arc::new(Ffmpeg::new(binaries_dir.clone()).or_else(|e| {
+        warn!("FFmpeg init failed: {}", e);
+        Ffmpeg::new(std::path::PathBuf::from("."))
+    }).map_err(|e| e.to_string())?);
+    let whisper = Arc::new(Whisper::new(binaries_dir.clone()).or_else(|e| {
+        warn!("Whisper init failed: {}", e);
+        Whisper::new(std::path::PathBuf::from("."))
+    }).map_err(|e| e.to_string())?);
+
+    // Binaries downloaded earlier stand in for missing bundled ones
+    commands::sidecars::use_downloaded(app_data_dir, &ffmpeg, &whisper);
+    Ok((ffmpeg, whisper))
 }
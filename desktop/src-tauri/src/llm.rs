@@ -0,0 +1,498 @@
+//! LLM Backends
+//!
+//! The narrative and enrichment engines generate text through [`LlmBackend`],
+//! implemented by the Gemini API client and by [`LocalBackend`], a model
+//! served on this machine. [`RoutedBackend`] picks one of them for every
+//! request, so users without connectivity or an API key still get results.
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use thiserror::Error;
+
+use crate::gemini::GeminiError;
+use crate::services::data_manager::ConnectivityMode;
+use crate::{config, settings};
+
+pub use crate::local_llm::LocalBackend;
+
+/// Largest total of inline image data Gemini accepts in one request
+pub const MAX_INLINE_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// A base64 encoded image and its MIME type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePart {
+    pub mime_type: &'static str,
+    pub data: String,
+    /// Text sent just before the image, e.g. `[Frame at 02:30]`
+    pub label: Option<String>,
+}
+
+/// Why a scene frame can't be sent to the model
+///
+/// `index` is the frame's position in the request.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    #[error("Scene frame {index} isn't valid base64 image data")]
+    InvalidData { index: usize },
+
+    #[error("Scene frame {index} isn't a supported image; use JPEG, PNG, WebP or HEIC")]
+    UnsupportedFormat { index: usize },
+
+    #[error("Scene frame {index} is {bytes} bytes, over the {max} byte limit for images sent to the model")]
+    TooLarge { index: usize, bytes: usize, max: usize },
+
+    #[error("Scene frame {index} takes the request over the {max} byte limit for images; send fewer or smaller frames")]
+    TotalTooLarge { index: usize, max: usize },
+}
+
+impl ImagePart {
+    /// Image part from base64 data or a `data:` URI
+    ///
+    /// The type is taken from the image's own bytes, not from the URI, which
+    /// may well claim JPEG for a PNG snapshot.
+    pub fn from_base64(index: usize, encoded: &str) -> Result<Self, ImageError> {
+        let data = match encoded.split_once(',') {
+            Some((header, data)) if header.starts_with("data:") => data,
+            _ => encoded,
+        }
+        .trim();
+
+        let bytes = general_purpose::STANDARD
+            .decode(data)
+            .map_err(|_| ImageError::InvalidData { index })?;
+        let mime_type = detect_image_type(&bytes).ok_or(ImageError::UnsupportedFormat { index })?;
+        if bytes.len() > MAX_INLINE_IMAGE_BYTES {
+            return Err(ImageError::TooLarge { index, bytes: bytes.len(), max: MAX_INLINE_IMAGE_BYTES });
+        }
+
+        Ok(Self { mime_type, data: data.to_string(), label: None })
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+/// Image parts for frames given with their index in the request, in order
+///
+/// Fails on the first frame that can't be sent, or that takes the request
+/// over the inline data limit.
+pub fn image_parts<'a>(frames: impl IntoIterator<Item = (usize, &'a str)>) -> Result<Vec<ImagePart>, ImageError> {
+    let mut total = 0;
+    frames
+        .into_iter()
+        .map(|(index, frame)| {
+            let part = ImagePart::from_base64(index, frame)?;
+            // Base64 length is within a few bytes of 4/3 of the decoded size
+            total += part.data.len() / 4 * 3;
+            if total > MAX_INLINE_IMAGE_BYTES {
+                return Err(ImageError::TotalTooLarge { index, max: MAX_INLINE_IMAGE_BYTES });
+            }
+            Ok(part)
+        })
+        .collect()
+}
+
+/// MIME type of an image Gemini accepts, from its magic number
+fn detect_image_type(bytes: &[u8]) -> Option<&'static str> {
+    match bytes {
+        [0xFF, 0xD8, 0xFF, ..] => Some("image/jpeg"),
+        [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some("image/png"),
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Some("image/webp"),
+        [_, _, _, _, b'f', b't', b'y', b'p', brand @ ..] if brand.len() >= 4 => match &brand[..4] {
+            b"heic" | b"heix" | b"heim" | b"heis" => Some("image/heic"),
+            b"mif1" | b"msf1" | b"heif" => Some("image/heif"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Generated text and why the model stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Generation {
+    pub text: String,
+    pub finish_reason: FinishReason,
+    /// `None` when the backend didn't report it, or the text came from the cache
+    pub usage: Option<TokenUsage>,
+}
+
+/// Tokens a request consumed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl Generation {
+    /// Text the model finished on its own
+    pub fn complete(text: impl Into<String>) -> Self {
+        Self { text: text.into(), finish_reason: FinishReason::Stop, usage: None }
+    }
+
+    /// Whether the text was cut off at the output token limit
+    pub fn is_truncated(&self) -> bool {
+        self.finish_reason == FinishReason::MaxTokens
+    }
+}
+
+/// Why a model stopped generating
+///
+/// Responses blocked by safety filters are errors rather than a reason here,
+/// since they come without usable text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    /// Natural end of the response
+    Stop,
+    /// Hit the output token limit; the text is incomplete
+    MaxTokens,
+    /// Anything else the backend reported, as sent
+    Other(String),
+}
+
+impl FinishReason {
+    /// Name for logs and response meta, e.g. `max_tokens`
+    pub fn as_str(&self) -> &str {
+        match self {
+            FinishReason::Stop => "stop",
+            FinishReason::MaxTokens => "max_tokens",
+            FinishReason::Other(reason) => reason,
+        }
+    }
+}
+
+/// Highest sampling temperature the backends accept
+pub const MAX_TEMPERATURE: f32 = 2.0;
+
+/// How a model samples its response; whatever's unset is the model's default
+///
+/// With the same seed, prompt and parameters Gemini gives the same response,
+/// as far as it can, so a narration written again only changes where its
+/// prompt does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sampling {
+    /// From 0 to [`MAX_TEMPERATURE`]; higher reads less predictably
+    pub temperature: Option<f32>,
+    /// Only the likeliest tokens making up this share of the probability,
+    /// from 0 to 1, are sampled
+    pub top_p: Option<f32>,
+    /// Only this many of the likeliest tokens are sampled
+    pub top_k: Option<u32>,
+    /// Makes the random choices repeatable, where the backend supports it
+    pub seed: Option<i32>,
+    /// Longest response, in tokens
+    pub max_output_tokens: Option<u32>,
+}
+
+impl Sampling {
+    #[allow(dead_code)]
+    pub fn at_temperature(temperature: f32) -> Self {
+        Self { temperature: Some(temperature), ..Self::default() }
+    }
+
+    /// Sampling for a creativity from 0, sticking to the facts, to 1,
+    /// free with its words
+    pub fn from_creativity(creativity: f32) -> Self {
+        let creativity = if creativity.is_finite() { creativity.clamp(0.0, 1.0) } else { 0.5 };
+        let round = |value: f32| (value * 100.0).round() / 100.0;
+        Self {
+            temperature: Some(round(0.2 + creativity)),
+            top_p: Some(round(0.8 + 0.15 * creativity)),
+            ..Self::default()
+        }
+    }
+
+    /// Whether every parameter is the model's default
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// These parameters, with the unset ones taken from `defaults`
+    pub fn or(self, defaults: Sampling) -> Self {
+        Self {
+            temperature: self.temperature.or(defaults.temperature),
+            top_p: self.top_p.or(defaults.top_p),
+            top_k: self.top_k.or(defaults.top_k),
+            seed: self.seed.or(defaults.seed),
+            max_output_tokens: self.max_output_tokens.or(defaults.max_output_tokens),
+        }
+    }
+
+    /// These parameters without the ones out of range, which are left to the model
+    pub fn checked(self) -> Self {
+        Self {
+            temperature: self.temperature.filter(|t| (0.0..=MAX_TEMPERATURE).contains(t)),
+            top_p: self.top_p.filter(|p| (0.0..=1.0).contains(p)),
+            top_k: self.top_k.filter(|k| *k > 0),
+            seed: self.seed,
+            max_output_tokens: self.max_output_tokens.filter(|n| *n > 0),
+        }
+    }
+}
+
+/// A seed for a request that doesn't give one, so it can be repeated
+pub fn random_seed() -> i32 {
+    (uuid::Uuid::new_v4().as_u128() as u32 >> 1) as i32
+}
+
+/// Boxed future returned by [`LlmBackend`] methods
+pub type BackendFuture<'a> = Pin<Box<dyn Future<Output = Result<Generation, GeminiError>> + Send + 'a>>;
+
+/// Text generation backend used by the narrative and enrichment engines
+///
+/// Implemented by [`GeminiClient`](crate::gemini::GeminiClient) for the API and
+/// [`LocalBackend`] for a local server; tests inject a mock so the prompt
+/// building and response parsing can run without a key or network.
+pub trait LlmBackend: Send + Sync {
+    /// Generate text from a system instruction, a prompt and images
+    ///
+    /// The system instruction sets the model's role and rules apart from the
+    /// request itself. With a `response_schema` (an OpenAPI-style schema
+    /// object) the model is asked for JSON matching it rather than free text.
+    /// With `allow_cache` false an earlier response to the same request isn't
+    /// reused.
+    fn generate_with_system<'a>(
+        &'a self,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a>;
+
+    /// [`generate_with_system`](Self::generate_with_system), with the
+    /// `sampling` parameters that are set rather than the model's defaults
+    ///
+    /// Backends that can't set them use their defaults.
+    fn generate_with_sampling<'a>(
+        &'a self,
+        sampling: Sampling,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        let _ = sampling;
+        self.generate_with_system(system_instruction, prompt, images, response_schema, allow_cache)
+    }
+
+    /// Generate text from a prompt plus images, without a system instruction
+    fn generate_multimodal<'a>(
+        &'a self,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        self.generate_with_system(None, prompt, images, response_schema, allow_cache)
+    }
+
+    /// Generate text from a prompt only
+    fn generate_content<'a>(&'a self, prompt: &'a str) -> BackendFuture<'a> {
+        self.generate_multimodal(prompt, vec![], None, true)
+    }
+
+    /// Model the next request will use
+    fn model(&self) -> String;
+
+    /// Kind of backend the next request will use, e.g. `gemini` or `local`
+    fn engine(&self) -> &'static str;
+
+    /// Whether the next request goes out over the internet, so it mustn't
+    /// be made in offline mode
+    ///
+    /// Backends are taken to be remote unless they say otherwise.
+    fn is_remote(&self) -> bool {
+        true
+    }
+}
+
+/// Which backend generates text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmEngine {
+    /// Gemini, unless offline or no API key is configured
+    #[default]
+    Auto,
+    Gemini,
+    Local,
+}
+
+impl LlmEngine {
+    /// The backend to use, resolving [`LlmEngine::Auto`]
+    pub fn resolve(self, connectivity: ConnectivityMode, has_gemini_key: bool) -> LlmEngine {
+        match self {
+            LlmEngine::Auto if connectivity == ConnectivityMode::Offline || !has_gemini_key => LlmEngine::Local,
+            LlmEngine::Auto => LlmEngine::Gemini,
+            engine => engine,
+        }
+    }
+}
+
+/// Sends each request to Gemini or the local backend, as the settings say
+///
+/// The choice is made per request, so a changed setting, going offline or
+/// adding an API key applies right away.
+pub struct RoutedBackend {
+    gemini: Arc<dyn LlmBackend>,
+    local: Arc<dyn LlmBackend>,
+}
+
+impl RoutedBackend {
+    pub fn new(gemini: Arc<dyn LlmBackend>, local: Arc<dyn LlmBackend>) -> Self {
+        Self { gemini, local }
+    }
+
+    fn current(&self) -> &Arc<dyn LlmBackend> {
+        let settings = settings::get();
+        let has_key = !config::get_gemini_api_key().is_empty();
+        match settings.llm_engine.resolve(settings.connectivity_mode, has_key) {
+            LlmEngine::Local => &self.local,
+            _ => &self.gemini,
+        }
+    }
+}
+
+impl LlmBackend for RoutedBackend {
+    fn generate_with_system<'a>(
+        &'a self,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        self.current()
+            .generate_with_system(system_instruction, prompt, images, response_schema, allow_cache)
+    }
+
+    fn generate_with_sampling<'a>(
+        &'a self,
+        sampling: Sampling,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        self.current()
+            .generate_with_sampling(sampling, system_instruction, prompt, images, response_schema, allow_cache)
+    }
+
+    fn model(&self) -> String {
+        self.current().model()
+    }
+
+    fn engine(&self) -> &'static str {
+        self.current().engine()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.current().is_remote()
+    }
+}
+
+/// Samples every request to another backend with the same parameters, e.g.
+/// for narration variants that should read differently
+pub struct SamplingBackend {
+    inner: Arc<dyn LlmBackend>,
+    sampling: Sampling,
+}
+
+impl SamplingBackend {
+    pub fn new(inner: Arc<dyn LlmBackend>, sampling: Sampling) -> Self {
+        Self { inner, sampling }
+    }
+}
+
+impl LlmBackend for SamplingBackend {
+    fn generate_with_system<'a>(
+        &'a self,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        self.inner.generate_with_sampling(
+            self.sampling,
+            system_instruction,
+            prompt,
+            images,
+            response_schema,
+            allow_cache,
+        )
+    }
+
+    fn model(&self) -> String {
+        self.inner.model()
+    }
+
+    fn engine(&self) -> &'static str {
+        self.inner.engine()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.inner.is_remote()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_resolution() {
+        use ConnectivityMode::*;
+
+        assert_eq!(LlmEngine::Auto.resolve(Hybrid, true), LlmEngine::Gemini);
+        assert_eq!(LlmEngine::Auto.resolve(Online, true), LlmEngine::Gemini);
+        assert_eq!(LlmEngine::Auto.resolve(Offline, true), LlmEngine::Local);
+        assert_eq!(LlmEngine::Auto.resolve(Hybrid, false), LlmEngine::Local);
+
+        // An explicit choice always wins
+        assert_eq!(LlmEngine::Gemini.resolve(Offline, false), LlmEngine::Gemini);
+        assert_eq!(LlmEngine::Local.resolve(Online, true), LlmEngine::Local);
+    }
+
+    fn encode(bytes: &[u8]) -> String {
+        general_purpose::STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn test_image_type_from_bytes() {
+        let png = encode(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0]);
+        // The URI's claimed type doesn't matter
+        let part = ImagePart::from_base64(0, &format!("data:image/jpeg;base64,{}", png)).unwrap();
+        assert_eq!(part, ImagePart { mime_type: "image/png", data: png, label: None });
+
+        assert_eq!(ImagePart::from_base64(0, &encode(&[0xFF, 0xD8, 0xFF, 0xE0])).unwrap().mime_type, "image/jpeg");
+        assert_eq!(ImagePart::from_base64(0, &encode(b"RIFF\x10\0\0\0WEBPVP8 ")).unwrap().mime_type, "image/webp");
+        assert_eq!(ImagePart::from_base64(0, &encode(b"\0\0\0\x18ftypheic\0\0")).unwrap().mime_type, "image/heic");
+
+        assert_eq!(ImagePart::from_base64(2, &encode(b"GIF89a..")), Err(ImageError::UnsupportedFormat { index: 2 }));
+        assert_eq!(ImagePart::from_base64(3, "not base64!"), Err(ImageError::InvalidData { index: 3 }));
+    }
+
+    #[test]
+    fn test_image_parts_size_limit() {
+        let mut jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        jpeg.resize(MAX_INLINE_IMAGE_BYTES / 2 + 3, 0);
+        let frame = encode(&jpeg);
+
+        assert_eq!(image_parts([(0, frame.as_str())]).unwrap().len(), 1);
+        // Two fit on their own but not together; the second is named
+        let err = image_parts([(0, frame.as_str()), (1, frame.as_str())]).unwrap_err();
+        assert_eq!(err, ImageError::TotalTooLarge { index: 1, max: MAX_INLINE_IMAGE_BYTES });
+
+        jpeg.resize(MAX_INLINE_IMAGE_BYTES + 1, 0);
+        let err = ImagePart::from_base64(0, &encode(&jpeg)).unwrap_err();
+        assert!(matches!(err, ImageError::TooLarge { index: 0, .. }), "{:?}", err);
+    }
+}
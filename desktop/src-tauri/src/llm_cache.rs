@@ -0,0 +1,221 @@
+//! LLM Response Cache
+//!
+//! Gemini responses stored by a hash of everything that shapes them (model,
+//! system instruction, prompt, images and response schema), so re-enriching the same points or
+//! regenerating a narration from unchanged inputs doesn't pay for the same
+//! request twice. Entries expire after a week and the oldest are evicted
+//! once the cache outgrows its size cap.
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{debug, info, warn};
+
+use crate::llm::{ImagePart, Sampling};
+use crate::services::LocalDatabase;
+
+/// How long a cached response stays valid
+const TTL_DAYS: i64 = 7;
+
+/// Total size of cached responses before the oldest are evicted
+const MAX_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Expired and excess entries are pruned after this many new responses
+const PRUNE_EVERY: u64 = 50;
+
+/// Cache and request counts since the app started, for the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmUsage {
+    /// Requests sent to the API
+    pub requests: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Share of cache lookups answered from the cache, 0 to 1
+    pub cache_hit_rate: f64,
+    pub cache_entries: u64,
+    pub cache_bytes: u64,
+}
+
+/// Persistent response cache backed by the `llm_cache` table
+pub struct LlmCache {
+    db: LocalDatabase,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    requests: AtomicU64,
+    /// Responses stored since the last prune
+    stored: AtomicU64,
+}
+
+impl LlmCache {
+    pub fn new(db: LocalDatabase) -> Self {
+        Self {
+            db,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            stored: AtomicU64::new(0),
+        }
+    }
+
+    /// The cached response for `key`, if there's a fresh one
+    ///
+    /// A failing lookup counts as a miss; the request is just sent instead.
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let cached = self.db.llm_cache_get(key, ttl()).await.unwrap_or_else(|e| {
+            warn!("LLM cache lookup failed: {}", e);
+            None
+        });
+
+        if cached.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            debug!("LLM cache hit");
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        cached
+    }
+
+    /// Store a response, pruning the cache every so often
+    pub async fn put(&self, key: &str, model: &str, response: &str) {
+        if let Err(e) = self.db.llm_cache_put(key, model, response).await {
+            warn!("Failed to cache LLM response: {}", e);
+            return;
+        }
+
+        if self.stored.fetch_add(1, Ordering::Relaxed) + 1 >= PRUNE_EVERY {
+            self.stored.store(0, Ordering::Relaxed);
+            self.prune().await;
+        }
+    }
+
+    /// Count a request that went to the API
+    pub fn record_request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drop expired entries and evict the oldest beyond the size cap
+    pub async fn prune(&self) {
+        if let Err(e) = self.db.llm_cache_prune(ttl(), MAX_BYTES).await {
+            warn!("Failed to prune the LLM cache: {}", e);
+        }
+    }
+
+    /// Remove every cached response; returns how many were removed
+    pub async fn clear(&self) -> Result<usize, String> {
+        let removed = self
+            .db
+            .llm_cache_clear()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        info!("Cleared {} cached LLM responses", removed);
+        Ok(removed)
+    }
+
+    pub async fn usage(&self) -> Result<LlmUsage, String> {
+        let (cache_entries, cache_bytes) = self
+            .db
+            .llm_cache_stats()
+            .await
+            .map_err(|e| format!("Database error: {}", e))?;
+        let cache_hits = self.hits.load(Ordering::Relaxed);
+        let cache_misses = self.misses.load(Ordering::Relaxed);
+        let lookups = cache_hits + cache_misses;
+
+        Ok(LlmUsage {
+            requests: self.requests.load(Ordering::Relaxed),
+            cache_hits,
+            cache_misses,
+            cache_hit_rate: if lookups == 0 { 0.0 } else { cache_hits as f64 / lookups as f64 },
+            cache_entries,
+            cache_bytes,
+        })
+    }
+}
+
+fn ttl() -> chrono::Duration {
+    chrono::Duration::days(TTL_DAYS)
+}
+
+/// Hex SHA-256 identifying a request by everything that shapes its response
+///
+/// Images are hashed on their own first so the key input stays small for
+/// requests with large frames. Each part is length-prefixed so that moving
+/// text between the prompt and the model name can't produce the same key.
+pub fn cache_key(
+    model: &str,
+    system_instruction: Option<&str>,
+    prompt: &str,
+    images: &[ImagePart],
+    response_schema: Option<&serde_json::Value>,
+    sampling: &Sampling,
+) -> String {
+    let mut hasher = Sha256::new();
+    let mut part = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+
+    part(model.as_bytes());
+    part(system_instruction.unwrap_or_default().as_bytes());
+    part(prompt.as_bytes());
+    for image in images {
+        if let Some(label) = &image.label {
+            part(label.as_bytes());
+        }
+        part(&Sha256::digest(image.data.as_bytes()));
+    }
+    part(response_schema.map(|s| s.to_string()).unwrap_or_default().as_bytes());
+    if let Some(temperature) = sampling.temperature {
+        part(&temperature.to_le_bytes());
+    }
+    // Named, as they're all four bytes like the temperature
+    for (name, value) in [
+        ("top_p", sampling.top_p.map(f32::to_le_bytes)),
+        ("top_k", sampling.top_k.map(u32::to_le_bytes)),
+        ("seed", sampling.seed.map(i32::to_le_bytes)),
+        ("max_output_tokens", sampling.max_output_tokens.map(u32::to_le_bytes)),
+    ] {
+        if let Some(value) = value {
+            part(name.as_bytes());
+            part(&value);
+        }
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        let images = vec![ImagePart { mime_type: "image/jpeg", data: "aGVsbG8=".to_string(), label: None }];
+        let schema = serde_json::json!({ "type": "OBJECT" });
+        let none = Sampling::default();
+        let key = cache_key("gemini-3.0-flash", None, "Describe this", &images, Some(&schema), &none);
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, cache_key("gemini-3.0-flash", None, "Describe this", &images, Some(&schema), &none));
+
+        // Any input that can change the response changes the key
+        assert_ne!(key, cache_key("gemini-3.0-pro", None, "Describe this", &images, Some(&schema), &none));
+        assert_ne!(key, cache_key("gemini-3.0-flash", None, "Describe that", &images, Some(&schema), &none));
+        assert_ne!(key, cache_key("gemini-3.0-flash", None, "Describe this", &[], Some(&schema), &none));
+        let labelled = vec![images[0].clone().with_label("[Frame at 00:30]")];
+        assert_ne!(key, cache_key("gemini-3.0-flash", None, "Describe this", &labelled, Some(&schema), &none));
+        assert_ne!(key, cache_key("gemini-3.0-flash", None, "Describe this", &images, None, &none));
+        assert_ne!(key, cache_key("gemini-3.0-flash", Some("Be brief."), "Describe this", &images, Some(&schema), &none));
+        assert_ne!(key, cache_key("gemini-3.0-flash", None, "Describe this", &images, Some(&schema), &Sampling::at_temperature(1.2)));
+        let seeded = Sampling { seed: Some(7), ..none };
+        assert_ne!(key, cache_key("gemini-3.0-flash", None, "Describe this", &images, Some(&schema), &seeded));
+        assert_ne!(
+            cache_key("gemini-3.0-flash", None, "Describe this", &images, Some(&schema), &Sampling { top_k: Some(7), ..none }),
+            cache_key("gemini-3.0-flash", None, "Describe this", &images, Some(&schema), &seeded)
+        );
+        assert_ne!(
+            cache_key("ab", None, "c", &[], None, &none),
+            cache_key("a", None, "bc", &[], None, &none),
+        );
+    }
+}
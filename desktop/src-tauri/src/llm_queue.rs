@@ -0,0 +1,254 @@
+//! LLM Request Queue
+//!
+//! App-wide rate limiting for Gemini requests. Every request, from any
+//! engine, waits for a slot here first, so batch work such as enriching a
+//! long track queues up instead of tripping the API's rate limits. A rate
+//! limit response pauses the whole queue for as long as the API asks.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use crate::settings;
+
+/// Request caps, shared by all Gemini requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    pub requests_per_minute: u32,
+    /// Requests in flight at once
+    pub max_concurrent: usize,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        // The free tier's limit for Flash models
+        Self {
+            requests_per_minute: 15,
+            max_concurrent: 4,
+        }
+    }
+}
+
+impl RateLimit {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.requests_per_minute == 0 || self.max_concurrent == 0 {
+            return Err("Limits must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Snapshot of the queue, for the UI
+#[derive(Debug, Clone, Serialize)]
+pub struct LlmQueueStatus {
+    /// Requests waiting for a slot
+    pub queued: usize,
+    pub in_flight: usize,
+    pub sent_last_minute: usize,
+    pub limits: RateLimit,
+    /// Remaining wait asked for by the API's last rate limit response
+    pub paused_for_ms: Option<u64>,
+}
+
+static SHARED: Lazy<Arc<RateLimiter>> =
+    Lazy::new(|| Arc::new(RateLimiter::new(settings::get().gemini_rate_limit, Duration::from_secs(60))));
+
+/// The limiter all Gemini clients share
+pub fn shared() -> Arc<RateLimiter> {
+    SHARED.clone()
+}
+
+/// Sliding-window request rate and concurrency limiter
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+    /// Span `requests_per_minute` applies to; a minute outside of tests
+    window: Duration,
+    queued: AtomicUsize,
+    /// Woken when a slot frees up or the limits change
+    changed: Notify,
+}
+
+struct LimiterState {
+    limits: RateLimit,
+    /// Start times of requests within the window, oldest first
+    sent: VecDeque<Instant>,
+    in_flight: usize,
+    paused_until: Option<Instant>,
+}
+
+/// A request slot, released when dropped
+pub struct RatePermit<'a> {
+    limiter: &'a RateLimiter,
+}
+
+impl Drop for RatePermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.lock().in_flight -= 1;
+        self.limiter.changed.notify_waiters();
+    }
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimit, window: Duration) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                limits,
+                sent: VecDeque::new(),
+                in_flight: 0,
+                paused_until: None,
+            }),
+            window,
+            queued: AtomicUsize::new(0),
+            changed: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LimiterState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait until a request may be sent under the current limits
+    pub async fn acquire(&self) -> RatePermit<'_> {
+        // Counted as queued until this returns or the wait is given up
+        struct Queued<'a>(&'a AtomicUsize);
+        impl Drop for Queued<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        self.queued.fetch_add(1, Ordering::SeqCst);
+        let _queued = Queued(&self.queued);
+
+        loop {
+            // Registered before checking, so a slot freed meanwhile isn't missed
+            let changed = self.changed.notified();
+            tokio::pin!(changed);
+            changed.as_mut().enable();
+
+            let wait = {
+                let mut state = self.lock();
+                let now = Instant::now();
+                while state.sent.front().is_some_and(|t| now.duration_since(*t) >= self.window) {
+                    state.sent.pop_front();
+                }
+
+                if let Some(until) = state.paused_until.filter(|until| *until > now) {
+                    Some(until - now)
+                } else if state.in_flight >= state.limits.max_concurrent.max(1) {
+                    None
+                } else if state.sent.len() >= state.limits.requests_per_minute.max(1) as usize {
+                    state.sent.front().map(|oldest| *oldest + self.window - now)
+                } else {
+                    state.sent.push_back(now);
+                    state.in_flight += 1;
+                    break;
+                }
+            };
+
+            match wait {
+                Some(delay) => {
+                    debug!("Gemini request queued for {:?}", delay);
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = changed => {}
+                    }
+                }
+                None => changed.await,
+            }
+        }
+
+        RatePermit { limiter: self }
+    }
+
+    /// Hold all requests for `delay`, as asked by a rate limit response
+    pub fn pause_for(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut state = self.lock();
+        match state.paused_until {
+            Some(current) if current >= until => {}
+            _ => {
+                warn!("Gemini rate limit hit, pausing requests for {:?}", delay);
+                state.paused_until = Some(until);
+            }
+        }
+    }
+
+    /// Change the limits; queued requests are re-checked right away
+    pub fn set_limits(&self, limits: RateLimit) {
+        self.lock().limits = limits;
+        self.changed.notify_waiters();
+        info!("Gemini rate limit set to {:?}", limits);
+    }
+
+    pub fn status(&self) -> LlmQueueStatus {
+        let state = self.lock();
+        let now = Instant::now();
+        LlmQueueStatus {
+            queued: self.queued.load(Ordering::SeqCst),
+            in_flight: state.in_flight,
+            sent_last_minute: state.sent.iter().filter(|t| now.duration_since(**t) < self.window).count(),
+            limits: state.limits,
+            paused_for_ms: state
+                .paused_until
+                .filter(|until| *until > now)
+                .map(|until| (until - now).as_millis() as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_queues_beyond_limits() {
+        let limiter = Arc::new(RateLimiter::new(
+            RateLimit { requests_per_minute: 2, max_concurrent: 1 },
+            Duration::from_millis(200),
+        ));
+
+        let first = limiter.acquire().await;
+        let waiting = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move {
+                let _permit = limiter.acquire().await;
+                Instant::now()
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(limiter.status().queued, 1);
+        assert_eq!(limiter.status().in_flight, 1);
+
+        // Released concurrency slot lets the second through
+        let released = Instant::now();
+        drop(first);
+        assert!(waiting.await.unwrap() >= released);
+
+        // Third request waits for the window
+        let start = Instant::now();
+        let _third = limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(150), "{:?}", start.elapsed());
+
+        // A wait that's given up, like a cancelled job's, leaves the queue
+        let given_up = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(given_up.is_err());
+        assert_eq!(limiter.status().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pause_holds_requests() {
+        let limiter = RateLimiter::new(RateLimit::default(), Duration::from_secs(60));
+        limiter.pause_for(Duration::from_millis(100));
+        assert!(limiter.status().paused_for_ms.is_some());
+
+        let start = Instant::now();
+        let _permit = limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,421 @@
+//! Local LLM Backend
+//!
+//! Generates text with a model served on this machine by Ollama or the
+//! llama.cpp server, through their OpenAI-compatible `/v1/chat/completions`
+//! API. Requests skip the Gemini rate limiter and response cache: they cost
+//! nothing but local compute.
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
+
+use crate::gemini::GeminiError;
+use crate::llm::{BackendFuture, FinishReason, Generation, ImagePart, LlmBackend, Sampling, TokenUsage};
+use crate::settings;
+
+/// Where the local model server runs and which model it serves
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocalLlmSettings {
+    /// Server base URL, without the `/v1` path
+    pub url: String,
+    pub model: String,
+    /// Whether the model accepts images; frames are left out otherwise
+    pub vision: bool,
+    /// Time allowed for a whole request, so a stalled server doesn't hang narration
+    pub timeout_secs: u64,
+}
+
+impl Default for LocalLlmSettings {
+    fn default() -> Self {
+        // Ollama's default port and a small model that runs on most laptops
+        Self {
+            url: "http://localhost:11434".to_string(),
+            model: "llama3.2".to_string(),
+            vision: false,
+            // Generous: a large model on a laptop CPU writes slowly
+            timeout_secs: 600,
+        }
+    }
+}
+
+impl LocalLlmSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => return Err(format!("Invalid server URL: {}", self.url)),
+        }
+        if self.model.trim().is_empty() {
+            return Err("Model name is required".to_string());
+        }
+        if self.timeout_secs == 0 {
+            return Err("Timeout must be at least 1 second".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Client for a local OpenAI-compatible server, configured from the settings
+pub struct LocalBackend {
+    client: Client,
+    /// Fixed configuration, instead of the one from the settings
+    config: Option<LocalLlmSettings>,
+}
+
+impl LocalBackend {
+    /// Backend using the local server from the settings, looked up on every request
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            config: None,
+        }
+    }
+
+    fn config(&self) -> LocalLlmSettings {
+        self.config.clone().unwrap_or_else(|| settings::get().local_llm)
+    }
+
+    /// Generate text, with the `sampling` parameters that are set
+    pub async fn generate_multimodal(
+        &self,
+        system_instruction: Option<&str>,
+        prompt: &str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        sampling: Sampling,
+    ) -> Result<Generation, GeminiError> {
+        let config = self.config();
+        let url = format!("{}/v1/chat/completions", config.url.trim_end_matches('/'));
+
+        let images = if config.vision {
+            images
+        } else {
+            if !images.is_empty() {
+                warn!(
+                    "Local model {} is text-only, leaving out {} image(s)",
+                    config.model,
+                    images.len()
+                );
+            }
+            Vec::new()
+        };
+        let mut request = request_body(&config.model, system_instruction, prompt, &images, response_schema.is_some());
+        sample_with(&mut request, &sampling);
+
+        debug!("Sending request to local model ({} at {})...", config.model, config.url);
+        let response = self
+            .client
+            .post(&url)
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_connect() {
+                    GeminiError::LocalUnavailable(config.url.clone())
+                } else if e.is_timeout() {
+                    GeminiError::LocalTimeout(config.timeout_secs)
+                } else {
+                    GeminiError::Network(e.without_url().to_string())
+                }
+            })?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    GeminiError::LocalTimeout(config.timeout_secs)
+                } else {
+                    GeminiError::Network(e.without_url().to_string())
+                }
+            })?;
+        if !status.is_success() {
+            error!("Local model error ({}): {}", status, body);
+            return Err(GeminiError::Local {
+                status: status.as_u16(),
+                message: error_message(&body),
+            });
+        }
+
+        let generation = response_text(&body)?;
+        if generation.is_truncated() {
+            warn!("Local model response was cut off at the token limit");
+        }
+        info!("Local model response received");
+        Ok(generation)
+    }
+}
+
+impl Default for LocalBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LlmBackend for LocalBackend {
+    fn generate_with_system<'a>(
+        &'a self,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        _allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        Box::pin(LocalBackend::generate_multimodal(
+            self,
+            system_instruction,
+            prompt,
+            images,
+            response_schema,
+            Sampling::default(),
+        ))
+    }
+
+    fn generate_with_sampling<'a>(
+        &'a self,
+        sampling: Sampling,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        _allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        Box::pin(LocalBackend::generate_multimodal(self, system_instruction, prompt, images, response_schema, sampling))
+    }
+
+    fn model(&self) -> String {
+        self.config().model
+    }
+
+    fn engine(&self) -> &'static str {
+        "local"
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+}
+
+/// Build a chat completion request with one user message, after the system
+/// message if there is one
+///
+/// Text-only requests send the prompt as plain string content, which every
+/// server accepts; images use the content-part form. The Gemini schema
+/// dialect isn't JSON Schema, so only JSON mode is asked for.
+fn request_body(
+    model: &str,
+    system_instruction: Option<&str>,
+    prompt: &str,
+    images: &[ImagePart],
+    json_output: bool,
+) -> serde_json::Value {
+    let content = if images.is_empty() {
+        json!(prompt)
+    } else {
+        let mut parts = vec![json!({ "type": "text", "text": prompt })];
+        for image in images {
+            if let Some(label) = &image.label {
+                parts.push(json!({ "type": "text", "text": label }));
+            }
+            parts.push(json!({
+                "type": "image_url",
+                "image_url": { "url": format!("data:{};base64,{}", image.mime_type, image.data) },
+            }));
+        }
+        json!(parts)
+    };
+
+    let mut messages = Vec::new();
+    if let Some(system) = system_instruction {
+        messages.push(json!({ "role": "system", "content": system }));
+    }
+    messages.push(json!({ "role": "user", "content": content }));
+
+    let mut body = json!({
+        "model": model,
+        "messages": messages,
+        "stream": false,
+    });
+    if json_output {
+        body["response_format"] = json!({ "type": "json_object" });
+    }
+    body
+}
+
+/// Set the `sampling` parameters that are set on a chat completion request
+///
+/// `top_k` and `seed` aren't in the OpenAI API, but llama.cpp, Ollama and
+/// LM Studio take them; servers that don't ignore them.
+fn sample_with(request: &mut serde_json::Value, sampling: &Sampling) {
+    let parameters = [
+        ("temperature", sampling.temperature.map(|t| json!(t))),
+        ("top_p", sampling.top_p.map(|p| json!(p))),
+        ("top_k", sampling.top_k.map(|k| json!(k))),
+        ("seed", sampling.seed.map(|s| json!(s))),
+        ("max_tokens", sampling.max_output_tokens.map(|n| json!(n))),
+    ];
+    for (name, value) in parameters {
+        if let Some(value) = value {
+            request[name] = value;
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    #[serde(default)]
+    choices: Vec<ChatChoice>,
+    usage: Option<ChatUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoice {
+    message: ChatMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// Extract the generated text and finish reason from a chat completion response
+fn response_text(body: &str) -> Result<Generation, GeminiError> {
+    let response: ChatCompletionResponse =
+        serde_json::from_str(body).map_err(|e| GeminiError::MalformedResponse(e.to_string()))?;
+
+    let usage = response.usage.map(|u| TokenUsage {
+        prompt_tokens: u.prompt_tokens,
+        output_tokens: u.completion_tokens,
+    });
+    let choice = response
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| GeminiError::MalformedResponse("no content generated".to_string()))?;
+    let finish_reason = match choice.finish_reason.as_deref() {
+        None | Some("stop") => FinishReason::Stop,
+        Some("length") => FinishReason::MaxTokens,
+        Some(other) => FinishReason::Other(other.to_string()),
+    };
+
+    match choice.message.content.filter(|text| !text.is_empty()) {
+        Some(text) => Ok(Generation { text, finish_reason, usage }),
+        None if finish_reason == FinishReason::MaxTokens => Ok(Generation { text: String::new(), finish_reason, usage }),
+        None => Err(GeminiError::MalformedResponse("no content generated".to_string())),
+    }
+}
+
+/// The message of an OpenAI-style `{"error": {"message": ...}}` body, or the
+/// body itself (Ollama sends `{"error": "..."}`)
+fn error_message(body: &str) -> String {
+    let parsed: Option<serde_json::Value> = serde_json::from_str(body).ok();
+    parsed
+        .as_ref()
+        .and_then(|v| v["error"]["message"].as_str().or_else(|| v["error"].as_str()))
+        .unwrap_or(body)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_body() {
+        let body = request_body("llama3.2", None, "Describe the drive", &[], true);
+        assert_eq!(body["model"], "llama3.2");
+        assert_eq!(body["messages"][0]["content"], "Describe the drive");
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert_eq!(body["stream"], false);
+
+        let image = ImagePart { mime_type: "image/png", data: "AAAA".to_string(), label: None };
+        let body = request_body("llava", Some("You are a tour guide."), "What's this?", &[image.clone().with_label("[Frame at 00:12]"), image], false);
+        assert_eq!(body["messages"][0], json!({ "role": "system", "content": "You are a tour guide." }));
+        let parts = body["messages"][1]["content"].as_array().unwrap();
+        // The label goes just before its image
+        assert_eq!(parts.len(), 4);
+        assert_eq!(parts[1], json!({ "type": "text", "text": "[Frame at 00:12]" }));
+        assert_eq!(parts[2]["image_url"]["url"], "data:image/png;base64,AAAA");
+        assert_eq!(parts[3]["type"], "image_url");
+        assert!(body.get("response_format").is_none());
+
+        // Only the sampling parameters that are set go in
+        let mut body = request_body("llama3.2", None, "Describe the drive", &[], false);
+        sample_with(&mut body, &Sampling { seed: Some(42), max_output_tokens: Some(1024), ..Sampling::at_temperature(0.7) });
+        assert_eq!((body["temperature"].as_f64().unwrap() * 10.0).round(), 7.0);
+        assert_eq!((body["seed"].clone(), body["max_tokens"].clone()), (json!(42), json!(1024)));
+        assert!(body.get("top_p").is_none() && body.get("top_k").is_none());
+    }
+
+    #[test]
+    fn test_response_parsing() {
+        let body = r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"hello"}}]}"#;
+        assert_eq!(response_text(body).unwrap(), Generation::complete("hello"));
+        assert!(matches!(response_text(r#"{"choices":[]}"#), Err(GeminiError::MalformedResponse(_))));
+
+        let body = r#"{"choices":[{"message":{"content":"{\"chap"},"finish_reason":"length"}],
+            "usage":{"prompt_tokens":120,"completion_tokens":64,"total_tokens":184}}"#;
+        let generation = response_text(body).unwrap();
+        assert!(generation.is_truncated());
+        assert_eq!(generation.usage, Some(TokenUsage { prompt_tokens: 120, output_tokens: 64 }));
+
+        assert_eq!(error_message(r#"{"error":{"message":"model not found"}}"#), "model not found");
+        assert_eq!(error_message(r#"{"error":"model 'x' not found"}"#), "model 'x' not found");
+        assert_eq!(error_message("Bad Gateway"), "Bad Gateway");
+    }
+
+    #[tokio::test]
+    async fn test_server_not_running() {
+        // Grab a free port and close it again, so nothing is listening there
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let backend = LocalBackend {
+            client: Client::new(),
+            config: Some(LocalLlmSettings { url: url.clone(), ..Default::default() }),
+        };
+        let err = backend.generate_multimodal(None, "hello", vec![], None, Sampling::default()).await.unwrap_err();
+        assert_eq!(err, GeminiError::LocalUnavailable(url));
+    }
+
+    #[tokio::test]
+    async fn test_stalled_server_times_out() {
+        use tokio::io::AsyncReadExt;
+
+        // Accepts the request and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+        });
+
+        let backend = LocalBackend {
+            client: Client::new(),
+            config: Some(LocalLlmSettings { url, timeout_secs: 1, ..Default::default() }),
+        };
+        let started = std::time::Instant::now();
+        let err = backend.generate_multimodal(None, "hello", vec![], None, Sampling::default()).await.unwrap_err();
+        assert_eq!(err, GeminiError::LocalTimeout(1));
+        assert_eq!(err.kind(), "local_timeout");
+        assert!(started.elapsed() < std::time::Duration::from_secs(3));
+    }
+}
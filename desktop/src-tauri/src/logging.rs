@@ -0,0 +1,329 @@
+//! Logging
+//!
+//! Console output plus a daily rolling log file under the app data
+//! directory, so logs from packaged builds can be collected from users.
+//! The filter comes from `RUST_LOG`, else the settings, and can be changed
+//! while the app runs. Every line passes through redaction on its way
+//! out, so a key or the user's home directory that slips into a message
+//! doesn't reach the console or the files users send in. What jobs log is
+//! also kept with each job.
+
+use once_cell::sync::{Lazy, OnceCell};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing::Level;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry};
+
+use crate::job_log::JobLogLayer;
+use crate::{secrets, settings};
+
+/// Filter used when neither `RUST_LOG` nor the settings specify one
+pub const DEFAULT_FILTER: &str = "info,geotruth_lib=debug";
+
+const LOG_FILE_PREFIX: &str = "geotruth";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Daily files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 7;
+
+/// Total size of the kept log files; older files go first once it's exceeded
+const MAX_LOG_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Most lines `recent_lines` returns
+pub const MAX_RECENT_LINES: usize = 5000;
+
+static FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+/// Keeps the background file writer alive; dropping it stops file logging
+static FILE_GUARD: OnceCell<WorkerGuard> = OnceCell::new();
+
+static HOME: Lazy<Option<String>> = Lazy::new(|| dirs::home_dir().map(|home| home.display().to_string()));
+
+/// Directory holding the log files
+pub fn log_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("com.geotruth.app")
+        .join("logs")
+}
+
+/// Install the global subscriber
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| parse_filter(settings::get().log_level.as_deref()))
+        .unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let file = match file_writer() {
+        Ok(writer) => Some(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || Redacting(writer.clone())),
+        ),
+        Err(e) => {
+            // No subscriber yet to report this through
+            eprintln!("Logging to file disabled: {}", e);
+            None
+        }
+    };
+
+    // Pretty output for development, JSON in production
+    #[cfg(debug_assertions)]
+    let console = fmt::layer()
+        .with_target(true)
+        .with_thread_ids(false)
+        .with_file(true)
+        .with_line_number(true)
+        .with_writer(|| Redacting(io::stdout()));
+    #[cfg(not(debug_assertions))]
+    let console = fmt::layer().json().with_writer(|| Redacting(io::stdout()));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(file)
+        .with(JobLogLayer)
+        .init();
+
+    FILTER.set(handle).ok();
+}
+
+/// Replace the log filter, `None` restoring the default
+pub fn set_filter(directives: Option<&str>) -> Result<(), String> {
+    let filter = parse_filter(directives)?;
+    FILTER
+        .get()
+        .ok_or_else(|| "Logging is not initialized".to_string())?
+        .reload(filter)
+        .map_err(|e| e.to_string())
+}
+
+/// Parse a filter like `debug` or `info,geotruth_lib=trace`
+pub fn parse_filter(directives: Option<&str>) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(directives.unwrap_or(DEFAULT_FILTER)).map_err(|e| format!("Invalid log level: {}", e))
+}
+
+/// The last `count` lines logged to file, oldest first
+///
+/// With a `min_level` only lines at that level or more severe are kept;
+/// lines continuing a multi-line message go with the line they continue.
+pub fn recent_lines(count: usize, min_level: Option<Level>) -> Result<Vec<String>, String> {
+    let count = count.min(MAX_RECENT_LINES);
+    let mut lines = Vec::new();
+    for file in log_files(&log_dir())?.into_iter().rev() {
+        if lines.len() >= count {
+            break;
+        }
+        let text = std::fs::read_to_string(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        let mut kept = filter_lines(&text, min_level);
+        kept.append(&mut lines);
+        lines = kept;
+    }
+    Ok(lines.split_off(lines.len().saturating_sub(count)))
+}
+
+/// Parse a level name like `warn` for `recent_lines`
+pub fn parse_level(level: &str) -> Result<Level, String> {
+    level.trim().parse().map_err(|_| format!("Invalid log level: {}", level))
+}
+
+fn filter_lines(text: &str, min_level: Option<Level>) -> Vec<String> {
+    let mut keep = true;
+    text.lines()
+        .filter(|line| {
+            // Lines look like `2026-10-16T09:30:00.000000Z  INFO target: message`
+            if let Some(level) = line_level(line) {
+                keep = !matches!(min_level, Some(min) if level > min);
+            }
+            keep
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+fn line_level(line: &str) -> Option<Level> {
+    let mut tokens = line.split_whitespace();
+    let timestamp = tokens.next()?;
+    let level = tokens.next()?;
+    if !timestamp.starts_with(|c: char| c.is_ascii_digit()) || !level.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    level.parse().ok()
+}
+
+/// Log files in `dir`, oldest first
+///
+/// File names end in the date, so name order is age order.
+fn log_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(LOG_FILE_PREFIX) && name.ends_with(LOG_FILE_SUFFIX))
+        })
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Delete the oldest log files until the rest fit in `max_bytes`
+///
+/// The newest file is always kept, however large.
+fn prune_logs(dir: &Path, max_bytes: u64) -> Result<(), String> {
+    let mut total = 0;
+    for (i, file) in log_files(dir)?.iter().rev().enumerate() {
+        total += std::fs::metadata(file).map(|m| m.len()).unwrap_or(0);
+        if i > 0 && total > max_bytes {
+            std::fs::remove_file(file).map_err(|e| format!("Failed to delete {}: {}", file.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Replace the home directory with `~`, both as written and as `{:?}` escapes it
+fn redact_home(text: &str, home: &str) -> String {
+    if home.len() < 2 {
+        return text.to_string();
+    }
+    let escaped = home.escape_debug().to_string();
+    let text = text.replace(home, "~");
+    if escaped == home {
+        text
+    } else {
+        text.replace(&escaped, "~")
+    }
+}
+
+/// Writer that masks API keys and the home directory in each formatted
+/// event before passing it on
+///
+/// The formatter writes an event in one go, so a key is never split
+/// across two writes.
+struct Redacting<W>(W);
+
+impl<W: Write> Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut text = secrets::redact(&String::from_utf8_lossy(buf));
+        if let Some(home) = HOME.as_deref() {
+            text = redact_home(&text, home);
+        }
+        self.0.write_all(text.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+fn file_writer() -> Result<NonBlocking, String> {
+    let dir = log_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    if let Err(e) = prune_logs(&dir, MAX_LOG_BYTES) {
+        eprintln!("Failed to prune old log files: {}", e);
+    }
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+        .map_err(|e| e.to_string())?;
+
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    FILE_GUARD.set(guard).ok();
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_parse_filter() {
+        assert!(parse_filter(None).is_ok());
+        assert!(parse_filter(Some("debug")).is_ok());
+        assert!(parse_filter(Some("info,geotruth_lib=trace")).is_ok());
+        assert!(parse_filter(Some("geotruth_lib=loud")).unwrap_err().starts_with("Invalid log level"));
+    }
+
+    #[test]
+    fn test_keys_are_redacted_from_log_lines() {
+        let mut writer = Redacting(Vec::new());
+        let line = "DEBUG GET https://example.com/models?key=AIzaSyExampleKey1234&pageSize=1000\n";
+        assert_eq!(writer.write(line.as_bytes()).unwrap(), line.len());
+
+        let written = String::from_utf8(writer.0).unwrap();
+        assert_eq!(written, "DEBUG GET https://example.com/models?key=••••1234&pageSize=1000\n");
+    }
+
+    #[test]
+    fn test_home_directory_is_redacted() {
+        assert_eq!(
+            redact_home("Opened /home/alex/Videos/trip.mp4", "/home/alex"),
+            "Opened ~/Videos/trip.mp4"
+        );
+        assert_eq!(
+            redact_home(r#"path: "C:\\Users\\alex\\Videos""#, r"C:\Users\alex"),
+            r#"path: "~\\Videos""#
+        );
+        assert_eq!(redact_home("Opened /tmp/trip.mp4", "/"), "Opened /tmp/trip.mp4");
+    }
+
+    #[test]
+    fn test_filter_lines_by_level() {
+        let text = "\
+2026-10-16T09:30:00.000000Z  INFO geotruth_lib: started
+2026-10-16T09:30:01.000000Z  WARN geotruth_lib: slow request
+  continued on a second line
+2026-10-16T09:30:02.000000Z DEBUG geotruth_lib: details
+2026-10-16T09:30:03.000000Z ERROR geotruth_lib: failed
+";
+        assert_eq!(filter_lines(text, None).len(), 5);
+        assert_eq!(
+            filter_lines(text, Some(Level::WARN)),
+            vec![
+                "2026-10-16T09:30:01.000000Z  WARN geotruth_lib: slow request",
+                "  continued on a second line",
+                "2026-10-16T09:30:03.000000Z ERROR geotruth_lib: failed",
+            ]
+        );
+        assert_eq!(parse_level("warn").unwrap(), Level::WARN);
+        assert!(parse_level("loud").is_err());
+    }
+
+    #[test]
+    fn test_prune_keeps_newest_files_within_cap() {
+        let dir = fixtures::temp_dir("logs");
+        for day in ["2026-10-13", "2026-10-14", "2026-10-15", "2026-10-16"] {
+            std::fs::write(dir.join(format!("geotruth.{}.log", day)), vec![b'x'; 100]).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), vec![b'x'; 1000]).unwrap();
+
+        prune_logs(&dir, 250).unwrap();
+        let names: Vec<String> = log_files(&dir)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["geotruth.2026-10-15.log", "geotruth.2026-10-16.log"]);
+        assert!(dir.join("notes.txt").exists());
+
+        // The newest file stays even when it alone is over the cap
+        prune_logs(&dir, 10).unwrap();
+        assert_eq!(log_files(&dir).unwrap().len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,282 @@
+//! In-Memory Caches
+//!
+//! Bounded caches of values worth keeping around for a while, such as
+//! Truth Bundles and enrichments, so a long session can't hold on to
+//! hundreds of megabytes of them. Each cache is held to a number of entries
+//! and an estimate of their size, dropping the least recently used entries
+//! to stay within both, and forgets entries older than its time to live.
+//! What's dropped can always be worked out or read from the database again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How much a cache may hold, and for how long
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheLimits {
+    pub max_entries: usize,
+    /// Most bytes the entries may take, going by their size as JSON
+    pub max_bytes: u64,
+    /// How long an entry is kept after it's added (`None` = until evicted)
+    pub ttl_seconds: Option<u64>,
+}
+
+impl Default for CacheLimits {
+    fn default() -> Self {
+        Self { max_entries: 10_000, max_bytes: 64 * 1024 * 1024, ttl_seconds: Some(60 * 60) }
+    }
+}
+
+impl CacheLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_entries == 0 || self.max_bytes == 0 {
+            return Err("A cache must be allowed at least one entry and one byte".to_string());
+        }
+        if self.ttl_seconds == Some(0) {
+            return Err("A cache's time to live must be at least a second".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// How a cache is doing
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub limits: CacheLimits,
+    pub hits: u64,
+    pub misses: u64,
+    /// Dropped to stay within the limits
+    pub evictions: u64,
+    /// Dropped for outliving the time to live
+    pub expirations: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    bytes: u64,
+    added: Instant,
+    /// Position in the recency order
+    used: u64,
+}
+
+struct Inner<V> {
+    limits: CacheLimits,
+    entries: HashMap<String, Entry<V>>,
+    /// Keys by when they were last used, least recently first
+    recency: BTreeMap<u64, String>,
+    next_use: u64,
+    bytes: u64,
+    stats: CacheStats,
+}
+
+impl<V> Inner<V> {
+    fn remove(&mut self, key: &str) -> Option<Entry<V>> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.used);
+        self.bytes -= entry.bytes;
+        Some(entry)
+    }
+
+    fn is_expired(&self, entry: &Entry<V>, now: Instant) -> bool {
+        self.limits
+            .ttl_seconds
+            .is_some_and(|ttl| now.saturating_duration_since(entry.added) >= Duration::from_secs(ttl))
+    }
+
+    /// Drop the least recently used entries until the cache is within its limits
+    fn evict(&mut self) {
+        while self.entries.len() > self.limits.max_entries || self.bytes > self.limits.max_bytes {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.bytes;
+                self.stats.evictions += 1;
+            }
+        }
+    }
+}
+
+/// A bounded cache of values by key, least recently used out first
+pub struct MemoryCache<V> {
+    inner: Mutex<Inner<V>>,
+}
+
+impl<V: Clone + Serialize> MemoryCache<V> {
+    pub fn new(limits: CacheLimits) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                limits,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                next_use: 0,
+                bytes: 0,
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.get_at(key, Instant::now())
+    }
+
+    fn get_at(&self, key: &str, now: Instant) -> Option<V> {
+        let mut inner = self.lock();
+        let expired = match inner.entries.get(key) {
+            Some(entry) => inner.is_expired(entry, now),
+            None => {
+                inner.stats.misses += 1;
+                return None;
+            }
+        };
+        if expired {
+            inner.remove(key);
+            inner.stats.expirations += 1;
+            inner.stats.misses += 1;
+            return None;
+        }
+
+        let used = inner.next_use;
+        inner.next_use += 1;
+        let entry = inner.entries.get_mut(key)?;
+        let previous = std::mem::replace(&mut entry.used, used);
+        let value = entry.value.clone();
+        inner.recency.remove(&previous);
+        inner.recency.insert(used, key.to_string());
+        inner.stats.hits += 1;
+        Some(value)
+    }
+
+    /// Add `value`, replacing what was kept under `key`
+    ///
+    /// A value bigger than the whole budget isn't kept.
+    pub fn insert(&self, key: String, value: V) {
+        self.insert_at(key, value, Instant::now());
+    }
+
+    fn insert_at(&self, key: String, value: V, now: Instant) {
+        let bytes = serde_json::to_vec(&value).map_or(0, |json| json.len() as u64);
+        let mut inner = self.lock();
+        inner.remove(&key);
+        if bytes > inner.limits.max_bytes {
+            return;
+        }
+        let used = inner.next_use;
+        inner.next_use += 1;
+        inner.recency.insert(used, key.clone());
+        inner.entries.insert(key, Entry { value, bytes, added: now, used });
+        inner.bytes += bytes;
+        inner.evict();
+    }
+
+    pub fn remove(&self, key: &str) -> Option<V> {
+        self.lock().remove(key).map(|entry| entry.value)
+    }
+
+    /// Keep only the entries `keep` says to, returning how many were dropped
+    pub fn retain(&self, mut keep: impl FnMut(&str, &V) -> bool) -> usize {
+        let mut inner = self.lock();
+        let dropped: Vec<String> =
+            inner.entries.iter().filter(|(key, entry)| !keep(key, &entry.value)).map(|(key, _)| key.clone()).collect();
+        for key in &dropped {
+            inner.remove(key);
+        }
+        dropped.len()
+    }
+
+    /// Drop every entry, returning how many there were
+    pub fn clear(&self) -> usize {
+        let mut inner = self.lock();
+        let count = inner.entries.len();
+        inner.entries.clear();
+        inner.recency.clear();
+        inner.bytes = 0;
+        count
+    }
+
+    /// Change the limits, evicting what no longer fits
+    pub fn set_limits(&self, limits: CacheLimits) {
+        let mut inner = self.lock();
+        inner.limits = limits;
+        inner.evict();
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats_at(Instant::now())
+    }
+
+    /// Counts expired entries out first, so they don't look like they're kept
+    fn stats_at(&self, now: Instant) -> CacheStats {
+        let mut inner = self.lock();
+        let expired: Vec<String> =
+            inner.entries.iter().filter(|(_, entry)| inner.is_expired(entry, now)).map(|(key, _)| key.clone()).collect();
+        for key in &expired {
+            inner.remove(key);
+        }
+        inner.stats.expirations += expired.len() as u64;
+        CacheStats { entries: inner.entries.len(), bytes: inner.bytes, limits: inner.limits, ..inner.stats.clone() }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner<V>> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction_keeps_the_cache_bounded() {
+        let limits = CacheLimits { max_entries: 1_000, max_bytes: 200_000, ttl_seconds: None };
+        let cache = MemoryCache::new(limits);
+        // A kept entry in regular use outlasts the others
+        cache.insert("home".to_string(), "x".repeat(100));
+        for i in 0..100_000 {
+            cache.insert(format!("point-{}", i), "x".repeat(i % 500));
+            if i % 100 == 0 {
+                assert!(cache.get("home").is_some(), "evicted after {} inserts", i);
+            }
+            if i % 10_000 == 0 {
+                let stats = cache.stats();
+                assert!(stats.entries <= limits.max_entries && stats.bytes <= limits.max_bytes, "{:?}", stats);
+            }
+        }
+
+        let stats = cache.stats();
+        assert!(stats.entries <= limits.max_entries && stats.bytes <= limits.max_bytes, "{:?}", stats);
+        assert_eq!(stats.evictions, 100_001 - stats.entries as u64);
+        assert!(cache.get("point-99999").is_some());
+        assert!(cache.get("point-0").is_none());
+        // Too big to keep at all
+        cache.insert("huge".to_string(), "x".repeat(300_000));
+        assert!(cache.get("huge").is_none());
+    }
+
+    #[test]
+    fn test_entries_expire_and_are_dropped_on_request() {
+        let cache = MemoryCache::new(CacheLimits { ttl_seconds: Some(60), ..Default::default() });
+        let added = Instant::now();
+        cache.insert_at("old".to_string(), 1, added);
+        cache.insert_at("new".to_string(), 2, added + Duration::from_secs(30));
+        cache.insert_at("other".to_string(), 3, added + Duration::from_secs(30));
+
+        let later = added + Duration::from_secs(61);
+        assert_eq!(cache.get_at("old", later), None);
+        assert_eq!(cache.get_at("new", later), Some(2));
+        assert_eq!(cache.retain(|key, _| key != "other"), 1);
+        let stats = cache.stats_at(later);
+        assert_eq!((stats.entries, stats.hits, stats.misses, stats.expirations), (1, 1, 1, 1));
+
+        assert_eq!(cache.clear(), 1);
+        assert_eq!(cache.stats_at(later).bytes, 0);
+    }
+}
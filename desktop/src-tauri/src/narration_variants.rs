@@ -0,0 +1,318 @@
+//! Narration Variants
+//!
+//! A creator can ask for a few takes of the same narration and pick one.
+//! Each take is summarized so they can be weighed at a glance, and any
+//! narrations can be compared chapter by chapter: chapters from different
+//! takes that start at about the same time share a row, with the script
+//! lines each take speaks under it.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm::{Sampling, MAX_TEMPERATURE};
+use crate::pacing;
+use crate::types::{Chapter, NarrateResponse, ScriptSegment};
+
+/// Most variants generated for one request
+pub const MAX_VARIANTS: u8 = 4;
+
+/// Chapters of different narrations starting within this many seconds of
+/// a row's first one are compared with each other
+const ALIGN_WITHIN_SECONDS: f64 = 15.0;
+
+/// The temperature for each variant a request's options ask for
+///
+/// `variants` is how many, from 1 to [`MAX_VARIANTS`] and 1 when unset;
+/// `variant_temperatures` optionally gives them in order, from 0 to 2.
+/// Variants without one use the model's default.
+pub fn requested(options: &HashMap<String, serde_json::Value>) -> Result<Vec<Option<f32>>, String> {
+    let count = match options.get("variants") {
+        None | Some(serde_json::Value::Null) => 1,
+        Some(value) => value
+            .as_u64()
+            .filter(|n| (1..=MAX_VARIANTS as u64).contains(n))
+            .ok_or_else(|| format!("variants must be a whole number from 1 to {}", MAX_VARIANTS))? as usize,
+    };
+
+    let temperatures: Vec<f32> = match options.get("variant_temperatures") {
+        None | Some(serde_json::Value::Null) => Vec::new(),
+        Some(value) => serde_json::from_value(value.clone())
+            .map_err(|_| "variant_temperatures must be a list of numbers".to_string())?,
+    };
+    if temperatures.len() > count {
+        return Err(format!("{} temperatures given for {} variant(s)", temperatures.len(), count));
+    }
+    if let Some(t) = temperatures.iter().find(|t| !(0.0..=MAX_TEMPERATURE).contains(*t)) {
+        return Err(format!("Temperature {} is outside 0 to {}", t, MAX_TEMPERATURE));
+    }
+
+    Ok((0..count).map(|i| temperatures.get(i).copied()).collect())
+}
+
+/// The sampling for each variant: `base`, at the variant's temperature
+/// where it has one
+///
+/// Each variant after the first moves the seed on by one, or variants at
+/// the same temperature would be the same take.
+pub fn sampling(base: Sampling, temperatures: &[Option<f32>]) -> Vec<Sampling> {
+    temperatures
+        .iter()
+        .enumerate()
+        .map(|(i, temperature)| Sampling {
+            temperature: temperature.or(base.temperature),
+            seed: base.seed.map(|seed| seed.wrapping_add(i as i32)),
+            ..base
+        })
+        .collect()
+}
+
+/// One variant of a narration, at a glance
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct VariantSummary {
+    /// From 1, in the order requested
+    pub variant: usize,
+    /// The stored narration; `None` if it failed or couldn't be saved
+    pub narration_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    pub word_count: usize,
+    pub chapter_count: usize,
+    pub segment_count: usize,
+    /// Seconds the whole script takes to speak
+    pub estimated_duration_seconds: f64,
+    /// Why the variant failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Counts and spoken length of a narration
+pub fn summarize(response: &NarrateResponse) -> VariantSummary {
+    let segments = script(response);
+    let wpm = speech_rate(response);
+    VariantSummary {
+        word_count: segments.iter().map(|s| pacing::word_count(&s.narration)).sum(),
+        chapter_count: response.chapters.len(),
+        segment_count: segments.len(),
+        estimated_duration_seconds: segments
+            .iter()
+            .map(|s| s.estimated_duration_seconds.unwrap_or_else(|| pacing::spoken_seconds(&s.narration, wpm)))
+            .sum(),
+        ..Default::default()
+    }
+}
+
+/// Narrations side by side, a row per chapter
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NarrationComparison {
+    /// Ids of the narrations, in the order of each row's cells
+    pub narration_ids: Vec<String>,
+    pub summaries: Vec<VariantSummary>,
+    pub rows: Vec<ComparisonRow>,
+}
+
+/// Chapters of the narrations starting at about the same time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonRow {
+    /// When the row's earliest chapter starts
+    pub time_code: String,
+    /// One per narration; `None` where it has no chapter here
+    pub cells: Vec<Option<ChapterCell>>,
+    /// Every narration has this chapter, with the same title and script
+    pub identical: bool,
+}
+
+/// A chapter of one narration and the script spoken during it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChapterCell {
+    pub time_code: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The script lines from this chapter's start until the next one's
+    pub narration: Vec<String>,
+    pub word_count: usize,
+}
+
+/// Align `narrations` (id and response) chapter by chapter
+///
+/// Script lines before a narration's first chapter go with that chapter,
+/// and chapters with an unreadable time code are left out.
+pub fn compare(narrations: &[(String, NarrateResponse)]) -> NarrationComparison {
+    // Every readable chapter, as (start, narration, cell), in time order
+    let mut chapters: Vec<(f64, usize, ChapterCell)> = Vec::new();
+    for (index, (_, response)) in narrations.iter().enumerate() {
+        let mut starts: Vec<(f64, &Chapter)> = response
+            .chapters
+            .iter()
+            .filter_map(|c| pacing::time_code_seconds(&c.time_code).map(|start| (start, c)))
+            .collect();
+        starts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (i, &(start, chapter)) in starts.iter().enumerate() {
+            let from = if i == 0 { f64::NEG_INFINITY } else { start };
+            let until = starts.get(i + 1).map_or(f64::INFINITY, |next| next.0);
+            let narration: Vec<String> = script(response)
+                .iter()
+                .filter(|s| pacing::time_code_seconds(&s.time_code).is_some_and(|t| t >= from && t < until))
+                .map(|s| s.narration.trim().to_string())
+                .collect();
+            let cell = ChapterCell {
+                time_code: chapter.time_code.clone(),
+                title: chapter.title.clone(),
+                description: chapter.description.clone(),
+                word_count: narration.iter().map(|line| pacing::word_count(line)).sum(),
+                narration,
+            };
+            chapters.push((start, index, cell));
+        }
+    }
+    chapters.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // A new row when the chapter starts too long after the row's first, or
+    // its narration already has a chapter in the row
+    let mut rows: Vec<(f64, ComparisonRow)> = Vec::new();
+    for (start, index, cell) in chapters {
+        let fits = rows.last().is_some_and(|(row_start, row)| {
+            start - row_start <= ALIGN_WITHIN_SECONDS && row.cells[index].is_none()
+        });
+        if !fits {
+            let row = ComparisonRow {
+                time_code: pacing::format_time_code(start),
+                cells: vec![None; narrations.len()],
+                identical: false,
+            };
+            rows.push((start, row));
+        }
+        if let Some((_, row)) = rows.last_mut() {
+            row.cells[index] = Some(cell);
+        }
+    }
+
+    let rows = rows
+        .into_iter()
+        .map(|(_, mut row)| {
+            let first = row.cells.first().cloned().flatten();
+            row.identical = first.is_some_and(|first| {
+                row.cells.iter().all(|cell| {
+                    cell.as_ref().is_some_and(|c| c.title == first.title && c.narration == first.narration)
+                })
+            });
+            row
+        })
+        .collect();
+
+    NarrationComparison {
+        narration_ids: narrations.iter().map(|(id, _)| id.clone()).collect(),
+        summaries: narrations
+            .iter()
+            .enumerate()
+            .map(|(i, (id, response))| VariantSummary {
+                variant: i + 1,
+                narration_id: Some(id.clone()),
+                temperature: response.meta.get("temperature").and_then(|t| t.parse().ok()),
+                ..summarize(response)
+            })
+            .collect(),
+        rows,
+    }
+}
+
+fn script(response: &NarrateResponse) -> &[ScriptSegment] {
+    response.script.as_ref().map_or(&[], |script| script.segments.as_slice())
+}
+
+/// The speech rate a narration was paced for
+fn speech_rate(response: &NarrateResponse) -> u32 {
+    response
+        .meta
+        .get("speech_rate_wpm")
+        .and_then(|wpm| wpm.parse().ok())
+        .unwrap_or(pacing::DEFAULT_SPEECH_RATE_WPM)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NarrateScript;
+
+    fn narration(chapters: &[(&str, &str)], lines: &[(&str, &str)]) -> NarrateResponse {
+        NarrateResponse {
+            chapters: chapters
+                .iter()
+                .map(|(time_code, title)| Chapter {
+                    time_code: time_code.to_string(),
+                    title: title.to_string(),
+                    description: None,
+                })
+                .collect(),
+            script: Some(NarrateScript {
+                segments: lines
+                    .iter()
+                    .map(|(time_code, narration)| ScriptSegment {
+                        time_code: time_code.to_string(),
+                        narration: narration.to_string(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            }),
+            evidence: HashMap::new(),
+            meta: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_requested_variants() {
+        let options = |json: serde_json::Value| -> HashMap<String, serde_json::Value> {
+            serde_json::from_value(json).unwrap()
+        };
+        assert_eq!(requested(&HashMap::new()).unwrap(), [None]);
+        assert_eq!(
+            requested(&options(serde_json::json!({ "variants": 3, "variant_temperatures": [0.4, 1.2] }))).unwrap(),
+            [Some(0.4), Some(1.2), None]
+        );
+        assert!(requested(&options(serde_json::json!({ "variants": 0 }))).is_err());
+        assert!(requested(&options(serde_json::json!({ "variants": 2, "variant_temperatures": [0.4, 1.2, 0.9] }))).is_err());
+        assert!(requested(&options(serde_json::json!({ "variants": 2, "variant_temperatures": [3.0] }))).is_err());
+
+        let base = Sampling { top_p: Some(0.9), seed: Some(10), ..Sampling::at_temperature(0.8) };
+        let variants = sampling(base, &[None, Some(1.2), None]);
+        let temperatures: Vec<Option<f32>> = variants.iter().map(|s| s.temperature).collect();
+        assert_eq!(temperatures, [Some(0.8), Some(1.2), Some(0.8)]);
+        let seeds: Vec<Option<i32>> = variants.iter().map(|s| s.seed).collect();
+        assert_eq!(seeds, [Some(10), Some(11), Some(12)]);
+        assert!(variants.iter().all(|s| s.top_p == Some(0.9)));
+    }
+
+    #[test]
+    fn test_chapters_are_aligned_side_by_side() {
+        let first = narration(
+            &[("00:00", "Leaving town"), ("01:00", "The coast road"), ("02:30", "Arrival")],
+            &[("00:05", "We set off early."), ("01:10", "The sea opens up on the left."), ("02:40", "And here we are.")],
+        );
+        let second = narration(
+            &[("00:00", "Leaving town"), ("01:08", "Along the cliffs")],
+            &[("00:05", "We set off early."), ("01:20", "Cliffs drop to the water. Hold on tight.")],
+        );
+        let comparison = compare(&[("a".to_string(), first), ("b".to_string(), second)]);
+
+        assert_eq!(comparison.narration_ids, ["a", "b"]);
+        assert_eq!(comparison.summaries[1].word_count, 12);
+        assert_eq!(comparison.summaries[0].chapter_count, 3);
+
+        let time_codes: Vec<&str> = comparison.rows.iter().map(|r| r.time_code.as_str()).collect();
+        assert_eq!(time_codes, ["00:00", "01:00", "02:30"]);
+
+        // Same opening in both
+        assert!(comparison.rows[0].identical);
+        // Different takes on the coast, eight seconds apart
+        let coast = &comparison.rows[1];
+        assert!(!coast.identical);
+        assert_eq!(coast.cells[0].as_ref().unwrap().title, "The coast road");
+        let cliffs = coast.cells[1].as_ref().unwrap();
+        assert_eq!((cliffs.time_code.as_str(), cliffs.word_count), ("01:08", 8));
+        // Only the first has an arrival chapter
+        assert!(comparison.rows[2].cells[1].is_none());
+        assert_eq!(comparison.rows[2].cells[0].as_ref().unwrap().narration, ["And here we are."]);
+    }
+}
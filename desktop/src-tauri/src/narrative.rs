@@ -1,162 +1,2515 @@
-use crate::gemini::GeminiClient;
-use crate::types::{NarrateRequest, NarrateResponse, Chapter, ScriptSegment, NarrateScript};
+use crate::chapter_titles::{self, TitleProblem};
+use crate::citations;
+use crate::delivery;
+use crate::dialogue::{self, Gap};
+use crate::gemini::{GeminiClient, GeminiError, GeminiPurpose};
+use crate::llm::{
+    image_parts, FinishReason, ImageError, ImagePart, LlmBackend, LocalBackend, RoutedBackend, Sampling, SamplingBackend,
+};
+use crate::llm_cache::LlmCache;
+use crate::pacing;
+use crate::prompts::{self, PromptName, PromptTemplate};
+use crate::request_history::{RecordingBackend, RequestHistory};
+use crate::revision::{self, Region, Rewrite, Spliced};
+use crate::services::gps::TrackStats;
+use crate::services::{poi_passes, wikipedia};
+use crate::settings;
+use crate::template_narration;
+use crate::types::{
+    CitationStatus, Chapter, EventKind, NarrateRequest, NarrateResponse, NarrateScript, NarrationAudience, NarrationOptions, NarrationRevision,
+    NarrationTone, RouteSegment, SceneFrame, ScriptSegment, TruthEvent, POI, WeatherObservation, MAX_HUMOR_LEVEL,
+};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use tracing::{info, warn};
 use std::collections::HashMap;
+use std::sync::Arc;
 
-pub struct NarrativeEngine {
-    gemini: GeminiClient,
+/// How much of the request goes into a narration prompt
+#[derive(Debug, Clone, Copy)]
+struct PromptLimits {
+    events: usize,
+    transcript_chars: usize,
+    /// Ask for fewer chapters and shorter lines
+    brief: bool,
 }
 
-impl NarrativeEngine {
-    pub fn new() -> Self {
+/// Most scene frames sent with a narration; more are thinned out evenly over the video
+const MAX_NARRATION_FRAMES: usize = 16;
+
+/// Prompt note for frames sent with their times
+const FRAMES_NOTE: &str = "Scene frames follow this prompt, each after a [Frame at MM:SS] marker giving \
+its time into the video, on the same timeline as your time codes. Tie what a frame shows to the events and \
+narration at that time.";
+
+/// Prompt note for the events where the route approaches and passes landmarks
+const PASSES_NOTE: &str = "Approaching and passing events mark when the route closes in on a landmark and when \
+it's nearest. Introduce the landmark as it comes up (\"coming up on the left, ...\"), not after it's gone by.";
+
+/// Prompt note for events with a local time
+const LOCAL_TIME_NOTE: &str = "Event times are UTC; where a local time follows, that's the clock where the footage \
+was shot. Say the time of day by the local time (\"at a quarter to eleven in the morning\"), never by UTC.";
+
+/// Prompt note for events whose time is a guess
+const UNCERTAIN_TIME_NOTE: &str = "Times marked uncertain may be off, e.g. by a camera clock that was never set. \
+Keep their order and spacing, but don't state the time of day they'd put an event at; hedge it (\"later that \
+day\") or leave it out.";
+
+/// Landmarks whose article summary goes in a narration prompt, and how much of each
+const MAX_BACKGROUND_POIS: usize = 8;
+const BACKGROUND_EXTRACT_CHARS: usize = 300;
+
+/// Used to retry a narration chunk that ran into the output token limit
+const SHORT_PROMPT: PromptLimits = PromptLimits { events: 8, transcript_chars: 800, brief: true };
+
+/// Follow-up request for a narration response that wasn't valid JSON
+const REFORMAT_PROMPT: &str = "Reformat the following narration as strict JSON: one object with \
+\"chapters\" (time_code, title, description) and \"script\" (time_code, narration, source_refs) arrays. Keep the \
+wording as it is. Return ONLY the JSON, with no markdown or explanation.";
+
+/// Follow-up request for script segments too long for their slot
+const TRIM_PROMPT: &str = "These narration lines are too long to speak before the next line starts. \
+Shorten each one to at most the number of words given, keeping its facts, tone and language.";
+
+/// Follow-up request for chapter titles that are missing, generic or repeated
+const TITLE_PROMPT: &str = "These chapters of a travel video need new titles: the ones they have are empty, \
+only say which chapter they are, or repeat another chapter's. Write a title for each from what the chapter covers, \
+different from every other chapter's title.";
+
+/// Narration as the model returns it
+#[derive(serde::Deserialize)]
+struct NarrationOutput {
+    chapters: Vec<Chapter>,
+    script: Vec<ScriptSegment>,
+}
+
+fn parse_narration(text: &str) -> Result<NarrationOutput> {
+    let json = extract_json(text).context("Failed to find JSON in the model's response")?;
+    serde_json::from_str(&json).context("Failed to map JSON to output structure")
+}
+
+/// How much of a long narration request goes into each model request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NarrationChunking {
+    pub max_events: usize,
+    pub max_transcript_chars: usize,
+}
+
+impl Default for NarrationChunking {
+    fn default() -> Self {
+        // Comfortable for Flash models; Pro models take several times more
         Self {
-            gemini: GeminiClient::new(),
+            max_events: 20,
+            max_transcript_chars: 2000,
+        }
+    }
+}
+
+impl NarrationChunking {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_events == 0 || self.max_transcript_chars == 0 {
+            return Err("Chunks need room for at least one event and one transcript character".to_string());
         }
+        Ok(())
+    }
+}
+
+/// Progress of a narration, reported as each chunk starts and finishes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NarrationProgress {
+    /// Of the variants narrated at once, from 1
+    pub variant: usize,
+    /// From 1
+    pub chunk: usize,
+    pub chunk_count: usize,
+    pub message: String,
+    /// Whether `chunk` is done, rather than just started
+    pub finished: bool,
+    /// What a finished chunk narrated, on the video's timeline
+    ///
+    /// A preview: the whole narration is still placed around the dialogue,
+    /// trimmed and checked once every chunk is in.
+    pub chapters: Vec<Chapter>,
+    pub segments: Vec<ScriptSegment>,
+}
+
+pub struct NarrativeEngine {
+    text: Arc<dyn LlmBackend>,
+    /// Used instead of `text` when the request includes scene frames
+    vision: Arc<dyn LlmBackend>,
+    /// Fixed chunk sizes, instead of the ones from the settings
+    chunking: Option<NarrationChunking>,
+}
+
+impl NarrativeEngine {
+    /// Engine on Gemini, or the local model when the settings call for it,
+    /// recording its requests in `history`
+    pub fn new(cache: Arc<LlmCache>, history: Arc<RequestHistory>) -> Self {
+        let local: Arc<dyn LlmBackend> = Arc::new(LocalBackend::new());
+        let text = RoutedBackend::new(
+            Arc::new(GeminiClient::new_for(GeminiPurpose::Narration).with_cache(cache.clone())),
+            local.clone(),
+        );
+        let vision = RoutedBackend::new(Arc::new(GeminiClient::new_for(GeminiPurpose::Vision).with_cache(cache)), local);
+        Self::with_backends(
+            Arc::new(RecordingBackend::new(Arc::new(text), history.clone(), "narrate")),
+            Arc::new(RecordingBackend::new(Arc::new(vision), history, "narrate")),
+        )
+    }
+
+    /// Create an engine on top of specific generation backends, for text-only
+    /// requests and for requests with scene frames
+    pub fn with_backends(text: Arc<dyn LlmBackend>, vision: Arc<dyn LlmBackend>) -> Self {
+        Self { text, vision, chunking: None }
+    }
+
+    /// Narrate the request, a chunk of events at a time if it's too long for one
+    ///
+    /// `on_progress` is called as each chunk is started, and again with
+    /// what it narrated once it's finished. The `narrate` command goes
+    /// through [`generate_variants`](Self::generate_variants), which does
+    /// this once per variant.
+    #[allow(dead_code)]
+    pub async fn generate_narration(
+        &self,
+        request: NarrateRequest,
+        on_progress: &(dyn Fn(NarrationProgress) + Send + Sync),
+    ) -> Result<NarrateResponse> {
+        self.generate(request, &|progress| on_progress(NarrationProgress { variant: 1, ..progress })).await
     }
 
-    pub async fn generate_narration(&self, request: NarrateRequest) -> Result<NarrateResponse> {
+    /// Narrate the request once per entry of `sampling`, all at once
+    ///
+    /// Each variant samples with its parameters, in place of the request's.
+    /// All but the first skip the cache, so they're new takes rather than
+    /// the same one again. Requests still queue for the rate limiter.
+    pub async fn generate_variants(
+        &self,
+        request: NarrateRequest,
+        sampling: &[Sampling],
+        on_progress: &(dyn Fn(NarrationProgress) + Send + Sync),
+    ) -> Vec<Result<NarrateResponse>> {
+        let variants = sampling.iter().enumerate().map(|(i, sampling)| {
+            let mut request = request.clone();
+            request.options.insert("sampling".to_string(), serde_json::to_value(sampling).unwrap_or_default());
+            if i > 0 {
+                request.options.insert("fresh".to_string(), serde_json::Value::Bool(true));
+            }
+            async move {
+                self.generate(request, &|progress| on_progress(NarrationProgress { variant: i + 1, ..progress }))
+                    .await
+            }
+        });
+        futures_util::future::join_all(variants).await
+    }
+
+    async fn generate(
+        &self,
+        mut request: NarrateRequest,
+        on_progress: &(dyn Fn(NarrationProgress) + Send + Sync),
+    ) -> Result<NarrateResponse> {
+        if let Some(revision) = request.revision.take() {
+            return self.revise(request, revision, on_progress).await;
+        }
         info!("Generating narration for {} events", request.truth_bundle.events.len());
 
-        let prompt = self.build_narration_prompt(&request);
+        let options = NarrationOptions::from_options(&request.options).context("Invalid narration options")?;
+        let chunking = self.chunking.unwrap_or_else(|| settings::get().narration_chunking);
+
+        // Loaded once, so a retry uses the same templates as the first request
+        let system = prompts::load(PromptName::NarrationSystem);
+        let template = prompts::load(PromptName::Narration);
         
-        // Pre-process images (strip data URI prefix if present)
-        let images: Vec<String> = request.scene_frames.iter().map(|img| {
-            if let Some(idx) = img.find(',') {
-                img[idx+1..].to_string()
-            } else {
-                img.clone()
+        // Typed image parts in time order; a frame the model can't take fails the request by index
+        let images = frame_parts(&request.scene_frames)?;
+
+        // Call the model (Multimodal); Gemini or local, as the settings say
+        let backend = self.backend_for(&images, options.sampling);
+        let (engine, model) = (backend.engine(), backend.model());
+        let allow_cache = allows_cache(&request);
+
+        let mut chunks = split_into_chunks(&request, chunking);
+        if chunks.len() > 1 {
+            info!("Narrating {} events in {} chunks", request.truth_bundle.events.len(), chunks.len());
+        }
+
+        // Narration goes in the silences between what the creator says
+        let gaps = (options.respect_dialogue && !request.speech.is_empty())
+            .then(|| dialogue::free_gaps(&request.speech, request.video_duration_seconds, options.min_gap_seconds));
+        if let Some(gaps) = &gaps {
+            let starts: Vec<f64> = chunks.iter().map(|c| c.start_seconds).collect();
+            for (i, chunk) in chunks.iter_mut().enumerate() {
+                let from = if i == 0 { 0.0 } else { starts[i] };
+                let to = starts.get(i + 1).copied().unwrap_or(f64::INFINITY);
+                chunk.gaps = Some(dialogue::gaps_within(gaps, from, to));
             }
-        }).collect();
+        }
 
-        // Call Gemini (Multimodal)
-        let response_text = match self.gemini.generate_multimodal(&prompt, images).await {
-            Ok(text) => text,
-            Err(e) => {
-                warn!("Gemini API call failed: {}", e);
-                // In a real implementation, we might fallback to offline Llama here
-                // For now, return a placeholder or error
-                return Err(e.context("Gemini generation failed"));
+        // Each chunk's narration is shown as it comes, on the video's timeline
+        let duration = request.video_duration_seconds;
+        let on_progress = |mut progress: NarrationProgress| {
+            normalize_time_codes(&mut progress.chapters, &mut progress.segments, duration);
+            on_progress(progress)
+        };
+
+        // With no model to reach, the bundle still makes a plain narration
+        let narrated = self
+            .narrate_chunks(backend.as_ref(), &system, &template, &chunks, &options, images, chunking, allow_cache, &on_progress)
+            .await;
+        let (narrated, fallback) = match narrated {
+            Ok(narrated) => (narrated, None),
+            Err(e) if e.downcast_ref::<GeminiError>().is_some_and(GeminiError::is_unreachable) => {
+                warn!("{} couldn't be reached ({}), narrating from templates", engine, e);
+                let narration = template_narration::narrate(&request.truth_bundle, &options.language);
+                let output = NarrationOutput { chapters: narration.chapters, script: narration.segments };
+                let narrated = ChunkNarration { output, finish_reason: FinishReason::Stop, reformatted: false };
+                (narrated, Some((e, narration.language)))
             }
+            Err(e) => return Err(e),
         };
+        let ChunkNarration { output: NarrationOutput { mut chapters, script: mut segments }, finish_reason, reformatted } = narrated;
 
-        // Parse JSON
-        // Clean markdown code blocks if present ( ```json ... ``` )
-        let clean_json = strip_markdown(&response_text);
-        
-        let parsed: serde_json::Value = serde_json::from_str(&clean_json)
-            .context("Failed to parse Gemini JSON response")?;
-        
-        // Map to NarrateResponse
-        // Using intermediate structure to match JSON output
-        #[derive(serde::Deserialize)]
-        struct GeminiOutput {
-            chapters: Vec<Chapter>,
-            script: Vec<ScriptSegment>,
+        // Put the time codes on the video's timeline before anything is placed by them
+        let mut time_code_warnings = normalize_time_codes(&mut chapters, &mut segments, request.video_duration_seconds);
+        time_code_warnings.extend(chapter_titles::start_at_zero(&mut chapters));
+        if !time_code_warnings.is_empty() {
+            warn!("Repaired narration time codes: {}", time_code_warnings.join("; "));
+        }
+
+        // Move lines that talk over the creator, and drop the ones with nowhere to go
+        let placement = gaps.as_ref().map(|gaps| dialogue::place(&mut segments, gaps, options.speech_rate_wpm));
+        if let Some(placement) = placement.as_ref().filter(|p| !p.unplaceable.is_empty()) {
+            warn!("Dropped {} script segment(s) with no gap in the dialogue to go in", placement.unplaceable.len());
         }
-        
-        let output: GeminiOutput = serde_json::from_value(parsed)
-            .context("Failed to map JSON to output structure")?;
+
+        // Check the script fits its slots, and ask once for shorter lines where it doesn't
+        let wpm = options.speech_rate_wpm;
+        let mut overrunning = pacing::annotate(&mut segments, wpm, options.target_duration_seconds);
+        let mut trimmed = 0;
+        if !overrunning.is_empty() && fallback.is_none() {
+            warn!("{} script segment(s) run over their slot, asking {} to trim them", overrunning.len(), engine);
+            match self.trim_segments(backend.as_ref(), &mut segments, &overrunning, wpm).await {
+                Ok(count) => trimmed = count,
+                Err(e) => warn!("Failed to trim script segments: {:#}", e),
+            }
+            overrunning = pacing::annotate(&mut segments, wpm, options.target_duration_seconds);
+        }
+
+        // YouTube chapters need titles that say something, and say it once
+        let mut retitled = 0;
+        let bad_titles = chapter_titles::problems(&chapters);
+        if !bad_titles.is_empty() && fallback.is_none() {
+            warn!("{} chapter title(s) are empty, generic or repeated, asking {} for new ones", bad_titles.len(), engine);
+            match self.retitle_chapters(backend.as_ref(), &mut chapters, &segments, &bad_titles, &options).await {
+                Ok(count) => retitled = count,
+                Err(e) => warn!("Failed to retitle chapters: {:#}", e),
+            }
+        }
+
+        // Tie each line to the evidence it cites, and flag the ones that cite nothing real
+        let evidence = citations::check(&request.truth_bundle, &mut segments);
+        let unknown_hints = delivery::check(&mut segments, options.delivery_hints);
 
         let mut meta = HashMap::new();
-        meta.insert("engine".to_string(), "gemini-3.0-flash".to_string());
+        let language = match &fallback {
+            Some((error, language)) => {
+                meta.insert("engine".to_string(), template_narration::ENGINE.to_string());
+                meta.insert("fallback_reason".to_string(), error.to_string());
+                language.name().to_string()
+            }
+            None => {
+                model_meta(&mut meta, engine, model, &template, &system);
+                options.language.clone()
+            }
+        };
+        meta.insert("finish_reason".to_string(), finish_reason.as_str().to_string());
+        if fallback.is_none() {
+            sampling_meta(&mut meta, &options.sampling);
+        }
+        if reformatted {
+            meta.insert("reformatted".to_string(), "true".to_string());
+        }
+        if chunks.len() > 1 {
+            meta.insert("chunks".to_string(), chunks.len().to_string());
+        }
+        style_meta(&mut meta, &options, language);
+        if trimmed > 0 {
+            meta.insert("trimmed_segments".to_string(), trimmed.to_string());
+        }
+        if retitled > 0 {
+            meta.insert("retitled_chapters".to_string(), retitled.to_string());
+        }
+        if !time_code_warnings.is_empty() {
+            meta.insert("time_code_warnings".to_string(), time_code_warnings.join("\n"));
+        }
+        if !unknown_hints.is_empty() {
+            meta.insert("unknown_delivery_hints".to_string(), unknown_hints.join(", "));
+        }
+        if let Some(placement) = placement {
+            meta.insert("dialogue_adjusted_segments".to_string(), placement.adjusted.to_string());
+            if !placement.unplaceable.is_empty() {
+                meta.insert("unplaceable_segments".to_string(), placement.unplaceable.join("\n"));
+            }
+        }
+        flag_segments(&mut meta, &segments, &overrunning);
 
         Ok(NarrateResponse {
-            chapters: output.chapters,
-            script: Some(NarrateScript { segments: output.script }),
+            chapters,
+            script: Some(NarrateScript { segments }),
+            evidence,
             meta,
         })
     }
 
-    fn build_narration_prompt(&self, request: &NarrateRequest) -> String {
-        let events = &request.truth_bundle.events;
-        
-        let event_descriptions: Vec<String> = events.iter().take(20).map(|event| {
-            let pois = if event.pois.is_empty() {
-                "No landmarks".to_string()
-            } else {
-                event.pois.iter().take(3).map(|p| p.name.clone()).collect::<Vec<_>>().join(", ")
+    /// Write the chapters of `revision.previous` that its changed windows
+    /// touch again, keeping the rest of it exactly as it was
+    ///
+    /// Each stretch is one request, with the narration around it for
+    /// context. There's no fallback to templates: an unreachable model
+    /// fails the revision, leaving the earlier narration to stand.
+    async fn revise(
+        &self,
+        request: NarrateRequest,
+        revision: NarrationRevision,
+        on_progress: &(dyn Fn(NarrationProgress) + Send + Sync),
+    ) -> Result<NarrateResponse> {
+        let previous = &revision.previous;
+        let regions = revision::regions(previous, &revision.changed_windows).map_err(anyhow::Error::msg)?;
+        let described: Vec<String> = regions.iter().map(Region::describe).collect();
+        info!("Revising narration from {}", described.join(", "));
+
+        let options = NarrationOptions::from_options(&request.options).context("Invalid narration options")?;
+        let chunking = self.chunking.unwrap_or_else(|| settings::get().narration_chunking);
+        let system = prompts::load(PromptName::NarrationSystem);
+        let template = prompts::load(PromptName::Narration);
+        let images = frame_parts(&request.scene_frames)?;
+        let backend = self.backend_for(&images, options.sampling);
+        let (engine, model) = (backend.engine(), backend.model());
+        let allow_cache = allows_cache(&request);
+        let wpm = options.speech_rate_wpm;
+
+        let mut events: Vec<&TruthEvent> = request.truth_bundle.events.iter().collect();
+        events.sort_by_key(|e| e.timestamp);
+        let first = events.first().map(|e| e.timestamp);
+        let seconds_in = |event: &TruthEvent| {
+            first.map_or(0.0, |first| (event.timestamp - first).num_milliseconds() as f64 / 1000.0)
+        };
+        let gaps = (options.respect_dialogue && !request.speech.is_empty())
+            .then(|| dialogue::free_gaps(&request.speech, request.video_duration_seconds, options.min_gap_seconds));
+
+        let mut rewrites = Vec::with_capacity(regions.len());
+        let mut evidence = HashMap::new();
+        let (mut warnings, mut unplaceable, mut adjusted) = (Vec::new(), Vec::new(), 0);
+        let mut unknown_hints = Vec::new();
+        let (mut finish_reason, mut reformatted) = (FinishReason::Stop, false);
+        for (index, region) in regions.iter().enumerate() {
+            on_progress(NarrationProgress {
+                chunk: index + 1,
+                chunk_count: regions.len(),
+                message: format!("Rewriting {}", region.describe()),
+                ..Default::default()
+            });
+
+            let chunk = NarrationChunk {
+                events: events.iter().copied().filter(|e| region.contains(seconds_in(e))).collect(),
+                // The transcript has no timing to pick this stretch's part of it by
+                transcript: String::new(),
+                start_seconds: region.start,
+                end_seconds: region.end,
+                gaps: gaps.as_ref().map(|gaps| dialogue::gaps_within(gaps, region.start, region.end)),
+                track_stats: request.truth_bundle.track_stats.as_ref(),
+                route: &request.truth_bundle.route_segments,
+            };
+            let note = revision::context_note(previous, region);
+            // The frames show the footage as a whole; sending them once is enough
+            let images = if index == 0 { images.clone() } else { Vec::new() };
+            let narrated = self
+                .narrate_chunk(backend.as_ref(), &system, &template, &chunk, &note, &options, images, chunking, allow_cache)
+                .await?;
+            finish_reason = narrated.finish_reason;
+            reformatted |= narrated.reformatted;
+
+            let NarrationOutput { mut chapters, script: mut segments } = narrated.output;
+            sort_by_time_code(&mut chapters, |c| &c.time_code);
+            sort_by_time_code(&mut segments, |s| &s.time_code);
+            warnings.extend(normalize_time_codes(&mut chapters, &mut segments, request.video_duration_seconds));
+            if let Some(gaps) = &chunk.gaps {
+                let placement = dialogue::place(&mut segments, gaps, wpm);
+                adjusted += placement.adjusted;
+                unplaceable.extend(placement.unplaceable);
+            }
+            evidence.extend(citations::check(&request.truth_bundle, &mut segments));
+            unknown_hints.extend(delivery::check(&mut segments, options.delivery_hints));
+            rewrites.push(Rewrite { chapters, segments });
+        }
+
+        let Spliced { mut chapters, mut segments, rewritten, rewritten_chapters, dropped } =
+            revision::splice(previous, &regions, rewrites).map_err(anyhow::Error::msg)?;
+        warnings.extend(dropped);
+        warnings.extend(chapter_titles::start_at_zero(&mut chapters));
+        if !warnings.is_empty() {
+            warn!("Repaired revised narration time codes: {}", warnings.join("; "));
+        }
+        // The lines kept still point at the evidence they did
+        for (segment, _) in segments.iter().zip(&rewritten).filter(|(_, new)| !**new) {
+            for id in &segment.source_refs {
+                if let Some(found) = previous.evidence.get(id) {
+                    evidence.entry(id.clone()).or_insert_with(|| found.clone());
+                }
+            }
+        }
+
+        // A new line's slot runs to the next line, old or new; the old
+        // lines keep the timing they had
+        let target = options.target_duration_seconds;
+        let pace = |segments: &mut Vec<ScriptSegment>| {
+            let mut paced = segments.clone();
+            let overrunning = pacing::annotate(&mut paced, wpm, target);
+            for ((segment, paced), new) in segments.iter_mut().zip(paced).zip(&rewritten) {
+                if *new {
+                    *segment = paced;
+                }
+            }
+            overrunning
+        };
+        let mut overrunning = pace(&mut segments);
+        let to_trim: Vec<usize> = overrunning.iter().copied().filter(|&i| rewritten[i]).collect();
+        let mut trimmed = 0;
+        if !to_trim.is_empty() {
+            warn!("{} rewritten script segment(s) run over their slot, asking {} to trim them", to_trim.len(), engine);
+            match self.trim_segments(backend.as_ref(), &mut segments, &to_trim, wpm).await {
+                Ok(count) => trimmed = count,
+                Err(e) => warn!("Failed to trim script segments: {:#}", e),
+            }
+            overrunning = pace(&mut segments);
+        }
+
+        // Only the new chapters' titles are open to change
+        let mut retitled = 0;
+        let bad_titles: Vec<(usize, TitleProblem)> =
+            chapter_titles::problems(&chapters).into_iter().filter(|(i, _)| rewritten_chapters[*i]).collect();
+        if !bad_titles.is_empty() {
+            warn!("{} rewritten chapter title(s) are empty, generic or repeated, asking {} for new ones", bad_titles.len(), engine);
+            match self.retitle_chapters(backend.as_ref(), &mut chapters, &segments, &bad_titles, &options).await {
+                Ok(count) => retitled = count,
+                Err(e) => warn!("Failed to retitle chapters: {:#}", e),
+            }
+        }
+
+        // What the earlier narration said about itself, less what no longer holds
+        let mut meta = previous.meta.clone();
+        for key in REVISED_META {
+            meta.remove(*key);
+        }
+        model_meta(&mut meta, engine, model, &template, &system);
+        meta.insert("finish_reason".to_string(), finish_reason.as_str().to_string());
+        sampling_meta(&mut meta, &options.sampling);
+        if reformatted {
+            meta.insert("reformatted".to_string(), "true".to_string());
+        }
+        style_meta(&mut meta, &options, options.language.clone());
+        meta.insert("revised_windows".to_string(), described.join(", "));
+        meta.insert("revised_segments".to_string(), rewritten.iter().filter(|new| **new).count().to_string());
+        if trimmed > 0 {
+            meta.insert("trimmed_segments".to_string(), trimmed.to_string());
+        }
+        if retitled > 0 {
+            meta.insert("retitled_chapters".to_string(), retitled.to_string());
+        }
+        if !warnings.is_empty() {
+            meta.insert("time_code_warnings".to_string(), warnings.join("\n"));
+        }
+        if !unknown_hints.is_empty() {
+            meta.insert("unknown_delivery_hints".to_string(), unknown_hints.join(", "));
+        }
+        if gaps.is_some() {
+            meta.insert("dialogue_adjusted_segments".to_string(), adjusted.to_string());
+            if !unplaceable.is_empty() {
+                meta.insert("unplaceable_segments".to_string(), unplaceable.join("\n"));
+            }
+        }
+        flag_segments(&mut meta, &segments, &overrunning);
+
+        Ok(NarrateResponse {
+            chapters,
+            script: Some(NarrateScript { segments }),
+            evidence,
+            meta,
+        })
+    }
+
+    /// The backend for a request with `images` or without, sampling with `sampling`
+    fn backend_for(&self, images: &[ImagePart], sampling: Sampling) -> Arc<dyn LlmBackend> {
+        let backend = if images.is_empty() { &self.text } else { &self.vision };
+        if sampling.is_default() {
+            backend.clone()
+        } else {
+            Arc::new(SamplingBackend::new(backend.clone(), sampling))
+        }
+    }
+
+    /// Narrate each chunk in turn, carrying a summary of the story so far
+    /// into the next, and put the results together in time order
+    #[allow(clippy::too_many_arguments)]
+    async fn narrate_chunks(
+        &self,
+        backend: &dyn LlmBackend,
+        system: &PromptTemplate,
+        template: &PromptTemplate,
+        chunks: &[NarrationChunk<'_>],
+        options: &NarrationOptions,
+        images: Vec<ImagePart>,
+        chunking: NarrationChunking,
+        allow_cache: bool,
+        on_progress: &(dyn Fn(NarrationProgress) + Send + Sync),
+    ) -> Result<ChunkNarration> {
+        let (mut chapters, mut segments) = (Vec::new(), Vec::new());
+        let mut finish_reason = FinishReason::Stop;
+        let mut reformatted = false;
+        for (index, chunk) in chunks.iter().enumerate() {
+            on_progress(NarrationProgress {
+                chunk: index + 1,
+                chunk_count: chunks.len(),
+                message: format!("Narrating part {} of {}", index + 1, chunks.len()),
+                ..Default::default()
+            });
+
+            let continuity = continuity_note(chunk, index, chunks.len(), &chapters, &segments);
+            // The frames show the footage as a whole; sending them once is enough
+            let images = if index == 0 { images.clone() } else { Vec::new() };
+            let narrated = self
+                .narrate_chunk(backend, system, template, chunk, &continuity, options, images, chunking, allow_cache)
+                .await?;
+            finish_reason = narrated.finish_reason;
+            reformatted |= narrated.reformatted;
+            on_progress(NarrationProgress {
+                chunk: index + 1,
+                chunk_count: chunks.len(),
+                message: format!("Narrated part {} of {}", index + 1, chunks.len()),
+                finished: true,
+                chapters: narrated.output.chapters.clone(),
+                segments: narrated.output.script.clone(),
+                ..Default::default()
+            });
+            chapters.extend(narrated.output.chapters);
+            segments.extend(narrated.output.script);
+        }
+
+        // Chunks overlap at their edges; put everything back in time order
+        if chunks.len() > 1 {
+            sort_by_time_code(&mut chapters, |c| &c.time_code);
+            sort_by_time_code(&mut segments, |s| &s.time_code);
+        }
+        Ok(ChunkNarration { output: NarrationOutput { chapters, script: segments }, finish_reason, reformatted })
+    }
+
+    /// Generate one chunk's chapters and script
+    ///
+    /// A response cut off at the output token limit is asked for again with
+    /// fewer events and briefer lines; one that isn't valid JSON, reformatted.
+    #[allow(clippy::too_many_arguments)]
+    async fn narrate_chunk(
+        &self,
+        backend: &dyn LlmBackend,
+        system: &PromptTemplate,
+        template: &PromptTemplate,
+        chunk: &NarrationChunk<'_>,
+        continuity: &str,
+        options: &NarrationOptions,
+        images: Vec<ImagePart>,
+        chunking: NarrationChunking,
+        allow_cache: bool,
+    ) -> Result<ChunkNarration> {
+        let engine = backend.engine();
+        let full = PromptLimits {
+            events: chunking.max_events,
+            transcript_chars: chunking.max_transcript_chars,
+            brief: false,
+        };
+        let prompt = self.build_narration_prompt(template, chunk, continuity, options, full, &images);
+        let mut generation = match backend
+            .generate_with_system(Some(&system.text), &prompt, images.clone(), Some(narration_schema(options.delivery_hints)), allow_cache)
+            .await
+        {
+            Ok(generation) => generation,
+            Err(e) => {
+                warn!("{} narration request failed: {:?}", engine, e);
+                // Surface the error; its message tells the user what to do
+                return Err(e.into());
+            }
+        };
+
+        // A cut-off JSON response is useless; ask once more for less
+        if generation.is_truncated() {
+            warn!("Narration hit the output token limit, retrying with a shorter prompt");
+            let prompt = self.build_narration_prompt(template, chunk, continuity, options, SHORT_PROMPT, &images);
+            generation = backend
+                .generate_with_system(Some(&system.text), &prompt, images, Some(narration_schema(options.delivery_hints)), false)
+                .await
+                .map_err(|e| {
+                    warn!("{} narration retry failed: {:?}", engine, e);
+                    e
+                })?;
+        }
+        if generation.is_truncated() {
+            anyhow::bail!("The narration was too long for the model's output limit, even when shortened; try a shorter clip or fewer events");
+        }
+
+        // Structured output is plain JSON, but models without it may still wrap
+        // it in prose or markdown; past what extraction can fix, ask once more
+        let mut reformatted = false;
+        let output = match parse_narration(&generation.text) {
+            Ok(output) => output,
+            Err(e) => {
+                warn!("Narration response wasn't usable JSON ({:#}), asking {} to reformat it", e, engine);
+                let prompt = format!("{}\n\n{}", REFORMAT_PROMPT, generation.text);
+                let generation = backend
+                    .generate_multimodal(&prompt, vec![], Some(narration_schema(options.delivery_hints)), false)
+                    .await?;
+                reformatted = true;
+                parse_narration(&generation.text).context("The reformatted narration still wasn't valid JSON")?
+            }
+        };
+
+        Ok(ChunkNarration { output, finish_reason: generation.finish_reason, reformatted })
+    }
+
+    /// Ask for shorter versions of the `overrunning` segments, replacing the
+    /// ones the model answers for; returns how many were replaced
+    async fn trim_segments(
+        &self,
+        backend: &dyn LlmBackend,
+        segments: &mut [ScriptSegment],
+        overrunning: &[usize],
+        wpm: u32,
+    ) -> Result<usize> {
+        let lines: Vec<String> = overrunning
+            .iter()
+            .map(|&i| {
+                let budget = pacing::word_budget(segments[i].slot_seconds.unwrap_or_default(), wpm).max(1);
+                format!("{}. (at most {} words) {}", i, budget, segments[i].narration)
+            })
+            .collect();
+        let prompt = format!(
+            "{}\n\n{}\n\nReturn a JSON array with one object per line: {{\"index\": <line number>, \"narration\": \"...\"}}.",
+            TRIM_PROMPT,
+            lines.join("\n")
+        );
+
+        let generation = backend.generate_multimodal(&prompt, vec![], Some(trimmed_schema()), true).await?;
+        let json = extract_json(&generation.text).context("No JSON in the trimmed lines")?;
+        let trimmed: Vec<TrimmedLine> = serde_json::from_str(&json).context("Unexpected trimmed lines")?;
+
+        let mut replaced = 0;
+        for line in trimmed {
+            let narration = line.narration.trim();
+            if overrunning.contains(&line.index) && !narration.is_empty() {
+                segments[line.index].narration = narration.to_string();
+                replaced += 1;
+            }
+        }
+        Ok(replaced)
+    }
+
+    /// Ask for new titles for the chapters with `bad_titles`, replacing the
+    /// ones the model answers for with a title that will do; returns how
+    /// many were replaced
+    async fn retitle_chapters(
+        &self,
+        backend: &dyn LlmBackend,
+        chapters: &mut [Chapter],
+        segments: &[ScriptSegment],
+        bad_titles: &[(usize, TitleProblem)],
+        options: &NarrationOptions,
+    ) -> Result<usize> {
+        let lines: Vec<String> = bad_titles
+            .iter()
+            .map(|&(i, problem)| {
+                format!(
+                    "{}. At {}, titled \"{}\" ({}): {}",
+                    i,
+                    chapters[i].time_code,
+                    chapters[i].title.trim(),
+                    problem.as_str(),
+                    chapter_titles::summary(chapters, segments, i)
+                )
+            })
+            .collect();
+        let others: Vec<&str> = chapters
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !bad_titles.iter().any(|(bad, _)| bad == i))
+            .map(|(_, chapter)| chapter.title.trim())
+            .collect();
+        let prompt = format!(
+            "{}\n\n- Style: {}\n- At most {} characters each\n- Written in {}\n- The other chapters are titled: {}\n\n{}\n\n\
+             Return a JSON array with one object per chapter: {{\"index\": <chapter number>, \"title\": \"...\"}}.",
+            TITLE_PROMPT,
+            chapter_titles::style_instruction(options.title_style),
+            chapter_titles::MAX_TITLE_CHARS,
+            options.language,
+            if others.is_empty() { "(none)".to_string() } else { others.join("; ") },
+            lines.join("\n")
+        );
+
+        let generation = backend.generate_multimodal(&prompt, vec![], Some(titles_schema()), true).await?;
+        let json = extract_json(&generation.text).context("No JSON in the chapter titles")?;
+        let titles: Vec<ChapterTitle> = serde_json::from_str(&json).context("Unexpected chapter titles")?;
+
+        let mut replaced = 0;
+        for answer in titles {
+            if !bad_titles.iter().any(|(i, _)| *i == answer.index) {
+                continue;
+            }
+            let title = chapter_titles::fit(&answer.title);
+            // A title that's no better, or clashes with one just given, isn't worth having
+            if chapter_titles::acceptable(&title, answer.index, chapters) {
+                chapters[answer.index].title = title;
+                replaced += 1;
+            }
+        }
+        Ok(replaced)
+    }
+
+    fn build_narration_prompt(
+        &self,
+        template: &PromptTemplate,
+        chunk: &NarrationChunk<'_>,
+        continuity: &str,
+        options: &NarrationOptions,
+        limits: PromptLimits,
+        images: &[ImagePart],
+    ) -> String {
+        let events = &chunk.events[..chunk.events.len().min(limits.events)];
+        let event_descriptions: Vec<String> = events.iter().map(|event| {
+            let pois = match (event.kind, event.pois.first()) {
+                (EventKind::Approach, Some(poi)) => format!("Approaching {}", landmark_position(event, poi)),
+                (EventKind::NearestPass, Some(poi)) => format!("Passing closest to {}", landmark_position(event, poi)),
+                (EventKind::SceneChange, _) => "The view changes".to_string(),
+                _ if event.pois.is_empty() => "No landmarks".to_string(),
+                _ => event.pois.iter().take(3).map(|p| format!("{} [{}]", p.name, p.id)).collect::<Vec<_>>().join(", "),
+            };
+            
+            let location = match &event.location {
+                Some(location) => format!("{:.4}, {:.4}", location.lat, location.lon),
+                None => "unknown".to_string(),
             };
             
             format!(
-                "- At {}: {} (location: {:.4}, {:.4})",
+                "- At {}{} [{}]: {} (location: {})",
                 event.timestamp.format("%H:%M:%S"),
+                clock_note(event),
+                event.id,
                 pois,
-                event.location.lat,
-                event.location.lon
+                location
             )
         }).collect();
 
-        let events_text = if event_descriptions.is_empty() {
+        let mut events_text = if event_descriptions.is_empty() {
             "No events recorded".to_string()
         } else {
             event_descriptions.join("\n")
         };
+        if events.iter().any(|event| matches!(event.kind, EventKind::Approach | EventKind::NearestPass)) {
+            events_text.push_str("\n\n");
+            events_text.push_str(PASSES_NOTE);
+        }
+        if images.iter().any(|image| image.label.is_some()) {
+            events_text.push_str("\n\n");
+            events_text.push_str(FRAMES_NOTE);
+        }
+        if events.iter().any(|event| event.local_time.is_some()) {
+            events_text.push_str("\n\n");
+            events_text.push_str(LOCAL_TIME_NOTE);
+        }
+        if events.iter().any(|event| event.time_uncertain) {
+            events_text.push_str("\n\n");
+            events_text.push_str(UNCERTAIN_TIME_NOTE);
+        }
+
+        let mut transcript_section = if chunk.transcript.is_empty() {
+            String::new()
+        } else {
+            format!("\n## Existing Audio Transcript\n{}\n", chunk.transcript.chars().take(limits.transcript_chars).collect::<String>())
+        };
+        if let Some(gaps) = &chunk.gaps {
+            transcript_section.push_str(&dialogue::gaps_note(gaps));
+        }
 
-        let transcript_section = if let Some(transcript) = &request.transcript {
-            format!("\n## Existing Audio Transcript\n{}\n", transcript.chars().take(2000).collect::<String>())
+        let length_note = if limits.brief {
+            "- Keep it brief: 3 chapters and one or two sentences of narration each"
         } else {
-            String::new()
+            "- Generate 3-5 chapters minimum"
         };
 
-        format!(
-r#"You are a travel documentary narrator creating engaging, fact-checked content.
-
-## Video Context
-This is travel footage with verified GPS and location data. Generate narration that:
-1. Only mentions facts that can be verified from the provided data
-2. Is engaging and suitable for a travel vlog
-3. Follows a natural storytelling flow
-
-## Verified Events and Locations
-{}
-{}
-## Output Requirements
-Generate a JSON response with this EXACT structure:
-{{
-  "chapters": [
-    {{
-      "time_code": "MM:SS",
-      "title": "Chapter Title",
-      "description": "Brief description"
-    }}
-  ],
-  "script": [
-    {{
-      "time_code": "MM:SS",
-      "narration": "Narration text to speak"
-    }}
-  ]
-}}
-
-Important:
-- Each chapter should be 2-5 minutes apart
-- Narration should be conversational and engaging
-- Only include verifiable facts from the provided data
-- Generate 3-5 chapters minimum
-
-Return ONLY valid JSON, no markdown formatting."#,
-            events_text,
-            transcript_section
-        )
+        template.render(&[
+            ("events", &events_text),
+            ("route", &route_section(chunk.route, events)),
+            ("conditions", &conditions_section(chunk.track_stats, events)),
+            ("background", &background_section(events)),
+            ("transcript", &transcript_section),
+            ("continuity", continuity),
+            ("style", &style_instructions(options)),
+            ("length_note", length_note),
+        ])
     }
 }
 
-fn strip_markdown(text: &str) -> String {
-    let text = text.trim();
-    if text.starts_with("```json") {
-        if let Some(end) = text.strip_prefix("```json") {
-             if let Some(stripped) = end.strip_suffix("```") {
-                 return stripped.trim().to_string();
-             }
+/// `"fresh": true` asks for a new variant rather than the last narration of the same inputs
+fn allows_cache(request: &NarrateRequest) -> bool {
+    !request.options.get("fresh").and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+/// The sampling parameters that were set, so a narration can be told apart
+/// from one written with others
+fn sampling_meta(meta: &mut HashMap<String, String>, sampling: &Sampling) {
+    let parameters = [
+        ("temperature", sampling.temperature.map(|t| t.to_string())),
+        ("top_p", sampling.top_p.map(|p| p.to_string())),
+        ("top_k", sampling.top_k.map(|k| k.to_string())),
+        ("seed", sampling.seed.map(|s| s.to_string())),
+        ("max_output_tokens", sampling.max_output_tokens.map(|n| n.to_string())),
+    ];
+    for (name, value) in parameters {
+        if let Some(value) = value {
+            meta.insert(name.to_string(), value);
         }
     }
-    if text.starts_with("```") {
-         if let Some(end) = text.strip_prefix("```") {
-             if let Some(stripped) = end.strip_suffix("```") {
-                 return stripped.trim().to_string();
-             }
+}
+
+/// Meta a revision recomputes rather than keeping from the earlier narration
+const REVISED_META: &[&str] = &[
+    "fallback_reason",
+    "temperature",
+    "top_p",
+    "top_k",
+    "seed",
+    "max_output_tokens",
+    "reformatted",
+    "chunks",
+    "trimmed_segments",
+    "retitled_chapters",
+    "time_code_warnings",
+    "dialogue_adjusted_segments",
+    "unplaceable_segments",
+    "uncited_segments",
+    "fabricated_segments",
+    "overrunning_segments",
+    "unknown_delivery_hints",
+    "target_duration_seconds",
+];
+
+/// Which model and prompts wrote a narration
+fn model_meta(
+    meta: &mut HashMap<String, String>,
+    engine: &str,
+    model: String,
+    template: &PromptTemplate,
+    system: &PromptTemplate,
+) {
+    meta.insert("engine".to_string(), engine.to_string());
+    meta.insert("model".to_string(), model);
+    meta.insert("prompt_template".to_string(), template.id());
+    meta.insert("system_template".to_string(), system.id());
+    let source = if template.custom || system.custom { "custom" } else { "default" };
+    meta.insert("prompt_source".to_string(), source.to_string());
+}
+
+/// The style a narration was written in
+fn style_meta(meta: &mut HashMap<String, String>, options: &NarrationOptions, language: String) {
+    meta.insert("tone".to_string(), options.tone.as_str().to_string());
+    meta.insert("audience".to_string(), options.audience.as_str().to_string());
+    meta.insert("language".to_string(), language);
+    meta.insert("humor_level".to_string(), options.humor_level.to_string());
+    meta.insert("speech_rate_wpm".to_string(), options.speech_rate_wpm.to_string());
+    meta.insert("title_style".to_string(), options.title_style.as_str().to_string());
+    meta.insert("delivery_hints".to_string(), options.delivery_hints.to_string());
+    if let Some(target) = options.target_duration_seconds {
+        meta.insert("target_duration_seconds".to_string(), target.to_string());
+    }
+}
+
+/// Indexes of the segments the UI should point out: uncited, fabricated,
+/// and still too long for their slot
+fn flag_segments(meta: &mut HashMap<String, String>, segments: &[ScriptSegment], overrunning: &[usize]) {
+    for (key, status) in [("uncited_segments", CitationStatus::Uncited), ("fabricated_segments", CitationStatus::Fabricated)] {
+        let indexes = citations::indexes_with(segments, status);
+        if !indexes.is_empty() {
+            meta.insert(key.to_string(), indexes);
+        }
+    }
+    if !overrunning.is_empty() {
+        let indexes: Vec<String> = overrunning.iter().map(usize::to_string).collect();
+        meta.insert("overrunning_segments".to_string(), indexes.join(","));
+    }
+}
+
+/// Prompt section with the track's figures and the light and weather at
+/// `events`, the only conditions the narration may mention; empty when
+/// none were recorded
+///
+/// Light and weather are listed where they change, to keep it short.
+fn conditions_section(track_stats: Option<&TrackStats>, events: &[&TruthEvent]) -> String {
+    let mut lines = Vec::new();
+    if let Some(stats) = track_stats {
+        let mut figures = vec![format!("{:.1} km", stats.distance_km)];
+        if let Some(seconds) = stats.duration_seconds {
+            let minutes = (seconds / 60.0).round() as u64;
+            figures.push(match minutes / 60 {
+                0 => format!("in {} min", minutes),
+                hours => format!("in {} h {} min", hours, minutes % 60),
+            });
+        }
+        if let Some(speed) = stats.avg_speed_kmh {
+            figures.push(format!("averaging {:.0} km/h", speed));
+        }
+        if let Some(speed) = stats.max_speed_kmh {
+            figures.push(format!("top speed {:.0} km/h", speed));
+        }
+        if let Some(gain) = stats.elevation_gain_m {
+            figures.push(format!("{:.0} m climbed", gain));
+        }
+        if let Some(loss) = stats.elevation_loss_m {
+            figures.push(format!("{:.0} m descended", loss));
+        }
+        lines.push(format!("- Whole track: {}", figures.join(", ")));
+    }
+
+    if let Some(sun) = events.iter().find_map(|e| e.sun) {
+        let time = |t: Option<DateTime<Utc>>| t.map(|t| t.format("%H:%M").to_string());
+        lines.push(match (time(sun.sunrise), time(sun.sunset)) {
+            (Some(sunrise), Some(sunset)) => format!("- Sunrise {}, sunset {}", sunrise, sunset),
+            (Some(sunrise), None) => format!("- Sunrise {}", sunrise),
+            (None, Some(sunset)) => format!("- Sunset {}", sunset),
+            (None, None) if sun.elevation_deg > 0.0 => "- The sun doesn't set that day".to_string(),
+            (None, None) => "- The sun doesn't rise that day".to_string(),
+        });
+    }
+    let mut light = None;
+    for event in events {
+        let Some(sun) = event.sun.filter(|sun| Some(sun.light) != light) else { continue };
+        light = Some(sun.light);
+        let side = if sun.elevation_deg >= 0.0 { "above" } else { "below" };
+        lines.push(format!(
+            "- From {} [{}]: {}, sun {:.0}° {} the horizon",
+            event.timestamp.format("%H:%M:%S"),
+            event.id,
+            sun.light.as_str(),
+            sun.elevation_deg.abs(),
+            side
+        ));
+    }
+
+    let mut weather = None;
+    for event in events {
+        let Some(observed) = &event.weather else { continue };
+        let description = describe_weather(observed);
+        if description.is_empty() || weather.as_ref() == Some(&description) {
+            continue;
         }
+        lines.push(format!(
+            "- Weather at {} [{}]: {} (recorded by {})",
+            event.timestamp.format("%H:%M:%S"),
+            event.id,
+            description,
+            observed.source
+        ));
+        weather = Some(description);
+    }
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\n## Conditions\nRecorded and computed for this footage; times are UTC, like the events'. Mention weather, \
+         light, distances, speeds and climbs only as given here, and don't describe any that aren't.\n{}\n",
+        lines.join("\n")
+    )
+}
+
+/// The areas the route passes through while the events happen, to give
+/// the chapters their shape
+fn route_section(route: &[RouteSegment], events: &[&TruthEvent]) -> String {
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        return String::new();
+    };
+    let lines: Vec<String> = route
+        .iter()
+        .filter(|segment| segment.end_time >= first.timestamp && segment.start_time <= last.timestamp)
+        .map(|segment| {
+            let place: Vec<&str> = [&segment.city, &segment.region, &segment.country]
+                .into_iter()
+                .filter_map(|name| name.as_deref())
+                .collect();
+            format!(
+                "- {} to {}: {} ({:.1} km)",
+                segment.start_time.format("%H:%M:%S"),
+                segment.end_time.format("%H:%M:%S"),
+                place.join(", "),
+                segment.distance_km
+            )
+        })
+        .collect();
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\n## Route\nThe areas the footage passes through, in order. Let chapters follow them, and name places \
+         as they're named here rather than from an event's coordinates.\n{}\n",
+        lines.join("\n")
+    )
+}
+
+/// The opening of the article on each landmark the events see, once each
+fn background_section(events: &[&TruthEvent]) -> String {
+    let mut seen = Vec::new();
+    let mut lines = Vec::new();
+    for poi in events.iter().flat_map(|event| &event.pois) {
+        let Some(summary) = poi.facts.as_ref().and_then(|facts| facts.summary.as_ref()) else { continue };
+        if lines.len() == MAX_BACKGROUND_POIS || seen.contains(&poi.id) {
+            continue;
+        }
+        seen.push(poi.id.clone());
+        lines.push(format!(
+            "- {} [{}]: {} ({} {}, retrieved {})",
+            poi.name,
+            poi.id,
+            wikipedia::trim_extract(&summary.extract, BACKGROUND_EXTRACT_CHARS),
+            summary.language,
+            summary.source,
+            summary.retrieved_on
+        ));
+    }
+
+    if lines.is_empty() {
+        return String::new();
+    }
+    format!(
+        "\n## Background\nFrom the landmarks' encyclopedia articles, and as verified as the events. Draw on them \
+         only for landmarks the events mention, and cite the landmark's id.\n{}\n",
+        lines.join("\n")
+    )
+}
+
+/// e.g. `light rain, 14 °C, 1.2 mm of precipitation, wind 18 km/h`
+fn describe_weather(observed: &WeatherObservation) -> String {
+    let mut parts = Vec::new();
+    if let Some(condition) = observed.condition.as_deref().map(str::trim).filter(|c| !c.is_empty()) {
+        parts.push(condition.to_string());
+    }
+    if let Some(temperature) = observed.temperature_c {
+        parts.push(format!("{:.0} °C", temperature));
+    }
+    if let Some(precipitation) = observed.precipitation_mm.filter(|p| *p > 0.0) {
+        parts.push(format!("{:.1} mm of precipitation", precipitation));
+    }
+    if let Some(wind) = observed.wind_speed_kmh {
+        parts.push(format!("wind {:.0} km/h", wind));
+    }
+    if let Some(cloud) = observed.cloud_cover_percent {
+        parts.push(format!("{:.0}% cloud cover", cloud));
+    }
+    parts.join(", ")
+}
+
+/// Image parts for a request's scene frames, in time order, each timed one
+/// labelled with its time into the video
+///
+/// Frames without a time go after the others. Past [`MAX_NARRATION_FRAMES`],
+/// frames are picked evenly from the whole video. A frame the model can't
+/// take fails the request by its index in the request.
+fn frame_parts(frames: &[SceneFrame]) -> Result<Vec<ImagePart>, ImageError> {
+    let mut order: Vec<usize> = (0..frames.len()).collect();
+    order.sort_by(|&a, &b| match (frames[a].time_seconds, frames[b].time_seconds) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+    if order.len() > MAX_NARRATION_FRAMES {
+        warn!("{} scene frames sent, keeping {} spread over the video", order.len(), MAX_NARRATION_FRAMES);
+        let last = order.len() - 1;
+        order = (0..MAX_NARRATION_FRAMES).map(|i| order[i * last / (MAX_NARRATION_FRAMES - 1)]).collect();
+    }
+
+    let parts = image_parts(order.iter().map(|&i| (i, frames[i].image.as_str())))?;
+    Ok(parts
+        .into_iter()
+        .zip(&order)
+        .map(|(part, &i)| match frames[i].time_seconds {
+            Some(seconds) => part.with_label(format!("[Frame at {}]", pacing::format_time_code(seconds))),
+            None => part,
+        })
+        .collect())
+}
+
+/// What goes with an event's UTC time in a prompt: the local time and
+/// whether the time is a guess, e.g. ` (10:42 local, uncertain)`
+fn clock_note(event: &TruthEvent) -> String {
+    let local = event
+        .local_time
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| format!("{} local", t.format("%H:%M")));
+    let notes: Vec<String> = local.into_iter().chain(event.time_uncertain.then(|| "uncertain".to_string())).collect();
+    if notes.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", notes.join(", "))
+    }
+}
+
+/// Where a landmark an event approaches or passes is: how far, and which
+/// side when the direction of travel is known
+fn landmark_position(event: &TruthEvent, poi: &POI) -> String {
+    let distance = if poi.distance_m < 1000.0 {
+        format!("{:.0} m", poi.distance_m)
+    } else {
+        format!("{:.1} km", poi.distance_m / 1000.0)
+    };
+    match event.heading_deg {
+        Some(heading) => format!(
+            "{} [{}], {} away {}",
+            poi.name,
+            poi.id,
+            distance,
+            poi_passes::relative_direction(heading, poi.bearing_deg)
+        ),
+        None => format!("{} [{}], {} away", poi.name, poi.id, distance),
+    }
+}
+
+/// Rewrite time codes in canonical form, clamped to the video's duration
+///
+/// Chapters must start strictly after the one before. Chapters and segments
+/// whose time codes can't be read, and chapters out of order, are dropped;
+/// returns a warning for each one dropped or clamped.
+fn normalize_time_codes(
+    chapters: &mut Vec<Chapter>,
+    segments: &mut Vec<ScriptSegment>,
+    video_duration_seconds: Option<f64>,
+) -> Vec<String> {
+    let duration = video_duration_seconds.filter(|d| d.is_finite() && *d > 0.0);
+    let mut warnings = Vec::new();
+
+    // Whole seconds, as the canonical form shows them
+    let place = |kind: &str, index: usize, time_code: &str, warnings: &mut Vec<String>| -> Option<f64> {
+        let Some(seconds) = pacing::time_code_seconds(time_code) else {
+            warnings.push(format!("Dropped {} {}: unreadable time code '{}'", kind, index, time_code));
+            return None;
+        };
+        match duration {
+            Some(duration) if seconds > duration => {
+                warnings.push(format!(
+                    "Moved {} {} from {} to the end of the video at {}",
+                    kind,
+                    index,
+                    time_code,
+                    pacing::format_time_code(duration)
+                ));
+                Some(duration.floor())
+            }
+            _ => Some(seconds.floor()),
+        }
+    };
+
+    let mut previous: Option<f64> = None;
+    let mut kept = Vec::with_capacity(chapters.len());
+    for (index, mut chapter) in chapters.drain(..).enumerate() {
+        let Some(seconds) = place("chapter", index, &chapter.time_code, &mut warnings) else { continue };
+        if let Some(previous) = previous.filter(|p| seconds <= *p) {
+            warnings.push(format!(
+                "Dropped chapter {}: {} isn't after the previous chapter at {}",
+                index,
+                pacing::format_time_code(seconds),
+                pacing::format_time_code(previous)
+            ));
+            continue;
+        }
+        previous = Some(seconds);
+        chapter.time_code = pacing::format_time_code(seconds);
+        kept.push(chapter);
+    }
+    *chapters = kept;
+
+    let mut kept = Vec::with_capacity(segments.len());
+    for (index, mut segment) in segments.drain(..).enumerate() {
+        if let Some(seconds) = place("script segment", index, &segment.time_code, &mut warnings) {
+            segment.time_code = pacing::format_time_code(seconds);
+            kept.push(segment);
+        }
+    }
+    *segments = kept;
+
+    warnings
+}
+
+/// The events and transcript narrated in one request
+struct NarrationChunk<'a> {
+    events: Vec<&'a TruthEvent>,
+    transcript: String,
+    /// Seconds from the first event of the whole request to this chunk's first and last
+    start_seconds: f64,
+    end_seconds: f64,
+    /// Silences in this part of the video to narrate in, when keeping out of the dialogue
+    gaps: Option<Vec<Gap>>,
+    /// Figures for the whole GPS track
+    track_stats: Option<&'a TrackStats>,
+    /// The areas the whole route passes through
+    route: &'a [RouteSegment],
+}
+
+/// What the model made of one chunk, or of all of them
+struct ChunkNarration {
+    output: NarrationOutput,
+    finish_reason: FinishReason,
+    reformatted: bool,
+}
+
+/// Split the events, in time order, into consecutive windows of at most
+/// `max_events`, and the transcript evenly over the same windows
+///
+/// The transcript has no timing, so each window gets an equal share of it;
+/// there are more windows than the events alone need if the transcript
+/// wouldn't fit otherwise.
+fn split_into_chunks(request: &NarrateRequest, chunking: NarrationChunking) -> Vec<NarrationChunk<'_>> {
+    let mut events: Vec<&TruthEvent> = request.truth_bundle.events.iter().collect();
+    events.sort_by_key(|e| e.timestamp);
+    let transcript = request.transcript.as_deref().unwrap_or_default().trim();
+
+    let count = events
+        .len()
+        .div_ceil(chunking.max_events.max(1))
+        .max(transcript.chars().count().div_ceil(chunking.max_transcript_chars.max(1)))
+        .max(1);
+    let per_chunk = events.len().div_ceil(count).max(1);
+    let first = events.first().map(|e| e.timestamp);
+    let seconds_in = |event: &TruthEvent| {
+        first.map_or(0.0, |first| (event.timestamp - first).num_milliseconds() as f64 / 1000.0)
+    };
+
+    let mut transcripts = split_text(transcript, count).into_iter();
+    let mut event_windows = events.chunks(per_chunk);
+    (0..count)
+        .map(|_| {
+            let events = event_windows.next().map(<[_]>::to_vec).unwrap_or_default();
+            NarrationChunk {
+                start_seconds: events.first().map_or(0.0, |e| seconds_in(e)),
+                end_seconds: events.last().map_or(0.0, |e| seconds_in(e)),
+                events,
+                transcript: transcripts.next().unwrap_or_default(),
+                gaps: None,
+                track_stats: request.truth_bundle.track_stats.as_ref(),
+                route: &request.truth_bundle.route_segments,
+            }
+        })
+        .collect()
+}
+
+/// `text` in `parts` pieces of about equal length, split between words
+fn split_text(text: &str, parts: usize) -> Vec<String> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut pieces = Vec::with_capacity(parts);
+    let mut start = 0;
+    for part in 1..=parts {
+        let mut end = if part == parts { text.len() } else {
+            let target = chars.len() * part / parts;
+            chars[target.min(chars.len())..]
+                .iter()
+                .find(|(_, c)| c.is_whitespace())
+                .map_or(text.len(), |(i, _)| *i)
+        };
+        end = end.max(start);
+        pieces.push(text[start..end].trim().to_string());
+        start = end;
+    }
+    pieces
+}
+
+/// Prompt section placing a chunk in the whole, with a summary of what the
+/// chunks before it produced; empty for a request narrated in one go
+fn continuity_note(
+    chunk: &NarrationChunk<'_>,
+    index: usize,
+    count: usize,
+    chapters: &[Chapter],
+    segments: &[ScriptSegment],
+) -> String {
+    if count <= 1 {
+        return String::new();
+    }
+
+    let mut note = format!(
+        "\n## Part {} of {}\nThe video is narrated in parts. These events run from {} to {} into the video; \
+         use time codes from that range.\n",
+        index + 1,
+        count,
+        pacing::format_time_code(chunk.start_seconds),
+        pacing::format_time_code(chunk.end_seconds)
+    );
+    if index > 0 {
+        note.push_str("\n## Story So Far\nContinue from here without repeating it or starting over.\n");
+        let titles: Vec<String> = chapters.iter().map(|c| format!("{} {}", c.time_code, c.title)).collect();
+        if !titles.is_empty() {
+            note.push_str(&format!("Chapters: {}\n", titles.join("; ")));
+        }
+        for segment in segments.iter().rev().take(2).rev() {
+            note.push_str(&format!("Narration at {}: {}\n", segment.time_code, segment.narration));
+        }
+    }
+    note
+}
+
+/// Stable sort by time code; unreadable ones go first, to be dropped later
+fn sort_by_time_code<T>(items: &mut [T], time_code: impl Fn(&T) -> &str) {
+    items.sort_by(|a, b| {
+        let (a, b) = (pacing::time_code_seconds(time_code(a)), pacing::time_code_seconds(time_code(b)));
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+}
+
+/// Prompt lines asking for the tone, audience, humor and language chosen
+fn style_instructions(options: &NarrationOptions) -> String {
+    let tone = match options.tone {
+        NarrationTone::Documentary => "documentary: measured and informative",
+        NarrationTone::Casual => "casual: relaxed, like a friend showing their trip",
+        NarrationTone::Energetic => "energetic: upbeat and lively",
+        NarrationTone::Poetic => "poetic: evocative, dwelling on atmosphere and imagery",
+    };
+    let audience = match options.audience {
+        NarrationAudience::General => "a general audience",
+        NarrationAudience::Kids => "children: simple words, short sentences, nothing frightening",
+        NarrationAudience::Enthusiast => "travel enthusiasts: include history, geography and local detail",
+    };
+    let humor = match options.humor_level {
+        0 => "none, keep it serious",
+        1..=3 => "an occasional light touch",
+        4..=6 => "some, where it fits",
+        _ => "plenty, as long as the facts stay accurate",
+    };
+
+    let mut lines = format!(
+        "- Tone: {}\n- Audience: {}\n- Humor ({}/{}): {}\n- Write the chapter titles, descriptions and narration in {}; keep the JSON keys and time codes as they are",
+        tone,
+        audience,
+        options.humor_level,
+        MAX_HUMOR_LEVEL,
+        humor,
+        options.language
+    );
+
+    lines.push_str(&format!(
+        "\n- Chapter titles: {}; at most {} characters, none of them alike, and never just \"Chapter 3\"",
+        chapter_titles::style_instruction(options.title_style),
+        chapter_titles::MAX_TITLE_CHARS
+    ));
+    if options.delivery_hints {
+        lines.push('\n');
+        lines.push_str(&delivery::instruction());
+    }
+
+    let wpm = options.speech_rate_wpm;
+    lines.push_str(&format!(
+        "\n- Pacing: the script is read at {} words per minute, so each narration line must be short enough \
+         to finish before the next line's time code: at most {} words for every 10 seconds",
+        wpm,
+        pacing::word_budget(10.0, wpm)
+    ));
+    if let Some(target) = options.target_duration_seconds {
+        lines.push_str(&format!(
+            "\n- Length: the video runs {}:{:02}; spread the script over all of it, about {} words in total",
+            (target / 60.0).floor() as u64,
+            (target % 60.0).floor() as u64,
+            pacing::word_budget(target, wpm)
+        ));
+    }
+    lines
+}
+
+/// Response schema for trimmed script lines
+fn trimmed_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "ARRAY",
+        "items": {
+            "type": "OBJECT",
+            "properties": {
+                "index": { "type": "INTEGER" },
+                "narration": { "type": "STRING" }
+            },
+            "required": ["index", "narration"]
+        }
+    })
+}
+
+#[derive(serde::Deserialize)]
+struct TrimmedLine {
+    index: usize,
+    narration: String,
+}
+
+/// Response schema for new chapter titles
+fn titles_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "ARRAY",
+        "items": {
+            "type": "OBJECT",
+            "properties": {
+                "index": { "type": "INTEGER" },
+                "title": { "type": "STRING" }
+            },
+            "required": ["index", "title"]
+        }
+    })
+}
+
+#[derive(serde::Deserialize)]
+struct ChapterTitle {
+    index: usize,
+    title: String,
+}
+
+/// Response schema for structured output, matching the chapters/script shape
+/// in the prompt
+fn narration_schema(delivery_hints: bool) -> serde_json::Value {
+    let string = || serde_json::json!({ "type": "STRING" });
+    let mut schema = serde_json::json!({
+        "type": "OBJECT",
+        "properties": {
+            "chapters": {
+                "type": "ARRAY",
+                "items": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "time_code": string(),
+                        "title": string(),
+                        "description": string()
+                    },
+                    "required": ["time_code", "title"],
+                    "propertyOrdering": ["time_code", "title", "description"]
+                }
+            },
+            "script": {
+                "type": "ARRAY",
+                "items": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "time_code": string(),
+                        "narration": string(),
+                        "source_refs": { "type": "ARRAY", "items": string() }
+                    },
+                    "required": ["time_code", "narration", "source_refs"],
+                    "propertyOrdering": ["time_code", "narration", "source_refs"]
+                }
+            }
+        },
+        "required": ["chapters", "script"],
+        "propertyOrdering": ["chapters", "script"]
+    });
+    if delivery_hints {
+        let line = &mut schema["properties"]["script"]["items"];
+        line["properties"]["delivery"] = serde_json::json!({ "type": "STRING", "format": "enum", "enum": delivery::HINTS });
+        line["propertyOrdering"] = serde_json::json!(["time_code", "narration", "source_refs", "delivery"]);
+    }
+    schema
+}
+
+/// The JSON value in a model response, as strict JSON text
+///
+/// Models asked for JSON don't always return only JSON: they put it in
+/// markdown fences, add a sentence before it or an explanation after, or
+/// leave trailing commas. This finds the first complete object or array once
+/// fences are removed, dropping trailing commas if that makes it parse.
+/// `None` if there's no such value, e.g. when the response was cut off.
+pub(crate) fn extract_json(text: &str) -> Option<String> {
+    let text = strip_fences(text);
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find(['{', '[']).map(|i| offset + i) {
+        if let Some(len) = balanced_len(&text[start..]) {
+            let candidate = &text[start..start + len];
+            if serde_json::from_str::<serde_json::Value>(candidate).is_ok() {
+                return Some(candidate.to_string());
+            }
+            let repaired = remove_trailing_commas(candidate);
+            if serde_json::from_str::<serde_json::Value>(&repaired).is_ok() {
+                return Some(repaired);
+            }
+        }
+        // Brackets in the prose before the JSON, like "[1/2]"
+        offset = start + 1;
+    }
+    None
+}
+
+/// The text without markdown fences and their language tags, wherever they are
+fn strip_fences(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("```") {
+        stripped.push_str(&rest[..start]);
+        rest = rest[start + 3..].trim_start_matches(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Length of the object or array `text` starts with, up to its matching
+/// close; brackets inside strings don't count
+fn balanced_len(text: &str) -> Option<usize> {
+    let mut open = Vec::new();
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => open.push('}'),
+            '[' => open.push(']'),
+            '}' | ']' => {
+                if open.pop() != Some(c) {
+                    return None;
+                }
+                if open.is_empty() {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The JSON without commas right before a closing bracket
+fn remove_trailing_commas(json: &str) -> String {
+    let mut repaired = String::with_capacity(json.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, c) in json.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' && json[i + 1..].trim_start().starts_with(['}', ']']) {
+            continue;
+        }
+        repaired.push(c);
+    }
+    repaired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle_schema::TRUTH_BUNDLE_SCHEMA_VERSION;
+    use crate::fixtures::assert_golden_json;
+    use crate::gemini::mock::MockGemini;
+    use crate::llm::ImageError;
+    use crate::types::{
+        ArticleSummary, Daylight, LocationContext, LocationResult, POIFacts, RouteSegment, SpeechInterval, SunFacts, TruthBundle, TruthEvent,
+    };
+    use chrono::Utc;
+
+    const VALID_JSON: &str = r#"{
+        "chapters": [{"time_code": "00:00", "title": "Start", "description": "Leaving town"}],
+        "script": [{"time_code": "00:05", "narration": "We set off early."}]
+    }"#;
+
+    fn request(event_count: usize) -> NarrateRequest {
+        let events = (0..event_count)
+            .map(|i| TruthEvent {
+                id: format!("event-{}", i),
+                kind: EventKind::Speech,
+                timestamp: Utc::now(),
+                local_time: None,
+                time_uncertain: false,
+                duration_seconds: None,
+                location: Some(LocationResult { lat: 43.7384, lon: 7.4246 }),
+                heading_deg: None,
+                pois: vec![],
+                detected_objects: vec![],
+                image_path: None,
+                sun: None,
+                weather: None,
+                context: None,
+            })
+            .collect();
+
+        NarrateRequest {
+            truth_bundle: TruthBundle {
+                schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
+                project_id: None,
+                video_id: None,
+                events,
+                verification_mode: "offline".to_string(),
+                confidence: 0.0,
+                generated_at: Utc::now(),
+                track_stats: None,
+                meta: HashMap::new(),
+                route_segments: vec![],
+            },
+            transcript: None,
+            scene_frames: vec![],
+            video_duration_seconds: None,
+            speech: vec![],
+            options: HashMap::new(),
+            revision: None,
+        }
+    }
+
+    fn engine(mock: MockGemini) -> (NarrativeEngine, Arc<MockGemini>) {
+        let mock = Arc::new(mock);
+        let mut engine = NarrativeEngine::with_backends(mock.clone(), mock.clone());
+        // Not whatever the settings on this machine say
+        engine.chunking = Some(NarrationChunking::default());
+        (engine, mock)
+    }
+
+    #[tokio::test]
+    async fn test_valid_json_response() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON).with_model("gemini-3.0-pro"));
+        let response = engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        let schema = mock.schemas()[0].clone().expect("narration requests structured output");
+        assert_eq!(schema["required"], serde_json::json!(["chapters", "script"]));
+        assert_eq!(schema["properties"]["script"]["items"]["required"], serde_json::json!(["time_code", "narration", "source_refs"]));
+
+        assert_eq!(response.meta.get("model").map(String::as_str), Some("gemini-3.0-pro"));
+        assert_eq!(response.meta.get("finish_reason").map(String::as_str), Some("stop"));
+        assert_eq!(response.chapters.len(), 1);
+        assert_eq!(response.chapters[0].title, "Start");
+        assert_eq!(response.script.unwrap().segments[0].narration, "We set off early.");
+    }
+
+    #[tokio::test]
+    async fn test_prompt_templates_are_recorded() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let response = engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        // The narrator's role goes in the system instruction, the events in the prompt
+        let system = mock.system_instructions()[0].clone().expect("narration sends a system instruction");
+        assert!(system.contains("travel documentary narrator"));
+        assert!(!mock.prompts()[0].contains("{events}"));
+        assert!(mock.prompts()[0].contains("- At "));
+
+        let template = response.meta["prompt_template"].strip_prefix("narration@").unwrap();
+        assert_eq!(template.len(), 12);
+        assert!(response.meta["system_template"].starts_with("narration_system@"));
+    }
+
+    #[tokio::test]
+    async fn test_options_shape_prompt_and_meta() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(1);
+        req.options.insert("tone".to_string(), serde_json::json!("poetic"));
+        req.options.insert("audience".to_string(), serde_json::json!("kids"));
+        req.options.insert("language".to_string(), serde_json::json!("Español"));
+        req.options.insert("humor_level".to_string(), serde_json::json!(40));
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompt = &mock.prompts()[0];
+        assert!(prompt.contains("- Tone: poetic"));
+        assert!(prompt.contains("- Audience: children"));
+        assert!(prompt.contains("narration in Español"));
+        assert_eq!(response.meta["tone"], "poetic");
+        assert_eq!(response.meta["language"], "Español");
+        // Clamped to the scale
+        assert_eq!(response.meta["humor_level"], "10");
+    }
+
+    #[tokio::test]
+    async fn test_long_segments_are_trimmed() {
+        let long = vec!["word"; 40].join(" ");
+        let narration = serde_json::json!({
+            "chapters": [{"time_code": "00:00", "title": "Start"}],
+            "script": [
+                {"time_code": "00:00", "narration": long},
+                {"time_code": "00:05", "narration": "We arrive."},
+            ]
+        });
+        let (engine, mock) = engine(
+            MockGemini::new()
+                .with_text(&narration.to_string())
+                .with_text(r#"[{"index": 0, "narration": "We set off early."}]"#),
+        );
+        let mut req = request(1);
+        req.options.insert("target_duration_seconds".to_string(), serde_json::json!(8));
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompts = mock.prompts();
+        assert!(prompts[0].contains("at most 25 words for every 10 seconds"));
+        assert!(prompts[0].contains("the video runs 0:08"));
+        // 5 seconds at 150 words per minute
+        assert!(prompts[1].contains("0. (at most 12 words)"));
+
+        let segments = response.script.unwrap().segments;
+        assert_eq!(segments[0].narration, "We set off early.");
+        assert_eq!(segments[0].estimated_duration_seconds, Some(1.6));
+        assert_eq!(segments[1].slot_seconds, Some(3.0));
+        assert_eq!(response.meta["trimmed_segments"], "1");
+        assert!(!response.meta.contains_key("overrunning_segments"));
+    }
+
+    #[tokio::test]
+    async fn test_bad_chapter_titles_are_repaired() {
+        let narration = serde_json::json!({
+            "chapters": [
+                {"time_code": "00:04", "title": "Chapter 1"},
+                {"time_code": "00:30", "title": "The Harbour", "description": "Boats at the quay"},
+                {"time_code": "01:00", "title": "the harbour!"},
+                {"time_code": "01:30", "title": ""},
+            ],
+            "script": [
+                {"time_code": "00:04", "narration": "We leave Nice at dawn."},
+                {"time_code": "01:00", "narration": "The road climbs above the sea."},
+                {"time_code": "01:35", "narration": "Snow on the Col de Turini."},
+            ]
+        });
+        let (engine, mock) = engine(
+            MockGemini::new().with_text(&narration.to_string()).with_text(
+                r#"[
+                    {"index": 0, "title": "\"Leaving Nice at Dawn\""},
+                    {"index": 1, "title": "Somewhere Else"},
+                    {"index": 2, "title": "The Harbour"},
+                    {"index": 3, "title": "Snow on the Col de Turini, Where the Rally Cars Race Every Winter"}
+                ]"#,
+            ),
+        );
+        let mut req = request(1);
+        req.options.insert("title_style".to_string(), serde_json::json!("punchy"));
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompts = mock.prompts();
+        assert!(prompts[0].contains("Chapter titles: punchy"));
+        assert!(prompts[1].contains("At most 45 characters each"));
+        assert!(prompts[1].contains("0. At 00:00, titled \"Chapter 1\" (generic): We leave Nice at dawn."));
+        assert!(prompts[1].contains("2. At 01:00, titled \"the harbour!\" (duplicate): The road climbs above the sea."));
+        assert!(prompts[1].contains("3. At 01:30, titled \"\" (empty)"));
+        // Only the chapters with bad titles are asked about
+        assert!(!prompts[1].contains("1. At"));
+
+        let titles: Vec<&str> = response.chapters.iter().map(|c| c.title.as_str()).collect();
+        // The model's repeat of "The Harbour" is no better than what was there
+        assert_eq!(titles, ["Leaving Nice at Dawn", "The Harbour", "the harbour!", "Snow on the Col de Turini, Where the Rally"]);
+        assert_eq!(response.chapters[0].time_code, "00:00");
+        assert_eq!(response.meta["retitled_chapters"], "2");
+        assert_eq!(response.meta["title_style"], "punchy");
+        assert!(response.meta["time_code_warnings"].contains("Moved chapter 0 from 00:04"));
+    }
+
+    #[tokio::test]
+    async fn test_delivery_hints_are_checked_against_the_allowlist() {
+        let narration = serde_json::json!({
+            "chapters": [{"time_code": "00:00", "title": "The Tunnel"}],
+            "script": [
+                {"time_code": "00:00", "narration": "Into the dark.", "delivery": "Pause before"},
+                {"time_code": "00:10", "narration": "And out again.", "delivery": "sarcastic"},
+                {"time_code": "00:20", "narration": "Daylight."},
+            ]
+        });
+        let (engine, mock) = engine(MockGemini::new().with_text(&narration.to_string()).with_text(&narration.to_string()));
+        let response = engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        assert!(mock.prompts()[0].contains("- Delivery: give a line a \"delivery\" hint"));
+        let schema = mock.schemas()[0].clone().unwrap();
+        assert_eq!(schema["properties"]["script"]["items"]["properties"]["delivery"]["enum"][7], "pause_before");
+        let hints: Vec<Option<String>> = response.script.unwrap().segments.into_iter().map(|s| s.delivery).collect();
+        assert_eq!(hints, [Some("pause_before".to_string()), None, None]);
+        assert_eq!(response.meta["unknown_delivery_hints"], "sarcastic");
+
+        // Plain narration asks for none, and keeps none
+        let mut req = request(1);
+        req.options.insert("delivery_hints".to_string(), serde_json::json!(false));
+        req.options.insert("fresh".to_string(), serde_json::json!(true));
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+        assert!(!mock.prompts()[1].contains("Delivery:"));
+        assert!(mock.schemas()[1].as_ref().unwrap()["properties"]["script"]["items"]["properties"].get("delivery").is_none());
+        assert!(response.script.unwrap().segments.iter().all(|s| s.delivery.is_none()));
+        assert_eq!(response.meta["delivery_hints"], "false");
+    }
+
+    #[tokio::test]
+    async fn test_time_codes_are_normalized() {
+        let narration = serde_json::json!({
+            "chapters": [
+                {"time_code": "0:00", "title": "Start"},
+                {"time_code": "1:05:30", "title": "Past the end"},
+                {"time_code": "05:00", "title": "Out of order"},
+                {"time_code": "about noon", "title": "Unreadable"},
+            ],
+            "script": [
+                {"time_code": "00:07.5", "narration": "We set off."},
+                {"time_code": "65:00", "narration": "Nearly there."},
+                {"time_code": "", "narration": "Lost."},
+            ]
+        });
+        let (engine, _) = engine(MockGemini::new().with_text(&narration.to_string()));
+        let mut req = request(1);
+        req.video_duration_seconds = Some(600.0);
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let chapters: Vec<(&str, &str)> =
+            response.chapters.iter().map(|c| (c.time_code.as_str(), c.title.as_str())).collect();
+        assert_eq!(chapters, [("00:00", "Start"), ("10:00", "Past the end")]);
+        let segments = response.script.unwrap().segments;
+        let time_codes: Vec<&str> = segments.iter().map(|s| s.time_code.as_str()).collect();
+        assert_eq!(time_codes, ["00:07", "10:00"]);
+
+        let warnings: Vec<&str> = response.meta["time_code_warnings"].lines().collect();
+        assert_eq!(
+            warnings,
+            [
+                "Moved chapter 1 from 1:05:30 to the end of the video at 10:00",
+                "Dropped chapter 2: 05:00 isn't after the previous chapter at 10:00",
+                "Dropped chapter 3: unreadable time code 'about noon'",
+                "Moved script segment 1 from 65:00 to the end of the video at 10:00",
+                "Dropped script segment 2: unreadable time code ''",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_time_codes_without_duration() {
+        let mut chapters = vec![Chapter { time_code: "65:00".into(), title: "Late".into(), description: None }];
+        let mut segments = vec![];
+        assert!(normalize_time_codes(&mut chapters, &mut segments, None).is_empty());
+        assert_eq!(chapters[0].time_code, "01:05:00");
+    }
+
+    #[tokio::test]
+    async fn test_fabricated_citations_are_flagged() {
+        let narration = serde_json::json!({
+            "chapters": [{"time_code": "00:00", "title": "Start"}],
+            "script": [
+                {"time_code": "00:00", "narration": "We leave town.", "source_refs": ["event-0"]},
+                {"time_code": "00:10", "narration": "The famous tower rises ahead.", "source_refs": ["event-1", "poi-tower"]},
+                {"time_code": "00:20", "narration": "What a day."},
+            ]
+        });
+        let (engine, mock) = engine(MockGemini::new().with_text(&narration.to_string()));
+        let response = engine.generate_narration(request(2), &|_| {}).await.unwrap();
+
+        // Ids are shown to the model to cite
+        assert!(mock.prompts()[0].contains("[event-1]: No landmarks"));
+
+        let segments = response.script.unwrap().segments;
+        let statuses: Vec<CitationStatus> = segments.iter().map(|s| s.citation).collect();
+        assert_eq!(statuses, [CitationStatus::Verified, CitationStatus::Fabricated, CitationStatus::Uncited]);
+        assert_eq!(segments[1].unknown_refs, ["poi-tower"]);
+        assert_eq!(response.meta["fabricated_segments"], "1");
+        assert_eq!(response.meta["uncited_segments"], "2");
+
+        let mut cited: Vec<&String> = response.evidence.keys().collect();
+        cited.sort();
+        assert_eq!(cited, ["event-0", "event-1"]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_options_are_rejected() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(1);
+        req.options.insert("tone".to_string(), serde_json::json!("sarcastic"));
+
+        let err = engine.generate_narration(req, &|_| {}).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("unknown variant `sarcastic`"), "{:#}", err);
+        assert_eq!(mock.call_count(), 0);
+    }
+
+    #[test]
+    fn test_default_options() {
+        let mut options = HashMap::new();
+        options.insert("fresh".to_string(), serde_json::json!(true));
+        options.insert("language".to_string(), serde_json::json!("  "));
+        assert_eq!(NarrationOptions::from_options(&options).unwrap(), NarrationOptions::default());
+    }
+
+    #[tokio::test]
+    async fn test_scene_frames_use_vision_model() {
+        let text = Arc::new(MockGemini::new().with_text(VALID_JSON).with_model("gemini-3.0-flash"));
+        let vision = Arc::new(MockGemini::new().with_text(VALID_JSON).with_model("gemini-3.0-pro"));
+        let engine = NarrativeEngine::with_backends(text.clone(), vision.clone());
+
+        let mut req = request(1);
+        // An evidence snapshot: PNG, whatever the data URI claims
+        req.scene_frames = vec!["/9j/4A==".to_string().into(), "data:image/jpeg;base64,iVBORw0KGgo=".to_string().into()];
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        assert_eq!(response.meta.get("model").map(String::as_str), Some("gemini-3.0-pro"));
+        assert_eq!((text.call_count(), vision.call_count()), (0, 1));
+        let mime_types: Vec<&str> = vision.images()[0].iter().map(|i| i.mime_type).collect();
+        assert_eq!(mime_types, ["image/jpeg", "image/png"]);
+    }
+
+    #[tokio::test]
+    async fn test_variants_are_independent_takes() {
+        let second = VALID_JSON.replace("We set off early.", "Off we go at dawn.");
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON).with_text(&second));
+        let variants = engine.generate_variants(request(1), &[Sampling::at_temperature(0.3), Sampling::default()], &|_| {}).await;
+
+        assert_eq!(mock.temperatures(), [Some(0.3), None]);
+        // Only the first may come from the cache
+        assert_eq!(mock.allow_cache(), [true, false]);
+
+        let responses: Vec<NarrateResponse> = variants.into_iter().map(Result::unwrap).collect();
+        assert_eq!(responses[0].meta["temperature"], "0.3");
+        assert!(!responses[1].meta.contains_key("temperature"));
+        assert_eq!(responses[1].script.as_ref().unwrap().segments[0].narration, "Off we go at dawn.");
+    }
+
+    #[tokio::test]
+    async fn test_sampling_options_reach_the_model_and_meta() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(1);
+        req.options.insert(
+            "sampling".to_string(),
+            serde_json::json!({ "temperature": 0.7, "top_k": 40, "seed": 1234, "max_output_tokens": 0 }),
+        );
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        // A limit of no tokens at all is left to the model
+        let expected = Sampling { top_k: Some(40), seed: Some(1234), ..Sampling::at_temperature(0.7) };
+        assert_eq!(mock.samplings(), [expected]);
+        assert_eq!((response.meta["temperature"].as_str(), response.meta["seed"].as_str()), ("0.7", "1234"));
+        assert_eq!(response.meta["top_k"], "40");
+        assert!(!response.meta.contains_key("top_p") && !response.meta.contains_key("max_output_tokens"));
+    }
+
+    #[tokio::test]
+    async fn test_narration_is_kept_out_of_the_dialogue() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON).with_text(VALID_JSON));
+        let mut req = request(1);
+        // The creator talks for the first 12 seconds, over the line at 00:05
+        req.speech = vec![SpeechInterval { start_seconds: 0.0, end_seconds: 11.5 }];
+        let response = engine.generate_narration(req.clone(), &|_| {}).await.unwrap();
+
+        assert!(mock.prompts()[0].contains("## Dialogue"));
+        assert!(mock.prompts()[0].contains("- 00:12 to the end"));
+        assert_eq!(response.script.unwrap().segments[0].time_code, "00:12");
+        assert_eq!(response.meta["dialogue_adjusted_segments"], "1");
+
+        // Unless the options say to talk over it
+        req.options.insert("respect_dialogue".to_string(), serde_json::json!(false));
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+        assert!(!mock.prompts()[1].contains("## Dialogue"));
+        assert_eq!(response.script.unwrap().segments[0].time_code, "00:05");
+        assert!(!response.meta.contains_key("dialogue_adjusted_segments"));
+    }
+
+    #[tokio::test]
+    async fn test_scene_frames_are_ordered_and_marked() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(1);
+        let frames = serde_json::json!([
+            { "image": "iVBORw0KGgo=", "time_seconds": 150.0 },
+            "/9j/4A==",
+            { "image": "/9j/4A==", "time_seconds": 12.4 },
+        ]);
+        req.scene_frames = serde_json::from_value(frames).unwrap();
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let images = &mock.images()[0];
+        let labels: Vec<Option<&str>> = images.iter().map(|i| i.label.as_deref()).collect();
+        assert_eq!(labels, [Some("[Frame at 00:12]"), Some("[Frame at 02:30]"), None]);
+        assert_eq!(images[1].mime_type, "image/png");
+        assert!(mock.prompts()[0].contains("[Frame at MM:SS] marker"));
+
+        // Too many frames are thinned out over the whole video, first and last kept
+        let frames: Vec<SceneFrame> = (0..40)
+            .map(|i| SceneFrame { image: "/9j/4A==".to_string(), time_seconds: Some(i as f64 * 10.0) })
+            .collect();
+        let parts = frame_parts(&frames).unwrap();
+        assert_eq!(parts.len(), MAX_NARRATION_FRAMES);
+        assert_eq!(parts[0].label.as_deref(), Some("[Frame at 00:00]"));
+        assert_eq!(parts[MAX_NARRATION_FRAMES - 1].label.as_deref(), Some("[Frame at 06:30]"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_frame_names_index() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(1);
+        req.scene_frames = vec!["/9j/4A==".to_string().into(), "R0lGODlh".to_string().into()];
+
+        let err = engine.generate_narration(req, &|_| {}).await.unwrap_err();
+        assert_eq!(err.to_string(), ImageError::UnsupportedFormat { index: 1 }.to_string());
+        assert_eq!(mock.call_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_meta_reports_engine() {
+        let (engine, _) = engine(MockGemini::new().with_text(VALID_JSON).with_engine("local").with_model("llama3.2"));
+        let response = engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        assert_eq!(response.meta.get("engine").map(String::as_str), Some("local"));
+        assert_eq!(response.meta.get("model").map(String::as_str), Some("llama3.2"));
+    }
+
+    #[tokio::test]
+    async fn test_fresh_option_bypasses_cache() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON).with_text(VALID_JSON));
+        engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        let mut req = request(1);
+        req.options.insert("fresh".to_string(), serde_json::json!(true));
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        assert_eq!(mock.allow_cache(), [true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_truncated_response_retries_shorter() {
+        let (mut engine, mock) = engine(MockGemini::new().with_truncated(r#"{"chapters": [{"#).with_text(VALID_JSON));
+        engine.chunking = Some(NarrationChunking { max_events: 30, ..Default::default() });
+        let response = engine.generate_narration(request(30), &|_| {}).await.unwrap();
+
+        let prompts = mock.prompts();
+        assert_eq!(prompts.len(), 2);
+        assert_eq!(prompts[1].matches("- At ").count(), 8);
+        assert!(prompts[1].contains("Keep it brief"));
+        // The retry is a different request; a cached answer wouldn't help
+        assert_eq!(mock.allow_cache(), [true, false]);
+        assert_eq!(response.meta.get("finish_reason").map(String::as_str), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_twice_fails() {
+        let (engine, mock) = engine(MockGemini::new().with_truncated("{").with_truncated("{"));
+        let err = engine.generate_narration(request(1), &|_| {}).await.unwrap_err();
+        assert!(err.to_string().contains("output limit"));
+        assert_eq!(mock.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_markdown_wrapped_json_response() {
+        let wrapped = format!("```json\n{}\n```", VALID_JSON);
+        let (engine, _) = engine(MockGemini::new().with_text(&wrapped));
+        let response = engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        assert_eq!(response.chapters.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_response() {
+        let (engine, mock) = engine(MockGemini::new().with_text("{\"chapters\": [ oops").with_text("Sorry!"));
+        assert!(engine.generate_narration(request(1), &|_| {}).await.is_err());
+        // One reformat request, no more
+        assert_eq!(mock.call_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_response_is_reformatted() {
+        let prose = "Chapter 1 at 00:00, Start: leaving town. Narration at 00:05: We set off early.";
+        let (engine, mock) = engine(MockGemini::new().with_text(prose).with_text(VALID_JSON));
+        let response = engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        let prompts = mock.prompts();
+        assert!(prompts[1].starts_with("Reformat the following narration as strict JSON"));
+        assert!(prompts[1].ends_with(prose));
+        assert!(mock.images()[1].is_empty());
+        assert_eq!(response.chapters[0].title, "Start");
+        assert_eq!(response.meta.get("reformatted").map(String::as_str), Some("true"));
+    }
+
+    #[test]
+    fn test_extract_json_from_messy_responses() {
+        use serde_json::json;
+
+        let cases: &[(&str, Option<serde_json::Value>)] = &[
+            (r#"{"a": 1}"#, Some(json!({"a": 1}))),
+            ("```json\n{\"a\": 1}\n```", Some(json!({"a": 1}))),
+            ("```\n{\"a\": 1}\n```", Some(json!({"a": 1}))),
+            ("Here is the narration you asked for:\n\n```json\n{\"a\": 1}\n```", Some(json!({"a": 1}))),
+            ("{\"a\": 1}\n\nI kept each chapter short, as requested.", Some(json!({"a": 1}))),
+            ("```json\n{\"a\": 1}\n```\nOr, shorter:\n```json\n{\"b\": 2}\n```", Some(json!({"a": 1}))),
+            ("```json{\"a\": 1}```", Some(json!({"a": 1}))),
+            (r#"{"narration": "A sign reads \"}{\" here"}"#, Some(json!({"narration": "A sign reads \"}{\" here"}))),
+            (r#"{"path": "C:\\", "b": [1]}"#, Some(json!({"path": "C:\\", "b": [1]}))),
+            (r#"{"chapters": [{"title": "Start",},], "script": [],}"#, Some(json!({"chapters": [{"title": "Start"}], "script": []}))),
+            (r#"{"title": "Lunch, then the beach",}"#, Some(json!({"title": "Lunch, then the beach"}))),
+            (r#"Result [draft 2]: {"a": 1}"#, Some(json!({"a": 1}))),
+            (r#"Sure! [{"index": 0, "description": "Harbour"}]"#, Some(json!([{"index": 0, "description": "Harbour"}]))),
+            (r#"{"chapters": [{"title": "St"#, None),
+            ("I can't narrate this footage.", None),
+        ];
+
+        for (text, expected) in cases {
+            let extracted = extract_json(text).map(|json| serde_json::from_str::<serde_json::Value>(&json).unwrap());
+            assert_eq!(&extracted, expected, "{}", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_safety_blocked_response() {
+        let (engine, _) = engine(MockGemini::new().with_error(GeminiError::SafetyBlocked { categories: vec![] }));
+        let err = engine.generate_narration(request(1), &|_| {}).await.unwrap_err();
+        assert!(err.to_string().starts_with("Gemini declined to respond"));
+        assert!(matches!(err.downcast_ref::<GeminiError>(), Some(GeminiError::SafetyBlocked { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_key_message() {
+        let (engine, _) = engine(MockGemini::new().with_error(GeminiError::InvalidKey));
+        let err = engine.generate_narration(request(1), &|_| {}).await.unwrap_err();
+        assert_eq!(err.to_string(), GeminiError::InvalidKey.to_string());
+        assert!(err.to_string().contains("API key was rejected"));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_model_falls_back_to_templates() {
+        let bundle: TruthBundle = serde_json::from_str(include_str!("testdata/offline_bundle.json")).unwrap();
+        let cases = [
+            ("English", "offline_narration_en.json", include_str!("testdata/offline_narration_en.json")),
+            ("Español", "offline_narration_es.json", include_str!("testdata/offline_narration_es.json")),
+        ];
+
+        for (language, name, golden) in cases {
+            let unavailable = GeminiError::LocalUnavailable("http://localhost:11434".to_string());
+            let (engine, _) = engine(MockGemini::new().with_error(unavailable.clone()));
+            let mut req = request(0);
+            req.truth_bundle = bundle.clone();
+            req.options.insert("language".to_string(), serde_json::json!(language));
+            let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+            assert_eq!(response.meta.get("engine").map(String::as_str), Some("offline-template"));
+            assert_eq!(response.meta.get("language").map(String::as_str), Some(language));
+            assert_eq!(response.meta.get("fallback_reason"), Some(&unavailable.to_string()));
+            assert!(!response.meta.contains_key("model"));
+            // Every line is built from the bundle, so every line checks out
+            let segments = &response.script.as_ref().unwrap().segments;
+            assert!(segments.iter().all(|s| s.citation == CitationStatus::Verified));
+
+            let narration = serde_json::json!({ "chapters": response.chapters, "script": segments });
+            assert_golden_json(name, golden, &narration);
+        }
+
+        // Languages without bundled sentences get English; other failures aren't papered over
+        let (timed_out, _) = engine(MockGemini::new().with_error(GeminiError::Timeout));
+        let mut req = request(1);
+        req.options.insert("language".to_string(), serde_json::json!("Deutsch"));
+        let response = timed_out.generate_narration(req, &|_| {}).await.unwrap();
+        assert_eq!(response.meta.get("language").map(String::as_str), Some("English"));
+        assert_eq!(response.chapters[0].title, "Part 1");
+
+        let (overloaded, _) = engine(MockGemini::new().with_error(GeminiError::Overloaded));
+        assert!(overloaded.generate_narration(request(1), &|_| {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_long_requests_are_narrated_in_chunks() {
+        let part = |minute: u32, title: &str| {
+            serde_json::json!({
+                "chapters": [{"time_code": format!("{:02}:00", minute), "title": title}],
+                "script": [{"time_code": format!("{:02}:30", minute), "narration": format!("{} begins.", title)}]
+            })
+            .to_string()
+        };
+        // The model answers out of order; the merged result isn't
+        let (engine, mock) = engine(
+            MockGemini::new()
+                .with_text(&part(0, "Harbour"))
+                .with_text(&part(25, "Old town"))
+                .with_text(&part(12, "Coast road")),
+        );
+        let mut req = request(30);
+        let start = Utc::now();
+        for (i, event) in req.truth_bundle.events.iter_mut().enumerate() {
+            event.timestamp = start + chrono::Duration::minutes(i as i64);
+        }
+        // 5000 characters of transcript needs three chunks of 2000, though the events need only two
+        req.transcript = Some("word ".repeat(1000));
+        req.scene_frames = vec!["/9j/4A==".to_string().into()];
+
+        let progress = std::sync::Mutex::new(Vec::new());
+        let response = engine.generate_narration(req, &|p| progress.lock().unwrap().push(p)).await.unwrap();
+
+        let progress = progress.into_inner().unwrap();
+        let started: Vec<(usize, usize)> = progress.iter().filter(|p| !p.finished).map(|p| (p.chunk, p.chunk_count)).collect();
+        assert_eq!(started, [(1, 3), (2, 3), (3, 3)]);
+        // Each chunk's chapters and lines come as it finishes, in the order narrated
+        let finished: Vec<(usize, &str, &str)> = progress
+            .iter()
+            .filter(|p| p.finished)
+            .map(|p| (p.chunk, p.chapters[0].title.as_str(), p.segments[0].time_code.as_str()))
+            .collect();
+        assert_eq!(finished, [(1, "Harbour", "00:30"), (2, "Old town", "25:30"), (3, "Coast road", "12:30")]);
+        assert!(progress.iter().all(|p| p.variant == 1));
+        let prompts = mock.prompts();
+        for prompt in &prompts {
+            assert_eq!(prompt.matches("- At ").count(), 10);
+            assert!(!prompt.contains(&"word ".repeat(400)));
+        }
+        assert!(prompts[0].contains("## Part 1 of 3"));
+        assert!(!prompts[0].contains("## Story So Far"));
+        assert!(prompts[1].contains("These events run from 10:00 to 19:00"));
+        assert!(prompts[1].contains("Chapters: 00:00 Harbour"));
+        assert!(prompts[2].contains("Narration at 25:30: Old town begins."));
+        // The frames went with the first chunk only
+        let image_counts: Vec<usize> = mock.images().iter().map(Vec::len).collect();
+        assert_eq!(image_counts, [1, 0, 0]);
+
+        let titles: Vec<&str> = response.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Harbour", "Coast road", "Old town"]);
+        let time_codes: Vec<String> = response.script.unwrap().segments.into_iter().map(|s| s.time_code).collect();
+        assert_eq!(time_codes, ["00:30", "12:30", "25:30"]);
+        assert_eq!(response.meta["chunks"], "3");
+    }
+
+    #[tokio::test]
+    async fn test_passes_are_described_as_they_come_up() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(3);
+        let bridge = |distance_m: f64, bearing_deg: f64| POI {
+            id: "poi-bridge".to_string(),
+            name: "Golden Gate Bridge".to_string(),
+            name_local: None,
+            category: "bridge".to_string(),
+            subcategory: None,
+            lat: 37.8199,
+            lon: -122.4783,
+            distance_m,
+            bearing_deg,
+            in_fov: false,
+            confidence: 1.0,
+            facts: None,
+            pinned: false,
+        };
+        let events = &mut req.truth_bundle.events;
+        events[1].kind = EventKind::Approach;
+        events[1].heading_deg = Some(10.0);
+        events[1].pois = vec![bridge(1_420.0, 320.0)];
+        events[2].kind = EventKind::NearestPass;
+        events[2].pois = vec![bridge(85.0, 270.0)];
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompt = &mock.prompts()[0];
+        assert!(prompt.contains("[event-1]: Approaching Golden Gate Bridge [poi-bridge], 1.4 km away on the left"));
+        // Which side isn't known without a heading
+        assert!(prompt.contains("[event-2]: Passing closest to Golden Gate Bridge [poi-bridge], 85 m away (location"));
+        assert!(prompt.contains("coming up on the left"));
+    }
+
+    #[tokio::test]
+    async fn test_local_and_uncertain_times_are_in_the_prompt() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(3);
+        let events = &mut req.truth_bundle.events;
+        let start = DateTime::parse_from_rfc3339("2025-06-01T08:42:00Z").unwrap().with_timezone(&Utc);
+        for (i, event) in events.iter_mut().enumerate() {
+            event.timestamp = start + chrono::Duration::minutes(i as i64);
+        }
+        events[0].context = Some(LocationContext { timezone: Some("Europe/Monaco".to_string()), ..Default::default() });
+        events[0].localize_time();
+        events[2].time_uncertain = true;
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompt = &mock.prompts()[0];
+        assert!(prompt.contains("- At 08:42:00 (10:42 local) [event-0]"));
+        assert!(prompt.contains("- At 08:43:00 [event-1]"));
+        assert!(prompt.contains("- At 08:44:00 (uncertain) [event-2]"));
+        assert!(prompt.contains(LOCAL_TIME_NOTE) && prompt.contains(UNCERTAIN_TIME_NOTE));
+    }
+
+    #[tokio::test]
+    async fn test_landmark_articles_are_background() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(3);
+        let extract = "The Prince's Palace of Monaco is the official residence of the Sovereign Prince of Monaco. \
+            Built in 1191 as a Genoese fortress, it has been bombed and besieged by many foreign powers. "
+            .repeat(3);
+        let palace = POI {
+            id: "poi-palace".to_string(),
+            name: "Prince's Palace".to_string(),
+            name_local: None,
+            category: "castle".to_string(),
+            subcategory: None,
+            lat: 43.7308,
+            lon: 7.4204,
+            distance_m: 240.0,
+            bearing_deg: 90.0,
+            in_fov: true,
+            confidence: 1.0,
+            facts: Some(POIFacts {
+                summary: Some(ArticleSummary {
+                    title: "Prince's Palace of Monaco".to_string(),
+                    language: "en".to_string(),
+                    extract,
+                    url: None,
+                    source: "wikipedia".to_string(),
+                    retrieved_on: "2026-10-16".parse().unwrap(),
+                }),
+                ..Default::default()
+            }),
+            pinned: false,
+        };
+        req.truth_bundle.events[1].pois = vec![palace.clone()];
+        req.truth_bundle.events[2].pois = vec![palace];
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompt = &mock.prompts()[0];
+        assert!(prompt.contains("## Background"));
+        assert_eq!(prompt.matches("- Prince's Palace [poi-palace]: The Prince's Palace").count(), 1);
+        // Cut after the last sentence that fits
+        assert!(prompt.contains("foreign powers. The Prince's Palace of Monaco is the official residence of the \
+            Sovereign Prince of Monaco. (en wikipedia, retrieved 2026-10-16)"));
+    }
+
+    #[tokio::test]
+    async fn test_route_segments_shape_the_prompt() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON));
+        let mut req = request(3);
+        let start = req.truth_bundle.events[0].timestamp;
+        let segment = |from: i64, to: i64, city: &str, country: &str| RouteSegment {
+            start_time: start + chrono::Duration::minutes(from),
+            end_time: start + chrono::Duration::minutes(to),
+            country: Some(country.to_string()),
+            region: None,
+            city: Some(city.to_string()),
+            distance_km: 2.34,
+        };
+        req.truth_bundle.route_segments = vec![
+            segment(-30, -10, "Nice", "France"),
+            segment(-10, 5, "Monte-Carlo", "Monaco"),
+            segment(5, 20, "Menton", "France"),
+        ];
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        let prompt = &mock.prompts()[0];
+        assert!(prompt.contains("## Route"));
+        assert!(prompt.contains(": Monte-Carlo, Monaco (2.3 km)"));
+        // Only the stretch the events are on
+        assert!(!prompt.contains("Nice") && !prompt.contains("Menton"));
+    }
+
+    #[tokio::test]
+    async fn test_conditions_are_in_the_prompt() {
+        let (engine, mock) = engine(MockGemini::new().with_text(VALID_JSON).with_text(VALID_JSON));
+        let mut req = request(3);
+        req.truth_bundle.track_stats = Some(TrackStats {
+            distance_km: 84.23,
+            duration_seconds: Some(8100.0),
+            avg_speed_kmh: Some(37.4),
+            max_speed_kmh: None,
+            elevation_gain_m: Some(1204.0),
+            elevation_loss_m: Some(310.0),
+        });
+        let sun = |elevation_deg: f64, light: Daylight| SunFacts {
+            elevation_deg,
+            azimuth_deg: 290.0,
+            sunrise: None,
+            sunset: Some("2025-06-01T20:41:00Z".parse().unwrap()),
+            light,
+        };
+        let events = &mut req.truth_bundle.events;
+        events[0].sun = Some(sun(5.2, Daylight::GoldenHour));
+        events[1].sun = Some(sun(3.9, Daylight::GoldenHour));
+        events[2].sun = Some(sun(-4.6, Daylight::BlueHour));
+        events[2].weather = Some(WeatherObservation {
+            temperature_c: Some(14.3),
+            precipitation_mm: Some(1.2),
+            wind_speed_kmh: None,
+            cloud_cover_percent: None,
+            condition: Some("light rain".to_string()),
+            source: "open-meteo".to_string(),
+        });
+        engine.generate_narration(req, &|_| {}).await.unwrap();
+        engine.generate_narration(request(1), &|_| {}).await.unwrap();
+
+        let prompts = mock.prompts();
+        let prompt = &prompts[0];
+        assert!(prompt.contains("## Conditions"));
+        assert!(prompt.contains("- Whole track: 84.2 km, in 2 h 15 min, averaging 37 km/h, 1204 m climbed, 310 m descended"));
+        assert!(prompt.contains("- Sunset 20:41"));
+        // Listed where the light changes
+        assert_eq!(prompt.matches("golden hour, sun 5° above the horizon").count(), 1);
+        assert!(prompt.contains("blue hour, sun 5° below the horizon"));
+        assert!(prompt.contains("light rain, 14 °C, 1.2 mm of precipitation (recorded by open-meteo)"));
+        assert!(prompt.contains("only as listed under Conditions"));
+
+        assert!(!prompts[1].contains("## Conditions"));
+    }
+
+    #[tokio::test]
+    async fn test_revision_rewrites_only_changed_chapters() {
+        let rewrite = serde_json::json!({
+            "chapters": [{"time_code": "01:00", "title": "Cliffs"}],
+            "script": [
+                {"time_code": "01:20", "narration": "Cliffs drop to the sea.", "source_refs": ["event-1"]},
+                {"time_code": "03:00", "narration": "Strayed past the stretch."}
+            ]
+        });
+        let (engine, mock) = engine(MockGemini::new().with_text(&rewrite.to_string()));
+        let previous: NarrateResponse = serde_json::from_value(serde_json::json!({
+            "chapters": [
+                {"time_code": "00:00", "title": "Harbour"},
+                {"time_code": "01:00", "title": "Coast road"},
+                {"time_code": "02:00", "title": "Old town"}
+            ],
+            "script": {"segments": [
+                {"time_code": "00:10", "narration": "Boats  bob in the harbour.", "slot_seconds": 60.0},
+                {"time_code": "01:10", "narration": "The road hugs the coast."},
+                {"time_code": "02:10", "narration": "Into the old town."}
+            ]},
+            "meta": {"model": "earlier", "chunks": "3"}
+        }))
+        .unwrap();
+
+        let mut req = request(30);
+        let start = Utc::now();
+        for (i, event) in req.truth_bundle.events.iter_mut().enumerate() {
+            event.timestamp = start + chrono::Duration::minutes(i as i64);
+        }
+        req.revision = Some(NarrationRevision {
+            previous: previous.clone(),
+            changed_windows: vec![crate::types::TimeWindow { start_seconds: 70.0, end_seconds: 80.0 }],
+        });
+        let response = engine.generate_narration(req, &|_| {}).await.unwrap();
+
+        // One request, for the chapter the window falls in
+        let prompts = mock.prompts();
+        assert_eq!(prompts.len(), 1);
+        assert!(prompts[0].contains("Only the narration from 01:00 to 02:00 is being written again"));
+        assert!(prompts[0].contains("Narration before, at 00:10: Boats  bob in the harbour."));
+        assert!(prompts[0].contains("Chapter after: 02:00 Old town"));
+        assert_eq!(prompts[0].matches("- At ").count(), 1);
+
+        let titles: Vec<&str> = response.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Harbour", "Cliffs", "Old town"]);
+        let segments = response.script.unwrap().segments;
+        let time_codes: Vec<&str> = segments.iter().map(|s| s.time_code.as_str()).collect();
+        assert_eq!(time_codes, ["00:10", "01:20", "02:10"]);
+        // The lines outside it are untouched, down to their spacing and timing
+        let old = previous.script.unwrap().segments;
+        for (kept, was) in [(&segments[0], &old[0]), (&segments[2], &old[2])] {
+            assert_eq!(serde_json::to_string(kept).unwrap(), serde_json::to_string(was).unwrap());
+        }
+        assert_eq!(segments[1].citation, CitationStatus::Verified);
+        assert_eq!(segments[1].slot_seconds, Some(50.0));
+
+        assert_eq!(response.meta["revised_windows"], "01:00 to 02:00");
+        assert_eq!(response.meta["revised_segments"], "1");
+        assert!(!response.meta.contains_key("chunks"));
+        assert!(response.meta["time_code_warnings"].contains("outside 01:00 to 02:00"));
+    }
+
+    #[test]
+    fn test_short_requests_are_one_chunk() {
+        let mut req = request(20);
+        req.transcript = Some("A short transcript".to_string());
+        let chunks = split_into_chunks(&req, NarrationChunking::default());
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].events.len(), chunks[0].transcript.as_str()), (20, "A short transcript"));
+        assert_eq!(continuity_note(&chunks[0], 0, 1, &[], &[]), "");
+
+        assert_eq!(split_text("aa bb cc dd", 2), ["aa bb", "cc dd"]);
+        assert_eq!(split_text("", 3), ["", "", ""]);
     }
-    text.to_string()
 }
@@ -0,0 +1,285 @@
+//! User Overrides
+//!
+//! Corrections the user makes to an event's enrichment: a context value
+//! set by hand, such as the right city, or a POI pinned to the event. They
+//! are kept in their own table rather than only in the stored Truth Event,
+//! and applied over it last, so enriching the event again can't undo them.
+
+use anyhow::{Context, Result};
+use tracing::warn;
+
+use crate::enrich::stored_truth;
+use crate::services::database::{EventOverride, PoiRecord};
+use crate::services::truth_engine::{self, VerificationConfidence};
+use crate::services::LocalDatabase;
+use crate::state::AppState;
+use crate::types::{AttributedValue, FactSource, LocationContext, TruthEvent, POI};
+
+/// Field of the overrides that pin a POI, whose value is the POI's id
+pub const PINNED_POI: &str = "poi";
+
+/// Check that a context override can be kept: a field the user can set,
+/// with a value it can hold
+pub fn check_context(field: &str, value: &str) -> Result<(), String> {
+    set_field(&mut LocationContext::default(), field, value)
+}
+
+/// Apply overrides over a Truth Event, so they win over what enrichment found
+///
+/// `pinned` has the POIs the overrides pin; those the event already lists
+/// are only marked. Pinned POIs go first, closest first, so a long list
+/// being cut short never loses them. Overrides of other events are skipped.
+pub fn apply(truth: &mut TruthEvent, overrides: &[EventOverride], pinned: &[PoiRecord]) {
+    let confidence = VerificationConfidence::High.as_f64();
+    let event_id = truth.id.clone();
+    for event_override in overrides.iter().filter(|o| o.event_id == event_id) {
+        let EventOverride { field, value, .. } = event_override;
+        if field == PINNED_POI {
+            pin(truth, value, pinned, confidence);
+            continue;
+        }
+
+        let context = truth.context.get_or_insert_with(LocationContext::default);
+        match set_field(context, field, value) {
+            Ok(()) => {
+                let value = value.trim().to_string();
+                let attributed = AttributedValue { value, source: FactSource::User, confidence };
+                context.attribution.insert(field.clone(), attributed);
+            }
+            // Kept before the field could be set, or edited by hand
+            Err(e) => warn!("Ignoring an override of event {}: {}", truth.id, e),
+        }
+    }
+    truth.localize_time();
+    // Stable, so the other POIs stay as they were
+    truth.pois.sort_by(|a, b| match (a.pinned, b.pinned) {
+        (true, true) => a.distance_m.total_cmp(&b.distance_m),
+        (a_pinned, b_pinned) => b_pinned.cmp(&a_pinned),
+    });
+}
+
+/// Undo an override in a Truth Event, before the remaining ones are applied again
+///
+/// A context field is emptied rather than put back the way enrichment had
+/// it; enriching again fills it in. Unpinned POIs are dropped, and listed
+/// again if enrichment finds them nearby. Without a `value` every pinned POI
+/// is unpinned.
+pub fn clear(truth: &mut TruthEvent, field: &str, value: Option<&str>) {
+    if field == PINNED_POI {
+        truth.pois.retain(|poi| !poi.pinned || value.is_some_and(|id| id != poi.id));
+    } else if let Some(context) = &mut truth.context {
+        clear_field(context, field);
+        context.attribution.remove(field);
+        truth.localize_time();
+    }
+}
+
+/// The POIs pinned by `overrides`, from the database; ones no longer there
+/// are left out
+pub async fn pinned_pois(db: &LocalDatabase, overrides: &[EventOverride]) -> Vec<PoiRecord> {
+    let mut ids: Vec<&str> = overrides.iter().filter(|o| o.field == PINNED_POI).map(|o| o.value.as_str()).collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut pois = Vec::with_capacity(ids.len());
+    for id in ids {
+        match db.get_poi(id).await {
+            Ok(poi) => pois.push(poi),
+            Err(e) => warn!("Pinned POI {} can't be read: {}", id, e),
+        }
+    }
+    pois
+}
+
+/// Apply an event's overrides to its stored Truth Event, after undoing
+/// `cleared` (a field and maybe a value), and keep the result
+///
+/// The video's cached bundle in `state` is dropped, as it no longer matches.
+pub async fn rewrite_event(
+    db: &LocalDatabase,
+    state: &AppState,
+    event_id: &str,
+    cleared: Option<(&str, Option<&str>)>,
+) -> Result<TruthEvent> {
+    let event = db.get_event(event_id).await.with_context(|| format!("Event {} not found", event_id))?;
+    let mut truth = stored_truth(&event)
+        .with_context(|| format!("Event {} has a stored bundle that can't be read", event_id))?;
+    if let Some((field, value)) = cleared {
+        clear(&mut truth, field, value);
+    }
+
+    let overrides = db.get_event_overrides(event_id).await?;
+    let pinned = pinned_pois(db, &overrides).await;
+    apply(&mut truth, &overrides, &pinned);
+    db.set_event_truth(&[(event_id.to_string(), serde_json::to_string(&truth)?)]).await?;
+    state.event_overridden(&event.video_id);
+    Ok(truth)
+}
+
+fn pin(truth: &mut TruthEvent, poi_id: &str, pinned: &[PoiRecord], confidence: f64) {
+    if let Some(poi) = truth.pois.iter_mut().find(|poi| poi.id == poi_id) {
+        poi.pinned = true;
+        poi.confidence = confidence;
+        return;
+    }
+    let Some(record) = pinned.iter().find(|record| record.id == poi_id) else {
+        warn!("POI {} pinned to event {} is no longer in the map data", poi_id, truth.id);
+        return;
+    };
+
+    // Without a location of its own the event is taken to be at the POI
+    let (lat, lon) = truth.location.as_ref().map_or((record.lat, record.lon), |l| (l.lat, l.lon));
+    let Some(local) = truth_engine::within_radius(lat, lon, vec![record.clone()], f64::INFINITY).pop() else {
+        return;
+    };
+    truth.pois.push(POI { pinned: true, confidence, ..POI::from(local) });
+}
+
+/// Set a context field from its value as text
+fn set_field(context: &mut LocationContext, field: &str, value: &str) -> Result<(), String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("An override of {} needs a value", field));
+    }
+    let text = Some(value.to_string());
+    match field {
+        "country" => context.country = text,
+        "state" => context.state = text,
+        "county" => context.county = text,
+        "region" => context.region = text,
+        "city" => context.city = text,
+        "road" => context.road = text,
+        "timezone" => context.timezone = text,
+        "population" => {
+            let population = value.parse::<i64>().ok().filter(|p| *p > 0);
+            context.population = Some(population.ok_or_else(|| format!("Population must be a positive whole number, not {}", value))?);
+        }
+        "elevation_m" => {
+            let elevation = value.parse::<f64>().ok().filter(|e| e.is_finite());
+            context.elevation_m = Some(elevation.ok_or_else(|| format!("Elevation must be a number of meters, not {}", value))?);
+        }
+        _ => return Err(format!("{} isn't a context field that can be set", field)),
+    }
+    Ok(())
+}
+
+fn clear_field(context: &mut LocationContext, field: &str) {
+    match field {
+        "country" => context.country = None,
+        "state" => context.state = None,
+        "county" => context.county = None,
+        "region" => context.region = None,
+        "city" => context.city = None,
+        "road" => context.road = None,
+        "timezone" => context.timezone = None,
+        "population" => context.population = None,
+        "elevation_m" => context.elevation_m = None,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EventKind, LocationResult};
+    use chrono::Utc;
+
+    fn truth() -> TruthEvent {
+        TruthEvent {
+            id: "e1".to_string(),
+            kind: EventKind::Speech,
+            timestamp: Utc::now(),
+            local_time: None,
+            time_uncertain: false,
+            duration_seconds: None,
+            location: Some(LocationResult { lat: 43.7384, lon: 7.4246 }),
+            heading_deg: None,
+            pois: vec![],
+            detected_objects: vec![],
+            image_path: None,
+            sun: None,
+            weather: None,
+            context: None,
+        }
+    }
+
+    fn user_override(field: &str, value: &str) -> EventOverride {
+        EventOverride { event_id: "e1".to_string(), field: field.to_string(), value: value.to_string() }
+    }
+
+    fn record(id: &str, lat: f64, lon: f64) -> PoiRecord {
+        PoiRecord { id: id.to_string(), name: id.to_string(), category: "landmark".to_string(), lat, lon, ..Default::default() }
+    }
+
+    #[test]
+    fn test_overrides_win_over_enrichment() {
+        let mut truth = truth();
+        let mut context = LocationContext { city: Some("Nice".to_string()), country: Some("France".to_string()), ..Default::default() };
+        context.attribute(FactSource::Llm, 0.3);
+        truth.context = Some(context);
+
+        let overrides = [
+            user_override("city", " Monaco "),
+            user_override("population", "38000"),
+            EventOverride { event_id: "e2".to_string(), ..user_override("country", "Italy") },
+        ];
+        apply(&mut truth, &overrides, &[]);
+
+        let context = truth.context.unwrap();
+        assert_eq!(context.city.as_deref(), Some("Monaco"));
+        assert_eq!(context.population, Some(38_000));
+        let city = &context.attribution["city"];
+        assert_eq!((city.value.as_str(), city.source), ("Monaco", FactSource::User));
+        assert_eq!(city.confidence, VerificationConfidence::High.as_f64());
+        // Left as enrichment had it; that override was another event's
+        assert_eq!(context.country.as_deref(), Some("France"));
+        assert_eq!(context.attribution["country"].source, FactSource::Llm);
+    }
+
+    #[test]
+    fn test_pinned_pois_are_listed_first_and_unpinned_again() {
+        let mut truth = truth();
+        let listed = |record: PoiRecord| {
+            POI::from(truth_engine::within_radius(43.7384, 7.4246, vec![record], f64::INFINITY).pop().unwrap())
+        };
+        let palace = record("palace", 43.7308, 7.4204);
+        let fort = record("fort", 43.7338, 7.4268);
+        // As enrichment lists them, closest first
+        truth.pois = vec![listed(record("casino", 43.7393, 7.4283)), listed(palace.clone())];
+
+        let overrides = [user_override(PINNED_POI, "palace"), user_override(PINNED_POI, "fort")];
+        apply(&mut truth, &overrides, &[fort]);
+        let ids = |truth: &TruthEvent| truth.pois.iter().map(|p| p.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&truth), ["fort", "palace", "casino"]);
+        assert!(truth.pois[1].pinned && !truth.pois[2].pinned);
+        assert_eq!(truth.pois[1].confidence, VerificationConfidence::High.as_f64());
+        assert!(truth.pois[0].distance_m > 0.0);
+
+        clear(&mut truth, PINNED_POI, Some("palace"));
+        assert_eq!(ids(&truth), ["fort", "casino"]);
+        clear(&mut truth, PINNED_POI, None);
+        assert_eq!(ids(&truth), ["casino"]);
+    }
+
+    #[test]
+    fn test_context_overrides_are_checked() {
+        assert!(check_context("city", "Monaco").is_ok());
+        assert!(check_context("city", "  ").is_err());
+        assert!(check_context("population", "many").is_err());
+        assert!(check_context("population", "-5").is_err());
+        assert!(check_context("elevation_m", "65.5").is_ok());
+        assert!(check_context("attribution", "x").is_err());
+    }
+
+    #[test]
+    fn test_cleared_context_override_empties_the_field() {
+        let mut truth = truth();
+        apply(&mut truth, &[user_override("city", "Monaco"), user_override("road", "Quai Antoine 1er")], &[]);
+
+        clear(&mut truth, "city", None);
+        let context = truth.context.unwrap();
+        assert_eq!(context.city, None);
+        assert!(!context.attribution.contains_key("city"));
+        assert_eq!(context.attribution["road"].source, FactSource::User);
+    }
+}
@@ -0,0 +1,130 @@
+//! Narration Pacing
+//!
+//! Keeps a script speakable in the time it has. Each segment's slot runs
+//! from its time code to the next segment's, and the last one's to the end
+//! of the target duration. Spoken length is estimated from the word count
+//! at the chosen speech rate, which is close enough to place segments on
+//! the timeline and to catch the ones that would run into the next.
+
+use crate::types::ScriptSegment;
+
+/// Speech rate of a relaxed narrator, in words per minute
+pub const DEFAULT_SPEECH_RATE_WPM: u32 = 150;
+
+/// A segment may run this much over its slot before it's too long
+const OVERRUN_TOLERANCE: f64 = 1.1;
+
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Seconds `text` takes to speak at `wpm`
+pub fn spoken_seconds(text: &str, wpm: u32) -> f64 {
+    word_count(text) as f64 * 60.0 / wpm.max(1) as f64
+}
+
+/// Words that can be spoken in `seconds` at `wpm`
+pub fn word_budget(seconds: f64, wpm: u32) -> usize {
+    (seconds.max(0.0) * wpm as f64 / 60.0).floor() as usize
+}
+
+/// Seconds from the start of the video for an `MM:SS` or `HH:MM:SS` time code
+pub fn time_code_seconds(time_code: &str) -> Option<f64> {
+    let parts: Vec<&str> = time_code.trim().split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+
+    let mut seconds = 0.0;
+    for part in parts {
+        let value: f64 = part.trim().parse().ok()?;
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    Some(seconds)
+}
+
+/// Canonical time code for `seconds`: `MM:SS`, or `HH:MM:SS` from an hour on
+pub fn format_time_code(seconds: f64) -> String {
+    let total = seconds.max(0.0).floor() as u64;
+    let (hours, minutes, seconds) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Fill in each segment's slot and estimated spoken duration
+///
+/// Returns the indexes of segments that run over their slot. Segments with
+/// a time code that can't be read, or the last one without a target
+/// duration, have no slot and never run over.
+pub fn annotate(segments: &mut [ScriptSegment], wpm: u32, target_duration_seconds: Option<f64>) -> Vec<usize> {
+    let starts: Vec<Option<f64>> = segments.iter().map(|s| time_code_seconds(&s.time_code)).collect();
+    let mut overrunning = Vec::new();
+
+    for (i, segment) in segments.iter_mut().enumerate() {
+        let next_start = match starts.get(i + 1) {
+            Some(next) => *next,
+            None => target_duration_seconds,
+        };
+        segment.slot_seconds = starts[i].zip(next_start).map(|(start, end)| (end - start).max(0.0));
+
+        let estimated = spoken_seconds(&segment.narration, wpm);
+        segment.estimated_duration_seconds = Some(estimated);
+        if segment.slot_seconds.is_some_and(|slot| estimated > slot * OVERRUN_TOLERANCE) {
+            overrunning.push(i);
+        }
+    }
+    overrunning
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(time_code: &str, words: usize) -> ScriptSegment {
+        ScriptSegment {
+            time_code: time_code.to_string(),
+            narration: vec!["word"; words].join(" "),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_time_codes() {
+        assert_eq!(time_code_seconds("01:05"), Some(65.0));
+        assert_eq!(time_code_seconds("1:05:30"), Some(3930.0));
+        assert_eq!(time_code_seconds(" 65:00 "), Some(3900.0));
+        assert_eq!(time_code_seconds("00:07.5"), Some(7.5));
+        assert_eq!(time_code_seconds("90"), None);
+        assert_eq!(time_code_seconds("ab:cd"), None);
+        assert_eq!(time_code_seconds("-1:00"), None);
+
+        assert_eq!(format_time_code(7.9), "00:07");
+        assert_eq!(format_time_code(3900.0), "01:05:00");
+    }
+
+    #[test]
+    fn test_overrunning_segments() {
+        // 10 s at 150 wpm fits 25 words
+        assert_eq!(word_budget(10.0, 150), 25);
+
+        let mut segments = vec![segment("00:00", 25), segment("00:10", 40), segment("00:20", 80), segment("??", 5)];
+        let overrunning = annotate(&mut segments, 150, Some(60.0));
+
+        assert_eq!(overrunning, [1]);
+        assert_eq!(segments[0].slot_seconds, Some(10.0));
+        assert_eq!(segments[1].estimated_duration_seconds, Some(16.0));
+        // Neither the segment before an unreadable time code nor that one has a slot
+        assert_eq!((segments[2].slot_seconds, segments[3].slot_seconds), (None, None));
+        assert_eq!(segments[3].estimated_duration_seconds, Some(2.0));
+
+        // Without a target the last segment can run as long as it likes
+        let mut segments = vec![segment("00:00", 10), segment("00:05", 500)];
+        assert!(annotate(&mut segments, 150, None).is_empty());
+    }
+}
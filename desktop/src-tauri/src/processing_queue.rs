@@ -0,0 +1,344 @@
+//! Processing Queue
+//!
+//! Videos waiting to be processed, so a whole card's worth can be queued
+//! and left to run. Videos start in the order they're queued, one at a time
+//! unless the settings allow more, since Whisper alone keeps the CPU busy.
+//! Each runs as a `processing` job of its own (see `commands::queue`).
+//!
+//! The queue is kept in the database as it changes. Videos still waiting
+//! or running when the app closes are queued again at the next start; one
+//! that was running picks up after the stages it finished.
+
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::processor::ProcessOptions;
+use crate::services::database::ProcessingStatus;
+use crate::services::LocalDatabase;
+
+/// Videos processed at once when the settings don't say
+pub const DEFAULT_CONCURRENCY: usize = 1;
+
+/// A video in the queue, as kept in the database
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedVideo {
+    pub video_id: String,
+    pub options: ProcessOptions,
+    /// Start over rather than after the stages an earlier run finished;
+    /// once it has started, a run resumed after a restart picks up instead
+    pub force: bool,
+    /// Status before it was queued, and why it failed if it did, to go back
+    /// to if it's taken off the queue before it's done
+    pub previous_status: ProcessingStatus,
+    #[serde(default)]
+    pub previous_error: Option<String>,
+}
+
+/// A video in the queue, as reported
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueueItem {
+    pub video_id: String,
+    /// `queued` or `processing`
+    pub status: ProcessingStatus,
+    /// The job processing it, once it's started
+    pub job_id: Option<String>,
+    /// From 0 to 1
+    pub progress: f32,
+}
+
+/// The whole queue, emitted as `processing-queue` whenever it changes
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct QueueStatus {
+    /// Running videos, then the waiting ones in the order they'll start
+    pub items: Vec<QueueItem>,
+    /// Videos finished since the queue was last empty
+    pub done: usize,
+    pub failed: usize,
+    /// Videos in this round of the queue: finished, running and waiting;
+    /// cancelled ones aren't counted
+    pub total: usize,
+    /// From 0 to 1
+    pub progress: f32,
+}
+
+struct Running {
+    video: QueuedVideo,
+    job_id: Option<String>,
+    progress: f32,
+}
+
+#[derive(Default)]
+struct QueueState {
+    waiting: Vec<QueuedVideo>,
+    running: Vec<Running>,
+    done: usize,
+    failed: usize,
+}
+
+/// The processing queue, shared through `AppState`
+#[derive(Default)]
+pub struct ProcessingQueue {
+    state: Mutex<QueueState>,
+    /// Wakes whoever starts the next videos
+    pub wake: Notify,
+    /// Writes of the queue to the database, one at a time so an older
+    /// view of it never lands last
+    persisting: tokio::sync::Mutex<()>,
+}
+
+impl ProcessingQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add videos to the end of the queue, returning the ids of those added;
+    /// ones already waiting or running keep their place
+    pub fn enqueue(&self, videos: Vec<QueuedVideo>) -> Vec<String> {
+        let mut state = self.lock();
+        if state.waiting.is_empty() && state.running.is_empty() {
+            // A new round
+            state.done = 0;
+            state.failed = 0;
+        }
+        drop(state);
+        let mut added = Vec::new();
+        for video in videos {
+            if self.contains(&video.video_id) {
+                continue;
+            }
+            added.push(video.video_id.clone());
+            self.lock().waiting.push(video);
+        }
+        if !added.is_empty() {
+            self.wake.notify_one();
+        }
+        added
+    }
+
+    /// Put the waiting videos in the order of `video_ids`; those not listed
+    /// follow, in the order they were in
+    pub fn reorder(&self, video_ids: &[String]) {
+        let mut state = self.lock();
+        let position = |video: &QueuedVideo| video_ids.iter().position(|id| *id == video.video_id).unwrap_or(video_ids.len());
+        // Stable, so the unlisted ones keep their order
+        state.waiting.sort_by_key(position);
+    }
+
+    /// Take a waiting video off the queue
+    pub fn remove_waiting(&self, video_id: &str) -> Option<QueuedVideo> {
+        let mut state = self.lock();
+        let index = state.waiting.iter().position(|v| v.video_id == video_id)?;
+        Some(state.waiting.remove(index))
+    }
+
+    /// Whether a video is waiting or running
+    pub fn contains(&self, video_id: &str) -> bool {
+        let state = self.lock();
+        state.waiting.iter().any(|v| v.video_id == video_id) || state.running.iter().any(|r| r.video.video_id == video_id)
+    }
+
+    /// The job processing a running video
+    pub fn running_job(&self, video_id: &str) -> Option<String> {
+        self.lock().running.iter().find(|r| r.video.video_id == video_id).and_then(|r| r.job_id.clone())
+    }
+
+    /// Move the next waiting video to running, unless `limit` are running already
+    pub fn start_next(&self, limit: usize) -> Option<QueuedVideo> {
+        let mut state = self.lock();
+        if state.running.len() >= limit.max(1) || state.waiting.is_empty() {
+            return None;
+        }
+        let video = state.waiting.remove(0);
+        // Kept as it resumes; the stages it finishes now are worth keeping
+        let resumed = QueuedVideo { force: false, ..video.clone() };
+        state.running.push(Running { video: resumed, job_id: None, progress: 0.0 });
+        Some(video)
+    }
+
+    /// Record the job a running video was started as
+    pub fn started(&self, video_id: &str, job_id: &str) {
+        if let Some(running) = self.lock().running.iter_mut().find(|r| r.video.video_id == video_id) {
+            running.job_id = Some(job_id.to_string());
+        }
+    }
+
+    pub fn set_progress(&self, video_id: &str, progress: f32) {
+        if let Some(running) = self.lock().running.iter_mut().find(|r| r.video.video_id == video_id) {
+            running.progress = progress.clamp(0.0, 1.0);
+        }
+    }
+
+    /// Take a running video off the queue as it stops: `Some(true)` when it
+    /// was processed, `Some(false)` when it failed and `None` if cancelled
+    pub fn finish(&self, video_id: &str, succeeded: Option<bool>) -> Option<QueuedVideo> {
+        let mut state = self.lock();
+        let index = state.running.iter().position(|r| r.video.video_id == video_id)?;
+        let running = state.running.remove(index);
+        match succeeded {
+            Some(true) => state.done += 1,
+            Some(false) => state.failed += 1,
+            None => {}
+        }
+        drop(state);
+        self.wake.notify_one();
+        Some(running.video)
+    }
+
+    pub fn status(&self) -> QueueStatus {
+        let state = self.lock();
+        let running = state.running.iter().map(|r| QueueItem {
+            video_id: r.video.video_id.clone(),
+            status: ProcessingStatus::Processing,
+            job_id: r.job_id.clone(),
+            progress: r.progress,
+        });
+        let waiting = state.waiting.iter().map(|v| QueueItem {
+            video_id: v.video_id.clone(),
+            status: ProcessingStatus::Queued,
+            job_id: None,
+            progress: 0.0,
+        });
+        let items: Vec<QueueItem> = running.chain(waiting).collect();
+
+        let finished = state.done + state.failed;
+        let total = finished + items.len();
+        let under_way: f32 = items.iter().map(|item| item.progress).sum();
+        let progress = if total == 0 { 1.0 } else { (finished as f32 + under_way) / total as f32 };
+        QueueStatus { items, done: state.done, failed: state.failed, total, progress }
+    }
+
+    /// What to pick up at the next start: the running videos, then the waiting ones
+    pub fn unfinished(&self) -> Vec<QueuedVideo> {
+        let state = self.lock();
+        state.running.iter().map(|r| r.video.clone()).chain(state.waiting.iter().cloned()).collect()
+    }
+
+    /// Keep the queue in the database as it is now
+    pub async fn persist(&self, db: &LocalDatabase) {
+        let _writing = self.persisting.lock().await;
+        let entries: Vec<(String, String)> = self
+            .unfinished()
+            .into_iter()
+            .filter_map(|video| Some((video.video_id.clone(), serde_json::to_string(&video).ok()?)))
+            .collect();
+        if let Err(e) = db.set_processing_queue(&entries).await {
+            warn!("Failed to keep the processing queue: {}", e);
+        }
+    }
+
+    /// The queue kept in the database by the last run
+    pub async fn load(db: &LocalDatabase) -> Vec<QueuedVideo> {
+        match db.get_processing_queue().await {
+            Ok(entries) => entries
+                .iter()
+                .filter_map(|json| match serde_json::from_str(json) {
+                    Ok(video) => Some(video),
+                    Err(e) => {
+                        warn!("Dropping a queued video that can't be read: {}", e);
+                        None
+                    }
+                })
+                .collect(),
+            Err(e) => {
+                warn!("Failed to read the processing queue: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str) -> QueuedVideo {
+        QueuedVideo {
+            video_id: id.to_string(),
+            options: ProcessOptions::default(),
+            force: false,
+            previous_status: ProcessingStatus::Pending,
+            previous_error: None,
+        }
+    }
+
+    fn ids(queue: &ProcessingQueue) -> Vec<String> {
+        queue.status().items.into_iter().map(|item| item.video_id).collect()
+    }
+
+    #[test]
+    fn test_videos_start_in_order_within_the_limit() {
+        let queue = ProcessingQueue::new();
+        let added = queue.enqueue(vec![video("a"), video("b"), video("c"), video("a")]);
+        assert_eq!(added, ["a", "b", "c"]);
+
+        assert_eq!(queue.start_next(DEFAULT_CONCURRENCY).map(|v| v.video_id).as_deref(), Some("a"));
+        assert_eq!(queue.start_next(DEFAULT_CONCURRENCY), None);
+        // Already running, so not queued again
+        assert!(queue.enqueue(vec![video("a")]).is_empty());
+
+        queue.started("a", "job-a");
+        queue.set_progress("a", 0.5);
+        let status = queue.status();
+        assert_eq!(status.items[0].status, ProcessingStatus::Processing);
+        assert_eq!(status.items[0].job_id.as_deref(), Some("job-a"));
+        assert_eq!(status.items[1].status, ProcessingStatus::Queued);
+        assert_eq!(status.total, 3);
+        assert!((status.progress - 0.5 / 3.0).abs() < 1e-6);
+        assert_eq!(queue.running_job("a").as_deref(), Some("job-a"));
+
+        // A higher limit lets the next one start alongside
+        assert_eq!(queue.start_next(2).map(|v| v.video_id).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn test_reorder_and_remove_waiting_videos() {
+        let queue = ProcessingQueue::new();
+        queue.enqueue(vec![QueuedVideo { force: true, ..video("a") }, video("b"), video("c"), video("d")]);
+        assert!(queue.start_next(1).unwrap().force);
+
+        queue.reorder(&["d".to_string(), "c".to_string(), "a".to_string()]);
+        assert_eq!(ids(&queue), ["a", "d", "c", "b"]);
+
+        assert!(queue.contains("a") && queue.contains("c"));
+        assert_eq!(queue.remove_waiting("c").map(|v| v.video_id).as_deref(), Some("c"));
+        assert!(!queue.contains("c"));
+        // Running, not waiting; it's cancelled through its job
+        assert_eq!(queue.remove_waiting("a"), None);
+        assert_eq!(ids(&queue), ["a", "d", "b"]);
+        // Running first, so it's resumed first after a restart
+        let unfinished: Vec<String> = queue.unfinished().into_iter().map(|v| v.video_id).collect();
+        assert_eq!(unfinished, ["a", "d", "b"]);
+        // Already started over, so it picks up where it got to
+        assert!(!queue.unfinished()[0].force);
+    }
+
+    #[test]
+    fn test_finished_videos_count_until_the_queue_empties() {
+        let queue = ProcessingQueue::new();
+        queue.enqueue(vec![video("a"), video("b"), video("c")]);
+
+        queue.start_next(1);
+        assert!(queue.finish("a", Some(true)).is_some());
+        queue.start_next(1);
+        queue.finish("b", Some(false));
+        queue.start_next(1);
+        queue.finish("c", None);
+        assert_eq!(queue.finish("c", Some(true)), None);
+
+        let status = queue.status();
+        assert!(status.items.is_empty());
+        assert_eq!((status.done, status.failed, status.total), (1, 1, 2));
+        assert_eq!(status.progress, 1.0);
+
+        // The next round starts counting again
+        queue.enqueue(vec![video("d")]);
+        let status = queue.status();
+        assert_eq!((status.done, status.total, status.progress), (0, 1, 0.0));
+    }
+}
@@ -1,96 +1,1903 @@
-use crate::services::{Ffmpeg, Whisper, parse_gps_file, WhisperModel};
-use crate::types::{TruthBundle, TruthEvent, LocationResult};
+use crate::bundle_schema::TRUTH_BUNDLE_SCHEMA_VERSION;
+use crate::confidence::{self, Evidence};
+use crate::services::{Ffmpeg, Whisper, parse_gps_file, GpsTrack, LocalDatabase, WhisperModel};
+use crate::services::ffmpeg::{VideoMetadata, VideoMoment};
+use crate::services::gps::{GpsPoint, TrackStats};
+use crate::services::poi_passes::{self, PoiPass};
+use crate::services::sun;
+use crate::services::temp_files::JobDir;
+use crate::services::truth_engine::{self, in_fov, LocalTruthEngine, DEFAULT_FOV_DEG};
+use crate::services::sync::{SyncError, SyncMethod, SyncResult, TimeSyncEngine};
+use crate::services::whisper::{self, Transcription, TranscriptionSegment};
+use crate::settings;
+use crate::types::{EventKind, TruthBundle, TruthEvent, LocationResult, POI};
 use anyhow::{Context, Result};
-use chrono::Utc;
-use std::path::PathBuf;
+use chrono::{DateTime, Datelike, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::pin::Pin;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tracing::{info, debug};
+use std::time::{Duration, Instant};
+use tracing::{info, debug, warn, info_span, Instrument};
 use uuid::Uuid;
 
+/// Wall-clock time spent in each processing stage
+struct StageTimings {
+    stages: Vec<(&'static str, Duration)>,
+    started: Instant,
+}
+
+impl Default for StageTimings {
+    fn default() -> Self {
+        Self { stages: Vec::new(), started: Instant::now() }
+    }
+}
+
+impl StageTimings {
+    /// Run a stage inside its own tracing span and record how long it took
+    async fn time<T>(&mut self, stage: &'static str, fut: impl Future<Output = T>) -> T {
+        let span = info_span!("process_stage", stage, elapsed_ms = tracing::field::Empty);
+        let start = Instant::now();
+        let output = fut.instrument(span.clone()).await;
+        let elapsed = start.elapsed();
+
+        span.record("elapsed_ms", elapsed.as_millis() as u64);
+        debug!(parent: &span, "Stage {} finished in {:?}", stage, elapsed);
+
+        self.stages.push((stage, elapsed));
+        output
+    }
+
+    /// Add the stages timed for a branch that ran alongside these
+    fn extend(&mut self, other: StageTimings) {
+        self.stages.extend(other.stages);
+    }
+
+    /// Time since processing started; less than the stages add up to when
+    /// some ran side by side
+    fn total(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Timings as bundle meta entries, e.g. `timing.transcribe_ms = "42113"`
+    fn to_meta(&self) -> HashMap<String, String> {
+        let mut meta: HashMap<String, String> = self.stages
+            .iter()
+            .map(|(stage, d)| (format!("timing.{}_ms", stage), d.as_millis().to_string()))
+            .collect();
+        meta.insert("timing.total_ms".to_string(), self.total().as_millis().to_string());
+        meta
+    }
+
+    /// One-line summary for the log, e.g. "metadata=120ms transcribe=42113ms"
+    fn summary(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(stage, d)| format!("{}={}ms", stage, d.as_millis()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// User choices for processing a video
+///
+/// Commands given none go by the ones kept in the settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessOptions {
+    /// Transcribe the audio; without it there are no speech events
+    pub transcription: bool,
+    /// Whisper model to transcribe with (`None` = Base, or the smallest
+    /// installed without it)
+    pub whisper_model: Option<WhisperModel>,
+    /// Spoken language, e.g. `de` (`None` = detected by Whisper)
+    pub language: Option<String>,
+    /// Add an event at scene changes, so stretches without speech get some
+    pub scene_moments: bool,
+    /// How much the picture must change for a scene change, from 0 to 1
+    /// (`None` = 0.4)
+    pub scene_threshold: Option<f32>,
+    /// Scene changes this close to a speech event, in seconds, are left to
+    /// it (`None` = 10)
+    pub scene_dedup_seconds: Option<f64>,
+    /// Fewest seconds between scene change events (`None` = 30)
+    pub scene_min_spacing_seconds: Option<f64>,
+    /// Camera field of view, to tell which passed landmarks are in view
+    /// (`None` = a dashcam's)
+    pub fov_deg: Option<f64>,
+    /// Check the route against the map data for landmarks it passes
+    pub truth_verification: bool,
+    /// Audio track to transcribe, by position among the audio streams
+    /// (see `VideoMetadata::audio_streams`); the first when unset
+    pub audio_stream_index: Option<usize>,
+    /// Keep the extracted audio so processing the video again, e.g. to
+    /// compare transcription models, doesn't extract it again
+    pub keep_audio: bool,
+    /// Look up the weather at each located event when the app is online;
+    /// needs the recording time, from the video or its GPS track
+    pub weather: bool,
+    /// Video time at which the GPS track starts, as a manual sync found it;
+    /// worked out from the recording times when unset
+    pub sync_offset_seconds: Option<f64>,
+}
+
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        Self {
+            transcription: true,
+            whisper_model: None,
+            language: None,
+            scene_moments: true,
+            scene_threshold: None,
+            scene_dedup_seconds: None,
+            scene_min_spacing_seconds: None,
+            fov_deg: None,
+            truth_verification: true,
+            audio_stream_index: None,
+            keep_audio: false,
+            weather: false,
+            sync_offset_seconds: None,
+        }
+    }
+}
+
+impl ProcessOptions {
+    /// Check the options can be used, before a run rather than partway through
+    pub fn validate(&self) -> Result<(), String> {
+        if self.scene_threshold.is_some_and(|t| !(0.0..=1.0).contains(&t)) {
+            return Err("The scene threshold must be from 0 to 1".to_string());
+        }
+        let seconds = [self.scene_dedup_seconds, self.scene_min_spacing_seconds];
+        if seconds.iter().flatten().any(|s| !(s.is_finite() && *s >= 0.0)) {
+            return Err("Scene change windows must be zero or more seconds".to_string());
+        }
+        if self.fov_deg.is_some_and(|f| !(f > 0.0 && f <= 360.0)) {
+            return Err("The field of view must be more than 0 and at most 360 degrees".to_string());
+        }
+        if self.language.as_deref().is_some_and(|l| l.trim().is_empty()) {
+            return Err("The language can't be empty; leave it unset to detect it".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Bundle meta holding the options a video was processed with, as JSON
+pub const OPTIONS_META: &str = "processing_options";
+
+/// Scene changes the picture must change this much for, unless the options say
+const DEFAULT_SCENE_THRESHOLD: f32 = 0.4;
+
+/// Seconds from a speech event within which a scene change is left to it,
+/// unless the options say
+const DEFAULT_SCENE_DEDUP_SECONDS: f64 = 10.0;
+
+/// Fewest seconds between scene change events, unless the options say
+const DEFAULT_SCENE_SPACING_SECONDS: f64 = 30.0;
+
+/// Bundle meta holding when the video started, when that's known
+pub const RECORDED_AT_META: &str = "recorded_at";
+
+/// Bundle meta set when the time the video started is a guess
+pub const RECORDED_AT_UNCERTAIN_META: &str = "recorded_at_uncertain";
+
+/// Camera clocks that were never set start at an epoch, e.g. 1904 for
+/// QuickTime or 1970; a creation time before this year is one of those
+const EARLIEST_RECORDING_YEAR: i32 = 2000;
+
+/// Verification mode of bundles no truth engine looked at, e.g. without
+/// downloaded map data
+const UNVERIFIED_MODE: &str = "pending";
+
+/// What processing an imported video produces
+pub struct ProcessedVideo {
+    pub bundle: TruthBundle,
+    /// Where the extracted audio was kept, if it was
+    pub audio_path: Option<PathBuf>,
+    /// Positions along the video, timed like its events; empty without GPS
+    /// or when the video was recorded isn't known
+    pub route: Vec<GpsPoint>,
+    /// The options as they were applied: with the model used and the
+    /// language spoken, where they were left to be worked out
+    pub options: ProcessOptions,
+    /// Stages whose output an earlier run kept, and weren't run again
+    pub resumed: Vec<&'static str>,
+}
+
+/// What the transcription stage produced
+struct Transcribed {
+    transcription: Transcription,
+    /// Where the extracted audio was kept, if it was
+    audio_path: Option<PathBuf>,
+    /// Whether audio extracted by an earlier run was used
+    audio_reused: bool,
+    /// Whether an earlier run's transcript was used, without transcribing again
+    resumed: bool,
+}
+
+/// What the GPS stage produced; all `None` without a GPS track
+#[derive(Default)]
+struct AlignedGps {
+    stats: Option<TrackStats>,
+    accuracy: Option<f64>,
+    sync: Option<(TimeSyncEngine, Result<SyncResult, SyncError>)>,
+}
+
+/// Future of a [`StageStore`] call
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Where processing keeps the output of its costly stages as it finishes
+/// them, so a run that fails partway can pick up after the last one
+///
+/// The GPS track needs no keeping: an imported video's points are stored
+/// at import, and aligning them again is quick and comes out the same.
+pub trait StageStore: Send + Sync {
+    /// What earlier runs of the video finished
+    fn load(&self, video_id: Uuid) -> StoreFuture<'_, Checkpoint>;
+    /// Keep the transcript, made from `inputs` going by `options`
+    fn save_transcription<'a>(
+        &'a self,
+        video_id: Uuid,
+        transcription: &'a Transcription,
+        options: &'a ProcessOptions,
+        inputs: &'a StageInputs,
+    ) -> StoreFuture<'a, ()>;
+    /// Keep the verified events, made from `inputs` going by `options`
+    fn save_events<'a>(
+        &'a self,
+        video_id: Uuid,
+        events: &'a [TruthEvent],
+        options: &'a ProcessOptions,
+        inputs: &'a StageInputs,
+    ) -> StoreFuture<'a, ()>;
+}
+
+/// Fingerprints of what the costly stages are made from, so only those
+/// whose inputs changed are run again
+///
+/// The transcript is made from the video file; the events from it, the
+/// transcript and the GPS track. A corrected GPS track has the events
+/// aligned and verified again without transcribing, and another Whisper
+/// model has them made again from the new transcript. Narrations written
+/// from the old events then read as stale, as the bundle changed under them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageInputs {
+    /// The video file, by its size and samples of its content, standing in
+    /// for its audio and pictures; `None` if it couldn't be read
+    pub video: Option<String>,
+    /// The GPS track's points; `None` without GPS
+    pub gps: Option<String>,
+}
+
+/// Bytes read from each of the start, middle and end of a file to fingerprint it
+const FINGERPRINT_SAMPLE_BYTES: u64 = 1 << 20;
+
+/// Fingerprint of a file by its size and samples of its content, quick
+/// however long the video
+pub fn fingerprint_file(path: &Path) -> io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+
+    let last = size.saturating_sub(FINGERPRINT_SAMPLE_BYTES);
+    let mut sample = Vec::with_capacity(FINGERPRINT_SAMPLE_BYTES as usize);
+    for offset in [0, last / 2, last] {
+        file.seek(SeekFrom::Start(offset))?;
+        sample.clear();
+        (&mut file).take(FINGERPRINT_SAMPLE_BYTES).read_to_end(&mut sample)?;
+        hasher.update(&sample);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Fingerprint of a GPS track by its points
+pub fn fingerprint_points(points: &[GpsPoint]) -> String {
+    let mut hasher = Sha256::new();
+    for point in points {
+        hasher.update(point.timestamp.timestamp_micros().to_le_bytes());
+        for value in [Some(point.lat), Some(point.lon), point.elevation_m, point.speed_kmh, point.heading_deg, point.accuracy_m] {
+            hasher.update(value.unwrap_or(f64::NAN).to_le_bytes());
+        }
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// What earlier runs of a video finished
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoint {
+    /// The options the last finished stage went by
+    pub options: Option<ProcessOptions>,
+    /// What the last finished stage was made from; `None` if it was kept
+    /// before inputs were fingerprinted, and then taken to be unchanged
+    pub inputs: Option<StageInputs>,
+    pub transcription: Option<Transcription>,
+    pub events: Option<Vec<TruthEvent>>,
+}
+
+impl Checkpoint {
+    /// The stages whose kept output a run going by `options` and made from
+    /// `inputs` would use, rather than run again
+    pub fn reusable(&self, options: &ProcessOptions, inputs: &StageInputs) -> Vec<&'static str> {
+        let transcript = self.transcription_for(options, inputs).is_some();
+        let events = self.events_for(options, transcript, inputs).is_some();
+        [(TRANSCRIBE_STAGE, transcript), (VERIFICATION_STAGE, events)]
+            .into_iter()
+            .filter_map(|(stage, reusable)| reusable.then_some(stage))
+            .collect()
+    }
+
+    /// The kept transcript, with the options it was made by, if it's what
+    /// `options` would transcribe from `inputs`
+    fn transcription_for(&self, options: &ProcessOptions, inputs: &StageInputs) -> Option<(&Transcription, &ProcessOptions)> {
+        let (transcription, kept) = self.transcription.as_ref().zip(self.options.as_ref())?;
+        let same_model = options.whisper_model.is_none() || options.whisper_model == kept.whisper_model;
+        let same_language = options.language.is_none() || options.language == kept.language;
+        let same_audio = options.audio_stream_index == kept.audio_stream_index;
+        let same_video = !matches!(&self.inputs, Some(kept) if kept.video != inputs.video);
+        (options.transcription && same_model && same_language && same_audio && same_video).then_some((transcription, kept))
+    }
+
+    /// The kept events, if `options` would verify the same ones: from the
+    /// kept transcript, if there's one to go by, the same video and GPS
+    /// track, and with the same settings
+    fn events_for(&self, options: &ProcessOptions, transcript_resumed: bool, inputs: &StageInputs) -> Option<&[TruthEvent]> {
+        let (events, kept) = self.events.as_ref().zip(self.options.as_ref())?;
+        let settings = |o: &ProcessOptions| {
+            (
+                o.transcription,
+                o.scene_moments,
+                o.scene_threshold,
+                o.scene_dedup_seconds,
+                o.scene_min_spacing_seconds,
+                o.fov_deg,
+                o.truth_verification,
+                o.sync_offset_seconds,
+            )
+        };
+        let same_transcript = transcript_resumed || !options.transcription;
+        let same_inputs = !matches!(&self.inputs, Some(kept) if kept != inputs);
+        (same_transcript && same_inputs && settings(options) == settings(kept)).then_some(events.as_slice())
+    }
+}
+
+/// Stages are kept with the video's status, the transcript in its own table
+impl StageStore for LocalDatabase {
+    fn load(&self, video_id: Uuid) -> StoreFuture<'_, Checkpoint> {
+        Box::pin(async move {
+            let id = video_id.to_string();
+            let status = self.get_video_status(&id).await?;
+            let finished = |stage: &str| status.completed_stages.iter().any(|s| s == stage);
+            let Some(options) = status.options_json.as_deref().and_then(|json| serde_json::from_str::<ProcessOptions>(json).ok()) else {
+                return Ok(Checkpoint::default());
+            };
+
+            let transcription = if finished(TRANSCRIBE_STAGE) {
+                let segments = self.get_transcription(&id).await?;
+                let full_text = segments.iter().map(|s| s.text.clone()).collect::<Vec<_>>().join(" ");
+                Some(Transcription { segments, language: options.language.clone(), full_text })
+            } else {
+                None
+            };
+            let events = match self.get_stage_events(&id).await? {
+                Some(json) if finished(VERIFICATION_STAGE) => Some(serde_json::from_str(&json)?),
+                _ => None,
+            };
+            let inputs = status.inputs_json.as_deref().and_then(|json| serde_json::from_str(json).ok());
+            Ok(Checkpoint { options: Some(options), inputs, transcription, events })
+        })
+    }
+
+    fn save_transcription<'a>(
+        &'a self,
+        video_id: Uuid,
+        transcription: &'a Transcription,
+        options: &'a ProcessOptions,
+        inputs: &'a StageInputs,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let id = video_id.to_string();
+            self.replace_transcription(&id, &transcription.segments, transcription.language.as_deref()).await?;
+            let (options, inputs) = (serde_json::to_string(options)?, serde_json::to_string(inputs)?);
+            self.complete_stage(&id, TRANSCRIBE_STAGE, &options, &inputs, None).await?;
+            Ok(())
+        })
+    }
+
+    fn save_events<'a>(
+        &'a self,
+        video_id: Uuid,
+        events: &'a [TruthEvent],
+        options: &'a ProcessOptions,
+        inputs: &'a StageInputs,
+    ) -> StoreFuture<'a, ()> {
+        Box::pin(async move {
+            let events = serde_json::to_string(events)?;
+            let (options, inputs) = (serde_json::to_string(options)?, serde_json::to_string(inputs)?);
+            self.complete_stage(&video_id.to_string(), VERIFICATION_STAGE, &options, &inputs, Some(&events)).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Stage a [`StageStore`] records once the transcript is kept
+const TRANSCRIBE_STAGE: &str = "transcribe";
+
+/// Stage a [`StageStore`] records once the events are kept
+const VERIFICATION_STAGE: &str = "verification";
+
+/// Where a video's GPS data comes from
+enum GpsInput {
+    File(PathBuf),
+    /// Already parsed, e.g. points stored at import
+    Track(GpsTrack),
+}
+
+impl GpsInput {
+    fn fingerprint(&self) -> Option<String> {
+        match self {
+            GpsInput::File(path) => fingerprint_file(path).ok(),
+            GpsInput::Track(track) => Some(fingerprint_points(&track.points)),
+        }
+    }
+}
+
+/// The stages whose output `store` kept for an imported video that a run
+/// going by `options` would use, rather than run again
+pub async fn reusable_stages(
+    store: &dyn StageStore,
+    video_id: Uuid,
+    video_path: &Path,
+    gps_track: Option<&GpsTrack>,
+    options: &ProcessOptions,
+) -> Result<Vec<&'static str>> {
+    let checkpoint = store.load(video_id).await?;
+    let inputs = StageInputs {
+        video: fingerprint_file(video_path).ok(),
+        gps: gps_track.map(|track| fingerprint_points(&track.points)),
+    };
+    Ok(checkpoint.reusable(options, &inputs))
+}
+
 pub struct VideoProcessor {
     ffmpeg: Arc<Ffmpeg>,
     whisper: Arc<Whisper>,
+    /// Holds each job's temp directory, and scene frames when there's
+    /// nowhere to keep them
     temp_dir: PathBuf,
+    /// Downloaded map data, to find the landmarks a route passes
+    pois: Option<LocalDatabase>,
+    /// Where files worth keeping between runs go, e.g. extracted audio
+    artifact_dir: Option<PathBuf>,
 }
 
 impl VideoProcessor {
     pub fn new(ffmpeg: Arc<Ffmpeg>, whisper: Arc<Whisper>, temp_dir: PathBuf) -> Self {
-        Self { ffmpeg, whisper, temp_dir }
+        Self { ffmpeg, whisper, temp_dir, pois: None, artifact_dir: None }
+    }
+
+    /// Keep extracted audio under `dir` when asked to, and reuse it from there
+    pub fn with_artifact_dir(mut self, dir: PathBuf) -> Self {
+        self.artifact_dir = Some(dir);
+        self
     }
 
-    pub async fn process_video(&self, video_path: PathBuf, gps_path: Option<PathBuf>) -> Result<TruthBundle> {
+    /// Add an event whenever an aligned route approaches or passes one of
+    /// the notable POIs in `db`
+    pub fn with_pois(mut self, db: LocalDatabase) -> Self {
+        self.pois = Some(db);
+        self
+    }
+
+    /// Where job temp directories go
+    pub fn temp_dir(&self) -> &Path {
+        &self.temp_dir
+    }
+
+    /// Process a video file, reporting each stage to `on_progress` as it's
+    /// reached, with how much of the work is done (see [`process_stored_video`](Self::process_stored_video))
+    pub async fn process_video(
+        &self,
+        video_path: PathBuf,
+        gps_path: Option<PathBuf>,
+        options: ProcessOptions,
+        on_progress: impl Fn(&'static str, f32) + Send + Sync,
+    ) -> Result<ProcessedVideo> {
+        // Nothing would find the audio again without a video record to keep it on
+        let options = ProcessOptions { keep_audio: false, ..options };
+        self.process(Uuid::new_v4(), video_path, gps_path.map(GpsInput::File), options, None, on_progress).await
+    }
+
+    /// Process an imported video, with the GPS track stored for it if any
+    ///
+    /// `on_progress` is told each stage as it's reached and how much of the
+    /// work is done, from 0 to 1: `metadata` at 5%, `audio_extract` at 15%,
+    /// `transcribe` up to 70%, and `verification` from 75% to 90%. The GPS
+    /// track is read and aligned while the audio is transcribed, adding
+    /// 5% as `gps` whenever it's done. Storing and enriching the bundle is
+    /// left to the caller.
+    ///
+    /// With a `store` the transcript and events are kept there as they're
+    /// made, and what an earlier run kept is used instead of making it again.
+    pub async fn process_stored_video(
+        &self,
+        video_id: Uuid,
+        video_path: PathBuf,
+        gps_track: Option<GpsTrack>,
+        options: ProcessOptions,
+        store: Option<&dyn StageStore>,
+        on_progress: impl Fn(&'static str, f32) + Send + Sync,
+    ) -> Result<ProcessedVideo> {
+        self.process(video_id, video_path, gps_track.map(GpsInput::Track), options, store, on_progress).await
+    }
+
+    async fn process(
+        &self,
+        video_id: Uuid,
+        video_path: PathBuf,
+        gps: Option<GpsInput>,
+        mut options: ProcessOptions,
+        store: Option<&dyn StageStore>,
+        on_progress: impl Fn(&'static str, f32) + Send + Sync,
+    ) -> Result<ProcessedVideo> {
         info!("Processing video: {:?}", video_path);
+        options.validate().map_err(anyhow::Error::msg)?;
+        // Removed with whatever's in it once processing ends, however it ends
+        let job_dir = JobDir::create(&self.temp_dir).context("Failed to create a temp directory for the job")?;
         
-        let video_id = Uuid::new_v4();
+        let mut timings = StageTimings::default();
         
         // 1. Extract Metadata
-        let metadata = self.ffmpeg.extract_metadata(&video_path).await
+        let metadata = timings.time("metadata", self.ffmpeg.extract_metadata(&video_path)).await
             .context("Failed to extract video metadata")?;
         debug!("Metadata extracted: {:?}", metadata);
+        on_progress("metadata", 0.05);
 
-        // 2. Extract Audio
-        let audio_filename = format!("{}.wav", video_id);
-        let audio_path = self.temp_dir.join(&audio_filename);
-        self.ffmpeg.extract_audio(&video_path, &audio_path).await
-            .context("Failed to extract audio")?;
-        
-        // 3. Transcribe Audio
-        info!("Transcribing audio...");
-        let transcription = self.whisper.transcribe(
-            &audio_path, 
-            WhisperModel::Base, // Default model
-            Some("en")
-        ).await.context("Failed to transcribe audio")?;
-        
-        // Clean up audio file
-        if audio_path.exists() {
-            let _ = std::fs::remove_file(&audio_path);
+        // What an earlier run finished, to pick up after
+        let checkpoint = match store {
+            Some(store) => store.load(video_id).await.unwrap_or_else(|e| {
+                warn!("Failed to read what earlier runs of video {} finished: {:#}", video_id, e);
+                Checkpoint::default()
+            }),
+            None => Checkpoint::default(),
+        };
+        let mut resumed = Vec::new();
+        // What the stages are made from, to tell whether what was kept is still theirs
+        let inputs = StageInputs {
+            video: fingerprint_file(&video_path)
+                .map_err(|e| warn!("Failed to fingerprint video {:?}: {}", video_path, e))
+                .ok(),
+            gps: gps.as_ref().and_then(GpsInput::fingerprint),
+        };
+
+        // 2-5. Extract and transcribe the audio while the GPS track is read
+        // and aligned with the video timeline; neither needs the other until
+        // the events are built
+        let video_start = recording_start(&metadata);
+        let sync_offset = options.sync_offset_seconds;
+        let progress = BranchProgress::new(&on_progress, 0.05);
+        let mut gps_timings = StageTimings::default();
+        let transcript_progress = |stage, done| progress.transcript(stage, done);
+        let transcription = self.transcription_stage(
+            video_id,
+            &video_path,
+            &metadata,
+            &mut options,
+            &checkpoint,
+            &inputs,
+            store,
+            job_dir.path(),
+            &mut timings,
+            &transcript_progress,
+        );
+        let gps = gps_stage(gps, &metadata, video_start, sync_offset, &mut gps_timings, || progress.gps_done());
+        // The first to fail drops the other, which stops what it was running
+        let (transcribed, aligned) = tokio::try_join!(transcription, gps)?;
+        timings.extend(gps_timings);
+        if transcribed.resumed {
+            resumed.push("transcribe");
         }
+        let Transcribed { transcription, audio_path: kept_audio, audio_reused: reused, .. } = transcribed;
+        let AlignedGps { stats: track_stats, accuracy: gps_accuracy, sync } = aligned;
+        progress.transcript("transcribe", 0.7);
 
-        // 4. Parse GPS
-        let _gps_track = if let Some(path) = gps_path {
-            info!("Parsing GPS track: {:?}", path);
-            Some(parse_gps_file(&path).await?)
+        // The GPS clock says when recording started if the video doesn't
+        let clock = recording_clock(video_start, sync.as_ref().map(|(_, result)| result));
+        let recorded_from = clock.start;
+
+        // 6. Build Truth Bundle, one event per transcription segment, unless
+        // an earlier run kept the events of the same transcript and options
+        let mut scene_count = None;
+        let mut pass_count = None;
+        let mut verified_count = None;
+        let events = if let Some(events) = checkpoint.events_for(&options, resumed.contains(&"transcribe"), &inputs) {
+            info!("Reusing the {} events verified by an earlier run", events.len());
+            resumed.push("verification");
+            events.to_vec()
         } else {
-            None
+            let mut events = speech_events(&transcription.segments, recorded_from, sync.as_ref());
+            if options.scene_moments {
+                let scene_events = self.scene_events(video_id, &video_path, &options, &transcription.segments, recorded_from, sync.as_ref());
+                let scenes = timings.time("scene_changes", scene_events).await;
+                scene_count = Some(scenes.len());
+                events.extend(scenes);
+                events.sort_by_key(|event| event.timestamp);
+            }
+            on_progress("verification", 0.8);
+
+            // 7. Landmarks the route approaches and passes, as events of their own
+            let fov_deg = options.fov_deg.unwrap_or(DEFAULT_FOV_DEG);
+            let verified = self.pois.as_ref().filter(|_| options.truth_verification);
+            if let (Some(db), Some((engine, Ok(result)))) = (verified, &sync) {
+                let duration = metadata
+                    .duration_seconds
+                    .or_else(|| result.aligned_points.last().map(|p| p.video_time_seconds))
+                    .unwrap_or_default();
+                let samples = poi_passes::sample_track(duration, poi_passes::SAMPLE_SECONDS, |t| {
+                    engine.interpolate_position(result, t)
+                });
+                let passes = timings.time("poi_passes", passes_along(db, &samples)).await;
+                pass_count = Some(passes.len());
+                events.extend(passes.into_iter().map(|pass| pass_event(pass, recorded_from, fov_deg)));
+                events.sort_by_key(|event| event.timestamp);
+            }
+
+            // 8. What the truth engine finds around each located event
+            if let Some(db) = verified {
+                let count = timings.time("truth_engine", verify_events(db, video_id, &mut events, fov_deg)).await;
+                verified_count = Some(count);
+            }
+            // On the local clock where the truth engine knows the time zone
+            for event in &mut events {
+                event.time_uncertain |= clock.uncertain;
+                event.localize_time();
+            }
+            if let Some(store) = store {
+                if let Err(e) = store.save_events(video_id, &events, &options, &inputs).await {
+                    warn!("Failed to keep the events of video {}: {:#}", video_id, e);
+                }
+            }
+            events
         };
+        on_progress("verification", 0.9);
 
-        // 5. Build Truth Bundle
-        // This is a simplified merge logic. 
-        // Real implementation would sync timestamps of transcription segments with GPS points if possible.
-        // For now, we create events from transcription segments.
-        
-        let mut events = Vec::new();
-        
-        // Create an event for each transcription segment
-        for segment in transcription.segments {
-             // Basic location interpolation could happen here if we had GPS timestamps
-             let location = LocationResult {
-                 lat: 0.0, // Placeholder
-                 lon: 0.0,
-                 // mismatched fields might need updates in types.rs or here
-             };
-             
-             let event = TruthEvent {
-                 id: Uuid::new_v4().to_string(),
-                 timestamp: Utc::now(), // Placeholder, should use segment start time + video start time
-                 duration_seconds: Some((segment.end_ms - segment.start_ms) as f64 / 1000.0),
-                 location,
-                 pois: vec![],
-                 detected_objects: vec![],
-             };
-             events.push(event);
+        let mut meta = timings.to_meta();
+        if let Some((_, result)) = &sync {
+            meta.extend(sync_meta(result.as_ref()));
+        }
+        if let Some(count) = pass_count {
+            meta.insert("poi_passes".to_string(), count.to_string());
+        }
+        if let Some(count) = scene_count {
+            meta.insert("scene_changes".to_string(), count.to_string());
+        }
+        if let Some(count) = verified_count {
+            meta.insert("verified_events".to_string(), count.to_string());
+        }
+        meta.insert(OPTIONS_META.to_string(), serde_json::to_string(&options)?);
+        if reused {
+            meta.insert("audio_reused".to_string(), "true".to_string());
+        }
+        if !resumed.is_empty() {
+            meta.insert("resumed_stages".to_string(), resumed.join(","));
         }
+        if let Some(start) = recorded_from {
+            meta.insert(RECORDED_AT_META.to_string(), start.to_rfc3339());
+        }
+        if clock.uncertain {
+            meta.insert(RECORDED_AT_UNCERTAIN_META.to_string(), "true".to_string());
+        }
+
+        let mut evidence = Evidence::from_events(&events);
+        evidence.sync = sync.as_ref().map(|(_, result)| result.as_ref().map_or(0.0, |r| r.confidence));
+        evidence.gps_accuracy = gps_accuracy;
+        meta.extend(evidence.to_meta());
 
+        // Kept events were verified the same way, with the same options
+        let verification_mode = if self.pois.is_some() && options.truth_verification {
+            truth_engine::VERIFICATION_MODE
+        } else {
+            UNVERIFIED_MODE
+        };
         let bundle = TruthBundle {
+            schema_version: TRUTH_BUNDLE_SCHEMA_VERSION,
             project_id: None,
             video_id: Some(video_id),
             events,
-            verification_mode: "offline".to_string(),
+            verification_mode: verification_mode.to_string(),
+            confidence: evidence.score(),
             generated_at: Utc::now(),
+            track_stats,
+            meta,
+            route_segments: Vec::new(),
         };
 
-        info!("Video processing complete. Generated Truth Bundle with {} events.", bundle.events.len());
-        Ok(bundle)
+        info!(
+            total_ms = timings.total().as_millis() as u64,
+            "Video processing complete. Generated Truth Bundle with {} events ({:.0}% verified). Stages: {}",
+            bundle.events.len(),
+            bundle.confidence * 100.0,
+            timings.summary()
+        );
+        let route = match (&sync, recorded_from) {
+            (Some((_, Ok(result))), Some(start)) => timed_route(result, start),
+            _ => Vec::new(),
+        };
+        Ok(ProcessedVideo { bundle, audio_path: kept_audio, route, options, resumed })
+    }
+
+    /// The video's transcription: the one an earlier run kept, if it's what
+    /// `options` ask for from the same `inputs`, or else a new one, kept in
+    /// `store` for later runs
+    ///
+    /// Fills in the model and language of `options` where they were left to
+    /// be worked out.
+    #[allow(clippy::too_many_arguments)]
+    async fn transcription_stage(
+        &self,
+        video_id: Uuid,
+        video_path: &PathBuf,
+        metadata: &VideoMetadata,
+        options: &mut ProcessOptions,
+        checkpoint: &Checkpoint,
+        inputs: &StageInputs,
+        store: Option<&dyn StageStore>,
+        job_dir: &Path,
+        timings: &mut StageTimings,
+        on_progress: &(impl Fn(&'static str, f32) + Send + Sync),
+    ) -> Result<Transcribed> {
+        if !options.transcription {
+            info!("Transcription is off; the video gets no speech events");
+            let transcription = Transcription { segments: Vec::new(), language: None, full_text: String::new() };
+            return Ok(Transcribed { transcription, audio_path: None, audio_reused: false, resumed: false });
+        }
+        if let Some((transcription, kept_with)) = checkpoint.transcription_for(options, inputs) {
+            info!("Reusing the transcript of an earlier run ({} segments)", transcription.segments.len());
+            options.whisper_model = kept_with.whisper_model;
+            options.language = kept_with.language.clone();
+            let transcription = transcription.clone();
+            return Ok(Transcribed { transcription, audio_path: None, audio_reused: false, resumed: true });
+        }
+
+        let (transcription, audio_path, audio_reused) =
+            self.transcribe(video_id, video_path, metadata, options, job_dir, timings, on_progress).await?;
+        if let Some(store) = store {
+            if let Err(e) = store.save_transcription(video_id, &transcription, options, inputs).await {
+                warn!("Failed to keep the transcript of video {}: {:#}", video_id, e);
+            }
+        }
+        Ok(Transcribed { transcription, audio_path, audio_reused, resumed: false })
+    }
+
+    /// Extract the audio, unless it was kept from an earlier run, and transcribe it
+    ///
+    /// Audio that isn't kept goes in `job_dir`. Fills in the model and
+    /// language of `options` where they were left to be worked out. Returns
+    /// the transcription, the audio if it's kept, and whether it was reused.
+    #[allow(clippy::too_many_arguments)]
+    async fn transcribe(
+        &self,
+        video_id: Uuid,
+        video_path: &PathBuf,
+        metadata: &VideoMetadata,
+        options: &mut ProcessOptions,
+        job_dir: &Path,
+        timings: &mut StageTimings,
+        on_progress: &(impl Fn(&'static str, f32) + Send + Sync),
+    ) -> Result<(Transcription, Option<PathBuf>, bool)> {
+        let audio_stream = audio_track(metadata.audio_streams.len(), options.audio_stream_index)?;
+        let kept_path = self.artifact_dir.as_deref().map(|dir| kept_audio_path(dir, video_id, audio_stream));
+        let reused = kept_path.as_deref().is_some_and(|path| audio_is_current(path, video_path));
+        let audio_path = match &kept_path {
+            Some(path) if reused || options.keep_audio => path.clone(),
+            _ => job_dir.join(format!("{}.wav", video_id)),
+        };
+        if reused {
+            info!("Reusing the audio extracted earlier: {:?}", audio_path);
+        } else {
+            if let Some(parent) = audio_path.parent() {
+                std::fs::create_dir_all(parent).context("Failed to create the audio directory")?;
+            }
+            timings.time("audio_extract", self.ffmpeg.extract_audio(video_path, &audio_path, audio_stream)).await
+                .context("Failed to extract audio")?;
+        }
+        on_progress("audio_extract", 0.15);
+
+        let model = *options.whisper_model.get_or_insert_with(|| self.whisper.auto_model());
+        let language = options.language.as_deref().unwrap_or(whisper::AUTO_LANGUAGE);
+        info!("Transcribing audio with the {} model ({})...", model, language);
+        let transcription = timings.time("transcribe", self.whisper.transcribe_with_progress(
+            &audio_path,
+            model,
+            Some(language),
+            |done| on_progress("transcribe", 0.15 + 0.55 * done),
+        )).await.context("Failed to transcribe audio")?;
+        if options.language.is_none() {
+            options.language = transcription.language.clone();
+        }
+
+        // Clean up audio file now, unless it's to be kept, rather than with the job
+        let kept_audio = (options.keep_audio && kept_path.as_ref() == Some(&audio_path)).then(|| audio_path.clone());
+        if kept_audio.is_none() && audio_path.exists() {
+            let _ = std::fs::remove_file(&audio_path);
+        }
+        Ok((transcription, kept_audio, reused))
+    }
+
+    /// One event at each scene change the transcript doesn't already
+    /// cover, placed like the speech events, with the frame captured there
+    ///
+    /// Frames are kept with the video's other artifacts, replacing those of
+    /// an earlier run. Scene changes are extra detail; a failed scan leaves
+    /// the bundle without them.
+    async fn scene_events(
+        &self,
+        video_id: Uuid,
+        video_path: &PathBuf,
+        options: &ProcessOptions,
+        segments: &[TranscriptionSegment],
+        recorded_from: Option<DateTime<Utc>>,
+        sync: Option<&(TimeSyncEngine, Result<SyncResult, SyncError>)>,
+    ) -> Vec<TruthEvent> {
+        let frame_dir = self.artifact_dir.as_deref().unwrap_or(&self.temp_dir).join("moments").join(video_id.to_string());
+        // Frames are matched to their times by name, so old ones would be mistimed
+        if frame_dir.exists() {
+            let _ = std::fs::remove_dir_all(&frame_dir);
+        }
+        let threshold = options.scene_threshold.unwrap_or(DEFAULT_SCENE_THRESHOLD);
+        let format = settings::get().thumbnail_format;
+        let moments = match self.ffmpeg.extract_key_moments(video_path, &frame_dir, threshold, format).await {
+            Ok(moments) => moments,
+            Err(e) => {
+                warn!("Failed to scan the video for scene changes: {}", e);
+                return Vec::new();
+            }
+        };
+
+        let found = moments.len();
+        let dedup = options.scene_dedup_seconds.unwrap_or(DEFAULT_SCENE_DEDUP_SECONDS);
+        let spacing = options.scene_min_spacing_seconds.unwrap_or(DEFAULT_SCENE_SPACING_SECONDS);
+        let selected = select_moments(moments.clone(), segments, dedup, spacing);
+        for dropped in moments.iter().filter(|m| !selected.iter().any(|s| s.path == m.path)) {
+            let _ = std::fs::remove_file(&dropped.path);
+        }
+        debug!("Kept {} of {} scene changes as events", selected.len(), found);
+
+        moment_events(&selected, EventKind::SceneChange, recorded_from, |t| match sync {
+            Some((engine, Ok(result))) => engine.interpolate_position(result, t),
+            _ => None,
+        })
+    }
+}
+
+/// Read the video's GPS track if it's in a file, and align it with the
+/// video timeline, calling `on_done` once it is
+async fn gps_stage(
+    gps: Option<GpsInput>,
+    metadata: &VideoMetadata,
+    video_start: Option<DateTime<Utc>>,
+    sync_offset_seconds: Option<f64>,
+    timings: &mut StageTimings,
+    on_done: impl Fn(),
+) -> Result<AlignedGps> {
+    let track = match gps {
+        Some(GpsInput::File(path)) => {
+            info!("Parsing GPS track: {:?}", path);
+            timings.time("gps_parse", parse_gps_file(&path)).await?
+        }
+        Some(GpsInput::Track(track)) => track,
+        None => return Ok(AlignedGps::default()),
+    };
+    let aligned = AlignedGps {
+        stats: Some(track.stats()),
+        accuracy: confidence::gps_accuracy_score(&track.points),
+        sync: Some(align(track, metadata, video_start, sync_offset_seconds)),
+    };
+    on_done();
+    Ok(aligned)
+}
+
+/// Share of the work the GPS stage stands for
+const GPS_PROGRESS: f32 = 0.05;
+
+/// Progress of the transcription and GPS stages as they run side by side,
+/// reported as one figure that only goes up: how far the transcription
+/// has got, plus [`GPS_PROGRESS`] once the GPS track is aligned
+struct BranchProgress<'a, F> {
+    on_progress: &'a F,
+    /// Progress the transcription reported last, and whether the GPS stage is done
+    reached: std::sync::Mutex<(f32, bool)>,
+}
+
+impl<'a, F: Fn(&'static str, f32)> BranchProgress<'a, F> {
+    fn new(on_progress: &'a F, start: f32) -> Self {
+        Self { on_progress, reached: std::sync::Mutex::new((start, false)) }
+    }
+
+    fn transcript(&self, stage: &'static str, progress: f32) {
+        let mut reached = self.reached.lock().unwrap();
+        reached.0 = progress;
+        self.report(stage, *reached);
+    }
+
+    fn gps_done(&self) {
+        let mut reached = self.reached.lock().unwrap();
+        reached.1 = true;
+        self.report("gps", *reached);
+    }
+
+    /// Called holding the lock, so reports go out in the order they're made
+    fn report(&self, stage: &'static str, (transcript, gps_done): (f32, bool)) {
+        (self.on_progress)(stage, transcript + if gps_done { GPS_PROGRESS } else { 0.0 });
+    }
+}
+
+/// Where the audio of a video's track is kept between runs
+fn kept_audio_path(artifact_dir: &Path, video_id: Uuid, audio_stream: Option<usize>) -> PathBuf {
+    let track = audio_stream.map_or_else(|| "default".to_string(), |index| format!("track{}", index));
+    artifact_dir.join("audio").join(format!("{}.{}.wav", video_id, track))
+}
+
+/// Whether `audio` was extracted from `video` as it is now: it's there and
+/// was written after the video last changed
+fn audio_is_current(audio: &Path, video: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(audio), modified(video)) {
+        (Some(audio), Some(video)) => audio >= video,
+        _ => false,
+    }
+}
+
+/// The audio track to extract: the requested one, or else the first
+///
+/// `None` leaves the choice to FFmpeg, for files whose audio FFprobe didn't list.
+fn audio_track(stream_count: usize, requested: Option<usize>) -> Result<Option<usize>> {
+    match requested {
+        Some(index) if index >= stream_count => Err(anyhow::anyhow!(
+            "Audio track {} doesn't exist; the video has {} audio track(s)",
+            index,
+            stream_count
+        )),
+        Some(index) => Ok(Some(index)),
+        None if stream_count > 0 => Ok(Some(0)),
+        None => Ok(None),
+    }
+}
+
+/// When the video started recording, from its container's creation time
+///
+/// A time from a clock that was never set, or from the future, isn't one.
+pub(crate) fn recording_start(metadata: &VideoMetadata) -> Option<DateTime<Utc>> {
+    let start = metadata.creation_time
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))?;
+    if start.year() < EARLIEST_RECORDING_YEAR || start > Utc::now() + chrono::Duration::days(1) {
+        warn!("Ignoring the creation time {} of {}, the camera clock can't have been set", start, metadata.filename);
+        return None;
+    }
+    Some(start)
+}
+
+/// When a video started recording, by the clock most to be trusted
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RecordingClock {
+    start: Option<DateTime<Utc>>,
+    /// `start` is a guess, or the camera and GPS clocks disagree on it
+    uncertain: bool,
+}
+
+/// Pick the clock the events are timed by, from the video's creation time
+/// and how its GPS track was aligned
+fn recording_clock(video_start: Option<DateTime<Utc>>, sync: Option<&Result<SyncResult, SyncError>>) -> RecordingClock {
+    let clock = |start, uncertain| RecordingClock { start, uncertain };
+    match (sync, video_start) {
+        // Lined up by hand, so the GPS clock is the one that's right
+        (Some(Ok(result)), _) if result.method == SyncMethod::Manual => clock(track_start(result), false),
+        // The GPS track overlaps the video where its creation time puts it
+        (Some(Ok(result)), Some(start)) if result.method == SyncMethod::VideoMetadata => clock(Some(start), false),
+        // The GPS track is somewhere else in time; one of the clocks is off
+        (Some(_), Some(start)) => clock(Some(start), true),
+        (None, Some(start)) => clock(Some(start), false),
+        // Taken to start with the track
+        (Some(Ok(result)), None) => clock(track_start(result), true),
+        (_, None) => clock(None, true),
+    }
+}
+
+/// Align a GPS track with the video's timeline: by `offset_seconds` when a
+/// manual sync found it, else from the recording times
+fn align(
+    track: GpsTrack,
+    metadata: &VideoMetadata,
+    video_start: Option<DateTime<Utc>>,
+    offset_seconds: Option<f64>,
+) -> (TimeSyncEngine, Result<SyncResult, SyncError>) {
+    let engine = TimeSyncEngine::new(track, metadata.duration_seconds, video_start)
+        .with_policy(settings::get().interpolation);
+    let result = match offset_seconds {
+        Some(offset) => engine.synchronize_with_offset(offset),
+        None => engine.synchronize(),
+    };
+    (engine, result)
+}
+
+/// One event per transcription segment, placed on the aligned GPS track
+fn speech_events(
+    segments: &[TranscriptionSegment],
+    recorded_from: Option<DateTime<Utc>>,
+    sync: Option<&(TimeSyncEngine, Result<SyncResult, SyncError>)>,
+) -> Vec<TruthEvent> {
+    match sync {
+        Some((engine, Ok(result))) => build_events(segments, recorded_from, |t| engine.interpolate_position(result, t)),
+        // A GPS file for another clip shouldn't cost the user the transcript
+        Some((_, Err(e))) => {
+            warn!("GPS sync failed, events will have no location: {}", e);
+            build_events(segments, recorded_from, |_| None)
+        }
+        None => build_events(segments, recorded_from, |_| None),
+    }
+}
+
+/// When the video starts by the clock of the GPS track aligned with it
+fn track_start(result: &SyncResult) -> Option<DateTime<Utc>> {
+    let point = result.aligned_points.first()?;
+    Some(point.gps.timestamp - chrono::Duration::milliseconds((point.video_time_seconds * 1000.0).round() as i64))
+}
+
+/// The aligned GPS points, timed from `start` by where they fall in the video
+fn timed_route(result: &SyncResult, start: DateTime<Utc>) -> Vec<GpsPoint> {
+    result
+        .aligned_points
+        .iter()
+        .map(|point| GpsPoint {
+            timestamp: start + chrono::Duration::milliseconds((point.video_time_seconds * 1000.0).round() as i64),
+            ..point.gps.clone()
+        })
+        .collect()
+}
+
+/// One event per transcription segment, located at the segment's midpoint
+///
+/// `position_at` maps a video time in seconds to (lat, lon, heading).
+fn build_events(
+    segments: &[TranscriptionSegment],
+    video_start: Option<DateTime<Utc>>,
+    position_at: impl Fn(f64) -> Option<(f64, f64, Option<f64>)>,
+) -> Vec<TruthEvent> {
+    segments
+        .iter()
+        .map(|segment| {
+            let midpoint = (segment.start_ms + segment.end_ms) as f64 / 2000.0;
+            let position = position_at(midpoint);
+            let location = position.map(|(lat, lon, _)| LocationResult { lat, lon });
+            let recorded_at = video_start.map(|start| start + chrono::Duration::milliseconds(segment.start_ms));
+            TruthEvent {
+                id: Uuid::new_v4().to_string(),
+                kind: EventKind::Speech,
+                // Without a recording time the best we have is the processing time
+                timestamp: recorded_at.unwrap_or_else(Utc::now),
+                local_time: None,
+                time_uncertain: recorded_at.is_none(),
+                duration_seconds: Some((segment.end_ms - segment.start_ms) as f64 / 1000.0),
+                // Only a real time and place put the sun anywhere
+                sun: recorded_at
+                    .zip(location.as_ref())
+                    .map(|(time, location)| sun::facts(time, location.lat, location.lon)),
+                location,
+                heading_deg: position.and_then(|(_, _, heading)| heading),
+                pois: vec![],
+                detected_objects: vec![],
+                image_path: None,
+                weather: None,
+                context: None,
+            }
+        })
+        .collect()
+}
+
+/// The notable POIs the sampled route approaches and passes
+///
+/// Passes are extra detail; a failed lookup leaves the bundle without them.
+async fn passes_along(db: &LocalDatabase, samples: &[poi_passes::TrackSample]) -> Vec<PoiPass> {
+    let Some(bounds) = poi_passes::bounds(samples, poi_passes::APPROACH_RADIUS_M) else {
+        return Vec::new();
+    };
+    let categories: Vec<String> = poi_passes::NOTABLE_CATEGORIES.iter().map(|c| c.to_string()).collect();
+    match db.pois_in_bounds(bounds, &categories).await {
+        Ok(candidates) => poi_passes::find_passes(samples, candidates),
+        Err(e) => {
+            warn!("Failed to look up POIs along the route: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Verify the located events against the downloaded POIs around them;
+/// returns how many were verified
+///
+/// The camera is taken to point the way the video's offset turns it from
+/// the heading. A point that can't be verified leaves its event as it was.
+async fn verify_events(db: &LocalDatabase, video_id: Uuid, events: &mut [TruthEvent], fov_deg: f64) -> usize {
+    let offset = db.camera_heading_offset(&video_id.to_string()).await.unwrap_or_else(|e| {
+        debug!("No camera heading offset for video {}: {}", video_id, e);
+        0.0
+    });
+    let mut located: Vec<&mut TruthEvent> = events.iter_mut().filter(|event| event.location.is_some()).collect();
+    let points: Vec<GpsPoint> = located.iter().filter_map(|event| event_point(event)).collect();
+
+    let engine = LocalTruthEngine::new().with_database(db.clone());
+    let mut count = 0;
+    for (event, bundle) in located.iter_mut().zip(engine.verify_track(&points, fov_deg, offset).await) {
+        match bundle {
+            Ok(bundle) => {
+                attach_verification(event, bundle);
+                count += 1;
+            }
+            Err(e) => warn!("Failed to verify event {}: {}", event.id, e),
+        }
+    }
+    debug!("Truth engine verified {} of {} located events", count, located.len());
+    count
+}
+
+/// Where and which way an event was recorded, as a track point
+fn event_point(event: &TruthEvent) -> Option<GpsPoint> {
+    let location = event.location.as_ref()?;
+    Some(GpsPoint {
+        timestamp: event.timestamp,
+        lat: location.lat,
+        lon: location.lon,
+        elevation_m: None,
+        speed_kmh: None,
+        heading_deg: event.heading_deg,
+        accuracy_m: None,
+    })
+}
+
+/// Give an event the POIs the truth engine found that it didn't have yet,
+/// closest first, and the location facts unless it has a context already
+fn attach_verification(event: &mut TruthEvent, bundle: truth_engine::TruthBundle) {
+    if event.context.is_none() {
+        event.context = bundle.location_context();
+    }
+    for poi in bundle.pois.into_iter().map(POI::from) {
+        if !event.pois.iter().any(|p| p.id == poi.id) {
+            event.pois.push(poi);
+        }
+    }
+}
+
+/// The scene changes worth an event of their own, in time order
+///
+/// Moments within `dedup_seconds` of a speech event, which the transcript
+/// already covers, are dropped, and of the rest one is kept at most every
+/// `min_spacing_seconds`, the earliest first.
+fn select_moments(
+    mut moments: Vec<VideoMoment>,
+    segments: &[TranscriptionSegment],
+    dedup_seconds: f64,
+    min_spacing_seconds: f64,
+) -> Vec<VideoMoment> {
+    moments.sort_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+    // Where build_events places each speech event
+    let spoken: Vec<f64> = segments.iter().map(|s| (s.start_ms + s.end_ms) as f64 / 2000.0).collect();
+    let mut last_kept: Option<f64> = None;
+    moments.retain(|moment| {
+        let near_speech = spoken.iter().any(|t| (t - moment.timestamp).abs() <= dedup_seconds);
+        let too_soon = last_kept.is_some_and(|last| moment.timestamp - last < min_spacing_seconds);
+        if near_speech || too_soon {
+            return false;
+        }
+        last_kept = Some(moment.timestamp);
+        true
+    });
+    moments
+}
+
+/// One event of `kind` at each moment, with the frame captured there
+///
+/// `position_at` maps a video time in seconds to (lat, lon, heading).
+fn moment_events(
+    moments: &[VideoMoment],
+    kind: EventKind,
+    recorded_from: Option<DateTime<Utc>>,
+    position_at: impl Fn(f64) -> Option<(f64, f64, Option<f64>)>,
+) -> Vec<TruthEvent> {
+    moments
+        .iter()
+        .map(|moment| {
+            let seconds = moment.timestamp;
+            let position = position_at(seconds);
+            let location = position.map(|(lat, lon, _)| LocationResult { lat, lon });
+            let recorded_at =
+                recorded_from.map(|start| start + chrono::Duration::milliseconds((seconds * 1000.0).round() as i64));
+            TruthEvent {
+                id: Uuid::new_v4().to_string(),
+                kind,
+                timestamp: recorded_at.unwrap_or_else(Utc::now),
+                local_time: None,
+                time_uncertain: recorded_at.is_none(),
+                duration_seconds: None,
+                sun: recorded_at
+                    .zip(location.as_ref())
+                    .map(|(time, location)| sun::facts(time, location.lat, location.lon)),
+                location,
+                heading_deg: position.and_then(|(_, _, heading)| heading),
+                pois: vec![],
+                detected_objects: vec![],
+                image_path: Some(moment.path.to_string_lossy().to_string()),
+                weather: None,
+                context: None,
+            }
+        })
+        .collect()
+}
+
+/// The event for a pass, at its time into the recording
+///
+/// The POI is in view if it lies within `fov_deg` of the heading, taking
+/// the camera to face the way it travels.
+fn pass_event(mut pass: PoiPass, recorded_from: Option<DateTime<Utc>>, fov_deg: f64) -> TruthEvent {
+    pass.poi.in_fov = pass.at.heading_deg.is_some_and(|heading| in_fov(heading, pass.poi.bearing_deg, fov_deg));
+    let recorded_at =
+        recorded_from.map(|start| start + chrono::Duration::milliseconds((pass.at.seconds * 1000.0).round() as i64));
+    let location = LocationResult { lat: pass.at.lat, lon: pass.at.lon };
+    TruthEvent {
+        id: Uuid::new_v4().to_string(),
+        kind: pass.kind,
+        timestamp: recorded_at.unwrap_or_else(Utc::now),
+        local_time: None,
+        time_uncertain: recorded_at.is_none(),
+        duration_seconds: None,
+        sun: recorded_at.map(|time| sun::facts(time, location.lat, location.lon)),
+        location: Some(location),
+        heading_deg: pass.at.heading_deg,
+        pois: vec![pass.poi],
+        detected_objects: vec![],
+        image_path: None,
+        weather: None,
+        context: None,
+    }
+}
+
+/// Bundle meta describing how (or whether) GPS was aligned
+fn sync_meta(result: Result<&SyncResult, &SyncError>) -> HashMap<String, String> {
+    let mut meta = HashMap::new();
+    match result {
+        Ok(sync) => {
+            meta.insert("sync.method".to_string(), format!("{:?}", sync.method));
+            meta.insert("sync.confidence".to_string(), format!("{:.2}", sync.confidence));
+        }
+        Err(e) => {
+            meta.insert("sync.confidence".to_string(), "0.00".to_string());
+            let note = match e {
+                SyncError::NoOverlap => "The GPS track doesn't overlap this video's time range, \
+                    so events have no location. Check that the GPS file belongs to this clip."
+                    .to_string(),
+                e => format!("GPS could not be aligned with the video ({}), so events have no location.", e),
+            };
+            meta.insert("sync.note".to_string(), note);
+        }
+    }
+    meta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::services::database::PoiRecord;
+    use crate::services::temp_files;
+
+    fn segments() -> Vec<TranscriptionSegment> {
+        vec![
+            TranscriptionSegment { start_ms: 0, end_ms: 4000, text: "Leaving the harbour".into() },
+            TranscriptionSegment { start_ms: 4000, end_ms: 10000, text: "Up the hill".into() },
+        ]
+    }
+
+    #[test]
+    fn test_events_without_sync_have_no_location() {
+        let start = DateTime::parse_from_rfc3339("2025-06-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let events = build_events(&segments(), Some(start), |_| None);
+
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.location.is_none()));
+        assert_eq!(events[1].timestamp, start + chrono::Duration::seconds(4));
+        assert_eq!(events[1].duration_seconds, Some(6.0));
+
+        let meta = sync_meta(Err(&SyncError::NoOverlap));
+        assert_eq!(meta["sync.confidence"], "0.00");
+        assert!(meta["sync.note"].contains("doesn't overlap"));
+    }
+
+    #[test]
+    fn test_events_located_at_segment_midpoint() {
+        let events = build_events(&segments(), None, |t| Some((43.0 + t / 100.0, 7.0, None)));
+
+        let location = events[1].location.as_ref().unwrap();
+        assert_eq!(location.lat, 43.07);
+        assert_eq!(location.lon, 7.0);
+    }
+
+    /// Along the Monaco harbour front every 5 s, with the fix lost for 70 s
+    const HARBOUR_GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="fixture"><trk><name>Port Hercule</name><trkseg>
+<trkpt lat="43.73540" lon="7.42100"><ele>3</ele><time>2025-06-01T10:00:00Z</time></trkpt>
+<trkpt lat="43.73555" lon="7.42160"><ele>3</ele><time>2025-06-01T10:00:05Z</time></trkpt>
+<trkpt lat="43.73570" lon="7.42220"><ele>3</ele><time>2025-06-01T10:00:10Z</time></trkpt>
+<trkpt lat="43.73585" lon="7.42280"><ele>4</ele><time>2025-06-01T10:00:15Z</time></trkpt>
+<trkpt lat="43.73600" lon="7.42340"><ele>4</ele><time>2025-06-01T10:00:20Z</time></trkpt>
+<trkpt lat="43.73615" lon="7.42400"><ele>4</ele><time>2025-06-01T10:00:25Z</time></trkpt>
+<trkpt lat="43.73630" lon="7.42460"><ele>5</ele><time>2025-06-01T10:00:30Z</time></trkpt>
+<trkpt lat="43.73800" lon="7.42700"><ele>12</ele><time>2025-06-01T10:01:40Z</time></trkpt>
+<trkpt lat="43.73830" lon="7.42740"><ele>13</ele><time>2025-06-01T10:01:45Z</time></trkpt>
+<trkpt lat="43.73860" lon="7.42780"><ele>14</ele><time>2025-06-01T10:01:50Z</time></trkpt>
+</trkseg></trk></gpx>"#;
+
+    /// FFprobe's view of the clip recorded along it, ten seconds in
+    const HARBOUR_VIDEO: &str = r#"{
+        "filename": "harbour.mp4", "duration_seconds": 100.0, "fps": 30.0, "width": 1920, "height": 1080,
+        "codec": "h264", "file_size_bytes": 120000000, "has_audio": true, "audio_codec": "aac",
+        "creation_time": "2025-06-01T10:00:10Z"
+    }"#;
+
+    /// The harbour track as a GPX file, in a directory of its own
+    fn harbour_gpx() -> PathBuf {
+        let path = fixtures::temp_dir("harbour").join("harbour.gpx");
+        std::fs::write(&path, HARBOUR_GPX).unwrap();
+        path
+    }
+
+    async fn harbour_track() -> GpsTrack {
+        let path = harbour_gpx();
+        let track = parse_gps_file(&path).await.unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+        track
+    }
+
+    #[tokio::test]
+    async fn test_speech_events_follow_the_gps_track() {
+        let track = harbour_track().await;
+        let metadata: VideoMetadata = serde_json::from_str(HARBOUR_VIDEO).unwrap();
+
+        let video_start = recording_start(&metadata);
+        let sync = align(track.clone(), &metadata, video_start, None);
+        let segments = [
+            TranscriptionSegment { start_ms: 2000, end_ms: 8000, text: "Along the quay".into() },
+            // Over half a minute from the nearest fix either side
+            TranscriptionSegment { start_ms: 52_000, end_ms: 58_000, text: "Into the tunnel".into() },
+            TranscriptionSegment { start_ms: 94_000, end_ms: 96_000, text: "Up to the casino".into() },
+        ];
+        let events = speech_events(&segments, video_start, Some(&sync));
+
+        // 10:00:15 on the track, five seconds past the clip's start
+        let start = DateTime::parse_from_rfc3339("2025-06-01T10:00:10Z").unwrap().with_timezone(&Utc);
+        assert_eq!(events[0].timestamp, start + chrono::Duration::seconds(2));
+        let location = events[0].location.as_ref().unwrap();
+        assert!((location.lat - 43.73585).abs() < 1e-9 && (location.lon - 7.42280).abs() < 1e-9);
+        // East-north-east along the quay
+        let heading = events[0].heading_deg.unwrap();
+        assert!((60.0..80.0).contains(&heading), "heading {}", heading);
+        assert!(events[0].sun.is_some());
+
+        // No position rather than a stale or zero one inside the dropout
+        assert!(events[1].location.is_none() && events[1].heading_deg.is_none());
+        assert!(events[1].sun.is_none());
+
+        let location = events[2].location.as_ref().unwrap();
+        assert!((43.7380..43.7386).contains(&location.lat) && (7.4270..7.4278).contains(&location.lon));
+
+        // A manual sync starting the track with the clip moves the events along it
+        let synced = align(track, &metadata, video_start, Some(0.0));
+        let events = speech_events(&segments[..1], video_start, Some(&synced));
+        let location = events[0].location.as_ref().unwrap();
+        assert!((location.lat - 43.73555).abs() < 1e-9 && (location.lon - 7.42160).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_harbour_events_carry_the_pois_around_them() {
+        let track = harbour_track().await;
+        let metadata: VideoMetadata = serde_json::from_str(HARBOUR_VIDEO).unwrap();
+        let video_start = recording_start(&metadata);
+        let sync = align(track, &metadata, video_start, None);
+        let segments = [
+            TranscriptionSegment { start_ms: 2000, end_ms: 8000, text: "Along the quay".into() },
+            TranscriptionSegment { start_ms: 52_000, end_ms: 58_000, text: "Into the tunnel".into() },
+            TranscriptionSegment { start_ms: 94_000, end_ms: 96_000, text: "Up to the casino".into() },
+        ];
+        let mut events = speech_events(&segments, video_start, Some(&sync));
+
+        // As a downloaded Monaco region lists them
+        let landmark = |id: &str, name: &str, lat: f64, lon: f64| PoiRecord {
+            id: id.to_string(),
+            name: name.to_string(),
+            category: "attraction".to_string(),
+            lat,
+            lon,
+            wikidata: Some("Q189316".to_string()),
+            ..Default::default()
+        };
+        let monaco = vec![
+            landmark("port", "Port Hercule", 43.7347, 7.4236),
+            landmark("casino", "Casino de Monte-Carlo", 43.7393, 7.4283),
+            landmark("palace", "Palais Princier", 43.7308, 7.4204),
+        ];
+        let engine = LocalTruthEngine::new();
+        for event in &mut events {
+            let Some(point) = event_point(event) else { continue };
+            let pois = truth_engine::within_radius(point.lat, point.lon, monaco.clone(), truth_engine::VERIFY_RADIUS_M);
+            attach_verification(event, engine.verify_with_pois(&point, pois, DEFAULT_FOV_DEG, 0.0));
+        }
+
+        let names = |event: &TruthEvent| event.pois.iter().map(|p| p.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&events[0]), ["Port Hercule"]);
+        assert_eq!(names(&events[2]), ["Casino de Monte-Carlo"]);
+        // Lost in the tunnel, so nothing to say is nearby; the palace is too far from either
+        assert!(events[1].pois.is_empty());
+        assert!(events[0].pois[0].facts.as_ref().is_some_and(|f| f.wikidata.is_some()));
+
+        // Verifying again doesn't list a POI twice
+        let point = event_point(&events[2]).unwrap();
+        let pois = truth_engine::within_radius(point.lat, point.lon, monaco, truth_engine::VERIFY_RADIUS_M);
+        attach_verification(&mut events[2], engine.verify_with_pois(&point, pois, DEFAULT_FOV_DEG, 0.0));
+        assert_eq!(events[2].pois.len(), 1);
+
+        let evidence = Evidence::from_events(&events);
+        assert!(evidence.score() > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_events_are_timed_by_the_clock_to_trust() {
+        let track = harbour_track().await;
+        let metadata: VideoMetadata = serde_json::from_str(HARBOUR_VIDEO).unwrap();
+        let camera = DateTime::parse_from_rfc3339("2025-06-01T10:00:10Z").unwrap().with_timezone(&Utc);
+        let clock_of = |metadata: &VideoMetadata, offset: Option<f64>| {
+            let video_start = recording_start(metadata);
+            let (_, result) = align(track.clone(), metadata, video_start, offset);
+            recording_clock(video_start, Some(&result))
+        };
+
+        // The camera's clock, backed up by the track
+        assert_eq!(clock_of(&metadata, None), RecordingClock { start: Some(camera), uncertain: false });
+        // Lined up by hand, the track's clock wins
+        let manual = clock_of(&metadata, Some(0.0));
+        assert_eq!(manual.start, DateTime::parse_from_rfc3339("2025-06-01T10:00:00Z").ok().map(|t| t.with_timezone(&Utc)));
+        assert!(!manual.uncertain);
+
+        // A clock never set is ignored, and the track's start is only a guess
+        let unset = VideoMetadata { creation_time: Some("1970-01-01T00:00:00Z".to_string()), ..metadata.clone() };
+        assert_eq!(recording_start(&unset), None);
+        let guessed = clock_of(&unset, None);
+        assert!(guessed.start.is_some() && guessed.uncertain);
+
+        // Without GPS the camera's clock is all there is
+        assert_eq!(recording_clock(Some(camera), None), RecordingClock { start: Some(camera), uncertain: false });
+        assert_eq!(recording_clock(None, None), RecordingClock { start: None, uncertain: true });
+
+        // Processing time stands in for an unknown recording time
+        let events = build_events(&segments(), None, |_| None);
+        assert!(events.iter().all(|e| e.time_uncertain));
+
+        let mut events = build_events(&segments(), Some(camera), |_| Some((43.7355, 7.4216, None)));
+        events[0].context = Some(crate::types::LocationContext { timezone: Some("Europe/Monaco".to_string()), ..Default::default() });
+        events[0].localize_time();
+        assert_eq!(events[0].local_time.as_deref(), Some("2025-06-01T12:00:10+02:00"));
+        assert!(!events[0].time_uncertain);
+    }
+
+    #[test]
+    fn test_pass_events_are_on_the_recording_clock() {
+        let start = DateTime::parse_from_rfc3339("2025-06-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let samples = poi_passes::sample_track(180.0, poi_passes::SAMPLE_SECONDS, |t| {
+            Some((43.7 + t * 15.0 / 111_320.0, 7.42, Some(0.0)))
+        });
+        let bridge = PoiRecord {
+            id: "bridge".to_string(),
+            name: "Pont Napoléon".to_string(),
+            category: "bridge".to_string(),
+            lat: 43.7 + 1_800.0 / 111_320.0,
+            lon: 7.4205,
+            ..Default::default()
+        };
+        let passes = poi_passes::find_passes(&samples, vec![bridge]);
+        let events: Vec<TruthEvent> = passes.into_iter().map(|pass| pass_event(pass, Some(start), DEFAULT_FOV_DEG)).collect();
+
+        let kinds: Vec<EventKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, [EventKind::Approach, EventKind::NearestPass]);
+        // Within 1.5 km 20-odd seconds in, nearest after 2 minutes
+        assert!(events[0].timestamp > start + chrono::Duration::seconds(19));
+        assert!(events[0].timestamp < start + chrono::Duration::seconds(25));
+        assert_eq!(events[1].timestamp, start + chrono::Duration::seconds(120));
+        assert_eq!(events[1].pois[0].id, "bridge");
+        assert_eq!(events[1].heading_deg, Some(0.0));
+        assert!(events[1].sun.is_some());
+        // Ahead while approaching, off to the side when passed
+        assert!(events[0].pois[0].in_fov);
+        assert!(!events[1].pois[0].in_fov);
+    }
+
+    #[test]
+    fn test_scene_events_are_placed_at_their_time() {
+        let start = DateTime::parse_from_rfc3339("2025-06-01T10:00:00Z").unwrap().with_timezone(&Utc);
+        let moments = [moment(12.5), moment(40.0)];
+        let events = moment_events(&moments, EventKind::SceneChange, Some(start), |t| {
+            (t < 30.0).then_some((43.7 + t / 100.0, 7.42, Some(90.0)))
+        });
+
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.kind == EventKind::SceneChange && e.duration_seconds.is_none()));
+        assert_eq!(events[0].timestamp, start + chrono::Duration::milliseconds(12_500));
+        assert_eq!(events[0].location.as_ref().unwrap().lat, 43.825);
+        assert_eq!(events[0].heading_deg, Some(90.0));
+        assert!(events[0].sun.is_some());
+        assert_eq!(events[0].image_path.as_deref(), Some("thumb_12.5.jpg"));
+        // Past the end of the track
+        assert!(events[1].location.is_none() && events[1].sun.is_none());
+    }
+
+    fn moment(timestamp: f64) -> VideoMoment {
+        VideoMoment { path: PathBuf::from(format!("thumb_{}.jpg", timestamp)), timestamp }
+    }
+
+    #[test]
+    fn test_scene_moments_leave_speech_alone_and_thin_out() {
+        // Speech events at 2 s and 7 s
+        let moments = [3.0, 15.0, 18.0, 30.0, 44.0, 46.0, 80.0, 95.0].map(moment).to_vec();
+
+        let kept = select_moments(moments.clone(), &segments(), 10.0, 30.0);
+        let times: Vec<f64> = kept.iter().map(|m| m.timestamp).collect();
+        assert_eq!(times, [18.0, 80.0]);
+
+        let kept = select_moments(moments, &[], 10.0, 0.0);
+        assert_eq!(kept.len(), 8);
+    }
+
+    #[test]
+    fn test_process_options_default_and_validate() {
+        // Options saved before these were added still read
+        let options: ProcessOptions = serde_json::from_str(r#"{"weather": true}"#).unwrap();
+        assert!(options.weather && options.transcription && options.truth_verification);
+        assert!(options.scene_moments);
+        assert_eq!((options.whisper_model, options.language.as_deref()), (None, None));
+        assert!(options.validate().is_ok());
+
+        let options: ProcessOptions = serde_json::from_str(r#"{"whisper_model": "small.en", "language": "en"}"#).unwrap();
+        assert_eq!(options.whisper_model, Some(WhisperModel::SmallEn));
+
+        assert!(ProcessOptions { scene_threshold: Some(1.5), ..Default::default() }.validate().is_err());
+        assert!(ProcessOptions { fov_deg: Some(0.0), ..Default::default() }.validate().is_err());
+        assert!(ProcessOptions { scene_min_spacing_seconds: Some(-1.0), ..Default::default() }.validate().is_err());
+        assert!(ProcessOptions { language: Some(" ".to_string()), ..Default::default() }.validate().is_err());
+    }
+
+    /// Keeps stage outputs in memory, as the database does
+    #[derive(Default)]
+    struct MemoryStore(std::sync::Mutex<Checkpoint>);
+
+    impl StageStore for MemoryStore {
+        fn load(&self, _: Uuid) -> StoreFuture<'_, Checkpoint> {
+            let checkpoint = self.0.lock().unwrap().clone();
+            Box::pin(async move { Ok(checkpoint) })
+        }
+
+        fn save_transcription<'a>(
+            &'a self,
+            _: Uuid,
+            transcription: &'a Transcription,
+            options: &'a ProcessOptions,
+            inputs: &'a StageInputs,
+        ) -> StoreFuture<'a, ()> {
+            let mut checkpoint = self.0.lock().unwrap();
+            checkpoint.transcription = Some(transcription.clone());
+            checkpoint.options = Some(options.clone());
+            checkpoint.inputs = Some(inputs.clone());
+            Box::pin(async { Ok(()) })
+        }
+
+        fn save_events<'a>(
+            &'a self,
+            _: Uuid,
+            events: &'a [TruthEvent],
+            options: &'a ProcessOptions,
+            inputs: &'a StageInputs,
+        ) -> StoreFuture<'a, ()> {
+            let mut checkpoint = self.0.lock().unwrap();
+            checkpoint.events = Some(events.to_vec());
+            checkpoint.options = Some(options.clone());
+            checkpoint.inputs = Some(inputs.clone());
+            Box::pin(async { Ok(()) })
+        }
+    }
+
+    /// The transcription stage of the harbour clip, going by `options` and what `store` kept
+    async fn transcribe_harbour(
+        processor: &VideoProcessor,
+        store: &MemoryStore,
+        mut options: ProcessOptions,
+    ) -> Result<(Transcribed, ProcessOptions)> {
+        let video_id = Uuid::new_v4();
+        let metadata: VideoMetadata = serde_json::from_str(HARBOUR_VIDEO).unwrap();
+        let checkpoint = store.load(video_id).await?;
+        let job_dir = JobDir::create(&std::env::temp_dir())?;
+        let transcribed = processor
+            .transcription_stage(
+                video_id,
+                &PathBuf::from("harbour.mp4"),
+                &metadata,
+                &mut options,
+                &checkpoint,
+                &StageInputs::default(),
+                Some(store),
+                job_dir.path(),
+                &mut StageTimings::default(),
+                &|_, _| {},
+            )
+            .await?;
+        Ok((transcribed, options))
+    }
+
+    #[tokio::test]
+    async fn test_retry_reuses_the_transcript_of_a_failed_run() {
+        // Without FFmpeg or Whisper, anything that runs them fails
+        let missing = fixtures::temp_dir("no-binaries");
+        let processor = VideoProcessor::new(
+            Arc::new(Ffmpeg::new(missing.clone()).unwrap()),
+            Arc::new(Whisper::new(missing.clone()).unwrap()),
+            std::env::temp_dir(),
+        );
+        let store = MemoryStore::default();
+        assert!(transcribe_harbour(&processor, &store, ProcessOptions::default()).await.is_err());
+
+        // As a run leaves it that transcribed, then failed verifying
+        let transcription = Transcription { segments: segments(), language: Some("fr".to_string()), full_text: String::new() };
+        let kept_with = ProcessOptions { whisper_model: Some(WhisperModel::Small), language: Some("fr".to_string()), ..Default::default() };
+        store.save_transcription(Uuid::new_v4(), &transcription, &kept_with, &StageInputs::default()).await.unwrap();
+
+        let (transcribed, options) = transcribe_harbour(&processor, &store, ProcessOptions::default()).await.unwrap();
+        assert!(transcribed.resumed);
+        assert_eq!(transcribed.transcription.segments.len(), 2);
+        assert_eq!(transcribed.audio_path, None);
+        // Recorded as the run that made it went
+        assert_eq!((options.whisper_model, options.language.as_deref()), (Some(WhisperModel::Small), Some("fr")));
+
+        // Another model or language has Whisper run again
+        let other_model = ProcessOptions { whisper_model: Some(WhisperModel::Base), ..Default::default() };
+        assert!(transcribe_harbour(&processor, &store, other_model).await.is_err());
+        let other_language = ProcessOptions { language: Some("en".to_string()), ..Default::default() };
+        assert!(transcribe_harbour(&processor, &store, other_language).await.is_err());
+        std::fs::remove_dir_all(&missing).ok();
+    }
+
+    #[tokio::test]
+    async fn test_failed_processing_leaves_no_temp_files() {
+        let missing = fixtures::temp_dir("no-binaries");
+        let jobs = fixtures::temp_dir("jobs");
+        let processor = VideoProcessor::new(
+            Arc::new(Ffmpeg::new(missing.clone()).unwrap()),
+            Arc::new(Whisper::new(missing.clone()).unwrap()),
+            jobs.clone(),
+        );
+
+        let processed = processor.process_video(PathBuf::from("harbour.mp4"), None, ProcessOptions::default(), |_, _| {}).await;
+        assert!(processed.is_err());
+        assert_eq!(temp_files::usage(&jobs).job_dirs, 0);
+        for dir in [missing, jobs] {
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gps_stage_runs_alongside_transcription() {
+        let path = harbour_gpx();
+        let metadata: VideoMetadata = serde_json::from_str(HARBOUR_VIDEO).unwrap();
+        let reports = std::sync::Mutex::new(Vec::new());
+        let on_progress = |stage: &'static str, progress: f32| reports.lock().unwrap().push((stage, progress));
+        let progress = BranchProgress::new(&on_progress, 0.05);
+        let aligned = tokio::sync::Notify::new();
+
+        // Only finishes once the GPS track is aligned, as it would if the stages ran one after the other
+        let transcription = async {
+            progress.transcript("audio_extract", 0.15);
+            tokio::time::timeout(Duration::from_secs(5), aligned.notified())
+                .await
+                .context("The GPS stage waited for the transcription")?;
+            progress.transcript("transcribe", 0.7);
+            Ok::<_, anyhow::Error>(())
+        };
+        let mut timings = StageTimings::default();
+        let gps = gps_stage(Some(GpsInput::File(path.clone())), &metadata, recording_start(&metadata), None, &mut timings, || {
+            progress.gps_done();
+            aligned.notify_one();
+        });
+        let ((), gps) = tokio::try_join!(transcription, gps).unwrap();
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert!(matches!(gps.sync, Some((_, Ok(_)))));
+        assert_eq!(timings.stages.len(), 1);
+        let reports = reports.into_inner().unwrap();
+        assert_eq!(reports.iter().map(|(stage, _)| *stage).collect::<Vec<_>>(), ["audio_extract", "gps", "transcribe"]);
+        // Never back down, however the stages interleave
+        assert!(reports.windows(2).all(|pair| pair[0].1 < pair[1].1));
+        assert!((reports[2].1 - 0.75).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn test_failed_gps_stage_stops_the_transcription() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        /// Stands for the Whisper process, killed as its future is dropped
+        struct Sidecar<'a>(&'a AtomicBool);
+        impl Drop for Sidecar<'_> {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let metadata: VideoMetadata = serde_json::from_str(HARBOUR_VIDEO).unwrap();
+        let (started, stopped) = (AtomicBool::new(false), AtomicBool::new(false));
+        // As Whisper would, taking far longer than a test should
+        let transcription = async {
+            started.store(true, Ordering::SeqCst);
+            let _sidecar = Sidecar(&stopped);
+            tokio::time::sleep(Duration::from_secs(600)).await;
+            Ok::<_, anyhow::Error>(())
+        };
+        let dir = fixtures::temp_dir("gps");
+        let mut timings = StageTimings::default();
+        let gps = gps_stage(Some(GpsInput::File(dir.join("missing.gpx"))), &metadata, None, None, &mut timings, || {});
+
+        let begun = Instant::now();
+        assert!(tokio::try_join!(transcription, gps).is_err());
+        assert!(begun.elapsed() < Duration::from_secs(5));
+        assert!(started.load(Ordering::SeqCst) && stopped.load(Ordering::SeqCst));
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_stage_leaves_the_ffmpeg_error_in_the_job_log() {
+        use crate::job_log::{self, JobLogLayer};
+        use crate::jobs;
+        use crate::state::AppState;
+        use std::os::unix::fs::PermissionsExt;
+        use tracing_subscriber::prelude::*;
+
+        let _subscriber = tracing_subscriber::registry().with(JobLogLayer).set_default();
+        // An FFmpeg that can't read the video
+        let binaries = fixtures::temp_dir("failing-ffmpeg");
+        let script = binaries.join("ffmpeg");
+        std::fs::write(&script, "#!/bin/sh\necho 'harbour.mp4: Invalid data found when processing input' >&2\nexit 1\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let processor = VideoProcessor::new(
+            Arc::new(Ffmpeg::new(binaries.clone()).unwrap()),
+            Arc::new(Whisper::new(binaries.clone()).unwrap()),
+            std::env::temp_dir(),
+        );
+
+        let state = Arc::new(AppState::new());
+        let (job_id, task) = jobs::start(&state, Arc::new(|_| {}), "processing", move |_| async move {
+            transcribe_harbour(&processor, &MemoryStore::default(), ProcessOptions::default())
+                .await
+                .map(|_| ())
+                .map_err(crate::error::CommandError::from)
+        });
+        task.await.unwrap();
+        std::fs::remove_dir_all(&binaries).ok();
+
+        let log = job_log::lines(&job_id).unwrap();
+        let failure = log.iter().find(|line| line.message.contains("Invalid data found")).unwrap();
+        assert_eq!((failure.level.as_str(), failure.stage.as_deref()), ("WARN", Some("audio_extract")));
+        let last = log.last().unwrap();
+        assert!(last.message.contains("Failed to extract audio"), "{}", last.message);
+        assert_eq!(last.stage, None);
+    }
+
+    #[test]
+    fn test_kept_events_only_go_with_the_same_options() {
+        let kept_with = ProcessOptions { whisper_model: Some(WhisperModel::Base), ..Default::default() };
+        let checkpoint = Checkpoint {
+            options: Some(kept_with.clone()),
+            inputs: None,
+            transcription: None,
+            events: Some(build_events(&segments(), None, |_| None)),
+        };
+        let inputs = StageInputs::default();
+
+        assert_eq!(checkpoint.events_for(&kept_with, true, &inputs).map(|events| events.len()), Some(2));
+        // Made from another transcript
+        assert!(checkpoint.events_for(&kept_with, false, &inputs).is_none());
+        let without_scenes = ProcessOptions { scene_moments: false, ..kept_with.clone() };
+        assert!(checkpoint.events_for(&without_scenes, true, &inputs).is_none());
+        // Weather is added to the bundle afterwards
+        assert!(checkpoint.events_for(&ProcessOptions { weather: true, ..kept_with }, true, &inputs).is_some());
+    }
+
+    #[test]
+    fn test_only_stages_whose_inputs_changed_run_again() {
+        let dir = fixtures::temp_dir("inputs");
+        let video = dir.join("harbour.mp4");
+        std::fs::write(&video, vec![7u8; 3 << 20]).unwrap();
+        let track = |lat: f64| {
+            let point = GpsPoint {
+                timestamp: Utc::now(),
+                lat,
+                lon: 7.4246,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: None,
+                accuracy_m: None,
+            };
+            fingerprint_points(&[point])
+        };
+        let inputs = StageInputs { video: fingerprint_file(&video).ok(), gps: Some(track(43.7384)) };
+        let kept_with = ProcessOptions { whisper_model: Some(WhisperModel::Base), ..Default::default() };
+        let checkpoint = Checkpoint {
+            options: Some(kept_with.clone()),
+            inputs: Some(inputs.clone()),
+            transcription: Some(Transcription { segments: segments(), language: None, full_text: String::new() }),
+            events: Some(build_events(&segments(), None, |_| None)),
+        };
+        assert_eq!(checkpoint.reusable(&kept_with, &inputs), [TRANSCRIBE_STAGE, VERIFICATION_STAGE]);
+
+        // A corrected GPS track is aligned and verified again, but not transcribed
+        let new_gps = StageInputs { gps: Some(track(43.7391)), ..inputs.clone() };
+        assert_eq!(checkpoint.reusable(&kept_with, &new_gps), [TRANSCRIBE_STAGE]);
+        // Another model transcribes again, and everything after it follows
+        let small = ProcessOptions { whisper_model: Some(WhisperModel::Small), ..kept_with.clone() };
+        assert!(checkpoint.reusable(&small, &inputs).is_empty());
+
+        // A different file in the video's place, even one the same size
+        let mut content = vec![7u8; 3 << 20];
+        content[(3 << 20) - 1] = 8;
+        std::fs::write(&video, content).unwrap();
+        let new_video = StageInputs { video: fingerprint_file(&video).ok(), ..inputs.clone() };
+        assert_ne!(new_video.video, inputs.video);
+        assert!(checkpoint.reusable(&kept_with, &new_video).is_empty());
+
+        // Kept before inputs were fingerprinted
+        let unfingerprinted = Checkpoint { inputs: None, ..checkpoint };
+        assert_eq!(unfingerprinted.reusable(&kept_with, &new_gps).len(), 2);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_kept_audio_is_reused_while_newer_than_the_video() {
+        let dir = fixtures::temp_dir("audio");
+        let video_id = Uuid::new_v4();
+        let audio = kept_audio_path(&dir, video_id, Some(1));
+        assert_eq!(audio, dir.join("audio").join(format!("{}.track1.wav", video_id)));
+        assert_ne!(audio, kept_audio_path(&dir, video_id, Some(0)));
+
+        std::fs::create_dir_all(audio.parent().unwrap()).unwrap();
+        let video = dir.join("ride.mp4");
+        std::fs::write(&video, b"video").unwrap();
+        assert!(!audio_is_current(&audio, &video));
+
+        std::fs::write(&audio, b"audio").unwrap();
+        let now = std::time::SystemTime::now();
+        let set_modified = |path: &Path, time| std::fs::File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+        set_modified(&video, now - Duration::from_secs(60));
+        set_modified(&audio, now);
+        assert!(audio_is_current(&audio, &video));
+
+        // The video was replaced since
+        set_modified(&video, now + Duration::from_secs(60));
+        assert!(!audio_is_current(&audio, &video));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_audio_track_selection() {
+        assert_eq!(audio_track(2, None).unwrap(), Some(0));
+        assert_eq!(audio_track(2, Some(1)).unwrap(), Some(1));
+        assert_eq!(audio_track(0, None).unwrap(), None);
+
+        let err = audio_track(2, Some(2)).unwrap_err();
+        assert_eq!(err.to_string(), "Audio track 2 doesn't exist; the video has 2 audio track(s)");
     }
 }
@@ -0,0 +1,276 @@
+//! Prompt Templates
+//!
+//! The narration and enrichment prompts are templates with `{name}` slots
+//! that are filled in for each request. The defaults are compiled in from
+//! `src/prompts/`; a file of the same name in the app's `prompts` directory
+//! overrides one, whether saved from the app or edited by hand. A template
+//! missing a slot it needs is refused when saved and ignored when loaded.
+//!
+//! Each template is identified by a short hash of its text, recorded with
+//! the narrations it produced so a change in output can be traced to a
+//! change in the prompt.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// The templates that can be customized
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptName {
+    /// System instruction for narration: the narrator's role and tone
+    NarrationSystem,
+    Narration,
+    /// Fallback for locations the offline data doesn't know
+    Enrichment,
+}
+
+impl PromptName {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PromptName::NarrationSystem => "narration_system",
+            PromptName::Narration => "narration",
+            PromptName::Enrichment => "enrichment",
+        }
+    }
+
+    fn default_text(self) -> &'static str {
+        match self {
+            PromptName::NarrationSystem => include_str!("prompts/narration_system.txt"),
+            PromptName::Narration => include_str!("prompts/narration.txt"),
+            PromptName::Enrichment => include_str!("prompts/enrichment.txt"),
+        }
+    }
+
+    /// Placeholders the template may use; the first `required` of them it must
+    fn placeholders(self) -> (&'static [&'static str], usize) {
+        match self {
+            PromptName::NarrationSystem => (&[], 0),
+            PromptName::Narration => (&["events", "route", "conditions", "background", "transcript", "continuity", "style", "length_note"], 1),
+            PromptName::Enrichment => (&["lat", "lon"], 2),
+        }
+    }
+}
+
+/// Why a template can't be used
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum PromptError {
+    #[error("The {name} template needs a {{{placeholder}}} placeholder")]
+    MissingPlaceholder { name: &'static str, placeholder: &'static str },
+
+    #[error("{{{placeholder}}} isn't a placeholder the {name} template can use")]
+    UnknownPlaceholder { name: &'static str, placeholder: String },
+
+    #[error("Failed to save the {name} template: {message}")]
+    Io { name: &'static str, message: String },
+}
+
+/// A template's text and where it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PromptTemplate {
+    pub name: PromptName,
+    pub text: String,
+    /// Whether the text was customized rather than the compiled-in default
+    pub custom: bool,
+    /// First 12 hex digits of the text's SHA-256
+    pub version: String,
+    /// Placeholders the template may use, e.g. `events`
+    pub placeholders: Vec<&'static str>,
+}
+
+impl PromptTemplate {
+    fn new(name: PromptName, text: &str, custom: bool) -> Self {
+        let text = text.trim_end().to_string();
+        let digest = format!("{:x}", Sha256::digest(text.as_bytes()));
+        Self {
+            name,
+            version: digest[..12].to_string(),
+            text,
+            custom,
+            placeholders: name.placeholders().0.to_vec(),
+        }
+    }
+
+    /// `name@version`, for response meta and logs
+    pub fn id(&self) -> String {
+        format!("{}@{}", self.name.as_str(), self.version)
+    }
+
+    /// The text with each `{name}` in `values` replaced
+    ///
+    /// Values are inserted as they are: braces in them aren't taken for
+    /// placeholders. Placeholders without a value are left in place.
+    pub fn render(&self, values: &[(&str, &str)]) -> String {
+        let mut rendered = String::with_capacity(self.text.len());
+        let mut rest = self.text.as_str();
+        while let Some((start, placeholder)) = next_placeholder(rest) {
+            rendered.push_str(&rest[..start]);
+            match values.iter().find(|(name, _)| *name == placeholder) {
+                Some((_, value)) => rendered.push_str(value),
+                None => rendered.push_str(&rest[start..start + placeholder.len() + 2]),
+            }
+            rest = &rest[start + placeholder.len() + 2..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+/// Directory holding customized templates
+pub fn prompts_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("com.geotruth.app")
+        .join("prompts")
+}
+
+/// The template in use: the customized one if it's valid, the default otherwise
+///
+/// Read on every call, so edits apply to the next request.
+pub fn load(name: PromptName) -> PromptTemplate {
+    load_from(&prompts_dir(), name)
+}
+
+/// Customize a template, or restore the default with `None` or blank text
+pub fn save(name: PromptName, text: Option<&str>) -> Result<PromptTemplate, PromptError> {
+    save_to(&prompts_dir(), name, text)
+}
+
+fn template_path(dir: &Path, name: PromptName) -> PathBuf {
+    dir.join(format!("{}.txt", name.as_str()))
+}
+
+fn load_from(dir: &Path, name: PromptName) -> PromptTemplate {
+    let path = template_path(dir, name);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => match validate(name, &text) {
+            Ok(()) => return PromptTemplate::new(name, &text, true),
+            Err(e) => warn!("Ignoring {}: {}", path.display(), e),
+        },
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => warn!("Failed to read {}: {}", path.display(), e),
+        Err(_) => {}
+    }
+    PromptTemplate::new(name, name.default_text(), false)
+}
+
+fn save_to(dir: &Path, name: PromptName, text: Option<&str>) -> Result<PromptTemplate, PromptError> {
+    let path = template_path(dir, name);
+    let io_error = |e: std::io::Error| PromptError::Io { name: name.as_str(), message: e.to_string() };
+
+    match text.filter(|t| !t.trim().is_empty()) {
+        Some(text) => {
+            validate(name, text)?;
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+            std::fs::write(&path, text).map_err(io_error)?;
+        }
+        None => match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(io_error(e)),
+            _ => {}
+        },
+    }
+
+    let template = load_from(dir, name);
+    info!("Prompt template {} is now {}", name.as_str(), template.id());
+    Ok(template)
+}
+
+/// Check that a template has every placeholder it needs and no others
+pub fn validate(name: PromptName, text: &str) -> Result<(), PromptError> {
+    let (allowed, required) = name.placeholders();
+    let mut rest = text;
+    let mut found = Vec::new();
+    while let Some((start, placeholder)) = next_placeholder(rest) {
+        if !allowed.contains(&placeholder) {
+            return Err(PromptError::UnknownPlaceholder { name: name.as_str(), placeholder: placeholder.to_string() });
+        }
+        found.push(placeholder);
+        rest = &rest[start + placeholder.len() + 2..];
+    }
+
+    match allowed[..required].iter().copied().find(|p| !found.contains(p)) {
+        Some(placeholder) => Err(PromptError::MissingPlaceholder { name: name.as_str(), placeholder }),
+        None => Ok(()),
+    }
+}
+
+/// Offset and name of the first `{name}` in `text`; names are lowercase
+/// letters and underscores, so the braces of a JSON example don't count
+fn next_placeholder(text: &str) -> Option<(usize, &str)> {
+    let mut offset = 0;
+    while let Some(start) = text[offset..].find('{').map(|i| offset + i) {
+        let name_len = text[start + 1..]
+            .find(|c: char| !(c.is_ascii_lowercase() || c == '_'))
+            .unwrap_or(text.len() - start - 1);
+        if name_len > 0 && text[start + 1 + name_len..].starts_with('}') {
+            return Some((start, &text[start + 1..start + 1 + name_len]));
+        }
+        offset = start + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        for name in [PromptName::NarrationSystem, PromptName::Narration, PromptName::Enrichment] {
+            assert_eq!(validate(name, name.default_text()), Ok(()), "{}", name.as_str());
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        assert_eq!(
+            validate(PromptName::Narration, "Narrate this trip."),
+            Err(PromptError::MissingPlaceholder { name: "narration", placeholder: "events" })
+        );
+        assert_eq!(
+            validate(PromptName::Enrichment, "Where is {lat}, {lng}?"),
+            Err(PromptError::UnknownPlaceholder { name: "enrichment", placeholder: "lng".to_string() })
+        );
+        assert_eq!(
+            PromptError::MissingPlaceholder { name: "narration", placeholder: "events" }.to_string(),
+            "The narration template needs a {events} placeholder"
+        );
+        // Optional slots can be left out, and JSON braces aren't placeholders
+        assert_eq!(validate(PromptName::Narration, "Events:\n{events}\nReturn {\"chapters\": []}"), Ok(()));
+    }
+
+    #[test]
+    fn test_render_inserts_values_once() {
+        let template = PromptTemplate::new(PromptName::Enrichment, "At {lat}, {lon} ({lat})", false);
+        assert_eq!(template.render(&[("lat", "{lon}"), ("lon", "7.4246")]), "At {lon}, 7.4246 ({lon})");
+        assert_eq!(template.render(&[]), "At {lat}, {lon} ({lat})");
+    }
+
+    #[test]
+    fn test_custom_template_round_trip() {
+        let dir = fixtures::temp_dir("prompts");
+        let default = load_from(&dir, PromptName::Narration);
+        assert!(!default.custom);
+        assert_eq!(default.id(), format!("narration@{}", default.version));
+
+        let err = save_to(&dir, PromptName::Narration, Some("No events here")).unwrap_err();
+        assert!(matches!(err, PromptError::MissingPlaceholder { .. }));
+        assert!(!template_path(&dir, PromptName::Narration).exists());
+
+        let custom = save_to(&dir, PromptName::Narration, Some("Be brief.\n{events}\n")).unwrap();
+        assert!(custom.custom);
+        assert_eq!(custom.text, "Be brief.\n{events}");
+        assert_ne!(custom.version, default.version);
+        assert_eq!(load_from(&dir, PromptName::Narration), custom);
+
+        // A file broken by hand falls back to the default
+        std::fs::write(template_path(&dir, PromptName::Narration), "{evnts}").unwrap();
+        assert_eq!(load_from(&dir, PromptName::Narration), default);
+
+        assert_eq!(save_to(&dir, PromptName::Narration, Some("  ")).unwrap(), default);
+        assert!(!template_path(&dir, PromptName::Narration).exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
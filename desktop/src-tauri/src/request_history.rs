@@ -0,0 +1,219 @@
+//! Request History
+//!
+//! The last few prompts sent for narration and enrichment, with the raw
+//! responses, so odd output can be traced to what the model was actually
+//! asked and said. Kept in memory in debug builds only; release builds
+//! record nothing. Keys are redacted before anything is recorded.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::llm::{BackendFuture, ImagePart, LlmBackend, Sampling};
+use crate::secrets;
+
+/// Requests kept in debug builds
+pub const HISTORY_SIZE: usize = 50;
+
+/// One request to a model and what came back
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordedRequest {
+    /// What the request was for, e.g. `narrate`
+    pub purpose: &'static str,
+    pub engine: &'static str,
+    pub model: String,
+    pub sent_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub system_instruction: Option<String>,
+    pub prompt: String,
+    pub image_count: usize,
+    pub response_schema: Option<serde_json::Value>,
+    /// Sampling parameters, where not the model's defaults
+    pub sampling: Sampling,
+    /// The response text exactly as received, before any parsing
+    pub response: Option<String>,
+    pub finish_reason: Option<String>,
+    pub error: Option<String>,
+}
+
+/// The most recent requests, oldest dropped first
+pub struct RequestHistory {
+    capacity: usize,
+    entries: Mutex<VecDeque<RecordedRequest>>,
+}
+
+impl RequestHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// History for this build: [`HISTORY_SIZE`] requests in debug builds, none in release
+    pub fn for_build() -> Self {
+        Self::new(if cfg!(debug_assertions) { HISTORY_SIZE } else { 0 })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn record(&self, request: RecordedRequest) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back(request);
+    }
+
+    /// Recorded requests, newest first
+    pub fn entries(&self) -> Vec<RecordedRequest> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().rev().cloned().collect()
+    }
+}
+
+/// Backend that records each request and its response in a [`RequestHistory`]
+pub struct RecordingBackend {
+    inner: Arc<dyn LlmBackend>,
+    history: Arc<RequestHistory>,
+    purpose: &'static str,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Arc<dyn LlmBackend>, history: Arc<RequestHistory>, purpose: &'static str) -> Self {
+        Self { inner, history, purpose }
+    }
+}
+
+impl LlmBackend for RecordingBackend {
+    fn generate_with_system<'a>(
+        &'a self,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        self.generate_with_sampling(Sampling::default(), system_instruction, prompt, images, response_schema, allow_cache)
+    }
+
+    fn generate_with_sampling<'a>(
+        &'a self,
+        sampling: Sampling,
+        system_instruction: Option<&'a str>,
+        prompt: &'a str,
+        images: Vec<ImagePart>,
+        response_schema: Option<serde_json::Value>,
+        allow_cache: bool,
+    ) -> BackendFuture<'a> {
+        if !self.history.is_enabled() {
+            return self.inner.generate_with_sampling(
+                sampling,
+                system_instruction,
+                prompt,
+                images,
+                response_schema,
+                allow_cache,
+            );
+        }
+
+        Box::pin(async move {
+            let (engine, model) = (self.inner.engine(), self.inner.model());
+            let image_count = images.len();
+            let schema = response_schema.clone();
+            let sent_at = Utc::now();
+            let started = Instant::now();
+
+            let result = self
+                .inner
+                .generate_with_sampling(sampling, system_instruction, prompt, images, response_schema, allow_cache)
+                .await;
+
+            let (response, finish_reason, error) = match &result {
+                Ok(generation) => (
+                    Some(secrets::redact(&generation.text)),
+                    Some(generation.finish_reason.as_str().to_string()),
+                    None,
+                ),
+                Err(e) => (None, None, Some(secrets::redact(&e.to_string()))),
+            };
+            self.history.record(RecordedRequest {
+                purpose: self.purpose,
+                engine,
+                model,
+                sent_at,
+                duration_ms: started.elapsed().as_millis() as u64,
+                system_instruction: system_instruction.map(secrets::redact),
+                prompt: secrets::redact(prompt),
+                image_count,
+                response_schema: schema,
+                sampling,
+                response,
+                finish_reason,
+                error,
+            });
+            result
+        })
+    }
+
+    fn model(&self) -> String {
+        self.inner.model()
+    }
+
+    fn engine(&self) -> &'static str {
+        self.inner.engine()
+    }
+
+    fn is_remote(&self) -> bool {
+        self.inner.is_remote()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::mock::MockGemini;
+    use crate::gemini::GeminiError;
+
+    #[tokio::test]
+    async fn test_requests_are_recorded_newest_first() {
+        let history = Arc::new(RequestHistory::new(2));
+        let mock = MockGemini::new()
+            .with_text("first")
+            .with_error(GeminiError::Network("POST /m:generateContent?key=AIzaSyExampleKey1234 timed out".into()))
+            .with_text("third")
+            .with_model("gemini-3.0-flash");
+        let backend = RecordingBackend::new(Arc::new(mock), history.clone(), "narrate");
+
+        backend.generate_with_system(Some("Be a guide"), "one", vec![], None, true).await.unwrap();
+        backend.generate_multimodal("two", vec![], None, true).await.unwrap_err();
+        backend.generate_multimodal("three", vec![], None, true).await.unwrap();
+
+        // The first fell out of the two kept
+        let entries = history.entries();
+        let prompts: Vec<&str> = entries.iter().map(|e| e.prompt.as_str()).collect();
+        assert_eq!(prompts, ["three", "two"]);
+        assert_eq!(entries[0].response.as_deref(), Some("third"));
+        assert_eq!(entries[0].finish_reason.as_deref(), Some("stop"));
+        assert_eq!((entries[0].purpose, entries[0].model.as_str()), ("narrate", "gemini-3.0-flash"));
+
+        let error = entries[1].error.as_deref().unwrap();
+        assert!(!error.contains("AIzaSyExampleKey1234"), "{}", error);
+        assert!(error.contains("key=••••1234"));
+    }
+
+    #[tokio::test]
+    async fn test_disabled_history_records_nothing() {
+        let history = Arc::new(RequestHistory::new(0));
+        let backend = RecordingBackend::new(Arc::new(MockGemini::new().with_text("ok")), history.clone(), "enrich");
+        backend.generate_multimodal("hello", vec![], None, true).await.unwrap();
+        assert!(history.entries().is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+//! Resource Governor
+//!
+//! App-wide caps on heavy work, so processing, region imports and downloads
+//! running together can't saturate the machine. Work comes in three kinds:
+//! CPU-heavy (FFmpeg and Whisper), IO-heavy (hashing, copying and packing
+//! large files) and network transfers (region downloads). Each kind has a
+//! number of slots, and anything about to start such work takes one first,
+//! waiting for it if need be; the slot is given back when its permit drops.
+//!
+//! Interactive work, such as capturing a frame for the UI, goes ahead of
+//! batch work waiting for the same kind of slot, and one slot of each kind
+//! that has more than one is kept for it, so it doesn't wait on a long
+//! Whisper run. Work already running is never interrupted.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tracing::{debug, info};
+
+use crate::settings;
+
+/// A kind of heavy work
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resource {
+    Cpu,
+    Io,
+    Network,
+}
+
+impl Resource {
+    /// Slots when the settings don't say
+    pub fn default_limit(self) -> usize {
+        match self {
+            // Half the available cores, since FFmpeg and Whisper are multi-threaded themselves
+            Self::Cpu => std::thread::available_parallelism().map(|n| (n.get() / 2).max(1)).unwrap_or(2),
+            // A disk does best with little else going on
+            Self::Io => 2,
+            Self::Network => 3,
+        }
+    }
+}
+
+/// Who's waiting on the work; interactive work goes first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// The user is waiting on it
+    Interactive,
+    /// Background processing, imports and downloads
+    Batch,
+}
+
+/// Slots for each kind of work (`None` = the default for this machine)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResourceLimits {
+    pub cpu: Option<usize>,
+    pub io: Option<usize>,
+    pub network: Option<usize>,
+}
+
+impl ResourceLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if [self.cpu, self.io, self.network].contains(&Some(0)) {
+            return Err("Limits must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// Slots for `resource` under these limits
+    pub fn effective(&self, resource: Resource) -> usize {
+        let limit = match resource {
+            Resource::Cpu => self.cpu,
+            Resource::Io => self.io,
+            Resource::Network => self.network,
+        };
+        limit.unwrap_or_else(|| resource.default_limit()).max(1)
+    }
+}
+
+/// How one kind of slot is being used
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PoolStatus {
+    pub limit: usize,
+    /// Slots taken; above the limit for a while after it's lowered
+    pub held: usize,
+    /// Work waiting for a slot
+    pub waiting: usize,
+    /// Of which the user is waiting on
+    pub waiting_interactive: usize,
+}
+
+/// Snapshot of the governor, for the UI
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ResourceStatus {
+    pub cpu: PoolStatus,
+    pub io: PoolStatus,
+    pub network: PoolStatus,
+}
+
+static SHARED: Lazy<Arc<ResourceGovernor>> =
+    Lazy::new(|| Arc::new(ResourceGovernor::new(settings::get().resource_limits)));
+
+/// The governor all heavy work shares
+pub fn shared() -> Arc<ResourceGovernor> {
+    SHARED.clone()
+}
+
+/// Wait for a slot of the shared governor
+pub async fn acquire(resource: Resource, priority: Priority) -> ResourcePermit {
+    SHARED.acquire(resource, priority).await
+}
+
+/// A place in line, by priority and then arrival
+type Ticket = (Priority, u64);
+
+struct PoolState {
+    limit: usize,
+    held: usize,
+    waiting: BTreeSet<Ticket>,
+    next_ticket: u64,
+}
+
+impl PoolState {
+    /// Slots work of `priority` may take between them
+    fn capacity(&self, priority: Priority) -> usize {
+        match priority {
+            Priority::Interactive => self.limit,
+            // One kept back for interactive work, when there's one to spare
+            Priority::Batch => self.limit.saturating_sub(1).max(1),
+        }
+    }
+
+    /// Take a slot for `ticket`, if it's first in line and one is free
+    fn try_take(&mut self, ticket: Ticket) -> bool {
+        if self.waiting.first() != Some(&ticket) || self.held >= self.capacity(ticket.0) {
+            return false;
+        }
+        self.waiting.remove(&ticket);
+        self.held += 1;
+        true
+    }
+}
+
+struct Pool {
+    resource: Resource,
+    state: Mutex<PoolState>,
+    /// Wakes the waiting work when a slot may have come free or the line moved
+    changed: Notify,
+}
+
+impl Pool {
+    fn lock(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn status(&self) -> PoolStatus {
+        let state = self.lock();
+        PoolStatus {
+            limit: state.limit,
+            held: state.held,
+            waiting: state.waiting.len(),
+            waiting_interactive: state.waiting.iter().filter(|(priority, _)| *priority == Priority::Interactive).count(),
+        }
+    }
+}
+
+/// Takes a ticket out of line if its wait is given up
+struct InLine<'a> {
+    pool: &'a Pool,
+    ticket: Option<Ticket>,
+}
+
+impl Drop for InLine<'_> {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.pool.lock().waiting.remove(&ticket);
+            self.pool.changed.notify_waiters();
+        }
+    }
+}
+
+/// A slot held until dropped
+pub struct ResourcePermit {
+    pool: Arc<Pool>,
+}
+
+impl Drop for ResourcePermit {
+    fn drop(&mut self) {
+        self.pool.lock().held -= 1;
+        self.pool.changed.notify_waiters();
+    }
+}
+
+/// Slots for each kind of heavy work
+pub struct ResourceGovernor {
+    cpu: Arc<Pool>,
+    io: Arc<Pool>,
+    network: Arc<Pool>,
+}
+
+impl ResourceGovernor {
+    pub fn new(limits: ResourceLimits) -> Self {
+        let pool = |resource| {
+            Arc::new(Pool {
+                resource,
+                state: Mutex::new(PoolState {
+                    limit: limits.effective(resource),
+                    held: 0,
+                    waiting: BTreeSet::new(),
+                    next_ticket: 0,
+                }),
+                changed: Notify::new(),
+            })
+        };
+        Self { cpu: pool(Resource::Cpu), io: pool(Resource::Io), network: pool(Resource::Network) }
+    }
+
+    fn pool(&self, resource: Resource) -> &Arc<Pool> {
+        match resource {
+            Resource::Cpu => &self.cpu,
+            Resource::Io => &self.io,
+            Resource::Network => &self.network,
+        }
+    }
+
+    /// Wait for a slot of `resource`; it's given back when the permit drops
+    ///
+    /// Giving up the wait, by dropping the future, leaves the line as it was.
+    pub async fn acquire(&self, resource: Resource, priority: Priority) -> ResourcePermit {
+        let pool = self.pool(resource).clone();
+        let ticket = {
+            let mut state = pool.lock();
+            let ticket = (priority, state.next_ticket);
+            state.next_ticket += 1;
+            state.waiting.insert(ticket);
+            ticket
+        };
+        let mut in_line = InLine { pool: &pool, ticket: Some(ticket) };
+
+        loop {
+            // Listening before looking, so a slot freed in between isn't missed
+            let mut changed = std::pin::pin!(pool.changed.notified());
+            changed.as_mut().enable();
+            if pool.lock().try_take(ticket) {
+                break;
+            }
+            changed.await;
+        }
+        in_line.ticket = None;
+        drop(in_line);
+        // Whoever is next in line may be able to start too
+        pool.changed.notify_waiters();
+        debug!("{:?} slot taken for {:?} work", pool.resource, priority);
+        ResourcePermit { pool }
+    }
+
+    /// Resize a kind of slot. Lowering takes effect as running work finishes.
+    pub fn set_limit(&self, resource: Resource, limit: usize) {
+        let limit = limit.max(1);
+        let pool = self.pool(resource);
+        let old_limit = std::mem::replace(&mut pool.lock().limit, limit);
+        pool.changed.notify_waiters();
+        if old_limit != limit {
+            info!("{:?} slots: {} -> {}", resource, old_limit, limit);
+        }
+    }
+
+    pub fn set_limits(&self, limits: ResourceLimits) {
+        for resource in [Resource::Cpu, Resource::Io, Resource::Network] {
+            self.set_limit(resource, limits.effective(resource));
+        }
+    }
+
+    pub fn limit(&self, resource: Resource) -> usize {
+        self.pool(resource).lock().limit
+    }
+
+    pub fn status(&self) -> ResourceStatus {
+        ResourceStatus { cpu: self.cpu.status(), io: self.io.status(), network: self.network.status() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn governor(cpu: usize) -> Arc<ResourceGovernor> {
+        Arc::new(ResourceGovernor::new(ResourceLimits { cpu: Some(cpu), ..Default::default() }))
+    }
+
+    #[tokio::test]
+    async fn test_no_more_than_the_limit_of_cpu_permits_are_held() {
+        let governor = governor(3);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..60)
+            .map(|i| {
+                let (governor, running, peak) = (governor.clone(), running.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let priority = if i % 4 == 0 { Priority::Interactive } else { Priority::Batch };
+                    let _permit = governor.acquire(Resource::Cpu, priority).await;
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    assert!(governor.status().cpu.held <= 3);
+                    tokio::time::sleep(Duration::from_millis(1 + i % 3)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        // Some waits are given up part way; they mustn't hold up the rest
+        for _ in 0..10 {
+            let _ = tokio::time::timeout(Duration::from_micros(50), governor.acquire(Resource::Cpu, Priority::Batch)).await;
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert!(peak.load(Ordering::SeqCst) <= 3, "{} held at once", peak.load(Ordering::SeqCst));
+        assert_eq!(governor.status().cpu, PoolStatus { limit: 3, ..Default::default() });
+    }
+
+    #[tokio::test]
+    async fn test_interactive_work_goes_ahead_of_batch_work() {
+        let governor = governor(2);
+        let batch = governor.acquire(Resource::Cpu, Priority::Batch).await;
+
+        // The other slot is kept for interactive work
+        let waiting_batch = tokio::spawn({
+            let governor = governor.clone();
+            async move { governor.acquire(Resource::Cpu, Priority::Batch).await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let status = governor.status().cpu;
+        assert_eq!((status.held, status.waiting), (1, 1));
+        let interactive = governor.acquire(Resource::Cpu, Priority::Interactive).await;
+        assert_eq!(governor.status().cpu.held, 2);
+
+        // Freed, the slot is kept back again, for interactive work that
+        // turns up after the batch work
+        drop(interactive);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(governor.status().cpu.waiting, 1);
+        let _interactive = governor.acquire(Resource::Cpu, Priority::Interactive).await;
+
+        drop(batch);
+        let _batch = waiting_batch.await.unwrap();
+        assert_eq!(governor.status().cpu, PoolStatus { limit: 2, held: 2, ..Default::default() });
+    }
+
+    #[tokio::test]
+    async fn test_lowering_a_limit_waits_for_running_work() {
+        let governor = governor(2);
+        let first = governor.acquire(Resource::Io, Priority::Batch).await;
+        let second = governor.acquire(Resource::Io, Priority::Interactive).await;
+        governor.set_limit(Resource::Io, 1);
+        assert_eq!(governor.status().io.held, 2);
+
+        drop(first);
+        let waiting = tokio::time::timeout(Duration::from_millis(20), governor.acquire(Resource::Io, Priority::Interactive));
+        assert!(waiting.await.is_err());
+        drop(second);
+        let _permit = governor.acquire(Resource::Io, Priority::Batch).await;
+        assert_eq!(governor.status().io, PoolStatus { limit: 1, held: 1, ..Default::default() });
+    }
+}
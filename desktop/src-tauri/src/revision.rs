@@ -0,0 +1,333 @@
+//! Narration Revisions
+//!
+//! After a few events are edited, only the narration around them needs
+//! writing again. Each changed window is widened to the whole chapters it
+//! touches, the model writes just those stretches, and what it writes is
+//! spliced into the earlier narration. Chapters and lines outside them are
+//! kept exactly as they were.
+
+use crate::pacing;
+use crate::types::{Chapter, NarrateResponse, ScriptSegment, TimeWindow};
+
+/// Lines of the narration around a stretch shown to the model on each side
+const CONTEXT_LINES: usize = 2;
+
+/// A stretch of the video narrated again, in seconds into it
+///
+/// `end` isn't part of it, and is infinite for a stretch running to the
+/// end of the video.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Region {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl Region {
+    pub fn contains(&self, seconds: f64) -> bool {
+        seconds >= self.start && seconds < self.end
+    }
+
+    /// e.g. `01:00 to 02:30`
+    pub fn describe(&self) -> String {
+        if self.end.is_finite() {
+            format!("{} to {}", pacing::format_time_code(self.start), pacing::format_time_code(self.end))
+        } else {
+            format!("{} to the end", pacing::format_time_code(self.start))
+        }
+    }
+}
+
+/// The stretches of `previous` to narrate again for `windows`, in time order
+///
+/// Each window is widened to the chapters it touches, or to the script
+/// lines it touches in a narration without chapters. Stretches that
+/// overlap or meet are joined.
+pub fn regions(previous: &NarrateResponse, windows: &[TimeWindow]) -> Result<Vec<Region>, String> {
+    if windows.is_empty() {
+        return Err("No changed time windows were given".to_string());
+    }
+    let boundaries = boundaries(previous)?;
+
+    let mut regions = Vec::with_capacity(windows.len());
+    for window in windows {
+        let (from, to) = (window.start_seconds, window.end_seconds);
+        if !(from.is_finite() && to.is_finite() && from >= 0.0 && to >= from) {
+            return Err(format!("Invalid time window from {} to {} seconds", from, to));
+        }
+        let start = boundaries.iter().rev().find(|b| **b <= from).copied().unwrap_or(0.0);
+        let end = boundaries.iter().find(|b| **b > start && **b >= to).copied().unwrap_or(f64::INFINITY);
+        regions.push(Region { start, end });
+    }
+    regions.sort_by(|a, b| a.start.total_cmp(&b.start));
+
+    let mut joined: Vec<Region> = Vec::with_capacity(regions.len());
+    for region in regions {
+        match joined.last_mut() {
+            Some(last) if region.start <= last.end => last.end = last.end.max(region.end),
+            _ => joined.push(region),
+        }
+    }
+    Ok(joined)
+}
+
+/// Where `previous` can be cut: its chapter starts, or its line starts
+/// when it has no chapters
+fn boundaries(previous: &NarrateResponse) -> Result<Vec<f64>, String> {
+    let time_codes: Vec<&str> = if previous.chapters.is_empty() {
+        script(previous).iter().map(|s| s.time_code.as_str()).collect()
+    } else {
+        previous.chapters.iter().map(|c| c.time_code.as_str()).collect()
+    };
+
+    let mut boundaries = time_codes
+        .into_iter()
+        .map(|time_code| {
+            pacing::time_code_seconds(time_code)
+                .ok_or_else(|| format!("The earlier narration has an unreadable time code '{}'", time_code))
+        })
+        .collect::<Result<Vec<f64>, String>>()?;
+    boundaries.sort_by(f64::total_cmp);
+    boundaries.dedup();
+    Ok(boundaries)
+}
+
+/// Prompt section asking for `region` of `previous` to be written again,
+/// with the narration on either side of it
+pub fn context_note(previous: &NarrateResponse, region: &Region) -> String {
+    let mut note = format!(
+        "\n## Revising Part of a Narration\nOnly the narration from {} is being written again; the rest \
+         stays as it is. Use time codes from that range. Pick up from the narration before it and lead into \
+         the narration after it, without repeating either.\n",
+        region.describe()
+    );
+
+    let seconds = |time_code: &str| pacing::time_code_seconds(time_code).unwrap_or_default();
+    let lines = script(previous);
+    let before: Vec<&ScriptSegment> = lines.iter().filter(|s| seconds(&s.time_code) < region.start).collect();
+    let after: Vec<&ScriptSegment> = lines.iter().filter(|s| seconds(&s.time_code) >= region.end).collect();
+
+    if let Some(chapter) = previous.chapters.iter().rev().find(|c| seconds(&c.time_code) < region.start) {
+        note.push_str(&format!("Chapter before: {} {}\n", chapter.time_code, chapter.title));
+    }
+    for segment in before.iter().rev().take(CONTEXT_LINES).rev() {
+        note.push_str(&format!("Narration before, at {}: {}\n", segment.time_code, segment.narration));
+    }
+    if let Some(chapter) = previous.chapters.iter().find(|c| seconds(&c.time_code) >= region.end) {
+        note.push_str(&format!("Chapter after: {} {}\n", chapter.time_code, chapter.title));
+    }
+    for segment in after.iter().take(CONTEXT_LINES) {
+        note.push_str(&format!("Narration after, at {}: {}\n", segment.time_code, segment.narration));
+    }
+    note
+}
+
+/// What the model wrote for one region
+#[derive(Debug, Default)]
+pub struct Rewrite {
+    pub chapters: Vec<Chapter>,
+    pub segments: Vec<ScriptSegment>,
+}
+
+/// An earlier narration with some regions written again
+#[derive(Debug)]
+pub struct Spliced {
+    pub chapters: Vec<Chapter>,
+    pub segments: Vec<ScriptSegment>,
+    /// Whether each segment is new
+    pub rewritten: Vec<bool>,
+    /// Whether each chapter is new
+    pub rewritten_chapters: Vec<bool>,
+    /// A note for each chapter or line written outside its region, which is dropped
+    pub dropped: Vec<String>,
+}
+
+/// Put each region's rewrite, in order, in place of what `previous` had there
+///
+/// The rewrites' time codes must already be canonical. Fails if the
+/// result isn't in time order: chapters must each start after the one
+/// before, and lines no earlier than the one before.
+pub fn splice(previous: &NarrateResponse, regions: &[Region], rewrites: Vec<Rewrite>) -> Result<Spliced, String> {
+    let mut dropped = Vec::new();
+    let (mut chapters, mut segments) = (Vec::with_capacity(regions.len()), Vec::with_capacity(regions.len()));
+    for (region, rewrite) in regions.iter().zip(rewrites) {
+        chapters.push(within(region, rewrite.chapters, "chapter", |c| &c.time_code, &mut dropped));
+        segments.push(within(region, rewrite.segments, "script segment", |s| &s.time_code, &mut dropped));
+    }
+
+    let (chapters, rewritten_chapters): (Vec<Chapter>, Vec<bool>) =
+        merge(&previous.chapters, regions, chapters, |c| &c.time_code).into_iter().unzip();
+    let (segments, rewritten): (Vec<ScriptSegment>, Vec<bool>) =
+        merge(script(previous), regions, segments, |s| &s.time_code).into_iter().unzip();
+
+    in_order(&chapters, "chapter", |c| &c.time_code, |a, b| a < b)?;
+    in_order(&segments, "script segment", |s| &s.time_code, |a, b| a <= b)?;
+    Ok(Spliced { chapters, segments, rewritten, rewritten_chapters, dropped })
+}
+
+/// The items of a rewrite inside `region`, in time order; the others are
+/// dropped with a note
+fn within<T>(
+    region: &Region,
+    mut items: Vec<T>,
+    kind: &str,
+    time_code: impl Fn(&T) -> &str,
+    dropped: &mut Vec<String>,
+) -> Vec<T> {
+    items.sort_by(|a, b| {
+        let (a, b) = (pacing::time_code_seconds(time_code(a)), pacing::time_code_seconds(time_code(b)));
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    items
+        .into_iter()
+        .filter(|item| {
+            let inside = pacing::time_code_seconds(time_code(item)).is_some_and(|t| region.contains(t));
+            if !inside {
+                dropped.push(format!("Dropped {} at {}: outside {}", kind, time_code(item), region.describe()));
+            }
+            inside
+        })
+        .collect()
+}
+
+/// `previous` in its own order, less what's in the regions, with each
+/// region's new items where it starts; each item says whether it's new
+fn merge<T: Clone>(
+    previous: &[T],
+    regions: &[Region],
+    new: Vec<Vec<T>>,
+    time_code: impl Fn(&T) -> &str,
+) -> Vec<(T, bool)> {
+    let mut merged = Vec::with_capacity(previous.len());
+    let mut pending = regions.iter().zip(new).peekable();
+    for item in previous {
+        // Unreadable ones were refused when the regions were found
+        let seconds = pacing::time_code_seconds(time_code(item)).unwrap_or_default();
+        while let Some((_, new)) = pending.next_if(|(region, _)| region.start <= seconds) {
+            merged.extend(new.into_iter().map(|item| (item, true)));
+        }
+        if !regions.iter().any(|region| region.contains(seconds)) {
+            merged.push((item.clone(), false));
+        }
+    }
+    for (_, new) in pending {
+        merged.extend(new.into_iter().map(|item| (item, true)));
+    }
+    merged
+}
+
+fn in_order<T>(
+    items: &[T],
+    kind: &str,
+    time_code: impl Fn(&T) -> &str,
+    follows: impl Fn(f64, f64) -> bool,
+) -> Result<(), String> {
+    let mut last: Option<(f64, &str)> = None;
+    for item in items {
+        let code = time_code(item);
+        let seconds = pacing::time_code_seconds(code)
+            .ok_or_else(|| format!("The revised narration has an unreadable time code '{}'", code))?;
+        if let Some((before, before_code)) = last {
+            if !follows(before, seconds) {
+                return Err(format!(
+                    "The revised narration is out of order: a {} at {} follows one at {}",
+                    kind, code, before_code
+                ));
+            }
+        }
+        last = Some((seconds, code));
+    }
+    Ok(())
+}
+
+fn script(response: &NarrateResponse) -> &[ScriptSegment] {
+    response.script.as_ref().map_or(&[], |script| script.segments.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::NarrateScript;
+    use std::collections::HashMap;
+
+    fn narration(chapters: &[&str], lines: &[&str]) -> NarrateResponse {
+        NarrateResponse {
+            chapters: chapters
+                .iter()
+                .map(|time_code| Chapter {
+                    time_code: time_code.to_string(),
+                    title: format!("Chapter at {}", time_code),
+                    description: None,
+                })
+                .collect(),
+            script: Some(NarrateScript {
+                segments: lines
+                    .iter()
+                    .map(|time_code| ScriptSegment {
+                        time_code: time_code.to_string(),
+                        narration: format!("Line at {}", time_code),
+                        ..Default::default()
+                    })
+                    .collect(),
+            }),
+            evidence: HashMap::new(),
+            meta: HashMap::new(),
+        }
+    }
+
+    fn window(start_seconds: f64, end_seconds: f64) -> TimeWindow {
+        TimeWindow { start_seconds, end_seconds }
+    }
+
+    #[test]
+    fn test_windows_widen_to_chapters() {
+        let previous = narration(&["00:00", "01:00", "02:00", "03:00"], &["00:10", "01:10", "02:10", "03:10"]);
+
+        // Within one chapter
+        assert_eq!(regions(&previous, &[window(70.0, 80.0)]).unwrap(), [Region { start: 60.0, end: 120.0 }]);
+        // Ending where the next chapter starts doesn't touch it
+        assert_eq!(regions(&previous, &[window(70.0, 120.0)]).unwrap(), [Region { start: 60.0, end: 120.0 }]);
+        // Apart, then joined once they meet; the last chapter runs to the end
+        assert_eq!(
+            regions(&previous, &[window(190.0, 200.0), window(70.0, 80.0)]).unwrap(),
+            [Region { start: 60.0, end: 120.0 }, Region { start: 180.0, end: f64::INFINITY }]
+        );
+        assert_eq!(
+            regions(&previous, &[window(190.0, 200.0), window(130.0, 140.0), window(70.0, 80.0)]).unwrap(),
+            [Region { start: 60.0, end: f64::INFINITY }]
+        );
+        // Without chapters, lines are the boundaries
+        let lines_only = narration(&[], &["00:10", "01:10", "02:10"]);
+        assert_eq!(regions(&lines_only, &[window(30.0, 40.0)]).unwrap(), [Region { start: 10.0, end: 70.0 }]);
+
+        assert!(regions(&previous, &[]).is_err());
+        assert!(regions(&previous, &[window(80.0, 70.0)]).is_err());
+    }
+
+    #[test]
+    fn test_rewrites_are_spliced_in_place() {
+        let previous = narration(&["00:00", "01:00", "02:00"], &["00:10", "01:10", "01:40", "02:10"]);
+        let regions = [Region { start: 60.0, end: 120.0 }];
+        let rewrite = Rewrite {
+            chapters: vec![Chapter { time_code: "01:05".into(), title: "Redone".into(), description: None }],
+            segments: ["01:30", "01:15", "02:30"]
+                .iter()
+                .map(|time_code| ScriptSegment { time_code: time_code.to_string(), ..Default::default() })
+                .collect(),
+        };
+
+        let spliced = splice(&previous, &regions, vec![rewrite]).unwrap();
+        let titles: Vec<&str> = spliced.chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Chapter at 00:00", "Redone", "Chapter at 02:00"]);
+        let time_codes: Vec<&str> = spliced.segments.iter().map(|s| s.time_code.as_str()).collect();
+        assert_eq!(time_codes, ["00:10", "01:15", "01:30", "02:10"]);
+        assert_eq!(spliced.rewritten, [false, true, true, false]);
+        assert_eq!(spliced.segments[3].narration, "Line at 02:10");
+        assert_eq!(spliced.dropped, ["Dropped script segment at 02:30: outside 01:00 to 02:00"]);
+
+        // A chapter already out of order stays out of order
+        let mut unordered = previous.clone();
+        unordered.chapters[0].time_code = "02:30".into();
+        let err = splice(&unordered, &regions, vec![Rewrite::default()]).unwrap_err();
+        assert!(err.contains("out of order"), "{}", err);
+    }
+}
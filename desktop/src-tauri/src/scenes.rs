@@ -0,0 +1,254 @@
+//! Scene Descriptions
+//!
+//! Describes what the camera sees at many events with few requests: frames
+//! are packed several to a request, and the model answers with a JSON array
+//! keyed by each image's position. Batches stay under the per-request image
+//! count and inline data limits, and a batch whose answer is cut off at the
+//! output token limit is split in two and asked again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use tracing::{debug, info, warn};
+
+use crate::gemini::GeminiError;
+use crate::llm::{ImagePart, LlmBackend, MAX_INLINE_IMAGE_BYTES};
+use crate::narrative::extract_json;
+
+/// Most frames sent in one request
+///
+/// Far below the API's own cap, so that a batch's descriptions fit in the
+/// output token limit.
+pub const MAX_IMAGES_PER_REQUEST: usize = 16;
+
+/// What the camera sees at one event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SceneDescription {
+    pub event_id: String,
+    pub description: String,
+}
+
+/// Requests and tokens spent on a set of scene descriptions
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SceneUsage {
+    pub requests: usize,
+    pub images: usize,
+    /// Sum over the requests that reported usage
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Descriptions in the order the frames were given
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SceneDescriptions {
+    pub descriptions: Vec<SceneDescription>,
+    /// Events the model gave no usable description for
+    pub missing: Vec<String>,
+    pub usage: SceneUsage,
+}
+
+/// Describe each event's frame, packing several frames into each request
+///
+/// `prompt_template` says what to describe; `{count}` in it is replaced by
+/// the number of images in the request. Instructions for the numbered JSON
+/// answer are appended. Failed requests fail the whole call, while answers
+/// that can't be parsed only leave their events `missing`.
+pub async fn describe_frames_batch(
+    backend: &dyn LlmBackend,
+    frames: Vec<(String, ImagePart)>,
+    prompt_template: &str,
+) -> Result<SceneDescriptions, GeminiError> {
+    let order: Vec<String> = frames.iter().map(|(event_id, _)| event_id.clone()).collect();
+    let mut batches: VecDeque<Vec<(String, ImagePart)>> =
+        batch_frames(frames, MAX_IMAGES_PER_REQUEST, MAX_INLINE_IMAGE_BYTES).into();
+    let mut described: HashMap<String, String> = HashMap::new();
+    let mut usage = SceneUsage::default();
+
+    while let Some(mut batch) = batches.pop_front() {
+        let prompt = batch_prompt(prompt_template, batch.len());
+        let images: Vec<ImagePart> = batch.iter().map(|(_, image)| image.clone()).collect();
+        debug!("Describing {} frame(s) in one request", images.len());
+
+        let generation = backend
+            .generate_multimodal(&prompt, images, Some(descriptions_schema()), true)
+            .await?;
+        usage.requests += 1;
+        usage.images += batch.len();
+        if let Some(tokens) = generation.usage {
+            usage.prompt_tokens += tokens.prompt_tokens;
+            usage.output_tokens += tokens.output_tokens;
+        }
+
+        if generation.is_truncated() && batch.len() > 1 {
+            warn!("Scene descriptions for {} frames were cut off, asking again in halves", batch.len());
+            let second = batch.split_off(batch.len() / 2);
+            batches.push_front(second);
+            batches.push_front(batch);
+            continue;
+        }
+
+        match parse_descriptions(&generation.text) {
+            Ok(by_index) => {
+                for (index, description) in by_index {
+                    // Indexes the model made up are dropped
+                    if let Some((event_id, _)) = batch.get(index) {
+                        described.insert(event_id.clone(), description);
+                    }
+                }
+            }
+            Err(e) => warn!("Couldn't parse scene descriptions for {} frame(s): {}", batch.len(), e),
+        }
+    }
+
+    let (mut descriptions, mut missing) = (Vec::new(), Vec::new());
+    for event_id in order {
+        match described.remove(&event_id) {
+            Some(description) => descriptions.push(SceneDescription { event_id, description }),
+            None => missing.push(event_id),
+        }
+    }
+
+    info!(
+        "Described {} scene(s) in {} request(s), {} missing",
+        descriptions.len(),
+        usage.requests,
+        missing.len()
+    );
+    Ok(SceneDescriptions { descriptions, missing, usage })
+}
+
+/// Split frames into batches of at most `max_images`, and `max_bytes` of image data
+fn batch_frames(frames: Vec<(String, ImagePart)>, max_images: usize, max_bytes: usize) -> Vec<Vec<(String, ImagePart)>> {
+    let mut batches: Vec<Vec<(String, ImagePart)>> = Vec::new();
+    let mut bytes = 0;
+
+    for frame in frames {
+        // Base64 length is within a few bytes of 4/3 of the decoded size
+        let size = frame.1.data.len() / 4 * 3;
+        match batches.last_mut() {
+            Some(batch) if batch.len() < max_images && bytes + size <= max_bytes => {
+                bytes += size;
+                batch.push(frame);
+            }
+            _ => {
+                bytes = size;
+                batches.push(vec![frame]);
+            }
+        }
+    }
+    batches
+}
+
+fn batch_prompt(template: &str, count: usize) -> String {
+    format!(
+        "{}\n\nThere are {} images, numbered from 0 in the order given. Return a JSON array \
+         with one object per image: {{\"index\": <image number>, \"description\": \"...\"}}.",
+        template.replace("{count}", &count.to_string()),
+        count
+    )
+}
+
+/// Response schema for the numbered descriptions
+fn descriptions_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "ARRAY",
+        "items": {
+            "type": "OBJECT",
+            "properties": {
+                "index": { "type": "INTEGER" },
+                "description": { "type": "STRING" }
+            },
+            "required": ["index", "description"]
+        }
+    })
+}
+
+#[derive(Deserialize)]
+struct NumberedDescription {
+    index: usize,
+    description: String,
+}
+
+/// Descriptions by image index; blank ones are left out
+fn parse_descriptions(text: &str) -> Result<HashMap<usize, String>, serde_json::Error> {
+    let json = extract_json(text).unwrap_or_else(|| text.trim().to_string());
+    let parsed: Vec<NumberedDescription> = serde_json::from_str(&json)?;
+    Ok(parsed
+        .into_iter()
+        .filter(|d| !d.description.trim().is_empty())
+        .map(|d| (d.index, d.description.trim().to_string()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gemini::mock::MockGemini;
+    use crate::llm::{Generation, TokenUsage};
+
+    fn frames(count: usize) -> Vec<(String, ImagePart)> {
+        (0..count)
+            .map(|i| (format!("event-{}", i), ImagePart { mime_type: "image/jpeg", data: "/9j/4A==".to_string(), label: None }))
+            .collect()
+    }
+
+    fn answer(indexes: std::ops::Range<usize>) -> String {
+        let items: Vec<_> = indexes
+            .map(|i| serde_json::json!({ "index": i, "description": format!("Scene {}", i) }))
+            .collect();
+        serde_json::to_string(&items).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_frames_are_batched_and_mapped_back() {
+        let first = Generation {
+            usage: Some(TokenUsage { prompt_tokens: 4000, output_tokens: 300 }),
+            ..Generation::complete(answer(0..MAX_IMAGES_PER_REQUEST))
+        };
+        let mock = MockGemini::new().with_generation(first).with_text(&answer(0..4));
+
+        let result = describe_frames_batch(&mock, frames(MAX_IMAGES_PER_REQUEST + 4), "Describe the {count} frames")
+            .await
+            .unwrap();
+
+        let sizes: Vec<usize> = mock.images().iter().map(Vec::len).collect();
+        assert_eq!(sizes, [MAX_IMAGES_PER_REQUEST, 4]);
+        assert!(mock.prompts()[1].starts_with("Describe the 4 frames"));
+
+        // Indexes restart in each request; the second batch's 0 is event 16
+        assert_eq!(result.descriptions.len(), MAX_IMAGES_PER_REQUEST + 4);
+        assert_eq!(result.descriptions[16], SceneDescription { event_id: "event-16".into(), description: "Scene 0".into() });
+        assert!(result.missing.is_empty());
+        assert_eq!(
+            result.usage,
+            SceneUsage { requests: 2, images: MAX_IMAGES_PER_REQUEST + 4, prompt_tokens: 4000, output_tokens: 300 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_batch_is_split() {
+        let mock = MockGemini::new()
+            .with_truncated(r#"[{"index": 0, "description": "A long"#)
+            .with_text(&answer(0..2))
+            // Missing index 1, and one the batch doesn't have
+            .with_text(r#"[{"index": 0, "description": "Harbour"}, {"index": 7, "description": "?"}]"#);
+
+        let result = describe_frames_batch(&mock, frames(4), "Describe").await.unwrap();
+
+        let sizes: Vec<usize> = mock.images().iter().map(Vec::len).collect();
+        assert_eq!(sizes, [4, 2, 2]);
+        assert_eq!(result.descriptions.len(), 3);
+        assert_eq!(result.descriptions[2].description, "Harbour");
+        assert_eq!(result.missing, ["event-3"]);
+        assert_eq!(result.usage.requests, 3);
+    }
+
+    #[test]
+    fn test_batches_respect_payload_limit() {
+        let large = ImagePart { mime_type: "image/jpeg", data: "A".repeat(8_000), label: None };
+        let frames: Vec<_> = (0..5).map(|i| (i.to_string(), large.clone())).collect();
+
+        // Two 6000 byte frames fit in 15000 bytes, a third doesn't
+        let sizes: Vec<usize> = batch_frames(frames, 16, 15_000).iter().map(Vec::len).collect();
+        assert_eq!(sizes, [2, 2, 1]);
+    }
+}
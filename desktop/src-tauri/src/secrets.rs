@@ -0,0 +1,301 @@
+//! Secret Storage
+//!
+//! Keeps the Gemini API key in the OS keychain (Keychain, Credential
+//! Manager, Secret Service). Where no keychain is available the key is
+//! stored AES-GCM encrypted under the app data directory instead. The
+//! encryption key sits next to it with owner-only permissions, so the file
+//! fallback keeps the key out of backups and casual reads of the data
+//! directory, not away from someone with access to the user account.
+//!
+//! The `GEMINI_API_KEY` environment variable still works when nothing is stored.
+//!
+//! The key exported Truth Bundles are signed with is generated on first use
+//! and kept the same way as the file encryption key.
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+const KEYRING_SERVICE: &str = "com.geotruth.app";
+const GEMINI_ACCOUNT: &str = "gemini-api-key";
+
+const ENCRYPTED_KEY_FILE: &str = "gemini_api_key.enc";
+const ENCRYPTION_KEY_FILE: &str = "secrets.key";
+const SIGNING_KEY_FILE: &str = "bundle_signing.key";
+
+/// Length of the AES-GCM nonce prefixed to the encrypted key
+const NONCE_LEN: usize = 12;
+
+/// Where the Gemini API key comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Keychain,
+    EncryptedFile,
+    Environment,
+}
+
+/// Stored key, looked up once and kept in memory so the keychain isn't
+/// queried (and possibly prompting) on every request
+static GEMINI_KEY: Lazy<RwLock<Option<(String, KeySource)>>> = Lazy::new(|| RwLock::new(load_gemini_api_key()));
+
+/// The Gemini API key and where it came from, if one is configured
+pub fn gemini_api_key() -> Option<(String, KeySource)> {
+    GEMINI_KEY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Store the Gemini API key, in the keychain when possible
+pub fn store_gemini_api_key(key: &str) -> Result<KeySource, String> {
+    let source = match keyring_entry().and_then(|entry| entry.set_password(key)) {
+        Ok(()) => {
+            // Don't leave an older copy behind in the file
+            remove_encrypted_key(&secrets_dir());
+            KeySource::Keychain
+        }
+        Err(e) => {
+            warn!("OS keychain unavailable ({}), storing the API key in an encrypted file", e);
+            write_encrypted_key(&secrets_dir(), key)?;
+            KeySource::EncryptedFile
+        }
+    };
+
+    *GEMINI_KEY.write().unwrap_or_else(|e| e.into_inner()) = Some((key.to_string(), source));
+    info!("Gemini API key stored ({:?}, {})", source, mask_key(key));
+    Ok(source)
+}
+
+/// Remove the stored Gemini API key from the keychain and the file fallback
+///
+/// A key set through the environment still applies afterwards.
+pub fn clear_gemini_api_key() {
+    if let Ok(entry) = keyring_entry() {
+        match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => warn!("Failed to remove the API key from the keychain: {}", e),
+        }
+    }
+    remove_encrypted_key(&secrets_dir());
+
+    *GEMINI_KEY.write().unwrap_or_else(|e| e.into_inner()) = env_key();
+    info!("Stored Gemini API key cleared");
+}
+
+/// `••••abcd`: enough to recognize a key without revealing it
+pub fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    // Short keys would be mostly revealed by their tail
+    if chars.len() <= 8 {
+        return "••••".to_string();
+    }
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("••••{}", tail)
+}
+
+/// Replace any occurrence of the configured key in `text`, and any key
+/// passed in a URL's `key=` parameter
+///
+/// Only a key already loaded is looked for. A key never loaded was never
+/// sent anywhere, and this runs for every log line, where a keychain
+/// lookup (and its prompt) would be out of place.
+pub fn redact(text: &str) -> String {
+    let loaded = Lazy::get(&GEMINI_KEY).and_then(|key| key.read().unwrap_or_else(|e| e.into_inner()).clone());
+    let text = match loaded {
+        Some((key, _)) if !key.is_empty() => redact_key(text, &key),
+        _ => text.to_string(),
+    };
+    redact_url_keys(&text)
+}
+
+/// Mask the value of every `key=` URL parameter in `text`, whichever key it is
+fn redact_url_keys(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("key=") {
+        let is_parameter = start > 0 && matches!(rest.as_bytes()[start - 1], b'?' | b'&');
+        let (before, after) = rest.split_at(start + "key=".len());
+        let end = after
+            .find(|c: char| matches!(c, '&' | '#' | '"' | '\'') || c.is_whitespace())
+            .unwrap_or(after.len());
+        let value = &after[..end];
+
+        redacted.push_str(before);
+        if is_parameter && !value.is_empty() && !value.starts_with("••••") {
+            redacted.push_str(&mask_key(value));
+        } else {
+            redacted.push_str(value);
+        }
+        rest = &after[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+fn redact_key(text: &str, key: &str) -> String {
+    text.replace(key, &mask_key(key))
+}
+
+fn load_gemini_api_key() -> Option<(String, KeySource)> {
+    match keyring_entry().and_then(|entry| entry.get_password()) {
+        Ok(key) => return Some((key, KeySource::Keychain)),
+        Err(keyring::Error::NoEntry) => {}
+        Err(e) => warn!("Couldn't read the OS keychain: {}", e),
+    }
+
+    match read_encrypted_key(&secrets_dir()) {
+        Ok(Some(key)) => return Some((key, KeySource::EncryptedFile)),
+        Ok(None) => {}
+        Err(e) => warn!("Couldn't read the stored API key: {}", e),
+    }
+
+    env_key()
+}
+
+fn env_key() -> Option<(String, KeySource)> {
+    std::env::var("GEMINI_API_KEY")
+        .ok()
+        .filter(|k| !k.trim().is_empty())
+        .map(|k| (k, KeySource::Environment))
+}
+
+fn keyring_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, GEMINI_ACCOUNT)
+}
+
+fn secrets_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("com.geotruth.app")
+}
+
+fn write_encrypted_key(dir: &Path, key: &str) -> Result<(), String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let cipher = Aes256Gcm::new(&encryption_key(dir)?);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, key.as_bytes())
+        .map_err(|_| "Failed to encrypt the API key".to_string())?;
+
+    let mut data = nonce.to_vec();
+    data.extend_from_slice(&ciphertext);
+    write_private(&dir.join(ENCRYPTED_KEY_FILE), general_purpose::STANDARD.encode(data).as_bytes())
+}
+
+fn read_encrypted_key(dir: &Path) -> Result<Option<String>, String> {
+    let path = dir.join(ENCRYPTED_KEY_FILE);
+    if !path.exists() || !dir.join(ENCRYPTION_KEY_FILE).exists() {
+        return Ok(None);
+    }
+
+    let encoded = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let data = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("Corrupt key file: {}", e))?;
+    if data.len() <= NONCE_LEN {
+        return Err("Corrupt key file: too short".to_string());
+    }
+
+    let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(&encryption_key(dir)?);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Key file can't be decrypted; set the API key again".to_string())?;
+
+    String::from_utf8(plaintext).map(Some).map_err(|e| e.to_string())
+}
+
+fn remove_encrypted_key(dir: &Path) {
+    std::fs::remove_file(dir.join(ENCRYPTED_KEY_FILE)).ok();
+}
+
+/// Load the file encryption key, creating it on first use
+fn encryption_key(dir: &Path) -> Result<Key<Aes256Gcm>, String> {
+    local_key(&dir.join(ENCRYPTION_KEY_FILE))
+}
+
+/// The key this installation signs exported Truth Bundles with, created on first use
+pub fn signing_key() -> Result<[u8; 32], String> {
+    let dir = secrets_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let key = local_key(&dir.join(SIGNING_KEY_FILE))?;
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&key);
+    Ok(bytes)
+}
+
+/// Load a random 256-bit key kept in a file, creating it if it's missing or malformed
+fn local_key(path: &Path) -> Result<Key<Aes256Gcm>, String> {
+    if let Ok(bytes) = std::fs::read(path) {
+        if bytes.len() == 32 {
+            return Ok(*Key::<Aes256Gcm>::from_slice(&bytes));
+        }
+        warn!("Ignoring malformed {:?}", path);
+    }
+
+    let key = Aes256Gcm::generate_key(&mut OsRng);
+    write_private(path, key.as_slice())?;
+    Ok(key)
+}
+
+/// Write a file only the current user can read
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    use std::io::Write;
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents))
+        .map_err(|e| format!("Failed to write {:?}: {}", path.file_name().unwrap_or_default(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_encrypted_key_file_round_trip() {
+        let dir = fixtures::temp_dir("secrets");
+        assert_eq!(read_encrypted_key(&dir).unwrap(), None);
+
+        write_encrypted_key(&dir, "AIzaSyExampleKey1234").unwrap();
+        let stored = std::fs::read_to_string(dir.join(ENCRYPTED_KEY_FILE)).unwrap();
+        assert!(!stored.contains("AIzaSyExampleKey1234"));
+        assert_eq!(read_encrypted_key(&dir).unwrap().as_deref(), Some("AIzaSyExampleKey1234"));
+
+        // A different encryption key can't read it
+        std::fs::write(dir.join(ENCRYPTION_KEY_FILE), [7u8; 32]).unwrap();
+        assert!(read_encrypted_key(&dir).is_err());
+
+        remove_encrypted_key(&dir);
+        assert_eq!(read_encrypted_key(&dir).unwrap(), None);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_mask_and_redact() {
+        assert_eq!(mask_key("AIzaSyExampleKey1234"), "••••1234");
+        assert_eq!(mask_key("short"), "••••");
+        assert_eq!(
+            redact_key("GET /models?key=AIzaSyExampleKey1234 failed", "AIzaSyExampleKey1234"),
+            "GET /models?key=••••1234 failed"
+        );
+        assert_eq!(
+            redact_url_keys("POST https://host/v1beta/models/m:generateContent?alt=json&key=AIzaSyOtherKey98765 (timeout)"),
+            "POST https://host/v1beta/models/m:generateContent?alt=json&key=••••8765 (timeout)"
+        );
+        // Already masked, or not a URL parameter
+        assert_eq!(redact_url_keys("?key=••••1234"), "?key=••••1234");
+        assert_eq!(redact_url_keys("The cache key=abc123"), "The cache key=abc123");
+    }
+}
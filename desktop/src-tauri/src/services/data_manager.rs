@@ -26,10 +26,11 @@ pub enum DataError {
 }
 
 /// Connectivity mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ConnectivityMode {
     Online,
     Offline,
+    #[default]
     Hybrid, // Use offline data when available, fallback to online
 }
 
@@ -3,15 +3,28 @@
 //!
 //! Embedded database for local project storage in the desktop app.
 
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use duckdb::{Connection, params};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use tokio::sync::Mutex;
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+
+use crate::services::whisper::TranscriptionSegment;
+
+/// Tables holding data derived from map regions, keyed by `region_id`
+pub const REGION_TABLES: &[&str] = &["pois", "boundaries"];
+
+/// `region_id` of POIs looked up online rather than taken from a region
+pub const ONLINE_POI_REGION: &str = "online";
+
+/// Attempts to open a database another process holds, in case it's just exiting
+const LOCK_RETRY_ATTEMPTS: u32 = 4;
+const LOCK_RETRY_INITIAL_MS: u64 = 250;
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
@@ -20,6 +33,10 @@ pub enum DatabaseError {
     
     #[error("Database not initialized")]
     NotInitialized,
+
+    /// DuckDB allows a single writer; another app instance has the file open
+    #[error("Database is in use by another GeoTruth instance")]
+    Locked,
     
     #[error("Record not found")]
     NotFound,
@@ -37,6 +54,10 @@ pub struct Project {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub video_count: u32,
+    /// Image shown for the project, captured from one of its videos
+    pub cover_image_path: Option<String>,
+    /// Camera direction relative to travel, for videos without their own
+    pub camera_heading_offset_deg: Option<f64>,
 }
 
 /// Video record
@@ -52,6 +73,12 @@ pub struct Video {
     pub codec: Option<String>,
     pub file_size_bytes: Option<i64>,
     pub file_path: String,
+    /// Camera direction relative to travel, clockwise (`None` = the project's)
+    pub camera_heading_offset_deg: Option<f64>,
+    /// Audio extracted for transcription and kept to process it again
+    /// without extracting it again; not carried over by project bundles
+    #[serde(default)]
+    pub audio_path: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -85,7 +112,194 @@ pub struct Event {
     pub created_at: DateTime<Utc>,
 }
 
+/// A generated narration, kept as history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Narration {
+    pub id: String,
+    pub project_id: Option<String>,
+    pub video_id: Option<String>,
+    /// Gemini model that wrote it
+    pub model: String,
+    /// The `NarrateResponse` as JSON
+    pub response_json: String,
+    /// The `NarrationOptions` it was written with, as JSON, to regenerate it
+    /// the same way; `None` for narrations from before options were kept
+    pub options_json: Option<String>,
+    /// Shared by the variants generated together, to tell them apart from
+    /// other narrations of the video
+    pub generation_group: Option<String>,
+    /// Hashes of the Truth Bundle it was written from, as JSON, to tell
+    /// when it's gone stale; `None` for narrations from before they were kept
+    #[serde(default)]
+    pub bundle_fingerprint: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// How far a video has got through processing (transcription and sync)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    Pending,
+    /// Waiting its turn in the processing queue
+    Queued,
+    Processing,
+    Complete,
+    Failed,
+}
+
+impl ProcessingStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Queued => "queued",
+            Self::Processing => "processing",
+            Self::Complete => "complete",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "queued" => Self::Queued,
+            "processing" => Self::Processing,
+            "complete" => Self::Complete,
+            "failed" => Self::Failed,
+            _ => Self::Pending,
+        }
+    }
+}
+
+/// Processing state of a video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoStatus {
+    pub video_id: String,
+    pub status: ProcessingStatus,
+    /// Last time processing completed
+    pub processed_at: Option<DateTime<Utc>>,
+    /// Why the last run failed
+    pub error: Option<String>,
+    /// The options the last run went by, as JSON
+    #[serde(default)]
+    pub options_json: Option<String>,
+    /// Stages whose output is kept, for a run to pick up after, in the
+    /// order they finished
+    #[serde(default)]
+    pub completed_stages: Vec<String>,
+    /// Fingerprints of what the last finished stage was made from, as JSON
+    #[serde(default)]
+    pub inputs_json: Option<String>,
+}
+
+/// New location for a stored event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventLocation {
+    pub event_id: String,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub heading_deg: Option<f64>,
+}
+
+/// POI row from a region's derived data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PoiRecord {
+    pub id: String,
+    pub name: String,
+    pub category: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// OSM `wikipedia` tag, where the region's data kept it
+    #[serde(default)]
+    pub wikipedia: Option<String>,
+    /// OSM `wikidata` tag, where the region's data kept it
+    #[serde(default)]
+    pub wikidata: Option<String>,
+}
+
+/// A user's correction to an event's enrichment
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventOverride {
+    pub event_id: String,
+    /// Context field, e.g. `city`, or `poi` for a pinned POI
+    pub field: String,
+    /// The value, or the pinned POI's id
+    pub value: String,
+}
+
+/// Administrative area from a region's `boundaries` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminArea {
+    pub name: String,
+    /// OSM admin level: 2 for countries, 4 for states, 6 for counties, 8 for towns
+    pub admin_level: i32,
+    pub population: Option<i64>,
+    /// Size of the bounding box in square degrees, to tell overlapping areas apart
+    pub extent: f64,
+    /// IANA time zone, e.g. `Europe/Monaco`, where the boundary is tagged with one
+    pub timezone: Option<String>,
+}
+
+/// Size of the database file and what's in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DbStats {
+    pub path: String,
+    /// Database file plus its write-ahead log
+    pub size_bytes: u64,
+    pub wal_bytes: u64,
+    /// Row count per table
+    pub table_counts: BTreeMap<String, u64>,
+}
+
+/// Database size before and after compaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompactResult {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+}
+
+/// A usage metric's running totals, see `usage_metrics`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricRow {
+    pub name: String,
+    /// What was counted within the metric, e.g. a Whisper model or a
+    /// histogram bucket; empty when it's counted as a whole
+    pub label: String,
+    pub count: u64,
+    pub sum: f64,
+    pub first_recorded: DateTime<Utc>,
+    pub last_recorded: DateTime<Utc>,
+}
+
+/// Stored transcript segment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptionRow {
+    pub id: String,
+    pub video_id: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+    pub language: Option<String>,
+}
+
+/// Every row belonging to a project, to move it between databases
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectSnapshot {
+    pub project: Project,
+    pub videos: Vec<Video>,
+    pub gps_points: Vec<GpsPoint>,
+    pub events: Vec<Event>,
+    pub transcriptions: Vec<TranscriptionRow>,
+    /// Narrations of the project or of any of its videos
+    pub narrations: Vec<Narration>,
+    pub video_statuses: Vec<VideoStatus>,
+}
+
+/// Tables whose rows are keyed by a `VARCHAR` id, for [`LocalDatabase::ids_in_use`]
+pub const ID_TABLES: &[&str] = &["projects", "videos", "events", "transcriptions", "narrations"];
+
 /// Local DuckDB database manager
+///
+/// Clones share the same connection.
+#[derive(Clone)]
 pub struct LocalDatabase {
     conn: Arc<Mutex<Connection>>,
     path: PathBuf,
@@ -93,6 +307,9 @@ pub struct LocalDatabase {
 
 impl LocalDatabase {
     /// Open or create database at path
+    ///
+    /// Retries with backoff while another process holds the file, then
+    /// gives up with [`DatabaseError::Locked`].
     pub fn open(path: PathBuf) -> Result<Self, DatabaseError> {
         info!("Opening local database: {:?}", path);
         
@@ -101,7 +318,23 @@ impl LocalDatabase {
             std::fs::create_dir_all(parent).ok();
         }
         
-        let conn = Connection::open(&path)?;
+        let mut attempt = 1;
+        let conn = loop {
+            match Connection::open(&path) {
+                Ok(conn) => break conn,
+                Err(e) if is_lock_error(&e.to_string()) => {
+                    if attempt >= LOCK_RETRY_ATTEMPTS {
+                        warn!("Database still locked after {} attempts: {}", attempt, e);
+                        return Err(DatabaseError::Locked);
+                    }
+                    let delay = LOCK_RETRY_INITIAL_MS << (attempt - 1);
+                    warn!("Database is locked, retrying in {}ms", delay);
+                    std::thread::sleep(std::time::Duration::from_millis(delay));
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
         
         let db = Self {
             conn: Arc::new(Mutex::new(conn)),
@@ -182,6 +415,99 @@ impl LocalDatabase {
                 language VARCHAR
             );
             
+            -- Generated narrations
+            CREATE TABLE IF NOT EXISTS narrations (
+                id VARCHAR PRIMARY KEY,
+                project_id VARCHAR,
+                video_id VARCHAR,
+                model VARCHAR NOT NULL,
+                response_json VARCHAR NOT NULL,
+                created_at TIMESTAMP DEFAULT current_timestamp
+            );
+            
+            -- Processing state per video
+            CREATE TABLE IF NOT EXISTS video_status (
+                video_id VARCHAR PRIMARY KEY,
+                status VARCHAR NOT NULL,
+                processed_at TIMESTAMP,
+                error VARCHAR,
+                updated_at TIMESTAMP DEFAULT current_timestamp
+            );
+            
+            -- Videos waiting or running in the processing queue, in order
+            CREATE TABLE IF NOT EXISTS processing_queue (
+                video_id VARCHAR PRIMARY KEY,
+                position INTEGER NOT NULL,
+                entry_json VARCHAR NOT NULL
+            );
+            
+            -- Gemini responses keyed by a hash of the request
+            CREATE TABLE IF NOT EXISTS llm_cache (
+                key VARCHAR PRIMARY KEY,
+                model VARCHAR NOT NULL,
+                response VARCHAR NOT NULL,
+                bytes BIGINT NOT NULL,
+                created_at TIMESTAMP DEFAULT current_timestamp
+            );
+            
+            -- Enrichments keyed by coordinate rounded to about 10 m
+            CREATE TABLE IF NOT EXISTS enrichment_cache (
+                key VARCHAR PRIMARY KEY,
+                response VARCHAR NOT NULL,
+                created_at TIMESTAMP DEFAULT current_timestamp,
+                -- NULL for enrichments that don't go stale
+                expires_at TIMESTAMP
+            );
+            
+            -- Past weather by location rounded to 0.1° and UTC hour; it doesn't change
+            CREATE TABLE IF NOT EXISTS weather_cache (
+                key VARCHAR PRIMARY KEY,
+                observation VARCHAR NOT NULL,
+                created_at TIMESTAMP DEFAULT current_timestamp
+            );
+            
+            -- Article summaries by article and language asked for
+            CREATE TABLE IF NOT EXISTS article_summaries (
+                key VARCHAR PRIMARY KEY,
+                summary VARCHAR NOT NULL,
+                retrieved_at TIMESTAMP DEFAULT current_timestamp
+            );
+            
+            -- Finished background jobs, the most recent ones, as JSON records
+            CREATE TABLE IF NOT EXISTS jobs (
+                job_id VARCHAR PRIMARY KEY,
+                finished_at TIMESTAMP NOT NULL,
+                record_json VARCHAR NOT NULL
+            );
+            
+            -- Each video's latest Truth Bundle as JSON, processed or imported
+            CREATE TABLE IF NOT EXISTS truth_bundles (
+                video_id VARCHAR PRIMARY KEY,
+                bundle_json VARCHAR NOT NULL,
+                updated_at TIMESTAMP DEFAULT current_timestamp
+            );
+            
+            -- Users' corrections to events' enrichment, applied over it
+            CREATE TABLE IF NOT EXISTS event_overrides (
+                event_id VARCHAR NOT NULL,
+                field VARCHAR NOT NULL,
+                value VARCHAR NOT NULL,
+                created_at TIMESTAMP DEFAULT current_timestamp,
+                PRIMARY KEY (event_id, field, value)
+            );
+            
+            -- Usage counts, kept only when the user opts in; histograms
+            -- have a row per bucket, labelled with its bound
+            CREATE TABLE IF NOT EXISTS metrics (
+                name VARCHAR NOT NULL,
+                label VARCHAR NOT NULL,
+                count BIGINT NOT NULL,
+                sum DOUBLE NOT NULL,
+                first_recorded TIMESTAMP NOT NULL,
+                last_recorded TIMESTAMP NOT NULL,
+                PRIMARY KEY (name, label)
+            );
+            
             -- Create indexes
             CREATE INDEX IF NOT EXISTS idx_videos_project ON videos(project_id);
             CREATE INDEX IF NOT EXISTS idx_gps_video ON gps_points(video_id);
@@ -189,6 +515,20 @@ impl LocalDatabase {
             CREATE INDEX IF NOT EXISTS idx_events_video ON events(video_id);
             CREATE INDEX IF NOT EXISTS idx_events_time ON events(start_time_seconds);
             CREATE INDEX IF NOT EXISTS idx_transcriptions_video ON transcriptions(video_id);
+            CREATE INDEX IF NOT EXISTS idx_narrations_video ON narrations(video_id);
+
+            -- Columns added after the initial schema
+            ALTER TABLE projects ADD COLUMN IF NOT EXISTS cover_image_path VARCHAR;
+            ALTER TABLE projects ADD COLUMN IF NOT EXISTS camera_heading_offset_deg DOUBLE;
+            ALTER TABLE videos ADD COLUMN IF NOT EXISTS camera_heading_offset_deg DOUBLE;
+            ALTER TABLE videos ADD COLUMN IF NOT EXISTS audio_path VARCHAR;
+            ALTER TABLE narrations ADD COLUMN IF NOT EXISTS options_json VARCHAR;
+            ALTER TABLE narrations ADD COLUMN IF NOT EXISTS generation_group VARCHAR;
+            ALTER TABLE narrations ADD COLUMN IF NOT EXISTS bundle_fingerprint VARCHAR;
+            ALTER TABLE video_status ADD COLUMN IF NOT EXISTS options_json VARCHAR;
+            ALTER TABLE video_status ADD COLUMN IF NOT EXISTS completed_stages VARCHAR;
+            ALTER TABLE video_status ADD COLUMN IF NOT EXISTS events_json VARCHAR;
+            ALTER TABLE video_status ADD COLUMN IF NOT EXISTS inputs_json VARCHAR;
 
             -- Ensure default project exists
             INSERT INTO projects (id, name, description) 
@@ -224,6 +564,8 @@ impl LocalDatabase {
             created_at: now,
             updated_at: now,
             video_count: 0,
+            cover_image_path: None,
+            camera_heading_offset_deg: None,
         })
     }
     
@@ -232,10 +574,11 @@ impl LocalDatabase {
         let conn = self.conn.lock().await;
         let mut stmt = conn.prepare(
             "SELECT p.id, p.name, p.description, p.created_at, p.updated_at, 
-                    COUNT(v.id) as video_count
+                    COUNT(v.id) as video_count, p.cover_image_path, p.camera_heading_offset_deg
              FROM projects p
              LEFT JOIN videos v ON v.project_id = p.id
-             GROUP BY p.id, p.name, p.description, p.created_at, p.updated_at
+             GROUP BY p.id, p.name, p.description, p.created_at, p.updated_at, p.cover_image_path,
+                      p.camera_heading_offset_deg
              ORDER BY p.updated_at DESC"
         )?;
         
@@ -247,12 +590,54 @@ impl LocalDatabase {
                 created_at: Utc::now(), // Simplified for demo
                 updated_at: Utc::now(),
                 video_count: row.get::<_, i64>(5)? as u32,
+                cover_image_path: row.get(6)?,
+                camera_heading_offset_deg: row.get(7)?,
             })
         })?.filter_map(|r| r.ok()).collect();
         
         Ok(projects)
     }
     
+    /// Get a project's cover image path, failing if the project doesn't exist
+    pub async fn get_project_cover(&self, project_id: &str) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT cover_image_path FROM projects WHERE id = ?")?;
+        
+        let cover = stmt.query_map(params![project_id], |row| row.get::<_, Option<String>>(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        
+        cover.ok_or(DatabaseError::NotFound)
+    }
+    
+    /// Set a project's cover image path
+    pub async fn set_project_cover(&self, project_id: &str, path: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE projects SET cover_image_path = ?, updated_at = ? WHERE id = ?",
+            params![path, Utc::now().to_rfc3339(), project_id],
+        )?;
+        
+        if updated == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+    
+    /// Set the camera heading offset for a project's videos (`None` = facing forward)
+    pub async fn set_project_camera_offset(&self, project_id: &str, offset_deg: Option<f64>) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE projects SET camera_heading_offset_deg = ?, updated_at = ? WHERE id = ?",
+            params![offset_deg, Utc::now().to_rfc3339(), project_id],
+        )?;
+        
+        if updated == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+    
     // ==========================================================================
     // Videos
     // ==========================================================================
@@ -292,6 +677,8 @@ impl LocalDatabase {
             codec,
             file_size_bytes: size,
             file_path: file_path.to_string(),
+            camera_heading_offset_deg: None,
+            audio_path: None,
             created_at: now,
         })
     }
@@ -300,7 +687,8 @@ impl LocalDatabase {
     pub async fn get_project_videos(&self, project_id: &str) -> Result<Vec<Video>, DatabaseError> {
         let conn = self.conn.lock().await;
         let mut stmt = conn.prepare(
-            "SELECT id, project_id, filename, file_path, duration_seconds, fps, width, height, codec, file_size_bytes, created_at
+            "SELECT id, project_id, filename, file_path, duration_seconds, fps, width, height, codec, file_size_bytes,
+                    camera_heading_offset_deg, audio_path
              FROM videos WHERE project_id = ? ORDER BY created_at DESC"
         )?;
         
@@ -316,6 +704,8 @@ impl LocalDatabase {
                 height: row.get(7)?,
                 codec: row.get(8)?,
                 file_size_bytes: row.get(9)?,
+                camera_heading_offset_deg: row.get(10)?,
+                audio_path: row.get(11)?,
                 created_at: Utc::now(),
             })
         })?.filter_map(|r| r.ok()).collect();
@@ -323,19 +713,1557 @@ impl LocalDatabase {
         Ok(videos)
     }
     
-    /// Get database path
-    pub fn path(&self) -> &PathBuf {
-        &self.path
+    /// Get a single video by id
+    pub async fn get_video(&self, video_id: &str) -> Result<Video, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, filename, file_path, duration_seconds, fps, width, height, codec, file_size_bytes,
+                    camera_heading_offset_deg, audio_path
+             FROM videos WHERE id = ?"
+        )?;
+        
+        let video = stmt.query_map(params![video_id], |row| {
+            Ok(Video {
+                id: row.get(0)?,
+                project_id: row.get(1)?,
+                filename: row.get(2)?,
+                file_path: row.get(3)?,
+                duration_seconds: row.get(4)?,
+                fps: row.get(5)?,
+                width: row.get(6)?,
+                height: row.get(7)?,
+                codec: row.get(8)?,
+                file_size_bytes: row.get(9)?,
+                camera_heading_offset_deg: row.get(10)?,
+                audio_path: row.get(11)?,
+                created_at: Utc::now(),
+            })
+        })?.filter_map(|r| r.ok()).next();
+        
+        video.ok_or(DatabaseError::NotFound)
     }
-}
+    
+    /// Set a video's camera heading offset (`None` = use the project's)
+    pub async fn set_video_camera_offset(&self, video_id: &str, offset_deg: Option<f64>) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE videos SET camera_heading_offset_deg = ? WHERE id = ?",
+            params![offset_deg, video_id],
+        )?;
+        
+        if updated == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+    
+    /// Record where a video's file is now
+    pub async fn set_video_file_path(&self, video_id: &str, file_path: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE videos SET file_path = ? WHERE id = ?",
+            params![file_path, video_id],
+        )?;
+        
+        if updated == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+    
+    /// Record where a video's extracted audio is kept (`None` = it isn't)
+    pub async fn set_video_audio_path(&self, video_id: &str, audio_path: Option<&str>) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let updated = conn.execute(
+            "UPDATE videos SET audio_path = ? WHERE id = ?",
+            params![audio_path, video_id],
+        )?;
+        
+        if updated == 0 {
+            return Err(DatabaseError::NotFound);
+        }
+        Ok(())
+    }
+    
+    /// Whether any video's file or kept audio is at one of `paths`
+    pub async fn is_video_path(&self, paths: &[&str]) -> Result<bool, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT COUNT(*) FROM videos WHERE file_path = ? OR audio_path = ?")?;
+        for path in paths {
+            let count: i64 = stmt.query_row(params![path, path], |row| row.get(0))?;
+            if count > 0 {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+    
+    /// Camera heading offset that applies to a video: its own, else its
+    /// project's, else 0 (facing the direction of travel)
+    pub async fn camera_heading_offset(&self, video_id: &str) -> Result<f64, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT COALESCE(v.camera_heading_offset_deg, p.camera_heading_offset_deg, 0)
+             FROM videos v LEFT JOIN projects p ON p.id = v.project_id
+             WHERE v.id = ?"
+        )?;
+        
+        let offset = stmt.query_map(params![video_id], |row| row.get::<_, f64>(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        
+        offset.ok_or(DatabaseError::NotFound)
+    }
+    
+    // ==========================================================================
+    // GPS Points
+    // ==========================================================================
+    
+    /// Replace the stored GPS points of a video
+    pub async fn replace_gps_points(
+        &self,
+        video_id: &str,
+        points: &[crate::services::gps::GpsPoint],
+    ) -> Result<usize, DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        tx.execute("DELETE FROM gps_points WHERE video_id = ?", params![video_id])?;
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO gps_points (id, video_id, timestamp, lat, lon, elevation_m, speed_kmh, heading_deg)
+                 VALUES (nextval('gps_points_seq'), ?, make_timestamp(?), ?, ?, ?, ?, ?)"
+            )?;
+            for point in points {
+                stmt.execute(params![
+                    video_id,
+                    point.timestamp.timestamp_micros(),
+                    point.lat,
+                    point.lon,
+                    point.elevation_m,
+                    point.speed_kmh,
+                    point.heading_deg,
+                ])?;
+            }
+        }
+        
+        tx.commit()?;
+        
+        debug!("Stored {} GPS points for video {}", points.len(), video_id);
+        Ok(points.len())
+    }
+    
+    /// Get the stored GPS points of a video, ordered by time
+    pub async fn get_gps_points(&self, video_id: &str) -> Result<Vec<GpsPoint>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, video_id, epoch_ms(timestamp), lat, lon, elevation_m, speed_kmh, heading_deg
+             FROM gps_points WHERE video_id = ? ORDER BY timestamp"
+        )?;
+        
+        let points = stmt.query_map(params![video_id], |row| {
+            let millis: i64 = row.get(2)?;
+            Ok(GpsPoint {
+                id: row.get(0)?,
+                video_id: row.get(1)?,
+                timestamp: DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+                lat: row.get(3)?,
+                lon: row.get(4)?,
+                elevation_m: row.get(5)?,
+                speed_kmh: row.get(6)?,
+                heading_deg: row.get(7)?,
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        
+        Ok(points)
+    }
+    
+    /// Elevation of the recorded GPS point closest to a location, within the
+    /// `(min_lat, min_lon, max_lat, max_lon)` box around it
+    pub async fn recorded_elevation(
+        &self,
+        lat: f64,
+        lon: f64,
+        (min_lat, min_lon, max_lat, max_lon): (f64, f64, f64, f64),
+    ) -> Result<Option<f64>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT elevation_m FROM gps_points
+             WHERE elevation_m IS NOT NULL AND lat BETWEEN ? AND ? AND lon BETWEEN ? AND ?
+             ORDER BY (lat - ?) * (lat - ?) + (lon - ?) * (lon - ?)
+             LIMIT 1"
+        )?;
+        
+        let elevation = stmt
+            .query_map(params![min_lat, max_lat, min_lon, max_lon, lat, lat, lon, lon], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        Ok(elevation)
+    }
+    
+    // ==========================================================================
+    // Transcriptions
+    // ==========================================================================
 
-/// Video metadata for import
-#[derive(Debug, Clone)]
-pub struct VideoMetadata {
-    pub duration_seconds: Option<f64>,
-    pub fps: Option<f64>,
-    pub width: Option<u32>,
-    pub height: Option<u32>,
-    pub codec: Option<String>,
-    pub file_size_bytes: Option<i64>,
+    /// Replace the stored transcript segments of a video
+    pub async fn replace_transcription(
+        &self,
+        video_id: &str,
+        segments: &[TranscriptionSegment],
+        language: Option<&str>,
+    ) -> Result<usize, DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+
+        tx.execute("DELETE FROM transcriptions WHERE video_id = ?", params![video_id])?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO transcriptions (id, video_id, start_ms, end_ms, text, language) VALUES (?, ?, ?, ?, ?, ?)"
+            )?;
+            for segment in segments {
+                stmt.execute(params![
+                    Uuid::new_v4().to_string(),
+                    video_id,
+                    segment.start_ms,
+                    segment.end_ms,
+                    segment.text,
+                    language,
+                ])?;
+            }
+        }
+
+        tx.commit()?;
+
+        debug!("Stored {} transcript segments for video {}", segments.len(), video_id);
+        Ok(segments.len())
+    }
+
+    /// Get the stored transcript segments of a video, ordered by time
+    pub async fn get_transcription(&self, video_id: &str) -> Result<Vec<TranscriptionSegment>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT start_ms, end_ms, text FROM transcriptions WHERE video_id = ? ORDER BY start_ms"
+        )?;
+
+        let segments = stmt.query_map(params![video_id], |row| {
+            Ok(TranscriptionSegment {
+                start_ms: row.get(0)?,
+                end_ms: row.get(1)?,
+                text: row.get(2)?,
+            })
+        })?.filter_map(|r| r.ok()).collect();
+
+        Ok(segments)
+    }
+
+    /// Record a video's processing state
+    ///
+    /// `processed_at` is set when the status becomes complete and kept
+    /// otherwise, as are the options without `options_json`.
+    pub async fn set_video_status(
+        &self,
+        video_id: &str,
+        status: ProcessingStatus,
+        error: Option<&str>,
+        options_json: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let now = Utc::now().timestamp_micros();
+        let processed_at = (status == ProcessingStatus::Complete).then_some(now);
+        
+        conn.execute(
+            "INSERT INTO video_status (video_id, status, processed_at, error, updated_at, options_json)
+             VALUES (?, ?, make_timestamp(?), ?, make_timestamp(?), ?)
+             ON CONFLICT (video_id) DO UPDATE SET
+                status = excluded.status,
+                processed_at = coalesce(excluded.processed_at, video_status.processed_at),
+                error = excluded.error,
+                updated_at = excluded.updated_at,
+                options_json = coalesce(excluded.options_json, video_status.options_json)",
+            params![video_id, status.as_str(), processed_at, error, now, options_json],
+        )?;
+        
+        debug!("Video {} is now {}", video_id, status.as_str());
+        Ok(())
+    }
+    
+    /// A video's processing state, pending if it was never processed
+    pub async fn get_video_status(&self, video_id: &str) -> Result<VideoStatus, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT status, epoch_ms(processed_at), error, options_json, completed_stages, inputs_json
+             FROM video_status WHERE video_id = ?"
+        )?;
+        
+        let status = stmt.query_map(params![video_id], |row| {
+            let status: String = row.get(0)?;
+            let processed_at: Option<i64> = row.get(1)?;
+            let stages: Option<String> = row.get(4)?;
+            Ok(VideoStatus {
+                video_id: video_id.to_string(),
+                status: ProcessingStatus::parse(&status),
+                processed_at: processed_at.and_then(DateTime::from_timestamp_millis),
+                error: row.get(2)?,
+                options_json: row.get(3)?,
+                completed_stages: stages.as_deref().map(split_stages).unwrap_or_default(),
+                inputs_json: row.get(5)?,
+            })
+        })?.filter_map(|r| r.ok()).next();
+        
+        Ok(status.unwrap_or_else(|| VideoStatus {
+            video_id: video_id.to_string(),
+            status: ProcessingStatus::Pending,
+            processed_at: None,
+            error: None,
+            options_json: None,
+            completed_stages: Vec::new(),
+            inputs_json: None,
+        }))
+    }
+
+    /// Record that a stage of processing a video finished, going by the
+    /// options in `options_json` and made from the inputs in `inputs_json`
+    ///
+    /// `output_json` is kept as the stage's output when it has none of its
+    /// own table, e.g. the events of verification.
+    pub async fn complete_stage(
+        &self,
+        video_id: &str,
+        stage: &str,
+        options_json: &str,
+        inputs_json: &str,
+        output_json: Option<&str>,
+    ) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let stored: Option<String> = conn
+            .prepare("SELECT completed_stages FROM video_status WHERE video_id = ?")?
+            .query_map(params![video_id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .next()
+            .flatten();
+        let mut stages = stored.as_deref().map(split_stages).unwrap_or_default();
+        if !stages.iter().any(|s| s == stage) {
+            stages.push(stage.to_string());
+        }
+
+        conn.execute(
+            "INSERT INTO video_status (video_id, status, updated_at, options_json, inputs_json, completed_stages, events_json)
+             VALUES (?, ?, make_timestamp(?), ?, ?, ?, ?)
+             ON CONFLICT (video_id) DO UPDATE SET
+                updated_at = excluded.updated_at,
+                options_json = excluded.options_json,
+                inputs_json = excluded.inputs_json,
+                completed_stages = excluded.completed_stages,
+                events_json = coalesce(excluded.events_json, video_status.events_json)",
+            params![
+                video_id,
+                ProcessingStatus::Processing.as_str(),
+                Utc::now().timestamp_micros(),
+                options_json,
+                inputs_json,
+                stages.join(","),
+                output_json,
+            ],
+        )?;
+
+        debug!("Video {} finished stage {}", video_id, stage);
+        Ok(())
+    }
+
+    /// The events kept by the verification stage of a video, as JSON
+    pub async fn get_stage_events(&self, video_id: &str) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT events_json FROM video_status WHERE video_id = ?")?;
+        let events = stmt.query_map(params![video_id], |row| row.get(0))?.filter_map(|r| r.ok()).next();
+        Ok(events.flatten())
+    }
+
+    /// Forget the stages of a video earlier runs finished, so the next starts over
+    pub async fn clear_completed_stages(&self, video_id: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "UPDATE video_status SET completed_stages = NULL, events_json = NULL, inputs_json = NULL WHERE video_id = ?",
+            params![video_id],
+        )?;
+        Ok(())
+    }
+    
+    /// Keep the processing queue, as video ids and their queue entries as
+    /// JSON in the order they run, replacing what was kept before
+    pub async fn set_processing_queue(&self, entries: &[(String, String)]) -> Result<(), DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        tx.execute("DELETE FROM processing_queue", [])?;
+        {
+            let mut stmt = tx.prepare("INSERT INTO processing_queue (video_id, position, entry_json) VALUES (?, ?, ?)")?;
+            for (position, (video_id, json)) in entries.iter().enumerate() {
+                stmt.execute(params![video_id, position as i64, json])?;
+            }
+        }
+        
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// The kept processing queue's entries as JSON, in the order they run
+    pub async fn get_processing_queue(&self) -> Result<Vec<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT entry_json FROM processing_queue ORDER BY position")?;
+        let entries = stmt.query_map([], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+        Ok(entries)
+    }
+    
+    // ==========================================================================
+    // Jobs
+    // ==========================================================================
+    
+    /// Keep a finished job's record as JSON, then drop all but the `keep`
+    /// most recently finished
+    pub async fn add_job_record(
+        &self,
+        job_id: &str,
+        finished_at: DateTime<Utc>,
+        record_json: &str,
+        keep: usize,
+    ) -> Result<(), DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        tx.execute(
+            "INSERT INTO jobs (job_id, finished_at, record_json) VALUES (?, make_timestamp(?), ?)
+             ON CONFLICT (job_id) DO UPDATE SET
+                finished_at = excluded.finished_at,
+                record_json = excluded.record_json",
+            params![job_id, finished_at.timestamp_micros(), record_json],
+        )?;
+        tx.execute(
+            "DELETE FROM jobs WHERE job_id NOT IN (SELECT job_id FROM jobs ORDER BY finished_at DESC LIMIT ?)",
+            params![keep as i64],
+        )?;
+        
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// The kept job records as JSON, most recently finished first
+    pub async fn get_job_records(&self) -> Result<Vec<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT record_json FROM jobs ORDER BY finished_at DESC")?;
+        let records = stmt.query_map([], |row| row.get(0))?.filter_map(|r| r.ok()).collect();
+        Ok(records)
+    }
+    
+    // ==========================================================================
+    // Usage Metrics
+    // ==========================================================================
+    
+    /// Add one to a metric's count and `value` to its sum
+    pub async fn record_metric(&self, name: &str, label: &str, value: f64) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let now = Utc::now().timestamp_micros();
+        conn.execute(
+            "INSERT INTO metrics (name, label, count, sum, first_recorded, last_recorded)
+             VALUES (?, ?, 1, ?, make_timestamp(?), make_timestamp(?))
+             ON CONFLICT (name, label) DO UPDATE SET
+                count = metrics.count + 1,
+                sum = metrics.sum + excluded.sum,
+                last_recorded = excluded.last_recorded",
+            params![name, label, value, now, now],
+        )?;
+        Ok(())
+    }
+    
+    /// Every recorded metric, by name and label
+    pub async fn get_metrics(&self) -> Result<Vec<MetricRow>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT name, label, count, sum, epoch_ms(first_recorded), epoch_ms(last_recorded)
+             FROM metrics ORDER BY name, label"
+        )?;
+        
+        let rows = stmt.query_map([], |row| {
+            let first: i64 = row.get(4)?;
+            let last: i64 = row.get(5)?;
+            Ok(MetricRow {
+                name: row.get(0)?,
+                label: row.get(1)?,
+                count: row.get::<_, i64>(2)? as u64,
+                sum: row.get(3)?,
+                first_recorded: DateTime::from_timestamp_millis(first).unwrap_or_default(),
+                last_recorded: DateTime::from_timestamp_millis(last).unwrap_or_default(),
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        Ok(rows)
+    }
+    
+    /// Forget every recorded metric; returns how many rows there were
+    pub async fn clear_metrics(&self) -> Result<usize, DatabaseError> {
+        let conn = self.conn.lock().await;
+        Ok(conn.execute("DELETE FROM metrics", [])?)
+    }
+    
+    // ==========================================================================
+    // LLM Cache
+    // ==========================================================================
+    
+    /// A cached response, unless it's older than `max_age`
+    pub async fn llm_cache_get(&self, key: &str, max_age: Duration) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let cutoff = (Utc::now() - max_age).timestamp_micros();
+        let mut stmt = conn.prepare(
+            "SELECT response FROM llm_cache WHERE key = ? AND created_at >= make_timestamp(?)"
+        )?;
+        
+        let response = stmt
+            .query_map(params![key, cutoff], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        Ok(response)
+    }
+    
+    /// Store a response, replacing any entry under the same key
+    pub async fn llm_cache_put(&self, key: &str, model: &str, response: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO llm_cache (key, model, response, bytes, created_at)
+             VALUES (?, ?, ?, ?, make_timestamp(?))
+             ON CONFLICT (key) DO UPDATE SET
+                model = excluded.model,
+                response = excluded.response,
+                bytes = excluded.bytes,
+                created_at = excluded.created_at",
+            params![key, model, response, response.len() as i64, Utc::now().timestamp_micros()],
+        )?;
+        Ok(())
+    }
+    
+    /// Drop entries older than `max_age`, then the oldest ones until the
+    /// rest fit in `max_bytes`; returns how many were removed
+    pub async fn llm_cache_prune(&self, max_age: Duration, max_bytes: u64) -> Result<usize, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let cutoff = (Utc::now() - max_age).timestamp_micros();
+        
+        let expired = conn.execute(
+            "DELETE FROM llm_cache WHERE created_at < make_timestamp(?)",
+            params![cutoff],
+        )?;
+        let evicted = conn.execute(
+            "DELETE FROM llm_cache WHERE key IN (
+                SELECT key FROM (
+                    SELECT key, sum(bytes) OVER (ORDER BY created_at DESC, key) AS running
+                    FROM llm_cache
+                ) WHERE running > ?
+             )",
+            params![max_bytes as i64],
+        )?;
+        
+        if expired + evicted > 0 {
+            debug!("Pruned {} expired and {} excess LLM cache entries", expired, evicted);
+        }
+        Ok(expired + evicted)
+    }
+    
+    /// Number of cached responses and their total size in bytes
+    pub async fn llm_cache_stats(&self) -> Result<(u64, u64), DatabaseError> {
+        let conn = self.conn.lock().await;
+        let (entries, bytes): (i64, i64) = conn.query_row(
+            "SELECT count(*), coalesce(sum(bytes), 0) FROM llm_cache",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok((entries as u64, bytes as u64))
+    }
+    
+    /// Remove every cached response; returns how many there were
+    pub async fn llm_cache_clear(&self) -> Result<usize, DatabaseError> {
+        let conn = self.conn.lock().await;
+        Ok(conn.execute("DELETE FROM llm_cache", [])?)
+    }
+    
+    // ==========================================================================
+    // Enrichment Cache
+    // ==========================================================================
+    
+    /// A cached enrichment, as JSON, unless it has expired
+    pub async fn enrichment_cache_get(&self, key: &str) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT response FROM enrichment_cache
+             WHERE key = ? AND (expires_at IS NULL OR expires_at > make_timestamp(?))"
+        )?;
+        
+        let response = stmt
+            .query_map(params![key, Utc::now().timestamp_micros()], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        Ok(response)
+    }
+    
+    /// Store an enrichment, replacing any entry under the same key
+    pub async fn enrichment_cache_put(
+        &self,
+        key: &str,
+        response: &str,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO enrichment_cache (key, response, created_at, expires_at)
+             VALUES (?, ?, make_timestamp(?), make_timestamp(?))
+             ON CONFLICT (key) DO UPDATE SET
+                response = excluded.response,
+                created_at = excluded.created_at,
+                expires_at = excluded.expires_at",
+            params![key, response, Utc::now().timestamp_micros(), expires_at.map(|at| at.timestamp_micros())],
+        )?;
+        Ok(())
+    }
+    
+    /// Drop expired enrichments; returns how many were removed
+    pub async fn enrichment_cache_prune(&self) -> Result<usize, DatabaseError> {
+        let conn = self.conn.lock().await;
+        Ok(conn.execute(
+            "DELETE FROM enrichment_cache WHERE expires_at <= make_timestamp(?)",
+            params![Utc::now().timestamp_micros()],
+        )?)
+    }
+    
+    // ==========================================================================
+    // Weather Cache
+    // ==========================================================================
+    
+    /// A cached weather observation, as JSON
+    pub async fn weather_cache_get(&self, key: &str) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT observation FROM weather_cache WHERE key = ?")?;
+        
+        let observation = stmt
+            .query_map(params![key], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        Ok(observation)
+    }
+    
+    /// Store weather observations by key, replacing any under the same keys
+    pub async fn weather_cache_put(&self, entries: &[(String, String)]) -> Result<(), DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO weather_cache (key, observation, created_at)
+                 VALUES (?, ?, make_timestamp(?))
+                 ON CONFLICT (key) DO UPDATE SET
+                    observation = excluded.observation,
+                    created_at = excluded.created_at"
+            )?;
+            let now = Utc::now().timestamp_micros();
+            for (key, observation) in entries {
+                stmt.execute(params![key, observation, now])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+    
+    // ==========================================================================
+    // Article Summaries
+    // ==========================================================================
+    
+    /// A kept article summary, as JSON, unless it was retrieved more than `max_age` ago
+    pub async fn article_summary_get(&self, key: &str, max_age: Duration) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT summary FROM article_summaries WHERE key = ? AND retrieved_at > make_timestamp(?)"
+        )?;
+        
+        let oldest = (Utc::now() - max_age).timestamp_micros();
+        let summary = stmt
+            .query_map(params![key, oldest], |row| row.get::<_, String>(0))?
+            .filter_map(|r| r.ok())
+            .next();
+        Ok(summary)
+    }
+    
+    /// Keep an article summary, replacing any under the same key
+    pub async fn article_summary_put(&self, key: &str, summary: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO article_summaries (key, summary, retrieved_at)
+             VALUES (?, ?, make_timestamp(?))
+             ON CONFLICT (key) DO UPDATE SET
+                summary = excluded.summary,
+                retrieved_at = excluded.retrieved_at",
+            params![key, summary, Utc::now().timestamp_micros()],
+        )?;
+        Ok(())
+    }
+    
+    // ==========================================================================
+    // Truth Bundles
+    // ==========================================================================
+    
+    /// Keep a video's Truth Bundle as JSON, replacing the one kept before
+    pub async fn put_truth_bundle(&self, video_id: &str, bundle_json: &str) -> Result<(), DatabaseError> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO truth_bundles (video_id, bundle_json, updated_at) VALUES (?, ?, make_timestamp(?))
+             ON CONFLICT (video_id) DO UPDATE SET
+                bundle_json = excluded.bundle_json,
+                updated_at = excluded.updated_at",
+            params![video_id, bundle_json, Utc::now().timestamp_micros()],
+        )?;
+        Ok(())
+    }
+    
+    /// The Truth Bundle kept for a video, as JSON
+    pub async fn get_truth_bundle(&self, video_id: &str) -> Result<Option<String>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare("SELECT bundle_json FROM truth_bundles WHERE video_id = ?")?;
+        let bundle = stmt.query_map(params![video_id], |row| row.get(0))?.filter_map(|r| r.ok()).next();
+        Ok(bundle)
+    }
+    
+    // ==========================================================================
+    // Events
+    // ==========================================================================
+    
+    /// Get the stored events of a video, ordered by start time
+    pub async fn get_video_events(&self, video_id: &str) -> Result<Vec<Event>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, video_id, event_type, start_time_seconds, end_time_seconds, lat, lon, heading_deg,
+                    verified, verification_mode, truth_bundle_json
+             FROM events WHERE video_id = ? ORDER BY start_time_seconds"
+        )?;
+        
+        let events = stmt.query_map(params![video_id], event_from_row)?.filter_map(|r| r.ok()).collect();
+        
+        Ok(events)
+    }
+    
+    /// Get a stored event by its ID
+    pub async fn get_event(&self, event_id: &str) -> Result<Event, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, video_id, event_type, start_time_seconds, end_time_seconds, lat, lon, heading_deg,
+                    verified, verification_mode, truth_bundle_json
+             FROM events WHERE id = ?"
+        )?;
+        
+        let event = stmt.query_map(params![event_id], event_from_row)?.filter_map(|r| r.ok()).next();
+        event.ok_or(DatabaseError::NotFound)
+    }
+    
+    // ==========================================================================
+    // Narrations
+    // ==========================================================================
+    
+    /// Record a generated narration
+    #[allow(clippy::too_many_arguments)]
+    pub async fn add_narration(
+        &self,
+        project_id: Option<&str>,
+        video_id: Option<&str>,
+        model: &str,
+        response_json: &str,
+        options_json: Option<&str>,
+        generation_group: Option<&str>,
+        bundle_fingerprint: Option<&str>,
+    ) -> Result<Narration, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let id = Uuid::new_v4().to_string();
+        let now = Utc::now();
+        
+        conn.execute(
+            "INSERT INTO narrations (id, project_id, video_id, model, response_json, options_json, generation_group, bundle_fingerprint, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, make_timestamp(?))",
+            params![
+                id, project_id, video_id, model, response_json, options_json, generation_group, bundle_fingerprint,
+                now.timestamp_micros(),
+            ],
+        )?;
+        
+        debug!("Recorded narration {} ({})", id, model);
+        
+        Ok(Narration {
+            id,
+            project_id: project_id.map(str::to_string),
+            video_id: video_id.map(str::to_string),
+            model: model.to_string(),
+            response_json: response_json.to_string(),
+            options_json: options_json.map(str::to_string),
+            generation_group: generation_group.map(str::to_string),
+            bundle_fingerprint: bundle_fingerprint.map(str::to_string),
+            created_at: now,
+        })
+    }
+    
+    /// Narrations generated for a video, newest first
+    pub async fn get_narrations(&self, video_id: &str) -> Result<Vec<Narration>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, video_id, model, response_json, options_json, epoch_ms(created_at), generation_group, bundle_fingerprint
+             FROM narrations WHERE video_id = ? ORDER BY created_at DESC"
+        )?;
+        
+        let narrations = stmt.query_map(params![video_id], |row| {
+            let millis: i64 = row.get(6)?;
+            Ok(Narration {
+                id: row.get(0)?,
+                project_id: row.get(1)?,
+                video_id: row.get(2)?,
+                model: row.get(3)?,
+                response_json: row.get(4)?,
+                options_json: row.get(5)?,
+                generation_group: row.get(7)?,
+                bundle_fingerprint: row.get(8)?,
+                created_at: DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        
+        Ok(narrations)
+    }
+    
+    /// A single narration by id
+    pub async fn get_narration(&self, narration_id: &str) -> Result<Narration, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, video_id, model, response_json, options_json, epoch_ms(created_at), generation_group, bundle_fingerprint
+             FROM narrations WHERE id = ?"
+        )?;
+        
+        let narration = stmt.query_map(params![narration_id], |row| {
+            let millis: i64 = row.get(6)?;
+            Ok(Narration {
+                id: row.get(0)?,
+                project_id: row.get(1)?,
+                video_id: row.get(2)?,
+                model: row.get(3)?,
+                response_json: row.get(4)?,
+                options_json: row.get(5)?,
+                generation_group: row.get(7)?,
+                bundle_fingerprint: row.get(8)?,
+                created_at: DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+            })
+        })?.filter_map(|r| r.ok()).next();
+        
+        narration.ok_or(DatabaseError::NotFound)
+    }
+    
+    /// Update the location of several events in one transaction, returning
+    /// how many rows were updated
+    pub async fn update_event_locations(
+        &self,
+        updates: &[EventLocation],
+    ) -> Result<usize, DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        let mut updated = 0;
+        {
+            let mut stmt = tx.prepare(
+                "UPDATE events SET lat = ?, lon = ?, heading_deg = ? WHERE id = ?"
+            )?;
+            for update in updates {
+                updated += stmt.execute(params![update.lat, update.lon, update.heading_deg, update.event_id])?;
+            }
+        }
+        
+        tx.commit()?;
+        Ok(updated)
+    }
+    
+    /// Replace the stored Truth Event JSON of several events in one transaction
+    pub async fn set_event_truth(&self, updates: &[(String, String)]) -> Result<usize, DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        {
+            let mut stmt = tx.prepare("UPDATE events SET truth_bundle_json = ? WHERE id = ?")?;
+            for (event_id, json) in updates {
+                stmt.execute(params![json, event_id])?;
+            }
+        }
+        
+        tx.commit()?;
+        Ok(updates.len())
+    }
+    
+    /// The user's overrides of an event
+    pub async fn get_event_overrides(&self, event_id: &str) -> Result<Vec<EventOverride>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT event_id, field, value FROM event_overrides WHERE event_id = ? ORDER BY created_at"
+        )?;
+        let overrides = stmt.query_map(params![event_id], override_from_row)?.filter_map(|r| r.ok()).collect();
+        Ok(overrides)
+    }
+    
+    /// The user's overrides of every event of a video
+    pub async fn get_video_event_overrides(&self, video_id: &str) -> Result<Vec<EventOverride>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT o.event_id, o.field, o.value FROM event_overrides o JOIN events e ON e.id = o.event_id
+             WHERE e.video_id = ? ORDER BY o.created_at"
+        )?;
+        let overrides = stmt.query_map(params![video_id], override_from_row)?.filter_map(|r| r.ok()).collect();
+        Ok(overrides)
+    }
+    
+    /// Keep an override, replacing the event's earlier ones of the same field
+    /// unless `replace` is false, as for pinned POIs, of which there can be several
+    pub async fn put_event_override(&self, event_override: &EventOverride, replace: bool) -> Result<(), DatabaseError> {
+        let EventOverride { event_id, field, value } = event_override;
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        if replace {
+            tx.execute("DELETE FROM event_overrides WHERE event_id = ? AND field = ?", params![event_id, field])?;
+        }
+        tx.execute(
+            "INSERT INTO event_overrides (event_id, field, value) VALUES (?, ?, ?) ON CONFLICT DO NOTHING",
+            params![event_id, field, value],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Drop an event's overrides of a field, only the one with `value` if
+    /// given, returning how many there were
+    pub async fn remove_event_overrides(
+        &self,
+        event_id: &str,
+        field: &str,
+        value: Option<&str>,
+    ) -> Result<usize, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let removed = match value {
+            Some(value) => conn.execute(
+                "DELETE FROM event_overrides WHERE event_id = ? AND field = ? AND value = ?",
+                params![event_id, field, value],
+            )?,
+            None => conn.execute(
+                "DELETE FROM event_overrides WHERE event_id = ? AND field = ?",
+                params![event_id, field],
+            )?,
+        };
+        Ok(removed)
+    }
+    
+    // ==========================================================================
+    // Region data
+    // ==========================================================================
+    
+    /// Number of derived POI rows per region id
+    ///
+    /// Empty until a `pois` table has been created by region processing.
+    pub async fn region_poi_counts(&self) -> Result<HashMap<String, u64>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        if !table_exists(&conn, "pois")? {
+            return Ok(HashMap::new());
+        }
+        
+        let mut stmt = conn.prepare("SELECT region_id, count(*) FROM pois GROUP BY region_id")?;
+        let counts = stmt.query_map([], |row| {
+            let count: i64 = row.get(1)?;
+            Ok((row.get::<_, String>(0)?, count as u64))
+        })?.filter_map(|r| r.ok()).collect();
+        
+        Ok(counts)
+    }
+    
+    /// A POI of the downloaded regions, or kept from an online lookup, by its ID
+    pub async fn get_poi(&self, poi_id: &str) -> Result<PoiRecord, DatabaseError> {
+        let conn = self.conn.lock().await;
+        if !table_exists(&conn, "pois")? {
+            return Err(DatabaseError::NotFound);
+        }
+        
+        let tag = |column: &str| -> Result<String, DatabaseError> {
+            Ok(if column_exists(&conn, "pois", column)? { format!("CAST({} AS VARCHAR)", column) } else { "NULL".to_string() })
+        };
+        let sql = format!(
+            "SELECT CAST(id AS VARCHAR), name, category, lat, lon, {}, {} FROM pois
+             WHERE CAST(id AS VARCHAR) = ? AND name IS NOT NULL LIMIT 1",
+            tag("wikipedia")?,
+            tag("wikidata")?
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let poi = stmt.query_map(params![poi_id], |row| {
+            Ok(PoiRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                category: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                lat: row.get(3)?,
+                lon: row.get(4)?,
+                wikipedia: row.get(5)?,
+                wikidata: row.get(6)?,
+            })
+        })?.filter_map(|r| r.ok()).next();
+        poi.ok_or(DatabaseError::NotFound)
+    }
+    
+    /// POIs inside a bounding box, optionally limited to some categories
+    ///
+    /// Empty until a `pois` table has been created by region processing.
+    pub async fn pois_in_bounds(
+        &self,
+        (min_lat, min_lon, max_lat, max_lon): (f64, f64, f64, f64),
+        categories: &[String],
+    ) -> Result<Vec<PoiRecord>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        if !table_exists(&conn, "pois")? {
+            return Ok(Vec::new());
+        }
+        
+        // Older regions' tables don't have the article tags
+        let tag = |column: &str| -> Result<String, DatabaseError> {
+            Ok(if column_exists(&conn, "pois", column)? { format!("CAST({} AS VARCHAR)", column) } else { "NULL".to_string() })
+        };
+        let mut sql = format!(
+            "SELECT CAST(id AS VARCHAR), name, category, lat, lon, {}, {} FROM pois
+             WHERE lat BETWEEN ? AND ? AND lon BETWEEN ? AND ? AND name IS NOT NULL",
+            tag("wikipedia")?,
+            tag("wikidata")?
+        );
+        if !categories.is_empty() {
+            sql.push_str(&format!(" AND lower(category) IN ({})", vec!["?"; categories.len()].join(", ")));
+        }
+        
+        let mut values: Vec<Box<dyn duckdb::ToSql>> = vec![
+            Box::new(min_lat), Box::new(max_lat), Box::new(min_lon), Box::new(max_lon),
+        ];
+        values.extend(categories.iter().map(|c| Box::new(c.to_lowercase()) as Box<dyn duckdb::ToSql>));
+        
+        let mut stmt = conn.prepare(&sql)?;
+        let pois = stmt.query_map(duckdb::params_from_iter(values.iter().map(|v| v.as_ref())), |row| {
+            Ok(PoiRecord {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                category: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                lat: row.get(3)?,
+                lon: row.get(4)?,
+                wikipedia: row.get(5)?,
+                wikidata: row.get(6)?,
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        
+        Ok(pois)
+    }
+    
+    /// Keep POIs looked up online, under the [`ONLINE_POI_REGION`] region,
+    /// so the point doesn't need looking up again
+    ///
+    /// Creates the `pois` table if no region has yet. POIs already kept
+    /// are left as they are; returns how many were added.
+    pub async fn add_online_pois(&self, pois: &[PoiRecord]) -> Result<usize, DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS pois (
+                id VARCHAR,
+                region_id VARCHAR,
+                name VARCHAR,
+                category VARCHAR,
+                lat DOUBLE,
+                lon DOUBLE
+            );
+            ALTER TABLE pois ADD COLUMN IF NOT EXISTS wikipedia VARCHAR;
+            ALTER TABLE pois ADD COLUMN IF NOT EXISTS wikidata VARCHAR;"
+        )?;
+        
+        let mut added = 0;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO pois (id, region_id, name, category, lat, lon, wikipedia, wikidata)
+                 SELECT ?, ?, ?, ?, ?, ?, ?, ?
+                 WHERE NOT EXISTS (SELECT 1 FROM pois WHERE CAST(id AS VARCHAR) = ? AND region_id = ?)"
+            )?;
+            for poi in pois {
+                added += stmt.execute(params![
+                    poi.id, ONLINE_POI_REGION, poi.name, poi.category, poi.lat, poi.lon, poi.wikipedia, poi.wikidata,
+                    poi.id, ONLINE_POI_REGION,
+                ])?;
+            }
+        }
+        
+        tx.commit()?;
+        debug!("Kept {} of {} online POIs", added, pois.len());
+        Ok(added)
+    }
+    
+    /// Administrative areas whose bounding box contains a point, by admin
+    /// level and then tightest box first
+    ///
+    /// Empty until a `boundaries` table has come with a region. Boxes of
+    /// neighbouring areas overlap, so near a border the tighter one is
+    /// first even if the point is in the other.
+    pub async fn admin_areas_at(&self, lat: f64, lon: f64) -> Result<Vec<AdminArea>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        if !table_exists(&conn, "boundaries")? {
+            return Ok(Vec::new());
+        }
+        
+        // Older extracts have no time zones
+        let timezone = if column_exists(&conn, "boundaries", "timezone")? {
+            "CAST(timezone AS VARCHAR)"
+        } else {
+            "NULL"
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT name, CAST(admin_level AS INTEGER), CAST(population AS BIGINT),
+                    (max_lat - min_lat) * (max_lon - min_lon) AS extent, {}
+             FROM boundaries
+             WHERE ? BETWEEN min_lat AND max_lat AND ? BETWEEN min_lon AND max_lon
+               AND name IS NOT NULL AND admin_level IS NOT NULL
+             ORDER BY 2, extent",
+            timezone
+        ))?;
+        let areas = stmt.query_map(params![lat, lon], |row| {
+            Ok(AdminArea {
+                name: row.get(0)?,
+                admin_level: row.get(1)?,
+                population: row.get(2)?,
+                extent: row.get(3)?,
+                timezone: row.get(4)?,
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        
+        Ok(areas)
+    }
+    
+    /// Write a region's rows of every existing region table to `<table>.parquet` in `dir`
+    ///
+    /// Returns the (table, file) pairs written.
+    pub async fn export_region_tables(
+        &self,
+        region_id: &str,
+        dir: &Path,
+    ) -> Result<Vec<(String, PathBuf)>, DatabaseError> {
+        let conn = self.conn.lock().await;
+        let mut exported = Vec::new();
+        
+        for table in REGION_TABLES {
+            if !table_exists(&conn, table)? {
+                continue;
+            }
+            // COPY doesn't take parameters
+            let path = dir.join(format!("{}.parquet", table));
+            conn.execute_batch(&format!(
+                "COPY (SELECT * FROM {} WHERE region_id = {}) TO {} (FORMAT PARQUET)",
+                table,
+                sql_literal(region_id),
+                sql_literal(&path.to_string_lossy()),
+            ))?;
+            exported.push((table.to_string(), path));
+        }
+        
+        debug!("Exported {} region tables for {}", exported.len(), region_id);
+        Ok(exported)
+    }
+    
+    /// Replace a region's rows with those in Parquet files from `export_region_tables`
+    ///
+    /// Tables that don't exist yet are created from the file's columns.
+    /// Unknown table names are ignored.
+    pub async fn import_region_tables(
+        &self,
+        region_id: &str,
+        files: &[(String, PathBuf)],
+    ) -> Result<(), DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        for (table, path) in files {
+            let Some(table) = REGION_TABLES.iter().find(|t| **t == table) else {
+                continue;
+            };
+            let source = format!("read_parquet({})", sql_literal(&path.to_string_lossy()));
+            
+            if table_exists(&tx, table)? {
+                tx.execute(&format!("DELETE FROM {} WHERE region_id = ?", table), params![region_id])?;
+                tx.execute_batch(&format!("INSERT INTO {} SELECT * FROM {}", table, source))?;
+            } else {
+                tx.execute_batch(&format!("CREATE TABLE {} AS SELECT * FROM {}", table, source))?;
+            }
+        }
+        
+        tx.commit()?;
+        Ok(())
+    }
+    
+    /// Database size and row counts per table
+    pub async fn db_stats(&self) -> Result<DbStats, DatabaseError> {
+        let conn = self.conn.lock().await;
+        
+        let tables: Vec<String> = conn
+            .prepare(
+                "SELECT table_name FROM information_schema.tables \
+                 WHERE table_schema = 'main' AND table_type = 'BASE TABLE' ORDER BY table_name",
+            )?
+            .query_map([], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+        
+        let mut table_counts = BTreeMap::new();
+        for table in tables {
+            let count: i64 = conn.query_row(
+                &format!("SELECT count(*) FROM \"{}\"", table.replace('"', "\"\"")),
+                [],
+                |row| row.get(0),
+            )?;
+            table_counts.insert(table, count as u64);
+        }
+        
+        let wal_bytes = file_size(&wal_path(&self.path));
+        Ok(DbStats {
+            path: self.path.display().to_string(),
+            size_bytes: file_size(&self.path) + wal_bytes,
+            wal_bytes,
+            table_counts,
+        })
+    }
+    
+    /// Reclaim space left by deleted rows and fold the WAL into the file
+    ///
+    /// Holds the connection for the duration, so other database work
+    /// pauses until it finishes; it runs on a blocking thread to keep the
+    /// async runtime responsive meanwhile.
+    pub async fn compact(&self) -> Result<CompactResult, DatabaseError> {
+        let conn = self.conn.clone().lock_owned().await;
+        let path = self.path.clone();
+        
+        tokio::task::spawn_blocking(move || {
+            let before_bytes = file_size(&path) + file_size(&wal_path(&path));
+            info!("Compacting database ({} bytes)", before_bytes);
+            
+            conn.execute_batch("VACUUM; FORCE CHECKPOINT;")?;
+            
+            let after_bytes = file_size(&path) + file_size(&wal_path(&path));
+            info!("Database compacted: {} -> {} bytes", before_bytes, after_bytes);
+            Ok(CompactResult { before_bytes, after_bytes })
+        })
+        .await
+        .map_err(|e| DatabaseError::Serialization(e.to_string()))?
+    }
+    
+    // ==========================================================================
+    // Project Bundles
+    // ==========================================================================
+    
+    /// Every row of a project, its videos and their data
+    pub async fn project_snapshot(&self, project_id: &str) -> Result<ProjectSnapshot, DatabaseError> {
+        let project = self
+            .get_projects()
+            .await?
+            .into_iter()
+            .find(|p| p.id == project_id)
+            .ok_or(DatabaseError::NotFound)?;
+        let videos = self.get_project_videos(project_id).await?;
+        
+        let mut gps_points = Vec::new();
+        let mut events = Vec::new();
+        let mut video_statuses = Vec::new();
+        for video in &videos {
+            gps_points.extend(self.get_gps_points(&video.id).await?);
+            events.extend(self.get_video_events(&video.id).await?);
+            video_statuses.push(self.get_video_status(&video.id).await?);
+        }
+        
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT t.id, t.video_id, t.start_ms, t.end_ms, t.text, t.language
+             FROM transcriptions t JOIN videos v ON v.id = t.video_id
+             WHERE v.project_id = ? ORDER BY t.video_id, t.start_ms"
+        )?;
+        let transcriptions = stmt.query_map(params![project_id], |row| {
+            Ok(TranscriptionRow {
+                id: row.get(0)?,
+                video_id: row.get(1)?,
+                start_ms: row.get(2)?,
+                end_ms: row.get(3)?,
+                text: row.get(4)?,
+                language: row.get(5)?,
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, project_id, video_id, model, response_json, options_json, epoch_ms(created_at), generation_group, bundle_fingerprint
+             FROM narrations
+             WHERE project_id = ? OR video_id IN (SELECT id FROM videos WHERE project_id = ?)
+             ORDER BY created_at"
+        )?;
+        let narrations = stmt.query_map(params![project_id, project_id], |row| {
+            let millis: i64 = row.get(6)?;
+            Ok(Narration {
+                id: row.get(0)?,
+                project_id: row.get(1)?,
+                video_id: row.get(2)?,
+                model: row.get(3)?,
+                response_json: row.get(4)?,
+                options_json: row.get(5)?,
+                generation_group: row.get(7)?,
+                bundle_fingerprint: row.get(8)?,
+                created_at: DateTime::from_timestamp_millis(millis).unwrap_or_default(),
+            })
+        })?.filter_map(|r| r.ok()).collect();
+        
+        Ok(ProjectSnapshot {
+            project,
+            videos,
+            gps_points,
+            events,
+            transcriptions,
+            narrations,
+            video_statuses,
+        })
+    }
+    
+    /// Which of `ids` already exist in `table`, one of [`ID_TABLES`]
+    pub async fn ids_in_use(&self, table: &str, ids: &[String]) -> Result<HashSet<String>, DatabaseError> {
+        if !ID_TABLES.contains(&table) {
+            return Err(DatabaseError::Serialization(format!("Not a table with ids: {}", table)));
+        }
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(&format!("SELECT count(*) FROM {} WHERE id = ?", table))?;
+        
+        let mut taken = HashSet::new();
+        for id in ids {
+            let count: i64 = stmt.query_row(params![id], |row| row.get(0))?;
+            if count > 0 {
+                taken.insert(id.clone());
+            }
+        }
+        Ok(taken)
+    }
+    
+    /// Insert every row of `snapshot` in one transaction
+    ///
+    /// Ids must not be in use yet; GPS points get new ones from the sequence.
+    pub async fn restore_project(&self, snapshot: &ProjectSnapshot) -> Result<(), DatabaseError> {
+        let mut conn = self.conn.lock().await;
+        let tx = conn.transaction()?;
+        
+        let project = &snapshot.project;
+        tx.execute(
+            "INSERT INTO projects (id, name, description, created_at, updated_at, cover_image_path, camera_heading_offset_deg)
+             VALUES (?, ?, ?, make_timestamp(?), make_timestamp(?), ?, ?)",
+            params![
+                project.id,
+                project.name,
+                project.description,
+                project.created_at.timestamp_micros(),
+                project.updated_at.timestamp_micros(),
+                project.cover_image_path,
+                project.camera_heading_offset_deg,
+            ],
+        )?;
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO videos (id, project_id, filename, file_path, duration_seconds, fps, width, height, codec,
+                                     file_size_bytes, camera_heading_offset_deg, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, make_timestamp(?))"
+            )?;
+            for v in &snapshot.videos {
+                stmt.execute(params![
+                    v.id, v.project_id, v.filename, v.file_path, v.duration_seconds, v.fps, v.width, v.height,
+                    v.codec, v.file_size_bytes, v.camera_heading_offset_deg, v.created_at.timestamp_micros(),
+                ])?;
+            }
+        }
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO gps_points (id, video_id, timestamp, lat, lon, elevation_m, speed_kmh, heading_deg)
+                 VALUES (nextval('gps_points_seq'), ?, make_timestamp(?), ?, ?, ?, ?, ?)"
+            )?;
+            for p in &snapshot.gps_points {
+                stmt.execute(params![
+                    p.video_id, p.timestamp.timestamp_micros(), p.lat, p.lon, p.elevation_m, p.speed_kmh, p.heading_deg,
+                ])?;
+            }
+        }
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO events (id, video_id, event_type, start_time_seconds, end_time_seconds, lat, lon, heading_deg,
+                                     verified, verification_mode, truth_bundle_json, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, make_timestamp(?))"
+            )?;
+            for e in &snapshot.events {
+                stmt.execute(params![
+                    e.id, e.video_id, e.event_type, e.start_time_seconds, e.end_time_seconds, e.lat, e.lon,
+                    e.heading_deg, e.verified, e.verification_mode, e.truth_bundle_json, e.created_at.timestamp_micros(),
+                ])?;
+            }
+        }
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO transcriptions (id, video_id, start_ms, end_ms, text, language) VALUES (?, ?, ?, ?, ?, ?)"
+            )?;
+            for t in &snapshot.transcriptions {
+                stmt.execute(params![t.id, t.video_id, t.start_ms, t.end_ms, t.text, t.language])?;
+            }
+        }
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO narrations (id, project_id, video_id, model, response_json, options_json, generation_group, bundle_fingerprint, created_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, make_timestamp(?))"
+            )?;
+            for n in &snapshot.narrations {
+                stmt.execute(params![
+                    n.id, n.project_id, n.video_id, n.model, n.response_json, n.options_json, n.generation_group,
+                    n.bundle_fingerprint, n.created_at.timestamp_micros(),
+                ])?;
+            }
+        }
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO video_status (video_id, status, processed_at, error, updated_at, options_json)
+                 VALUES (?, ?, make_timestamp(?), ?, current_timestamp, ?)"
+            )?;
+            for s in &snapshot.video_statuses {
+                stmt.execute(params![
+                    s.video_id,
+                    s.status.as_str(),
+                    s.processed_at.map(|t| t.timestamp_micros()),
+                    s.error,
+                    s.options_json,
+                ])?;
+            }
+        }
+        
+        tx.commit()?;
+        
+        debug!(
+            "Restored project {} ({} videos, {} GPS points, {} events)",
+            project.id,
+            snapshot.videos.len(),
+            snapshot.gps_points.len(),
+            snapshot.events.len()
+        );
+        Ok(())
+    }
+    
+    /// Get database path
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+/// DuckDB's write-ahead log, kept next to the database file
+fn wal_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".wal");
+    PathBuf::from(name)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+fn event_from_row(row: &duckdb::Row) -> duckdb::Result<Event> {
+    Ok(Event {
+        id: row.get(0)?,
+        video_id: row.get(1)?,
+        event_type: row.get(2)?,
+        start_time_seconds: row.get(3)?,
+        end_time_seconds: row.get(4)?,
+        lat: row.get(5)?,
+        lon: row.get(6)?,
+        heading_deg: row.get(7)?,
+        verified: row.get(8)?,
+        verification_mode: row.get(9)?,
+        truth_bundle_json: row.get(10)?,
+        created_at: Utc::now(),
+    })
+}
+
+fn override_from_row(row: &duckdb::Row) -> duckdb::Result<EventOverride> {
+    Ok(EventOverride { event_id: row.get(0)?, field: row.get(1)?, value: row.get(2)? })
+}
+
+/// Whether a DuckDB error means another process holds the file lock
+fn is_lock_error(message: &str) -> bool {
+    message.contains("Could not set lock") || message.contains("Conflicting lock")
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str) -> Result<bool, DatabaseError> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM information_schema.columns WHERE table_name = ? AND column_name = ?",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, DatabaseError> {
+    let count: i64 = conn.query_row(
+        "SELECT count(*) FROM information_schema.tables WHERE table_name = ?",
+        params![table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Stage names from their stored, comma-separated list
+fn split_stages(stages: &str) -> Vec<String> {
+    stages.split(',').filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+/// Quote a string as a SQL literal, for statements that can't take parameters
+fn sql_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Video metadata for import
+#[derive(Debug, Clone)]
+pub struct VideoMetadata {
+    pub duration_seconds: Option<f64>,
+    pub fps: Option<f64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub codec: Option<String>,
+    pub file_size_bytes: Option<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_lock_error() {
+        assert!(is_lock_error(
+            "IO Error: Could not set lock on file \"/data/geotruth_v1.duckdb\": Conflicting lock is held in /Applications/GeoTruth.app (PID 4242)"
+        ));
+        assert!(!is_lock_error("IO Error: Cannot open file \"/data/geotruth_v1.duckdb\": Permission denied"));
+    }
+
+    #[test]
+    fn test_processing_status_round_trip() {
+        for status in [
+            ProcessingStatus::Pending,
+            ProcessingStatus::Processing,
+            ProcessingStatus::Complete,
+            ProcessingStatus::Failed,
+        ] {
+            assert_eq!(ProcessingStatus::parse(status.as_str()), status);
+        }
+        assert_eq!(ProcessingStatus::parse("unknown"), ProcessingStatus::Pending);
+    }
+
+    #[test]
+    fn test_wal_path() {
+        assert_eq!(
+            wal_path(Path::new("/data/geotruth_v1.duckdb")),
+            PathBuf::from("/data/geotruth_v1.duckdb.wal")
+        );
+    }
 }
@@ -0,0 +1,204 @@
+//! Custom Region Extracts
+//!
+//! Fetches map data for arbitrary bounding boxes from public extract services.
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+/// Largest custom region we request, to stay polite to public servers
+pub const MAX_CUSTOM_AREA_KM2: f64 = 2_500.0;
+
+/// Attempts made before giving up on a throttled extract request
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Base delay for exponential backoff between attempts
+const BASE_BACKOFF_SECS: u64 = 2;
+
+/// Kilometres per degree of latitude
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// Public Overpass API instance
+pub const OVERPASS_ENDPOINT: &str = "https://overpass-api.de/api/interpreter";
+
+#[derive(Error, Debug)]
+pub enum ExtractError {
+    #[error("Invalid bounding box: {0}")]
+    InvalidBounds(String),
+
+    #[error("Area of {area_km2:.0} km² exceeds the {max_km2:.0} km² limit for custom regions")]
+    AreaTooLarge { area_km2: f64, max_km2: f64 },
+
+    #[error("Extract service is rate limiting requests, please try again later")]
+    RateLimited,
+
+    #[error("Extract request failed: {0}")]
+    RequestFailed(String),
+}
+
+/// Geographic bounding box
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    /// Create a validated bounding box
+    pub fn new(min_lat: f64, min_lon: f64, max_lat: f64, max_lon: f64) -> Result<Self, ExtractError> {
+        if !(-90.0..=90.0).contains(&min_lat) || !(-90.0..=90.0).contains(&max_lat) {
+            return Err(ExtractError::InvalidBounds("latitude must be within [-90, 90]".into()));
+        }
+        if !(-180.0..=180.0).contains(&min_lon) || !(-180.0..=180.0).contains(&max_lon) {
+            return Err(ExtractError::InvalidBounds("longitude must be within [-180, 180]".into()));
+        }
+        if min_lat >= max_lat || min_lon >= max_lon {
+            return Err(ExtractError::InvalidBounds("minimum must be below maximum".into()));
+        }
+
+        Ok(Self { min_lat, min_lon, max_lat, max_lon })
+    }
+
+    /// Approximate area in square kilometres
+    pub fn area_km2(&self) -> f64 {
+        let mid_lat = ((self.min_lat + self.max_lat) / 2.0).to_radians();
+        let height_km = (self.max_lat - self.min_lat) * KM_PER_DEGREE;
+        let width_km = (self.max_lon - self.min_lon) * KM_PER_DEGREE * mid_lat.cos();
+        height_km * width_km
+    }
+
+    /// Bounds in the (min_lat, min_lon, max_lat, max_lon) order used by region metadata
+    pub fn as_tuple(&self) -> (f64, f64, f64, f64) {
+        (self.min_lat, self.min_lon, self.max_lat, self.max_lon)
+    }
+}
+
+/// A service able to produce map data for an arbitrary bounding box
+pub trait ExtractProvider: Send + Sync {
+    /// Short provider name for logs and metadata
+    fn name(&self) -> &'static str;
+
+    /// Build the HTTP request for an extract covering `bbox`
+    fn request(&self, client: &reqwest::Client, bbox: &BoundingBox) -> reqwest::RequestBuilder;
+}
+
+/// Overpass API provider returning OSM XML for every feature in the box
+pub struct OverpassProvider {
+    endpoint: String,
+}
+
+impl OverpassProvider {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self { endpoint: endpoint.into() }
+    }
+}
+
+impl Default for OverpassProvider {
+    fn default() -> Self {
+        Self::new(OVERPASS_ENDPOINT)
+    }
+}
+
+impl ExtractProvider for OverpassProvider {
+    fn name(&self) -> &'static str {
+        "overpass"
+    }
+
+    fn request(&self, client: &reqwest::Client, bbox: &BoundingBox) -> reqwest::RequestBuilder {
+        // Overpass expects south,west,north,east
+        let query = format!(
+            "[out:xml][timeout:300];(nwr({},{},{},{}););(._;>;);out meta;",
+            bbox.min_lat, bbox.min_lon, bbox.max_lat, bbox.max_lon
+        );
+
+        client
+            .post(&self.endpoint)
+            .form(&[("data", query)])
+    }
+}
+
+/// Request an extract, backing off politely while the service is throttling us
+pub async fn fetch_extract(
+    provider: &dyn ExtractProvider,
+    bbox: &BoundingBox,
+) -> Result<reqwest::Response, ExtractError> {
+    let area_km2 = bbox.area_km2();
+    if area_km2 > MAX_CUSTOM_AREA_KM2 {
+        return Err(ExtractError::AreaTooLarge { area_km2, max_km2: MAX_CUSTOM_AREA_KM2 });
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("GeoTruth/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| ExtractError::RequestFailed(e.to_string()))?;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        debug!(provider = provider.name(), attempt, "Requesting extract");
+
+        let response = provider
+            .request(&client, bbox)
+            .send()
+            .await
+            .map_err(|e| ExtractError::RequestFailed(e.to_string()))?;
+
+        let status = response.status();
+        if status.is_success() {
+            info!(provider = provider.name(), area_km2, "Extract request accepted");
+            return Ok(response);
+        }
+
+        // 429 and gateway timeouts mean the server is busy, not that we asked wrong
+        let throttled = status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || status == reqwest::StatusCode::SERVICE_UNAVAILABLE
+            || status == reqwest::StatusCode::GATEWAY_TIMEOUT;
+
+        if !throttled {
+            let body = response.text().await.unwrap_or_default();
+            let excerpt: String = body.chars().take(200).collect();
+            return Err(ExtractError::RequestFailed(format!("{}: {}", status, excerpt.trim())));
+        }
+
+        if attempt == MAX_ATTEMPTS {
+            break;
+        }
+
+        let delay = retry_after(&response)
+            .unwrap_or_else(|| Duration::from_secs(BASE_BACKOFF_SECS << (attempt - 1)));
+        warn!(provider = provider.name(), %status, ?delay, "Extract service busy, backing off");
+        tokio::time::sleep(delay).await;
+    }
+
+    Err(ExtractError::RateLimited)
+}
+
+/// Parse a Retry-After header given in seconds
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+/// Turn a user supplied name into an id-safe slug
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-').to_string();
+
+    if slug.is_empty() {
+        "region".to_string()
+    } else {
+        slug
+    }
+}
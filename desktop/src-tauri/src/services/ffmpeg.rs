@@ -3,13 +3,19 @@
 //!
 //! Rust interface for executing FFmpeg and FFprobe as sidecars.
 
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::{Arc, RwLock};
 use tokio::process::Command;
+use tokio::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
+use super::sidecar;
+use crate::resources::Priority;
+
 #[derive(Error, Debug)]
 pub enum FfmpegError {
     #[error("FFmpeg binary not found at {0}")]
@@ -21,6 +27,9 @@ pub enum FfmpegError {
     #[error("Failed to parse output: {0}")]
     ParseError(String),
     
+    #[error("Invalid chapters: {0}")]
+    InvalidChapters(String),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
@@ -37,9 +46,24 @@ pub struct VideoMetadata {
     pub file_size_bytes: Option<u64>,
     pub has_audio: bool,
     pub audio_codec: Option<String>,
+    /// Every audio track, in the order `audio_stream_index` refers to
+    #[serde(default)]
+    pub audio_streams: Vec<AudioStreamInfo>,
     pub creation_time: Option<String>,
 }
 
+/// An audio track, e.g. a camera's onboard mic or an external mic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AudioStreamInfo {
+    /// Position among the audio streams (`-map 0:a:N`), not the container stream index
+    pub index: usize,
+    pub codec: Option<String>,
+    pub channels: Option<u32>,
+    /// ISO 639-2 code, e.g. `eng`; `None` when untagged or `und`
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
 /// FFprobe JSON output format
 #[derive(Debug, Deserialize)]
 struct FfprobeOutput {
@@ -62,19 +86,189 @@ struct FfprobeTags {
 
 #[derive(Debug, Deserialize)]
 struct FfprobeStream {
+    index: Option<u32>,
     codec_type: Option<String>,
     codec_name: Option<String>,
+    codec_tag_string: Option<String>,
     width: Option<u32>,
     height: Option<u32>,
     r_frame_rate: Option<String>,
     avg_frame_rate: Option<String>,
+    /// Seconds, for containers that report it per stream
+    duration: Option<String>,
+    nb_frames: Option<String>,
+    channels: Option<u32>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+/// Every stream in a file, and what telemetry they may carry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamProbe {
+    pub streams: Vec<StreamInfo>,
+    /// Streams that hold (or may hold) embedded GPS telemetry
+    pub telemetry: Vec<TelemetryStream>,
+    /// A stream in a known GPS telemetry format was found
+    pub gps_telemetry_detected: bool,
+}
+
+/// One stream of a container, as FFprobe reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamInfo {
+    /// Container stream index (`-map 0:N`)
+    pub index: u32,
+    /// `video`, `audio`, `subtitle`, `data` or `attachment`
+    pub kind: String,
+    pub codec: Option<String>,
+    /// Four-character code, e.g. `gpmd` for GoPro telemetry
+    pub codec_tag: Option<String>,
+    pub tags: HashMap<String, String>,
+}
+
+/// Embedded telemetry format, as far as the stream metadata tells
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryFormat {
+    /// GoPro Metadata Format
+    Gpmf,
+    /// Camera Motion Metadata, e.g. Insta360 and Google Street View cameras
+    Camm,
+    /// Subtitle track, which some cameras (DJI, dashcams) fill with GPS text
+    Subtitle,
+}
+
+impl TelemetryFormat {
+    /// Whether the format carries GPS whenever the camera recorded it, as
+    /// opposed to a subtitle track that might just be subtitles
+    pub fn is_gps(&self) -> bool {
+        matches!(self, TelemetryFormat::Gpmf | TelemetryFormat::Camm)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelemetryStream {
+    pub index: u32,
+    pub format: TelemetryFormat,
+}
+
+impl StreamProbe {
+    fn from_streams(streams: &[FfprobeStream]) -> Self {
+        let streams: Vec<StreamInfo> = streams
+            .iter()
+            .enumerate()
+            .map(|(position, s)| StreamInfo {
+                index: s.index.unwrap_or(position as u32),
+                kind: s.codec_type.clone().unwrap_or_else(|| "unknown".to_string()),
+                codec: s.codec_name.clone(),
+                // FFprobe prints `[0][0][0][0]` for streams without one
+                codec_tag: s.codec_tag_string.clone().filter(|t| !t.starts_with('[')),
+                tags: s.tags.clone(),
+            })
+            .collect();
+
+        let telemetry: Vec<TelemetryStream> = streams
+            .iter()
+            .filter_map(|s| telemetry_format(s).map(|format| TelemetryStream { index: s.index, format }))
+            .collect();
+
+        Self {
+            gps_telemetry_detected: telemetry.iter().any(|t| t.format.is_gps()),
+            streams,
+            telemetry,
+        }
+    }
+}
+
+fn telemetry_format(stream: &StreamInfo) -> Option<TelemetryFormat> {
+    let handler = stream.tags.get("handler_name").map(|h| h.to_lowercase()).unwrap_or_default();
+    match (stream.kind.as_str(), stream.codec_tag.as_deref()) {
+        (_, Some("gpmd")) => Some(TelemetryFormat::Gpmf),
+        (_, Some("camm")) => Some(TelemetryFormat::Camm),
+        ("data", _) if handler.contains("gopro met") => Some(TelemetryFormat::Gpmf),
+        ("subtitle", _) => Some(TelemetryFormat::Subtitle),
+        _ => None,
+    }
+}
+
+/// The audio tracks among a file's streams
+fn audio_streams(streams: &[FfprobeStream]) -> Vec<AudioStreamInfo> {
+    streams
+        .iter()
+        .filter(|s| s.codec_type.as_deref() == Some("audio"))
+        .enumerate()
+        .map(|(index, s)| AudioStreamInfo {
+            index,
+            codec: s.codec_name.clone(),
+            channels: s.channels,
+            language: s.tags.get("language").filter(|l| !l.is_empty() && *l != "und").cloned(),
+            // Cameras name tracks in `handler_name`, other muxers in `title`
+            title: s.tags.get("title").or_else(|| s.tags.get("handler_name")).cloned(),
+        })
+        .collect()
+}
+
+/// Still image format for captured frames and thumbnails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Jpeg,
+    /// Roughly 30% smaller than JPEG at similar quality; needs libwebp
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Webp => "webp",
+        }
+    }
+
+    /// FFmpeg encoder required for this format
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "mjpeg",
+            ImageFormat::Webp => "libwebp",
+        }
+    }
+
+    /// Encoder arguments for high quality stills
+    fn codec_args(&self) -> [&'static str; 4] {
+        match self {
+            ImageFormat::Jpeg => ["-c:v", "mjpeg", "-q:v", "2"],
+            ImageFormat::Webp => ["-c:v", "libwebp", "-quality", "80"],
+        }
+    }
 }
 
+/// Extensions of the video containers imports are tested with
+///
+/// The bundled FFmpeg reads more than these, but camera footage comes in
+/// these containers, so file dialogs offer them.
+pub const VIDEO_FILE_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v", "avi", "mkv", "mts", "m2ts", "webm"];
+
 /// FFmpeg/FFprobe sidecar manager
+///
+/// Clones share their binaries, so pointing one at a downloaded build
+/// repoints them all.
 #[derive(Clone)]
 pub struct Ffmpeg {
-    ffmpeg_path: PathBuf,
-    ffprobe_path: PathBuf,
+    binaries: Arc<RwLock<Binaries>>,
+}
+
+struct Binaries {
+    ffmpeg: PathBuf,
+    ffprobe: PathBuf,
+    /// Encoders compiled into the FFmpeg build, probed on first use
+    encoders: Arc<OnceCell<HashSet<String>>>,
 }
 
 impl Ffmpeg {
@@ -101,20 +295,88 @@ impl Ffmpeg {
         }
         
         Ok(Self {
-            ffmpeg_path,
-            ffprobe_path,
+            binaries: Arc::new(RwLock::new(Binaries {
+                ffmpeg: ffmpeg_path,
+                ffprobe: ffprobe_path,
+                encoders: Arc::new(OnceCell::new()),
+            })),
         })
     }
     
-    /// Extract video metadata using FFprobe
-    pub async fn extract_metadata(&self, video_path: &PathBuf) -> Result<VideoMetadata, FfmpegError> {
-        if !self.ffprobe_path.exists() {
-            return Err(FfmpegError::BinaryNotFound(self.ffprobe_path.clone()));
-        }
+    /// Where the FFmpeg binary is expected
+    pub fn ffmpeg_path(&self) -> PathBuf {
+        self.binaries.read().unwrap_or_else(|e| e.into_inner()).ffmpeg.clone()
+    }
+    
+    /// Where the FFprobe binary is expected
+    pub fn ffprobe_path(&self) -> PathBuf {
+        self.binaries.read().unwrap_or_else(|e| e.into_inner()).ffprobe.clone()
+    }
+    
+    /// Run FFmpeg from `path` from now on
+    pub fn set_ffmpeg_path(&self, path: PathBuf) {
+        let mut binaries = self.binaries.write().unwrap_or_else(|e| e.into_inner());
+        info!("Using FFmpeg at {:?}", path);
+        binaries.ffmpeg = path;
+        // A different build may have different encoders
+        binaries.encoders = Arc::new(OnceCell::new());
+    }
+    
+    /// Run FFprobe from `path` from now on
+    pub fn set_ffprobe_path(&self, path: PathBuf) {
+        info!("Using FFprobe at {:?}", path);
+        self.binaries.write().unwrap_or_else(|e| e.into_inner()).ffprobe = path;
+    }
+    
+    /// Check whether the FFmpeg build includes an encoder
+    pub async fn has_encoder(&self, name: &str) -> bool {
+        let (ffmpeg_path, encoders) = {
+            let binaries = self.binaries.read().unwrap_or_else(|e| e.into_inner());
+            (binaries.ffmpeg.clone(), binaries.encoders.clone())
+        };
+        let encoders = encoders.get_or_init(|| async {
+            let output = Command::new(&ffmpeg_path)
+                .args(["-hide_banner", "-encoders"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .output()
+                .await;
+            
+            match output {
+                // Lines look like " V....D libwebp   libwebp WebP image"
+                Ok(out) => String::from_utf8_lossy(&out.stdout)
+                    .lines()
+                    .filter_map(|line| line.split_whitespace().nth(1))
+                    .map(|name| name.to_string())
+                    .collect(),
+                Err(e) => {
+                    warn!("Failed to list FFmpeg encoders: {}", e);
+                    HashSet::new()
+                }
+            }
+        }).await;
         
-        debug!("Extracting metadata from: {:?}", video_path);
+        encoders.contains(name)
+    }
+    
+    /// Use the requested image format if this build can encode it, JPEG otherwise
+    pub async fn resolve_image_format(&self, requested: ImageFormat) -> ImageFormat {
+        if requested == ImageFormat::Jpeg || self.has_encoder(requested.encoder()).await {
+            requested
+        } else {
+            warn!("FFmpeg lacks the {} encoder, falling back to JPEG", requested.encoder());
+            ImageFormat::Jpeg
+        }
+    }
+    
+    /// Run FFprobe on a file and parse its format and stream listing
+    async fn probe(&self, video_path: &Path) -> Result<FfprobeOutput, FfmpegError> {
+        if !self.ffprobe_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffprobe_path()));
+        }
         
-        let output = Command::new(&self.ffprobe_path)
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        let output = Command::new(self.ffprobe_path())
             .args([
                 "-v", "quiet",
                 "-print_format", "json",
@@ -133,8 +395,28 @@ impl Ffmpeg {
         }
         
         let stdout = String::from_utf8_lossy(&output.stdout);
-        let probe: FfprobeOutput = serde_json::from_str(&stdout)
-            .map_err(|e| FfmpegError::ParseError(e.to_string()))?;
+        serde_json::from_str(&stdout).map_err(|e| FfmpegError::ParseError(e.to_string()))
+    }
+    
+    /// List every stream in a file and flag the ones that may carry GPS telemetry
+    pub async fn probe_streams(&self, video_path: &PathBuf) -> Result<StreamProbe, FfmpegError> {
+        debug!("Probing streams of: {:?}", video_path);
+        let probe = self.probe(video_path).await?;
+        let result = StreamProbe::from_streams(probe.streams.as_deref().unwrap_or_default());
+        
+        info!(
+            "Found {} streams in {:?}, telemetry: {:?}",
+            result.streams.len(),
+            video_path.file_name().unwrap_or_default(),
+            result.telemetry
+        );
+        Ok(result)
+    }
+    
+    /// Extract video metadata using FFprobe
+    pub async fn extract_metadata(&self, video_path: &PathBuf) -> Result<VideoMetadata, FfmpegError> {
+        debug!("Extracting metadata from: {:?}", video_path);
+        let probe = self.probe(video_path).await?;
         
         // Extract video stream info
         let video_stream = probe.streams.as_ref()
@@ -143,27 +425,24 @@ impl Ffmpeg {
         let audio_stream = probe.streams.as_ref()
             .and_then(|s| s.iter().find(|s| s.codec_type.as_deref() == Some("audio")));
         
-        // Parse FPS from frame rate string (e.g., "30000/1001" or "30/1")
-        let fps = video_stream
-            .and_then(|s| s.avg_frame_rate.as_ref().or(s.r_frame_rate.as_ref()))
-            .and_then(|rate| {
-                let parts: Vec<&str> = rate.split('/').collect();
-                if parts.len() == 2 {
-                    let num: f64 = parts[0].parse().ok()?;
-                    let den: f64 = parts[1].parse().ok()?;
-                    if den > 0.0 { Some(num / den) } else { None }
-                } else {
-                    rate.parse().ok()
-                }
+        let fps = video_stream.and_then(stream_fps);
+
+        // Fragmented MP4 and some MKVs have no container duration; then the
+        // streams' own, and failing that, a pass over the whole file
+        let mut duration_seconds = probe_duration(&probe);
+        if duration_seconds.is_none() {
+            warn!("No duration in the probe of {:?}, measuring it", video_path);
+            duration_seconds = self.measure_duration(video_path).await.unwrap_or_else(|e| {
+                warn!("Failed to measure the duration of {:?}: {}", video_path, e);
+                None
             });
+        }
         
         let metadata = VideoMetadata {
             filename: video_path.file_name()
                 .map(|n| n.to_string_lossy().to_string())
                 .unwrap_or_default(),
-            duration_seconds: probe.format.as_ref()
-                .and_then(|f| f.duration.as_ref())
-                .and_then(|d| d.parse().ok()),
+            duration_seconds,
             fps,
             width: video_stream.and_then(|s| s.width),
             height: video_stream.and_then(|s| s.height),
@@ -173,6 +452,7 @@ impl Ffmpeg {
                 .and_then(|s| s.parse().ok()),
             has_audio: audio_stream.is_some(),
             audio_codec: audio_stream.and_then(|s| s.codec_name.clone()),
+            audio_streams: probe.streams.as_deref().map(audio_streams).unwrap_or_default(),
             creation_time: probe.format
                 .and_then(|f| f.tags)
                 .and_then(|t| t.creation_time),
@@ -182,14 +462,39 @@ impl Ffmpeg {
         Ok(metadata)
     }
     
+    /// Duration found by reading the file through to the end, copying the
+    /// first video stream to nowhere rather than decoding it
+    async fn measure_duration(&self, video_path: &Path) -> Result<Option<f64>, FfmpegError> {
+        if !self.ffmpeg_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path()));
+        }
+
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        let output = Command::new(self.ffmpeg_path())
+            .args(["-v", "error", "-nostats", "-progress", "pipe:1", "-i"])
+            .arg(video_path)
+            .args(["-map", "0:v:0", "-c", "copy", "-f", "null", "-"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(FfmpegError::ExecutionFailed(stderr.to_string()));
+        }
+        Ok(progress_duration(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     /// Extract thumbnails from video at fixed intervals
     pub async fn extract_thumbnails(
         &self,
         video_path: &PathBuf,
         output_dir: &PathBuf,
         interval_seconds: f64,
+        format: ImageFormat,
     ) -> Result<Vec<VideoMoment>, FfmpegError> {
-        self.run_extraction(video_path, output_dir, FilterMode::Interval(interval_seconds)).await
+        self.run_extraction(video_path, output_dir, FilterMode::Interval(interval_seconds), format).await
     }
 
     /// Extract key moments using scene detection
@@ -198,8 +503,9 @@ impl Ffmpeg {
         video_path: &PathBuf,
         output_dir: &PathBuf,
         threshold: f32, // 0.0 to 1.0 (0.4 is good default)
+        format: ImageFormat,
     ) -> Result<Vec<VideoMoment>, FfmpegError> {
-        self.run_extraction(video_path, output_dir, FilterMode::Scene(threshold)).await
+        self.run_extraction(video_path, output_dir, FilterMode::Scene(threshold), format).await
     }
 
     async fn run_extraction(
@@ -207,9 +513,10 @@ impl Ffmpeg {
         video_path: &PathBuf,
         output_dir: &PathBuf,
         mode: FilterMode,
+        format: ImageFormat,
     ) -> Result<Vec<VideoMoment>, FfmpegError> {
-        if !self.ffmpeg_path.exists() {
-            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path.clone()));
+        if !self.ffmpeg_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path()));
         }
         
         debug!("Extracting frames from: {:?} (Mode: {:?})", video_path, mode);
@@ -219,51 +526,41 @@ impl Ffmpeg {
             std::fs::create_dir_all(output_dir)?;
         }
 
-        let output_pattern = output_dir.join("thumb_%04d.jpg");
+        let format = self.resolve_image_format(format).await;
+        let output_pattern = output_dir.join(format!("thumb_%04d.{}", format.extension()));
         
         let filter = match mode {
             FilterMode::Interval(seconds) => format!("fps=1/{},showinfo", seconds),
             FilterMode::Scene(threshold) => format!("select='gt(scene,{})',showinfo", threshold),
         };
 
-        let args = vec![
+        let mut args = vec![
             "-i".to_string(),
             video_path.to_string_lossy().to_string(),
             "-vf".to_string(), filter,
             "-vsync".to_string(), "vfr".to_string(),
-            "-q:v".to_string(), "2".to_string(),
+        ];
+        args.extend(format.codec_args().iter().map(|a| a.to_string()));
+        args.extend([
             "-y".to_string(),
             output_pattern.to_string_lossy().to_string(),
-        ];
+        ]);
 
-        let output = Command::new(&self.ffmpeg_path)
-            .args(&args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await?;
-        
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(FfmpegError::ExecutionFailed(stderr.to_string()));
-        }
-
-        // Parse timestamps from stderr
-        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Timestamps come from showinfo's stderr lines, picked out as they arrive
         let mut timestamps: Vec<f64> = Vec::new();
-
-        for line in stderr.lines() {
-            if line.contains("Parsed_showinfo") && line.contains("pts_time:") {
-                // Example: ... pts_time:12.345 ...
-                if let Some(idx) = line.find("pts_time:") {
-                    let rest = &line[idx + 9..];
-                    let end = rest.find(' ').unwrap_or(rest.len());
-                    let val_str = &rest[..end];
-                    if let Ok(ts) = val_str.parse::<f64>() {
-                        timestamps.push(ts);
-                    }
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        let output = sidecar::run(
+            Command::new(self.ffmpeg_path()).args(&args).stdout(Stdio::null()),
+            |line| {
+                if line.contains("Parsed_showinfo") {
+                    timestamps.extend(pts_time(line));
                 }
-            }
+            },
+        )
+        .await?;
+        
+        if !output.status.success() {
+            return Err(FfmpegError::ExecutionFailed(output.stderr));
         }
         
         // Collect generated thumbnails
@@ -273,6 +570,7 @@ impl Ffmpeg {
                 .flatten()
                 .map(|e| e.path())
                 .filter(|p| p.file_name().map(|n| n.to_string_lossy().starts_with("thumb_")).unwrap_or(false))
+                .filter(|p| p.extension().map(|e| e == format.extension()).unwrap_or(false))
                 .collect();
             
             paths.sort(); // thumb_0001, thumb_0002... matches timestamp order
@@ -291,20 +589,59 @@ impl Ffmpeg {
     }
 
     /// Extract audio from video as WAV (for Whisper)
+    ///
+    /// `audio_stream_index` picks a track by its position among the audio
+    /// streams; FFmpeg's default track is used without one.
     pub async fn extract_audio(
         &self,
         video_path: &PathBuf,
         output_path: &PathBuf,
+        audio_stream_index: Option<usize>,
     ) -> Result<(), FfmpegError> {
-        if !self.ffmpeg_path.exists() {
-            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path.clone()));
+        self.extract_audio_range(video_path, output_path, audio_stream_index, None).await
+    }
+
+    /// [`extract_audio`](Self::extract_audio), only `duration_seconds` of
+    /// it from `start_seconds` on
+    pub async fn extract_audio_clip(
+        &self,
+        video_path: &PathBuf,
+        output_path: &PathBuf,
+        audio_stream_index: Option<usize>,
+        start_seconds: f64,
+        duration_seconds: f64,
+    ) -> Result<(), FfmpegError> {
+        let range = (start_seconds, duration_seconds);
+        self.extract_audio_range(video_path, output_path, audio_stream_index, Some(range)).await
+    }
+
+    async fn extract_audio_range(
+        &self,
+        video_path: &PathBuf,
+        output_path: &PathBuf,
+        audio_stream_index: Option<usize>,
+        range: Option<(f64, f64)>,
+    ) -> Result<(), FfmpegError> {
+        if !self.ffmpeg_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path()));
         }
         
-        debug!("Extracting audio from: {:?}", video_path);
+        debug!("Extracting audio from: {:?} (track {:?}, range {:?})", video_path, audio_stream_index, range);
         
-        let output = Command::new(&self.ffmpeg_path)
-            .args(["-i"])
-            .arg(video_path)
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        let mut command = Command::new(self.ffmpeg_path());
+        if let Some((start, _)) = range {
+            // Before the input, so FFmpeg seeks rather than decodes its way there
+            command.args(["-ss", &format!("{:.3}", start)]);
+        }
+        command.args(["-i"]).arg(video_path);
+        if let Some((_, duration)) = range {
+            command.args(["-t", &format!("{:.3}", duration)]);
+        }
+        if let Some(index) = audio_stream_index {
+            command.args(["-map", &format!("0:a:{}", index)]);
+        }
+        command
             .args([
                 "-vn",                  // No video
                 "-acodec", "pcm_s16le", // PCM 16-bit
@@ -313,46 +650,89 @@ impl Ffmpeg {
                 "-y",                   // Overwrite
             ])
             .arg(output_path)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await?;
+            .stdout(Stdio::null());
+        let output = sidecar::run(&mut command, |_| {}).await?;
         
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(FfmpegError::ExecutionFailed(stderr.to_string()));
+            return Err(FfmpegError::ExecutionFailed(output.stderr));
         }
         
         info!("Audio extracted to: {:?}", output_path);
         Ok(())
     }
 
-    /// Capture a single frame at timestamp (ms) and return base64 string
+    /// Capture a single frame at timestamp (ms) and return it as a data URI
+    ///
+    /// The URI's MIME type matches the format actually produced, which is
+    /// JPEG when the FFmpeg build can't encode the requested format.
+    /// `priority` is whether the user is waiting on the frame.
     pub async fn capture_frame(
         &self,
         video_path: &PathBuf,
         timestamp_ms: u64,
+        format: ImageFormat,
+        priority: Priority,
     ) -> Result<String, FfmpegError> {
-        if !self.ffmpeg_path.exists() {
-            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path.clone()));
+        let (bytes, format) = self.capture_frame_bytes(video_path, timestamp_ms, format, priority).await?;
+
+        use base64::{Engine as _, engine::general_purpose};
+        let b64 = general_purpose::STANDARD.encode(&bytes);
+        let data_uri = format!("data:{};base64,{}", format.mime_type(), b64);
+
+        Ok(data_uri)
+    }
+
+    /// Capture a single frame at timestamp (ms) into `output_stem` plus the
+    /// extension of the format produced, returning the written path
+    pub async fn save_frame(
+        &self,
+        video_path: &PathBuf,
+        timestamp_ms: u64,
+        output_stem: &Path,
+        format: ImageFormat,
+    ) -> Result<PathBuf, FfmpegError> {
+        let (bytes, format) = self.capture_frame_bytes(video_path, timestamp_ms, format, Priority::Batch).await?;
+
+        let output_path = output_stem.with_extension(format.extension());
+        if let Some(parent) = output_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&output_path, bytes)?;
+
+        debug!("Saved frame to {:?}", output_path);
+        Ok(output_path)
+    }
+
+    /// Encoded image bytes of the frame at timestamp (ms), with the format used
+    async fn capture_frame_bytes(
+        &self,
+        video_path: &PathBuf,
+        timestamp_ms: u64,
+        format: ImageFormat,
+        priority: Priority,
+    ) -> Result<(Vec<u8>, ImageFormat), FfmpegError> {
+        if !self.ffmpeg_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path()));
         }
 
         let timestamp_seconds = timestamp_ms as f64 / 1000.0;
         debug!("Capturing frame from: {:?} at {}s", video_path, timestamp_seconds);
 
-        // Usage: ffmpeg -ss <time> -i <input> -frames:v 1 -f image2 pipe:1
+        let format = self.resolve_image_format(format).await;
+        
+        // Usage: ffmpeg -ss <time> -i <input> -frames:v 1 -f image2pipe pipe:1
         // Placing -ss before -i is faster (input seeking)
-        let output = Command::new(&self.ffmpeg_path)
+        let _permit = sidecar::acquire(priority).await;
+        let output = Command::new(self.ffmpeg_path())
             .args(["-ss", &timestamp_seconds.to_string()])
             .args(["-i"])
             .arg(video_path)
             .args([
                 "-frames:v", "1",
-                "-f", "image2", // Output format image
-                "-c:v", "mjpeg", // JPEG encoding
-                "-q:v", "2", // High quality
-                "pipe:1", // Output to stdout
+                "-f", "image2pipe", // Single image to a pipe
             ])
+            .args(format.codec_args())
+            .arg("pipe:1") // Output to stdout
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
@@ -363,12 +743,176 @@ impl Ffmpeg {
             return Err(FfmpegError::ExecutionFailed(stderr.to_string()));
         }
 
-        use base64::{Engine as _, engine::general_purpose};
-        let b64 = general_purpose::STANDARD.encode(&output.stdout);
-        let data_uri = format!("data:image/jpeg;base64,{}", b64);
+        if output.stdout.is_empty() {
+            return Err(FfmpegError::ExecutionFailed(format!("No frame at {}s", timestamp_seconds)));
+        }
 
-        Ok(data_uri)
+        Ok((output.stdout, format))
+    }
+
+    /// Timestamp (seconds) of the first scene change within the first
+    /// `max_seconds` of the video, if any
+    pub async fn first_scene_change(
+        &self,
+        video_path: &PathBuf,
+        threshold: f32,
+        max_seconds: f64,
+    ) -> Result<Option<f64>, FfmpegError> {
+        if !self.ffmpeg_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path()));
+        }
+
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        let output = Command::new(self.ffmpeg_path())
+            .args(["-t", &max_seconds.to_string()])
+            .args(["-i"])
+            .arg(video_path)
+            .args([
+                "-vf", &format!("select='gt(scene,{})',showinfo", threshold),
+                "-frames:v", "1",
+                "-f", "null",
+                "-",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(FfmpegError::ExecutionFailed(stderr.to_string()));
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Ok(stderr
+            .lines()
+            .filter(|line| line.contains("Parsed_showinfo"))
+            .find_map(pts_time))
+    }
+
+    /// Copy a video with `chapters` embedded, without re-encoding
+    ///
+    /// Each chapter runs until the next one starts, the last until the end
+    /// of the video. Existing metadata is kept; existing chapters are replaced.
+    pub async fn add_chapters(
+        &self,
+        input: &PathBuf,
+        output: &PathBuf,
+        chapters: &[VideoChapter],
+    ) -> Result<(), FfmpegError> {
+        if !self.ffmpeg_path().exists() {
+            return Err(FfmpegError::BinaryNotFound(self.ffmpeg_path()));
+        }
+        if input == output {
+            return Err(FfmpegError::ExecutionFailed("Output must not overwrite the input video".to_string()));
+        }
+        
+        let duration = self
+            .extract_metadata(input)
+            .await?
+            .duration_seconds
+            .ok_or_else(|| FfmpegError::ParseError("Video has no duration".to_string()))?;
+        validate_chapters(chapters, duration)?;
+        
+        let metadata_path = output.with_extension("ffmetadata");
+        std::fs::write(&metadata_path, chapters_ffmetadata(chapters, duration))?;
+        
+        debug!("Embedding {} chapters into {:?}", chapters.len(), output);
+        
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        let result = Command::new(self.ffmpeg_path())
+            .args(["-i"])
+            .arg(input)
+            .args(["-f", "ffmetadata", "-i"])
+            .arg(&metadata_path)
+            .args([
+                "-map", "0",            // Every stream of the video
+                "-map_metadata", "0",   // Keep its metadata (creation_time is used for GPS sync)
+                "-map_chapters", "1",   // Chapters from the metadata file
+                "-codec", "copy",       // No re-encoding
+                "-y",
+            ])
+            .arg(output)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .output()
+            .await;
+        
+        std::fs::remove_file(&metadata_path).ok();
+        let output_status = result?;
+        
+        if !output_status.status.success() {
+            std::fs::remove_file(output).ok();
+            let stderr = String::from_utf8_lossy(&output_status.stderr);
+            return Err(FfmpegError::ExecutionFailed(stderr.to_string()));
+        }
+        
+        info!("Embedded {} chapters into {:?}", chapters.len(), output);
+        Ok(())
+    }
+}
+
+/// A chapter to embed in a video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoChapter {
+    pub start_seconds: f64,
+    pub title: String,
+}
+
+/// Chapters must start inside the video, in increasing order
+fn validate_chapters(chapters: &[VideoChapter], duration_seconds: f64) -> Result<(), FfmpegError> {
+    if chapters.is_empty() {
+        return Err(FfmpegError::InvalidChapters("no chapters given".to_string()));
+    }
+    
+    let mut previous: Option<&VideoChapter> = None;
+    for chapter in chapters {
+        if !chapter.start_seconds.is_finite() || chapter.start_seconds < 0.0 || chapter.start_seconds >= duration_seconds {
+            return Err(FfmpegError::InvalidChapters(format!(
+                "'{}' starts at {:.1}s, outside the video (0-{:.1}s)",
+                chapter.title, chapter.start_seconds, duration_seconds
+            )));
+        }
+        if let Some(previous) = previous.filter(|p| chapter.start_seconds <= p.start_seconds) {
+            return Err(FfmpegError::InvalidChapters(format!(
+                "'{}' at {:.1}s doesn't start after '{}' at {:.1}s",
+                chapter.title, chapter.start_seconds, previous.title, previous.start_seconds
+            )));
+        }
+        previous = Some(chapter);
+    }
+    
+    Ok(())
+}
+
+/// FFmpeg metadata file describing `chapters`, in milliseconds
+fn chapters_ffmetadata(chapters: &[VideoChapter], duration_seconds: f64) -> String {
+    let to_ms = |seconds: f64| (seconds * 1000.0).round() as u64;
+    let mut out = String::from(";FFMETADATA1\n");
+    
+    for (i, chapter) in chapters.iter().enumerate() {
+        let end = chapters.get(i + 1).map_or(duration_seconds, |next| next.start_seconds);
+        out.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            to_ms(chapter.start_seconds),
+            to_ms(end),
+            escape_ffmetadata(&chapter.title)
+        ));
+    }
+    
+    out
+}
+
+/// Backslash-escape the characters with meaning in FFmpeg metadata files
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
     }
+    escaped
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -377,6 +921,80 @@ pub struct VideoMoment {
     pub timestamp: f64,
 }
 
+/// Frames per second from a rate like `30000/1001` or `30`
+fn parse_frame_rate(rate: &str) -> Option<f64> {
+    let fps = match rate.split_once('/') {
+        Some((num, den)) => {
+            let den: f64 = den.parse().ok()?;
+            if den <= 0.0 {
+                return None;
+            }
+            num.parse::<f64>().ok()? / den
+        }
+        None => rate.parse().ok()?,
+    };
+    (fps.is_finite() && fps > 0.0).then_some(fps)
+}
+
+fn stream_fps(stream: &FfprobeStream) -> Option<f64> {
+    // FFprobe reports "0/0" for rates it doesn't know
+    stream.avg_frame_rate.as_deref().and_then(parse_frame_rate)
+        .or_else(|| stream.r_frame_rate.as_deref().and_then(parse_frame_rate))
+}
+
+/// Duration in seconds from the container, or else from the streams
+///
+/// Tries the container's duration, the video stream's, the longest of the
+/// other streams', Matroska's per-stream `DURATION` tags, and finally the
+/// video's frame count over its frame rate.
+fn probe_duration(probe: &FfprobeOutput) -> Option<f64> {
+    let seconds = |value: &str| value.trim().parse::<f64>().ok().filter(|d| d.is_finite() && *d > 0.0);
+    let streams = probe.streams.as_deref().unwrap_or_default();
+    let video = streams.iter().find(|s| s.codec_type.as_deref() == Some("video"));
+    let longest = |durations: Vec<f64>| durations.into_iter().reduce(f64::max);
+
+    probe.format.as_ref().and_then(|f| f.duration.as_deref()).and_then(seconds)
+        .or_else(|| video.and_then(|v| v.duration.as_deref()).and_then(seconds))
+        .or_else(|| longest(streams.iter().filter_map(|s| s.duration.as_deref().and_then(seconds)).collect()))
+        .or_else(|| {
+            // e.g. "00:01:23.456000000"
+            let tagged = streams.iter().filter_map(|s| s.tags.get("DURATION")).filter_map(|d| timestamp_seconds(d));
+            longest(tagged.filter(|d| *d > 0.0).collect())
+        })
+        .or_else(|| {
+            let video = video?;
+            let frames: f64 = video.nb_frames.as_deref()?.parse().ok()?;
+            let fps = stream_fps(video)?;
+            (frames > 0.0).then(|| frames / fps)
+        })
+}
+
+/// Seconds in an `HH:MM:SS.fraction` timestamp
+fn timestamp_seconds(timestamp: &str) -> Option<f64> {
+    let mut parts = timestamp.trim().splitn(3, ':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// The last position reported in FFmpeg `-progress` output
+fn progress_duration(progress: &str) -> Option<f64> {
+    progress
+        .lines()
+        .filter_map(|line| line.strip_prefix("out_time="))
+        .filter_map(timestamp_seconds)
+        .next_back()
+        .filter(|d| *d > 0.0)
+}
+
+/// The frame time on a `showinfo` line, e.g. `... pts_time:12.345 ...`
+fn pts_time(line: &str) -> Option<f64> {
+    let rest = &line[line.find("pts_time:")? + 9..];
+    let end = rest.find(' ').unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
 #[derive(Debug)]
 enum FilterMode {
     Interval(f64),
@@ -397,4 +1015,136 @@ mod tests {
         let fps = num / den;
         assert!((fps - 29.97).abs() < 0.01);
     }
+
+    #[test]
+    fn test_duration_without_format_duration() {
+        // Fragmented MP4: no container duration, but the streams have theirs
+        let fragmented: FfprobeOutput = serde_json::from_str(r#"{
+            "format": {"filename": "clip.mp4", "size": "1048576"},
+            "streams": [
+                {"codec_type": "audio", "codec_name": "aac", "duration": "61.0"},
+                {"codec_type": "video", "codec_name": "h264", "avg_frame_rate": "30/1", "duration": "60.5"}
+            ]
+        }"#).unwrap();
+        assert_eq!(probe_duration(&fragmented), Some(60.5));
+
+        // Matroska keeps it in a tag
+        let mkv: FfprobeOutput = serde_json::from_str(r#"{"format": {}, "streams": [
+            {"codec_type": "video", "codec_name": "h264", "tags": {"DURATION": "00:01:23.500000000"}}
+        ]}"#).unwrap();
+        assert_eq!(probe_duration(&mkv), Some(83.5));
+
+        // Only the frame count and rate to go on
+        let counted: FfprobeOutput = serde_json::from_str(r#"{"streams": [
+            {"codec_type": "video", "avg_frame_rate": "0/0", "r_frame_rate": "25/1", "nb_frames": "750"}
+        ]}"#).unwrap();
+        assert_eq!(probe_duration(&counted), Some(30.0));
+
+        let nothing: FfprobeOutput = serde_json::from_str(r#"{"format": {"duration": "N/A"}, "streams": []}"#).unwrap();
+        assert_eq!(probe_duration(&nothing), None);
+    }
+
+    #[test]
+    fn test_progress_duration() {
+        let progress = "frame=100\nout_time_us=4000000\nout_time=00:00:04.000000\nprogress=continue\n\
+                        frame=2700\nout_time_us=90080000\nout_time=00:01:30.080000\nprogress=end\n";
+        assert_eq!(progress_duration(progress), Some(90.08));
+        assert_eq!(progress_duration("progress=end\n"), None);
+    }
+
+    #[test]
+    fn test_pts_time() {
+        let line = "[Parsed_showinfo_1 @ 0x7f] n:   3 pts:  12345 pts_time:12.345 duration:1 fmt:yuv420p";
+        assert_eq!(pts_time(line), Some(12.345));
+        assert_eq!(pts_time("[Parsed_showinfo_1 @ 0x7f] n:0 pts_time:7"), Some(7.0));
+        assert_eq!(pts_time("frame=  120 fps=30"), None);
+    }
+
+    #[test]
+    fn test_probe_detects_telemetry() {
+        // A GoPro file: video, audio, timecode and GPMF streams
+        let gopro: FfprobeOutput = serde_json::from_str(r#"{"streams": [
+            {"index": 0, "codec_type": "video", "codec_name": "hevc", "codec_tag_string": "hvc1"},
+            {"index": 1, "codec_type": "audio", "codec_name": "aac", "codec_tag_string": "mp4a"},
+            {"index": 2, "codec_type": "data", "codec_tag_string": "tmcd", "tags": {"handler_name": "GoPro TCD"}},
+            {"index": 3, "codec_type": "data", "codec_name": "bin_data", "codec_tag_string": "gpmd",
+             "tags": {"handler_name": "GoPro MET"}}
+        ]}"#).unwrap();
+        let probe = StreamProbe::from_streams(&gopro.streams.unwrap());
+
+        assert_eq!(probe.streams.len(), 4);
+        assert_eq!(probe.streams[3].kind, "data");
+        assert_eq!(probe.streams[3].tags["handler_name"], "GoPro MET");
+        assert_eq!(probe.telemetry, [TelemetryStream { index: 3, format: TelemetryFormat::Gpmf }]);
+        assert!(probe.gps_telemetry_detected);
+
+        // A subtitle track might be GPS text, but isn't reported as detected
+        let dashcam: FfprobeOutput = serde_json::from_str(r#"{"streams": [
+            {"index": 0, "codec_type": "video", "codec_name": "h264"},
+            {"index": 1, "codec_type": "subtitle", "codec_name": "mov_text", "codec_tag_string": "[0][0][0][0]"}
+        ]}"#).unwrap();
+        let probe = StreamProbe::from_streams(&dashcam.streams.unwrap());
+
+        assert_eq!(probe.streams[1].codec_tag, None);
+        assert_eq!(probe.telemetry, [TelemetryStream { index: 1, format: TelemetryFormat::Subtitle }]);
+        assert!(!probe.gps_telemetry_detected);
+    }
+
+    #[test]
+    fn test_audio_streams() {
+        let probe: FfprobeOutput = serde_json::from_str(r#"{"streams": [
+            {"codec_type": "video", "codec_name": "h264"},
+            {"codec_type": "audio", "codec_name": "aac", "channels": 2,
+             "tags": {"language": "und", "handler_name": "GoPro AAC"}},
+            {"codec_type": "data", "codec_name": "bin_data"},
+            {"codec_type": "audio", "codec_name": "pcm_s24le", "channels": 1,
+             "tags": {"language": "eng", "title": "Lav mic", "handler_name": "SoundHandler"}}
+        ]}"#).unwrap();
+
+        let streams = audio_streams(&probe.streams.unwrap());
+        assert_eq!(streams, [
+            AudioStreamInfo {
+                index: 0,
+                codec: Some("aac".to_string()),
+                channels: Some(2),
+                language: None,
+                title: Some("GoPro AAC".to_string()),
+            },
+            AudioStreamInfo {
+                index: 1,
+                codec: Some("pcm_s24le".to_string()),
+                channels: Some(1),
+                language: Some("eng".to_string()),
+                title: Some("Lav mic".to_string()),
+            },
+        ]);
+    }
+
+    fn chapter(start_seconds: f64, title: &str) -> VideoChapter {
+        VideoChapter { start_seconds, title: title.to_string() }
+    }
+
+    #[test]
+    fn test_chapters_ffmetadata() {
+        let chapters = [chapter(0.0, "Leaving Nice"), chapter(95.5, "Monaco; the harbour = yachts")];
+        let metadata = chapters_ffmetadata(&chapters, 300.0);
+
+        assert_eq!(
+            metadata,
+            ";FFMETADATA1\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=95500\ntitle=Leaving Nice\n\
+             \n[CHAPTER]\nTIMEBASE=1/1000\nSTART=95500\nEND=300000\ntitle=Monaco\\; the harbour \\= yachts\n"
+        );
+    }
+
+    #[test]
+    fn test_validate_chapters() {
+        assert!(validate_chapters(&[chapter(0.0, "a"), chapter(60.0, "b")], 120.0).is_ok());
+
+        assert!(validate_chapters(&[], 120.0).is_err());
+        assert!(validate_chapters(&[chapter(0.0, "a"), chapter(120.0, "b")], 120.0).is_err());
+        assert!(validate_chapters(&[chapter(-1.0, "a")], 120.0).is_err());
+        let err = validate_chapters(&[chapter(60.0, "a"), chapter(30.0, "b")], 120.0).unwrap_err();
+        assert!(err.to_string().contains("doesn't start after 'a'"));
+    }
 }
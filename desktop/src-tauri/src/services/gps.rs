@@ -7,7 +7,7 @@ use std::io::{BufRead, BufReader};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc, TimeZone, NaiveDateTime};
 use thiserror::Error;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 #[derive(Error, Debug)]
 pub enum GpsError {
@@ -20,6 +20,9 @@ pub enum GpsError {
     #[error("Failed to parse NMEA: {0}")]
     NmeaParseError(String),
     
+    #[error("Failed to parse CSV: {0}")]
+    CsvParseError(String),
+    
     #[error("Unknown file format")]
     UnknownFormat,
     
@@ -61,16 +64,307 @@ pub struct GpsBounds {
     pub max_lon: f64,
 }
 
+impl GpsTrack {
+    /// Build a track from already-parsed points (e.g. loaded from the database)
+    pub fn from_points(source_file: String, track_type: &str, mut points: Vec<GpsPoint>) -> Self {
+        points.sort_by_key(|a| a.timestamp);
+        let bounds = (!points.is_empty()).then(|| calculate_bounds(&points));
+
+        Self {
+            name: None,
+            source_file,
+            track_type: track_type.to_string(),
+            point_count: points.len(),
+            start_time: points.first().map(|p| p.timestamp),
+            end_time: points.last().map(|p| p.timestamp),
+            bounds,
+            points,
+        }
+    }
+}
+
+/// Summary statistics for a track
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrackStats {
+    pub distance_km: f64,
+    pub duration_seconds: Option<f64>,
+    pub avg_speed_kmh: Option<f64>,
+    pub max_speed_kmh: Option<f64>,
+    pub elevation_gain_m: Option<f64>,
+    pub elevation_loss_m: Option<f64>,
+}
+
+impl GpsTrack {
+    /// Distance, timing, speed and climb figures for the whole track
+    pub fn stats(&self) -> TrackStats {
+        let distance_km: f64 = self.points
+            .windows(2)
+            .map(|w| haversine_distance(w[0].lat, w[0].lon, w[1].lat, w[1].lon))
+            .sum();
+
+        let duration_seconds = match (self.start_time, self.end_time) {
+            (Some(start), Some(end)) if end > start => Some((end - start).num_milliseconds() as f64 / 1000.0),
+            _ => None,
+        };
+
+        // Prefer recorded speeds, fall back to speeds derived from positions
+        let recorded_max = self.points.iter().filter_map(|p| p.speed_kmh).fold(None, |max: Option<f64>, s| {
+            Some(max.map_or(s, |m| m.max(s)))
+        });
+        let derived_max = self.points.windows(2).filter_map(|w| {
+            let hours = (w[1].timestamp - w[0].timestamp).num_milliseconds() as f64 / 3_600_000.0;
+            (hours > 0.0).then(|| haversine_distance(w[0].lat, w[0].lon, w[1].lat, w[1].lon) / hours)
+        }).fold(None, |max: Option<f64>, s| Some(max.map_or(s, |m| m.max(s))));
+
+        let elevations: Vec<f64> = self.points.iter().filter_map(|p| p.elevation_m).collect();
+        let (gain, loss) = elevations.windows(2).fold((0.0, 0.0), |(gain, loss), w| {
+            let delta = w[1] - w[0];
+            if delta > 0.0 { (gain + delta, loss) } else { (gain, loss - delta) }
+        });
+        let has_elevation = elevations.len() >= 2;
+
+        TrackStats {
+            distance_km,
+            duration_seconds,
+            avg_speed_kmh: duration_seconds.map(|d| distance_km / (d / 3600.0)),
+            max_speed_kmh: recorded_max.or(derived_max),
+            elevation_gain_m: has_elevation.then_some(gain),
+            elevation_loss_m: has_elevation.then_some(loss),
+        }
+    }
+
+    /// Copy of the track reduced with Douglas-Peucker, keeping every point
+    /// that deviates more than `tolerance_m` metres from the simplified line
+    pub fn simplified(&self, tolerance_m: f64) -> GpsTrack {
+        if self.points.len() < 3 || tolerance_m <= 0.0 {
+            return self.clone();
+        }
+
+        // Local equirectangular projection in metres is plenty at track scale
+        let origin = &self.points[0];
+        let metres_per_deg_lat = 111_320.0;
+        let metres_per_deg_lon = metres_per_deg_lat * origin.lat.to_radians().cos();
+        let xy: Vec<(f64, f64)> = self.points
+            .iter()
+            .map(|p| ((p.lon - origin.lon) * metres_per_deg_lon, (p.lat - origin.lat) * metres_per_deg_lat))
+            .collect();
+
+        let mut keep = vec![false; xy.len()];
+        keep[0] = true;
+        keep[xy.len() - 1] = true;
+
+        let mut stack = vec![(0, xy.len() - 1)];
+        while let Some((first, last)) = stack.pop() {
+            let mut max_distance = 0.0;
+            let mut max_index = first;
+            for i in first + 1..last {
+                let d = perpendicular_distance(xy[i], xy[first], xy[last]);
+                if d > max_distance {
+                    max_distance = d;
+                    max_index = i;
+                }
+            }
+
+            if max_distance > tolerance_m {
+                keep[max_index] = true;
+                stack.push((first, max_index));
+                stack.push((max_index, last));
+            }
+        }
+
+        let points = self.points
+            .iter()
+            .zip(keep)
+            .filter(|(_, k)| *k)
+            .map(|(p, _)| p.clone())
+            .collect();
+
+        GpsTrack {
+            name: self.name.clone(),
+            ..GpsTrack::from_points(self.source_file.clone(), &self.track_type, points)
+        }
+    }
+}
+
+/// Distance from `p` to the segment `a`-`b` in projected metres
+fn perpendicular_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length_sq = dx * dx + dy * dy;
+    if length_sq == 0.0 {
+        return ((p.0 - a.0).powi(2) + (p.1 - a.1).powi(2)).sqrt();
+    }
+
+    let t = (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_sq).clamp(0.0, 1.0);
+    let (cx, cy) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()
+}
+
+/// Fixes further apart than this say nothing about the speed between them
+const ALIGN_MAX_GAP_SECONDS: f64 = 30.0;
+
+/// Most samples a speed profile is cut into, to keep matching quick on long tracks
+const ALIGN_MAX_SAMPLES: f64 = 3600.0;
+
+/// Shortest stretch the two tracks must share to be aligned at all
+const ALIGN_MIN_OVERLAP_SECONDS: f64 = 120.0;
+
+/// Shared stretch from which the overlap no longer holds the confidence back
+const ALIGN_FULL_OVERLAP_SECONDS: f64 = 600.0;
+
+/// How one track's clock relates to another's
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackAlignment {
+    /// Seconds to add to the second track's timestamps to put them on the first's clock
+    pub offset_seconds: f64,
+    /// From 0 to 1: how closely the speed profiles match at the offset,
+    /// held back when they share only a short stretch
+    pub confidence: f64,
+    /// Seconds the two tracks share once aligned
+    pub overlap_seconds: f64,
+}
+
+/// Line track `b` up with track `a` by matching their speed over time
+///
+/// For two devices recording the same trip with clocks that disagree.
+/// `None` when the tracks are too short, or move too steadily, to match.
+pub fn align_tracks(a: &GpsTrack, b: &GpsTrack) -> Option<TrackAlignment> {
+    let span = |track: &GpsTrack| {
+        let (first, last) = (track.points.first()?.timestamp, track.points.last()?.timestamp);
+        Some((first, (last - first).num_milliseconds() as f64 / 1000.0))
+    };
+    let ((a_start, a_span), (b_start, b_span)) = (span(a)?, span(b)?);
+    let step = (a_span.max(b_span) / ALIGN_MAX_SAMPLES).max(1.0);
+
+    let min_overlap = (ALIGN_MIN_OVERLAP_SECONDS / step).ceil() as usize;
+    let correlation = super::sync::cross_correlate(&speed_profile(a, step), &speed_profile(b, step), min_overlap)?;
+
+    // b's sample j, at b_start + j * step, is a's sample j + lag, at a_start + (j + lag) * step
+    let starts_apart = (a_start - b_start).num_milliseconds() as f64 / 1000.0;
+    let overlap_seconds = correlation.overlap as f64 * step;
+    let alignment = TrackAlignment {
+        offset_seconds: starts_apart + correlation.lag * step,
+        confidence: correlation.score.max(0.0) * (overlap_seconds / ALIGN_FULL_OVERLAP_SECONDS).min(1.0),
+        overlap_seconds,
+    };
+    debug!("Aligned tracks {} and {}: {:?}", a.source_file, b.source_file, alignment);
+    Some(alignment)
+}
+
+/// Speed in km/h every `step` seconds from the track's first fix; `None`
+/// where the fixes are too far apart to tell
+///
+/// Recorded speeds are used where both fixes around a sample have one,
+/// and speeds worked out from the positions otherwise.
+fn speed_profile(track: &GpsTrack, step: f64) -> Vec<Option<f64>> {
+    let Some(first) = track.points.first().map(|p| p.timestamp) else {
+        return Vec::new();
+    };
+    let seconds = |p: &GpsPoint| (p.timestamp - first).num_milliseconds() as f64 / 1000.0;
+    let span = track.points.last().map_or(0.0, seconds);
+
+    (0..=(span / step) as usize)
+        .map(|k| {
+            let t = k as f64 * step;
+            // The fix at or before the sample, and the one after it
+            let after = track.points.partition_point(|p| seconds(p) <= t).min(track.points.len() - 1);
+            let (p0, p1) = (&track.points[after.saturating_sub(1)], &track.points[after]);
+            let (t0, t1) = (seconds(p0), seconds(p1));
+            let dt = t1 - t0;
+            if dt <= 0.0 || dt > ALIGN_MAX_GAP_SECONDS {
+                return None;
+            }
+            match (p0.speed_kmh, p1.speed_kmh) {
+                (Some(v0), Some(v1)) => Some(v0 + (v1 - v0) * ((t - t0) / dt).clamp(0.0, 1.0)),
+                _ => Some(haversine_distance(p0.lat, p0.lon, p1.lat, p1.lon) / (dt / 3600.0)),
+            }
+        })
+        .collect()
+}
+
+/// Whether `lat` is from -90 to 90 and `lon` from -180 to 180
+pub fn is_valid_coordinate(lat: f64, lon: f64) -> bool {
+    (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)
+}
+
+/// The points with valid coordinates; a corrupt field can put a point
+/// anywhere, and one far off the map breaks the bounds and distances
+fn valid_points(points: Vec<GpsPoint>, format: &str) -> Vec<GpsPoint> {
+    let count = points.len();
+    let valid: Vec<GpsPoint> = points.into_iter().filter(|p| is_valid_coordinate(p.lat, p.lon)).collect();
+    if valid.len() < count {
+        warn!("Skipped {} {} points with coordinates out of range", count - valid.len(), format);
+    }
+    valid
+}
+
+/// Calculate distance between two GPS points in kilometers using the Haversine formula
+pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const R: f64 = 6371.0; // Earth radius in km
+    
+    let lat1_rad = lat1.to_radians();
+    let lat2_rad = lat2.to_radians();
+    let delta_lat = (lat2 - lat1).to_radians();
+    let delta_lon = (lon2 - lon1).to_radians();
+    
+    let a = (delta_lat / 2.0).sin().powi(2)
+        + lat1_rad.cos() * lat2_rad.cos() * (delta_lon / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().asin();
+    
+    R * c
+}
+
+/// Initial bearing from the first point to the second, in degrees clockwise from north
+pub fn initial_bearing(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let lat1_rad = lat1.to_radians();
+    let lat2_rad = lat2.to_radians();
+    let delta_lon = (lon2 - lon1).to_radians();
+    
+    let y = delta_lon.sin() * lat2_rad.cos();
+    let x = lat1_rad.cos() * lat2_rad.sin() - lat1_rad.sin() * lat2_rad.cos() * delta_lon.cos();
+    
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// GPS file formats the parser handles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GpsFileFormat {
+    Gpx,
+    Nmea,
+    Csv,
+}
+
+/// File extensions, lowercase, and the format each is parsed as
+const GPS_FORMATS_BY_EXTENSION: &[(&str, GpsFileFormat)] = &[
+    ("gpx", GpsFileFormat::Gpx),
+    ("nmea", GpsFileFormat::Nmea),
+    ("log", GpsFileFormat::Nmea),
+    ("txt", GpsFileFormat::Nmea),
+    ("csv", GpsFileFormat::Csv),
+];
+
+/// Extensions of the GPS files [`parse_gps_file`] can read
+pub fn gps_file_extensions() -> Vec<&'static str> {
+    GPS_FORMATS_BY_EXTENSION.iter().map(|(extension, _)| *extension).collect()
+}
+
+fn format_for_extension(extension: &str) -> Option<GpsFileFormat> {
+    GPS_FORMATS_BY_EXTENSION
+        .iter()
+        .find(|(known, _)| extension.eq_ignore_ascii_case(known))
+        .map(|(_, format)| *format)
+}
+
 /// Parse GPS file and return track
 pub async fn parse_gps_file(path: &PathBuf) -> Result<GpsTrack, GpsError> {
-    let extension = path.extension()
+    let format = path.extension()
         .and_then(|e| e.to_str())
-        .map(|e| e.to_lowercase());
+        .and_then(format_for_extension);
     
-    match extension.as_deref() {
-        Some("gpx") => parse_gpx(path).await,
-        Some("nmea") | Some("log") | Some("txt") => parse_nmea(path).await,
-        _ => {
+    match format {
+        Some(GpsFileFormat::Gpx) => parse_gpx(path).await,
+        Some(GpsFileFormat::Nmea) => parse_nmea(path).await,
+        Some(GpsFileFormat::Csv) => parse_csv_gps(path, None).await,
+        None => {
             // Try to detect format from content
             let content = std::fs::read_to_string(path)?;
             if content.contains("<gpx") {
@@ -89,39 +383,15 @@ async fn parse_gpx(path: &PathBuf) -> Result<GpsTrack, GpsError> {
     debug!("Parsing GPX file: {:?}", path);
     
     let content = std::fs::read_to_string(path)?;
-    let mut points = Vec::new();
-    let mut name = None;
-    
-    // Simple GPX parser (for production, use a proper XML parser)
-    // This handles basic GPX 1.1 format
-    
-    // Extract track name
-    if let Some(start) = content.find("<name>") {
-        if let Some(end) = content[start..].find("</name>") {
-            name = Some(content[start + 6..start + end].to_string());
-        }
-    }
-    
-    // Parse track points
-    for segment in content.split("<trkpt").skip(1) {
-        if let Some(point) = parse_gpx_point(segment) {
-            points.push(point);
-        }
-    }
-    
-    // Also parse waypoints
-    for segment in content.split("<wpt").skip(1) {
-        if let Some(point) = parse_gpx_point(segment) {
-            points.push(point);
-        }
-    }
+    let (name, points) = parse_gpx_content(&content)?;
+    let mut points = valid_points(points, "GPX");
     
     if points.is_empty() {
         return Err(GpsError::NoPoints);
     }
     
     // Sort by timestamp
-    points.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    points.sort_by_key(|a| a.timestamp);
     
     // Calculate bounds
     let bounds = calculate_bounds(&points);
@@ -142,42 +412,63 @@ async fn parse_gpx(path: &PathBuf) -> Result<GpsTrack, GpsError> {
     })
 }
 
-/// Parse a single GPX track point
-fn parse_gpx_point(segment: &str) -> Option<GpsPoint> {
-    // Extract lat
-    let lat_start = segment.find("lat=\"")? + 5;
-    let lat_end = segment[lat_start..].find('"')? + lat_start;
-    let lat: f64 = segment[lat_start..lat_end].parse().ok()?;
-    
-    // Extract lon
-    let lon_start = segment.find("lon=\"")? + 5;
-    let lon_end = segment[lon_start..].find('"')? + lon_start;
-    let lon: f64 = segment[lon_start..lon_end].parse().ok()?;
-    
-    // Extract elevation
-    let elevation_m = segment.find("<ele>")
-        .and_then(|start| {
-            let end = segment[start..].find("</ele>")?;
-            segment[start + 5..start + end].parse().ok()
-        });
-    
-    // Extract time
-    let timestamp = segment.find("<time>")
-        .and_then(|start| {
-            let end = segment[start..].find("</time>")?;
-            let time_str = &segment[start + 6..start + end];
-            DateTime::parse_from_rfc3339(time_str).ok()
-                .map(|dt| dt.with_timezone(&Utc))
-        })
+/// The track name and points of a GPX 1.0 or 1.1 document
+///
+/// The points are its track points; a file without any has its route
+/// points read instead, then its waypoints. Elements are matched by local
+/// name, so namespace prefixes don't matter, and speed and course are read
+/// wherever they sit under a point: directly in GPX 1.0, or in an
+/// extension such as Garmin's TrackPointExtension in 1.1.
+fn parse_gpx_content(content: &str) -> Result<(Option<String>, Vec<GpsPoint>), GpsError> {
+    let document = roxmltree::Document::parse(content).map_err(|e| GpsError::GpxParseError(e.to_string()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "gpx" {
+        return Err(GpsError::GpxParseError(format!("expected <gpx>, found <{}>", root.tag_name().name())));
+    }
+
+    let name = ["trk", "metadata"].iter().find_map(|parent| {
+        let parent = root.children().find(|n| n.tag_name().name() == *parent)?;
+        child_text(parent, "name").filter(|name| !name.is_empty()).map(str::to_string)
+    });
+
+    let points = ["trkpt", "rtept", "wpt"]
+        .iter()
+        .map(|tag| root.descendants().filter(|n| n.tag_name().name() == *tag).filter_map(parse_gpx_point).collect::<Vec<_>>())
+        .find(|points| !points.is_empty())
+        .unwrap_or_default();
+    Ok((name, points))
+}
+
+/// Trimmed text of `node`'s first child element named `tag`
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    node.children().find(|n| n.tag_name().name() == tag).and_then(|n| n.text()).map(str::trim)
+}
+
+/// Parse a single GPX point element
+fn parse_gpx_point(node: roxmltree::Node) -> Option<GpsPoint> {
+    let lat: f64 = node.attribute("lat")?.trim().parse().ok()?;
+    let lon: f64 = node.attribute("lon")?.trim().parse().ok()?;
+    let elevation_m = child_text(node, "ele").and_then(|ele| ele.parse().ok());
+    let timestamp = child_text(node, "time")
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map(|dt| dt.with_timezone(&Utc))
         .unwrap_or_else(Utc::now);
+
+    // Speed is in m/s in GPX and its extensions alike
+    let nested = |tag: &str| -> Option<f64> {
+        node.descendants()
+            .find(|n| n.tag_name().name() == tag)
+            .and_then(|n| n.text())
+            .and_then(|text| text.trim().parse().ok())
+    };
     
     Some(GpsPoint {
         timestamp,
         lat,
         lon,
         elevation_m,
-        speed_kmh: None,
-        heading_deg: None,
+        speed_kmh: nested("speed").map(|mps| mps * 3.6),
+        heading_deg: nested("course"),
         accuracy_m: None,
     })
 }
@@ -206,13 +497,14 @@ async fn parse_nmea(path: &PathBuf) -> Result<GpsTrack, GpsError> {
             }
         }
     }
+    let mut points = valid_points(points, "NMEA");
     
     if points.is_empty() {
         return Err(GpsError::NoPoints);
     }
     
     // Sort and deduplicate by timestamp
-    points.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    points.sort_by_key(|a| a.timestamp);
     
     let bounds = calculate_bounds(&points);
     
@@ -267,23 +559,8 @@ fn parse_nmea_rmc(line: &str) -> Option<GpsPoint> {
     
     let timestamp = Utc.from_utc_datetime(&naive);
     
-    // Parse latitude
-    let lat_raw: f64 = parts[3].parse().ok()?;
-    let lat_deg = (lat_raw / 100.0).floor();
-    let lat_min = lat_raw - (lat_deg * 100.0);
-    let mut lat = lat_deg + (lat_min / 60.0);
-    if parts[4] == "S" {
-        lat = -lat;
-    }
-    
-    // Parse longitude
-    let lon_raw: f64 = parts[5].parse().ok()?;
-    let lon_deg = (lon_raw / 100.0).floor();
-    let lon_min = lon_raw - (lon_deg * 100.0);
-    let mut lon = lon_deg + (lon_min / 60.0);
-    if parts[6] == "W" {
-        lon = -lon;
-    }
+    let lat = nmea_degrees(parts[3], parts[4] == "S")?;
+    let lon = nmea_degrees(parts[5], parts[6] == "W")?;
     
     // Parse speed (knots to km/h)
     let speed_kmh = parts.get(7)
@@ -333,23 +610,8 @@ fn parse_nmea_gga(line: &str) -> Option<GpsPoint> {
     let naive = today.and_hms_opt(hour, min, sec)?;
     let timestamp = Utc.from_utc_datetime(&naive);
     
-    // Parse latitude
-    let lat_raw: f64 = parts[2].parse().ok()?;
-    let lat_deg = (lat_raw / 100.0).floor();
-    let lat_min = lat_raw - (lat_deg * 100.0);
-    let mut lat = lat_deg + (lat_min / 60.0);
-    if parts[3] == "S" {
-        lat = -lat;
-    }
-    
-    // Parse longitude
-    let lon_raw: f64 = parts[4].parse().ok()?;
-    let lon_deg = (lon_raw / 100.0).floor();
-    let lon_min = lon_raw - (lon_deg * 100.0);
-    let mut lon = lon_deg + (lon_min / 60.0);
-    if parts[5] == "W" {
-        lon = -lon;
-    }
+    let lat = nmea_degrees(parts[2], parts[3] == "S")?;
+    let lon = nmea_degrees(parts[4], parts[5] == "W")?;
     
     // Parse elevation
     let elevation_m = parts.get(9)
@@ -366,6 +628,241 @@ fn parse_nmea_gga(line: &str) -> Option<GpsPoint> {
     })
 }
 
+/// Degrees from an NMEA `dddmm.mmmm` field, negative south or west
+///
+/// Minutes of 60 or more mean the field is corrupt; that's returned as
+/// out of range, so the point is skipped with the others that are.
+fn nmea_degrees(field: &str, negative: bool) -> Option<f64> {
+    let raw: f64 = field.parse().ok()?;
+    let degrees = (raw / 100.0).floor();
+    let minutes = raw - degrees * 100.0;
+    let value = if minutes < 60.0 { degrees + minutes / 60.0 } else { f64::INFINITY };
+    Some(if negative { -value } else { value })
+}
+
+/// Which columns of a CSV log hold which values
+///
+/// Columns are named by their header. Columns left unset are detected from
+/// common header names (`lat`/`latitude`, `lon`/`lng`/`longitude`, ...).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvColumnMapping {
+    pub timestamp: Option<String>,
+    pub lat: Option<String>,
+    pub lon: Option<String>,
+    pub elevation: Option<String>,
+    pub speed: Option<String>,
+    pub heading: Option<String>,
+    pub timestamp_format: CsvTimestampFormat,
+    pub speed_unit: SpeedUnit,
+}
+
+/// How the timestamp column is written
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvTimestampFormat {
+    /// RFC 3339, `YYYY-MM-DD HH:MM:SS`, or epoch seconds/milliseconds by magnitude
+    #[default]
+    Auto,
+    Rfc3339,
+    EpochSeconds,
+    EpochMillis,
+    /// chrono `strftime` pattern, read as UTC unless it includes an offset
+    Pattern(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedUnit {
+    #[default]
+    Kmh,
+    Mps,
+    Mph,
+    Knots,
+}
+
+impl SpeedUnit {
+    fn to_kmh(self, speed: f64) -> f64 {
+        match self {
+            Self::Kmh => speed,
+            Self::Mps => speed * 3.6,
+            Self::Mph => speed * 1.609_344,
+            Self::Knots => speed * 1.852,
+        }
+    }
+}
+
+/// Header names recognized for each column, compared after [`normalize_header`]
+const TIMESTAMP_HEADERS: &[&str] = &["timestamp", "time", "datetime", "date_time", "utc", "gps_time", "time_utc"];
+const LAT_HEADERS: &[&str] = &["lat", "latitude"];
+const LON_HEADERS: &[&str] = &["lon", "lng", "long", "longitude"];
+const ELEVATION_HEADERS: &[&str] = &["ele", "elevation", "alt", "altitude"];
+const SPEED_HEADERS: &[&str] = &["speed", "spd"];
+const HEADING_HEADERS: &[&str] = &["heading", "course", "bearing", "direction"];
+
+/// Parse a CSV GPS log with a header row
+///
+/// Rows whose timestamp or position can't be read are skipped.
+pub async fn parse_csv_gps(path: &PathBuf, mapping: Option<&CsvColumnMapping>) -> Result<GpsTrack, GpsError> {
+    debug!("Parsing CSV GPS file: {:?}", path);
+    
+    let content = std::fs::read_to_string(path)?;
+    let mapping = mapping.cloned().unwrap_or_default();
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    
+    let header_line = lines.next().ok_or(GpsError::NoPoints)?;
+    let delimiter = detect_delimiter(header_line);
+    let headers: Vec<String> = split_csv_line(header_line, delimiter)
+        .iter()
+        .map(|h| normalize_header(h))
+        .collect();
+    
+    let find = |configured: &Option<String>, candidates: &[&str], what: &str| -> Result<Option<usize>, GpsError> {
+        match configured {
+            Some(name) => headers
+                .iter()
+                .position(|h| *h == normalize_header(name))
+                .map(Some)
+                .ok_or_else(|| GpsError::CsvParseError(format!("No column named '{}' for {}", name, what))),
+            None => Ok(headers.iter().position(|h| candidates.contains(&h.as_str()))),
+        }
+    };
+    
+    let required = |column: Option<usize>, what: &str| {
+        column.ok_or_else(|| GpsError::CsvParseError(format!("Couldn't find the {} column; set it in the column mapping", what)))
+    };
+    
+    let timestamp_col = required(find(&mapping.timestamp, TIMESTAMP_HEADERS, "timestamp")?, "timestamp")?;
+    let lat_col = required(find(&mapping.lat, LAT_HEADERS, "latitude")?, "latitude")?;
+    let lon_col = required(find(&mapping.lon, LON_HEADERS, "longitude")?, "longitude")?;
+    let elevation_col = find(&mapping.elevation, ELEVATION_HEADERS, "elevation")?;
+    let speed_col = find(&mapping.speed, SPEED_HEADERS, "speed")?;
+    let heading_col = find(&mapping.heading, HEADING_HEADERS, "heading")?;
+    
+    let mut points = Vec::new();
+    let mut skipped = 0;
+    
+    for line in lines {
+        let fields = split_csv_line(line, delimiter);
+        let number = |col: Option<usize>| {
+            col.and_then(|c| fields.get(c))
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .filter(|v| v.is_finite())
+        };
+        
+        let timestamp = fields
+            .get(timestamp_col)
+            .and_then(|v| parse_csv_timestamp(v.trim(), &mapping.timestamp_format));
+        let lat = number(Some(lat_col)).filter(|v| (-90.0..=90.0).contains(v));
+        let lon = number(Some(lon_col)).filter(|v| (-180.0..=180.0).contains(v));
+        
+        let (Some(timestamp), Some(lat), Some(lon)) = (timestamp, lat, lon) else {
+            skipped += 1;
+            continue;
+        };
+        
+        points.push(GpsPoint {
+            timestamp,
+            lat,
+            lon,
+            elevation_m: number(elevation_col),
+            speed_kmh: number(speed_col).map(|v| mapping.speed_unit.to_kmh(v)),
+            heading_deg: number(heading_col),
+            accuracy_m: None,
+        });
+    }
+    
+    if points.is_empty() {
+        return Err(GpsError::NoPoints);
+    }
+    if skipped > 0 {
+        warn!("Skipped {} CSV rows without a readable time or a position in range", skipped);
+    }
+    
+    info!("Parsed {} GPS points from CSV", points.len());
+    
+    let source_file = path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(GpsTrack::from_points(source_file, "csv", points))
+}
+
+/// Lowercase header without units or quotes, e.g. `"Speed (km/h)"` -> `speed`
+fn normalize_header(header: &str) -> String {
+    header
+        .split(['(', '['])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches('"')
+        .trim()
+        .to_lowercase()
+        .replace([' ', '-'], "_")
+}
+
+/// Comma unless the header has more semicolons or tabs
+fn detect_delimiter(header: &str) -> char {
+    [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| (header.matches(*d).count(), *d == ','))
+        .unwrap_or(',')
+}
+
+/// Split a CSV line, honoring double-quoted fields
+fn split_csv_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+fn parse_csv_timestamp(value: &str, format: &CsvTimestampFormat) -> Option<DateTime<Utc>> {
+    let epoch_millis = |ms: f64| Utc.timestamp_millis_opt(ms.round() as i64).single();
+    
+    match format {
+        CsvTimestampFormat::Rfc3339 => DateTime::parse_from_rfc3339(value).ok().map(|dt| dt.with_timezone(&Utc)),
+        CsvTimestampFormat::EpochSeconds => value.parse::<f64>().ok().and_then(|s| epoch_millis(s * 1000.0)),
+        CsvTimestampFormat::EpochMillis => value.parse::<f64>().ok().and_then(epoch_millis),
+        CsvTimestampFormat::Pattern(pattern) => DateTime::parse_from_str(value, pattern)
+            .map(|dt| dt.with_timezone(&Utc))
+            .or_else(|_| NaiveDateTime::parse_from_str(value, pattern).map(|naive| Utc.from_utc_datetime(&naive)))
+            .ok(),
+        CsvTimestampFormat::Auto => {
+            if let Ok(number) = value.parse::<f64>() {
+                // Epoch seconds stay below 1e11 until the year 5138
+                return if number.abs() >= 1e11 {
+                    epoch_millis(number)
+                } else {
+                    epoch_millis(number * 1000.0)
+                };
+            }
+            DateTime::parse_from_rfc3339(value)
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok()
+                .or_else(|| {
+                    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y/%m/%d %H:%M:%S%.f"]
+                        .iter()
+                        .find_map(|f| NaiveDateTime::parse_from_str(value, f).ok())
+                        .map(|naive| Utc.from_utc_datetime(&naive))
+                })
+        }
+    }
+}
+
 /// Calculate bounding box for points
 fn calculate_bounds(points: &[GpsPoint]) -> GpsBounds {
     let min_lat = points.iter().map(|p| p.lat).fold(f64::INFINITY, f64::min);
@@ -380,3 +877,213 @@ fn calculate_bounds(points: &[GpsPoint]) -> GpsBounds {
         max_lon,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    fn point(seconds: i64, lat: f64, lon: f64, elevation_m: Option<f64>) -> GpsPoint {
+        GpsPoint {
+            timestamp: Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap(),
+            lat,
+            lon,
+            elevation_m,
+            speed_kmh: None,
+            heading_deg: None,
+            accuracy_m: None,
+        }
+    }
+
+    #[test]
+    fn test_align_tracks_recovers_clock_offset() {
+        // Half an hour heading north at a speed that keeps changing
+        let speed_at = |t: f64| 30.0 + 20.0 * (t / 37.0).sin() + 10.0 * (t / 11.0).sin();
+        let mut lat = 45.0;
+        let a_points: Vec<GpsPoint> = (0..1800)
+            .map(|t| {
+                let fix = point(t, lat, 7.0, None);
+                lat += speed_at(t as f64) / 3.6 / 111_320.0;
+                fix
+            })
+            .collect();
+        let a = GpsTrack::from_points("a.gpx".into(), "gpx", a_points.clone());
+
+        // A second device logging every 2 s for twenty minutes, its clock 137 s behind
+        let b_points: Vec<GpsPoint> = a_points[300..1500]
+            .iter()
+            .step_by(2)
+            .map(|p| GpsPoint { timestamp: p.timestamp - chrono::Duration::seconds(137), ..p.clone() })
+            .collect();
+        let b = GpsTrack::from_points("b.gpx".into(), "gpx", b_points);
+
+        let alignment = align_tracks(&a, &b).unwrap();
+        assert!((alignment.offset_seconds - 137.0).abs() < 1.5, "{:?}", alignment);
+        assert!(alignment.confidence > 0.9, "{:?}", alignment);
+        assert!((alignment.overlap_seconds - 1200.0).abs() < 5.0, "{:?}", alignment);
+
+        // Steady speed has nothing to match on
+        let steady: Vec<GpsPoint> = (0..600).map(|t| point(t, 45.0 + t as f64 * 0.0001, 7.0, None)).collect();
+        let steady = GpsTrack::from_points("steady.gpx".into(), "gpx", steady);
+        assert!(align_tracks(&steady, &steady).is_none());
+    }
+
+    #[test]
+    fn test_simplify_drops_collinear_points() {
+        // A straight line north with one 50 m detour east in the middle
+        let mut points: Vec<GpsPoint> = (0..10).map(|i| point(i, 45.0 + i as f64 * 0.001, 7.0, None)).collect();
+        points[5].lon += 50.0 / (111_320.0 * 45f64.to_radians().cos());
+        let track = GpsTrack::from_points("test.gpx".into(), "gpx", points);
+
+        let simplified = track.simplified(10.0);
+        assert_eq!(simplified.point_count, 5);
+        assert_eq!(simplified.points[2].lon, track.points[5].lon);
+
+        // Large tolerance keeps only the endpoints, zero keeps everything
+        assert_eq!(track.simplified(100.0).point_count, 2);
+        assert_eq!(track.simplified(0.0).point_count, 10);
+    }
+
+    #[test]
+    fn test_track_stats() {
+        let points = vec![
+            point(0, 45.0, 7.0, Some(100.0)),
+            point(60, 45.01, 7.0, Some(130.0)),
+            point(120, 45.02, 7.0, Some(110.0)),
+        ];
+        let stats = GpsTrack::from_points("test.gpx".into(), "gpx", points).stats();
+
+        assert!((stats.distance_km - 2.224).abs() < 0.01);
+        assert_eq!(stats.duration_seconds, Some(120.0));
+        assert!((stats.avg_speed_kmh.unwrap() - 66.7).abs() < 0.5);
+        assert_eq!(stats.elevation_gain_m, Some(30.0));
+        assert_eq!(stats.elevation_loss_m, Some(20.0));
+    }
+
+    fn write_csv(content: &str) -> PathBuf {
+        write_file(content, "csv")
+    }
+
+    /// `content` as a file of its own directory, which the test removes
+    fn write_file(content: &str, extension: &str) -> PathBuf {
+        let path = fixtures::temp_dir("gps").join(format!("track.{}", extension));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_nmea_points_out_of_range_are_skipped() {
+        let path = write_file(
+            "$GPRMC,100000,A,4344.304,N,00725.476,E,20.0,90.0,010524,,*00\n\
+             $GPRMC,100001,A,9999.999,N,00725.477,E,20.0,90.0,010524,,*00\n\
+             $GPRMC,100002,A,4344.306,N,18500.000,W,20.0,90.0,010524,,*00\n\
+             $GPRMC,100003,A,4375.000,N,00725.478,E,20.0,90.0,010524,,*00\n\
+             $GPRMC,100004,A,4344.308,N,00725.479,E,20.0,90.0,010524,,*00\n",
+            "nmea",
+        );
+
+        let track = parse_gps_file(&path).await.unwrap();
+        assert_eq!(track.point_count, 2);
+        assert!((track.points[0].lat - 43.7384).abs() < 1e-4, "{}", track.points[0].lat);
+        assert!((track.points[1].lon - 7.4246).abs() < 1e-4, "{}", track.points[1].lon);
+        let bounds = track.bounds.unwrap();
+        assert!(bounds.max_lat < 44.0 && bounds.min_lon > 7.0);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_csv_header_auto_detection() {
+        let path = write_csv(
+            "Time,Latitude,Lng,\"Altitude (m)\",Speed,Course\n\
+             2024-05-01T10:00:00Z,43.7384,7.4246,52.5,36,90\n\
+             2024-05-01 10:00:01,43.7385,7.4247,53,,\n\
+             not a time,43.7386,7.4248,54,36,90\n",
+        );
+
+        let track = parse_gps_file(&path).await.unwrap();
+        assert_eq!(track.track_type, "csv");
+        assert_eq!(track.point_count, 2);
+        assert_eq!(track.points[0].elevation_m, Some(52.5));
+        assert_eq!(track.points[0].speed_kmh, Some(36.0));
+        assert_eq!(track.points[0].heading_deg, Some(90.0));
+        assert_eq!(track.points[1].speed_kmh, None);
+        assert_eq!((track.points[1].timestamp - track.points[0].timestamp).num_seconds(), 1);
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_csv_mapping_with_epoch_millis() {
+        let path = write_csv("t_ms;y;x;v\n1700000000500;45.0;7.0;10\n1700000001500;45.001;7.0;10\n");
+        let mapping = CsvColumnMapping {
+            timestamp: Some("t_ms".into()),
+            lat: Some("y".into()),
+            lon: Some("x".into()),
+            speed: Some("v".into()),
+            timestamp_format: CsvTimestampFormat::EpochMillis,
+            speed_unit: SpeedUnit::Mps,
+            ..Default::default()
+        };
+
+        let track = parse_csv_gps(&path, Some(&mapping)).await.unwrap();
+        assert_eq!(track.point_count, 2);
+        assert_eq!(track.points[0].timestamp.timestamp_millis(), 1_700_000_000_500);
+        assert_eq!(track.points[0].speed_kmh, Some(36.0));
+
+        // Without a mapping the unusual headers aren't recognized
+        let err = parse_csv_gps(&path, None).await.unwrap_err();
+        assert!(err.to_string().contains("timestamp column"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[tokio::test]
+    async fn test_gpx_track_points_with_extensions() {
+        let path = write_file(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1"
+     xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v2">
+  <metadata><name>Metadata name</name></metadata>
+  <wpt lat="43.0" lon="7.0"><name>Not a track point</name></wpt>
+  <trk>
+    <name><![CDATA[Nice & the coast]]></name>
+    <trkseg>
+      <trkpt lat="43.7385" lon="7.4247"><ele>53</ele><time>2024-05-01T10:00:01Z</time></trkpt>
+      <trkpt lat=" 43.7384 " lon="7.4246">
+        <ele>52.5</ele>
+        <time>2024-05-01T10:00:00Z</time>
+        <extensions><gpxtpx:TrackPointExtension><gpxtpx:speed>10</gpxtpx:speed><gpxtpx:course>90.5</gpxtpx:course></gpxtpx:TrackPointExtension></extensions>
+      </trkpt>
+      <trkpt lat="95.0" lon="7.4248"><time>2024-05-01T10:00:02Z</time></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#,
+            "gpx",
+        );
+
+        let track = parse_gps_file(&path).await.unwrap();
+        assert_eq!(track.name.as_deref(), Some("Nice & the coast"));
+        assert_eq!(track.point_count, 2);
+        assert_eq!(track.points[0].elevation_m, Some(52.5));
+        assert_eq!(track.points[0].speed_kmh, Some(36.0));
+        assert_eq!(track.points[0].heading_deg, Some(90.5));
+        assert_eq!(track.points[1].speed_kmh, None);
+
+        // Waypoints stand in for a track only when there's none
+        let waypoints = parse_gpx_content(r#"<gpx version="1.0"><wpt lat="43.0" lon="7.0"><speed>2</speed></wpt></gpx>"#).unwrap();
+        assert_eq!(waypoints.1.len(), 1);
+        assert!(matches!(parse_gpx_content("<kml/>"), Err(GpsError::GpxParseError(_))));
+        assert!(matches!(parse_gpx_content("<gpx><trkpt"), Err(GpsError::GpxParseError(_))));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_extensions_match_dispatch() {
+        assert_eq!(gps_file_extensions(), ["gpx", "nmea", "log", "txt", "csv"]);
+        assert_eq!(format_for_extension("GPX"), Some(GpsFileFormat::Gpx));
+        assert_eq!(format_for_extension("log"), Some(GpsFileFormat::Nmea));
+        assert_eq!(format_for_extension("kml"), None);
+    }
+}
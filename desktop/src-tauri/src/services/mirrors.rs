@@ -0,0 +1,162 @@
+//! Region Download Sources
+//!
+//! Catalog regions can be fetched from Geofabrik or from any server laid out
+//! the same way. Mirrors are URL templates configured in settings and are
+//! tried in order after the primary.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Geofabrik, always the first source
+pub const GEOFABRIK_TEMPLATE: &str = "https://download.geofabrik.de/{path}-latest.osm.pbf";
+
+/// Placeholders a template may use
+///
+/// - `{path}`: Geofabrik-style path, e.g. `north-america/us/california`
+/// - `{id}`: GeoTruth region id, e.g. `us/california`
+/// - `{name}`: last path segment, e.g. `california`
+const PLACEHOLDERS: &[&str] = &["{path}", "{id}", "{name}"];
+
+#[derive(Error, Debug)]
+pub enum MirrorError {
+    #[error("Invalid mirror URL '{0}': {1}")]
+    InvalidTemplate(String, String),
+
+    #[error("Download logic not implemented for: {0}")]
+    UnknownRegion(String),
+}
+
+/// Something able to serve catalog region extracts
+pub trait DownloadProvider: Send + Sync {
+    /// Short name recorded in region metadata
+    fn name(&self) -> String;
+
+    /// Download URL for a region, if this provider carries it
+    fn url(&self, region_id: &str) -> Option<String>;
+}
+
+/// Provider described by a URL template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateProvider {
+    template: String,
+}
+
+impl TemplateProvider {
+    /// Create a provider after checking the template is usable
+    pub fn new(template: impl Into<String>) -> Result<Self, MirrorError> {
+        let template = template.into();
+        validate_template(&template)?;
+        Ok(Self { template })
+    }
+
+    /// The primary Geofabrik provider
+    pub fn geofabrik() -> Self {
+        Self { template: GEOFABRIK_TEMPLATE.to_string() }
+    }
+}
+
+impl DownloadProvider for TemplateProvider {
+    fn name(&self) -> String {
+        reqwest::Url::parse(&self.template.replace(['{', '}'], ""))
+            .ok()
+            .and_then(|url| url.host_str().map(|h| h.to_string()))
+            .unwrap_or_else(|| self.template.clone())
+    }
+
+    fn url(&self, region_id: &str) -> Option<String> {
+        let path = geofabrik_path(region_id)?;
+        let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+        Some(
+            self.template
+                .replace("{path}", &path)
+                .replace("{id}", region_id)
+                .replace("{name}", &name),
+        )
+    }
+}
+
+/// A resolved place to download one region from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionSource {
+    pub name: String,
+    pub url: String,
+}
+
+/// Check that a mirror template is an http(s) URL with a region placeholder
+pub fn validate_template(template: &str) -> Result<(), MirrorError> {
+    let invalid = |reason: &str| MirrorError::InvalidTemplate(template.to_string(), reason.to_string());
+
+    if !PLACEHOLDERS.iter().any(|p| template.contains(p)) {
+        return Err(invalid("must contain {path}, {id} or {name}"));
+    }
+
+    let url = reqwest::Url::parse(&template.replace(['{', '}'], ""))
+        .map_err(|e| invalid(&e.to_string()))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(invalid("must be an http or https URL"));
+    }
+
+    Ok(())
+}
+
+/// Geofabrik-style path for a catalog region id
+pub fn geofabrik_path(region_id: &str) -> Option<String> {
+    if let Some(state) = region_id.strip_prefix("us/") {
+        Some(format!("north-america/us/{}", state))
+    } else if let Some(country) = region_id.strip_prefix("europe/") {
+        Some(format!("europe/{}", country))
+    } else {
+        match region_id {
+            "monaco" => Some("europe/monaco".to_string()),
+            "california" => Some("north-america/us/california".to_string()), // Legacy fallback
+            _ => None,
+        }
+    }
+}
+
+/// Ordered download sources for a region: Geofabrik, then each mirror
+///
+/// Invalid mirror templates are skipped rather than failing the download.
+pub fn sources_for(region_id: &str, mirrors: &[String]) -> Result<Vec<RegionSource>, MirrorError> {
+    let providers = std::iter::once(TemplateProvider::geofabrik())
+        .chain(mirrors.iter().filter_map(|t| TemplateProvider::new(t.as_str()).ok()));
+
+    let sources: Vec<RegionSource> = providers
+        .filter_map(|p| p.url(region_id).map(|url| RegionSource { name: p.name(), url }))
+        .collect();
+
+    if sources.is_empty() {
+        return Err(MirrorError::UnknownRegion(region_id.to_string()));
+    }
+    Ok(sources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sources_in_order() {
+        let mirrors = vec![
+            "https://osm.example.org/extracts/{path}-latest.osm.pbf".to_string(),
+            "not a url".to_string(),
+            "http://nas.local:8080/{name}.osm.pbf".to_string(),
+        ];
+
+        let sources = sources_for("us/california", &mirrors).unwrap();
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].name, "download.geofabrik.de");
+        assert_eq!(sources[0].url, "https://download.geofabrik.de/north-america/us/california-latest.osm.pbf");
+        assert_eq!(sources[1].url, "https://osm.example.org/extracts/north-america/us/california-latest.osm.pbf");
+        assert_eq!(sources[2].name, "nas.local");
+        assert_eq!(sources[2].url, "http://nas.local:8080/california.osm.pbf");
+    }
+
+    #[test]
+    fn test_template_validation() {
+        assert!(validate_template("https://mirror.example/{id}.osm.pbf").is_ok());
+        assert!(validate_template("https://mirror.example/latest.osm.pbf").is_err());
+        assert!(validate_template("ftp://mirror.example/{path}.osm.pbf").is_err());
+        assert!(sources_for("mars/olympus", &[]).is_err());
+    }
+}
@@ -7,8 +7,18 @@ pub mod whisper;
 pub mod database;
 pub mod gps;
 pub mod sync;
+pub mod sun;
+pub mod poi_passes;
 pub mod truth_engine;
 pub mod data_manager;
+pub mod extracts;
+pub mod online_pois;
+pub mod weather;
+pub mod wikipedia;
+pub mod sidecar;
+pub mod pbf;
+pub mod mirrors;
+pub mod temp_files;
 
 pub use ffmpeg::Ffmpeg;
 pub use whisper::{Whisper, WhisperModel};
@@ -0,0 +1,153 @@
+//! Online POIs
+//!
+//! POIs around a point from the Overpass API, for places no downloaded
+//! region covers. Only named features of the kinds a narrator would
+//! mention are asked for, and each takes its category from the first of
+//! its tags that says what it is.
+
+use std::time::Duration;
+use serde::Deserialize;
+use tracing::debug;
+
+use super::database::PoiRecord;
+use super::extracts::{ExtractError, OVERPASS_ENDPOINT};
+
+/// Seconds Overpass may spend on the query, and we on the request
+const QUERY_TIMEOUT_SECS: u64 = 25;
+
+/// Most POIs asked for in one lookup
+const MAX_RESULTS: usize = 100;
+
+/// Tags whose value is the category, in order of preference
+const CATEGORY_TAGS: &[&str] = &["tourism", "historic", "natural", "leisure", "man_made", "amenity"];
+
+#[derive(Deserialize)]
+struct OverpassResponse {
+    #[serde(default)]
+    elements: Vec<Element>,
+}
+
+#[derive(Deserialize)]
+struct Element {
+    id: i64,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    /// Ways and relations come with their center instead of a position
+    center: Option<Center>,
+    #[serde(default)]
+    tags: std::collections::HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Center {
+    lat: f64,
+    lon: f64,
+}
+
+/// Named POIs within `radius_m` of a point
+pub async fn fetch_nearby(lat: f64, lon: f64, radius_m: f64) -> Result<Vec<PoiRecord>, ExtractError> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("GeoTruth/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(QUERY_TIMEOUT_SECS + 5))
+        .build()
+        .map_err(|e| ExtractError::RequestFailed(e.to_string()))?;
+
+    let response = client
+        .post(OVERPASS_ENDPOINT)
+        .form(&[("data", query(lat, lon, radius_m))])
+        .send()
+        .await
+        .map_err(|e| ExtractError::RequestFailed(e.to_string()))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(ExtractError::RateLimited);
+    }
+    if !status.is_success() {
+        return Err(ExtractError::RequestFailed(status.to_string()));
+    }
+
+    let body: OverpassResponse = response
+        .json()
+        .await
+        .map_err(|e| ExtractError::RequestFailed(format!("Unexpected Overpass response: {}", e)))?;
+    let pois = parse_elements(body.elements);
+    debug!("Overpass listed {} POIs within {:.0} m of {}, {}", pois.len(), radius_m, lat, lon);
+    Ok(pois)
+}
+
+/// Overpass QL for the named, categorized features around a point
+fn query(lat: f64, lon: f64, radius_m: f64) -> String {
+    let around = format!("(around:{:.0},{},{})", radius_m, lat, lon);
+    let sets: String = CATEGORY_TAGS.iter().map(|tag| format!("nwr{}[name][{}];", around, tag)).collect();
+    format!("[out:json][timeout:{}];({});out center {};", QUERY_TIMEOUT_SECS, sets, MAX_RESULTS)
+}
+
+fn parse_elements(elements: Vec<Element>) -> Vec<PoiRecord> {
+    elements
+        .into_iter()
+        .filter_map(|element| {
+            let (lat, lon) = match (element.lat, element.lon, &element.center) {
+                (Some(lat), Some(lon), _) => (lat, lon),
+                (_, _, Some(center)) => (center.lat, center.lon),
+                _ => return None,
+            };
+            let name = element.tags.get("name")?.trim().to_string();
+            if name.is_empty() {
+                return None;
+            }
+            Some(PoiRecord {
+                id: element.id.to_string(),
+                name,
+                category: category(&element.tags)?,
+                lat,
+                lon,
+                wikipedia: element.tags.get("wikipedia").cloned(),
+                wikidata: element.tags.get("wikidata").cloned(),
+            })
+        })
+        .collect()
+}
+
+/// `castle` for `historic=castle`, but `historic` for `historic=yes`
+fn category(tags: &std::collections::HashMap<String, String>) -> Option<String> {
+    CATEGORY_TAGS.iter().find_map(|&tag| {
+        let value = tags.get(tag)?;
+        Some(if value == "yes" { tag.to_string() } else { value.to_lowercase() })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_elements() {
+        let json = r#"{"elements": [
+            {"type": "node", "id": 1, "lat": 43.7393, "lon": 7.4283,
+             "tags": {"name": "Casino de Monte-Carlo", "amenity": "casino", "tourism": "attraction"}},
+            {"type": "way", "id": 2, "center": {"lat": 43.7308, "lon": 7.4204},
+             "tags": {"name": "Palais Princier", "historic": "yes", "wikidata": "Q1140624"}},
+            {"type": "node", "id": 3, "lat": 43.73, "lon": 7.42, "tags": {"name": " ", "tourism": "viewpoint"}},
+            {"type": "node", "id": 4, "lat": 43.73, "lon": 7.42, "tags": {"name": "Shop", "shop": "bakery"}},
+            {"type": "relation", "id": 5, "tags": {"name": "Nowhere", "natural": "peak"}}
+        ]}"#;
+        let response: OverpassResponse = serde_json::from_str(json).unwrap();
+
+        let pois = parse_elements(response.elements);
+        assert_eq!(pois.len(), 2);
+        assert_eq!((pois[0].id.as_str(), pois[0].category.as_str()), ("1", "attraction"));
+        assert_eq!((pois[1].name.as_str(), pois[1].category.as_str()), ("Palais Princier", "historic"));
+        assert_eq!((pois[1].lat, pois[1].lon), (43.7308, 7.4204));
+        assert_eq!(pois[1].wikidata.as_deref(), Some("Q1140624"));
+    }
+
+    #[test]
+    fn test_query_asks_for_each_category() {
+        let query = query(43.7393, 7.4283, 500.0);
+        assert!(query.starts_with("[out:json]"));
+        assert!(query.contains("nwr(around:500,43.7393,7.4283)[name][historic];"));
+        assert_eq!(query.matches("nwr(").count(), CATEGORY_TAGS.len());
+        assert!(query.ends_with("out center 100;"));
+    }
+}
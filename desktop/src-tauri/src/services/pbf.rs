@@ -0,0 +1,356 @@
+//! OSM PBF Validation
+//!
+//! Lightweight checks that a downloaded file really is an OSM PBF extract,
+//! by reading the first blob and decoding its HeaderBlock.
+
+use std::io::Read;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// BlobHeaders are capped at 64 KiB by the format
+const MAX_BLOB_HEADER_SIZE: u32 = 64 * 1024;
+
+/// Blobs are capped at 32 MiB by the format
+const MAX_BLOB_SIZE: i64 = 32 * 1024 * 1024;
+
+/// Required features we know how to read
+const SUPPORTED_FEATURES: &[&str] = &["OsmSchema-V0.6", "DenseNodes", "HistoricalInformation"];
+
+#[derive(Error, Debug)]
+pub enum PbfError {
+    #[error("Failed to read file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Not an OSM PBF file: {0}")]
+    InvalidFormat(String),
+
+    #[error("Unsupported required features: {0}")]
+    UnsupportedFeatures(String),
+}
+
+/// Decoded OSM PBF HeaderBlock
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PbfHeader {
+    pub required_features: Vec<String>,
+    pub optional_features: Vec<String>,
+    pub writing_program: Option<String>,
+    pub source: Option<String>,
+    /// Bounding box as (min_lat, min_lon, max_lat, max_lon), if the writer recorded one
+    pub bbox: Option<(f64, f64, f64, f64)>,
+    /// Replication timestamp (seconds since epoch) of the data
+    pub replication_timestamp: Option<i64>,
+}
+
+/// Validate the header of an OSM PBF file
+pub fn validate_pbf(path: &Path) -> Result<PbfHeader, PbfError> {
+    let mut file = std::fs::File::open(path)?;
+
+    // BlobHeader length (big-endian) followed by the BlobHeader itself
+    let mut len_buf = [0u8; 4];
+    file.read_exact(&mut len_buf)
+        .map_err(|_| PbfError::InvalidFormat("file is too short".into()))?;
+    let header_len = u32::from_be_bytes(len_buf);
+    if header_len == 0 || header_len > MAX_BLOB_HEADER_SIZE {
+        return Err(PbfError::InvalidFormat(format!("implausible blob header size {}", header_len)));
+    }
+
+    let mut blob_header = vec![0u8; header_len as usize];
+    file.read_exact(&mut blob_header)
+        .map_err(|_| PbfError::InvalidFormat("truncated blob header".into()))?;
+
+    let mut blob_type = None;
+    let mut data_size = None;
+    for field in Fields::new(&blob_header) {
+        match field? {
+            (1, Value::Bytes(b)) => blob_type = Some(String::from_utf8_lossy(b).to_string()),
+            (3, Value::Varint(v)) => data_size = Some(v as i64),
+            _ => {}
+        }
+    }
+
+    if blob_type.as_deref() != Some("OSMHeader") {
+        return Err(PbfError::InvalidFormat(format!(
+            "first blob is {:?}, expected \"OSMHeader\"",
+            blob_type.unwrap_or_default()
+        )));
+    }
+
+    let data_size = data_size
+        .filter(|s| *s > 0 && *s <= MAX_BLOB_SIZE)
+        .ok_or_else(|| PbfError::InvalidFormat("missing or invalid blob size".into()))?;
+
+    let mut blob = vec![0u8; data_size as usize];
+    file.read_exact(&mut blob)
+        .map_err(|_| PbfError::InvalidFormat("truncated header blob".into()))?;
+
+    let header_block = blob_payload(&blob)?;
+    let header = parse_header_block(&header_block)?;
+
+    if !header.required_features.iter().any(|f| f == "OsmSchema-V0.6") {
+        return Err(PbfError::InvalidFormat("missing OsmSchema-V0.6 feature".into()));
+    }
+
+    let unsupported: Vec<&str> = header.required_features
+        .iter()
+        .map(String::as_str)
+        .filter(|f| !SUPPORTED_FEATURES.contains(f))
+        .collect();
+    if !unsupported.is_empty() {
+        return Err(PbfError::UnsupportedFeatures(unsupported.join(", ")));
+    }
+
+    Ok(header)
+}
+
+/// Extract the uncompressed payload from a Blob message
+fn blob_payload(blob: &[u8]) -> Result<Vec<u8>, PbfError> {
+    for field in Fields::new(blob) {
+        match field? {
+            (1, Value::Bytes(raw)) => return Ok(raw.to_vec()),
+            (3, Value::Bytes(compressed)) => {
+                let mut out = Vec::new();
+                flate2::read::ZlibDecoder::new(compressed)
+                    .read_to_end(&mut out)
+                    .map_err(|e| PbfError::InvalidFormat(format!("corrupt zlib data: {}", e)))?;
+                return Ok(out);
+            }
+            (4..=7, Value::Bytes(_)) => {
+                return Err(PbfError::UnsupportedFeatures("non-zlib blob compression".into()));
+            }
+            _ => {}
+        }
+    }
+
+    Err(PbfError::InvalidFormat("blob has no data".into()))
+}
+
+fn parse_header_block(data: &[u8]) -> Result<PbfHeader, PbfError> {
+    let mut header = PbfHeader::default();
+    let text = |b: &[u8]| String::from_utf8_lossy(b).to_string();
+
+    for field in Fields::new(data) {
+        match field? {
+            (1, Value::Bytes(b)) => header.bbox = Some(parse_bbox(b)?),
+            (4, Value::Bytes(b)) => header.required_features.push(text(b)),
+            (5, Value::Bytes(b)) => header.optional_features.push(text(b)),
+            (16, Value::Bytes(b)) => header.writing_program = Some(text(b)),
+            (17, Value::Bytes(b)) => header.source = Some(text(b)),
+            (32, Value::Varint(v)) => header.replication_timestamp = Some(v as i64),
+            _ => {}
+        }
+    }
+
+    Ok(header)
+}
+
+/// Decode a HeaderBBox (sint64 nanodegrees) into (min_lat, min_lon, max_lat, max_lon)
+fn parse_bbox(data: &[u8]) -> Result<(f64, f64, f64, f64), PbfError> {
+    let mut edges = [None; 4];
+    for field in Fields::new(data) {
+        if let (n @ 1..=4, Value::Varint(v)) = field? {
+            let zigzag = ((v >> 1) as i64) ^ -((v & 1) as i64);
+            edges[n as usize - 1] = Some(zigzag as f64 / 1e9);
+        }
+    }
+
+    match edges {
+        [Some(left), Some(right), Some(top), Some(bottom)] => Ok((bottom, left, top, right)),
+        _ => Err(PbfError::InvalidFormat("incomplete bounding box".into())),
+    }
+}
+
+/// A decoded protobuf field value
+enum Value<'a> {
+    Varint(u64),
+    Fixed,
+    Bytes(&'a [u8]),
+}
+
+/// Minimal protobuf wire-format iterator yielding (field number, value)
+struct Fields<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, PbfError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.data.get(self.pos)
+                .ok_or_else(|| PbfError::InvalidFormat("truncated varint".into()))?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(PbfError::InvalidFormat("varint too long".into()))
+    }
+
+    fn skip(&mut self, n: usize) -> Result<&'a [u8], PbfError> {
+        let end = self.pos.checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| PbfError::InvalidFormat("truncated field".into()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn next_field(&mut self) -> Result<(u32, Value<'a>), PbfError> {
+        let key = self.varint()?;
+        let number = (key >> 3) as u32;
+        let value = match key & 0x7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.skip(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.skip(len)?)
+            }
+            5 => {
+                self.skip(4)?;
+                Value::Fixed
+            }
+            wire => return Err(PbfError::InvalidFormat(format!("unknown wire type {}", wire))),
+        };
+        Ok((number, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u32, Value<'a>), PbfError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let field = self.next_field();
+        if field.is_err() {
+            // Stop after the first malformed field
+            self.pos = self.data.len();
+        }
+        Some(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::io::Write;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut out = Vec::new();
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return out;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+
+    fn field_bytes(number: u32, bytes: &[u8]) -> Vec<u8> {
+        let mut out = varint(((number << 3) | 2) as u64);
+        out.extend(varint(bytes.len() as u64));
+        out.extend_from_slice(bytes);
+        out
+    }
+
+    fn write_pbf(path: &Path, features: &[&str]) {
+        write_pbf_with(path, features, &[]);
+    }
+
+    fn write_pbf_with(path: &Path, features: &[&str], extra: &[u8]) {
+        let mut header_block = extra.to_vec();
+        for f in features {
+            header_block.extend(field_bytes(4, f.as_bytes()));
+        }
+        header_block.extend(field_bytes(16, b"osmium/1.16"));
+
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&header_block).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut blob = vec![0x10]; // raw_size
+        blob.extend(varint(header_block.len() as u64));
+        blob.extend(field_bytes(3, &compressed));
+
+        let mut blob_header = field_bytes(1, b"OSMHeader");
+        blob_header.push(0x18); // datasize
+        blob_header.extend(varint(blob.len() as u64));
+
+        let mut file = Vec::new();
+        file.extend((blob_header.len() as u32).to_be_bytes());
+        file.extend(blob_header);
+        file.extend(blob);
+        std::fs::write(path, file).unwrap();
+    }
+
+    /// `name` in a directory of its own, which the test removes
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        fixtures::temp_dir("pbf").join(name)
+    }
+
+    #[test]
+    fn test_valid_pbf_header() {
+        let path = temp_path("valid.osm.pbf");
+        write_pbf(&path, &["OsmSchema-V0.6", "DenseNodes"]);
+
+        let header = validate_pbf(&path).unwrap();
+        assert_eq!(header.required_features, vec!["OsmSchema-V0.6", "DenseNodes"]);
+        assert_eq!(header.writing_program.as_deref(), Some("osmium/1.16"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_bbox_decoded() {
+        let path = temp_path("bbox.osm.pbf");
+        let sint = |v: i64| varint(((v << 1) ^ (v >> 63)) as u64);
+        let mut bbox = Vec::new();
+        for (number, value) in [(1u64, 7_400_000_000i64), (2, 7_440_000_000), (3, 43_760_000_000), (4, 43_720_000_000)] {
+            bbox.push((number << 3) as u8);
+            bbox.extend(sint(value));
+        }
+        let mut extra = field_bytes(1, &bbox);
+        extra.extend(varint(32 << 3));
+        extra.extend(varint(1_700_000_000));
+        write_pbf_with(&path, &["OsmSchema-V0.6"], &extra);
+
+        let header = validate_pbf(&path).unwrap();
+        assert_eq!(header.bbox, Some((43.72, 7.4, 43.76, 7.44)));
+        assert_eq!(header.replication_timestamp, Some(1_700_000_000));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_html_page_rejected() {
+        let path = temp_path("html.osm.pbf");
+        std::fs::write(&path, b"<!DOCTYPE html><html><body>Too many requests</body></html>").unwrap();
+
+        assert!(matches!(validate_pbf(&path), Err(PbfError::InvalidFormat(_))));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_unsupported_feature_rejected() {
+        let path = temp_path("future.osm.pbf");
+        write_pbf(&path, &["OsmSchema-V0.6", "SomethingNew"]);
+
+        assert!(matches!(validate_pbf(&path), Err(PbfError::UnsupportedFeatures(_))));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
@@ -0,0 +1,287 @@
+//! POI Passes
+//!
+//! Turns the landmarks along a route into moments on the video's timeline:
+//! when the camera starts closing in on one, and when it's as close as it
+//! gets. The narrator can then say "coming up on the left" while it's
+//! coming up, instead of listing what happens to be nearby.
+//!
+//! The track is sampled on the video's timeline once it's aligned; each
+//! POI gets at most one pass, at its minimum distance along the path.
+
+use crate::services::database::PoiRecord;
+use crate::services::gps::{haversine_distance, initial_bearing};
+use crate::types::{EventKind, POIFacts, POI};
+
+/// Seconds of video between track samples
+pub const SAMPLE_SECONDS: f64 = 2.0;
+
+/// Closer than this, the camera is approaching a POI
+pub const APPROACH_RADIUS_M: f64 = 1_500.0;
+
+/// A POI the route never comes this close to isn't passed
+pub const PASS_RADIUS_M: f64 = 400.0;
+
+/// An approach needs this long before the pass to be worth its own event
+const MIN_APPROACH_LEAD_SECONDS: f64 = 10.0;
+
+/// Most POIs to report passes for, the closest ones
+pub const MAX_PASSES: usize = 20;
+
+/// POI categories worth narrating a pass of
+pub const NOTABLE_CATEGORIES: &[&str] = &[
+    "attraction",
+    "beach",
+    "bridge",
+    "castle",
+    "historic",
+    "landmark",
+    "lighthouse",
+    "memorial",
+    "monument",
+    "museum",
+    "national_park",
+    "peak",
+    "ruins",
+    "tourism",
+    "viewpoint",
+    "volcano",
+    "waterfall",
+];
+
+/// Metres per degree of latitude
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Where the camera was, `seconds` into the video
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackSample {
+    pub seconds: f64,
+    pub lat: f64,
+    pub lon: f64,
+    /// Direction of travel, clockwise from true north
+    pub heading_deg: Option<f64>,
+}
+
+/// A moment the camera approaches or passes a POI
+#[derive(Debug, Clone)]
+pub struct PoiPass {
+    /// [`EventKind::Approach`] or [`EventKind::NearestPass`]
+    pub kind: EventKind,
+    /// Where the camera was then
+    pub at: TrackSample,
+    /// Distance and bearing from the camera at the time
+    pub poi: POI,
+}
+
+/// Sample the camera's position every `step` seconds over the video
+///
+/// `position_at` maps a video time in seconds to (lat, lon, heading); times
+/// it has no position for are left out.
+pub fn sample_track(
+    duration_seconds: f64,
+    step: f64,
+    position_at: impl Fn(f64) -> Option<(f64, f64, Option<f64>)>,
+) -> Vec<TrackSample> {
+    let count = (duration_seconds.max(0.0) / step).floor() as usize + 1;
+    (0..count)
+        .filter_map(|i| {
+            let seconds = i as f64 * step;
+            position_at(seconds).map(|(lat, lon, heading_deg)| TrackSample { seconds, lat, lon, heading_deg })
+        })
+        .collect()
+}
+
+/// Bounding box `(min_lat, min_lon, max_lat, max_lon)` of the samples,
+/// widened by `margin_m`
+pub fn bounds(samples: &[TrackSample], margin_m: f64) -> Option<(f64, f64, f64, f64)> {
+    let first = samples.first()?;
+    let (mut min_lat, mut min_lon, mut max_lat, mut max_lon) = (first.lat, first.lon, first.lat, first.lon);
+    for sample in samples {
+        min_lat = min_lat.min(sample.lat);
+        max_lat = max_lat.max(sample.lat);
+        min_lon = min_lon.min(sample.lon);
+        max_lon = max_lon.max(sample.lon);
+    }
+
+    let delta_lat = margin_m / METERS_PER_DEGREE;
+    let widest = min_lat.abs().max(max_lat.abs()).min(89.0);
+    let delta_lon = margin_m / (METERS_PER_DEGREE * widest.to_radians().cos());
+    Some((
+        (min_lat - delta_lat).max(-90.0),
+        (min_lon - delta_lon).max(-180.0),
+        (max_lat + delta_lat).min(90.0),
+        (max_lon + delta_lon).min(180.0),
+    ))
+}
+
+/// The passes of the candidates the route comes within [`PASS_RADIUS_M`]
+/// of, in time order
+///
+/// Each POI passed gets a nearest pass, and an approach when the camera
+/// came from further than [`APPROACH_RADIUS_M`] away long enough before.
+/// Only the [`MAX_PASSES`] closest POIs are kept.
+pub fn find_passes(samples: &[TrackSample], candidates: Vec<PoiRecord>) -> Vec<PoiPass> {
+    if samples.is_empty() {
+        return Vec::new();
+    }
+
+    let mut nearest: Vec<(f64, PoiRecord, TrackSample, Option<TrackSample>)> = candidates
+        .into_iter()
+        .filter_map(|poi| {
+            let distances: Vec<f64> =
+                samples.iter().map(|s| haversine_distance(s.lat, s.lon, poi.lat, poi.lon) * 1000.0).collect();
+            let closest = (0..distances.len()).min_by(|&a, &b| distances[a].total_cmp(&distances[b]))?;
+            let (at, distance) = closest_approach(samples, closest, &poi);
+            if distance > PASS_RADIUS_M {
+                return None;
+            }
+
+            // The stretch within the approach radius leading up to the pass
+            let mut start = closest;
+            while start > 0 && distances[start - 1] <= APPROACH_RADIUS_M {
+                start -= 1;
+            }
+            let approach = (start > 0 && at.seconds - samples[start].seconds >= MIN_APPROACH_LEAD_SECONDS)
+                .then_some(samples[start]);
+            Some((distance, poi, at, approach))
+        })
+        .collect();
+    nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+    nearest.truncate(MAX_PASSES);
+
+    let mut passes = Vec::new();
+    for (_, poi, at, approach) in nearest {
+        if let Some(approach) = approach {
+            passes.push(PoiPass { kind: EventKind::Approach, at: approach, poi: seen_from(&approach, &poi) });
+        }
+        passes.push(PoiPass { kind: EventKind::NearestPass, at, poi: seen_from(&at, &poi) });
+    }
+    passes.sort_by(|a, b| a.at.seconds.total_cmp(&b.at.seconds));
+    passes
+}
+
+/// Where along the path either side of sample `index` the camera is
+/// closest to `poi`, and how close
+///
+/// Samples are far enough apart that the closest point usually lies
+/// between two; it's found on the straight line between them, unless
+/// there's a gap in the track there.
+fn closest_approach(samples: &[TrackSample], index: usize, poi: &PoiRecord) -> (TrackSample, f64) {
+    let sample = samples[index];
+    let mut best = (sample, haversine_distance(sample.lat, sample.lon, poi.lat, poi.lon) * 1000.0);
+    let neighbours = [index.checked_sub(1), Some(index + 1).filter(|&i| i < samples.len())];
+    for other in neighbours.into_iter().flatten().map(|i| samples[i]) {
+        if (other.seconds - sample.seconds).abs() > SAMPLE_SECONDS * 1.5 {
+            continue;
+        }
+        // Flat metres around the sample are close enough over a few seconds of travel
+        let scale = sample.lat.to_radians().cos();
+        let to_xy = |lat: f64, lon: f64| ((lon - sample.lon) * scale * METERS_PER_DEGREE, (lat - sample.lat) * METERS_PER_DEGREE);
+        let (dx, dy) = to_xy(other.lat, other.lon);
+        let (px, py) = to_xy(poi.lat, poi.lon);
+        let length = dx * dx + dy * dy;
+        if length == 0.0 {
+            continue;
+        }
+        let t = ((px * dx + py * dy) / length).clamp(0.0, 1.0);
+        let at = TrackSample {
+            seconds: sample.seconds + t * (other.seconds - sample.seconds),
+            lat: sample.lat + t * (other.lat - sample.lat),
+            lon: sample.lon + t * (other.lon - sample.lon),
+            heading_deg: sample.heading_deg.or(other.heading_deg),
+        };
+        let distance = haversine_distance(at.lat, at.lon, poi.lat, poi.lon) * 1000.0;
+        if distance < best.1 {
+            best = (at, distance);
+        }
+    }
+    best
+}
+
+/// `poi` with its distance and bearing from the camera at `at`
+fn seen_from(at: &TrackSample, poi: &PoiRecord) -> POI {
+    POI {
+        id: poi.id.clone(),
+        name: poi.name.clone(),
+        name_local: None,
+        category: poi.category.clone(),
+        subcategory: None,
+        lat: poi.lat,
+        lon: poi.lon,
+        distance_m: haversine_distance(at.lat, at.lon, poi.lat, poi.lon) * 1000.0,
+        bearing_deg: initial_bearing(at.lat, at.lon, poi.lat, poi.lon),
+        // Which way the camera faces isn't known here
+        in_fov: false,
+        // Straight from the downloaded map data
+        confidence: 1.0,
+        facts: POIFacts::with_articles(poi.wikipedia.clone(), poi.wikidata.clone()),
+        pinned: false,
+    }
+}
+
+/// Where a bearing lies for someone travelling along `heading_deg`:
+/// `ahead`, `on the right`, `behind` or `on the left`
+pub fn relative_direction(heading_deg: f64, bearing_deg: f64) -> &'static str {
+    // Signed angle from the heading, in [-180, 180)
+    let angle = (bearing_deg - heading_deg + 180.0).rem_euclid(360.0) - 180.0;
+    match angle.abs() {
+        a if a <= 30.0 => "ahead",
+        a if a >= 150.0 => "behind",
+        _ if angle > 0.0 => "on the right",
+        _ => "on the left",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poi(id: &str, lat: f64, lon: f64) -> PoiRecord {
+        PoiRecord { id: id.to_string(), name: id.to_string(), category: "landmark".to_string(), lat, lon, ..Default::default() }
+    }
+
+    /// Heading north at about 15 m/s from the equator
+    fn northbound(duration_seconds: f64) -> Vec<TrackSample> {
+        sample_track(duration_seconds, SAMPLE_SECONDS, |t| Some((t * 15.0 / METERS_PER_DEGREE, 0.0, Some(0.0))))
+    }
+
+    #[test]
+    fn test_approach_and_nearest_pass() {
+        let samples = northbound(300.0);
+        // 3 km up the road, 100 m to the east: level with it 201 s in, between two samples
+        let bridge = poi("bridge", 3_015.0 / METERS_PER_DEGREE, 100.0 / METERS_PER_DEGREE);
+        // Never within the pass radius
+        let far = poi("far", 1_000.0 / METERS_PER_DEGREE, 2_000.0 / METERS_PER_DEGREE);
+        // Passed right at the start, so there's no approach
+        let start = poi("start", 0.0, -50.0 / METERS_PER_DEGREE);
+
+        let passes = find_passes(&samples, vec![bridge, far, start]);
+        let found: Vec<(EventKind, &str)> = passes.iter().map(|p| (p.kind, p.poi.id.as_str())).collect();
+        assert_eq!(
+            found,
+            [(EventKind::NearestPass, "start"), (EventKind::Approach, "bridge"), (EventKind::NearestPass, "bridge")]
+        );
+
+        // The approach starts when it comes within 1.5 km
+        let approach = &passes[1];
+        assert!((approach.poi.distance_m - APPROACH_RADIUS_M).abs() < 30.0, "{}", approach.poi.distance_m);
+        assert_eq!(relative_direction(0.0, approach.poi.bearing_deg), "ahead");
+
+        // Closest between two samples, level with it
+        let pass = &passes[2];
+        assert!((pass.at.seconds - 201.0).abs() < 0.1, "{}", pass.at.seconds);
+        assert!((pass.poi.distance_m - 100.0).abs() < 1.0, "{}", pass.poi.distance_m);
+        assert_eq!(relative_direction(0.0, pass.poi.bearing_deg), "on the right");
+        assert_eq!(relative_direction(90.0, pass.poi.bearing_deg), "ahead");
+        assert_eq!(relative_direction(180.0, pass.poi.bearing_deg), "on the left");
+    }
+
+    #[test]
+    fn test_bounds_cover_the_route_and_margin() {
+        let samples = northbound(100.0);
+        let (min_lat, min_lon, max_lat, max_lon) = bounds(&samples, 1_000.0).unwrap();
+        assert!((min_lat + 1_000.0 / METERS_PER_DEGREE).abs() < 1e-9);
+        assert!((max_lat - 2_500.0 / METERS_PER_DEGREE).abs() < 1e-9);
+        assert!(min_lon < -0.008 && max_lon > 0.008);
+        assert!(bounds(&[], 1_000.0).is_none());
+    }
+}
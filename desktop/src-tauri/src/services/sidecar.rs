@@ -0,0 +1,209 @@
+//! Sidecar Process Limits
+//!
+//! Every code path that spawns an FFmpeg/Whisper process takes one of the
+//! resource governor's CPU slots first, so bulk operations queue up instead
+//! of saturating the machine.
+//!
+//! Sidecars can also write a lot: Whisper's progress and FFmpeg's
+//! `showinfo` run to megabytes of stderr on long media. [`run`] reads it
+//! as it comes and keeps only the end, for reporting a failure; the last
+//! lines of it are logged when a sidecar fails, so they're in the log of
+//! the job it ran for.
+
+use std::collections::VecDeque;
+use std::process::{ExitStatus, Stdio};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tracing::warn;
+
+use crate::resources::{self, Priority, Resource, ResourcePermit};
+
+/// Wait for a CPU slot to run a sidecar in; the slot is released when the
+/// permit drops
+pub async fn acquire(priority: Priority) -> ResourcePermit {
+    resources::acquire(Resource::Cpu, priority).await
+}
+
+/// Bytes of a sidecar's stderr kept to report why it failed
+pub const STDERR_TAIL_BYTES: usize = 16 * 1024;
+
+/// Longest stderr line passed on whole; FFmpeg's progress can go on for
+/// a long time without a newline
+const MAX_LINE_BYTES: usize = 4 * 1024;
+
+/// Lines of stderr logged when a sidecar fails
+const LOGGED_STDERR_LINES: usize = 20;
+
+/// How a sidecar finished
+pub struct SidecarOutput {
+    pub status: ExitStatus,
+    /// Everything written to stdout, when it was piped
+    pub stdout: Vec<u8>,
+    /// The end of what was written to stderr
+    pub stderr: String,
+}
+
+/// Run `command` to the end, reading its stderr as it comes
+///
+/// Each stderr line goes to `on_line`, and only the last
+/// [`STDERR_TAIL_BYTES`] are kept. Stdout is kept whole when the caller
+/// pipes it, for sidecars that write their result there. Dropping the
+/// future kills the process, so a cancelled job leaves nothing running.
+/// A process that fails has the end of its stderr logged.
+pub async fn run(command: &mut Command, mut on_line: impl FnMut(&str)) -> std::io::Result<SidecarOutput> {
+    let mut child = command.stderr(Stdio::piped()).kill_on_drop(true).spawn()?;
+    let stdout_pipe = child.stdout.take();
+    let stderr_pipe = child.stderr.take();
+
+    // Both at once, so neither pipe fills up and stalls the process
+    let read_stdout = async {
+        let mut stdout = Vec::new();
+        if let Some(mut pipe) = stdout_pipe {
+            pipe.read_to_end(&mut stdout).await?;
+        }
+        Ok::<_, std::io::Error>(stdout)
+    };
+    let read_stderr = async {
+        let mut tail = Tail::new(STDERR_TAIL_BYTES);
+        let mut lines = Lines::default();
+        if let Some(mut pipe) = stderr_pipe {
+            let mut chunk = [0u8; 8192];
+            loop {
+                let read = pipe.read(&mut chunk).await?;
+                if read == 0 {
+                    break;
+                }
+                tail.push(&chunk[..read]);
+                lines.push(&chunk[..read], &mut on_line);
+            }
+        }
+        lines.finish(&mut on_line);
+        Ok::<_, std::io::Error>(tail)
+    };
+    let (stdout, tail) = tokio::try_join!(read_stdout, read_stderr)?;
+
+    let status = child.wait().await?;
+    let stderr = tail.text();
+    if !status.success() {
+        let program = command.as_std().get_program().to_string_lossy();
+        warn!("{} exited with {}:\n{}", program, status, last_lines(&stderr, LOGGED_STDERR_LINES));
+    }
+    Ok(SidecarOutput { status, stdout, stderr })
+}
+
+/// The last `count` lines of `text`
+fn last_lines(text: &str, count: usize) -> &str {
+    let text = text.trim_end();
+    match text.rmatch_indices('\n').nth(count.saturating_sub(1)) {
+        Some((at, _)) => &text[at + 1..],
+        None => text,
+    }
+}
+
+/// The last bytes written to a stream, up to a capacity
+struct Tail {
+    bytes: VecDeque<u8>,
+    capacity: usize,
+    dropped: u64,
+    /// The bytes kept start a line
+    at_line_start: bool,
+}
+
+impl Tail {
+    fn new(capacity: usize) -> Self {
+        Self { bytes: VecDeque::with_capacity(capacity), capacity, dropped: 0, at_line_start: true }
+    }
+
+    fn push(&mut self, chunk: &[u8]) {
+        self.bytes.extend(chunk);
+        let excess = self.bytes.len().saturating_sub(self.capacity);
+        if excess > 0 {
+            self.at_line_start = self.bytes[excess - 1] == b'\n';
+        }
+        self.bytes.drain(..excess);
+        self.dropped += excess as u64;
+    }
+
+    /// The bytes kept as text, saying how much came before them
+    fn text(&self) -> String {
+        let (front, back) = self.bytes.as_slices();
+        let text = String::from_utf8_lossy(&[front, back].concat()).into_owned();
+        if self.dropped == 0 {
+            return text;
+        }
+        // Start at a line, not in the middle of one
+        let text = match text.split_once('\n') {
+            Some((_, rest)) if !self.at_line_start => rest,
+            _ => text.as_str(),
+        };
+        format!("[{} earlier bytes left out]\n{}", self.dropped, text)
+    }
+}
+
+/// Splits a stream into lines at `\n` or `\r` as it arrives, cutting
+/// overlong ones short
+#[derive(Default)]
+struct Lines {
+    pending: Vec<u8>,
+}
+
+impl Lines {
+    fn push(&mut self, chunk: &[u8], on_line: &mut impl FnMut(&str)) {
+        for &byte in chunk {
+            if byte == b'\n' || byte == b'\r' {
+                self.finish(on_line);
+            } else if self.pending.len() < MAX_LINE_BYTES {
+                self.pending.push(byte);
+            }
+        }
+    }
+
+    /// Pass on the line so far, if there is one
+    fn finish(&mut self, on_line: &mut impl FnMut(&str)) {
+        if !self.pending.is_empty() {
+            on_line(&String::from_utf8_lossy(&self.pending));
+            self.pending.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_keeps_the_end_from_a_line_start() {
+        let mut tail = Tail::new(16);
+        tail.push(b"one\n");
+        assert_eq!(tail.text(), "one\n");
+
+        tail.push(b"two\nthree\nfour\n");
+        assert_eq!(tail.bytes.len(), 16);
+        // What's left of "one" is cut off
+        assert_eq!(tail.text(), "[3 earlier bytes left out]\ntwo\nthree\nfour\n");
+
+        tail.push(b"five\n");
+        assert_eq!(tail.text(), "[8 earlier bytes left out]\nthree\nfour\nfive\n");
+    }
+
+    #[test]
+    fn test_lines_split_across_chunks_and_carriage_returns() {
+        let mut found = Vec::new();
+        let mut lines = Lines::default();
+        let mut on_line = |line: &str| found.push(line.to_string());
+        lines.push(b"frame=1\rframe=2\r\n[Parsed_showinfo_1 @ 0x1] n:0 pts_ti", &mut on_line);
+        lines.push(b"me:1.5 pos\n", &mut on_line);
+        lines.push(&vec![b'x'; MAX_LINE_BYTES + 10], &mut on_line);
+        lines.finish(&mut on_line);
+
+        assert_eq!(found[..3], ["frame=1", "frame=2", "[Parsed_showinfo_1 @ 0x1] n:0 pts_time:1.5 pos"]);
+        assert_eq!(found[3].len(), MAX_LINE_BYTES);
+    }
+
+    #[test]
+    fn test_last_lines() {
+        assert_eq!(last_lines("one\ntwo\nthree\n", 2), "two\nthree");
+        assert_eq!(last_lines("one\ntwo", 5), "one\ntwo");
+        assert_eq!(last_lines("", 3), "");
+    }
+}
@@ -0,0 +1,297 @@
+//! Sun Position
+//!
+//! Where the sun is in the sky at a time and place, and the reverse: the
+//! time of day at which it stood where a frame shows it. The position uses
+//! NOAA's low-precision solar equations, good to a fraction of a degree,
+//! far better than a direction judged from a frame or a shadow.
+
+use chrono::{DateTime, Duration, TimeZone, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Daylight, SunFacts};
+
+/// Height of the sun's centre as its upper edge meets the horizon, allowing
+/// for refraction
+const HORIZON_DEG: f64 = -0.833;
+
+/// The sun's place in the sky, in degrees
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SunPosition {
+    /// Clockwise from true north
+    pub azimuth_deg: f64,
+    /// Above the horizon; negative at night
+    pub elevation_deg: f64,
+}
+
+/// Where the sun appears in a frame, as marked by the user
+///
+/// Bearings are clockwise from the direction the camera points. Either the
+/// sun or the shadows it casts can be given; the sun's direction without its
+/// height leaves the time of day ambiguous only near noon, its height without
+/// its direction always leaves morning and afternoon to choose from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SunObservation {
+    pub sun_bearing_deg: Option<f64>,
+    /// Direction shadows fall, away from the sun
+    pub shadow_bearing_deg: Option<f64>,
+    pub sun_elevation_deg: Option<f64>,
+    /// Length of a shadow over the height of what casts it
+    pub shadow_length_ratio: Option<f64>,
+}
+
+impl SunObservation {
+    /// The sun's direction relative to the camera, in `[0, 360)`
+    pub fn relative_bearing_deg(&self) -> Option<f64> {
+        self.sun_bearing_deg
+            .or_else(|| self.shadow_bearing_deg.map(|b| b + 180.0))
+            .filter(|b| b.is_finite())
+            .map(|b| b.rem_euclid(360.0))
+    }
+
+    /// The sun's height above the horizon
+    pub fn elevation_deg(&self) -> Option<f64> {
+        self.sun_elevation_deg
+            .or_else(|| {
+                let ratio = self.shadow_length_ratio.filter(|r| *r > 0.0)?;
+                Some(ratio.recip().atan().to_degrees())
+            })
+            .filter(|e| e.is_finite() && (0.0..=90.0).contains(e))
+    }
+}
+
+/// Time at which the sun best matched an observation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SunMatch {
+    pub time: DateTime<Utc>,
+    /// Angle between the observed and computed positions, over the parts observed
+    pub error_deg: f64,
+}
+
+/// Position of the sun at `time` seen from `lat`, `lon`
+pub fn sun_position(time: DateTime<Utc>, lat: f64, lon: f64) -> SunPosition {
+    let julian_day = time.timestamp_millis() as f64 / 86_400_000.0 + 2_440_587.5;
+    let t = (julian_day - 2_451_545.0) / 36_525.0;
+
+    let mean_longitude = (280.46646 + t * (36_000.769_83 + t * 0.0003032)).rem_euclid(360.0);
+    let mean_anomaly = 357.52911 + t * (35_999.050_29 - 0.0001537 * t);
+    let eccentricity = 0.016708634 - t * (0.000042037 + 0.0000001267 * t);
+    let m = mean_anomaly.to_radians();
+    let center = m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289;
+
+    let omega = (125.04 - 1934.136 * t).to_radians();
+    let apparent_longitude = (mean_longitude + center - 0.00569 - 0.00478 * omega.sin()).to_radians();
+    let mean_obliquity = 23.0 + (26.0 + (21.448 - t * (46.815 + t * (0.00059 - t * 0.001813))) / 60.0) / 60.0;
+    let obliquity = (mean_obliquity + 0.00256 * omega.cos()).to_radians();
+    let declination = (obliquity.sin() * apparent_longitude.sin()).asin();
+
+    // Equation of time, in minutes
+    let y = (obliquity / 2.0).tan().powi(2);
+    let l0 = mean_longitude.to_radians();
+    let equation_of_time = 4.0
+        * (y * (2.0 * l0).sin() - 2.0 * eccentricity * m.sin()
+            + 4.0 * eccentricity * y * m.sin() * (2.0 * l0).cos()
+            - 0.5 * y * y * (4.0 * l0).sin()
+            - 1.25 * eccentricity * eccentricity * (2.0 * m).sin())
+        .to_degrees();
+
+    let utc_minutes = time.num_seconds_from_midnight() as f64 / 60.0;
+    let solar_minutes = utc_minutes + equation_of_time + 4.0 * lon;
+    let hour_angle = (solar_minutes / 4.0 - 180.0).to_radians();
+
+    let lat = lat.to_radians();
+    let elevation = (lat.sin() * declination.sin() + lat.cos() * declination.cos() * hour_angle.cos()).asin();
+    // From south, positive westward; turned to from north
+    let azimuth = hour_angle.sin().atan2(hour_angle.cos() * lat.sin() - declination.tan() * lat.cos());
+
+    SunPosition {
+        azimuth_deg: (azimuth.to_degrees() + 180.0).rem_euclid(360.0),
+        elevation_deg: elevation.to_degrees(),
+    }
+}
+
+/// Sunrise and sunset on the solar day around `time` at `lat`, `lon`
+///
+/// Either is `None` on a day the sun doesn't rise or doesn't set.
+pub fn sunrise_sunset(time: DateTime<Utc>, lat: f64, lon: f64) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    // Noon by the clock of the meridian, close enough to find the crossings either side of it
+    let offset = Duration::milliseconds((lon / 15.0 * 3_600_000.0) as i64);
+    let Some(midday) = (time + offset).date_naive().and_hms_opt(12, 0, 0) else {
+        return (None, None);
+    };
+    let noon = Utc.from_utc_datetime(&midday) - offset;
+
+    let up = |t: DateTime<Utc>| sun_position(t, lat, lon).elevation_deg > HORIZON_DEG;
+    let half_day = Duration::hours(12);
+    (crossing(noon - half_day, noon, up), crossing(noon, noon + half_day, up))
+}
+
+/// When `up` changes between `from` and `to`, to the minute, if it does
+fn crossing(mut from: DateTime<Utc>, mut to: DateTime<Utc>, up: impl Fn(DateTime<Utc>) -> bool) -> Option<DateTime<Utc>> {
+    let up_at_start = up(from);
+    if up(to) == up_at_start {
+        return None;
+    }
+    while to - from > Duration::seconds(30) {
+        let middle = from + (to - from) / 2;
+        if up(middle) == up_at_start {
+            from = middle;
+        } else {
+            to = middle;
+        }
+    }
+    Some(to)
+}
+
+/// The light the sun gives at `elevation_deg`
+pub fn daylight(elevation_deg: f64) -> Daylight {
+    match elevation_deg {
+        e if e >= 6.0 => Daylight::Day,
+        e if e >= -4.0 => Daylight::GoldenHour,
+        e if e >= -6.0 => Daylight::BlueHour,
+        _ => Daylight::Night,
+    }
+}
+
+/// The sun at `time` seen from `lat`, `lon`, for a Truth Bundle event
+pub fn facts(time: DateTime<Utc>, lat: f64, lon: f64) -> SunFacts {
+    let position = sun_position(time, lat, lon);
+    let (sunrise, sunset) = sunrise_sunset(time, lat, lon);
+    SunFacts {
+        elevation_deg: position.elevation_deg,
+        azimuth_deg: position.azimuth_deg,
+        sunrise,
+        sunset,
+        light: daylight(position.elevation_deg),
+    }
+}
+
+/// The time within `window` of `around` at which the sun best matches
+///
+/// `sun_azimuth_deg` and `sun_elevation_deg` are the observed position; at
+/// least one is needed. Only times with the sun up are considered. Where
+/// several times match about as well, as morning and afternoon do for the
+/// height alone, the one closest to `around` wins.
+pub fn match_time(
+    lat: f64,
+    lon: f64,
+    around: DateTime<Utc>,
+    window: Duration,
+    sun_azimuth_deg: Option<f64>,
+    sun_elevation_deg: Option<f64>,
+) -> Option<SunMatch> {
+    if sun_azimuth_deg.is_none() && sun_elevation_deg.is_none() {
+        return None;
+    }
+
+    let step = Duration::minutes(1);
+    let steps = window.num_minutes();
+    let candidates: Vec<SunMatch> = (-steps..=steps)
+        .filter_map(|i| {
+            let time = around + step * i as i32;
+            let position = sun_position(time, lat, lon);
+            if position.elevation_deg <= 0.0 {
+                return None;
+            }
+            let mut squares = Vec::new();
+            if let Some(azimuth) = sun_azimuth_deg {
+                let diff = (position.azimuth_deg - azimuth + 180.0).rem_euclid(360.0) - 180.0;
+                squares.push(diff * diff);
+            }
+            if let Some(elevation) = sun_elevation_deg {
+                squares.push((position.elevation_deg - elevation).powi(2));
+            }
+            let error_deg = (squares.iter().sum::<f64>() / squares.len() as f64).sqrt();
+            Some(SunMatch { time, error_deg })
+        })
+        .collect();
+
+    // The best time in each stretch that matches; within a degree of the
+    // best of them is as good a match as a marked frame allows
+    let minima: Vec<SunMatch> = (0..candidates.len())
+        .filter(|&i| {
+            let error = candidates[i].error_deg;
+            (i == 0 || candidates[i - 1].error_deg >= error)
+                && !candidates.get(i + 1).is_some_and(|next| next.error_deg <= error)
+        })
+        .map(|i| candidates[i])
+        .collect();
+    let best = minima.iter().map(|c| c.error_deg).reduce(f64::min)?;
+    minima
+        .into_iter()
+        .filter(|c| c.error_deg <= best + 1.0)
+        .min_by_key(|c| (c.time - around).num_seconds().abs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_sun_position() {
+        // Greenwich at noon on the June solstice: high in the south
+        let noon = Utc.with_ymd_and_hms(2024, 6, 21, 12, 0, 0).unwrap();
+        let position = sun_position(noon, 51.4779, 0.0);
+        assert!((position.elevation_deg - 62.0).abs() < 0.5, "{:?}", position);
+        assert!((position.azimuth_deg - 180.0).abs() < 2.0, "{:?}", position);
+
+        // Morning in the east, evening in the west, midnight below the horizon
+        let morning = sun_position(Utc.with_ymd_and_hms(2024, 6, 21, 6, 0, 0).unwrap(), 51.4779, 0.0);
+        assert!((60.0..120.0).contains(&morning.azimuth_deg) && morning.elevation_deg > 0.0);
+        let evening = sun_position(Utc.with_ymd_and_hms(2024, 6, 21, 18, 0, 0).unwrap(), 51.4779, 0.0);
+        assert!((240.0..300.0).contains(&evening.azimuth_deg) && evening.elevation_deg > 0.0);
+        assert!(sun_position(Utc.with_ymd_and_hms(2024, 6, 21, 0, 0, 0).unwrap(), 51.4779, 0.0).elevation_deg < 0.0);
+    }
+
+    #[test]
+    fn test_sunrise_and_sunset() {
+        // Greenwich on the June solstice: up at 03:43, down at 20:21
+        let solstice = Utc.with_ymd_and_hms(2024, 6, 21, 9, 0, 0).unwrap();
+        let (sunrise, sunset) = sunrise_sunset(solstice, 51.4779, 0.0);
+        let near = |time: Option<DateTime<Utc>>, h, m| {
+            let expected = Utc.with_ymd_and_hms(2024, 6, 21, h, m, 0).unwrap();
+            time.is_some_and(|t| (t - expected).num_minutes().abs() <= 3)
+        };
+        assert!(near(sunrise, 3, 43), "{:?}", sunrise);
+        assert!(near(sunset, 20, 21), "{:?}", sunset);
+
+        // Tromsø has midnight sun in June and polar night in December
+        assert_eq!(sunrise_sunset(solstice, 69.65, 18.96), (None, None));
+        let winter = Utc.with_ymd_and_hms(2024, 12, 21, 11, 0, 0).unwrap();
+        assert_eq!(sunrise_sunset(winter, 69.65, 18.96), (None, None));
+
+        let evening = facts(Utc.with_ymd_and_hms(2024, 6, 21, 19, 50, 0).unwrap(), 51.4779, 0.0);
+        assert_eq!(evening.light, Daylight::GoldenHour);
+        assert_eq!(daylight(-5.0), Daylight::BlueHour);
+    }
+
+    #[test]
+    fn test_match_time() {
+        // Flagstaff, 9:30 local (16:30 UTC), with a clock an hour and a half slow
+        let (lat, lon) = (35.1983, -111.6513);
+        let actual = Utc.with_ymd_and_hms(2024, 9, 14, 16, 30, 0).unwrap();
+        let seen = sun_position(actual, lat, lon);
+        let clock = actual - Duration::minutes(90);
+
+        let matched = match_time(lat, lon, clock, Duration::hours(12), Some(seen.azimuth_deg), Some(seen.elevation_deg)).unwrap();
+        assert!((matched.time - actual).num_minutes().abs() <= 1);
+        assert!(matched.error_deg < 0.5);
+
+        // The height alone fits the afternoon too; the clock's morning wins
+        let matched = match_time(lat, lon, clock, Duration::hours(12), None, Some(seen.elevation_deg)).unwrap();
+        assert!((matched.time - actual).num_minutes().abs() <= 10);
+
+        assert_eq!(match_time(lat, lon, clock, Duration::hours(12), None, None), None);
+    }
+
+    #[test]
+    fn test_observation_from_shadows() {
+        let observation = SunObservation { shadow_bearing_deg: Some(270.0), shadow_length_ratio: Some(1.0), ..Default::default() };
+        assert_eq!(observation.relative_bearing_deg(), Some(90.0));
+        assert!((observation.elevation_deg().unwrap() - 45.0).abs() < 1e-9);
+        assert_eq!(SunObservation::default().elevation_deg(), None);
+    }
+}
@@ -8,7 +8,17 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, info};
 
-use super::gps::{GpsPoint, GpsTrack};
+use super::gps::{initial_bearing, GpsPoint, GpsTrack};
+use super::sun::{self, SunObservation};
+use super::truth_engine::camera_bearing;
+
+/// Highest confidence of an offset estimated from the sun
+///
+/// Low, since it rests on a direction and height judged by eye.
+const SUN_SYNC_MAX_CONFIDENCE: f64 = 0.3;
+
+/// Sun positions further than this from the observed one aren't a match
+const SUN_SYNC_MAX_ERROR_DEG: f64 = 20.0;
 
 #[derive(Error, Debug)]
 pub enum SyncError {
@@ -43,10 +53,54 @@ pub enum SyncMethod {
     FirstGpsPoint,
     /// User-provided offset
     Manual,
-    /// AI-detected sync point (future)
+    /// Estimated from the content of the footage, e.g. the sun's position
     AutoDetect,
 }
 
+/// Limits on estimating positions between and around GPS fixes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InterpolationPolicy {
+    /// No position when the closest fix on either side is further away than this
+    pub max_gap_seconds: f64,
+    /// How far before the first or after the last fix its position is still used
+    pub extrapolation_tolerance_seconds: f64,
+}
+
+impl Default for InterpolationPolicy {
+    fn default() -> Self {
+        Self {
+            max_gap_seconds: 30.0,
+            extrapolation_tolerance_seconds: 2.0,
+        }
+    }
+}
+
+impl InterpolationPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        let valid = |v: f64| v.is_finite() && v >= 0.0;
+        if !valid(self.max_gap_seconds) || !valid(self.extrapolation_tolerance_seconds) {
+            return Err("Interpolation limits must be zero or more seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// An offset suggested from the sun's position in a frame, for the user to accept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SunSyncSuggestion {
+    /// In the `SyncResult` convention, to pass to a manual resync
+    pub offset_seconds: f64,
+    pub confidence: f64,
+    pub method: SyncMethod,
+    /// When the frame was taken, going by the sun
+    pub estimated_time: DateTime<Utc>,
+    /// How far the sun then was from where it was observed, in degrees
+    pub error_deg: f64,
+    /// Whether the sun's direction was used, rather than only its height
+    pub used_direction: bool,
+}
+
 /// A GPS point aligned to video time
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlignedPoint {
@@ -54,27 +108,121 @@ pub struct AlignedPoint {
     pub gps: GpsPoint,
 }
 
+/// How well two evenly sampled signals match at the lag where they match best
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correlation {
+    /// Samples `b` is shifted by, so `b[j]` lines up with `a[j + lag]`;
+    /// between whole samples when the peak falls between them
+    pub lag: f64,
+    /// Pearson correlation over the overlap at the best whole-sample lag, from -1 to 1
+    pub score: f64,
+    /// Samples present in both signals at that lag
+    pub overlap: usize,
+}
+
+/// The lag at which `b` best matches `a`, for automatic alignment
+///
+/// Only lags with at least `min_overlap` samples present in both count,
+/// and missing samples are left out. Where the signals don't vary over
+/// the overlap there's nothing to match, so that lag doesn't count either.
+pub fn cross_correlate(a: &[Option<f64>], b: &[Option<f64>], min_overlap: usize) -> Option<Correlation> {
+    let min_overlap = min_overlap.max(2);
+    let first_lag = -(b.len() as isize) + 1;
+    let scores: Vec<Option<(f64, usize)>> = (first_lag..a.len() as isize)
+        .map(|lag| {
+            let pairs: Vec<(f64, f64)> = (0..b.len() as isize)
+                .filter_map(|j| Some((a.get(usize::try_from(j + lag).ok()?).copied()??, b[j as usize]?)))
+                .collect();
+            (pairs.len() >= min_overlap).then(|| pearson(&pairs)).flatten().map(|r| (r, pairs.len()))
+        })
+        .collect();
+
+    let (best, &(score, overlap)) = scores
+        .iter()
+        .enumerate()
+        .filter_map(|(i, s)| s.as_ref().map(|s| (i, s)))
+        .max_by(|(_, x), (_, y)| x.0.total_cmp(&y.0))?;
+
+    // A parabola through the peak and its neighbours puts it between samples
+    let mut lag = (best as isize + first_lag) as f64;
+    let neighbour = |i: Option<usize>| i.and_then(|i| scores.get(i).copied().flatten()).map(|(r, _)| r);
+    if let (Some(before), Some(after)) = (neighbour(best.checked_sub(1)), neighbour(Some(best + 1))) {
+        let curvature = before - 2.0 * score + after;
+        if curvature < 0.0 {
+            lag += (0.5 * (before - after) / curvature).clamp(-0.5, 0.5);
+        }
+    }
+    Some(Correlation { lag, score, overlap })
+}
+
+/// Pearson correlation of the pairs; `None` when either side doesn't vary
+/// by more than rounding noise
+fn pearson(pairs: &[(f64, f64)]) -> Option<f64> {
+    let n = pairs.len() as f64;
+    let (mean_x, mean_y) = pairs.iter().fold((0.0, 0.0), |(x, y), (a, b)| (x + a / n, y + b / n));
+    let (mut covariance, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        let (dx, dy) = (x - mean_x, y - mean_y);
+        covariance += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+    // Spread below a millionth of the signal's size is noise, not variation
+    let varies = |variance: f64, mean: f64| (variance / n).sqrt() > 1e-6 * mean.abs().max(1.0);
+    (varies(var_x, mean_x) && varies(var_y, mean_y)).then(|| covariance / (var_x * var_y).sqrt())
+}
+
 /// Time sync engine
 pub struct TimeSyncEngine {
     gps_track: GpsTrack,
     video_duration_seconds: f64,
     video_start_time: Option<DateTime<Utc>>,
+    policy: InterpolationPolicy,
 }
 
 impl TimeSyncEngine {
     /// Create new sync engine
+    ///
+    /// Some containers don't report a duration. When `video_duration_seconds`
+    /// is `None` (or not a positive number) the GPS track span is used
+    /// instead, on the assumption that the recording covers the track. If the
+    /// track has no usable span either, every point at or after the start is
+    /// accepted.
     pub fn new(
         gps_track: GpsTrack,
-        video_duration_seconds: f64,
+        video_duration_seconds: Option<f64>,
         video_start_time: Option<DateTime<Utc>>,
     ) -> Self {
+        let video_duration_seconds = match video_duration_seconds {
+            Some(d) if d.is_finite() && d > 0.0 => d,
+            _ => {
+                let fallback = gps_span_seconds(&gps_track)
+                    .filter(|span| *span > 0.0)
+                    .unwrap_or(f64::INFINITY);
+                debug!("Video duration unknown, using GPS span: {} seconds", fallback);
+                fallback
+            }
+        };
+
         Self {
             gps_track,
             video_duration_seconds,
             video_start_time,
+            policy: InterpolationPolicy::default(),
         }
     }
     
+    /// Use `policy` instead of the default interpolation limits
+    pub fn with_policy(mut self, policy: InterpolationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Effective video duration used for alignment
+    pub fn video_duration_seconds(&self) -> f64 {
+        self.video_duration_seconds
+    }
+    
     /// Synchronize GPS track to video timeline
     pub fn synchronize(&self) -> Result<SyncResult, SyncError> {
         if self.gps_track.points.is_empty() {
@@ -90,6 +238,104 @@ impl TimeSyncEngine {
         self.sync_by_first_point()
     }
     
+    /// Synchronize with a user-provided offset
+    ///
+    /// `offset_seconds` follows the `SyncResult` convention: the video time at
+    /// which the GPS track starts (negative if the track starts before the video).
+    pub fn synchronize_with_offset(&self, offset_seconds: f64) -> Result<SyncResult, SyncError> {
+        let gps_start = self.gps_track.start_time
+            .ok_or(SyncError::NoGpsPoints)?;
+        
+        let aligned_points: Vec<AlignedPoint> = self.gps_track.points
+            .iter()
+            .filter_map(|point| {
+                let video_time = (point.timestamp - gps_start).num_milliseconds() as f64 / 1000.0
+                    + offset_seconds;
+                
+                if video_time >= 0.0 && video_time <= self.video_duration_seconds {
+                    Some(AlignedPoint {
+                        video_time_seconds: video_time,
+                        gps: point.clone(),
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        
+        if aligned_points.is_empty() {
+            return Err(SyncError::NoOverlap);
+        }
+        
+        info!("Manual sync: offset = {} seconds, {} aligned points", offset_seconds, aligned_points.len());
+        
+        Ok(SyncResult {
+            offset_seconds,
+            confidence: 1.0, // The user chose this alignment
+            method: SyncMethod::Manual,
+            aligned_points,
+        })
+    }
+    
+    /// Suggest an offset from where the sun appears at `video_time_seconds`
+    ///
+    /// The time of day at which the sun stood there is searched for within
+    /// twelve hours of the time the current alignment gives the frame, from
+    /// the position there. The sun's direction relative to the camera only
+    /// counts when the GPS heading is known; `camera_heading_offset_deg` is
+    /// the camera's direction relative to travel. A coarse estimate at best:
+    /// expect it to be off by tens of minutes.
+    pub fn suggest_offset_from_sun(
+        &self,
+        video_time_seconds: f64,
+        observation: &SunObservation,
+        camera_heading_offset_deg: f64,
+    ) -> Result<SunSyncSuggestion, SyncError> {
+        let gps_start = self.gps_track.start_time.ok_or(SyncError::NoGpsPoints)?;
+        let current_offset = self.synchronize().map(|r| r.offset_seconds).unwrap_or(0.0);
+        let frame_time = gps_start + chrono::Duration::milliseconds(((video_time_seconds - current_offset) * 1000.0) as i64);
+
+        let point = self.gps_track.points
+            .iter()
+            .min_by_key(|p| (p.timestamp - frame_time).num_milliseconds().abs())
+            .ok_or(SyncError::NoGpsPoints)?;
+        let sun_azimuth = observation
+            .relative_bearing_deg()
+            .zip(point.heading_deg)
+            .map(|(relative, heading)| (camera_bearing(heading, camera_heading_offset_deg) + relative).rem_euclid(360.0));
+        let sun_elevation = observation.elevation_deg();
+        if sun_azimuth.is_none() && sun_elevation.is_none() {
+            return Err(SyncError::SyncFailed(
+                "Mark the sun's height, or its direction on footage with a GPS heading".into(),
+            ));
+        }
+
+        let matched = sun::match_time(point.lat, point.lon, frame_time, chrono::Duration::hours(12), sun_azimuth, sun_elevation)
+            .filter(|m| m.error_deg <= SUN_SYNC_MAX_ERROR_DEG)
+            .ok_or_else(|| SyncError::SyncFailed("The sun is never where it was marked on this day and place".into()))?;
+
+        // The frame at `video_time_seconds` was taken at `matched.time`
+        let offset_seconds = video_time_seconds - (matched.time - gps_start).num_milliseconds() as f64 / 1000.0;
+        let mut confidence = SUN_SYNC_MAX_CONFIDENCE * (1.0 - matched.error_deg / SUN_SYNC_MAX_ERROR_DEG);
+        if sun_azimuth.is_none() {
+            // Morning or afternoon was a guess
+            confidence /= 2.0;
+        }
+
+        info!(
+            "Sun sync: frame at {}s taken around {} (error {:.1} deg), offset {} seconds",
+            video_time_seconds, matched.time, matched.error_deg, offset_seconds
+        );
+        Ok(SunSyncSuggestion {
+            offset_seconds,
+            confidence,
+            method: SyncMethod::AutoDetect,
+            estimated_time: matched.time,
+            error_deg: matched.error_deg,
+            used_direction: sun_azimuth.is_some(),
+        })
+    }
+
     /// Sync using video creation time metadata
     fn sync_by_video_metadata(&self) -> Option<SyncResult> {
         let video_start = self.video_start_time?;
@@ -99,7 +345,7 @@ impl TimeSyncEngine {
         
         debug!("Video metadata sync: offset = {} seconds", offset);
         
-        let aligned_points = self.align_points(offset);
+        let aligned_points = self.align_points_from_start(video_start);
         
         if aligned_points.is_empty() {
             return None;
@@ -137,49 +383,29 @@ impl TimeSyncEngine {
         })
     }
     
-    /// Align GPS points to video timeline with offset
-    fn align_points(&self, offset_seconds: f64) -> Vec<AlignedPoint> {
-        let video_start = match self.video_start_time {
-            Some(t) => t,
-            None => return vec![],
-        };
-        
-        self.gps_track.points
-            .iter()
-            .filter_map(|point| {
-                let point_offset = (point.timestamp - video_start).num_milliseconds() as f64 / 1000.0;
-                let video_time = point_offset - offset_seconds;
-                
-                // Only include points within video duration
-                if video_time >= 0.0 && video_time <= self.video_duration_seconds {
-                    Some(AlignedPoint {
-                        video_time_seconds: video_time,
-                        gps: point.clone(),
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect()
+    /// Align points by their time since `start`, when the video started
+    fn align_points_from_start(&self, start: DateTime<Utc>) -> Vec<AlignedPoint> {
+        self.align_within_video(|point| (point.timestamp - start).num_milliseconds() as f64 / 1000.0)
     }
     
-    /// Align points assuming GPS track starts at video start
-    fn align_points_from_start(&self, gps_start: DateTime<Utc>) -> Vec<AlignedPoint> {
-        self.gps_track.points
+    /// Points whose video time falls within the video, logging how many don't
+    fn align_within_video(&self, video_time: impl Fn(&GpsPoint) -> f64) -> Vec<AlignedPoint> {
+        let aligned: Vec<AlignedPoint> = self.gps_track.points
             .iter()
             .filter_map(|point| {
-                let video_time = (point.timestamp - gps_start).num_milliseconds() as f64 / 1000.0;
-                
-                if video_time >= 0.0 && video_time <= self.video_duration_seconds {
-                    Some(AlignedPoint {
-                        video_time_seconds: video_time,
-                        gps: point.clone(),
-                    })
-                } else {
-                    None
-                }
+                let video_time = video_time(point);
+                (video_time >= 0.0 && video_time <= self.video_duration_seconds).then(|| AlignedPoint {
+                    video_time_seconds: video_time,
+                    gps: point.clone(),
+                })
             })
-            .collect()
+            .collect();
+        
+        let dropped = self.gps_track.points.len() - aligned.len();
+        if dropped > 0 {
+            debug!("{} of {} GPS points fall outside the video and were dropped", dropped, self.gps_track.points.len());
+        }
+        aligned
     }
     
     /// Get GPS point at specific video time
@@ -190,12 +416,16 @@ impl TimeSyncEngine {
             .min_by(|a, b| {
                 let diff_a = (a.video_time_seconds - video_time_seconds).abs();
                 let diff_b = (b.video_time_seconds - video_time_seconds).abs();
-                diff_a.partial_cmp(&diff_b).unwrap()
+                diff_a.total_cmp(&diff_b)
             })
             .map(|p| p.gps.clone())
     }
     
     /// Interpolate GPS position at specific video time
+    ///
+    /// Returns `None` inside GPS dropouts longer than the policy's max gap,
+    /// and before the first or after the last fix beyond its tolerance, so
+    /// events aren't pinned to stale positions.
     pub fn interpolate_position(
         &self, 
         sync_result: &SyncResult, 
@@ -219,32 +449,66 @@ impl TimeSyncEngine {
             }
         }
         
+        let policy = &self.policy;
         match (before, after) {
             (Some(b), Some(a)) => {
+                let nearest = (video_time_seconds - b.video_time_seconds)
+                    .min(a.video_time_seconds - video_time_seconds);
+                if nearest > policy.max_gap_seconds {
+                    return None;
+                }
+                
+                let span = a.video_time_seconds - b.video_time_seconds;
+                
+                // Points sharing a timestamp would divide by zero and yield NaN
+                if span.abs() < f64::EPSILON {
+                    return Some((b.gps.lat, b.gps.lon, b.gps.heading_deg));
+                }
+                
                 // Linear interpolation
-                let t = (video_time_seconds - b.video_time_seconds) 
-                    / (a.video_time_seconds - b.video_time_seconds);
+                let t = ((video_time_seconds - b.video_time_seconds) / span).clamp(0.0, 1.0);
                 
                 let lat = b.gps.lat + t * (a.gps.lat - b.gps.lat);
                 let lon = b.gps.lon + t * (a.gps.lon - b.gps.lon);
                 let heading = match (b.gps.heading_deg, a.gps.heading_deg) {
                     (Some(h1), Some(h2)) => Some(h1 + t * (h2 - h1)),
                     (Some(h), None) | (None, Some(h)) => Some(h),
+                    // Tracks without headings, e.g. most GPX files, go the way they move
+                    _ if (b.gps.lat, b.gps.lon) != (a.gps.lat, a.gps.lon) => {
+                        Some(initial_bearing(b.gps.lat, b.gps.lon, a.gps.lat, a.gps.lon))
+                    }
                     _ => None,
                 };
                 
                 Some((lat, lon, heading))
             }
-            (Some(b), None) => Some((b.gps.lat, b.gps.lon, b.gps.heading_deg)),
-            (None, Some(a)) => Some((a.gps.lat, a.gps.lon, a.gps.heading_deg)),
+            (Some(b), None) => (video_time_seconds - b.video_time_seconds <= policy.extrapolation_tolerance_seconds)
+                .then_some((b.gps.lat, b.gps.lon, b.gps.heading_deg)),
+            (None, Some(a)) => (a.video_time_seconds - video_time_seconds <= policy.extrapolation_tolerance_seconds)
+                .then_some((a.gps.lat, a.gps.lon, a.gps.heading_deg)),
             (None, None) => None,
         }
     }
 }
 
+/// Time covered by a GPS track, from its recorded bounds or its points
+fn gps_span_seconds(track: &GpsTrack) -> Option<f64> {
+    let (start, end) = match (track.start_time, track.end_time) {
+        (Some(start), Some(end)) => (start, end),
+        _ => {
+            let start = track.points.iter().map(|p| p.timestamp).min()?;
+            let end = track.points.iter().map(|p| p.timestamp).max()?;
+            (start, end)
+        }
+    };
+
+    Some((end - start).num_milliseconds() as f64 / 1000.0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::{Duration, TimeZone};
     
     #[test]
     fn test_interpolation() {
@@ -281,10 +545,158 @@ mod tests {
             points: points.clone(),
         };
         
-        let engine = TimeSyncEngine::new(track, 10.0, Some(points[0].timestamp));
+        let engine = TimeSyncEngine::new(track, Some(10.0), Some(points[0].timestamp));
         
         // Sync should work
         let result = engine.synchronize();
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_offset_from_sun() {
+        // Heading north through Flagstaff for ten minutes from 16:30 UTC
+        let start = Utc.with_ymd_and_hms(2024, 9, 14, 16, 30, 0).unwrap();
+        let points: Vec<GpsPoint> = (0..=10)
+            .map(|i| GpsPoint {
+                timestamp: start + Duration::minutes(i),
+                lat: 35.1983 + i as f64 * 0.001,
+                lon: -111.6513,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: Some(0.0),
+                accuracy_m: None,
+            })
+            .collect();
+        let track = GpsTrack::from_points("drive.gpx".to_string(), "gpx", points);
+        let engine = TimeSyncEngine::new(track, Some(600.0), None);
+
+        // 60 s into the video, filmed at 16:00 UTC: the track starts 31 minutes in
+        let sun = sun::sun_position(start - Duration::minutes(30), 35.1983, -111.6513);
+        let observation = SunObservation {
+            sun_bearing_deg: Some(sun.azimuth_deg),
+            sun_elevation_deg: Some(sun.elevation_deg),
+            ..Default::default()
+        };
+        let suggestion = engine.suggest_offset_from_sun(60.0, &observation, 0.0).unwrap();
+        assert_eq!(suggestion.method, SyncMethod::AutoDetect);
+        assert!((suggestion.offset_seconds - 1860.0).abs() <= 120.0, "{:?}", suggestion);
+        assert!(suggestion.used_direction);
+        assert!(suggestion.confidence > 0.0 && suggestion.confidence <= SUN_SYNC_MAX_CONFIDENCE);
+
+        // The sun never gets that high in Arizona in September
+        let overhead = SunObservation { sun_elevation_deg: Some(89.0), ..Default::default() };
+        assert!(matches!(engine.suggest_offset_from_sun(60.0, &overhead, 0.0), Err(SyncError::SyncFailed(_))));
+        assert!(engine.suggest_offset_from_sun(60.0, &SunObservation::default(), 0.0).is_err());
+    }
+
+    #[test]
+    fn test_interpolation_identical_video_times() {
+        let timestamp = Utc::now();
+        let point = |lat: f64| AlignedPoint {
+            video_time_seconds: 5.0,
+            gps: GpsPoint {
+                timestamp,
+                lat,
+                lon: -112.0,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: Some(90.0),
+                accuracy_m: None,
+            },
+        };
+
+        let sync_result = SyncResult {
+            offset_seconds: 0.0,
+            confidence: 1.0,
+            method: SyncMethod::Manual,
+            aligned_points: vec![point(36.0), point(36.1)],
+        };
+
+        let track = GpsTrack::from_points("test.gpx".to_string(), "gpx", vec![]);
+        let engine = TimeSyncEngine::new(track, Some(10.0), None);
+
+        for query in [4.9, 5.0, 5.1] {
+            let (lat, lon, heading) = engine.interpolate_position(&sync_result, query).unwrap();
+            assert!(lat.is_finite() && lon.is_finite(), "NaN at {}", query);
+            assert_eq!(heading, Some(90.0));
+        }
+    }
+
+    #[test]
+    fn test_interpolation_policy_limits() {
+        let start = Utc::now();
+        let aligned = |t: f64, lat: f64| AlignedPoint {
+            video_time_seconds: t,
+            gps: GpsPoint {
+                timestamp: start,
+                lat,
+                lon: -112.0,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: None,
+                accuracy_m: None,
+            },
+        };
+
+        // Fixes at 10s and 20s, then a dropout until 200s
+        let sync_result = SyncResult {
+            offset_seconds: 0.0,
+            confidence: 1.0,
+            method: SyncMethod::Manual,
+            aligned_points: vec![aligned(10.0, 36.0), aligned(20.0, 36.1), aligned(200.0, 37.0)],
+        };
+
+        let track = GpsTrack::from_points("test.gpx".to_string(), "gpx", vec![]);
+        let engine = TimeSyncEngine::new(track, Some(300.0), None).with_policy(InterpolationPolicy {
+            max_gap_seconds: 30.0,
+            extrapolation_tolerance_seconds: 2.0,
+        });
+
+        // Moving north, with no heading recorded
+        let (_, _, heading) = engine.interpolate_position(&sync_result, 15.0).unwrap();
+        assert_eq!(heading, Some(0.0));
+        // Near either side of the dropout is fine, the middle is not
+        assert!(engine.interpolate_position(&sync_result, 45.0).is_some());
+        assert!(engine.interpolate_position(&sync_result, 110.0).is_none());
+        assert!(engine.interpolate_position(&sync_result, 180.0).is_some());
+
+        assert!(engine.interpolate_position(&sync_result, 9.0).is_some());
+        assert!(engine.interpolate_position(&sync_result, 5.0).is_none());
+        assert!(engine.interpolate_position(&sync_result, 201.5).is_some());
+        assert!(engine.interpolate_position(&sync_result, 260.0).is_none());
+    }
+
+    #[test]
+    fn test_missing_duration_uses_gps_span() {
+        let start = Utc::now();
+        let points: Vec<GpsPoint> = (0..=30)
+            .map(|i| GpsPoint {
+                timestamp: start + Duration::seconds(i * 10),
+                lat: 36.0 + i as f64 * 0.001,
+                lon: -112.0,
+                elevation_m: None,
+                speed_kmh: None,
+                heading_deg: None,
+                accuracy_m: None,
+            })
+            .collect();
+
+        let track = GpsTrack {
+            name: None,
+            source_file: "test.gpx".to_string(),
+            track_type: "gpx".to_string(),
+            point_count: points.len(),
+            start_time: Some(start),
+            end_time: Some(start + Duration::seconds(300)),
+            bounds: None,
+            points,
+        };
+
+        let engine = TimeSyncEngine::new(track, None, Some(start));
+        assert_eq!(engine.video_duration_seconds(), 300.0);
+
+        let result = engine.synchronize().unwrap();
+        assert_eq!(result.method, SyncMethod::VideoMetadata);
+        assert_eq!(result.aligned_points.len(), 31);
+    }
 }
@@ -0,0 +1,174 @@
+//! Job Temp Directories
+//!
+//! Scratch space for processing jobs, such as the audio extracted for
+//! Whisper. Each job gets a directory of its own under the app cache,
+//! removed when the job lets go of it, whether it finished or failed. What
+//! a crash leaves behind is swept at the next startup once it's old enough
+//! that no job can still be using it.
+
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Names of job directories start with this; nothing else is swept
+const JOB_DIR_PREFIX: &str = "job-";
+
+/// Age at which a job directory left behind is swept at startup
+pub const STALE_AFTER: Duration = Duration::from_secs(2 * 24 * 60 * 60);
+
+/// A job's temp directory, removed with everything in it when dropped
+#[derive(Debug)]
+pub struct JobDir {
+    path: PathBuf,
+}
+
+impl JobDir {
+    /// Create a new job directory under `root`
+    pub fn create(root: &Path) -> io::Result<Self> {
+        let path = root.join(format!("{}{}", JOB_DIR_PREFIX, Uuid::new_v4()));
+        std::fs::create_dir_all(&path)?;
+        debug!("Created job directory {:?}", path);
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for JobDir {
+    fn drop(&mut self) {
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(()) => debug!("Removed job directory {:?}", self.path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            // Swept at a later startup
+            Err(e) => warn!("Failed to remove job directory {:?}: {}", self.path, e),
+        }
+    }
+}
+
+/// Disk used by job directories
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct TempUsage {
+    pub path: String,
+    /// Running jobs' directories plus any left behind
+    pub job_dirs: usize,
+    pub bytes: u64,
+}
+
+/// How much the job directories under `root` hold
+pub fn usage(root: &Path) -> TempUsage {
+    let dirs = job_dirs(root);
+    TempUsage {
+        path: root.display().to_string(),
+        job_dirs: dirs.len(),
+        bytes: dirs.iter().map(|dir| dir_size(dir)).sum(),
+    }
+}
+
+/// Remove job directories under `root` last changed more than `max_age`
+/// ago; returns the bytes freed
+pub fn sweep(root: &Path, max_age: Duration) -> u64 {
+    sweep_at(root, max_age, SystemTime::now())
+}
+
+fn sweep_at(root: &Path, max_age: Duration, now: SystemTime) -> u64 {
+    let mut removed = 0;
+    let mut freed = 0;
+    for dir in job_dirs(root) {
+        let modified = std::fs::metadata(&dir).and_then(|m| m.modified());
+        let stale = modified.is_ok_and(|t| now.duration_since(t).is_ok_and(|age| age > max_age));
+        if !stale {
+            continue;
+        }
+        let bytes = dir_size(&dir);
+        match std::fs::remove_dir_all(&dir) {
+            Ok(()) => {
+                removed += 1;
+                freed += bytes;
+            }
+            Err(e) => warn!("Failed to remove stale job directory {:?}: {}", dir, e),
+        }
+    }
+    if removed > 0 {
+        info!("Swept {} stale job directories, {} bytes", removed, freed);
+    }
+    freed
+}
+
+fn job_dirs(root: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|entry| entry.file_name().to_str().is_some_and(|n| n.starts_with(JOB_DIR_PREFIX)))
+        .map(|entry| entry.path())
+        .collect()
+}
+
+/// Bytes in the files under `path`, not following symlinks
+fn dir_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => dir_size(&entry.path()),
+            Ok(t) if t.is_file() => entry.metadata().map_or(0, |m| m.len()),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    /// A stage that gets as far as writing its output, then fails
+    fn failing_stage(root: &Path) -> Result<(), String> {
+        let job = JobDir::create(root).map_err(|e| e.to_string())?;
+        std::fs::write(job.path().join("audio.wav"), vec![0u8; 4096]).map_err(|e| e.to_string())?;
+        assert_eq!(usage(root).bytes, 4096);
+        Err("Failed to transcribe audio".to_string())
+    }
+
+    #[test]
+    fn test_job_dir_is_removed_when_a_stage_fails() {
+        let root = fixtures::temp_dir("jobs");
+        assert!(failing_stage(&root).is_err());
+        assert_eq!(usage(&root).job_dirs, 0);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_sweep_removes_only_stale_job_dirs() {
+        let root = fixtures::temp_dir("jobs");
+        let left_behind = root.join(format!("{}crashed", JOB_DIR_PREFIX));
+        std::fs::create_dir_all(left_behind.join("moments")).unwrap();
+        std::fs::write(left_behind.join("moments").join("frame.jpg"), [0u8; 100]).unwrap();
+        std::fs::write(left_behind.join("audio.wav"), [0u8; 1000]).unwrap();
+        let other = root.join("not-a-job");
+        std::fs::create_dir_all(&other).unwrap();
+
+        let running = JobDir::create(&root).unwrap();
+        let usage_now = usage(&root);
+        assert_eq!((usage_now.job_dirs, usage_now.bytes), (2, 1100));
+
+        // Too recent for either job directory
+        assert_eq!(sweep(&root, STALE_AFTER), 0);
+        assert!(left_behind.exists());
+
+        let later = SystemTime::now() + STALE_AFTER + Duration::from_secs(60);
+        assert_eq!(sweep_at(&root, STALE_AFTER, later), 1100);
+        assert!(!left_behind.exists() && !running.path().exists());
+        assert!(other.exists());
+
+        drop(running);
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -8,7 +8,22 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
-use super::gps::GpsPoint;
+use super::database::{DatabaseError, PoiRecord};
+use super::gps::{haversine_distance, initial_bearing, is_valid_coordinate, GpsPoint};
+use super::LocalDatabase;
+use crate::types::{AttributedValue, FactSource, LocationContext, POIFacts, POI};
+
+/// Field of view assumed when none is given, about a dashcam's
+pub const DEFAULT_FOV_DEG: f64 = 120.0;
+
+/// Metres per degree of latitude
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// Metres around a point its POIs are looked up within
+pub const VERIFY_RADIUS_M: f64 = 500.0;
+
+/// How bundles verified against downloaded data say they were
+pub const VERIFICATION_MODE: &str = "offline";
 
 #[derive(Error, Debug)]
 pub enum TruthEngineError {
@@ -18,6 +33,9 @@ pub enum TruthEngineError {
     #[error("Verification failed: {0}")]
     VerificationFailed(String),
     
+    #[error("Coordinates out of range: {lat}, {lon}")]
+    InvalidCoordinates { lat: f64, lon: f64 },
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
@@ -56,7 +74,7 @@ pub struct VerifiedFact {
     pub name: String,
     pub value: String,
     pub confidence: VerificationConfidence,
-    pub source: String,
+    pub source: FactSource,
 }
 
 /// A verified POI from local data
@@ -71,6 +89,10 @@ pub struct LocalPOI {
     pub bearing_deg: f64,
     pub in_fov: bool,
     pub facts: Vec<VerifiedFact>,
+    #[serde(default)]
+    pub wikipedia: Option<String>,
+    #[serde(default)]
+    pub wikidata: Option<String>,
 }
 
 /// Truth Bundle for a location
@@ -83,6 +105,40 @@ pub struct TruthBundle {
     pub confidence: VerificationConfidence,
 }
 
+impl TruthBundle {
+    /// Take the time zone from map data, over any estimate
+    pub fn set_timezone(&mut self, timezone: String) {
+        self.facts.retain(|fact| fact.fact_type != "timezone");
+        self.facts.push(VerifiedFact {
+            fact_type: "timezone".to_string(),
+            name: "Timezone".to_string(),
+            value: timezone.clone(),
+            confidence: VerificationConfidence::High,
+            source: FactSource::MapData,
+        });
+        self.location.timezone = Some(timezone);
+    }
+
+    /// The location facts as an event's context, each attributed to where
+    /// it came from; `None` without any
+    pub fn location_context(&self) -> Option<LocationContext> {
+        let mut context = LocationContext::default();
+        for fact in &self.facts {
+            let field = match fact.fact_type.as_str() {
+                "country" => &mut context.country,
+                "state" => &mut context.state,
+                "timezone" => &mut context.timezone,
+                "road" => &mut context.road,
+                _ => continue,
+            };
+            *field = Some(fact.value.clone());
+            let attributed = AttributedValue { value: fact.value.clone(), source: fact.source, confidence: fact.confidence.as_f64() };
+            context.attribution.insert(fact.fact_type.clone(), attributed);
+        }
+        (!context.attribution.is_empty()).then_some(context)
+    }
+}
+
 /// Verified location context
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VerifiedLocation {
@@ -100,6 +156,8 @@ pub struct VerifiedLocation {
 pub struct LocalTruthEngine {
     tiles_path: Option<PathBuf>,
     poi_db_path: Option<PathBuf>,
+    /// Holds the `pois` table of downloaded regions
+    db: Option<LocalDatabase>,
     initialized: bool,
 }
 
@@ -109,6 +167,7 @@ impl LocalTruthEngine {
         Self {
             tiles_path: None,
             poi_db_path: None,
+            db: None,
             initialized: false,
         }
     }
@@ -135,19 +194,82 @@ impl LocalTruthEngine {
         self
     }
     
+    /// Look POIs up in the regions stored in `db`
+    pub fn with_database(mut self, db: LocalDatabase) -> Self {
+        self.db = Some(db);
+        self
+    }
+    
     /// Check if engine is available for offline use
     pub fn is_available(&self) -> bool {
-        self.tiles_path.is_some() || self.poi_db_path.is_some()
+        self.tiles_path.is_some() || self.poi_db_path.is_some() || self.db.is_some()
     }
     
     /// Verify a GPS point and return Truth Bundle
+    ///
+    /// `camera_heading_offset_deg` is where the camera points relative to the
+    /// direction of travel, clockwise: 90 for a mount facing right, 180 for a
+    /// rear-facing camera. POIs are marked `in_fov` against that bearing.
     pub async fn verify_point(
         &self,
         point: &GpsPoint,
         fov_deg: f64,
+        camera_heading_offset_deg: f64,
     ) -> Result<TruthBundle, TruthEngineError> {
+        if !is_valid_coordinate(point.lat, point.lon) {
+            return Err(TruthEngineError::InvalidCoordinates { lat: point.lat, lon: point.lon });
+        }
         debug!("Verifying point: ({}, {})", point.lat, point.lon);
         
+        // Query local POIs (simplified - would use spatial index)
+        let pois = self.query_nearby_pois(point.lat, point.lon, VERIFY_RADIUS_M).await?;
+        let mut bundle = self.verify_with_pois(point, pois, fov_deg, camera_heading_offset_deg);
+        if let Some(timezone) = self.map_timezone(point.lat, point.lon).await {
+            bundle.set_timezone(timezone);
+        }
+        Ok(bundle)
+    }
+
+    /// The time zone the downloaded boundaries give a point, from the most
+    /// local area tagged with one
+    async fn map_timezone(&self, lat: f64, lon: f64) -> Option<String> {
+        let areas = match self.db.as_ref()?.admin_areas_at(lat, lon).await {
+            Ok(areas) => areas,
+            Err(e) => {
+                warn!("Failed to look up the time zone at {}, {}: {}", lat, lon, e);
+                return None;
+            }
+        };
+        areas
+            .into_iter()
+            .filter(|a| a.timezone.is_some())
+            .min_by(|a, b| b.admin_level.cmp(&a.admin_level).then(a.extent.total_cmp(&b.extent)))
+            .and_then(|a| a.timezone)
+    }
+
+    /// Verify each point of a track, as [`verify_point`](Self::verify_point)
+    /// does; a point that can't be verified doesn't stop the others
+    pub async fn verify_track(
+        &self,
+        points: &[GpsPoint],
+        fov_deg: f64,
+        camera_heading_offset_deg: f64,
+    ) -> Vec<Result<TruthBundle, TruthEngineError>> {
+        let mut bundles = Vec::with_capacity(points.len());
+        for point in points {
+            bundles.push(self.verify_point(point, fov_deg, camera_heading_offset_deg).await);
+        }
+        bundles
+    }
+
+    /// The Truth Bundle of a point with the POIs found around it
+    pub fn verify_with_pois(
+        &self,
+        point: &GpsPoint,
+        mut pois: Vec<LocalPOI>,
+        fov_deg: f64,
+        camera_heading_offset_deg: f64,
+    ) -> TruthBundle {
         // Build verified location
         let location = VerifiedLocation {
             lat: point.lat,
@@ -160,9 +282,9 @@ impl LocalTruthEngine {
             timezone: self.estimate_timezone(point.lat, point.lon),
         };
         
-        // Query local POIs (simplified - would use spatial index)
-        let pois = self.query_nearby_pois(point.lat, point.lon, 500.0, point.heading_deg, fov_deg)
-            .await?;
+        // Without a travel heading there's no telling where the camera points
+        let camera_bearing = point.heading_deg.map(|h| camera_bearing(h, camera_heading_offset_deg));
+        mark_in_fov(&mut pois, camera_bearing, fov_deg);
         
         // Build facts from location
         let mut facts = Vec::new();
@@ -173,7 +295,8 @@ impl LocalTruthEngine {
                 name: "Country".to_string(),
                 value: country.clone(),
                 confidence: VerificationConfidence::Medium,
-                source: "local".to_string(),
+                // Rough bounds, not map data
+                source: FactSource::Estimate,
             });
         }
         
@@ -183,7 +306,8 @@ impl LocalTruthEngine {
                 name: "Timezone".to_string(),
                 value: tz.clone(),
                 confidence: VerificationConfidence::High,
-                source: "local".to_string(),
+                // Rough bounds, not map data
+                source: FactSource::Estimate,
             });
         }
         
@@ -196,29 +320,28 @@ impl LocalTruthEngine {
             VerificationConfidence::Medium
         };
         
-        Ok(TruthBundle {
+        TruthBundle {
             location,
             pois,
             facts,
-            verification_mode: "offline".to_string(),
+            verification_mode: VERIFICATION_MODE.to_string(),
             confidence,
-        })
+        }
     }
     
     /// Query nearby POIs from local database
     async fn query_nearby_pois(
         &self,
-        _lat: f64,
-        _lon: f64,
-        _radius_m: f64,
-        _heading_deg: Option<f64>,
-        _fov_deg: f64,
+        lat: f64,
+        lon: f64,
+        radius_m: f64,
     ) -> Result<Vec<LocalPOI>, TruthEngineError> {
-        // Placeholder - would query local SQLite/DuckDB POI database
-        // with spatial index for efficient radius queries
-        
-        // For now, return empty list (POIs would come from downloaded data)
-        Ok(vec![])
+        let Some(db) = &self.db else {
+            return Ok(vec![]);
+        };
+        nearby_pois(db, lat, lon, radius_m, &[])
+            .await
+            .map_err(|e| TruthEngineError::VerificationFailed(e.to_string()))
     }
     
     /// Estimate country from coordinates (simplified)
@@ -226,11 +349,11 @@ impl LocalTruthEngine {
         // Very simplified - just check rough bounds
         // Real implementation would use reverse geocoding tiles
         
-        if lat >= 24.0 && lat <= 50.0 && lon >= -125.0 && lon <= -66.0 {
+        if (24.0..=50.0).contains(&lat) && (-125.0..=-66.0).contains(&lon) {
             Some("United States".to_string())
-        } else if lat >= 41.0 && lat <= 84.0 && lon >= -141.0 && lon <= -52.0 {
+        } else if (41.0..=84.0).contains(&lat) && (-141.0..=-52.0).contains(&lon) {
             Some("Canada".to_string())
-        } else if lat >= 14.0 && lat <= 33.0 && lon >= -118.0 && lon <= -86.0 {
+        } else if (14.0..=33.0).contains(&lat) && (-118.0..=-86.0).contains(&lon) {
             Some("Mexico".to_string())
         } else {
             None
@@ -242,13 +365,13 @@ impl LocalTruthEngine {
         // Simplified timezone estimation based on longitude
         // Real implementation would use timezone boundary tiles
         
-        if lon >= -125.0 && lon < -115.0 {
+        if (-125.0..-115.0).contains(&lon) {
             Some("America/Los_Angeles".to_string())
-        } else if lon >= -115.0 && lon < -100.0 {
+        } else if (-115.0..-100.0).contains(&lon) {
             Some("America/Denver".to_string())
-        } else if lon >= -100.0 && lon < -85.0 {
+        } else if (-100.0..-85.0).contains(&lon) {
             Some("America/Chicago".to_string())
-        } else if lon >= -85.0 && lon < -66.0 {
+        } else if (-85.0..-66.0).contains(&lon) {
             Some("America/New_York".to_string())
         } else {
             None
@@ -261,3 +384,247 @@ impl Default for LocalTruthEngine {
         Self::new()
     }
 }
+
+/// POIs of the downloaded regions within `radius_m` of a point, closest
+/// first, optionally limited to some categories
+///
+/// None are marked `in_fov`; that's up to the caller, who knows where the
+/// camera points.
+pub async fn nearby_pois(
+    db: &LocalDatabase,
+    lat: f64,
+    lon: f64,
+    radius_m: f64,
+    categories: &[String],
+) -> Result<Vec<LocalPOI>, DatabaseError> {
+    let candidates = db.pois_in_bounds(search_bounds(lat, lon, radius_m), categories).await?;
+    Ok(within_radius(lat, lon, candidates, radius_m))
+}
+
+/// The candidates within `radius_m` of the point, closest first
+///
+/// Box corners lie outside the circle; POIs there are dropped so that a
+/// closer POI just past the box's edge can't be ranked below them.
+pub fn within_radius(lat: f64, lon: f64, candidates: Vec<PoiRecord>, radius_m: f64) -> Vec<LocalPOI> {
+    let mut pois: Vec<LocalPOI> = candidates
+        .into_iter()
+        .map(|c| LocalPOI {
+            distance_m: haversine_distance(lat, lon, c.lat, c.lon) * 1000.0,
+            bearing_deg: initial_bearing(lat, lon, c.lat, c.lon),
+            id: c.id,
+            name: c.name,
+            category: c.category,
+            lat: c.lat,
+            lon: c.lon,
+            in_fov: false,
+            facts: vec![],
+            wikipedia: c.wikipedia,
+            wikidata: c.wikidata,
+        })
+        .filter(|p| p.distance_m <= radius_m)
+        .collect();
+
+    pois.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+    pois
+}
+
+/// Bounding box `(min_lat, min_lon, max_lat, max_lon)` enclosing a circle around the point
+pub fn search_bounds(lat: f64, lon: f64, radius_m: f64) -> (f64, f64, f64, f64) {
+    let delta_lat = radius_m / METERS_PER_DEGREE;
+    // Near the poles the circle spans every longitude
+    let delta_lon = (radius_m / (METERS_PER_DEGREE * lat.to_radians().cos().max(1e-6))).min(180.0);
+
+    (
+        (lat - delta_lat).max(-90.0),
+        (lon - delta_lon).max(-180.0),
+        (lat + delta_lat).min(90.0),
+        (lon + delta_lon).min(180.0),
+    )
+}
+
+impl From<LocalPOI> for POI {
+    fn from(poi: LocalPOI) -> Self {
+        let mut facts = POIFacts::with_articles(poi.wikipedia, poi.wikidata);
+        for fact in poi.facts {
+            let facts = facts.get_or_insert_with(POIFacts::default);
+            match fact.fact_type.as_str() {
+                "established" => facts.established = Some(fact.value),
+                "depth_m" => facts.depth_m = fact.value.parse().ok(),
+                "unesco_site" => facts.unesco_site = fact.value.parse().ok(),
+                _ => {
+                    facts.extra.insert(fact.fact_type, serde_json::Value::String(fact.value));
+                }
+            }
+        }
+        POI {
+            id: poi.id,
+            name: poi.name,
+            name_local: None,
+            category: poi.category,
+            subcategory: None,
+            lat: poi.lat,
+            lon: poi.lon,
+            distance_m: poi.distance_m,
+            bearing_deg: poi.bearing_deg,
+            in_fov: poi.in_fov,
+            // Straight from the downloaded map data
+            confidence: 1.0,
+            facts,
+            pinned: false,
+        }
+    }
+}
+
+/// Direction the camera faces, in `[0, 360)`: the travel heading turned
+/// clockwise by the mount offset
+pub fn camera_bearing(travel_heading_deg: f64, offset_deg: f64) -> f64 {
+    (travel_heading_deg + offset_deg).rem_euclid(360.0)
+}
+
+/// Whether a bearing lies within a field of view of `fov_deg` centered on the camera
+pub fn in_fov(camera_bearing_deg: f64, bearing_deg: f64, fov_deg: f64) -> bool {
+    // Signed angle between the two, in [-180, 180)
+    let diff = (bearing_deg - camera_bearing_deg + 180.0).rem_euclid(360.0) - 180.0;
+    diff.abs() <= fov_deg / 2.0
+}
+
+/// Set `in_fov` on each POI; none are in view when the camera bearing is unknown
+pub fn mark_in_fov(pois: &mut [LocalPOI], camera_bearing_deg: Option<f64>, fov_deg: f64) {
+    for poi in pois {
+        poi.in_fov = camera_bearing_deg.is_some_and(|camera| in_fov(camera, poi.bearing_deg, fov_deg));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn poi(id: &str, bearing_deg: f64) -> LocalPOI {
+        LocalPOI {
+            id: id.to_string(),
+            name: id.to_string(),
+            category: "landmark".to_string(),
+            lat: 0.0,
+            lon: 0.0,
+            distance_m: 100.0,
+            bearing_deg,
+            in_fov: false,
+            facts: vec![],
+            wikipedia: None,
+            wikidata: None,
+        }
+    }
+
+    fn in_view(pois: &[LocalPOI]) -> Vec<&str> {
+        pois.iter().filter(|p| p.in_fov).map(|p| p.id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_verify_point_rejects_out_of_range_coordinates() {
+        let engine = LocalTruthEngine::new();
+        let point = GpsPoint {
+            timestamp: chrono::Utc::now(),
+            lat: 9999.0,
+            lon: 7.4246,
+            elevation_m: None,
+            speed_kmh: None,
+            heading_deg: None,
+            accuracy_m: None,
+        };
+
+        let result = engine.verify_point(&point, 60.0, 0.0).await;
+        assert!(matches!(result, Err(TruthEngineError::InvalidCoordinates { .. })));
+    }
+
+    #[test]
+    fn test_pois_within_radius_become_pois() {
+        let record = |id: &str, lat: f64, lon: f64| PoiRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            category: "castle".to_string(),
+            lat,
+            lon,
+            ..Default::default()
+        };
+        // About 111 m north, 80 m east and 445 m north
+        let candidates = vec![record("north", 43.001, 7.0), record("east", 43.0, 7.001), record("far", 43.004, 7.0)];
+
+        let mut pois = within_radius(43.0, 7.0, candidates, 250.0);
+        mark_in_fov(&mut pois, Some(0.0), 60.0);
+        let pois: Vec<POI> = pois.into_iter().map(POI::from).collect();
+
+        let ids: Vec<&str> = pois.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, ["east", "north"]);
+        assert!(!pois[0].in_fov && pois[1].in_fov);
+        assert!((pois[1].distance_m - 111.2).abs() < 1.0);
+        assert!(pois.iter().all(|p| p.confidence == 1.0 && p.category == "castle"));
+    }
+
+    #[test]
+    fn test_verified_facts_reach_the_event() {
+        let engine = LocalTruthEngine::new();
+        let point = GpsPoint {
+            timestamp: chrono::Utc::now(),
+            lat: 40.7484,
+            lon: -73.9857,
+            elevation_m: None,
+            speed_kmh: None,
+            heading_deg: Some(0.0),
+            accuracy_m: None,
+        };
+        let fact = |fact_type: &str, value: &str| VerifiedFact {
+            fact_type: fact_type.to_string(),
+            name: fact_type.to_string(),
+            value: value.to_string(),
+            confidence: VerificationConfidence::High,
+            source: FactSource::MapData,
+        };
+        let mut tower = poi("tower", 2.0);
+        tower.facts = vec![fact("established", "1931"), fact("depth_m", "deep"), fact("architect", "Shreve")];
+
+        let bundle = engine.verify_with_pois(&point, vec![tower], 60.0, 0.0);
+        assert_eq!(bundle.verification_mode, VERIFICATION_MODE);
+        assert_eq!(in_view(&bundle.pois), ["tower"]);
+
+        let context = bundle.location_context().unwrap();
+        assert_eq!(context.country.as_deref(), Some("United States"));
+        assert_eq!(context.timezone.as_deref(), Some("America/New_York"));
+        assert_eq!(context.attribution["country"].source, FactSource::Estimate);
+
+        let facts = POI::from(bundle.pois[0].clone()).facts.unwrap();
+        assert_eq!(facts.established.as_deref(), Some("1931"));
+        // Not a number, so left out rather than made up
+        assert_eq!(facts.depth_m, None);
+        assert_eq!(facts.extra["architect"], "Shreve");
+    }
+
+    #[test]
+    fn test_heading_offset_shifts_fov() {
+        // Driving north with POIs ahead, to the right and behind
+        let mut pois = vec![poi("ahead", 5.0), poi("right", 95.0), poi("behind", 182.0)];
+
+        mark_in_fov(&mut pois, Some(camera_bearing(0.0, 0.0)), 60.0);
+        assert_eq!(in_view(&pois), ["ahead"]);
+
+        // Side mount
+        mark_in_fov(&mut pois, Some(camera_bearing(0.0, 90.0)), 60.0);
+        assert_eq!(in_view(&pois), ["right"]);
+
+        // Rear-facing camera
+        mark_in_fov(&mut pois, Some(camera_bearing(0.0, 180.0)), 60.0);
+        assert_eq!(in_view(&pois), ["behind"]);
+
+        mark_in_fov(&mut pois, None, 60.0);
+        assert!(in_view(&pois).is_empty());
+    }
+
+    #[test]
+    fn test_fov_wraps_around_north() {
+        assert_eq!(camera_bearing(270.0, 180.0), 90.0);
+        assert_eq!(camera_bearing(10.0, -90.0), 280.0);
+
+        assert!(in_fov(350.0, 15.0, 60.0));
+        assert!(in_fov(10.0, 345.0, 60.0));
+        assert!(!in_fov(10.0, 300.0, 60.0));
+    }
+}
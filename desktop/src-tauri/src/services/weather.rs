@@ -0,0 +1,221 @@
+//! Historical weather
+//!
+//! Hourly weather from Open-Meteo, which needs no key. Past days come from
+//! its archive; the archive lags a few days behind, so recent days come
+//! from the forecast API, which also serves the days just gone. A lookup
+//! fetches the whole UTC day at once, and hours are keyed by the location
+//! rounded to 0.1°, about the resolution of the reanalysis behind them.
+
+use chrono::{DateTime, Duration as ChronoDuration, NaiveDate, NaiveDateTime, Timelike, Utc};
+use serde::Deserialize;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::types::WeatherObservation;
+
+/// Named as the source of the observations
+pub const SOURCE: &str = "open-meteo";
+
+const ARCHIVE_ENDPOINT: &str = "https://archive-api.open-meteo.com/v1/archive";
+const FORECAST_ENDPOINT: &str = "https://api.open-meteo.com/v1/forecast";
+
+/// Days the archive may lag behind today
+const ARCHIVE_DELAY_DAYS: i64 = 5;
+
+const HOURLY_VARIABLES: &str = "temperature_2m,precipitation,wind_speed_10m,cloud_cover,weather_code";
+
+const REQUEST_TIMEOUT_SECS: u64 = 20;
+
+#[derive(Error, Debug)]
+pub enum WeatherError {
+    #[error("Weather service is rate limiting requests, please try again later")]
+    RateLimited,
+
+    #[error("Weather request failed: {0}")]
+    RequestFailed(String),
+}
+
+#[derive(Deserialize)]
+struct ForecastResponse {
+    hourly: Hourly,
+}
+
+/// Columns of hourly values; any value can be missing
+#[derive(Deserialize)]
+struct Hourly {
+    time: Vec<String>,
+    #[serde(default)]
+    temperature_2m: Vec<Option<f64>>,
+    #[serde(default)]
+    precipitation: Vec<Option<f64>>,
+    #[serde(default)]
+    wind_speed_10m: Vec<Option<f64>>,
+    #[serde(default)]
+    cloud_cover: Vec<Option<f64>>,
+    #[serde(default)]
+    weather_code: Vec<Option<i64>>,
+}
+
+/// The hour a time is closest to
+pub fn nearest_hour(time: DateTime<Utc>) -> DateTime<Utc> {
+    let hour = time
+        .with_minute(0)
+        .and_then(|t| t.with_second(0))
+        .and_then(|t| t.with_nanosecond(0))
+        .unwrap_or(time);
+    if time.minute() >= 30 { hour + ChronoDuration::hours(1) } else { hour }
+}
+
+/// Location rounded to 0.1°
+pub fn rounded(lat: f64, lon: f64) -> (f64, f64) {
+    // Adding zero turns -0.0 into 0.0, so both give one key
+    let round = |value: f64| (value * 10.0).round() / 10.0 + 0.0;
+    (round(lat), round(lon))
+}
+
+/// Cache key of the weather at a location in the hour nearest `time`
+pub fn cache_key(lat: f64, lon: f64, time: DateTime<Utc>) -> String {
+    let (lat, lon) = rounded(lat, lon);
+    format!("{:.1},{:.1},{}", lat, lon, nearest_hour(time).format("%Y-%m-%dT%H"))
+}
+
+/// Every hour of a UTC day at a location, by the hour it starts
+///
+/// The location is rounded the way [`cache_key`] rounds it, so the hours
+/// can be cached under their keys.
+pub async fn fetch_day(lat: f64, lon: f64, date: NaiveDate) -> Result<Vec<(DateTime<Utc>, WeatherObservation)>, WeatherError> {
+    let (lat, lon) = rounded(lat, lon);
+    let recent = Utc::now().date_naive() - date < ChronoDuration::days(ARCHIVE_DELAY_DAYS);
+    let endpoint = if recent { FORECAST_ENDPOINT } else { ARCHIVE_ENDPOINT };
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("GeoTruth/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| WeatherError::RequestFailed(e.to_string()))?;
+
+    let day = date.format("%Y-%m-%d").to_string();
+    let response = client
+        .get(endpoint)
+        .query(&[
+            ("latitude", lat.to_string()),
+            ("longitude", lon.to_string()),
+            ("start_date", day.clone()),
+            ("end_date", day),
+            ("hourly", HOURLY_VARIABLES.to_string()),
+            ("timezone", "UTC".to_string()),
+        ])
+        .send()
+        .await
+        .map_err(|e| WeatherError::RequestFailed(e.to_string()))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(WeatherError::RateLimited);
+    }
+    if !status.is_success() {
+        return Err(WeatherError::RequestFailed(status.to_string()));
+    }
+
+    let body: ForecastResponse = response
+        .json()
+        .await
+        .map_err(|e| WeatherError::RequestFailed(format!("Unexpected Open-Meteo response: {}", e)))?;
+    let hours = parse_hourly(body.hourly);
+    debug!("Open-Meteo gave {} hours of weather at {}, {} on {}", hours.len(), lat, lon, date);
+    Ok(hours)
+}
+
+/// Observations by hour, leaving out hours with nothing recorded
+fn parse_hourly(hourly: Hourly) -> Vec<(DateTime<Utc>, WeatherObservation)> {
+    let value = |column: &[Option<f64>], i: usize| column.get(i).copied().flatten();
+    hourly
+        .time
+        .iter()
+        .enumerate()
+        .filter_map(|(i, time)| {
+            let hour = NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M").ok()?.and_utc();
+            let observation = WeatherObservation {
+                temperature_c: value(&hourly.temperature_2m, i),
+                precipitation_mm: value(&hourly.precipitation, i),
+                wind_speed_kmh: value(&hourly.wind_speed_10m, i),
+                cloud_cover_percent: value(&hourly.cloud_cover, i),
+                condition: hourly.weather_code.get(i).copied().flatten().and_then(condition).map(str::to_string),
+                source: SOURCE.to_string(),
+            };
+            let recorded = observation.temperature_c.is_some()
+                || observation.precipitation_mm.is_some()
+                || observation.wind_speed_kmh.is_some()
+                || observation.cloud_cover_percent.is_some()
+                || observation.condition.is_some();
+            recorded.then_some((hour, observation))
+        })
+        .collect()
+}
+
+/// A WMO weather code in words
+fn condition(code: i64) -> Option<&'static str> {
+    Some(match code {
+        0 => "clear sky",
+        1 => "mainly clear",
+        2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51 => "light drizzle",
+        53 => "drizzle",
+        55 => "dense drizzle",
+        56 | 57 => "freezing drizzle",
+        61 => "light rain",
+        63 => "rain",
+        65 => "heavy rain",
+        66 | 67 => "freezing rain",
+        71 => "light snow",
+        73 => "snow",
+        75 => "heavy snow",
+        77 => "snow grains",
+        80 => "light rain showers",
+        81 => "rain showers",
+        82 => "violent rain showers",
+        85 | 86 => "snow showers",
+        95 => "thunderstorm",
+        96 | 99 => "thunderstorm with hail",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_hourly() {
+        let json = r#"{"latitude": 43.7, "longitude": 7.4, "hourly": {
+            "time": ["2024-05-01T00:00", "2024-05-01T01:00", "2024-05-01T02:00"],
+            "temperature_2m": [14.2, 13.9, null],
+            "precipitation": [0.0, 0.4, null],
+            "wind_speed_10m": [9.7, 11.2, null],
+            "cloud_cover": [20, 100, null],
+            "weather_code": [1, 61, null]
+        }}"#;
+        let response: ForecastResponse = serde_json::from_str(json).unwrap();
+
+        let hours = parse_hourly(response.hourly);
+        assert_eq!(hours.len(), 2);
+        let (hour, observed) = &hours[1];
+        assert_eq!(*hour, Utc.with_ymd_and_hms(2024, 5, 1, 1, 0, 0).unwrap());
+        assert_eq!(observed.temperature_c, Some(13.9));
+        assert_eq!(observed.precipitation_mm, Some(0.4));
+        assert_eq!(observed.cloud_cover_percent, Some(100.0));
+        assert_eq!(observed.condition.as_deref(), Some("light rain"));
+        assert_eq!(observed.source, "open-meteo");
+    }
+
+    #[test]
+    fn test_cache_key_rounds_location_and_time() {
+        let time = Utc.with_ymd_and_hms(2024, 5, 1, 23, 40, 0).unwrap();
+        assert_eq!(cache_key(43.7384, 7.4246, time), "43.7,7.4,2024-05-02T00");
+        assert_eq!(cache_key(-0.04, 7.46, time - ChronoDuration::minutes(20)), "0.0,7.5,2024-05-01T23");
+    }
+}
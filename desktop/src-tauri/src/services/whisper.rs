@@ -3,13 +3,19 @@
 //!
 //! Rust interface for executing Whisper.cpp for audio transcription.
 
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::RwLock;
 use tokio::process::Command;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
+use super::sidecar;
+use crate::resources::Priority;
+
 #[derive(Error, Debug)]
 pub enum WhisperError {
     #[error("Whisper binary not found at {0}")]
@@ -24,12 +30,20 @@ pub enum WhisperError {
     #[error("Failed to parse output: {0}")]
     ParseError(String),
     
+    #[error("Unknown Whisper model '{0}', expected one of: {1}")]
+    UnknownModel(String, String),
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
 
+/// Language that has Whisper detect the one spoken
+pub const AUTO_LANGUAGE: &str = "auto";
+
 /// Whisper model sizes
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+///
+/// Serialized by its whisper.cpp name (`"tiny"`, `"base.en"`, `"large-v3"`, ...).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum WhisperModel {
     Tiny,
     TinyEn,
@@ -43,6 +57,34 @@ pub enum WhisperModel {
 }
 
 impl WhisperModel {
+    /// Every model, smallest first
+    pub const ALL: [WhisperModel; 9] = [
+        WhisperModel::Tiny,
+        WhisperModel::TinyEn,
+        WhisperModel::Base,
+        WhisperModel::BaseEn,
+        WhisperModel::Small,
+        WhisperModel::SmallEn,
+        WhisperModel::Medium,
+        WhisperModel::MediumEn,
+        WhisperModel::Large,
+    ];
+    
+    /// Canonical whisper.cpp name, as in `ggml-<name>.bin`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WhisperModel::Tiny => "tiny",
+            WhisperModel::TinyEn => "tiny.en",
+            WhisperModel::Base => "base",
+            WhisperModel::BaseEn => "base.en",
+            WhisperModel::Small => "small",
+            WhisperModel::SmallEn => "small.en",
+            WhisperModel::Medium => "medium",
+            WhisperModel::MediumEn => "medium.en",
+            WhisperModel::Large => "large-v3",
+        }
+    }
+    
     pub fn filename(&self) -> &'static str {
         match self {
             WhisperModel::Tiny => "ggml-tiny.bin",
@@ -68,6 +110,46 @@ impl WhisperModel {
     }
 }
 
+impl fmt::Display for WhisperModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WhisperModel {
+    type Err = WhisperError;
+    
+    /// Accepts canonical names case-insensitively, plus `large` for the
+    /// current large model
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_lowercase();
+        if name == "large" {
+            return Ok(WhisperModel::Large);
+        }
+        
+        WhisperModel::ALL
+            .into_iter()
+            .find(|m| m.as_str() == name)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = WhisperModel::ALL.iter().map(|m| m.as_str()).collect();
+                WhisperError::UnknownModel(s.to_string(), valid.join(", "))
+            })
+    }
+}
+
+impl Serialize for WhisperModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for WhisperModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 /// A transcription segment
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranscriptionSegment {
@@ -86,7 +168,8 @@ pub struct Transcription {
 
 /// Whisper.cpp sidecar manager
 pub struct Whisper {
-    binary_path: PathBuf,
+    /// Can be swapped for a downloaded build while the app runs
+    binary_path: RwLock<PathBuf>,
     models_dir: PathBuf,
 }
 
@@ -106,11 +189,27 @@ impl Whisper {
         }
         
         Ok(Self {
-            binary_path,
+            binary_path: RwLock::new(binary_path),
             models_dir,
         })
     }
     
+    /// Where the Whisper binary is expected
+    pub fn binary_path(&self) -> PathBuf {
+        self.binary_path.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+    
+    /// Run Whisper from `path` from now on
+    pub fn set_binary_path(&self, path: PathBuf) {
+        info!("Using Whisper at {:?}", path);
+        *self.binary_path.write().unwrap_or_else(|e| e.into_inner()) = path;
+    }
+    
+    /// The directory models are looked up in
+    pub fn models_dir(&self) -> &Path {
+        &self.models_dir
+    }
+    
     /// Check if a model is available
     pub fn has_model(&self, model: WhisperModel) -> bool {
         self.models_dir.join(model.filename()).exists()
@@ -118,13 +217,20 @@ impl Whisper {
     
     /// Get available models
     pub fn available_models(&self) -> Vec<WhisperModel> {
-        use WhisperModel::*;
-        
-        [Tiny, TinyEn, Base, BaseEn, Small, SmallEn, Medium, MediumEn, Large]
+        WhisperModel::ALL
             .into_iter()
             .filter(|m| self.has_model(*m))
             .collect()
     }
+
+    /// The model to use when none is asked for: Base if it's installed, else
+    /// the smallest one that is
+    pub fn auto_model(&self) -> WhisperModel {
+        if self.has_model(WhisperModel::Base) {
+            return WhisperModel::Base;
+        }
+        self.available_models().into_iter().next().unwrap_or(WhisperModel::Base)
+    }
     
     /// Transcribe audio file
     pub async fn transcribe(
@@ -133,8 +239,24 @@ impl Whisper {
         model: WhisperModel,
         language: Option<&str>,
     ) -> Result<Transcription, WhisperError> {
-        if !self.binary_path.exists() {
-            return Err(WhisperError::BinaryNotFound(self.binary_path.clone()));
+        self.transcribe_with_progress(audio_path, model, language, |_| {}).await
+    }
+
+    /// [`transcribe`](Self::transcribe), passing `on_progress` how much of
+    /// the audio is done (0 to 1) as Whisper reports it
+    ///
+    /// A `language` of [`AUTO_LANGUAGE`] has Whisper detect it; the transcription then
+    /// has the language it detected.
+    pub async fn transcribe_with_progress(
+        &self,
+        audio_path: &PathBuf,
+        model: WhisperModel,
+        language: Option<&str>,
+        mut on_progress: impl FnMut(f32),
+    ) -> Result<Transcription, WhisperError> {
+        let binary_path = self.binary_path();
+        if !binary_path.exists() {
+            return Err(WhisperError::BinaryNotFound(binary_path));
         }
         
         let model_path = self.models_dir.join(model.filename());
@@ -158,16 +280,20 @@ impl Whisper {
             args.push(lang.to_string());
         }
         
-        let output = Command::new(&self.binary_path)
-            .args(&args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()
-            .await?;
+        let _permit = sidecar::acquire(Priority::Batch).await;
+        // Progress goes to stderr, along with the detected language and why it failed if it does
+        let mut detected = None;
+        let output = sidecar::run(Command::new(&binary_path).args(&args).stdout(Stdio::piped()), |line| {
+            if let Some(percent) = progress_percent(line) {
+                on_progress(percent / 100.0);
+            } else if let Some(language) = detected_language(line) {
+                detected = Some(language);
+            }
+        })
+        .await?;
         
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(WhisperError::ExecutionFailed(stderr.to_string()));
+            return Err(WhisperError::ExecutionFailed(output.stderr));
         }
         
         let stdout = String::from_utf8_lossy(&output.stdout);
@@ -183,13 +309,13 @@ impl Whisper {
         
         Ok(Transcription {
             segments,
-            language: language.map(|s| s.to_string()),
+            language: detected.or_else(|| language.filter(|l| *l != AUTO_LANGUAGE).map(|s| s.to_string())),
             full_text,
         })
     }
     
     /// Parse SRT format output
-    fn parse_srt(&self, content: &str) -> Result<Vec<TranscriptionSegment>, WhisperError> {
+    pub(crate) fn parse_srt(&self, content: &str) -> Result<Vec<TranscriptionSegment>, WhisperError> {
         let mut segments = Vec::new();
         let mut lines = content.lines().peekable();
         
@@ -201,12 +327,11 @@ impl Whisper {
                     if let Some((start, end)) = self.parse_timestamp_line(timestamp_line) {
                         // Collect text lines until empty line
                         let mut text_lines = Vec::new();
-                        while let Some(line) = lines.peek() {
+                        for line in lines.by_ref() {
                             if line.trim().is_empty() {
-                                lines.next();
                                 break;
                             }
-                            text_lines.push(lines.next().unwrap().to_string());
+                            text_lines.push(line.to_string());
                         }
                         
                         segments.push(TranscriptionSegment {
@@ -251,3 +376,58 @@ impl Whisper {
         }
     }
 }
+
+/// The percentage in a `-pp` progress line, e.g.
+/// `whisper_print_progress_callback: progress =  45%`
+fn progress_percent(line: &str) -> Option<f32> {
+    let (_, after) = line.split_once("progress =")?;
+    let percent: f32 = after.trim().strip_suffix('%')?.trim().parse().ok()?;
+    Some(percent.clamp(0.0, 100.0))
+}
+
+/// The language in a line like
+/// `whisper_full_with_state: auto-detected language: de (p = 0.967)`
+fn detected_language(line: &str) -> Option<String> {
+    let (_, after) = line.split_once("auto-detected language:")?;
+    let language = after.split_whitespace().next()?;
+    Some(language.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_percent() {
+        assert_eq!(progress_percent("whisper_print_progress_callback: progress =  45%"), Some(45.0));
+        assert_eq!(progress_percent("whisper_print_progress_callback: progress = 100%"), Some(100.0));
+        assert_eq!(progress_percent("whisper_full_with_state: auto-detected language: en"), None);
+        assert_eq!(progress_percent("progress = lots%"), None);
+    }
+
+    #[test]
+    fn test_detected_language() {
+        let line = "whisper_full_with_state: auto-detected language: de (p = 0.967315)";
+        assert_eq!(detected_language(line).as_deref(), Some("de"));
+        assert_eq!(detected_language("whisper_print_progress_callback: progress =  45%"), None);
+    }
+
+    #[test]
+    fn test_model_names_round_trip() {
+        for model in WhisperModel::ALL {
+            assert_eq!(model.to_string().parse::<WhisperModel>().unwrap(), model);
+            assert_eq!(model.filename(), format!("ggml-{}.bin", model));
+        }
+        assert_eq!("Large".parse::<WhisperModel>().unwrap(), WhisperModel::Large);
+        assert_eq!(serde_json::to_string(&WhisperModel::TinyEn).unwrap(), "\"tiny.en\"");
+        assert_eq!(serde_json::from_str::<WhisperModel>("\"base\"").unwrap(), WhisperModel::Base);
+    }
+
+    #[test]
+    fn test_unknown_model_lists_valid_names() {
+        let err = "huge".parse::<WhisperModel>().unwrap_err().to_string();
+        assert!(err.contains("'huge'"));
+        assert!(err.contains("tiny, tiny.en, base"));
+        assert!(serde_json::from_str::<WhisperModel>("\"TinyEn\"").is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! Wikipedia summaries
+//!
+//! The opening paragraph of a POI's article, from the Wikipedia REST
+//! summary API, for POIs whose OSM data names one with a `wikipedia` or
+//! `wikidata` tag. The article is read in the narration's language when
+//! the POI's own article is in it or Wikidata links one that is, and in
+//! the language of the POI's own article otherwise.
+
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::debug;
+
+use crate::types::ArticleSummary;
+
+/// Named as the source of the summaries
+pub const SOURCE: &str = "wikipedia";
+
+/// Longest extract kept; longer ones are cut at a sentence
+pub const MAX_EXTRACT_CHARS: usize = 1000;
+
+const WIKIDATA_ENDPOINT: &str = "https://www.wikidata.org/w/api.php";
+
+/// Language of articles read when neither the narration's nor the POI's is available
+const FALLBACK_LANGUAGE: &str = "en";
+
+const REQUEST_TIMEOUT_SECS: u64 = 15;
+
+/// Wikipedia codes of narration languages given by name
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("español", "es"),
+    ("espanol", "es"),
+    ("spanish", "es"),
+    ("castellano", "es"),
+    ("français", "fr"),
+    ("francais", "fr"),
+    ("french", "fr"),
+    ("deutsch", "de"),
+    ("german", "de"),
+    ("italiano", "it"),
+    ("italian", "it"),
+    ("português", "pt"),
+    ("portugues", "pt"),
+    ("portuguese", "pt"),
+    ("nederlands", "nl"),
+    ("dutch", "nl"),
+    ("polski", "pl"),
+    ("polish", "pl"),
+    ("svenska", "sv"),
+    ("swedish", "sv"),
+    ("norsk", "no"),
+    ("norwegian", "no"),
+    ("dansk", "da"),
+    ("danish", "da"),
+    ("русский", "ru"),
+    ("russian", "ru"),
+    ("日本語", "ja"),
+    ("japanese", "ja"),
+    ("中文", "zh"),
+    ("chinese", "zh"),
+];
+
+#[derive(Error, Debug)]
+pub enum WikiError {
+    #[error("Wikipedia is rate limiting requests, please try again later")]
+    RateLimited,
+
+    #[error("Wikipedia request failed: {0}")]
+    RequestFailed(String),
+}
+
+#[derive(Deserialize)]
+struct SummaryResponse {
+    #[serde(rename = "type", default)]
+    kind: String,
+    title: String,
+    #[serde(default)]
+    extract: String,
+    content_urls: Option<ContentUrls>,
+}
+
+#[derive(Deserialize)]
+struct ContentUrls {
+    desktop: Option<PageUrl>,
+}
+
+#[derive(Deserialize)]
+struct PageUrl {
+    page: String,
+}
+
+#[derive(Deserialize)]
+struct EntitiesResponse {
+    #[serde(default)]
+    entities: HashMap<String, Entity>,
+}
+
+#[derive(Deserialize)]
+struct Entity {
+    #[serde(default)]
+    sitelinks: HashMap<String, Sitelink>,
+}
+
+#[derive(Deserialize)]
+struct Sitelink {
+    title: String,
+}
+
+/// The Wikipedia code for a narration language like `Español` or `es`
+pub fn language_code(language: &str) -> Option<String> {
+    let language = language.trim().to_lowercase();
+    if let Some((_, code)) = LANGUAGE_NAMES.iter().find(|(name, _)| *name == language) {
+        return Some(code.to_string());
+    }
+    is_language_code(&language).then_some(language)
+}
+
+/// Language and title of an OSM `wikipedia` tag like `fr:Tour Eiffel`;
+/// tags without a language are taken as English
+pub fn parse_wikipedia_tag(tag: &str) -> Option<(String, String)> {
+    let (language, title) = match tag.split_once(':') {
+        Some((language, title)) if is_language_code(language) => (language.to_string(), title),
+        _ => (FALLBACK_LANGUAGE.to_string(), tag),
+    };
+    let title = title.trim().replace('_', " ");
+    (!title.is_empty()).then_some((language, title))
+}
+
+/// One key per article asked for, so POIs naming the same one share a fetch
+pub fn article_key(wikipedia: Option<&str>, wikidata: Option<&str>, language: &str) -> Option<String> {
+    if let Some(id) = wikidata.map(str::trim).filter(|id| is_wikidata_id(id)) {
+        return Some(format!("{}@{}", id, language));
+    }
+    let (tag_language, title) = parse_wikipedia_tag(wikipedia?)?;
+    Some(format!("{}:{}@{}", tag_language, title, language))
+}
+
+/// The summary of a POI's article, preferably in `language`; `None` when
+/// there's no such article, or it only lists pages of the same name
+pub async fn fetch_summary(
+    wikipedia: Option<&str>,
+    wikidata: Option<&str>,
+    language: &str,
+) -> Result<Option<ArticleSummary>, WikiError> {
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("GeoTruth/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| WikiError::RequestFailed(e.to_string()))?;
+
+    let tagged = wikipedia.and_then(parse_wikipedia_tag);
+    let wikidata = wikidata.map(str::trim).filter(|id| is_wikidata_id(id));
+    let article = match (&tagged, wikidata) {
+        (Some((tag_language, _)), _) if tag_language == language => tagged.clone(),
+        (_, Some(id)) => {
+            let mut wanted = vec![language];
+            wanted.extend(tagged.as_ref().map(|(tag_language, _)| tag_language.as_str()));
+            wanted.push(FALLBACK_LANGUAGE);
+            match sitelink(&client, id, &wanted).await {
+                Ok(Some(found)) => Some(found),
+                Ok(None) => tagged.clone(),
+                Err(e) if tagged.is_some() => {
+                    debug!("Wikidata lookup of {} failed, reading the tagged article: {}", id, e);
+                    tagged.clone()
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        _ => tagged.clone(),
+    };
+    let Some((language, title)) = article else {
+        return Ok(None);
+    };
+
+    let mut url = reqwest::Url::parse(&format!("https://{}.wikipedia.org/api/rest_v1/page/summary/", language))
+        .map_err(|e| WikiError::RequestFailed(e.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|_| WikiError::RequestFailed("Unexpected summary URL".to_string()))?
+        .pop_if_empty()
+        .push(&title.replace(' ', "_"));
+
+    let response = client.get(url).send().await.map_err(|e| WikiError::RequestFailed(e.to_string()))?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_FOUND {
+        debug!("No {} Wikipedia article titled {}", language, title);
+        return Ok(None);
+    }
+    check_status(status)?;
+
+    let body: SummaryResponse = response
+        .json()
+        .await
+        .map_err(|e| WikiError::RequestFailed(format!("Unexpected Wikipedia summary: {}", e)))?;
+    Ok(summary_from(body, &language))
+}
+
+/// The first of `languages` Wikidata links an article in, with its title
+async fn sitelink(
+    client: &reqwest::Client,
+    id: &str,
+    languages: &[&str],
+) -> Result<Option<(String, String)>, WikiError> {
+    let sites: Vec<String> = languages.iter().map(|language| format!("{}wiki", language.replace('-', "_"))).collect();
+    let response = client
+        .get(WIKIDATA_ENDPOINT)
+        .query(&[
+            ("action", "wbgetentities"),
+            ("ids", id),
+            ("props", "sitelinks"),
+            ("sitefilter", &sites.join("|")),
+            ("format", "json"),
+        ])
+        .send()
+        .await
+        .map_err(|e| WikiError::RequestFailed(e.to_string()))?;
+    check_status(response.status())?;
+
+    let body: EntitiesResponse = response
+        .json()
+        .await
+        .map_err(|e| WikiError::RequestFailed(format!("Unexpected Wikidata response: {}", e)))?;
+    Ok(pick_sitelink(body, id, languages))
+}
+
+fn pick_sitelink(body: EntitiesResponse, id: &str, languages: &[&str]) -> Option<(String, String)> {
+    let entity = body.entities.get(id)?;
+    languages.iter().find_map(|language| {
+        let link = entity.sitelinks.get(&format!("{}wiki", language.replace('-', "_")))?;
+        Some((language.to_string(), link.title.clone()))
+    })
+}
+
+fn check_status(status: reqwest::StatusCode) -> Result<(), WikiError> {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(WikiError::RateLimited);
+    }
+    if !status.is_success() {
+        return Err(WikiError::RequestFailed(status.to_string()));
+    }
+    Ok(())
+}
+
+fn summary_from(response: SummaryResponse, language: &str) -> Option<ArticleSummary> {
+    let extract = trim_extract(&response.extract, MAX_EXTRACT_CHARS);
+    if response.kind == "disambiguation" || extract.is_empty() {
+        return None;
+    }
+    Some(ArticleSummary {
+        title: response.title,
+        language: language.to_string(),
+        extract,
+        url: response.content_urls.and_then(|urls| urls.desktop).map(|desktop| desktop.page),
+        source: SOURCE.to_string(),
+        retrieved_on: Utc::now().date_naive(),
+    })
+}
+
+/// `text` in at most `max_chars`, cut after its last whole sentence that
+/// fits, or at a word when the first sentence alone is too long
+pub fn trim_extract(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= max_chars {
+        return text;
+    }
+    let cut: String = text.chars().take(max_chars).collect();
+    let sentence_end = cut
+        .char_indices()
+        .filter(|&(i, c)| matches!(c, '.' | '!' | '?' | '。') && cut[i + c.len_utf8()..].starts_with([' ', '\u{3000}']))
+        .map(|(i, c)| i + c.len_utf8())
+        .next_back();
+    match sentence_end {
+        Some(end) => cut[..end].to_string(),
+        None => {
+            let words = cut.rsplit_once(' ').map_or(cut.as_str(), |(words, _)| words);
+            format!("{}…", words.trim_end_matches([',', ';', ':']))
+        }
+    }
+}
+
+/// A Wikipedia language code like `en` or `zh-yue`, safe to put in a host name
+fn is_language_code(code: &str) -> bool {
+    (2..=12).contains(&code.len())
+        && code.split('-').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_lowercase()))
+}
+
+fn is_wikidata_id(id: &str) -> bool {
+    id.len() > 1 && id.starts_with('Q') && id[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_and_languages() {
+        assert_eq!(parse_wikipedia_tag("fr:Tour_Eiffel"), Some(("fr".into(), "Tour Eiffel".into())));
+        assert_eq!(parse_wikipedia_tag("Big Ben"), Some(("en".into(), "Big Ben".into())));
+        assert_eq!(parse_wikipedia_tag("Star Wars: A New Hope"), Some(("en".into(), "Star Wars: A New Hope".into())));
+        assert_eq!(parse_wikipedia_tag("de: "), None);
+
+        assert_eq!(language_code(" Español ").as_deref(), Some("es"));
+        assert_eq!(language_code("de").as_deref(), Some("de"));
+        assert_eq!(language_code("evil.example.com/"), None);
+
+        assert_eq!(article_key(Some("fr:Tour Eiffel"), Some("Q243"), "es").as_deref(), Some("Q243@es"));
+        assert_eq!(article_key(Some("fr:Tour_Eiffel"), Some("243"), "es").as_deref(), Some("fr:Tour Eiffel@es"));
+        assert_eq!(article_key(None, None, "en"), None);
+    }
+
+    #[test]
+    fn test_summary_from_response() {
+        let json = r#"{"type": "standard", "title": "Prince's Palace of Monaco",
+            "extract": "The Prince's Palace of Monaco is the official residence of the Sovereign Prince of Monaco.",
+            "content_urls": {"desktop": {"page": "https://en.wikipedia.org/wiki/Prince%27s_Palace_of_Monaco"}}}"#;
+        let summary = summary_from(serde_json::from_str(json).unwrap(), "en").unwrap();
+        assert_eq!(summary.title, "Prince's Palace of Monaco");
+        assert_eq!(summary.source, "wikipedia");
+        assert_eq!(summary.url.as_deref(), Some("https://en.wikipedia.org/wiki/Prince%27s_Palace_of_Monaco"));
+
+        let json = r#"{"type": "disambiguation", "title": "Palace", "extract": "Palace may refer to:"}"#;
+        assert!(summary_from(serde_json::from_str(json).unwrap(), "en").is_none());
+    }
+
+    #[test]
+    fn test_sitelink_follows_language_preference() {
+        let json = r#"{"entities": {"Q243": {"sitelinks": {
+            "enwiki": {"site": "enwiki", "title": "Eiffel Tower"},
+            "frwiki": {"site": "frwiki", "title": "Tour Eiffel"}
+        }}}}"#;
+        let body = || serde_json::from_str::<EntitiesResponse>(json).unwrap();
+        assert_eq!(pick_sitelink(body(), "Q243", &["es", "fr", "en"]), Some(("fr".into(), "Tour Eiffel".into())));
+        assert_eq!(pick_sitelink(body(), "Q243", &["en", "fr"]), Some(("en".into(), "Eiffel Tower".into())));
+        assert_eq!(pick_sitelink(body(), "Q1", &["en"]), None);
+    }
+
+    #[test]
+    fn test_trim_extract_keeps_whole_sentences() {
+        let text = "The tower is 330 m tall.  It was built for the 1889 World's Fair. It is the most visited monument.";
+        assert_eq!(trim_extract(text, 500), text.replace("  ", " "));
+        assert_eq!(trim_extract(text, 70), "The tower is 330 m tall. It was built for the 1889 World's Fair.");
+        assert_eq!(trim_extract("A very long first sentence without any end", 20), "A very long first…");
+    }
+}
@@ -0,0 +1,380 @@
+//! User Settings
+//!
+//! Persistent, user-editable settings stored in `settings.json` under the
+//! app data directory. Environment-based configuration lives in `config`,
+//! which falls back on these settings.
+//!
+//! The file records the [`SCHEMA_VERSION`] it was written with; one from an
+//! earlier version is migrated as it's loaded. Settings can be changed one
+//! at a time or several at once with a JSON merge patch ([`patched`]), and
+//! are validated as a whole before they're kept.
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tracing::{info, warn};
+
+use crate::gemini::{GeminiModels, NetworkSettings, RetryPolicy, SafetySettings};
+use crate::llm::LlmEngine;
+use crate::llm_queue::RateLimit;
+use crate::local_llm::LocalLlmSettings;
+use crate::logging;
+use crate::memory_cache::CacheLimits;
+use crate::narrative::NarrationChunking;
+use crate::processor::ProcessOptions;
+use crate::resources::ResourceLimits;
+use crate::services::data_manager::ConnectivityMode;
+use crate::services::ffmpeg::ImageFormat;
+use crate::services::mirrors;
+use crate::services::sync::InterpolationPolicy;
+use crate::updater::UpdatePolicy;
+
+/// Layout of the settings file; raised when a setting moves or changes meaning
+///
+/// 1: `max_sidecar_processes` became `resource_limits.cpu`
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Application settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    /// Layout the settings were written with (0 = from before there were versions)
+    pub schema_version: u32,
+    /// GeoTruth API server (`None` = `GEOTRUTH_API_URL`, else the local
+    /// Docker backend)
+    pub api_url: Option<String>,
+    /// Slots for CPU-heavy work, IO-heavy work and network transfers
+    pub resource_limits: ResourceLimits,
+    /// Format for captured frames and moment thumbnails
+    pub thumbnail_format: ImageFormat,
+    /// Alternate region download URL templates, tried in order after Geofabrik
+    pub download_mirrors: Vec<String>,
+    /// Cap on region download speed, in bytes per second (0 = none)
+    pub download_rate_limit: u64,
+    /// When downloaded regions are refreshed in the background
+    pub update_policy: UpdatePolicy,
+    /// Time of the last completed scheduled update run (RFC 3339)
+    pub last_scheduled_update: Option<String>,
+    /// Whether online services and remote map data may be used
+    pub connectivity_mode: ConnectivityMode,
+    /// Most online calls one enrichment run, e.g. of a video's events, may
+    /// make (`None` = 50)
+    pub enrichment_call_budget: Option<usize>,
+    /// Limits on estimating positions across GPS dropouts
+    pub interpolation: InterpolationPolicy,
+    /// How videos are processed when no options are given
+    pub processing: ProcessOptions,
+    /// Queued videos processed at once (`None` = 1; Whisper alone keeps
+    /// the CPU busy)
+    pub processing_concurrency: Option<usize>,
+    /// Seconds Whisper took per second of audio on this machine, by model,
+    /// as timed for processing estimates
+    pub whisper_speed: BTreeMap<String, f64>,
+    /// Bounds on each in-memory cache, of Truth Bundles and of enrichments
+    pub memory_cache: CacheLimits,
+    /// Retries for rate-limited or overloaded Gemini requests
+    pub gemini_retry: RetryPolicy,
+    /// Gemini model for each feature
+    pub gemini_models: GeminiModels,
+    /// Caps on Gemini requests, shared by all features
+    pub gemini_rate_limit: RateLimit,
+    /// How readily Gemini blocks responses, per harm category
+    pub gemini_safety: SafetySettings,
+    /// Timeouts and proxy for Gemini requests
+    pub gemini_network: NetworkSettings,
+    /// Whether narration and enrichment use Gemini or a local model
+    pub llm_engine: LlmEngine,
+    /// Local model server used when the engine resolves to local
+    pub local_llm: LocalLlmSettings,
+    /// How many events and how much transcript go into each narration request
+    pub narration_chunking: NarrationChunking,
+    /// From 0, sticking to the facts, to 1, free with its words; sets the
+    /// sampling narrations don't set themselves (`None` = the model's defaults)
+    pub narration_creativity: Option<f32>,
+    /// Whether clock offsets may be estimated from the sun's position in a
+    /// frame; experimental, so off unless asked for
+    pub experimental_sun_sync: bool,
+    /// Whether feature usage is counted in the local database, for the user
+    /// to look at and choose to share; off unless asked for
+    pub usage_metrics: bool,
+    /// Log filter, e.g. `debug` (`None` = default; `RUST_LOG` takes precedence)
+    pub log_level: Option<String>,
+    /// Where processing jobs keep their temporary files, in a `jobs`
+    /// directory (`None` = the app cache); takes effect on the next start
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl AppSettings {
+    /// The settings of a fresh install
+    pub fn defaults() -> Self {
+        Self { schema_version: SCHEMA_VERSION, ..Default::default() }
+    }
+
+    /// Check every setting, naming the first that's invalid
+    pub fn validate(&self) -> Result<(), String> {
+        let field = |name: &str, result: Result<(), String>| result.map_err(|e| format!("{}: {}", name, e));
+
+        if let Some(url) = &self.api_url {
+            match reqwest::Url::parse(url) {
+                Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+                _ => return Err(format!("api_url: Invalid server URL: {}", url)),
+            }
+        }
+        field("resource_limits", self.resource_limits.validate())?;
+        for url in &self.download_mirrors {
+            field("download_mirrors", mirrors::validate_template(url).map_err(|e| e.to_string()))?;
+        }
+        field("update_policy", self.update_policy.validate())?;
+        field("interpolation", self.interpolation.validate())?;
+        field("processing", self.processing.validate())?;
+        if self.processing.audio_stream_index.is_some() || self.processing.sync_offset_seconds.is_some() {
+            return Err("processing: The audio track and sync offset are chosen per video, not kept as defaults".to_string());
+        }
+        if self.processing_concurrency == Some(0) {
+            return Err("processing_concurrency: Limit must be at least 1".to_string());
+        }
+        field("memory_cache", self.memory_cache.validate())?;
+        field("gemini_retry", self.gemini_retry.validate())?;
+        field("gemini_rate_limit", self.gemini_rate_limit.validate())?;
+        field("gemini_network", self.gemini_network.validate())?;
+        let models = &self.gemini_models;
+        if [&models.narration_model, &models.enrichment_model, &models.vision_model].iter().any(|m| m.trim().is_empty()) {
+            return Err("gemini_models: Every feature needs a model".to_string());
+        }
+        field("local_llm", self.local_llm.validate())?;
+        field("narration_chunking", self.narration_chunking.validate())?;
+        if self.narration_creativity.is_some_and(|c| !(0.0..=1.0).contains(&c)) {
+            return Err("narration_creativity: Creativity must be from 0 to 1".to_string());
+        }
+        if let Some(level) = &self.log_level {
+            field("log_level", logging::parse_filter(Some(level)).map(|_| ()))?;
+        }
+        if self.temp_dir.as_ref().is_some_and(|dir| !dir.is_absolute()) {
+            return Err("temp_dir: The temporary directory must be an absolute path".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Global settings, loaded from disk on first access
+static SETTINGS: Lazy<RwLock<AppSettings>> = Lazy::new(|| {
+    RwLock::new(load_from_disk().unwrap_or_else(AppSettings::defaults))
+});
+
+/// Snapshot of the current settings
+pub fn get() -> AppSettings {
+    SETTINGS.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Modify the settings and persist them, returning the new values
+pub fn update(f: impl FnOnce(&mut AppSettings)) -> AppSettings {
+    let mut settings = SETTINGS.write().unwrap_or_else(|e| e.into_inner());
+    f(&mut settings);
+    save_to_disk(&settings);
+    settings.clone()
+}
+
+/// Modify the settings and persist them if `f` succeeds and they're all
+/// still valid, returning the new values; otherwise they're left as they were
+pub fn try_update(f: impl FnOnce(&mut AppSettings) -> Result<(), String>) -> Result<AppSettings, String> {
+    let mut settings = SETTINGS.write().unwrap_or_else(|e| e.into_inner());
+    let mut changed = settings.clone();
+    f(&mut changed)?;
+    changed.validate()?;
+    *settings = changed;
+    save_to_disk(&settings);
+    Ok(settings.clone())
+}
+
+/// Restore the defaults, keeping what was timed on this machine and when
+/// regions were last updated
+pub fn reset() -> AppSettings {
+    update(|s| {
+        *s = AppSettings {
+            whisper_speed: std::mem::take(&mut s.whisper_speed),
+            last_scheduled_update: s.last_scheduled_update.take(),
+            ..AppSettings::defaults()
+        }
+    })
+}
+
+/// `settings` with `changes` applied, a JSON merge patch (RFC 7396):
+/// objects are merged field by field, and `null` restores a field's default
+///
+/// Fields the settings don't have are refused, as is the schema version.
+pub fn patched(settings: &AppSettings, changes: Value) -> Result<AppSettings, String> {
+    let Value::Object(changes) = changes else {
+        return Err("Settings changes must be an object".to_string());
+    };
+    if changes.contains_key("schema_version") {
+        return Err("schema_version can't be changed".to_string());
+    }
+    let mut value = serde_json::to_value(settings).map_err(|e| e.to_string())?;
+    if let Value::Object(fields) = &mut value {
+        merge(fields, changes, "")?;
+    }
+    serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))
+}
+
+fn merge(target: &mut Map<String, Value>, changes: Map<String, Value>, path: &str) -> Result<(), String> {
+    for (key, change) in changes {
+        let name = format!("{}{}", path, key);
+        let Some(current) = target.get_mut(&key) else {
+            return Err(format!("Unknown setting: {}", name));
+        };
+        match change {
+            Value::Null => {
+                target.remove(&key);
+            }
+            Value::Object(change) => match current {
+                Value::Object(current) => merge(current, change, &format!("{}.", name))?,
+                current => *current = Value::Object(change),
+            },
+            change => *current = change,
+        }
+    }
+    Ok(())
+}
+
+/// Bring settings written with an earlier [`SCHEMA_VERSION`] up to date
+fn migrate(value: &mut Value) {
+    let Value::Object(fields) = value else {
+        return;
+    };
+    let version = fields.get("schema_version").and_then(Value::as_u64).unwrap_or(0);
+    if version > SCHEMA_VERSION as u64 {
+        warn!("Settings are from a newer version ({}); settings it added are dropped", version);
+    }
+    if version < 1 {
+        if let Some(limit) = fields.remove("max_sidecar_processes").filter(|limit| !limit.is_null()) {
+            let limits = fields.entry("resource_limits").or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(limits) = limits {
+                limits.entry("cpu").and_modify(|cpu| if cpu.is_null() { *cpu = limit.clone() }).or_insert(limit);
+            }
+        }
+    }
+    fields.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+}
+
+/// Settings from the contents of a settings file, migrated
+fn parse(json: &str) -> Result<AppSettings, String> {
+    let mut value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    migrate(&mut value);
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// Helper to get persistence file path
+fn settings_file_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("com.geotruth.app")
+        .join("settings.json")
+}
+
+/// Helper to save settings to disk
+fn save_to_disk(settings: &AppSettings) {
+    let path = settings_file_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+
+    match serde_json::to_string_pretty(settings) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                warn!("Failed to save settings: {}", e);
+            } else {
+                info!("Saved settings to {:?}", path);
+            }
+        }
+        Err(e) => warn!("Failed to serialize settings: {}", e),
+    }
+}
+
+/// Helper to load settings from disk
+fn load_from_disk() -> Option<AppSettings> {
+    let path = settings_file_path();
+    if !path.exists() {
+        return None;
+    }
+
+    match std::fs::read_to_string(&path) {
+        Ok(json) => match parse(&json) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                warn!("Failed to parse settings file: {}", e);
+                None
+            }
+        },
+        Err(e) => {
+            warn!("Failed to read settings file: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_settings_from_before_versions_are_migrated() {
+        let settings = parse(r#"{"max_sidecar_processes": 3, "thumbnail_format": "webp"}"#).unwrap();
+        assert_eq!(settings.schema_version, SCHEMA_VERSION);
+        assert_eq!(settings.resource_limits, ResourceLimits { cpu: Some(3), ..Default::default() });
+        assert_eq!(settings.thumbnail_format, ImageFormat::Webp);
+
+        // Set since, the CPU slots win
+        let settings = parse(r#"{"max_sidecar_processes": 3, "resource_limits": {"cpu": 5}}"#).unwrap();
+        assert_eq!(settings.resource_limits.cpu, Some(5));
+        let settings = parse(r#"{"schema_version": 1, "resource_limits": {"io": 4}}"#).unwrap();
+        assert_eq!(settings.resource_limits, ResourceLimits { io: Some(4), ..Default::default() });
+    }
+
+    #[test]
+    fn test_changes_are_merged_field_by_field() {
+        let mut settings = AppSettings::defaults();
+        settings.narration_creativity = Some(0.3);
+        settings.processing.transcription = false;
+
+        let changed = patched(
+            &settings,
+            json!({"processing": {"weather": true}, "narration_creativity": null, "download_rate_limit": 1024}),
+        )
+        .unwrap();
+        assert!(changed.processing.weather && !changed.processing.transcription);
+        assert_eq!(changed.narration_creativity, None);
+        assert_eq!(changed.download_rate_limit, 1024);
+        // A whole section set back to its defaults
+        let changed = patched(&changed, json!({"processing": null})).unwrap();
+        assert_eq!(changed.processing.transcription, ProcessOptions::default().transcription);
+
+        let err = patched(&settings, json!({"processing": {"speed": 2}})).unwrap_err();
+        assert_eq!(err, "Unknown setting: processing.speed");
+        assert!(patched(&settings, json!({"schema_version": 7})).is_err());
+        assert!(patched(&settings, json!({"download_rate_limit": "fast"})).unwrap_err().starts_with("Invalid settings"));
+        assert!(patched(&settings, json!([1])).is_err());
+    }
+
+    #[test]
+    fn test_validation_names_the_invalid_setting() {
+        assert!(AppSettings::defaults().validate().is_ok());
+
+        let invalid = [
+            json!({"api_url": "ftp://example.com"}),
+            json!({"memory_cache": {"max_entries": 0}}),
+            json!({"gemini_retry": {"initial_backoff_ms": 60000}}),
+            json!({"narration_creativity": 1.5}),
+            json!({"processing": {"sync_offset_seconds": 2.0}}),
+            json!({"temp_dir": "relative/jobs"}),
+        ];
+        for changes in invalid {
+            let field = changes.as_object().unwrap().keys().next().unwrap().clone();
+            let err = patched(&AppSettings::defaults(), changes).unwrap().validate().unwrap_err();
+            assert!(err.starts_with(&format!("{}: ", field)), "{}", err);
+        }
+    }
+}
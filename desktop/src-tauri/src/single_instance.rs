@@ -0,0 +1,89 @@
+//! Single Instance
+//!
+//! Launching the app again hands over to the window already open instead
+//! of starting a second copy that would fight it for the database. The
+//! running app comes to the front, and files the new launch was given
+//! (e.g. a video opened with GeoTruth) are passed to the frontend.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tracing::{info, warn};
+
+/// Files another launch asked to open, as `external-open-request` sends them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExternalOpenRequest {
+    pub paths: Vec<String>,
+}
+
+/// The plugin that forwards later launches to this one; registered first,
+/// so a second launch exits before setting anything up
+pub fn plugin<R: Runtime>() -> TauriPlugin<R> {
+    tauri_plugin_single_instance::init(on_second_launch)
+}
+
+fn on_second_launch<R: Runtime>(app: &AppHandle<R>, args: Vec<String>, cwd: String) {
+    info!("Another launch was forwarded here ({} arguments)", args.len().saturating_sub(1));
+    if let Some(window) = app.get_webview_window("main") {
+        window.unminimize().ok();
+        window.show().ok();
+        if let Err(e) = window.set_focus() {
+            warn!("Failed to focus the main window: {}", e);
+        }
+    }
+
+    let paths = requested_paths(&args, Path::new(&cwd));
+    if paths.is_empty() {
+        return;
+    }
+    let request = ExternalOpenRequest {
+        paths: paths.iter().map(|path| path.to_string_lossy().to_string()).collect(),
+    };
+    if let Err(e) = app.emit("external-open-request", request) {
+        warn!("Failed to forward files to open: {}", e);
+    }
+}
+
+/// Existing files among a launch's arguments, made absolute against the
+/// directory it was started in
+///
+/// The first argument is the executable; options starting with `-` are skipped.
+fn requested_paths(args: &[String], cwd: &Path) -> Vec<PathBuf> {
+    args.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    #[test]
+    fn test_requested_paths_are_existing_files_resolved_against_cwd() {
+        let dir = fixtures::temp_dir("instance");
+        std::fs::write(dir.join("ride.mp4"), b"mp4").unwrap();
+        let absolute = dir.join("track.gpx");
+        std::fs::write(&absolute, b"gpx").unwrap();
+
+        let args: Vec<String> = [
+            "geotruth",
+            "ride.mp4",
+            "--verbose",
+            absolute.to_str().unwrap(),
+            "missing.mp4",
+            ".",
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+        assert_eq!(requested_paths(&args, &dir), vec![dir.join("ride.mp4"), absolute]);
+        assert!(requested_paths(&args[..1], &dir).is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
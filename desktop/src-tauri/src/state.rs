@@ -1,29 +1,95 @@
 #![allow(unused)]
-use crate::types::TruthBundle;
+use crate::error::ErrorCode;
+use crate::jobs::{JobHandle, JobRecord};
+use crate::memory_cache::MemoryCache;
+use crate::processing_queue::ProcessingQueue;
+use crate::request_history::RequestHistory;
+use crate::resources::{self, ResourceGovernor};
+use crate::settings;
+use crate::types::{EnrichResponse, LocationResult, TruthBundle};
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tracing::debug;
 
 /// In-memory state shared across the application
 pub struct AppState {
-    /// Caching for truth bundles or temporary processing results
-    pub truth_cache: DashMap<String, TruthBundle>,
-    /// Active processing jobs
-    pub active_jobs: DashMap<String, JobStatus>,
+    /// Truth Bundles by video id
+    pub truth_cache: MemoryCache<TruthBundle>,
+    /// This run's jobs, running or finished
+    pub active_jobs: DashMap<String, JobRecord>,
+    /// The latest changes of this run's jobs, oldest first, for views that
+    /// missed their events
+    pub job_events: DashMap<String, VecDeque<JobRecord>>,
+    /// Kind and handle of the jobs still running, to cancel them
+    pub job_tasks: DashMap<String, (&'static str, JobHandle)>,
+    /// Enrichment results by coordinate rounded to about 10 m
+    pub enrich_cache: MemoryCache<EnrichResponse>,
+    /// Recent narration and enrichment requests, in debug builds
+    pub request_history: Arc<RequestHistory>,
+    /// Videos waiting to be processed, or being processed, in the background
+    pub processing_queue: Arc<ProcessingQueue>,
+    /// Slots for CPU-heavy work, IO-heavy work and network transfers, the
+    /// same ones the services take
+    pub resources: Arc<ResourceGovernor>,
 }
 
 impl AppState {
     pub fn new() -> Self {
+        let limits = settings::get().memory_cache;
         Self {
-            truth_cache: DashMap::new(),
+            truth_cache: MemoryCache::new(limits),
             active_jobs: DashMap::new(),
+            job_events: DashMap::new(),
+            job_tasks: DashMap::new(),
+            enrich_cache: MemoryCache::new(limits),
+            request_history: Arc::new(RequestHistory::for_build()),
+            processing_queue: Arc::new(ProcessingQueue::new()),
+            resources: resources::shared(),
         }
     }
+
+    /// Forget what was cached from a region's map data, as it's downloaded
+    /// again; `bounds` are (min lat, min lon, max lat, max lon)
+    pub fn region_changed(&self, bounds: (f64, f64, f64, f64)) {
+        let inside = |location: &LocationResult| {
+            let (min_lat, min_lon, max_lat, max_lon) = bounds;
+            (min_lat..=max_lat).contains(&location.lat) && (min_lon..=max_lon).contains(&location.lon)
+        };
+        let enrichments = self.enrich_cache.retain(|_, response| !inside(&response.location));
+        let bundles = self
+            .truth_cache
+            .retain(|_, bundle| !bundle.events.iter().filter_map(|e| e.location.as_ref()).any(inside));
+        debug!("Dropped {} cached enrichments and {} bundles for new map data", enrichments, bundles);
+    }
+
+    /// Forget the cached bundle of a video, as the user overrides one of its events
+    pub fn event_overridden(&self, video_id: &str) {
+        self.truth_cache.remove(video_id);
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
 pub enum JobStatus {
     Pending,
+    /// `progress` from 0 to 1
     Processing { progress: f32 },
     Completed,
-    Failed { error: String },
+    /// `code` as a command failing with `error` would have it; records kept
+    /// from before there were codes read as `internal`
+    Failed {
+        error: String,
+        #[serde(default)]
+        code: ErrorCode,
+    },
+    Cancelled,
+}
+
+impl JobStatus {
+    /// Whether the job has stopped, one way or another
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed { .. } | Self::Cancelled)
+    }
 }
@@ -0,0 +1,347 @@
+//! Template Narration
+//!
+//! Narration without a model, for when neither Gemini nor the local server
+//! can be reached. The Truth Bundle's events are grouped into chapters at
+//! time gaps and changes of place, and each landmark passed gets a line
+//! filled in from fixed sentences and its verified facts. It reads plainer
+//! than a model's script, but says nothing the bundle doesn't back.
+//!
+//! Time codes count from the first event, as the model is asked to. Event
+//! timestamps are UTC and the local time zone isn't known, so the lines
+//! don't mention the time of day.
+
+use chrono::{DateTime, Utc};
+
+use crate::pacing;
+use crate::services::gps::haversine_distance;
+use crate::types::{Chapter, POIFacts, ScriptSegment, TruthBundle, TruthEvent, POI};
+
+/// Engine name reported in the response meta
+pub const ENGINE: &str = "offline-template";
+
+/// A pause this long between events starts a new chapter
+const CHAPTER_GAP_SECONDS: i64 = 300;
+
+/// Moving this far from where a chapter started starts a new one...
+const CHAPTER_DISTANCE_KM: f64 = 2.0;
+
+/// ...once the chapter has run this long
+const MIN_CHAPTER_SECONDS: i64 = 30;
+
+/// Landmarks named in a chapter's description
+const DESCRIBED_LANDMARKS: usize = 3;
+
+/// Languages with bundled sentences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateLanguage {
+    English,
+    Spanish,
+}
+
+impl TemplateLanguage {
+    /// The bundled language for a narration option like `English`, `Español` or `es`
+    pub fn from_name(language: &str) -> Option<Self> {
+        match language.trim().to_lowercase().as_str() {
+            "english" | "en" => Some(Self::English),
+            "spanish" | "español" | "espanol" | "castellano" | "es" => Some(Self::Spanish),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::English => "English",
+            Self::Spanish => "Español",
+        }
+    }
+
+    fn chapter_title(self, landmark: Option<&str>, number: usize) -> String {
+        match (self, landmark) {
+            (Self::English, Some(name)) => format!("Near {}", name),
+            (Self::English, None) => format!("Part {}", number),
+            (Self::Spanish, Some(name)) => format!("Cerca de {}", name),
+            (Self::Spanish, None) => format!("Parte {}", number),
+        }
+    }
+
+    fn chapter_description(self, landmarks: &[&str]) -> Option<String> {
+        if landmarks.is_empty() {
+            return None;
+        }
+        Some(match self {
+            Self::English => format!("Passing {}.", self.list(landmarks)),
+            Self::Spanish => format!("Pasando por {}.", self.list(landmarks)),
+        })
+    }
+
+    /// The line for the first landmark of an event
+    fn passing(self, poi: &POI) -> String {
+        match self {
+            Self::English => match category_noun(&poi.category) {
+                Some(noun) => format!("You pass {}, {} {}.", poi.name, indefinite_article(&noun), noun),
+                None => format!("You pass {}.", poi.name),
+            },
+            // Categories are English map tags; without a translation, leave them out
+            Self::Spanish => format!("Pasas junto a {}.", poi.name),
+        }
+    }
+
+    fn nearby(self, name: &str) -> String {
+        match self {
+            Self::English => format!("{} is nearby.", name),
+            Self::Spanish => format!("Cerca está {}.", name),
+        }
+    }
+
+    /// The line for a chapter with no landmarks to name
+    fn continues(self) -> &'static str {
+        match self {
+            Self::English => "The journey continues.",
+            Self::Spanish => "El viaje continúa.",
+        }
+    }
+
+    /// One sentence per verified fact, each standing alone so no word has
+    /// to agree with the landmark's name
+    fn facts(self, facts: &POIFacts) -> Vec<String> {
+        let mut sentences = Vec::new();
+        if let Some(established) = facts.established.as_deref().map(str::trim).filter(|e| !e.is_empty()) {
+            sentences.push(match self {
+                Self::English => format!("It dates from {}.", established),
+                Self::Spanish => format!("Data de {}.", established),
+            });
+        }
+        if facts.unesco_site == Some(true) {
+            sentences.push(
+                match self {
+                    Self::English => "It is a UNESCO World Heritage Site.",
+                    Self::Spanish => "Es Patrimonio de la Humanidad de la UNESCO.",
+                }
+                .to_string(),
+            );
+        }
+        if let Some(depth) = facts.depth_m.filter(|d| d.is_finite() && *d > 0.0) {
+            sentences.push(match self {
+                Self::English => format!("It is {} metres deep.", self.number(depth)),
+                Self::Spanish => format!("Tiene {} metros de profundidad.", self.number(depth)),
+            });
+        }
+        sentences
+    }
+
+    /// Whole numbers as they are, others to one decimal in the language's notation
+    fn number(self, value: f64) -> String {
+        let text = if value.fract() == 0.0 { format!("{:.0}", value) } else { format!("{:.1}", value) };
+        match self {
+            Self::English => text,
+            Self::Spanish => text.replace('.', ","),
+        }
+    }
+
+    /// `a`, `a and b`, `a, b and c`
+    fn list(self, items: &[&str]) -> String {
+        let Some((last, rest)) = items.split_last() else { return String::new() };
+        if rest.is_empty() {
+            return last.to_string();
+        }
+        let and = match self {
+            Self::English => "and",
+            // `y` becomes `e` before an /i/ sound, as in `Pisa e Ibiza`
+            Self::Spanish if starts_with_i_sound(last) => "e",
+            Self::Spanish => "y",
+        };
+        format!("{} {} {}", rest.join(", "), and, last)
+    }
+}
+
+/// What the model would have written, built from the bundle alone
+#[derive(Debug, Clone)]
+pub struct TemplateNarration {
+    pub chapters: Vec<Chapter>,
+    pub segments: Vec<ScriptSegment>,
+    /// The language written in: the one asked for, or English if it isn't bundled
+    pub language: TemplateLanguage,
+}
+
+/// Narrate `bundle` in `language`, or in English if it has no bundled sentences
+pub fn narrate(bundle: &TruthBundle, language: &str) -> TemplateNarration {
+    let language = TemplateLanguage::from_name(language).unwrap_or(TemplateLanguage::English);
+    let mut events: Vec<&TruthEvent> = bundle.events.iter().collect();
+    events.sort_by_key(|e| e.timestamp);
+    let Some(first) = events.first().map(|e| e.timestamp) else {
+        return TemplateNarration { chapters: Vec::new(), segments: Vec::new(), language };
+    };
+    let seconds_in = |time: DateTime<Utc>| (time - first).num_milliseconds() as f64 / 1000.0;
+
+    let mut chapters = Vec::new();
+    let mut segments = Vec::new();
+    let mut mentioned: Vec<&str> = Vec::new();
+    for (index, group) in group_into_chapters(&events).into_iter().enumerate() {
+        let start = pacing::format_time_code(seconds_in(group[0].timestamp));
+        let mut landmarks: Vec<&str> = Vec::new();
+        let segment_count = segments.len();
+
+        for event in &group {
+            // Each landmark is introduced once, by the first event near it
+            let new_pois: Vec<&POI> = event.pois.iter().filter(|p| !mentioned.contains(&p.id.as_str())).take(2).collect();
+            let Some(&poi) = new_pois.first() else { continue };
+            let mut lines = vec![language.passing(poi)];
+            if let Some(facts) = &poi.facts {
+                lines.extend(language.facts(facts));
+            }
+            let mut source_refs = vec![event.id.clone(), poi.id.clone()];
+            mentioned.push(&poi.id);
+            if let Some(other) = new_pois.get(1) {
+                lines.push(language.nearby(&other.name));
+                source_refs.push(other.id.clone());
+                mentioned.push(&other.id);
+            }
+            if !landmarks.contains(&poi.name.as_str()) {
+                landmarks.push(&poi.name);
+            }
+
+            segments.push(ScriptSegment {
+                time_code: pacing::format_time_code(seconds_in(event.timestamp)),
+                narration: lines.join(" "),
+                source_refs,
+                ..Default::default()
+            });
+        }
+
+        if segments.len() == segment_count {
+            segments.push(ScriptSegment {
+                time_code: start.clone(),
+                narration: language.continues().to_string(),
+                source_refs: vec![group[0].id.clone()],
+                ..Default::default()
+            });
+        }
+        landmarks.truncate(DESCRIBED_LANDMARKS);
+        chapters.push(Chapter {
+            time_code: start,
+            title: language.chapter_title(landmarks.first().copied(), index + 1),
+            description: language.chapter_description(&landmarks),
+        });
+    }
+
+    TemplateNarration { chapters, segments, language }
+}
+
+/// Consecutive runs of `events`, in time order, split at long pauses and,
+/// once a chapter has run a while, at moving far from where it started
+fn group_into_chapters<'a>(events: &[&'a TruthEvent]) -> Vec<Vec<&'a TruthEvent>> {
+    let mut chapters: Vec<Vec<&TruthEvent>> = Vec::new();
+    for &event in events {
+        let starts_new = match chapters.last() {
+            None => true,
+            Some(chapter) => {
+                let (start, previous) = (chapter[0], chapter[chapter.len() - 1]);
+                let gap = (event.timestamp - previous.timestamp).num_seconds();
+                let length = (event.timestamp - start.timestamp).num_seconds();
+                let origin = chapter.iter().find_map(|e| e.location.as_ref());
+                let moved = match (origin, &event.location) {
+                    (Some(a), Some(b)) => haversine_distance(a.lat, a.lon, b.lat, b.lon) > CHAPTER_DISTANCE_KM,
+                    _ => false,
+                };
+                gap > CHAPTER_GAP_SECONDS || (moved && length >= MIN_CHAPTER_SECONDS)
+            }
+        };
+        if starts_new {
+            chapters.push(vec![event]);
+        } else if let Some(chapter) = chapters.last_mut() {
+            chapter.push(event);
+        }
+    }
+    chapters
+}
+
+/// A map category like `place_of_worship` as a noun, if it says anything
+fn category_noun(category: &str) -> Option<String> {
+    let noun = category.trim().replace('_', " ").to_lowercase();
+    match noun.as_str() {
+        "" | "unknown" | "other" | "yes" => None,
+        _ => Some(noun),
+    }
+}
+
+/// `a` or `an`, by how `noun` is usually pronounced
+fn indefinite_article(noun: &str) -> &'static str {
+    let noun = noun.to_lowercase();
+    let sounds_like_you = ["uni", "use", "usu", "uti", "euro"].iter().any(|p| noun.starts_with(p));
+    match noun.chars().next() {
+        Some('a' | 'e' | 'i' | 'o' | 'u') if !sounds_like_you => "an",
+        _ if noun.starts_with("hour") || noun.starts_with("honou") => "an",
+        _ => "a",
+    }
+}
+
+/// Whether a Spanish word starts with an /i/ sound (`i`, `hi`, but not `hie`/`hia`)
+fn starts_with_i_sound(word: &str) -> bool {
+    let word = word.to_lowercase();
+    let word = word.strip_prefix('h').unwrap_or(&word);
+    (word.starts_with('i') || word.starts_with('í')) && !word.starts_with("ie") && !word.starts_with("ia")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{EventKind, LocationResult};
+    use chrono::{Duration, TimeZone};
+
+    fn event(id: &str, seconds: i64, lat: f64) -> TruthEvent {
+        TruthEvent {
+            id: id.to_string(),
+            kind: EventKind::Speech,
+            timestamp: Utc.with_ymd_and_hms(2024, 5, 4, 8, 0, 0).unwrap() + Duration::seconds(seconds),
+            local_time: None,
+            time_uncertain: false,
+            duration_seconds: None,
+            location: Some(LocationResult { lat, lon: 7.42 }),
+            heading_deg: None,
+            pois: vec![],
+            detected_objects: vec![],
+            image_path: None,
+            sun: None,
+            weather: None,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_chapters_split_at_gaps_and_moves() {
+        let events = [
+            event("a", 0, 43.73),
+            event("b", 20, 43.76),   // Far, but too soon into the chapter
+            event("c", 60, 43.731),  // Back near the start
+            event("d", 90, 43.76),   // Far, and the chapter has run long enough
+            event("e", 500, 43.761), // After a long pause
+        ];
+        let refs: Vec<&TruthEvent> = events.iter().collect();
+        let ids: Vec<Vec<&str>> = group_into_chapters(&refs)
+            .iter()
+            .map(|c| c.iter().map(|e| e.id.as_str()).collect())
+            .collect();
+        assert_eq!(ids, [vec!["a", "b", "c"], vec!["d"], vec!["e"]]);
+    }
+
+    #[test]
+    fn test_grammar_helpers() {
+        assert_eq!(indefinite_article("museum"), "a");
+        assert_eq!(indefinite_article("observation deck"), "an");
+        assert_eq!(indefinite_article("university"), "a");
+        assert_eq!(indefinite_article("hour glass"), "an");
+        assert_eq!(category_noun("place_of_worship").as_deref(), Some("place of worship"));
+        assert_eq!(category_noun("yes"), None);
+
+        let english = TemplateLanguage::English;
+        assert_eq!(english.list(&["A"]), "A");
+        assert_eq!(english.list(&["A", "B", "C"]), "A, B and C");
+        let spanish = TemplateLanguage::Spanish;
+        assert_eq!(spanish.list(&["Pisa", "Ibiza"]), "Pisa e Ibiza");
+        assert_eq!(spanish.list(&["Pisa", "Hierro"]), "Pisa y Hierro");
+        assert_eq!(spanish.number(12.5), "12,5");
+
+        assert_eq!(TemplateLanguage::from_name(" Español "), Some(spanish));
+        assert_eq!(TemplateLanguage::from_name("Deutsch"), None);
+    }
+}
@@ -0,0 +1,409 @@
+//! Trip Summary
+//!
+//! An overview of a whole project for the trip card: how far and how long
+//! it went, the countries and states it crossed, the landmarks it passed
+//! and how the elevation changed along the way.
+//!
+//! Each video's stored GPS points are a track of their own. Totals add the
+//! tracks up, and the profile and region samples run along them in the
+//! order they were recorded, leaving out the gaps between clips.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{debug, warn};
+
+use crate::commands::ingest::track_point;
+use crate::enrich::EnrichmentEngine;
+use crate::services::database::{DatabaseError, Event};
+use crate::services::gps::{haversine_distance, GpsBounds, TrackStats};
+use crate::services::{GpsTrack, LocalDatabase};
+use crate::types::{EnrichRequest, EnrichResponse, TruthBundle, TruthEvent, POI};
+
+/// Kilometres of route between points looked up for the regions crossed
+const REGION_SAMPLE_KM: f64 = 10.0;
+
+/// Most points looked up for the regions crossed; longer trips space them out
+const MAX_REGION_SAMPLES: usize = 25;
+
+/// Most points in the elevation profile
+const MAX_PROFILE_POINTS: usize = 200;
+
+/// Most POIs listed
+const TOP_POIS: usize = 10;
+
+/// Overview of a project's trip
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TripSummary {
+    pub project_id: String,
+    pub video_count: usize,
+    /// Videos with stored GPS points; the rest add nothing to the figures
+    pub videos_with_gps: usize,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Totals over every track: time is time recorded, not time between
+    /// the first and last clip
+    pub stats: TrackStats,
+    pub bounds: Option<GpsBounds>,
+    /// In the order they were first reached
+    pub countries: Vec<String>,
+    /// States, provinces or regions, in the order they were first reached
+    pub states: Vec<String>,
+    /// Most mentioned first
+    pub pois: Vec<TripPoi>,
+    pub elevation_profile: Vec<ElevationSample>,
+}
+
+/// A POI the trip passed
+#[derive(Debug, Clone, Serialize)]
+pub struct TripPoi {
+    /// `distance_m` is the closest the route came to it
+    pub poi: POI,
+    /// Video it was first passed in
+    pub video_id: String,
+    /// Events it was part of
+    pub mentions: usize,
+}
+
+/// Elevation some way into the trip
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ElevationSample {
+    pub distance_km: f64,
+    pub elevation_m: f64,
+}
+
+/// Summarize the trip of a project's videos
+///
+/// Regions are looked up through enrichment, so they're cached like any
+/// other enrichment; when lookups fail the summary comes back without them.
+pub async fn summarize(
+    db: &LocalDatabase,
+    enrichment: &EnrichmentEngine,
+    project_id: &str,
+) -> Result<TripSummary, DatabaseError> {
+    let videos = db.get_project_videos(project_id).await?;
+
+    let mut tracks = Vec::new();
+    let mut events = Vec::new();
+    for video in &videos {
+        let points = db.get_gps_points(&video.id).await?;
+        if !points.is_empty() {
+            let points = points.into_iter().map(track_point).collect();
+            tracks.push((video.id.clone(), GpsTrack::from_points(video.filename.clone(), "stored", points)));
+        }
+        events.extend(db.get_video_events(&video.id).await?);
+    }
+    tracks.sort_by_key(|(_, track)| track.start_time);
+
+    let route = route(tracks.iter().map(|(_, track)| track));
+    let samples = region_samples(&route);
+    let requests: Vec<EnrichRequest> = samples.iter().map(|&(_, lat, lon)| EnrichRequest::at(lat, lon)).collect();
+    let (countries, states) = match enrichment.enrich_points(requests).await {
+        Ok(responses) => regions_crossed(&responses),
+        Err(e) => {
+            warn!("Couldn't look up the regions of project {}: {:#}", project_id, e);
+            (Vec::new(), Vec::new())
+        }
+    };
+
+    let tracks: Vec<GpsTrack> = tracks.into_iter().map(|(_, track)| track).collect();
+    let summary = TripSummary {
+        project_id: project_id.to_string(),
+        video_count: videos.len(),
+        videos_with_gps: tracks.len(),
+        start_time: tracks.iter().filter_map(|t| t.start_time).min(),
+        end_time: tracks.iter().filter_map(|t| t.end_time).max(),
+        stats: total_stats(&tracks),
+        bounds: total_bounds(&tracks),
+        countries,
+        states,
+        pois: top_pois(&events, TOP_POIS),
+        elevation_profile: elevation_profile(&route, MAX_PROFILE_POINTS),
+    };
+    debug!(
+        "Trip summary of project {}: {:.1} km over {} videos, {} regions looked up",
+        project_id, summary.stats.distance_km, summary.videos_with_gps, samples.len()
+    );
+    Ok(summary)
+}
+
+/// A fix along the route: kilometres from the start, position and elevation
+type RoutePoint = (f64, f64, f64, Option<f64>);
+
+/// Every fix of the tracks in turn, with the distance covered so far
+/// within the tracks
+fn route<'a>(tracks: impl Iterator<Item = &'a GpsTrack>) -> Vec<RoutePoint> {
+    let mut route = Vec::new();
+    let mut distance_km = 0.0;
+    for track in tracks {
+        for (i, p) in track.points.iter().enumerate() {
+            if i > 0 {
+                let prev = &track.points[i - 1];
+                distance_km += haversine_distance(prev.lat, prev.lon, p.lat, p.lon);
+            }
+            route.push((distance_km, p.lat, p.lon, p.elevation_m));
+        }
+    }
+    route
+}
+
+/// Stats of all the tracks together
+///
+/// The average speed only counts tracks whose duration is known.
+fn total_stats(tracks: &[GpsTrack]) -> TrackStats {
+    let stats: Vec<TrackStats> = tracks.iter().map(GpsTrack::stats).collect();
+    let sum = |values: Vec<f64>| (!values.is_empty()).then(|| values.iter().sum::<f64>());
+
+    let timed: Vec<(f64, f64)> =
+        stats.iter().filter_map(|s| s.duration_seconds.map(|d| (s.distance_km, d))).collect();
+    let duration_seconds = sum(timed.iter().map(|&(_, d)| d).collect());
+    let timed_km: f64 = timed.iter().map(|&(km, _)| km).sum();
+
+    TrackStats {
+        distance_km: stats.iter().map(|s| s.distance_km).sum(),
+        duration_seconds,
+        avg_speed_kmh: duration_seconds.filter(|&d| d > 0.0).map(|d| timed_km / (d / 3600.0)),
+        max_speed_kmh: stats.iter().filter_map(|s| s.max_speed_kmh).reduce(f64::max),
+        elevation_gain_m: sum(stats.iter().filter_map(|s| s.elevation_gain_m).collect()),
+        elevation_loss_m: sum(stats.iter().filter_map(|s| s.elevation_loss_m).collect()),
+    }
+}
+
+fn total_bounds(tracks: &[GpsTrack]) -> Option<GpsBounds> {
+    tracks.iter().filter_map(|t| t.bounds.clone()).reduce(|a, b| GpsBounds {
+        min_lat: a.min_lat.min(b.min_lat),
+        max_lat: a.max_lat.max(b.max_lat),
+        min_lon: a.min_lon.min(b.min_lon),
+        max_lon: a.max_lon.max(b.max_lon),
+    })
+}
+
+/// Points every `REGION_SAMPLE_KM` along the route, or further apart when
+/// that would be more than `MAX_REGION_SAMPLES`, plus the start and end
+fn region_samples(route: &[RoutePoint]) -> Vec<(f64, f64, f64)> {
+    let (Some(first), Some(last)) = (route.first(), route.last()) else {
+        return Vec::new();
+    };
+    let spacing = REGION_SAMPLE_KM.max(last.0 / (MAX_REGION_SAMPLES - 1) as f64);
+
+    let mut samples = vec![(first.0, first.1, first.2)];
+    let mut next = first.0 + spacing;
+    for &(distance_km, lat, lon, _) in &route[1..] {
+        if distance_km >= next {
+            samples.push((distance_km, lat, lon));
+            next = distance_km + spacing;
+        }
+    }
+    if route.len() > 1 && samples.last().map(|s| s.0) != Some(last.0) {
+        samples.push((last.0, last.1, last.2));
+    }
+    samples
+}
+
+/// Distinct countries and states in the order the samples reached them
+///
+/// A state is whatever the lookup knew of the state, the region or the county.
+fn regions_crossed(responses: &[EnrichResponse]) -> (Vec<String>, Vec<String>) {
+    let mut countries: Vec<String> = Vec::new();
+    let mut states: Vec<String> = Vec::new();
+    let add = |list: &mut Vec<String>, name: Option<&String>| {
+        if let Some(name) = name.map(|n| n.trim()).filter(|n| !n.is_empty()) {
+            if !list.iter().any(|known| known == name) {
+                list.push(name.to_string());
+            }
+        }
+    };
+
+    for response in responses {
+        let context = &response.context;
+        add(&mut countries, context.country.as_ref());
+        add(&mut states, context.state.as_ref().or(context.region.as_ref()).or(context.county.as_ref()));
+    }
+    (countries, states)
+}
+
+/// Fixes with an elevation, thinned out evenly to at most `max_points`;
+/// the first and last are always kept
+fn elevation_profile(route: &[RoutePoint], max_points: usize) -> Vec<ElevationSample> {
+    let samples: Vec<ElevationSample> = route
+        .iter()
+        .filter_map(|&(distance_km, _, _, elevation)| elevation.map(|elevation_m| ElevationSample { distance_km, elevation_m }))
+        .collect();
+    if samples.len() <= max_points || max_points < 2 {
+        return samples;
+    }
+
+    let step = (samples.len() - 1) as f64 / (max_points - 1) as f64;
+    (0..max_points).map(|i| samples[(i as f64 * step).round() as usize]).collect()
+}
+
+/// The POIs of an event's stored JSON, which holds the event or, from
+/// older processing, the whole bundle
+fn event_pois(event: &Event) -> Vec<POI> {
+    let Some(json) = event.truth_bundle_json.as_deref() else {
+        return Vec::new();
+    };
+    if let Ok(truth) = serde_json::from_str::<TruthEvent>(json) {
+        return truth.pois;
+    }
+    match serde_json::from_str::<TruthBundle>(json) {
+        Ok(bundle) => bundle.events.into_iter().flat_map(|e| e.pois).collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The POIs most events were about, then the ones passed closest
+fn top_pois(events: &[Event], limit: usize) -> Vec<TripPoi> {
+    let mut by_id: HashMap<String, TripPoi> = HashMap::new();
+    for event in events {
+        for poi in event_pois(event) {
+            by_id
+                .entry(poi.id.clone())
+                .and_modify(|seen| {
+                    seen.mentions += 1;
+                    if poi.distance_m < seen.poi.distance_m {
+                        seen.poi.distance_m = poi.distance_m;
+                    }
+                })
+                .or_insert_with(|| TripPoi { poi, video_id: event.video_id.clone(), mentions: 1 });
+        }
+    }
+
+    let mut pois: Vec<TripPoi> = by_id.into_values().collect();
+    pois.sort_by(|a, b| b.mentions.cmp(&a.mentions).then(a.poi.distance_m.total_cmp(&b.poi.distance_m)));
+    pois.truncate(limit);
+    pois
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use crate::services::gps::GpsPoint;
+    use crate::types::{LocationContext, LocationResult};
+
+    fn track(start_minute: i64, fixes: &[(f64, f64, Option<f64>)]) -> GpsTrack {
+        let points = fixes
+            .iter()
+            .enumerate()
+            .map(|(i, &(lat, lon, elevation_m))| GpsPoint {
+                elevation_m,
+                ..fixtures::gps_point(60 * (start_minute + i as i64), lat, lon)
+            })
+            .collect();
+        fixtures::track(points)
+    }
+
+    fn poi(id: &str, distance_m: f64) -> POI {
+        POI { distance_m, ..fixtures::poi(id) }
+    }
+
+    fn event(video_id: &str, pois: Vec<POI>) -> Event {
+        Event { video_id: video_id.to_string(), ..fixtures::event(0.0, Some(&fixtures::truth(0, pois))) }
+    }
+
+    fn located(country: Option<&str>, state: Option<&str>) -> EnrichResponse {
+        EnrichResponse {
+            location: LocationResult { lat: 0.0, lon: 0.0 },
+            context: LocationContext {
+                country: country.map(String::from),
+                state: state.map(String::from),
+                ..Default::default()
+            },
+            pois: vec![],
+            source: Default::default(),
+            confidence: 0.0,
+            debug: None,
+        }
+    }
+
+    #[test]
+    fn test_totals_add_up_tracks() {
+        // 0.01 degrees of latitude is about 1.11 km
+        let morning = track(0, &[(43.70, 7.40, Some(10.0)), (43.71, 7.40, Some(40.0)), (43.72, 7.40, Some(30.0))]);
+        let afternoon = track(30, &[(44.00, 7.40, Some(100.0)), (44.01, 7.40, Some(90.0))]);
+        let tracks = [morning, afternoon];
+
+        let stats = total_stats(&tracks);
+        assert!((stats.distance_km - 3.336).abs() < 0.01, "{}", stats.distance_km);
+        assert_eq!(stats.duration_seconds, Some(180.0));
+        assert!((stats.avg_speed_kmh.unwrap() - 66.7).abs() < 0.5);
+        assert_eq!(stats.elevation_gain_m, Some(30.0));
+        assert_eq!(stats.elevation_loss_m, Some(20.0));
+
+        let bounds = total_bounds(&tracks).unwrap();
+        assert_eq!((bounds.min_lat, bounds.max_lat), (43.70, 44.01));
+
+        // The gap between the clips isn't part of the route
+        let route = route(tracks.iter());
+        assert_eq!(route.len(), 5);
+        assert!((route[4].0 - 3.336).abs() < 0.01);
+        assert_eq!(route[3].0, route[2].0);
+    }
+
+    #[test]
+    fn test_region_samples_are_spaced_along_the_route() {
+        let route: Vec<RoutePoint> = (0..=81).map(|i| (i as f64 * 2.5, 43.0, 7.0, None)).collect();
+        let samples = region_samples(&route);
+        let distances: Vec<f64> = samples.iter().map(|s| s.0).collect();
+        assert_eq!(&distances[..4], [0.0, 10.0, 20.0, 30.0]);
+        // The end is looked up too, even short of the next sample
+        assert_eq!(&distances[20..], [200.0, 202.5]);
+
+        // A long trip is spaced out to stay within the limit
+        let route: Vec<RoutePoint> = (0..=1000).map(|i| (i as f64, 43.0, 7.0, None)).collect();
+        assert!(region_samples(&route).len() <= MAX_REGION_SAMPLES + 1);
+
+        assert!(region_samples(&[]).is_empty());
+        assert_eq!(region_samples(&[(0.0, 43.0, 7.0, None)]).len(), 1);
+    }
+
+    #[test]
+    fn test_regions_crossed_in_order() {
+        let responses = [
+            located(Some("France"), Some("Provence-Alpes-Côte d'Azur")),
+            located(Some("Monaco"), None),
+            located(None, None),
+            located(Some("France"), Some("Provence-Alpes-Côte d'Azur")),
+            located(Some("Italy"), Some("Liguria")),
+        ];
+
+        let (countries, states) = regions_crossed(&responses);
+        assert_eq!(countries, ["France", "Monaco", "Italy"]);
+        assert_eq!(states, ["Provence-Alpes-Côte d'Azur", "Liguria"]);
+    }
+
+    #[test]
+    fn test_elevation_profile_is_thinned_evenly() {
+        let route: Vec<RoutePoint> = (0..=10)
+            .map(|i| (i as f64, 43.0, 7.0, (i != 3).then_some(i as f64 * 100.0)))
+            .collect();
+
+        assert_eq!(elevation_profile(&route, 200).len(), 10);
+
+        let profile = elevation_profile(&route, 4);
+        let elevations: Vec<f64> = profile.iter().map(|s| s.elevation_m).collect();
+        assert_eq!(elevations, [0.0, 400.0, 700.0, 1000.0]);
+        assert_eq!(profile[3].distance_km, 10.0);
+    }
+
+    #[test]
+    fn test_top_pois_rank_by_mentions_then_distance() {
+        let events = [
+            event("v1", vec![poi("castle", 300.0), poi("bridge", 50.0)]),
+            event("v2", vec![poi("castle", 120.0)]),
+            event("v2", vec![poi("museum", 20.0)]),
+            Event { truth_bundle_json: Some("not json".to_string()), ..event("v2", vec![]) },
+        ];
+
+        let pois = top_pois(&events, 10);
+        let ids: Vec<&str> = pois.iter().map(|p| p.poi.id.as_str()).collect();
+        assert_eq!(ids, ["castle", "museum", "bridge"]);
+        assert_eq!(pois[0].mentions, 2);
+        assert_eq!(pois[0].video_id, "v1");
+        assert_eq!(pois[0].poi.distance_m, 120.0);
+
+        assert_eq!(top_pois(&events, 1).len(), 1);
+    }
+}
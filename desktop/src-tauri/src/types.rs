@@ -1,9 +1,13 @@
 #![allow(dead_code)]
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use std::collections::HashMap;
 
+use crate::geo::PlaceCandidate;
+use crate::llm::Sampling;
+use crate::services::gps::TrackStats;
+
 // =============================================================================
 // Common Models
 // =============================================================================
@@ -18,7 +22,7 @@ pub struct Coordinates {
 // POI Models
 // =============================================================================
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct POIFacts {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub established: Option<String>,
@@ -26,10 +30,41 @@ pub struct POIFacts {
     pub depth_m: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub unesco_site: Option<bool>,
+    /// OSM `wikipedia` tag, e.g. `fr:Tour Eiffel`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wikipedia: Option<String>,
+    /// OSM `wikidata` tag, e.g. `Q243`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wikidata: Option<String>,
+    /// Opening paragraph of the POI's article
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<ArticleSummary>,
     #[serde(flatten)]
     pub extra: HashMap<String, serde_json::Value>,
 }
 
+impl POIFacts {
+    /// Facts naming the POI's articles, if it has any
+    pub fn with_articles(wikipedia: Option<String>, wikidata: Option<String>) -> Option<Self> {
+        (wikipedia.is_some() || wikidata.is_some()).then(|| POIFacts { wikipedia, wikidata, ..Default::default() })
+    }
+}
+
+/// The opening paragraph of an encyclopedia article, as retrieved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArticleSummary {
+    pub title: String,
+    /// Wikipedia language code, e.g. `en`
+    pub language: String,
+    pub extract: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// The site it came from
+    pub source: String,
+    pub retrieved_on: NaiveDate,
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct POI {
     pub id: String,
@@ -47,6 +82,9 @@ pub struct POI {
     pub confidence: f64,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub facts: Option<POIFacts>,
+    /// Pinned to its event by the user, so it stays whatever enrichment finds
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 // =============================================================================
@@ -64,7 +102,7 @@ pub struct LocationResult {
     // Add matched location if needed later
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct LocationContext {
     pub country: Option<String>,
     pub city: Option<String>,
@@ -75,12 +113,98 @@ pub struct LocationContext {
     pub elevation_m: Option<f64>,
     pub state: Option<String>,
     pub county: Option<String>,
+    /// Where each field's value came from, keyed by field name, e.g. `country`
+    #[serde(default)]
+    pub attribution: HashMap<String, AttributedValue>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl LocationContext {
+    /// Attribute every field that has a value, and no attribution yet, to `source`
+    pub fn attribute(&mut self, source: FactSource, confidence: f64) {
+        for (field, value) in self.values() {
+            if let Some(value) = value {
+                self.attribution
+                    .entry(field.to_string())
+                    .or_insert(AttributedValue { value, source, confidence });
+            }
+        }
+    }
+
+    /// Fields with a value but no attribution; empty when every value says where it came from
+    pub fn unattributed(&self) -> Vec<&'static str> {
+        self.values()
+            .into_iter()
+            .filter(|(field, value)| value.is_some() && !self.attribution.contains_key(*field))
+            .map(|(field, _)| field)
+            .collect()
+    }
+
+    /// Each field by name, with its value as text
+    fn values(&self) -> [(&'static str, Option<String>); 9] {
+        [
+            ("country", self.country.clone()),
+            ("city", self.city.clone()),
+            ("road", self.road.clone()),
+            ("region", self.region.clone()),
+            ("population", self.population.map(|p| p.to_string())),
+            ("timezone", self.timezone.clone()),
+            ("elevation_m", self.elevation_m.map(|e| e.to_string())),
+            ("state", self.state.clone()),
+            ("county", self.county.clone()),
+        ]
+    }
+}
+
+/// Where a fact about a location came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactSource {
+    /// Downloaded map data for the location
+    MapData,
+    /// The model's answer; a guess nothing has checked
+    Llm,
+    /// A rule of thumb, such as rough bounds per country
+    Estimate,
+    /// Set by the user; taken over anything else
+    User,
+}
+
+/// A location fact with its source, so verified values can be told apart from guesses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributedValue {
+    pub value: String,
+    pub source: FactSource,
+    /// From 0 to 1
+    pub confidence: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EnrichRequest {
     pub lat: f64,
     pub lon: f64,
+    /// How far around the point to list POIs (`None` = 500 m)
+    #[serde(default)]
+    pub radius_m: Option<f64>,
+    /// Direction the camera faces, clockwise from true north; without it
+    /// no POI is marked in view
+    #[serde(default)]
+    pub heading_deg: Option<f64>,
+    /// Camera field of view (`None` = a dashcam's)
+    #[serde(default)]
+    pub fov_deg: Option<f64>,
+    /// POI categories to list (empty = all)
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Include the map features the place and road names were picked from
+    #[serde(default)]
+    pub verbose: bool,
+}
+
+impl EnrichRequest {
+    /// A request for the point with the default POI search
+    pub fn at(lat: f64, lon: f64) -> Self {
+        Self { lat, lon, ..Default::default() }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +212,64 @@ pub struct EnrichResponse {
     pub location: LocationResult,
     pub context: LocationContext,
     pub pois: Vec<POI>,
+    #[serde(default)]
+    pub source: EnrichmentSource,
+    /// How far the context can be trusted, from 0 to 1
+    #[serde(default)]
+    pub confidence: f64,
+    /// Only for verbose requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<EnrichDebug>,
+}
+
+/// What went into an enrichment, for working out why it says what it does
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnrichDebug {
+    /// Named map features around the point, nearest first
+    pub place_candidates: Vec<PlaceCandidate>,
+}
+
+/// Everything known about a clicked map position, for the inspection panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocationInspection {
+    pub location: LocationResult,
+    pub context: LocationContext,
+    /// Where the context came from
+    pub source: EnrichmentSource,
+    /// Named map features around the point, nearest first
+    pub place_candidates: Vec<PlaceCandidate>,
+    /// The closest POIs, closest first
+    pub pois: Vec<POI>,
+    /// From the nearest recorded GPS point, or else the context
+    pub elevation_m: Option<f64>,
+    /// Current time in the point's time zone, RFC 3339 with its UTC offset
+    pub local_time: Option<String>,
+    /// `None` when no downloaded region covers the point
+    pub region: Option<CoveringRegion>,
+}
+
+/// Downloaded region a point lies in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoveringRegion {
+    pub id: String,
+    pub name: String,
+}
+
+/// Where an enrichment's location context came from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnrichmentSource {
+    /// Offline map data
+    Local,
+    /// The model's answer for a place the offline data doesn't know; a guess, not verified
+    Llm,
+    /// Only what the offline data had, since the model couldn't be asked; fields
+    /// it didn't have are `None`
+    #[default]
+    Partial,
+    /// Nothing, since the offline data didn't know the place and the model
+    /// wasn't asked: the app is offline, or the run's online calls were spent
+    Uncovered,
 }
 
 // =============================================================================
@@ -97,18 +279,132 @@ pub struct EnrichResponse {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TruthEvent {
     pub id: String,
+    /// What the event marks; bundles from before there were kinds only had speech
+    #[serde(default)]
+    pub kind: EventKind,
     pub timestamp: DateTime<Utc>,
+    /// `timestamp` on the clock where the event happened, e.g.
+    /// `2025-06-01T12:00:10+02:00`; `None` while the time zone isn't known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local_time: Option<String>,
+    /// `timestamp` is a guess rather than a recorded time, e.g. the
+    /// processing time or a camera clock that can't be trusted
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub time_uncertain: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub duration_seconds: Option<f64>,
-    pub location: LocationResult,
+    /// `None` when GPS is missing or couldn't be aligned with the video
+    #[serde(default)]
+    pub location: Option<LocationResult>,
+    /// Direction of travel, clockwise from true north
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading_deg: Option<f64>,
     #[serde(default)]
     pub pois: Vec<POI>,
     #[serde(default)]
     pub detected_objects: Vec<serde_json::Value>,
+    /// Frame captured at the event, for narration to show as the scene
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_path: Option<String>,
+    /// Where the sun was, worked out from the time and location
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sun: Option<SunFacts>,
+    /// The weather recorded nearby, when it could be looked up online
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<WeatherObservation>,
+    /// Country, city and road at the event's location, once enriched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<LocationContext>,
+}
+
+impl TruthEvent {
+    /// Set `local_time` by the time zone of the event's context; `None`
+    /// without one, or with a zone that isn't known
+    pub fn localize_time(&mut self) {
+        let zone = self.context.as_ref().and_then(|c| c.timezone.as_deref());
+        let zone = zone.and_then(|zone| zone.trim().parse::<chrono_tz::Tz>().ok());
+        self.local_time = zone.map(|zone| self.timestamp.with_timezone(&zone).to_rfc3339());
+    }
+}
+
+/// What a Truth Event marks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// A stretch of the transcript
+    #[default]
+    Speech,
+    /// The camera starts closing in on the event's POI
+    Approach,
+    /// The camera is as close to the event's POI as the route gets
+    NearestPass,
+    /// The picture changes, e.g. at a cut or as a new view opens up
+    SceneChange,
+}
+
+/// The sun at an event's time and place
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SunFacts {
+    /// Above the horizon; negative at night
+    pub elevation_deg: f64,
+    /// Clockwise from true north
+    pub azimuth_deg: f64,
+    /// `None` on days the sun doesn't rise or set
+    pub sunrise: Option<DateTime<Utc>>,
+    pub sunset: Option<DateTime<Utc>>,
+    pub light: Daylight,
+}
+
+/// The light the sun gives at its height
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Daylight {
+    Night,
+    /// Just before sunrise or after sunset, with the sun 4 to 6 degrees down
+    BlueHour,
+    /// The sun lower than 6 degrees up, down to 4 below
+    GoldenHour,
+    Day,
+}
+
+impl Daylight {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Daylight::Night => "night",
+            Daylight::BlueHour => "blue hour",
+            Daylight::GoldenHour => "golden hour",
+            Daylight::Day => "daylight",
+        }
+    }
 }
 
+/// Weather recorded by a weather service near an event, around its time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeatherObservation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature_c: Option<f64>,
+    /// In the hour around the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precipitation_mm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wind_speed_kmh: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cloud_cover_percent: Option<f64>,
+    /// In words, e.g. `light rain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+    /// The service it came from
+    pub source: String,
+}
+
+/// Serialized by hand so bundles of earlier schema versions are upgraded as
+/// they're read; see [`crate::bundle_schema`]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(remote = "Self")]
 pub struct TruthBundle {
+    /// Schema version the bundle follows; always the current one once read
+    #[serde(default)]
+    pub schema_version: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_id: Option<Uuid>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -116,7 +412,34 @@ pub struct TruthBundle {
     #[serde(default)]
     pub events: Vec<TruthEvent>,
     pub verification_mode: String,
+    /// How well the events are backed by evidence, 0 to 1 (see `confidence`)
+    #[serde(default)]
+    pub confidence: f64,
     pub generated_at: DateTime<Utc>,
+    /// Distance, speed and climb over the GPS track, when there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_stats: Option<TrackStats>,
+    /// Free-form processing details, e.g. per-stage timings
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub meta: HashMap<String, String>,
+    /// The areas the route passes through, in order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route_segments: Vec<RouteSegment>,
+}
+
+/// A stretch of the route within one area, timed like the events
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteSegment {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// State or region
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    pub distance_km: f64,
 }
 
 // =============================================================================
@@ -130,9 +453,223 @@ pub struct NarrateRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transcript: Option<String>,
     #[serde(default)]
-    pub scene_frames: Vec<String>, // Base64 encoded images
+    pub scene_frames: Vec<SceneFrame>,
+    /// Length of the video being narrated; time codes past it are pulled back
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_duration_seconds: Option<f64>,
+    /// When the creator is already talking: transcript segments and voice
+    /// activity regions. The video's stored transcript is used when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub speech: Vec<SpeechInterval>,
+    /// Style choices, see [`NarrationOptions`], and `"fresh": true` to skip the cache
     #[serde(default)]
     pub options: HashMap<String, serde_json::Value>,
+    /// Update an earlier narration rather than writing a new one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<NarrationRevision>,
+}
+
+/// Which parts of an earlier narration to write again
+///
+/// Only the chapters the changed windows touch are rewritten; the rest of
+/// the narration is kept exactly as it was.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NarrationRevision {
+    pub previous: NarrateResponse,
+    /// Where the events changed since `previous` was written
+    pub changed_windows: Vec<TimeWindow>,
+}
+
+/// A stretch of the video, in seconds from its start
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TimeWindow {
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+}
+
+/// A frame from the video to show the model
+///
+/// Sent as just the base64 image data (or a `data:` URI), or as an object
+/// with the time it was taken, so the model can tie it to the events then.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "SceneFrameInput")]
+pub struct SceneFrame {
+    pub image: String,
+    /// Seconds into the video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_seconds: Option<f64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SceneFrameInput {
+    Image(String),
+    Timed {
+        image: String,
+        #[serde(default)]
+        time_seconds: Option<f64>,
+    },
+}
+
+impl From<SceneFrameInput> for SceneFrame {
+    fn from(input: SceneFrameInput) -> Self {
+        match input {
+            SceneFrameInput::Image(image) => image.into(),
+            SceneFrameInput::Timed { image, time_seconds } => Self {
+                image,
+                time_seconds: time_seconds.filter(|t| t.is_finite() && *t >= 0.0),
+            },
+        }
+    }
+}
+
+impl From<String> for SceneFrame {
+    fn from(image: String) -> Self {
+        Self { image, time_seconds: None }
+    }
+}
+
+/// A stretch of the video where someone is speaking
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeechInterval {
+    /// Seconds into the video
+    pub start_seconds: f64,
+    pub end_seconds: f64,
+}
+
+/// Largest `humor_level`
+pub const MAX_HUMOR_LEVEL: u8 = 10;
+
+/// How a narration should sound, taken from `NarrateRequest::options`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NarrationOptions {
+    pub tone: NarrationTone,
+    pub audience: NarrationAudience,
+    /// Language to write the chapters and script in, e.g. `English` or `Español`
+    pub language: String,
+    /// From 0 for none to [`MAX_HUMOR_LEVEL`]
+    pub humor_level: u8,
+    /// How long the whole script should take to speak, usually the video's length
+    pub target_duration_seconds: Option<f64>,
+    /// Words per minute the script will be read at, from 60 to 300
+    pub speech_rate_wpm: u32,
+    /// Keep the narration to the gaps where the creator isn't talking
+    pub respect_dialogue: bool,
+    /// Shortest silence worth narrating in, from 0.5 to 60 seconds
+    pub min_gap_seconds: f64,
+    /// How chapter titles are written
+    pub title_style: TitleStyle,
+    /// Ask for a delivery hint on lines to be read differently, for SSML
+    /// and editors; off for plain narration
+    pub delivery_hints: bool,
+    /// How the model samples the narration; unset parameters come from the
+    /// creativity setting, or are the model's defaults
+    pub sampling: Sampling,
+}
+
+impl Default for NarrationOptions {
+    fn default() -> Self {
+        Self {
+            tone: NarrationTone::default(),
+            audience: NarrationAudience::default(),
+            language: "English".to_string(),
+            humor_level: 2,
+            target_duration_seconds: None,
+            speech_rate_wpm: crate::pacing::DEFAULT_SPEECH_RATE_WPM,
+            respect_dialogue: true,
+            min_gap_seconds: crate::dialogue::DEFAULT_MIN_GAP_SECONDS,
+            title_style: TitleStyle::default(),
+            delivery_hints: true,
+            sampling: Sampling::default(),
+        }
+    }
+}
+
+impl NarrationOptions {
+    /// Options from a request's options map; missing ones get their defaults
+    /// and keys other than the options' own are ignored
+    pub fn from_options(options: &HashMap<String, serde_json::Value>) -> Result<Self, serde_json::Error> {
+        let map = options.clone().into_iter().collect();
+        let mut parsed: Self = serde_json::from_value(serde_json::Value::Object(map))?;
+        parsed.language = parsed.language.trim().to_string();
+        if parsed.language.is_empty() {
+            parsed.language = Self::default().language;
+        }
+        parsed.humor_level = parsed.humor_level.min(MAX_HUMOR_LEVEL);
+        parsed.target_duration_seconds = parsed.target_duration_seconds.filter(|d| d.is_finite() && *d > 0.0);
+        parsed.speech_rate_wpm = parsed.speech_rate_wpm.clamp(60, 300);
+        parsed.min_gap_seconds = if parsed.min_gap_seconds.is_finite() {
+            parsed.min_gap_seconds.clamp(0.5, 60.0)
+        } else {
+            Self::default().min_gap_seconds
+        };
+        parsed.sampling = parsed.sampling.checked();
+        Ok(parsed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NarrationTone {
+    #[default]
+    Documentary,
+    Casual,
+    Energetic,
+    Poetic,
+}
+
+impl NarrationTone {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NarrationTone::Documentary => "documentary",
+            NarrationTone::Casual => "casual",
+            NarrationTone::Energetic => "energetic",
+            NarrationTone::Poetic => "poetic",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NarrationAudience {
+    #[default]
+    General,
+    Kids,
+    /// Viewers who want the history and geography in detail
+    Enthusiast,
+}
+
+impl NarrationAudience {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NarrationAudience::General => "general",
+            NarrationAudience::Kids => "kids",
+            NarrationAudience::Enthusiast => "enthusiast",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleStyle {
+    /// Where we are or what happens
+    #[default]
+    Descriptive,
+    /// A few words to make you watch
+    Punchy,
+    /// Just the place
+    Minimal,
+}
+
+impl TitleStyle {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TitleStyle::Descriptive => "descriptive",
+            TitleStyle::Punchy => "punchy",
+            TitleStyle::Minimal => "minimal",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -143,10 +680,61 @@ pub struct Chapter {
     pub description: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScriptSegment {
     pub time_code: String,
     pub narration: String,
+    /// How to read the line, one of [`crate::delivery::HINTS`], e.g. `whisper`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<String>,
+    /// Ids of the Truth Bundle events and POIs the narration draws on; only
+    /// ones that exist in the bundle are kept
+    #[serde(default)]
+    pub source_refs: Vec<String>,
+    /// Ids the model cited that aren't in the bundle
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown_refs: Vec<String>,
+    #[serde(default)]
+    pub citation: CitationStatus,
+    /// Seconds the narration takes to speak at the requested rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_duration_seconds: Option<f64>,
+    /// Seconds until the next segment starts, or the target duration ends
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot_seconds: Option<f64>,
+}
+
+/// Whether a script segment is backed by the Truth Bundle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CitationStatus {
+    /// Cites only events and POIs in the bundle
+    Verified,
+    /// Cites nothing, so nothing backs it
+    #[default]
+    Uncited,
+    /// Cites ids the bundle doesn't have, a likely hallucination
+    Fabricated,
+}
+
+/// What a cited id refers to, for showing the evidence behind a line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evidence {
+    pub kind: EvidenceKind,
+    /// The POI's name, or the event's landmarks
+    pub label: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvidenceKind {
+    Event,
+    Poi,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +747,9 @@ pub struct NarrateResponse {
     pub chapters: Vec<Chapter>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub script: Option<NarrateScript>,
+    /// The evidence behind each id cited in the script
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub evidence: HashMap<String, Evidence>,
     #[serde(default)]
     pub meta: HashMap<String, String>,
 }
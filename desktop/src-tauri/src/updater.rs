@@ -0,0 +1,332 @@
+//! Scheduled Region Updates
+//!
+//! Background task that keeps downloaded map data fresh according to the
+//! user's update policy. It wakes up periodically, checks upstream for newer
+//! extracts and re-downloads regions older than the policy window, one at a
+//! time and only while no other download is running.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::commands::{self, RegionStatus, RegionUpdateStatus};
+use crate::services::mirrors::{DownloadProvider, TemplateProvider};
+use crate::settings;
+
+/// Delay before the first check, so startup isn't slowed down
+const STARTUP_DELAY: Duration = Duration::from_secs(120);
+
+/// How often the task wakes up to see whether a run is due
+const POLL_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to look again while another download is running
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bytes fetched to estimate connection speed
+const SPEED_PROBE_BYTES: u64 = 512 * 1024;
+
+/// How long to wait for the task to stop on exit
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often regions are updated automatically
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateFrequency {
+    #[default]
+    Never,
+    Weekly,
+    Monthly,
+}
+
+impl UpdateFrequency {
+    /// Minimum age before data is refreshed, `None` when disabled
+    pub fn window(&self) -> Option<chrono::Duration> {
+        match self {
+            Self::Never => None,
+            Self::Weekly => Some(chrono::Duration::days(7)),
+            Self::Monthly => Some(chrono::Duration::days(30)),
+        }
+    }
+}
+
+/// User policy for automatic region updates
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UpdatePolicy {
+    pub frequency: UpdateFrequency,
+    /// Only update on a fast connection, as a stand-in for "Wi-Fi only"
+    pub fast_connection_only: bool,
+    /// Measured throughput (bytes/s) that counts as a fast connection
+    pub min_bytes_per_sec: u64,
+}
+
+impl Default for UpdatePolicy {
+    fn default() -> Self {
+        Self {
+            frequency: UpdateFrequency::Never,
+            fast_connection_only: false,
+            min_bytes_per_sec: 1024 * 1024,
+        }
+    }
+}
+
+impl UpdatePolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.fast_connection_only && self.min_bytes_per_sec == 0 {
+            return Err("Minimum connection speed must be above zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Payload of the `region-auto-update-finished` event
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegionUpdateSummary {
+    /// Downloaded regions that were checked upstream
+    pub checked: usize,
+    pub updated: Vec<String>,
+    pub failed: Vec<String>,
+    /// Why no updates were attempted, if they were skipped
+    pub skipped_reason: Option<String>,
+}
+
+/// Handle to the background update task, managed as app state
+pub struct UpdateScheduler {
+    shutdown: watch::Sender<bool>,
+    task: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+}
+
+impl UpdateScheduler {
+    /// Spawn the background task
+    pub fn start(app: AppHandle) -> Self {
+        let (shutdown, rx) = watch::channel(false);
+        let task = tauri::async_runtime::spawn(run(app, rx));
+        Self {
+            shutdown,
+            task: std::sync::Mutex::new(Some(task)),
+        }
+    }
+
+    /// Signal the task to stop and wait briefly for it to finish
+    ///
+    /// An update interrupted mid-download keeps its partial file and is
+    /// resumed on the next run.
+    pub async fn stop(&self) {
+        let _ = self.shutdown.send(true);
+        let task = self.task.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(task) = task {
+            if tokio::time::timeout(SHUTDOWN_TIMEOUT, task).await.is_err() {
+                warn!("Region update scheduler did not stop in time");
+            }
+        }
+    }
+}
+
+async fn run(app: AppHandle, mut shutdown: watch::Receiver<bool>) {
+    info!("Region update scheduler started");
+    let mut delay = STARTUP_DELAY;
+
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => break,
+        }
+        delay = POLL_INTERVAL;
+
+        let current = settings::get();
+        let policy = current.update_policy;
+        let now = Utc::now();
+        if !is_due(&policy, current.last_scheduled_update.as_deref(), now) {
+            continue;
+        }
+
+        let result = tokio::select! {
+            result = run_updates(&app, &policy, now) => result,
+            _ = shutdown.changed() => break,
+        };
+
+        match result {
+            Ok(summary) => {
+                info!(
+                    "Scheduled region update: {} checked, {} updated, {} failed",
+                    summary.checked,
+                    summary.updated.len(),
+                    summary.failed.len()
+                );
+                settings::update(|s| s.last_scheduled_update = Some(now.to_rfc3339()));
+                let _ = app.emit("region-auto-update-finished", summary);
+            }
+            // Probably offline; try again on the next poll
+            Err(e) => warn!("Scheduled region update failed: {}", e),
+        }
+    }
+
+    info!("Region update scheduler stopped");
+}
+
+/// Whether the policy calls for a run, given when the last one happened
+fn is_due(policy: &UpdatePolicy, last_run: Option<&str>, now: DateTime<Utc>) -> bool {
+    let Some(window) = policy.frequency.window() else {
+        return false;
+    };
+
+    last_run
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| now - t.with_timezone(&Utc) >= window)
+        .unwrap_or(true)
+}
+
+/// Regions with an upstream update whose local data is older than `window`
+fn due_regions(statuses: &[RegionUpdateStatus], window: chrono::Duration, now: DateTime<Utc>) -> Vec<String> {
+    statuses
+        .iter()
+        .filter(|s| s.update_available)
+        .filter(|s| {
+            s.local_timestamp
+                .as_deref()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| now - t.with_timezone(&Utc) >= window)
+                .unwrap_or(true)
+        })
+        .map(|s| s.region_id.clone())
+        .collect()
+}
+
+async fn run_updates(
+    app: &AppHandle,
+    policy: &UpdatePolicy,
+    now: DateTime<Utc>,
+) -> Result<RegionUpdateSummary, String> {
+    let Some(window) = policy.frequency.window() else {
+        return Ok(RegionUpdateSummary::default());
+    };
+
+    let statuses = commands::check_region_updates(app.clone()).await.map_err(|e| e.to_string())?;
+    let due = due_regions(&statuses, window, now);
+    let mut summary = RegionUpdateSummary {
+        checked: statuses.len(),
+        ..Default::default()
+    };
+
+    if due.is_empty() {
+        return Ok(summary);
+    }
+
+    if policy.fast_connection_only {
+        let probe_url = due.iter().find_map(|id| TemplateProvider::geofabrik().url(id));
+        let speed = match probe_url {
+            Some(url) => probe_speed(&url).await,
+            None => None,
+        };
+
+        match speed {
+            Some(rate) if rate >= policy.min_bytes_per_sec as f64 => {}
+            Some(rate) => {
+                summary.skipped_reason = Some(format!(
+                    "Connection too slow for automatic updates ({:.0} KB/s)",
+                    rate / 1024.0
+                ));
+                return Ok(summary);
+            }
+            None => {
+                summary.skipped_reason = Some("Could not measure connection speed".to_string());
+                return Ok(summary);
+            }
+        }
+    }
+
+    for region_id in &due {
+        commands::set_region_status(region_id, RegionStatus::Queued).await;
+    }
+
+    for region_id in due {
+        // Never compete with a download the user started
+        while commands::downloads_in_flight() {
+            tokio::time::sleep(IDLE_POLL_INTERVAL).await;
+        }
+
+        match commands::update_region(app.clone(), region_id.clone()).await {
+            Ok(_) => summary.updated.push(region_id),
+            Err(e) => {
+                warn!("Automatic update of {} failed: {}", region_id, e);
+                summary.failed.push(region_id);
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Estimate throughput in bytes/s by fetching the start of a file
+async fn probe_speed(url: &str) -> Option<f64> {
+    use futures_util::StreamExt;
+
+    let client = reqwest::Client::new();
+    let start = std::time::Instant::now();
+    let response = client
+        .get(url)
+        .header(reqwest::header::RANGE, format!("bytes=0-{}", SPEED_PROBE_BYTES - 1))
+        .send()
+        .await
+        .ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let mut received = 0u64;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        received += chunk.ok()?.len() as u64;
+        // Servers ignoring the range would send the whole extract
+        if received >= SPEED_PROBE_BYTES {
+            break;
+        }
+    }
+
+    let elapsed = start.elapsed().as_secs_f64();
+    (elapsed > 0.0 && received > 0).then(|| received as f64 / elapsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(id: &str, local: Option<&str>, update_available: bool) -> RegionUpdateStatus {
+        RegionUpdateStatus {
+            region_id: id.to_string(),
+            name: id.to_string(),
+            local_timestamp: local.map(|t| t.to_string()),
+            remote_timestamp: None,
+            update_available,
+            days_outdated: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_due() {
+        let now = DateTime::parse_from_rfc3339("2025-03-15T12:00:00Z").unwrap().with_timezone(&Utc);
+        let weekly = UpdatePolicy { frequency: UpdateFrequency::Weekly, ..Default::default() };
+
+        assert!(!is_due(&UpdatePolicy::default(), None, now));
+        assert!(is_due(&weekly, None, now));
+        assert!(is_due(&weekly, Some("2025-03-01T12:00:00Z"), now));
+        assert!(!is_due(&weekly, Some("2025-03-12T12:00:00Z"), now));
+    }
+
+    #[test]
+    fn test_due_regions_respect_window() {
+        let now = DateTime::parse_from_rfc3339("2025-03-15T12:00:00Z").unwrap().with_timezone(&Utc);
+        let statuses = vec![
+            status("us/old", Some("2025-01-01T00:00:00Z"), true),
+            status("us/recent", Some("2025-03-10T00:00:00Z"), true),
+            status("us/current", Some("2025-01-01T00:00:00Z"), false),
+            status("us/unknown", None, true),
+        ];
+
+        let due = due_regions(&statuses, UpdateFrequency::Monthly.window().unwrap(), now);
+        assert_eq!(due, vec!["us/old", "us/unknown"]);
+    }
+}
@@ -0,0 +1,293 @@
+//! Usage Metrics
+//!
+//! Counts of which features get used, kept in the local database only
+//! when the user opts in with the `usage_metrics` setting. Nothing is sent
+//! anywhere: `get_usage_metrics` shows exactly what's recorded, and a
+//! report can be written for the user to share if they choose.
+//!
+//! Only fixed metric names are recorded, with labels from fixed sets such
+//! as Whisper model names; histograms keep a count per bucket rather than
+//! the values themselves. No paths, ids, places or times of day.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tracing::warn;
+
+use crate::services::database::MetricRow;
+use crate::services::LocalDatabase;
+use crate::settings;
+
+/// Label of the bucket past a histogram's last bound
+const OVERFLOW_BUCKET: &str = "inf";
+
+/// Something counted each time it happens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    VideosProcessed,
+    ProcessingFailed,
+    /// Labelled with the model transcribed with
+    WhisperModel,
+    /// Each variant counts as one
+    NarrationsGenerated,
+    NarrationsFailed,
+    RegionsDownloaded,
+    RegionDownloadsFailed,
+}
+
+impl Counter {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::VideosProcessed => "videos_processed",
+            Self::ProcessingFailed => "processing_failed",
+            Self::WhisperModel => "whisper_model",
+            Self::NarrationsGenerated => "narrations_generated",
+            Self::NarrationsFailed => "narrations_failed",
+            Self::RegionsDownloaded => "regions_downloaded",
+            Self::RegionDownloadsFailed => "region_downloads_failed",
+        }
+    }
+}
+
+/// A measurement whose spread is kept, in buckets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Histogram {
+    /// Length of processed videos
+    VideoDurationSeconds,
+    /// Time a video took to process
+    ProcessingSeconds,
+    /// Time a narration took to write, all its variants together
+    NarrationSeconds,
+    RegionDownloadMegabytes,
+}
+
+impl Histogram {
+    pub const ALL: [Histogram; 4] = [
+        Self::VideoDurationSeconds,
+        Self::ProcessingSeconds,
+        Self::NarrationSeconds,
+        Self::RegionDownloadMegabytes,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::VideoDurationSeconds => "video_duration_seconds",
+            Self::ProcessingSeconds => "processing_seconds",
+            Self::NarrationSeconds => "narration_seconds",
+            Self::RegionDownloadMegabytes => "region_download_megabytes",
+        }
+    }
+
+    /// Upper bounds of the buckets, ascending; larger values go in an overflow bucket
+    pub fn bounds(self) -> &'static [f64] {
+        match self {
+            Self::VideoDurationSeconds => &[60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0],
+            Self::ProcessingSeconds => &[30.0, 120.0, 300.0, 900.0, 1800.0, 3600.0],
+            Self::NarrationSeconds => &[5.0, 15.0, 30.0, 60.0, 120.0, 300.0],
+            Self::RegionDownloadMegabytes => &[10.0, 50.0, 100.0, 500.0, 1000.0, 5000.0],
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|h| h.name() == name)
+    }
+}
+
+/// What's been recorded, as `get_usage_metrics` shows it
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageMetrics {
+    /// Whether anything is being recorded now
+    pub enabled: bool,
+    /// When the first of these was recorded
+    pub since: Option<DateTime<Utc>>,
+    pub counters: Vec<CounterValue>,
+    pub histograms: Vec<HistogramValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CounterValue {
+    pub name: String,
+    /// Empty for a counter counted as a whole
+    pub label: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistogramValue {
+    pub name: String,
+    pub count: u64,
+    pub mean: f64,
+    /// Every bucket, empty ones too, smallest first
+    pub buckets: Vec<BucketCount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketCount {
+    /// Largest value in the bucket (`None` = the overflow bucket)
+    pub le: Option<f64>,
+    pub count: u64,
+}
+
+/// What `export_metrics_report` writes: the metrics without the times
+/// they were recorded, and with only the app version and OS alongside
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsReport {
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub generated_on: NaiveDate,
+    /// Day the first of these was recorded
+    pub recorded_since: Option<NaiveDate>,
+    pub counters: Vec<CounterValue>,
+    pub histograms: Vec<HistogramValue>,
+}
+
+impl MetricsReport {
+    pub fn new(metrics: UsageMetrics) -> Self {
+        Self {
+            app_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            generated_on: Utc::now().date_naive(),
+            recorded_since: metrics.since.map(|since| since.date_naive()),
+            counters: metrics.counters,
+            histograms: metrics.histograms,
+        }
+    }
+}
+
+/// Count one `counter`, if the user opted in
+///
+/// Metrics are a convenience; failing to record one is only logged.
+pub async fn count(db: &LocalDatabase, counter: Counter, label: Option<&str>) {
+    record(db, counter.name(), label.unwrap_or_default(), 0.0).await;
+}
+
+/// Add `value` to `histogram`, if the user opted in
+pub async fn observe(db: &LocalDatabase, histogram: Histogram, value: f64) {
+    if !value.is_finite() {
+        return;
+    }
+    record(db, histogram.name(), &bucket_label(histogram.bounds(), value), value).await;
+}
+
+async fn record(db: &LocalDatabase, name: &str, label: &str, value: f64) {
+    if !settings::get().usage_metrics {
+        return;
+    }
+    if let Err(e) = db.record_metric(name, label, value).await {
+        warn!("Failed to record usage metric {}: {}", name, e);
+    }
+}
+
+/// Label of the bucket `value` falls in
+fn bucket_label(bounds: &[f64], value: f64) -> String {
+    bounds
+        .iter()
+        .find(|&&bound| value <= bound)
+        .map(|bound| format!("le_{}", bound))
+        .unwrap_or_else(|| OVERFLOW_BUCKET.to_string())
+}
+
+/// Counters and histograms from the stored rows, each histogram with all
+/// its buckets; rows of metrics this version doesn't know are counters
+pub fn aggregate(rows: &[MetricRow], enabled: bool) -> UsageMetrics {
+    let mut counters = Vec::new();
+    let mut histograms = Vec::new();
+    for histogram in Histogram::ALL {
+        let rows: Vec<&MetricRow> = rows.iter().filter(|r| r.name == histogram.name()).collect();
+        if rows.is_empty() {
+            continue;
+        }
+        let count_of = |label: &str| rows.iter().filter(|r| r.label == label).map(|r| r.count).sum();
+        let mut buckets: Vec<BucketCount> = histogram
+            .bounds()
+            .iter()
+            .map(|&bound| BucketCount { le: Some(bound), count: count_of(&format!("le_{}", bound)) })
+            .collect();
+        buckets.push(BucketCount { le: None, count: count_of(OVERFLOW_BUCKET) });
+
+        let count: u64 = rows.iter().map(|r| r.count).sum();
+        let sum: f64 = rows.iter().map(|r| r.sum).sum();
+        histograms.push(HistogramValue {
+            name: histogram.name().to_string(),
+            count,
+            mean: if count == 0 { 0.0 } else { sum / count as f64 },
+            buckets,
+        });
+    }
+    for row in rows.iter().filter(|r| Histogram::from_name(&r.name).is_none()) {
+        counters.push(CounterValue { name: row.name.clone(), label: row.label.clone(), count: row.count });
+    }
+
+    UsageMetrics {
+        enabled,
+        since: rows.iter().map(|r| r.first_recorded).min(),
+        counters,
+        histograms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn row(name: &str, label: &str, count: u64, sum: f64, day: u32) -> MetricRow {
+        let at = Utc.with_ymd_and_hms(2026, 5, day, 14, 30, 0).unwrap();
+        MetricRow {
+            name: name.to_string(),
+            label: label.to_string(),
+            count,
+            sum,
+            first_recorded: at,
+            last_recorded: at,
+        }
+    }
+
+    #[test]
+    fn test_values_fall_in_the_smallest_bucket_that_holds_them() {
+        let bounds = Histogram::VideoDurationSeconds.bounds();
+        assert_eq!(bucket_label(bounds, 12.0), "le_60");
+        assert_eq!(bucket_label(bounds, 60.0), "le_60");
+        assert_eq!(bucket_label(bounds, 61.0), "le_300");
+        assert_eq!(bucket_label(bounds, 9000.0), "inf");
+    }
+
+    #[test]
+    fn test_rows_are_aggregated_into_counters_and_histograms() {
+        let rows = [
+            row("processing_seconds", "le_120", 2, 150.0, 3),
+            row("processing_seconds", "inf", 1, 4000.0, 1),
+            row("videos_processed", "", 3, 0.0, 2),
+            row("whisper_model", "base", 2, 0.0, 2),
+            row("whisper_model", "small", 1, 0.0, 4),
+        ];
+        let metrics = aggregate(&rows, true);
+
+        assert_eq!(metrics.since, Some(Utc.with_ymd_and_hms(2026, 5, 1, 14, 30, 0).unwrap()));
+        assert_eq!(metrics.counters.len(), 3);
+        assert_eq!(
+            metrics.counters[1],
+            CounterValue { name: "whisper_model".to_string(), label: "base".to_string(), count: 2 }
+        );
+
+        assert_eq!(metrics.histograms.len(), 1);
+        let processing = &metrics.histograms[0];
+        assert_eq!(processing.name, "processing_seconds");
+        assert_eq!(processing.count, 3);
+        assert!((processing.mean - 1383.33).abs() < 0.01);
+        // Empty buckets are there too
+        assert_eq!(processing.buckets.len(), Histogram::ProcessingSeconds.bounds().len() + 1);
+        assert_eq!(processing.buckets[0], BucketCount { le: Some(30.0), count: 0 });
+        assert_eq!(processing.buckets[1], BucketCount { le: Some(120.0), count: 2 });
+        assert_eq!(processing.buckets.last(), Some(&BucketCount { le: None, count: 1 }));
+    }
+
+    #[test]
+    fn test_report_keeps_only_the_day_things_were_recorded() {
+        let report = MetricsReport::new(aggregate(&[row("videos_processed", "", 1, 0.0, 7)], true));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["recorded_since"], "2026-05-07");
+        assert_eq!(json["counters"][0]["count"], 1);
+        assert!(json.get("enabled").is_none());
+        assert!(!json.to_string().contains("14:30"));
+    }
+}
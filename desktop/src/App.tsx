@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { open } from '@tauri-apps/plugin-dialog';
 import { Video, Search, Bot, Globe, WifiOff, Loader2, RefreshCw } from 'lucide-react';
 import { MapPacksModal } from './components/MapPacksModal';
@@ -7,6 +8,13 @@ import { ImportProgressModal } from './components/ImportProgressModal';
 import { ProjectList, type Project } from './components/ProjectList';
 import { CreateProjectModal } from './components/CreateProjectModal';
 import { EditorPage } from './pages/EditorPage';
+import { errorCode, errorMessage } from './api';
+
+/** What `intake_files` planned for a set of files */
+interface IntakePlan {
+  imports: { video_path: string; gps_path: string | null }[];
+  skipped: { path: string; reason: string }[];
+}
 
 function App() {
   const [appVersion, setAppVersion] = useState<string>('');
@@ -40,6 +48,16 @@ function App() {
 
     // Fetch projects
     fetchProjects();
+
+    // The database comes online after the window opens; fetch again once it has
+    const unlisten = listen('app-ready', () => {
+      checkMapPacksStatus();
+      fetchProjects();
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
   }, []);
 
   const checkConnection = async () => {
@@ -71,7 +89,10 @@ function App() {
       const result = await invoke<Project[]>('get_projects');
       setProjects(result);
     } catch (e) {
-      console.error('Failed to fetch projects:', e);
+      // Still starting up; fetched again on app-ready
+      if (errorCode(e) !== 'not_ready') {
+        console.error('Failed to fetch projects:', e);
+      }
     }
   };
 
@@ -86,41 +107,81 @@ function App() {
     }
   };
 
+  const importVideo = async (videoPath: string, gpsPath: string | null) => {
+    try {
+      console.log('🎬 Starting import for:', videoPath);
+      setIsImporting(true);
+
+      // Import to default project (auto-creates if needed)
+      const result = await invoke('import_video', {
+        projectId: 'default',
+        videoPath,
+        gpsPath,
+      });
+
+      console.log('✅ Import successful:', result);
+      // Set active video path after successful import
+      setActiveVideoPath(videoPath);
+    } catch (e) {
+      console.error('❌ Import failed:', e);
+      alert(errorCode(e) === 'not_ready' ? errorMessage(e) : `Import failed: ${errorMessage(e)}`);
+      setIsImporting(false);
+    }
+  };
+
   const handleImportVideo = async () => {
     try {
+      const formats = await invoke<{ video: string[]; gps: string[] }>('get_supported_formats');
       const selected = await open({
         multiple: false,
         filters: [
           {
             name: 'Video',
-            extensions: ['mp4', 'mov', 'avi', 'mkv'],
+            extensions: formats.video,
           },
         ],
       });
 
       if (selected) {
-        const videoPath = selected as string;
-        console.log('🎬 Starting import for:', videoPath);
-        setIsImporting(true);
-
-        // Import to default project (auto-creates if needed)
-        const result = await invoke('import_video', {
-          projectId: 'default',
-          videoPath,
-          gpsPath: null,
-        });
-
-        console.log('✅ Import successful:', result);
-        // Set active video path after successful import
-        setActiveVideoPath(videoPath);
+        await importVideo(selected as string, null);
       }
     } catch (e) {
       console.error('❌ Import failed:', e);
-      alert(`Import failed: ${e}`);
-      setIsImporting(false);
+      alert(errorCode(e) === 'not_ready' ? errorMessage(e) : `Import failed: ${errorMessage(e)}`);
     }
   };
 
+  // Files opened with GeoTruth while it was already running: pair them up
+  // and import the first video with its track, as the Import Video card would
+  const handleExternalOpen = async (paths: string[]) => {
+    try {
+      const plan = await invoke<IntakePlan>('intake_files', { paths, projectId: null });
+      const [first, ...rest] = plan.imports;
+      if (!first) {
+        const reasons = plan.skipped.map((s) => `${s.path}: ${s.reason}`).join('\n');
+        alert(`There was no video to import.${reasons ? `\n\n${reasons}` : ''}`);
+        return;
+      }
+      if (rest.length > 0) {
+        alert(`Opening ${first.video_path}; import the other ${rest.length} videos one at a time.`);
+      }
+      await importVideo(first.video_path, first.gps_path);
+    } catch (e) {
+      console.error('❌ Opening files failed:', e);
+      alert(errorCode(e) === 'not_ready' ? errorMessage(e) : `Import failed: ${errorMessage(e)}`);
+    }
+  };
+
+  useEffect(() => {
+    const unlisten = listen<{ paths: string[] }>('external-open-request', (event) => {
+      handleExternalOpen(event.payload.paths);
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, []);
+
   const handleImportComplete = () => {
     console.log('📝 Import complete callback triggered');
     console.log('📹 Active video path:', activeVideoPath);
@@ -21,6 +21,8 @@ export interface EnrichRequest {
   timestamp?: string;
   heading_deg?: number;
   fov_deg?: number;
+  /** Include the map features the place and road names were picked from */
+  verbose?: boolean;
 }
 
 export interface POI {
@@ -32,6 +34,15 @@ export interface POI {
   in_fov: boolean;
   confidence: number;
   facts?: Record<string, unknown>;
+  /** Pinned to its event by the user */
+  pinned?: boolean;
+}
+
+export interface AttributedValue {
+  value: string;
+  source: 'map_data' | 'llm' | 'estimate' | 'user';
+  /** 0 to 1 */
+  confidence: number;
 }
 
 export interface EnrichResponse {
@@ -46,13 +57,101 @@ export interface EnrichResponse {
     };
   };
   context: {
-    country: string;
-    state: string;
-    county: string;
-    timezone: string;
-    elevation_m: number;
+    country: string | null;
+    state: string | null;
+    county: string | null;
+    city: string | null;
+    road: string | null;
+    timezone: string | null;
+    elevation_m: number | null;
+    /** Per field: whether the value is from map data or a guess */
+    attribution: Record<string, AttributedValue>;
   };
   pois: POI[];
+  /** 'llm' context is the model's unverified guess; 'partial' has only what offline data had;
+   *  'uncovered' has nothing, as the model wasn't asked (offline, or the run's online calls were spent) */
+  source: 'local' | 'llm' | 'partial' | 'uncovered';
+  /** 0 to 1 */
+  confidence: number;
+  /** Only for verbose requests */
+  debug?: {
+    place_candidates: PlaceCandidate[];
+  };
+}
+
+export interface PlaceCandidate {
+  name: string;
+  kind: 'neighbourhood' | 'suburb' | 'village' | 'town' | 'city' | 'road' | 'other';
+  distance_m: number;
+}
+
+/** Everything known about a clicked map position */
+export interface LocationInspection {
+  location: EnrichResponse['location'];
+  context: EnrichResponse['context'];
+  source: EnrichResponse['source'];
+  /** Nearest first */
+  place_candidates: PlaceCandidate[];
+  /** Closest first */
+  pois: POI[];
+  elevation_m: number | null;
+  /** RFC 3339, with the point's UTC offset */
+  local_time: string | null;
+  /** null when no downloaded region covers the point */
+  region: { id: string; name: string } | null;
+}
+
+/** What kind of failure a command ran into, as `ErrorCode` in error.rs */
+export type ErrorCode =
+  | 'internal'
+  | 'invalid_input'
+  | 'not_found'
+  | 'file_not_found'
+  | 'permission_denied'
+  | 'invalid_file'
+  | 'database_unavailable'
+  | 'database_error'
+  | 'sidecar_missing'
+  | 'sidecar_failed'
+  | 'model_missing'
+  | 'api_key_missing'
+  | 'api_key_invalid'
+  | 'rate_limited'
+  | 'network'
+  | 'timeout'
+  | 'offline'
+  | 'service_failed'
+  | 'cancelled'
+  | 'coverage_missing'
+  | 'sync_failed'
+  | 'not_ready';
+
+/** What a failed command rejects with */
+export interface CommandError {
+  code: ErrorCode;
+  /** Fit to show the user */
+  message: string;
+  details?: unknown;
+}
+
+export function isCommandError(e: unknown): e is CommandError {
+  return (
+    typeof e === 'object' &&
+    e !== null &&
+    typeof (e as CommandError).code === 'string' &&
+    typeof (e as CommandError).message === 'string'
+  );
+}
+
+/** The code of a failed command, or null for anything else that was thrown */
+export function errorCode(e: unknown): ErrorCode | null {
+  return isCommandError(e) ? e.code : null;
+}
+
+/** A message to show for whatever a command or other call threw */
+export function errorMessage(e: unknown): string {
+  if (isCommandError(e) || e instanceof Error) return e.message;
+  return String(e);
 }
 
 class ApiClient {
@@ -82,7 +181,7 @@ class ApiClient {
       };
     } catch (e) {
       console.error('Health check failed', e);
-      throw new Error(`Health check failed: ${e}`);
+      throw new Error(`Health check failed: ${errorMessage(e)}`);
     }
   }
 
@@ -95,6 +194,15 @@ class ApiClient {
     }
   }
 
+  async inspectLocation(lat: number, lon: number): Promise<LocationInspection> {
+    try {
+      return await invoke<LocationInspection>('inspect_location', { lat, lon });
+    } catch (e) {
+      console.error('Location inspection failed', e);
+      throw e;
+    }
+  }
+
   async enrichBatch(points: EnrichRequest[]): Promise<{ results: EnrichResponse[] }> {
     // Parallelize requests since we lack a batch command for now
     const results = await Promise.all(points.map((p) => this.enrich(p)));
@@ -1,6 +1,8 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { Map, X, Check, Download, Trash2, RefreshCw } from 'lucide-react';
+import { errorCode, errorMessage } from '../api';
 
 const formatBytes = (bytes: number) => {
   if (bytes === 0) return '0 B';
@@ -10,6 +12,15 @@ const formatBytes = (bytes: number) => {
   return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + ' ' + sizes[i];
 };
 
+type RegionStatus =
+  | { state: 'not_downloaded' }
+  | { state: 'queued' }
+  | { state: 'downloading' }
+  | { state: 'processing' }
+  | { state: 'ready' }
+  | { state: 'failed'; reason: string; resumable: boolean }
+  | { state: 'update_available' };
+
 interface RegionInfo {
   id: string;
   name: string;
@@ -18,16 +29,38 @@ interface RegionInfo {
   last_updated: string | null;
   poi_count: number;
   bounds: [number, number, number, number];
+  status: RegionStatus;
+}
+
+interface RegionUpdateStatus {
+  region_id: string;
+  update_available: boolean;
+}
+
+interface RegionPage {
+  regions: RegionInfo[];
+  /** Regions matching the filters across all pages */
+  total: number;
 }
 
 interface DownloadProgress {
   region_id: string;
   bytes_downloaded: number;
-  total_bytes: number;
-  progress_percent: number;
+  /** null while the server hasn't told us the size */
+  total_bytes: number | null;
+  progress_percent: number | null;
   status: string;
+  bytes_per_sec: number;
+  eta_seconds: number | null;
 }
 
+const formatEta = (seconds: number) => {
+  if (seconds < 60) return `${seconds}s`;
+  const minutes = Math.floor(seconds / 60);
+  if (minutes < 60) return `${minutes}m ${seconds % 60}s`;
+  return `${Math.floor(minutes / 60)}h ${minutes % 60}m`;
+};
+
 interface MapPacksModalProps {
   isOpen: boolean;
   onClose: () => void;
@@ -42,6 +75,10 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
   const [activeDownload, setActiveDownload] = useState<string | null>(null);
   const [availableRegions, setAvailableRegions] = useState<RegionInfo[]>([]);
   const [selectedRegionId, setSelectedRegionId] = useState<string>('');
+  const [checkingUpdates, setCheckingUpdates] = useState(false);
+  const [updateSummary, setUpdateSummary] = useState<string | null>(null);
+  // Set while the app is still starting up; loading is retried as it comes online
+  const [waitingForStartup, setWaitingForStartup] = useState(false);
 
   useEffect(() => {
     if (isOpen) {
@@ -49,6 +86,18 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
     }
   }, [isOpen]);
 
+  useEffect(() => {
+    if (!isOpen || !waitingForStartup) return;
+
+    const unlisten = listen('app-ready', () => {
+      loadRegions();
+    });
+
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [isOpen, waitingForStartup]);
+
   useEffect(() => {
     let interval: number | null = null;
 
@@ -77,19 +126,25 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
   const loadRegions = async () => {
     setLoading(true);
     setError(null);
+    setWaitingForStartup(false);
     try {
       const current: RegionInfo[] = await invoke('get_map_regions');
       setRegions(current);
 
       // Also load available regions for the dropdown
-      const available: RegionInfo[] = await invoke('get_available_regions');
-      setAvailableRegions(available);
+      const available: RegionPage = await invoke('get_available_regions', { limit: 1000 });
+      setAvailableRegions(available.regions);
 
       const downloaded = current.filter((r) => r.downloaded).length;
       onStatusChange(downloaded, current.length);
     } catch (e) {
-      setError(`Failed to load regions: ${e}`);
-      console.error('Failed to load regions:', e);
+      if (errorCode(e) === 'not_ready') {
+        setWaitingForStartup(true);
+        setError(errorMessage(e));
+      } else {
+        setError(`Failed to load regions: ${errorMessage(e)}`);
+        console.error('Failed to load regions:', e);
+      }
     } finally {
       setLoading(false);
     }
@@ -101,7 +156,11 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
     try {
       await invoke('download_map_region', { regionId });
     } catch (e) {
-      setError(`Download failed: ${e}`);
+      setError(
+        errorCode(e) === 'offline'
+          ? "Map packs can't be downloaded while GeoTruth is in offline mode."
+          : `Download failed: ${errorMessage(e)}`
+      );
       setActiveDownload(null);
     }
   };
@@ -113,7 +172,30 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
       await invoke('delete_map_region', { regionId });
       await loadRegions();
     } catch (e) {
-      setError(`Delete failed: ${e}`);
+      setError(`Delete failed: ${errorMessage(e)}`);
+    }
+  };
+
+  const handleCheckUpdates = async () => {
+    setCheckingUpdates(true);
+    setError(null);
+    try {
+      const statuses: RegionUpdateStatus[] = await invoke('check_region_updates');
+      const outdated = statuses.filter((s) => s.update_available).length;
+      setUpdateSummary(
+        outdated === 0
+          ? 'All map packs are up to date'
+          : `${outdated} map pack${outdated === 1 ? '' : 's'} can be updated`
+      );
+      await loadRegions();
+    } catch (e) {
+      setError(
+        errorCode(e) === 'offline'
+          ? "Updates can't be checked while GeoTruth is in offline mode."
+          : `Update check failed: ${errorMessage(e)}`
+      );
+    } finally {
+      setCheckingUpdates(false);
     }
   };
 
@@ -125,7 +207,7 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
       setSelectedRegionId('');
     } catch (error) {
       console.error('Failed to add region:', error);
-      setError('Failed to add region');
+      setError(`Failed to add region: ${errorMessage(error)}`);
     }
   };
 
@@ -217,16 +299,29 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
                         • Updated {new Date(region.last_updated).toLocaleDateString()}
                       </span>
                     )}
+                    {region.status.state === 'update_available' && (
+                      <span className="updated"> • Update available</span>
+                    )}
+                    {region.status.state === 'queued' && <span className="updated"> • Queued</span>}
                   </div>
+                  {region.status.state === 'failed' && activeDownload !== region.id && (
+                    <div className="region-meta" style={{ color: 'var(--color-error)' }}>
+                      {region.status.reason}
+                    </div>
+                  )}
                 </div>
 
                 <div className="region-actions">
                   {activeDownload === region.id ? (
                     <div className="download-progress">
-                      <div
-                        className="progress-bar"
-                        style={{ width: `${downloadProgress?.progress_percent || 0}%` }}
-                      />
+                      {downloadProgress && downloadProgress.progress_percent === null ? (
+                        <div className="progress-bar indeterminate" />
+                      ) : (
+                        <div
+                          className="progress-bar"
+                          style={{ width: `${downloadProgress?.progress_percent || 0}%` }}
+                        />
+                      )}
                       <div className="progress-details">
                         <span className="progress-text">
                           {downloadProgress?.status || 'Downloading...'}
@@ -234,9 +329,21 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
                         <span className="progress-stats">
                           {downloadProgress && (
                             <>
-                              {formatBytes(downloadProgress.bytes_downloaded)} /{' '}
-                              {formatBytes(downloadProgress.total_bytes)} (
-                              {Math.round(downloadProgress.progress_percent)}%)
+                              {formatBytes(downloadProgress.bytes_downloaded)}
+                              {downloadProgress.total_bytes !== null &&
+                                downloadProgress.progress_percent !== null && (
+                                  <>
+                                    {' '}
+                                    / {formatBytes(downloadProgress.total_bytes)} (
+                                    {Math.round(downloadProgress.progress_percent)}%)
+                                  </>
+                                )}
+                              {downloadProgress.bytes_per_sec > 0 && (
+                                <> • {formatBytes(downloadProgress.bytes_per_sec)}/s</>
+                              )}
+                              {downloadProgress.eta_seconds !== null && (
+                                <> • {formatEta(downloadProgress.eta_seconds)} left</>
+                              )}
                             </>
                           )}
                         </span>
@@ -273,7 +380,9 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
                       disabled={activeDownload !== null}
                     >
                       <Download className="w-4 h-4 inline-block mr-1" />
-                      Download
+                      {region.status.state === 'failed' && region.status.resumable
+                        ? 'Resume'
+                        : 'Download'}
                     </button>
                   )}
                 </div>
@@ -285,7 +394,17 @@ export function MapPacksModal({ isOpen, onClose, onStatusChange }: MapPacksModal
         <div className="modal-footer">
           <span className="footer-info">
             {regions.filter((r) => r.downloaded).length} of {regions.length} packs downloaded
+            {updateSummary && <> • {updateSummary}</>}
           </span>
+          <button
+            className="download-button"
+            onClick={handleCheckUpdates}
+            disabled={checkingUpdates || activeDownload !== null}
+            style={{ marginLeft: '12px', padding: '6px 12px', fontSize: '0.8rem' }}
+          >
+            <RefreshCw className="w-4 h-4 inline-block mr-1" />
+            {checkingUpdates ? 'Checking...' : 'Check for updates'}
+          </button>
         </div>
       </div>
     </div>
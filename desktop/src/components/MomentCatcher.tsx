@@ -3,6 +3,7 @@ import { MediaPlayer, MediaProvider, MediaPlayerInstance, Track } from '@vidstac
 import { VideoLayout } from './player/VideoLayout';
 import { invoke, convertFileSrc } from '@tauri-apps/api/core';
 import 'vidstack/player/styles/default/theme.css';
+import { errorMessage } from '../api';
 
 interface MomentCatcherProps {
   videoPath: string;
@@ -90,7 +91,7 @@ export function MomentCatcher({
       }, 800);
     } catch (err) {
       console.error('Failed to capture moment:', err);
-      log(`Capture failed: ${err}`);
+      log(`Capture failed: ${errorMessage(err)}`);
       setAnalyzing(false);
     }
   };
@@ -120,7 +121,7 @@ export function MomentCatcher({
       log('Auto-analysis complete.');
     } catch (err) {
       console.error('Auto-scan failed:', err);
-      log(`Auto-scan failed: ${err}`);
+      log(`Auto-scan failed: ${errorMessage(err)}`);
       setAnalyzing(false);
     }
   };